@@ -0,0 +1,78 @@
+/*
+ * C API for the gameboy_emulator core, built with `cargo build --release --features capi`.
+ * Link against the cdylib (libgameboy_emulator.so / gameboy_emulator.dll).
+ *
+ * Every function takes the handle from gbemu_create() and does nothing (or returns
+ * false/NULL/0) when given NULL. A handle must only be used from one thread at a time.
+ */
+#ifndef GAMEBOY_EMULATOR_H
+#define GAMEBOY_EMULATOR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define GBEMU_SCREEN_WIDTH 160
+#define GBEMU_SCREEN_HEIGHT 144
+
+/* Masks for gbemu_set_buttons() */
+#define GBEMU_BUTTON_RIGHT (1 << 0)
+#define GBEMU_BUTTON_LEFT (1 << 1)
+#define GBEMU_BUTTON_UP (1 << 2)
+#define GBEMU_BUTTON_DOWN (1 << 3)
+#define GBEMU_BUTTON_A (1 << 4)
+#define GBEMU_BUTTON_B (1 << 5)
+#define GBEMU_BUTTON_SELECT (1 << 6)
+#define GBEMU_BUTTON_START (1 << 7)
+
+typedef struct GbEmu GbEmu;
+
+GbEmu *gbemu_create(void);
+void gbemu_destroy(GbEmu *emu);
+
+/* Copies the ROM and powers on. Returns false if it's too small to be a ROM. */
+bool gbemu_load_rom(GbEmu *emu, const uint8_t *data, size_t len);
+
+/* Runs until the next frame is complete.
+ * Returns false if no ROM is loaded or emulation hit an error, see gbemu_error. */
+bool gbemu_run_frame(GbEmu *emu);
+
+/* Runs one instruction. Returns 1 if that completed a frame, 0 if it didn't and -1 if
+ * there's no ROM or it hit an error or a panic, see gbemu_error. */
+int gbemu_step(GbEmu *emu);
+
+/* The last emulation error along with a CPU dump, NULL if there hasn't been one since the
+ * ROM was loaded. Valid until the next call that takes emu. */
+const char *gbemu_error(const GbEmu *emu);
+
+/* Memory as the CPU sees it. Reads return 0xFF if no ROM is loaded. */
+uint8_t gbemu_read(const GbEmu *emu, uint16_t addr);
+void gbemu_write(GbEmu *emu, uint16_t addr, uint8_t value);
+
+/*
+ * GBEMU_SCREEN_WIDTH * GBEMU_SCREEN_HEIGHT bytes, one per pixel, 0 = black, 255 = white.
+ * Valid until the next call that takes emu, NULL if no ROM is loaded.
+ */
+const uint8_t *gbemu_framebuffer(const GbEmu *emu);
+
+/* buttons is a mask of the GBEMU_BUTTON_* values that are held down. */
+void gbemu_set_buttons(GbEmu *emu, uint8_t buttons);
+
+/*
+ * Writes a save state to buffer if it fits in capacity bytes. Returns the size of the
+ * state either way (0 if no ROM is loaded), so call it with NULL first to size the buffer.
+ */
+size_t gbemu_save_state(const GbEmu *emu, uint8_t *buffer, size_t capacity);
+
+/* Returns false and leaves the emulator as it was if the state can't be loaded. */
+bool gbemu_load_state(GbEmu *emu, const uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,163 @@
+"""Python bindings for the gameboy_emulator core.
+
+Wraps the C API (see include/gameboy_emulator.h) with ctypes, so the only thing needed is
+the library from `cargo build --release --features capi`. It's looked up in
+$GAMEBOY_EMULATOR_LIB, next to this package, then in the repository's target/ directory.
+
+    import gameboy
+
+    gb = gameboy.GameBoy(open("tetris.gb", "rb").read())
+    gb.set_buttons({gameboy.Button.START})
+    gb.run_frame()
+    pixels = gb.framebuffer()  # numpy array of shape (144, 160) if numpy is installed
+"""
+import ctypes
+import enum
+import os
+import sys
+from pathlib import Path
+
+WIDTH = 160
+HEIGHT = 144
+
+__all__ = ["Button", "EmulationError", "GameBoy", "StateError", "WIDTH", "HEIGHT"]
+
+
+class Button(enum.IntFlag):
+    RIGHT = 1 << 0
+    LEFT = 1 << 1
+    UP = 1 << 2
+    DOWN = 1 << 3
+    A = 1 << 4
+    B = 1 << 5
+    SELECT = 1 << 6
+    START = 1 << 7
+
+
+class StateError(ValueError):
+    """The save state doesn't belong to this ROM or is damaged"""
+
+
+class EmulationError(RuntimeError):
+    """The game did something the emulator can't handle, the message includes a CPU dump"""
+
+
+def _library_candidates():
+    if sys.platform == "win32":
+        name = "gameboy_emulator.dll"
+    elif sys.platform == "darwin":
+        name = "libgameboy_emulator.dylib"
+    else:
+        name = "libgameboy_emulator.so"
+
+    if "GAMEBOY_EMULATOR_LIB" in os.environ:
+        yield Path(os.environ["GAMEBOY_EMULATOR_LIB"])
+    here = Path(__file__).resolve().parent
+    yield here / name
+    for profile in ("release", "debug"):
+        yield here.parent.parent / "target" / profile / name
+
+
+def _load_library():
+    for path in _library_candidates():
+        if path.exists():
+            lib = ctypes.CDLL(str(path))
+            break
+    else:
+        raise ImportError(
+            "Couldn't find the gameboy_emulator library, build it with "
+            "`cargo build --release --features capi` or set GAMEBOY_EMULATOR_LIB"
+        )
+
+    handle = ctypes.c_void_p
+    buffer = ctypes.POINTER(ctypes.c_uint8)
+    signatures = {
+        "gbemu_create": ([], handle),
+        "gbemu_destroy": ([handle], None),
+        "gbemu_load_rom": ([handle, ctypes.c_char_p, ctypes.c_size_t], ctypes.c_bool),
+        "gbemu_run_frame": ([handle], ctypes.c_bool),
+        "gbemu_step": ([handle], ctypes.c_int),
+        "gbemu_error": ([handle], ctypes.c_char_p),
+        "gbemu_read": ([handle, ctypes.c_uint16], ctypes.c_uint8),
+        "gbemu_write": ([handle, ctypes.c_uint16, ctypes.c_uint8], None),
+        "gbemu_framebuffer": ([handle], buffer),
+        "gbemu_set_buttons": ([handle, ctypes.c_uint8], None),
+        "gbemu_save_state": ([handle, buffer, ctypes.c_size_t], ctypes.c_size_t),
+        "gbemu_load_state": ([handle, ctypes.c_char_p, ctypes.c_size_t], ctypes.c_bool),
+    }
+    for name, (argtypes, restype) in signatures.items():
+        function = getattr(lib, name)
+        function.argtypes = argtypes
+        function.restype = restype
+    return lib
+
+
+_lib = _load_library()
+
+try:
+    import numpy as _np
+except ImportError:
+    _np = None
+
+
+class GameBoy:
+    def __init__(self, rom: bytes):
+        self._handle = _lib.gbemu_create()
+        if not _lib.gbemu_load_rom(self._handle, rom, len(rom)):
+            _lib.gbemu_destroy(self._handle)
+            self._handle = None
+            raise ValueError(f"{len(rom)} bytes is too small to be a ROM")
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _lib.gbemu_destroy(self._handle)
+            self._handle = None
+
+    def step(self) -> bool:
+        """Runs one instruction, returns True if that completed a frame"""
+        result = _lib.gbemu_step(self._handle)
+        if result < 0:
+            self._raise_error()
+        return result == 1
+
+    def run_frame(self):
+        if not _lib.gbemu_run_frame(self._handle):
+            self._raise_error()
+
+    def _raise_error(self):
+        raise EmulationError(_lib.gbemu_error(self._handle).decode(errors="replace"))
+
+    def read(self, addr: int) -> int:
+        return _lib.gbemu_read(self._handle, addr)
+
+    def write(self, addr: int, value: int):
+        _lib.gbemu_write(self._handle, addr, value)
+
+    def set_buttons(self, buttons):
+        """Holds down exactly `buttons`, a Button mask or an iterable of Buttons"""
+        if not isinstance(buttons, int):
+            mask = 0
+            for button in buttons:
+                mask |= button
+            buttons = mask
+        _lib.gbemu_set_buttons(self._handle, int(buttons))
+
+    def framebuffer(self):
+        """A copy of the screen, one byte per pixel from 0 (black) to 255 (white).
+
+        A (144, 160) uint8 numpy array if numpy is available, otherwise bytes.
+        """
+        pixels = ctypes.string_at(_lib.gbemu_framebuffer(self._handle), WIDTH * HEIGHT)
+        if _np is None:
+            return pixels
+        return _np.frombuffer(pixels, dtype=_np.uint8).reshape(HEIGHT, WIDTH)
+
+    def save_state(self) -> bytes:
+        size = _lib.gbemu_save_state(self._handle, None, 0)
+        buffer = (ctypes.c_uint8 * size)()
+        _lib.gbemu_save_state(self._handle, buffer, size)
+        return bytes(buffer)
+
+    def load_state(self, state: bytes):
+        if not _lib.gbemu_load_state(self._handle, state, len(state)):
+            raise StateError("Couldn't load save state")
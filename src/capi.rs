@@ -0,0 +1,213 @@
+//! C API for embedding the core, see `include/gameboy_emulator.h`
+//!
+//! Every function takes the handle from [`gbemu_create`] and is a no-op (or returns
+//! false/null/0) when given a null handle. Nothing here is thread safe, a handle has to be
+//! used from one thread at a time.
+use std::{
+    ffi::{c_char, c_int, CString},
+    ptr, slice,
+};
+
+use tracing::error;
+
+use crate::emulator::{error::Crash, rom, Emulator, GAMEBOY_HEIGHT, GAMEBOY_WIDTH};
+
+pub const GBEMU_SCREEN_WIDTH: c_int = GAMEBOY_WIDTH as c_int;
+pub const GBEMU_SCREEN_HEIGHT: c_int = GAMEBOY_HEIGHT as c_int;
+
+/// Opaque to C
+pub struct GbEmu {
+    emulator: Option<Emulator>,
+    /// Last error with the CPU dump, for [`gbemu_error`]
+    error: Option<CString>,
+}
+
+impl GbEmu {
+    fn record(&mut self, crash: Crash) {
+        error!("{}", crash.message);
+        let report = format!("{}\n\n{}", crash.message, crash.cpu_dump);
+        self.error = CString::new(report.replace('\0', "")).ok();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn gbemu_create() -> *mut GbEmu {
+    Box::into_raw(Box::new(GbEmu {
+        emulator: None,
+        error: None,
+    }))
+}
+
+/// # Safety
+/// `emu` has to come from [`gbemu_create`] and can't be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_destroy(emu: *mut GbEmu) {
+    if !emu.is_null() {
+        drop(Box::from_raw(emu));
+    }
+}
+
+/// Copies the ROM and powers on, replacing whatever was running.
+/// Returns false if it's too small to be a ROM.
+///
+/// # Safety
+/// `emu` has to be valid and `data` has to point to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_load_rom(emu: *mut GbEmu, data: *const u8, len: usize) -> bool {
+    let Some(emu) = emu.as_mut() else {
+        return false;
+    };
+    if data.is_null() || len < rom::MIN_SIZE {
+        error!("gbemu_load_rom: {} bytes is too small for a ROM", len);
+        return false;
+    }
+    emu.emulator = Some(Emulator::new(slice::from_raw_parts(data, len)));
+    emu.error = None;
+    true
+}
+
+/// Runs until the next frame is complete.
+/// Returns false if no ROM is loaded or emulation hit an error, see [`gbemu_error`].
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_run_frame(emu: *mut GbEmu) -> bool {
+    let Some(emu) = emu.as_mut() else {
+        return false;
+    };
+    let Some(emulator) = emu.emulator.as_mut() else {
+        return false;
+    };
+    match emulator.run_frame_catching() {
+        Ok(_) => true,
+        Err(crash) => {
+            emu.record(crash);
+            false
+        }
+    }
+}
+
+/// Runs one instruction. Returns 1 if that completed a frame, 0 if it didn't and -1 if
+/// there's no ROM or it hit an error or a panic, see [`gbemu_error`].
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_step(emu: *mut GbEmu) -> c_int {
+    let Some(emu) = emu.as_mut() else {
+        return -1;
+    };
+    let Some(emulator) = emu.emulator.as_mut() else {
+        return -1;
+    };
+    match emulator.step_catching() {
+        Ok(frame) => frame as c_int,
+        Err(crash) => {
+            emu.record(crash);
+            -1
+        }
+    }
+}
+
+/// The last emulation error along with a CPU dump, null if there hasn't been one since the ROM
+/// was loaded. Valid until the next call that takes `emu`.
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_error(emu: *const GbEmu) -> *const c_char {
+    match emu.as_ref().and_then(|emu| emu.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Reads a byte the way the CPU would see it, 0xFF if no ROM is loaded
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_read(emu: *const GbEmu, addr: u16) -> u8 {
+    match emu.as_ref().and_then(|emu| emu.emulator.as_ref()) {
+        Some(emulator) => emulator.memory_bus().peek(addr),
+        None => 0xFF,
+    }
+}
+
+/// Writes a byte the way the CPU would, including any side effects
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_write(emu: *mut GbEmu, addr: u16, value: u8) {
+    if let Some(emulator) = emu.as_mut().and_then(|emu| emu.emulator.as_mut()) {
+        emulator.memory_bus_mut().write_u8(addr, value);
+    }
+}
+
+/// The screen as one byte per pixel, 0 = black, 255 = white.
+/// Valid until the next call that takes `emu`, null if no ROM is loaded.
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_framebuffer(emu: *const GbEmu) -> *const u8 {
+    match emu.as_ref().and_then(|emu| emu.emulator.as_ref()) {
+        Some(emulator) => emulator.frame_buffer().as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// `buttons` is a mask of the `GBEMU_BUTTON_*` values that are held down
+///
+/// # Safety
+/// `emu` has to be valid
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_set_buttons(emu: *mut GbEmu, buttons: u8) {
+    if let Some(emulator) = emu.as_mut().and_then(|emu| emu.emulator.as_mut()) {
+        emulator.memory_bus_mut().joypad_mut().set_pressed(buttons);
+    }
+}
+
+/// Writes a save state to `buffer` if it fits in `capacity` bytes.
+/// Returns the size of the state either way, 0 if no ROM is loaded.
+///
+/// # Safety
+/// `emu` has to be valid and `buffer` has to point to `capacity` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_save_state(
+    emu: *const GbEmu,
+    buffer: *mut u8,
+    capacity: usize,
+) -> usize {
+    let Some(emulator) = emu.as_ref().and_then(|emu| emu.emulator.as_ref()) else {
+        return 0;
+    };
+    let state = emulator.save_state();
+    if !buffer.is_null() && state.len() <= capacity {
+        ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+    }
+    state.len()
+}
+
+/// Returns false and leaves the emulator as it was if the state can't be loaded
+///
+/// # Safety
+/// `emu` has to be valid and `data` has to point to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn gbemu_load_state(emu: *mut GbEmu, data: *const u8, len: usize) -> bool {
+    let Some(emulator) = emu.as_mut().and_then(|emu| emu.emulator.as_mut()) else {
+        return false;
+    };
+    if data.is_null() {
+        return false;
+    }
+    match emulator.load_state(slice::from_raw_parts(data, len)) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("gbemu_load_state: {}", e);
+            false
+        }
+    }
+}
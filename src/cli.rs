@@ -0,0 +1,439 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::emulator::{
+    boot::Boot, movie::MovieMode, paths::Paths, serial::tcp::SERIAL_CLOCK_HZ, Options,
+};
+
+pub const USAGE: &str = "\
+Usage: gameboy_emulator [OPTIONS] [ROM]
+       gameboy_emulator savestate <export|import> <ROM> <FILE>
+       gameboy_emulator sram <export|import> <SAV> <FILE>
+       gameboy_emulator trace diff <ROM> <LOG>
+
+Runs ROM, which can be zipped or gzipped, or the built-in one if not given.
+
+Subcommands:
+    savestate export <ROM> <FILE>
+                        Copy the state --resume saved for ROM to FILE
+    savestate import <ROM> <FILE>
+                        Check FILE is a state for ROM and make it the one --resume loads
+    sram export <SAV> <FILE>
+                        Write just the cartridge RAM in SAV to FILE, without an RTC footer
+    sram import <SAV> <FILE>
+                        Replace the cartridge RAM in SAV with FILE, keeping SAV's RTC footer
+    trace diff <ROM> <LOG>
+                        Run ROM against a Game Boy Doctor or BGB trace LOG and show the first
+                        line the registers don't match
+
+Options:
+    --record <MOVIE>    Record joypad input from power on into MOVIE
+    --play <MOVIE>      Play back joypad input from MOVIE
+    --link-local        Open a second instance connected through the link cable
+    --link-host <ADDR>  Wait for another emulator to connect its link cable on ADDR
+    --link-connect <ADDR>
+                        Connect the link cable to an emulator hosting on ADDR
+    --printer <DIR>     Plug in a Game Boy Printer that saves printouts to DIR
+    --link-partner <BYTE[@HZ]>
+                        Plug in a partner that keeps clocking BYTE (hex) over the link at HZ
+                        bits a second (default 8192), for games waiting on the external clock
+    --ir-loopback       Point the CGB infrared port at a mirror, it sees its own LED
+    --ir-host <ADDR>    Wait for another emulator to connect its infrared port on ADDR
+    --ir-connect <ADDR> Point the infrared port at an emulator hosting on ADDR
+    --ir-record <FILE>  Write what the infrared LED does to FILE
+    --ir-play <FILE>    Shine infrared recorded with --ir-record at the game
+                        (with --link-local the two instances' ports face each other)
+    --camera <FILE>     Point the Game Boy Camera at the picture in FILE, a PGM or PPM image
+    --headless <FRAMES> Run FRAMES frames without a window and print a hash of the last one
+    --hash-every <N>    With --headless, also print a hash of every Nth frame
+    --serial            With --headless, also print each line the game prints over the link
+                        port as it goes, unless something's plugged in
+    --serial-until <REGEX>
+                        With --headless, stop once a line the game prints over the link port
+                        matches REGEX, failing if none does
+    --bench <ROM>       Run ROM without a window or frame limiter and print how fast it went
+    --frames <N>        How many frames --bench runs (default 3600, a minute of game time)
+    --strict-memory     Stop on reads and writes of unmapped memory instead of ignoring them
+    --verify-rom        Refuse to run ROMs with a bad logo or checksum in the header, which are
+                        otherwise only warned about
+    --model <MODEL>     Hardware to emulate: dmg0, dmg (default), mgb, sgb, sgb2, cgb or agb
+    --boot <BOOT>       What runs first: skip (default) starts the game as the boot ROM would,
+                        open runs a built-in boot ROM with the logo scroll (DMG family only)
+                        and anything else is a file with a dump of the model's boot ROM
+    --cgb-palette <BUTTONS>
+                        Colors for a DMG game on cgb or agb, picked like holding BUTTONS
+                        during the boot logo: up, up+a, left+b, right+a (default) and so on
+    --sym <FILE>        Load labels from an RGBDS symbol file, ROM.sym is used if it exists
+    --log <FILTER>      Which logs to show, like info,cpu=trace (default info).
+                        Targets are cpu, bus, ppu, apu, timer, serial and joypad
+    --no-oam-bug        Don't emulate OAM corruption by 16-bit INC/DEC during OAM scan
+    --no-watchdog       Keep running when the game looks hung instead of stopping with an error
+    --backend <BACKEND> How to run instructions: interpreter (default), or cached to decode each
+                        block of code once (needs the cached-interpreter feature)
+    --coverage <FILE>   Write which opcodes ran and which ROM bytes were executed to FILE on exit
+    --access-stats <FILE>
+                        Count memory reads and writes, writing totals to FILE on exit
+    --roms <DIR>        Pick a game from the ROMs in DIR when ROM isn't given, zipped or not
+    --recent <N>        Run the Nth most recently opened ROM, 1 is the last one
+    --pause-in-background
+                        Pause while the window isn't focused (not with --link-local)
+    --reduce-flashing   Tone down sudden full screen flashes
+    --resume            Save state on exit and pick up from it next time this game starts
+    --config-dir <DIR>  Keep everything in DIR instead of the platform's usual places
+    --save-dir <DIR>    Keep battery saves in DIR
+    --state-dir <DIR>   Keep save states in DIR
+                        (the directory options can go before a subcommand too)
+    -h, --help          Print this message";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkArg {
+    /// Second instance in this process
+    Local,
+    Host(String),
+    Connect(String),
+    Printer(PathBuf),
+    /// See [`crate::emulator::serial::partner`]
+    Partner {
+        byte: u8,
+        hz: u32,
+    },
+}
+
+impl LinkArg {
+    fn partner(value: &str) -> Result<Self, String> {
+        let bad = || {
+            format!(
+                "--link-partner takes a hex byte and optionally @HZ, got '{}'",
+                value
+            )
+        };
+        let (byte, hz) = match value.split_once('@') {
+            Some((byte, hz)) => (byte, hz.parse().ok().filter(|&hz| hz > 0).ok_or_else(bad)?),
+            None => (value, SERIAL_CLOCK_HZ),
+        };
+        let byte = byte.trim_start_matches("0x");
+        let byte = u8::from_str_radix(byte, 16).map_err(|_| bad())?;
+        Ok(LinkArg::Partner { byte, hz })
+    }
+}
+
+/// What the infrared port is pointed at, see [`crate::emulator::infrared`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum IrArg {
+    Loopback,
+    Host(String),
+    Connect(String),
+    Record(PathBuf),
+    Play(PathBuf),
+}
+
+/// A minute at 60 FPS
+const DEFAULT_BENCH_FRAMES: u32 = 3600;
+
+#[derive(Debug)]
+pub struct Headless {
+    pub frames: u32,
+    /// Print hashes of frames in between too
+    pub hash_every: Option<u32>,
+    /// Stop early once a line of serial output matches
+    pub serial_until: Option<Regex>,
+    /// Print serial output between the hashes
+    pub serial: bool,
+}
+
+/// Where to keep things instead of where [`Paths::find`] says
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Dirs {
+    pub config: Option<PathBuf>,
+    pub saves: Option<PathBuf>,
+    pub states: Option<PathBuf>,
+}
+
+impl Dirs {
+    const FLAGS: [&'static str; 3] = ["--config-dir", "--save-dir", "--state-dir"];
+
+    fn set(&mut self, flag: &str, dir: String) {
+        let dir = Some(PathBuf::from(dir));
+        match flag {
+            "--config-dir" => self.config = dir,
+            "--save-dir" => self.saves = dir,
+            _ => self.states = dir,
+        }
+    }
+
+    /// `None` if there's nowhere to put the rest
+    pub fn paths(&self) -> Option<Paths> {
+        let mut paths = match &self.config {
+            Some(dir) => Paths::in_dir(dir),
+            None => Paths::find()?,
+        };
+        for (dir, path) in [
+            (&self.saves, &mut paths.saves),
+            (&self.states, &mut paths.states),
+        ] {
+            if let Some(dir) = dir {
+                path.clone_from(dir);
+            }
+        }
+        Some(paths)
+    }
+}
+
+/// Works on files instead of running anything
+#[derive(Debug, PartialEq, Eq)]
+pub enum Subcommand {
+    ExportState { rom: PathBuf, file: PathBuf },
+    ImportState { rom: PathBuf, file: PathBuf },
+    ExportSram { sav: PathBuf, file: PathBuf },
+    ImportSram { sav: PathBuf, file: PathBuf },
+    DiffTrace { rom: PathBuf, log: PathBuf },
+}
+
+impl Subcommand {
+    /// `kind` is `savestate`, `sram` or `trace`, `args` everything after it
+    fn parse(kind: &str, args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let args: Vec<String> = args.collect();
+        let [action, target, file] = &args[..] else {
+            let actions = if kind == "trace" {
+                "diff"
+            } else {
+                "export or import"
+            };
+            return Err(format!("{} takes {} and two files", kind, actions));
+        };
+        let (target, file) = (PathBuf::from(target), PathBuf::from(file));
+        Ok(match (kind, action.as_str()) {
+            ("savestate", "export") => Subcommand::ExportState { rom: target, file },
+            ("savestate", "import") => Subcommand::ImportState { rom: target, file },
+            ("sram", "export") => Subcommand::ExportSram { sav: target, file },
+            ("sram", "import") => Subcommand::ImportSram { sav: target, file },
+            ("trace", "diff") => Subcommand::DiffTrace {
+                rom: target,
+                log: file,
+            },
+            (_, other) => return Err(format!("Unknown {} action '{}'", kind, other)),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub options: Options,
+    pub rom: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    /// A boot ROM dump to read into [`Options::boot`]
+    pub boot_rom: Option<PathBuf>,
+    pub link: Option<LinkArg>,
+    pub infrared: Option<IrArg>,
+    /// Picture for the Game Boy Camera
+    pub camera: Option<PathBuf>,
+    pub headless: Option<Headless>,
+    /// Frames to benchmark [`Args::rom`] for
+    pub bench: Option<u32>,
+    /// See [`crate::logging`]
+    pub log: Option<String>,
+    /// See [`crate::recent`]
+    pub recent: Option<u32>,
+    /// Where the ROM browser looks
+    pub roms: Option<PathBuf>,
+    pub pause_in_background: bool,
+    pub dirs: Dirs,
+    pub subcommand: Option<Subcommand>,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().peekable();
+        let mut hash_every = None;
+        let mut serial_until = None;
+        let mut serial = false;
+        let mut bench = false;
+        let mut bench_frames = None;
+
+        while let Some(flag) = args.next_if(|arg| Dirs::FLAGS.contains(&arg.as_str())) {
+            parsed.dirs.set(&flag, Self::value(&flag, args.next())?);
+        }
+        if let Some(kind) =
+            args.next_if(|arg| matches!(arg.as_str(), "savestate" | "sram" | "trace"))
+        {
+            if args
+                .peek()
+                .is_some_and(|arg| arg == "-h" || arg == "--help")
+            {
+                parsed.help = true;
+            } else {
+                parsed.subcommand = Some(Subcommand::parse(&kind, args)?);
+            }
+            return Ok(parsed);
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" | "--play" => {
+                    if parsed.options.movie.is_some() {
+                        return Err("Only one of --record and --play may be given".into());
+                    }
+                    let path = PathBuf::from(Self::value(&arg, args.next())?);
+                    parsed.options.movie = Some(if arg == "--record" {
+                        MovieMode::Record(path)
+                    } else {
+                        MovieMode::Play(path)
+                    });
+                }
+                "--link-local" | "--link-host" | "--link-connect" | "--printer"
+                | "--link-partner" => {
+                    if parsed.link.is_some() {
+                        return Err("Only one link cable option may be given".into());
+                    }
+                    parsed.link = Some(match arg.as_str() {
+                        "--link-local" => LinkArg::Local,
+                        "--link-host" => LinkArg::Host(Self::value(&arg, args.next())?),
+                        "--printer" => {
+                            LinkArg::Printer(PathBuf::from(Self::value(&arg, args.next())?))
+                        }
+                        "--link-partner" => LinkArg::partner(&Self::value(&arg, args.next())?)?,
+                        _ => LinkArg::Connect(Self::value(&arg, args.next())?),
+                    });
+                }
+                "--ir-loopback" | "--ir-host" | "--ir-connect" | "--ir-record" | "--ir-play" => {
+                    if parsed.infrared.is_some() {
+                        return Err("Only one infrared option may be given".into());
+                    }
+                    parsed.infrared = Some(match arg.as_str() {
+                        "--ir-loopback" => IrArg::Loopback,
+                        "--ir-host" => IrArg::Host(Self::value(&arg, args.next())?),
+                        "--ir-connect" => IrArg::Connect(Self::value(&arg, args.next())?),
+                        "--ir-record" => {
+                            IrArg::Record(PathBuf::from(Self::value(&arg, args.next())?))
+                        }
+                        _ => IrArg::Play(PathBuf::from(Self::value(&arg, args.next())?)),
+                    });
+                }
+                "--camera" => parsed.camera = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--headless" => {
+                    parsed.headless = Some(Headless {
+                        frames: Self::count(&arg, args.next())?,
+                        hash_every: None,
+                        serial_until: None,
+                        serial: false,
+                    })
+                }
+                "--hash-every" => hash_every = Some(Self::count(&arg, args.next())?),
+                "--serial" => serial = true,
+                "--serial-until" => {
+                    let pattern = Self::value(&arg, args.next())?;
+                    let regex = Regex::new(&pattern)
+                        .map_err(|e| format!("Bad --serial-until pattern: {}", e))?;
+                    serial_until = Some(regex);
+                }
+                "--bench" => {
+                    if parsed.rom.is_some() {
+                        return Err("--bench takes the ROM, it can't be given again".into());
+                    }
+                    parsed.rom = Some(PathBuf::from(Self::value(&arg, args.next())?));
+                    bench = true;
+                }
+                "--frames" => bench_frames = Some(Self::count(&arg, args.next())?),
+                "--strict-memory" => parsed.options.strict_memory = true,
+                "--verify-rom" => parsed.options.verify_rom = true,
+                "--sym" => parsed.symbols = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--no-oam-bug" => parsed.options.no_oam_bug = true,
+                "--no-watchdog" => parsed.options.no_watchdog = true,
+                "--resume" => parsed.options.resume = true,
+                "--reduce-flashing" => parsed.options.reduce_flashing = true,
+                "--pause-in-background" => parsed.pause_in_background = true,
+                "--roms" => parsed.roms = Some(PathBuf::from(Self::value(&arg, args.next())?)),
+                "--recent" => parsed.recent = Some(Self::count(&arg, args.next())?),
+                "--log" => parsed.log = Some(Self::value(&arg, args.next())?),
+                "--coverage" => {
+                    parsed.options.coverage = Some(PathBuf::from(Self::value(&arg, args.next())?))
+                }
+                "--access-stats" => {
+                    parsed.options.access_stats =
+                        Some(PathBuf::from(Self::value(&arg, args.next())?))
+                }
+                "--model" => parsed.options.model = Self::value(&arg, args.next())?.parse()?,
+                "--boot" => match Self::value(&arg, args.next())?.as_str() {
+                    "skip" => parsed.options.boot = Boot::Skip,
+                    "open" => parsed.options.boot = Boot::Open,
+                    file => parsed.boot_rom = Some(PathBuf::from(file)),
+                },
+                "--cgb-palette" => {
+                    parsed.options.cgb_palette = Some(Self::value(&arg, args.next())?.parse()?)
+                }
+                "--backend" => parsed.options.backend = Self::value(&arg, args.next())?.parse()?,
+                flag if Dirs::FLAGS.contains(&flag) => {
+                    parsed.dirs.set(flag, Self::value(flag, args.next())?)
+                }
+                "-h" | "--help" => parsed.help = true,
+                other if other.starts_with('-') => {
+                    return Err(format!("Unknown argument '{}'", other))
+                }
+                rom => {
+                    if parsed.rom.is_some() {
+                        return Err(format!("Unexpected argument '{}'", rom));
+                    }
+                    parsed.rom = Some(PathBuf::from(rom));
+                }
+            }
+        }
+
+        if let Some(every) = hash_every {
+            match parsed.headless.as_mut() {
+                Some(headless) => headless.hash_every = Some(every),
+                None => return Err("--hash-every requires --headless".into()),
+            }
+        }
+        if let Some(regex) = serial_until {
+            match parsed.headless.as_mut() {
+                Some(headless) => headless.serial_until = Some(regex),
+                None => return Err("--serial-until requires --headless".into()),
+            }
+        }
+        if serial {
+            match parsed.headless.as_mut() {
+                Some(headless) => headless.serial = true,
+                None => return Err("--serial requires --headless".into()),
+            }
+        }
+        match (bench, bench_frames) {
+            (true, frames) => parsed.bench = Some(frames.unwrap_or(DEFAULT_BENCH_FRAMES)),
+            (false, Some(_)) => return Err("--frames requires --bench".into()),
+            (false, None) => {}
+        }
+        if parsed.bench.is_some() && (parsed.headless.is_some() || parsed.link.is_some()) {
+            return Err("--bench runs on its own, without --headless or a link cable".into());
+        }
+        if parsed.recent.is_some() && parsed.rom.is_some() {
+            return Err("Only one of ROM and --recent may be given".into());
+        }
+        if parsed.pause_in_background && parsed.link == Some(LinkArg::Local) {
+            // Only one of the windows can have focus, the other would stop both
+            return Err("--pause-in-background can't be used with --link-local".into());
+        }
+        if parsed.headless.is_some() && parsed.link == Some(LinkArg::Local) {
+            return Err("--link-local needs windows, it can't be used with --headless".into());
+        }
+
+        Ok(parsed)
+    }
+
+    /// A positive number
+    fn count(flag: &str, value: Option<String>) -> Result<u32, String> {
+        let value = Self::value(flag, value)?;
+        match value.parse() {
+            Ok(0) | Err(_) => Err(format!(
+                "{} requires a positive number, got '{}'",
+                flag, value
+            )),
+            Ok(count) => Ok(count),
+        }
+    }
+
+    fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+        value.ok_or_else(|| format!("{} requires a value", flag))
+    }
+}
@@ -1,53 +1,782 @@
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    mpsc::Receiver,
-    Arc, Mutex,
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Instant,
 };
 
+use tracing::{debug, error, info, warn};
+
+pub mod apu;
+use apu::view::ApuView;
+pub mod archive;
+pub mod boot;
+use boot::Boot;
+pub mod cheats;
+use cheats::Cheat;
+pub mod coverage;
+use coverage::Coverage;
 pub mod cpu;
-use cpu::CPU;
+use cpu::{
+    backend::{Backend, CpuCore},
+    CPU,
+};
+pub mod debugger;
+use debugger::{DebugCommand, DebugView, Debugger};
+pub mod error;
+use error::{Crash, EmulatorError};
+pub mod hardware;
+use hardware::HardwareModel;
+pub mod infrared;
+use infrared::InfraredLink;
 pub mod instructions;
+pub mod joypad;
+use joypad::{Button, TurboConfig};
+pub mod mbc;
+use mbc::{camera::CameraSource, mbc7::TiltDirection};
 pub mod memory_bus;
 use memory_bus::MemoryBus;
+pub mod movie;
+use movie::{Movie, MovieHeader, MovieMode, MoviePlayer, MovieRecorder, MovieStart};
+pub mod paths;
+use paths::Paths;
+pub mod png;
 pub mod ppu;
-use ppu::PPU;
+use ppu::{
+    compat_palettes::CompatPalette, flash_filter::FlashFilter, frame_blend::FrameBlend, PPU,
+};
+pub mod rom;
+use rom::Location;
+pub mod rtc;
+pub mod save_file;
+pub mod serial;
+use serial::{console::Lines, SerialLink};
+pub mod state;
+use state::{StateError, StateReader, StateWriter};
+pub mod stats;
+use stats::{Stats, StatsMeter, Throughput};
+pub mod symbols;
+use symbols::Symbols;
+pub mod timer;
+pub mod trace;
+pub mod triple_buffer;
+use triple_buffer::TripleBuffer;
+pub mod watchdog;
+use watchdog::{CpuState, Watchdog};
 
 #[cfg(test)]
 pub mod unit_tests;
 
-const GAMEBOY_WIDTH: usize = 160;
-const GAMEBOY_HEIGHT: usize = 144;
+pub const GAMEBOY_WIDTH: usize = 160;
+pub const GAMEBOY_HEIGHT: usize = 144;
 
-pub struct DoubleBuffer {
-    buffers: [Mutex<ppu::FrameBuffer>; 2],
-    curr_buffer: AtomicUsize,
+/// Messages from the frontend to the emulator thread.
+/// These are only applied on frame boundaries, so the result doesn't depend on
+/// how the OS schedules the two threads.
+#[derive(Clone, Debug)]
+pub enum Command {
+    Button(Button, bool),
+    Turbo(Button, bool),
+    SetTurboConfig(TurboConfig),
+    /// Replaces the active cheat list
+    SetCheats(Vec<Cheat>),
+    Debug(DebugCommand),
+    /// Stops running frames while true, like the window being in the background. Separate from
+    /// pausing in the debugger, so neither undoes the other.
+    SetInactive(bool),
+    /// See [`FrameBlend::set_persistence`]
+    SetFrameBlend(f32),
+    /// See [`FlashFilter`]
+    SetReduceFlashing(bool),
+    /// Sends an [`ApuView`] with every frame while true, including what the channels have
+    /// been playing, see [`Apu::set_scope`](apu::Apu::set_scope)
+    SetAudioView(bool),
+    /// Tips the cartridge all the way towards one edge or back, only MBC7 games notice
+    Tilt(TiltDirection, bool),
+    /// Power cycles the console, see [`Emulator::reset`]
+    Reset,
+    /// Finish up (flush movies etc.) and stop the emulator thread
+    Quit,
 }
 
-impl Default for DoubleBuffer {
-    fn default() -> Self {
-        Self {
-            buffers: [
-                Mutex::new([0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH]),
-                Mutex::new([0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH]),
-            ],
-            curr_buffer: AtomicUsize::new(0),
+/// The console itself, without any threads or timing
+pub struct Emulator {
+    /// Whatever runs the code, see [`Emulator::set_core`]
+    core: Box<dyn CpuCore>,
+    ppu: PPU,
+    memory_bus: MemoryBus,
+    frame_buffer: Box<ppu::FrameBuffer>,
+    /// Where the instruction being run started, for reporting panics
+    instruction_pc: u16,
+    symbols: Symbols,
+    coverage: Option<Coverage>,
+    watchdog: Option<Watchdog>,
+    /// Where the last [`RECENT_INSTRUCTIONS`] instructions were, oldest first
+    recent: VecDeque<u16>,
+    on_frame: Option<FrameCallback>,
+    /// T-cycles the PPU hasn't been ticked for yet, see [`Emulator::run_frame`]
+    ppu_behind: u32,
+    /// How far behind the PPU can get, from [`PPU::cycles_until_event`]
+    ppu_deadline: u32,
+    /// See [`Emulator::cycles`]
+    cycles: u64,
+    /// See [`Emulator::instructions`]
+    instructions: u64,
+}
+
+/// Called with every finished frame, see [`Emulator::set_frame_callback`]
+pub type FrameCallback = Box<dyn FnMut(&ppu::FrameBuffer) + Send>;
+
+/// How many instructions a [`Crash`] shows
+const RECENT_INSTRUCTIONS: usize = 16;
+
+impl Emulator {
+    /// Powers on as a [`HardwareModel::Dmg`] that's just finished booting
+    pub fn new(rom: &[u8]) -> Self {
+        let mut emulator = Self {
+            core: Box::new(CPU::default()),
+            ppu: PPU::default(),
+            memory_bus: MemoryBus::new(rom),
+            frame_buffer: Box::new([0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH]),
+            instruction_pc: 0,
+            symbols: Symbols::default(),
+            coverage: None,
+            watchdog: Some(Watchdog::new(watchdog::DEFAULT_LIMIT)),
+            recent: VecDeque::with_capacity(RECENT_INSTRUCTIONS),
+            on_frame: None,
+            ppu_behind: 0,
+            ppu_deadline: 0,
+            cycles: 0,
+            instructions: 0,
+        };
+        emulator.set_model(HardwareModel::default());
+        emulator
+    }
+
+    /// Switches the model's quirks on and, since this belongs right after power on, sets
+    /// everything up for the cartridge to start. With a
+    /// [boot ROM](MemoryBus::set_boot_rom) that's running it from 0 with the registers cleared,
+    /// otherwise it's loading what the model's boot ROM would leave.
+    pub fn set_model(&mut self, model: HardwareModel) {
+        self.memory_bus.set_model(model);
+        if self.memory_bus.map_boot_rom() {
+            self.core.registers_mut().power_on();
+            debug!("Running a boot ROM on the {}", model);
+            return;
+        }
+        self.memory_bus.apu_mut().boot(model);
+        let header_checksum = self.memory_bus.header_checksum();
+        self.core.registers_mut().reset(model, header_checksum);
+        debug!(
+            "Skipped the {} boot ROM ({})",
+            model,
+            model.boot_rom().file_name
+        );
+    }
+
+    pub fn model(&self) -> HardwareModel {
+        self.memory_bus.model()
+    }
+
+    /// T-cycles run since [`Emulator::new`], resets included. Loading a state doesn't change
+    /// it either, it's only for measuring how fast emulation goes.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Instructions run since [`Emulator::new`], not counting interrupt dispatches or halted
+    /// cycles. Like [`Emulator::cycles`] nothing resets it.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Turns it off and on again. The cartridge, cheats, link cable, infrared link, core and
+    /// settings stay, everything else starts over like [`Emulator::new`] with the same model.
+    pub fn reset(&mut self) {
+        self.memory_bus.reset();
+        self.ppu = PPU::default();
+        self.frame_buffer.fill(0);
+        self.ppu_behind = 0;
+        self.ppu_deadline = 0;
+        self.recent.clear();
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.reset();
+        }
+        self.set_model(self.model());
+    }
+
+    /// Runs `rom` before the cartridge from now on, see [`MemoryBus::set_boot_rom`]. Power
+    /// cycles with [`Emulator::reset`] so it's running straight away.
+    pub fn set_boot_rom(&mut self, rom: Option<Vec<u8>>) {
+        self.memory_bus.set_boot_rom(rom);
+        self.reset();
+    }
+
+    /// Runs one instruction (or interrupt dispatch).
+    /// Returns true if that finished a frame, [`Emulator::frame_buffer`] is complete then.
+    pub fn step(&mut self) -> Result<bool, EmulatorError> {
+        self.step_with(false)
+    }
+
+    /// With `lazy_ppu` the PPU is only ticked once it's due an interrupt or a frame, or before
+    /// an instruction that might look at it, and a halted CPU skips ahead to the next thing
+    /// that could wake it. Whoever uses it has to [`Emulator::catch_up_ppu`] before anything
+    /// else can see the emulator.
+    fn step_with(&mut self, lazy_ppu: bool) -> Result<bool, EmulatorError> {
+        self.instruction_pc = self.cpu().PC;
+        let eager_ppu = !lazy_ppu || self.may_touch_ppu();
+        if eager_ppu {
+            self.catch_up_ppu();
+        }
+        if !self.symbols.is_empty() {
+            if let Some(name) = self.symbols.get(self.location(self.cpu().PC)) {
+                debug!("Entering {}", name);
+            }
+        }
+        // Fetched first, the instruction could overwrite itself
+        let bytes = self
+            .coverage
+            .is_some()
+            .then(|| self.memory_bus.get_instr(self.cpu().PC));
+        let mut ticks = self.core.step(&mut self.memory_bus);
+        if lazy_ppu && self.cpu().halted && self.memory_bus.get_next_interrupt().is_none() {
+            // Only time passes until something requests an interrupt
+            let idle = self
+                .ppu_deadline
+                .saturating_sub(self.ppu_behind)
+                .min(self.memory_bus.cycles_until_interrupt());
+            ticks = ticks.max(idle / 4);
+        }
+        let location = self.location(self.instruction_pc);
+        if let (Some(coverage), Some(bytes)) = (self.coverage.as_mut(), bytes) {
+            if self.core.registers().last_instruction == Some(self.instruction_pc) {
+                coverage.record(location, &bytes);
+            }
+        }
+        if let Some(pc) = self.cpu().last_instruction {
+            self.instructions += 1;
+            if self.recent.len() == RECENT_INSTRUCTIONS {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(pc);
+        }
+        self.memory_bus.tick(ticks * 4);
+        self.ppu_behind += ticks * 4;
+        self.cycles += ticks as u64 * 4;
+        let frame_done = (eager_ppu || self.ppu_behind >= self.ppu_deadline) && self.catch_up_ppu();
+        if let Some(error) = self.memory_bus.take_fault() {
+            return Err(error);
+        }
+        self.check_watchdog(ticks * 4, frame_done)?;
+        if !frame_done {
+            return Ok(false);
+        }
+
+        // The frame ends as V-blank starts
+        self.memory_bus.apply_ram_cheats();
+        self.memory_bus.joypad_mut().frame_tick();
+        if let Some(on_frame) = self.on_frame.as_mut() {
+            on_frame(&self.frame_buffer);
+        }
+        Ok(true)
+    }
+
+    /// Ticks the PPU for however long it's been put off, returns true if that finished a frame
+    fn catch_up_ppu(&mut self) -> bool {
+        let frame_done = self.ppu_behind > 0
+            && self.ppu.tick(
+                &mut self.memory_bus,
+                &mut self.frame_buffer,
+                self.ppu_behind,
+            );
+        self.ppu_behind = 0;
+        self.ppu_deadline = self.ppu.cycles_until_event(&self.memory_bus);
+        frame_done
+    }
+
+    /// Whether the next instruction (or interrupt dispatch) could read or write anything the
+    /// PPU uses or changes, leaning towards yes. Only the opcode is looked at: any address a
+    /// register pair holds counts, along with the stack and the code itself.
+    fn may_touch_ppu(&self) -> bool {
+        let cpu = self.cpu();
+        let peek = |offset| self.memory_bus.peek(cpu.PC.wrapping_add(offset));
+        let operand = match peek(0) {
+            // LDH [a8], A and LDH A, [a8]
+            0xE0 | 0xF0 => Some(0xFF00 | peek(1) as u16),
+            // LDH [C], A and LDH A, [C]
+            0xE2 | 0xF2 => Some(0xFF00 | cpu.C as u16),
+            // LD [a16], SP, LD [a16], A and LD A, [a16]
+            0x08 | 0xEA | 0xFA => Some(u16::from_le_bytes([peek(1), peek(2)])),
+            _ => None,
+        };
+        let word_after = operand.map(|addr| addr.wrapping_add(1));
+        let stack = (0..4).map(|i| cpu.SP.wrapping_sub(2).wrapping_add(i));
+        [cpu.get_bc(), cpu.get_de(), cpu.get_hl(), cpu.PC]
+            .into_iter()
+            .chain(stack)
+            .chain(operand)
+            .chain(word_after)
+            .any(|addr| {
+                matches!(
+                    addr,
+                    0x8000..=0x9FFF | 0xFE00..=0xFEFF | 0xFF40..=0xFF4B | 0xFF68..=0xFF6C
+                )
+            })
+    }
+
+    fn check_watchdog(&mut self, cycles: u32, frame_done: bool) -> Result<(), EmulatorError> {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return Ok(());
+        };
+        let registers = self.core.registers();
+        let cpu = if registers.halted {
+            CpuState::Halted
+        } else if registers.last_instruction == Some(registers.PC) {
+            CpuState::Looping
+        } else {
+            CpuState::Running
+        };
+        let ie = self.memory_bus.peek(memory_bus::IE);
+        let wakeable = ie & 0x1F != 0;
+        // A blank frame from the LCD being off doesn't count, it could be off forever
+        let found = watchdog.tick(
+            cycles,
+            frame_done && !self.ppu.lcd_off(),
+            cpu,
+            wakeable,
+            wakeable && registers.IME,
+        );
+        match found {
+            Some((hang, cycles)) => Err(EmulatorError::Hung {
+                hang,
+                cycles,
+                pc: registers.PC,
+                ie,
+                if_: self.memory_bus.peek(memory_bus::IF),
+                ime: registers.IME,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops [`Emulator::step`] with [`EmulatorError::Hung`] after `limit` T-cycles of
+    /// something that can't recover, `None` to keep going forever
+    pub fn set_watchdog(&mut self, limit: Option<u64>) {
+        self.watchdog = limit.map(Watchdog::new);
+    }
+
+    /// Calls `on_frame` with each frame as it finishes, for frontends that would rather be told
+    /// than check what [`Emulator::step`] returns
+    pub fn set_frame_callback(&mut self, on_frame: impl FnMut(&ppu::FrameBuffer) + Send + 'static) {
+        self.on_frame = Some(Box::new(on_frame));
+    }
+
+    pub fn clear_frame_callback(&mut self) {
+        self.on_frame = None;
+    }
+
+    /// Runs instructions until the next thing that has to happen on time: an interrupt being
+    /// requested or a frame finishing, the PPU changing what the CPU sees, or the CPU looking
+    /// at the PPU. In between the PPU isn't ticked and a halted CPU skips straight to the end,
+    /// which is much cheaper than [`Emulator::step`]. Returns true if a frame finished.
+    pub fn run_until_event(&mut self) -> Result<bool, EmulatorError> {
+        loop {
+            match self.step_with(true) {
+                Ok(frame_done) if self.ppu_behind == 0 => return Ok(frame_done),
+                Ok(_) => {}
+                Err(error) => {
+                    self.catch_up_ppu();
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Runs until the PPU finishes a frame and returns it, with [`Emulator::run_until_event`]
+    pub fn run_frame(&mut self) -> Result<&ppu::FrameBuffer, EmulatorError> {
+        while !self.run_until_event()? {}
+        Ok(&self.frame_buffer)
+    }
+
+    /// Steps until a frame finishes or `stop` returns true after an instruction.
+    /// Returns true if the frame finished, `stop` may have been reached too then.
+    pub fn run_until(
+        &mut self,
+        mut stop: impl FnMut(&Self) -> bool,
+    ) -> Result<bool, EmulatorError> {
+        loop {
+            let frame_done = self.step()?;
+            if frame_done || stop(self) {
+                return Ok(frame_done);
+            }
+        }
+    }
+
+    /// Like [`Emulator::run_frame`], but a panic comes back as a [`Crash`] instead of unwinding.
+    /// The emulator is in whatever state the panic left it in then.
+    pub fn run_frame_catching(&mut self) -> Result<&ppu::FrameBuffer, Crash> {
+        self.catching(|emulator| emulator.run_frame().map(|_| ()))?;
+        Ok(&self.frame_buffer)
+    }
+
+    /// [`Emulator::step`] with panics caught like [`Emulator::run_frame_catching`]
+    pub fn step_catching(&mut self) -> Result<bool, Crash> {
+        self.catching(Emulator::step)
+    }
+
+    /// [`Emulator::run_until`] with panics caught like [`Emulator::run_frame_catching`]
+    pub fn run_until_catching(&mut self, stop: impl FnMut(&Self) -> bool) -> Result<bool, Crash> {
+        self.catching(|emulator| emulator.run_until(stop))
+    }
+
+    fn catching<T>(
+        &mut self,
+        run: impl FnOnce(&mut Self) -> Result<T, EmulatorError>,
+    ) -> Result<T, Crash> {
+        match panic::catch_unwind(AssertUnwindSafe(|| run(self))) {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => Err(self.crash(&e)),
+            Err(payload) => Err(self.panic_crash(payload.as_ref())),
+        }
+    }
+
+    /// `error` along with the state of the CPU that ran into it
+    pub fn crash(&self, error: &EmulatorError) -> Crash {
+        Crash {
+            message: self.with_symbol(error.to_string()),
+            cpu_dump: self.cpu_dump(),
+        }
+    }
+
+    fn cpu_dump(&self) -> String {
+        let recent: Vec<String> = self
+            .recent
+            .iter()
+            .map(|&pc| debugger::describe(self, pc))
+            .collect();
+        format!(
+            "{}\nRecent instructions, oldest first:\n{}",
+            self.cpu(),
+            recent.join("\n")
+        )
+    }
+
+    /// Adds the label `instruction_pc` is in to `message`, if there's one
+    fn with_symbol(&self, message: String) -> String {
+        match self.symbols.describe(self.location(self.instruction_pc)) {
+            Some(symbol) => format!("{} ({})", message, symbol),
+            None => message,
+        }
+    }
+
+    /// `addr` qualified with whatever bank is mapped there
+    pub fn location(&self, addr: u16) -> Location {
+        Location::mapped(addr, self.memory_bus.rom_bank() as u16)
+            .unwrap_or(Location { bank: 0, addr })
+    }
+
+    /// Labels for [`Emulator::crash`] reports and logs, usually from an RGBDS `.sym` file
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Starts recording a [`Coverage`] from here on
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.memory_bus.rom().len()));
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Can be switched at any point, the registers carry over. Switching to
+    /// [`Backend::Cached`] starts with nothing decoded.
+    pub fn set_backend(&mut self, backend: Backend) {
+        if self.backend() != Some(backend) {
+            self.set_core(|cpu| backend.core(cpu));
+        }
+    }
+
+    /// `None` with a core from [`Emulator::set_core`] that isn't one of the built in ones
+    pub fn backend(&self) -> Option<Backend> {
+        self.core.backend()
+    }
+
+    /// Raises `interrupt` through the core, like the hardware that normally would
+    pub fn inject_interrupt(&mut self, interrupt: memory_bus::Interrupt) {
+        self.core.inject_interrupt(&mut self.memory_bus, interrupt);
+    }
+
+    /// Swaps in another core, `make` gets the current one's registers
+    pub fn set_core(&mut self, make: impl FnOnce(CPU) -> Box<dyn CpuCore>) {
+        let old = std::mem::replace(&mut self.core, Box::new(CPU::default()));
+        self.core = make(old.into_registers());
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    fn panic_crash(&self, payload: &(dyn Any + Send)) -> Crash {
+        let reason = match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(reason), _) => reason,
+            (_, Some(reason)) => reason.as_str(),
+            _ => "unknown reason",
+        };
+        let pc = self.instruction_pc;
+        // Whatever panicked might well have been reading the instruction
+        let instruction = panic::catch_unwind(AssertUnwindSafe(|| {
+            let bytes = self.memory_bus.get_instr(pc);
+            instructions::Instruction::parse(&bytes)
+                .map(|(_, instruction)| instruction.to_string())
+                .ok()
+        }));
+        let instruction = match instruction {
+            Ok(Some(instruction)) => instruction,
+            Ok(None) => "an illegal instruction".into(),
+            Err(_) => "an unreadable instruction".into(),
+        };
+        // Reading the instruction back can fault too, that's not what went wrong
+        self.memory_bus.clear_fault();
+        Crash {
+            message: self.with_symbol(format!(
+                "Panicked running {} at {:#06X}: {}",
+                instruction, pc, reason
+            )),
+            cpu_dump: self.cpu_dump(),
+        }
+    }
+
+    /// Last completed frame (and whatever's been drawn of the next one), one byte per pixel
+    pub fn frame_buffer(&self) -> &ppu::FrameBuffer {
+        &self.frame_buffer
+    }
+
+    /// The frame buffer in color, on color models. DMG games get the colors in palette RAM
+    /// there too, see [`MemoryBus::set_compat_palette`]. DMGs only have the shades.
+    pub fn color_frame(&self) -> Option<&ppu::ColorFrameBuffer> {
+        self.memory_bus
+            .model()
+            .is_color()
+            .then(|| self.ppu.color_frame())
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(self.memory_bus.rom_checksum());
+        self.core.save_state(&mut state);
+        self.ppu.save_state(&mut state);
+        self.memory_bus.save_state(&mut state);
+        state.bytes(&self.frame_buffer[..]);
+        state.finish()
+    }
+
+    /// Leaves the emulator untouched if the state can't be loaded
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(bytes, self.memory_bus.rom_checksum())?;
+        // Everything has a fixed size, so checking that up front means nothing fails halfway
+        let expected = self.save_state().len();
+        if bytes.len() < expected {
+            return Err(StateError::Truncated);
+        }
+        if bytes.len() > expected {
+            return Err(StateError::TrailingData(bytes.len() - expected));
         }
+
+        self.core.load_state(&mut state)?;
+        self.ppu.load_state(&mut state)?;
+        self.memory_bus.load_state(&mut state)?;
+        state.fill(&mut self.frame_buffer[..])?;
+        state.finish()
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        self.core.registers()
+    }
+
+    pub fn memory_bus(&self) -> &MemoryBus {
+        &self.memory_bus
     }
+
+    pub fn memory_bus_mut(&mut self) -> &mut MemoryBus {
+        &mut self.memory_bus
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// ROM to run, [`DEFAULT_ROM`] if not given
+    pub rom: Option<Vec<u8>>,
+    pub movie: Option<MovieMode>,
+    /// Whatever is plugged into the link port
+    pub link: Option<Box<dyn SerialLink>>,
+    /// Whatever the infrared port is pointed at
+    pub infrared: Option<Box<dyn InfraredLink>>,
+    /// What the Game Boy Camera sees, ignored for other cartridges
+    pub camera: Option<Box<dyn CameraSource>>,
+    /// Where per-game data like cheats and saves is kept, nothing is saved without it
+    pub paths: Option<Paths>,
+    /// See [`MemoryBus::set_strict`]
+    pub strict_memory: bool,
+    pub model: HardwareModel,
+    /// What runs before the cartridge, see [`boot`]
+    pub boot: Boot,
+    /// See [`MemoryBus::set_oam_bug`]
+    pub no_oam_bug: bool,
+    /// See [`Emulator::set_watchdog`]
+    pub no_watchdog: bool,
+    pub symbols: Option<Symbols>,
+    /// Where to write a [`Coverage`] report when emulation stops
+    pub coverage: Option<PathBuf>,
+    /// Where to write [`AccessStats`](memory_bus::access_stats::AccessStats) when emulation
+    /// stops
+    pub access_stats: Option<PathBuf>,
+    /// Save a state when quitting and load it the next time the same game starts, only with
+    /// [`Options::paths`]
+    pub resume: bool,
+    /// Start with the debugger paused, nothing runs until it's continued
+    pub paused: bool,
+    /// Start with the [`FlashFilter`] on
+    pub reduce_flashing: bool,
+    /// See [`MemoryBus::set_compat_palette`], the boot ROM's pick if not given
+    pub cgb_palette: Option<CompatPalette>,
+    /// See [`Emulator::set_backend`]
+    pub backend: Backend,
+    /// Don't run ROMs that fail [`rom::check_integrity`]. Only frontends look at it, before
+    /// they call [`run`], since a ROM that's already been handed over runs regardless.
+    pub verify_rom: bool,
+}
+
+pub struct EmulatorHandle {
+    pub buffer: Arc<TripleBuffer>,
+    pub commands: Sender<Command>,
+    pub thread: JoinHandle<()>,
+    /// Gets a message if emulation stops on an error
+    pub crashes: Receiver<Crash>,
+    /// Sent whenever a [`Command::Debug`] is applied
+    pub debug_views: Receiver<DebugView>,
+    /// See [`Command::SetAudioView`]
+    pub audio_views: Receiver<ApuView>,
+    /// Whatever the game sent over the link cable since the last message, see
+    /// [`Serial::take_sent`](serial::Serial::take_sent)
+    pub serial_output: Receiver<Vec<u8>>,
+    /// Cheats saved for the loaded game
+    pub cheats: Vec<Cheat>,
+    /// Updated every [`stats::WINDOW`] while the game runs
+    pub stats: Arc<Mutex<Stats>>,
+    /// What [`rom::check_integrity`] found, which has been logged already
+    pub rom_problems: Vec<rom::RomProblem>,
+}
+
+enum ActiveMovie {
+    Recording(MovieRecorder<BufWriter<File>>),
+    Playing(MoviePlayer),
 }
 
-impl DoubleBuffer {
-    pub fn get_current(&self) -> &Mutex<ppu::FrameBuffer> {
-        &self.buffers[self.curr_buffer.load(Ordering::Relaxed)]
+impl ActiveMovie {
+    fn open(mode: &MovieMode, rom_checksum: u16) -> Result<Self, movie::MovieError> {
+        match mode {
+            MovieMode::Record(path) => {
+                let header = MovieHeader {
+                    start: MovieStart::PowerOn,
+                    rom_checksum,
+                };
+                let writer = BufWriter::new(File::create(path)?);
+                Ok(ActiveMovie::Recording(MovieRecorder::new(writer, header)?))
+            }
+            MovieMode::Play(path) => {
+                let movie = Movie::read(std::io::BufReader::new(File::open(path)?))?;
+                if movie.header.rom_checksum != rom_checksum {
+                    warn!(
+                        "Movie was recorded with a different ROM (checksum {:#06X}, loaded {:#06X})",
+                        movie.header.rom_checksum, rom_checksum
+                    );
+                }
+                info!("Playing back {} frames", movie.frames.len());
+                Ok(ActiveMovie::Playing(MoviePlayer::new(movie)))
+            }
+        }
     }
 
-    pub fn get_off(&self) -> &Mutex<ppu::FrameBuffer> {
-        &self.buffers[(self.curr_buffer.load(Ordering::Relaxed) + 1) % 2]
+    /// Logs the error and carries on without a movie if it can't be opened
+    fn start(mode: Option<&MovieMode>, emulator: &Emulator) -> Option<Self> {
+        let mode = mode?;
+        Self::open(mode, emulator.memory_bus().rom_checksum())
+            .map_err(|e| error!("Failed to open movie {:?}: {}", mode, e))
+            .ok()
+    }
+
+    /// Returns false once the movie is over
+    fn frame(&mut self, memory_bus: &mut MemoryBus) -> bool {
+        match self {
+            ActiveMovie::Recording(recorder) => {
+                if let Err(e) = recorder.record_frame(memory_bus.joypad().pressed()) {
+                    error!("Failed to record movie frame, stopping recording: {}", e);
+                    return false;
+                }
+                true
+            }
+            ActiveMovie::Playing(player) => match player.next_frame() {
+                Some(buttons) => {
+                    memory_bus.joypad_mut().set_pressed(buttons);
+                    true
+                }
+                None => {
+                    info!("Movie playback finished");
+                    memory_bus.joypad_mut().set_pressed(0);
+                    false
+                }
+            },
+        }
     }
 
-    pub fn swap(&self) {
-        let mut num = self.curr_buffer.load(Ordering::Acquire);
-        num = (num + 1) % 2;
-        self.curr_buffer.store(num, Ordering::Release);
+    fn finish(self) {
+        if let ActiveMovie::Recording(recorder) = self {
+            let frames = recorder.frames();
+            match recorder.finish() {
+                Ok(_) => info!("Recorded {} frames", frames),
+                Err(e) => error!("Failed to finish movie: {}", e),
+            }
+        }
+    }
+}
+
+fn apply_command(memory_bus: &mut MemoryBus, cheat_file: Option<&Path>, command: Command) {
+    match command {
+        Command::Button(button, pressed) => memory_bus.joypad_mut().set_button(button, pressed),
+        Command::Turbo(button, pressed) => memory_bus.joypad_mut().set_turbo(button, pressed),
+        Command::SetTurboConfig(config) => memory_bus.joypad_mut().set_turbo_config(config),
+        Command::SetCheats(cheats) => {
+            if let Some(path) = cheat_file {
+                if let Err(e) = cheats::save_cheats(path, &cheats) {
+                    error!("Failed to save cheats to {:?}: {}", path, e);
+                }
+            }
+            memory_bus.cheats_mut().set(cheats);
+        }
+        Command::Tilt(direction, held) => {
+            if let Some(accelerometer) = memory_bus.accelerometer_mut() {
+                accelerometer.set_held(direction, held);
+            }
+        }
+        Command::SetAudioView(enabled) => memory_bus.apu_mut().set_scope(enabled),
+        // Handled by the thread, they need the whole emulator
+        Command::Debug(_)
+        | Command::SetInactive(_)
+        | Command::SetFrameBlend(_)
+        | Command::SetReduceFlashing(_)
+        | Command::Reset
+        | Command::Quit => {}
     }
 }
 
@@ -63,53 +792,509 @@ fn timer_periodic(ms: u64) -> Receiver<()> {
     rx
 }
 
-pub fn run() -> Arc<DoubleBuffer> {
-    let buffer = Arc::new(DoubleBuffer::default());
+/// Used when [`Options::rom`] isn't given
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/test.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/hello-world.gb");
+pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/tetris.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/alu-test.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/dmg-acid2.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/cpu_instrs.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/01-special.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/02-interrupts.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/03-op sp,hl.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/04-op r,imm.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/05-op rp.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/06-ld r,r.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/07-jr,jp,call,ret,rst.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/08-misc instrs.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/09-op r,r.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/10-bit ops.gb");
+// pub const DEFAULT_ROM: &[u8] = include_bytes!("../roms/11-op a,(hl).gb");
+
+/// Builds the emulator for `options` with the saved cheats applied and the link plugged in.
+/// Also returns where cheats are saved.
+fn power_on(options: &mut Options) -> (Emulator, Option<PathBuf>, Vec<Cheat>) {
+    let rom = options.rom.as_deref().unwrap_or(DEFAULT_ROM);
+    // Before it's padded, which would hide that it's too short
+    for problem in rom::check_integrity(rom) {
+        warn!("{}", problem);
+    }
+    let mut emulator = Emulator::new(rom);
+    if rom::is_mbc1_multicart(emulator.memory_bus().rom()) {
+        info!("This is an MBC1 multicart, its games are banked like one");
+    }
+    emulator.set_model(options.model);
+    if let Some(boot_rom) = options.boot.rom(options.model) {
+        emulator.set_boot_rom(Some(boot_rom));
+    }
+    if let Some(symbols) = options.symbols.take() {
+        info!("Loaded {} symbols", symbols.len());
+        emulator.set_symbols(symbols);
+    }
+    if options.coverage.is_some() {
+        emulator.enable_coverage();
+    }
+    if options.no_watchdog {
+        emulator.set_watchdog(None);
+    }
+    emulator.set_backend(options.backend);
+    let memory_bus = emulator.memory_bus_mut();
+    if options.access_stats.is_some() {
+        memory_bus.enable_access_stats();
+    }
+    memory_bus.set_strict(options.strict_memory);
+    memory_bus.set_oam_bug(!options.no_oam_bug);
+    if let Some(palette) = options.cgb_palette {
+        memory_bus.set_compat_palette(palette);
+    }
+
+    let cheat_file = options
+        .paths
+        .as_ref()
+        .map(|paths| cheats::cheat_file(paths, &memory_bus.rom_title(), memory_bus.rom_checksum()));
+    let saved_cheats = match cheat_file.as_deref().map(cheats::load_cheats) {
+        Some(Ok(cheats)) => cheats,
+        Some(Err(e)) => {
+            error!("Failed to load cheats from {:?}: {}", cheat_file, e);
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    memory_bus.cheats_mut().set(saved_cheats.clone());
+    if let Some(link) = options.link.take() {
+        memory_bus.serial_mut().connect(link);
+    }
+    if let Some(infrared) = options.infrared.take() {
+        memory_bus.infrared_mut().connect(infrared);
+    }
+    if let Some(source) = options.camera.take() {
+        match memory_bus.camera_mut() {
+            Some(camera) => camera.connect(source),
+            None => warn!("Not a Game Boy Camera, nothing will see the picture"),
+        }
+    }
+    (emulator, cheat_file, saved_cheats)
+}
+
+pub fn run(mut options: Options) -> EmulatorHandle {
+    let buffer = Arc::new(TripleBuffer::default());
+    let (command_sender, commands) = std::sync::mpsc::channel();
+    let (crash_sender, crashes) = std::sync::mpsc::channel();
+    let (view_sender, debug_views) = std::sync::mpsc::channel();
+    let (audio_sender, audio_views) = std::sync::mpsc::channel();
+    let (serial_sender, serial_output) = std::sync::mpsc::channel();
+    let (mut emulator, cheat_file, saved_cheats) = power_on(&mut options);
+    let rom_problems = rom::check_integrity(options.rom.as_deref().unwrap_or(DEFAULT_ROM));
+    let resume_file = options
+        .paths
+        .as_ref()
+        .filter(|_| options.resume)
+        .map(|paths| {
+            let memory_bus = emulator.memory_bus();
+            state::resume_file(paths, &memory_bus.rom_title(), memory_bus.rom_checksum())
+        });
+    // Movies start from a blank cartridge, and a run that's only watched shouldn't change it
+    let battery_file = options
+        .paths
+        .as_ref()
+        .filter(|_| options.movie.is_none() && emulator.memory_bus().battery().is_some())
+        .map(|paths| {
+            let memory_bus = emulator.memory_bus();
+            mbc::battery_file(paths, &memory_bus.rom_title(), memory_bus.rom_checksum())
+        });
+    let mut battery = battery_file.map(|path| BatteryFile::load(&mut emulator, path));
+    if let Some(path) = &resume_file {
+        if options.movie.is_some() {
+            info!("Not resuming, movies start from power on");
+        } else {
+            resume(&mut emulator, path);
+        }
+    }
 
     let emu_buffer = Arc::clone(&buffer);
-    std::thread::spawn(move || {
+    let stats = Arc::new(Mutex::new(Stats::default()));
+    let shared_stats = Arc::clone(&stats);
+    let thread = std::thread::spawn(move || {
         let buffer = emu_buffer;
-        // let file = include_bytes!("../roms/test.gb");
-        // let file = include_bytes!("../roms/hello-world.gb");
-        let file = include_bytes!("../roms/tetris.gb");
-        // let file = include_bytes!("../roms/alu-test.gb");
-        // let file = include_bytes!("../roms/dmg-acid2.gb");
-        // let file = include_bytes!("../roms/cpu_instrs.gb");
-        // let file = include_bytes!("../roms/01-special.gb");
-        // let file = include_bytes!("../roms/02-interrupts.gb");
-        // let file = include_bytes!("../roms/03-op sp,hl.gb");
-        // let file = include_bytes!("../roms/04-op r,imm.gb");
-        // let file = include_bytes!("../roms/05-op rp.gb");
-        // let file = include_bytes!("../roms/06-ld r,r.gb");
-        // let file = include_bytes!("../roms/07-jr,jp,call,ret,rst.gb");
-        // let file = include_bytes!("../roms/08-misc instrs.gb");
-        // let file = include_bytes!("../roms/09-op r,r.gb");
-        // let file = include_bytes!("../roms/10-bit ops.gb");
-        // let file = include_bytes!("../roms/11-op a,(hl).gb");
-
-        let mut memory_bus = MemoryBus::new(file.as_slice());
-        let mut cpu = CPU::default();
-        let mut ppu = PPU::default();
+        let publish = |stats| *shared_stats.lock().unwrap() = stats;
+        let mut meter = StatsMeter::new(Instant::now(), emulator.cycles());
+        let mut movie = ActiveMovie::start(options.movie.as_ref(), &emulator);
+        let mut debugger = Debugger::new(view_sender);
+        if options.paused {
+            // Pausing doesn't run anything, so it can't crash
+            let _ = debugger.apply(DebugCommand::Pause, &mut emulator);
+        }
+        let finish =
+            |movie: Option<ActiveMovie>, battery: Option<BatteryFile>, emulator: &Emulator| {
+                if let Some(active) = movie {
+                    active.finish();
+                }
+                if let Some(mut battery) = battery {
+                    battery.flush(emulator, true);
+                }
+                write_reports(emulator, &options);
+            };
+        // Crashes finish without this, resuming into one isn't useful
+        let quit =
+            |movie: Option<ActiveMovie>, battery: Option<BatteryFile>, emulator: &Emulator| {
+                if let Some(path) = &resume_file {
+                    suspend(emulator, path);
+                }
+                finish(movie, battery, emulator);
+            };
 
         // Thanks to https://github.com/mvdnes/rboy/blob/c6630fa97e55a5595109a37c807038deb7a734fb/src/main.rs#L285
         // 16ms period = 60fps
         let periodic = timer_periodic(16);
+        let mut inactive = false;
+        let mut blend = FrameBlend::default();
+        let mut flash_filter = FlashFilter::default();
+        flash_filter.set_enabled(options.reduce_flashing);
 
         loop {
-            let mut lock = buffer.get_off().lock().unwrap();
-            while !ppu.updated {
-                let ticks = cpu.tick(&mut memory_bus);
-                ppu.tick(&mut memory_bus, &mut *lock, ticks * 4);
+            let frame_done = if debugger.paused() || inactive {
+                // Nothing happens until the debugger says so, commands are the only thing to do
+                let Ok(command) = commands.recv() else {
+                    return quit(movie, battery, &emulator);
+                };
+                publish(meter.pause(Instant::now(), emulator.cycles()));
+                match command {
+                    Command::Debug(command) => match debugger.apply(command, &mut emulator) {
+                        Ok(frame_done) => {
+                            // Show how far the frame has got
+                            buffer.publish(emulator.frame_buffer(), emulator.color_frame());
+                            frame_done
+                        }
+                        Err(crash) => {
+                            let _ = crash_sender.send(crash);
+                            return finish(movie, battery, &emulator);
+                        }
+                    },
+                    Command::Quit => return quit(movie, battery, &emulator),
+                    Command::Reset => {
+                        if reset(&mut emulator, movie.is_some()) {
+                            buffer.publish(emulator.frame_buffer(), emulator.color_frame());
+                        }
+                        false
+                    }
+                    Command::SetInactive(value) => {
+                        inactive = value;
+                        false
+                    }
+                    Command::SetFrameBlend(persistence) => {
+                        blend.set_persistence(persistence);
+                        false
+                    }
+                    Command::SetReduceFlashing(enabled) => {
+                        flash_filter.set_enabled(enabled);
+                        false
+                    }
+                    command => {
+                        apply_command(emulator.memory_bus_mut(), cheat_file.as_deref(), command);
+                        false
+                    }
+                }
+            } else {
+                let frame_done = match debugger.run_frame(&mut emulator) {
+                    Ok(frame_done) => frame_done,
+                    Err(crash) => {
+                        let _ = crash_sender.send(crash);
+                        return finish(movie, battery, &emulator);
+                    }
+                };
+                // Only whole frames get blended or filtered, a half finished one isn't what the
+                // LCD showed. Colors are shown as they are.
+                let frame = if frame_done {
+                    flash_filter.apply(blend.apply(emulator.frame_buffer()))
+                } else {
+                    emulator.frame_buffer()
+                };
+                buffer.publish(frame, emulator.color_frame());
+
+                let mut quitting = false;
+                for command in commands.try_iter() {
+                    quitting |= matches!(command, Command::Quit);
+                    match command {
+                        Command::Debug(command) => {
+                            if let Err(crash) = debugger.apply(command, &mut emulator) {
+                                let _ = crash_sender.send(crash);
+                                return finish(movie, battery, &emulator);
+                            }
+                        }
+                        Command::Reset => {
+                            reset(&mut emulator, movie.is_some());
+                        }
+                        Command::SetInactive(value) => inactive = value,
+                        Command::SetFrameBlend(persistence) => blend.set_persistence(persistence),
+                        Command::SetReduceFlashing(enabled) => flash_filter.set_enabled(enabled),
+                        command => {
+                            apply_command(emulator.memory_bus_mut(), cheat_file.as_deref(), command)
+                        }
+                    }
+                }
+                if quitting {
+                    return quit(movie, battery, &emulator);
+                }
+                if frame_done {
+                    periodic.recv().unwrap();
+                    if let Some(stats) = meter.frame(Instant::now(), emulator.cycles()) {
+                        publish(stats);
+                    }
+                }
+                frame_done
+            };
+            if emulator.memory_bus().apu().scope().is_some() {
+                let _ = audio_sender.send(ApuView::new(emulator.memory_bus()));
+            }
+            let sent = emulator.memory_bus_mut().serial_mut().take_sent();
+            if !sent.is_empty() {
+                let _ = serial_sender.send(sent);
             }
-            ppu.updated = false;
 
-            // Reduce contention by dropping this lock before swap
-            // Contention can still happen if the render thread is rendering when we swap
-            drop(lock);
-            buffer.swap();
-            periodic.recv().unwrap();
+            // Movie input goes last so it always wins over live input
+            if frame_done {
+                if let Some(battery) = battery.as_mut() {
+                    battery.frame(&mut emulator);
+                }
+                if let Some(active) = movie.as_mut() {
+                    if !active.frame(emulator.memory_bus_mut()) {
+                        movie.take().unwrap().finish();
+                    }
+                }
+            }
         }
     });
 
-    buffer
+    EmulatorHandle {
+        buffer,
+        commands: command_sender,
+        thread,
+        crashes,
+        debug_views,
+        audio_views,
+        serial_output,
+        cheats: saved_cheats,
+        stats,
+        rom_problems,
+    }
+}
+
+/// Resets unless a movie is running, it depends on everything happening from power on.
+/// Returns whether it did.
+fn reset(emulator: &mut Emulator, movie_active: bool) -> bool {
+    if movie_active {
+        info!("Not resetting while a movie is recording or playing");
+        return false;
+    }
+    info!("Resetting");
+    emulator.reset();
+    true
+}
+
+/// Runs `frames` frames as fast as possible on this thread, calling `on_frame` with the number
+/// (from 1) and contents of each one, and what the game sent over the link cable during it.
+/// Stops early once `on_frame` returns false. Input only comes from the movie, if there is one.
+/// Returns how long that took, `on_frame` included.
+pub fn run_headless(
+    mut options: Options,
+    frames: u32,
+    mut on_frame: impl FnMut(u32, &ppu::FrameBuffer, &[u8]) -> bool,
+) -> Result<Throughput, Crash> {
+    let (mut emulator, _, _) = power_on(&mut options);
+    let mut movie = ActiveMovie::start(options.movie.as_ref(), &emulator);
+
+    let start = Instant::now();
+    let mut result = Ok(());
+    let mut run = 0;
+    for frame in 1..=frames {
+        if let Err(crash) = emulator.run_frame_catching() {
+            result = Err(crash);
+            break;
+        }
+        run = frame;
+        let sent = emulator.memory_bus_mut().serial_mut().take_sent();
+        if !on_frame(frame, emulator.frame_buffer(), &sent) {
+            break;
+        }
+        if let Some(active) = movie.as_mut() {
+            if !active.frame(emulator.memory_bus_mut()) {
+                movie.take().unwrap().finish();
+            }
+        }
+    }
+    let throughput = Throughput {
+        frames: run as u64,
+        cycles: emulator.cycles(),
+        instructions: emulator.instructions(),
+        elapsed: start.elapsed(),
+    };
+    if let Some(active) = movie {
+        active.finish();
+    }
+    write_reports(&emulator, &options);
+    result.map(|()| throughput)
+}
+
+/// What [`print_headless`] prints besides the last frame's hash
+#[derive(Debug, Default)]
+pub struct HeadlessOutput {
+    /// Print hashes of frames in between too
+    pub hash_every: Option<u32>,
+    /// Print each line the game writes over the link port as it's done, if nothing's plugged
+    /// in to take them
+    pub serial: bool,
+}
+
+/// Runs `frames` frames with [`run_headless`], printing `<frame> <hash>` lines to `out`: for
+/// the frames `output` asks for, the last frame, and the one where a line of serial output
+/// first passes `until`, which stops the run. Serial lines come in between if asked for,
+/// otherwise `out` is only hashes. Returns whether `until` passed any line.
+pub fn print_headless(
+    options: Options,
+    frames: u32,
+    output: HeadlessOutput,
+    until: impl Fn(&str) -> bool,
+    out: &mut impl Write,
+) -> Result<bool, Crash> {
+    let serial = output.serial && options.link.is_none();
+    let mut lines = Lines::default();
+    let mut matched = false;
+    let result = run_headless(options, frames, |frame, buffer, sent| {
+        let finished = lines.push(sent);
+        if serial {
+            for line in &finished {
+                let _ = writeln!(out, "{}", line);
+            }
+        }
+        // The last line might never get a line break
+        matched = finished
+            .iter()
+            .map(String::as_str)
+            .chain([lines.partial()])
+            .any(&until);
+        let due = output.hash_every.is_some_and(|every| frame % every == 0);
+        if due || frame == frames || matched {
+            let _ = writeln!(out, "{} {:08x}", frame, frame_hash(buffer));
+        }
+        !matched
+    });
+    if let Some(line) = lines.finish().filter(|_| serial) {
+        let _ = writeln!(out, "{}", line);
+    }
+    result.map(|_| matched)
+}
+
+/// Loads the state saved by [`suspend`], if there is one
+fn resume(emulator: &mut Emulator, path: &Path) {
+    match std::fs::read(path) {
+        Ok(bytes) => match emulator.load_state(&bytes) {
+            Ok(()) => info!("Resumed from {:?}", path),
+            Err(e) => warn!("Not resuming from {:?}: {}", path, e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => error!("Failed to read {:?}: {}", path, e),
+    }
+}
+
+fn suspend(emulator: &Emulator, path: &Path) {
+    match save_file::write_atomic(path, &emulator.save_state()) {
+        Ok(()) => info!("Saved state for resuming to {:?}", path),
+        Err(e) => error!("Failed to save state to {:?}: {}", path, e),
+    }
+}
+
+/// Frames between checks for a changed battery save, in case the game never disables RAM
+const BATTERY_FLUSH_FRAMES: u32 = 60 * 60;
+
+/// A game's battery save on disk. It's written when the emulator stops, when the game disables
+/// cartridge RAM, which games do once they're done saving, and every [`BATTERY_FLUSH_FRAMES`]
+/// otherwise. Only if it changed, each write pushes the backups along.
+struct BatteryFile {
+    path: PathBuf,
+    /// What the file has, or had when it was loaded
+    saved: Option<Vec<u8>>,
+    frames: u32,
+}
+
+impl BatteryFile {
+    /// Loads the save, or the newest backup that fits the cartridge if it's missing or doesn't.
+    /// An RTC footer is optional, other emulators don't all write one.
+    fn load(emulator: &mut Emulator, path: PathBuf) -> Self {
+        let battery = emulator.memory_bus().battery().unwrap_or_default();
+        let size = rtc::split_sav(&battery).0.len();
+        match save_file::read_with_backups(&path, |bytes| rtc::split_sav(bytes).0.len() == size) {
+            Ok(Some(bytes)) => match emulator.memory_bus_mut().load_battery(&bytes) {
+                Ok(()) => info!("Loaded battery save from {:?}", path),
+                Err(e) => warn!("Not loading battery save from {:?}: {}", path, e),
+            },
+            Ok(None) if path.exists() => {
+                warn!(
+                    "Not loading battery save from {:?}, it and its backups don't fit",
+                    path
+                )
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to read {:?}: {}", path, e),
+        }
+        Self {
+            saved: emulator.memory_bus().battery(),
+            path,
+            frames: 0,
+        }
+    }
+
+    /// Called after every frame
+    fn frame(&mut self, emulator: &mut Emulator) {
+        self.frames += 1;
+        if emulator.memory_bus_mut().take_ram_disabled() || self.frames >= BATTERY_FLUSH_FRAMES {
+            self.flush(emulator, false);
+        }
+    }
+
+    /// Writes the save if it changed. A running clock changes it every second, so unless
+    /// `with_clock` only RAM counts. The footer gets the clock right from any save anyway.
+    fn flush(&mut self, emulator: &Emulator, with_clock: bool) {
+        self.frames = 0;
+        let Some(bytes) = emulator.memory_bus().battery() else {
+            return;
+        };
+        let unchanged = self.saved.as_deref().is_some_and(|saved| {
+            if with_clock {
+                saved == bytes
+            } else {
+                rtc::split_sav(saved).0 == rtc::split_sav(&bytes).0
+            }
+        });
+        if unchanged {
+            return;
+        }
+        match save_file::write_atomic(&self.path, &bytes) {
+            Ok(()) => {
+                info!("Saved battery save to {:?}", self.path);
+                self.saved = Some(bytes);
+            }
+            Err(e) => error!("Failed to save battery save to {:?}: {}", self.path, e),
+        }
+    }
+}
+
+/// Writes the coverage and access reports that were asked for
+fn write_reports(emulator: &Emulator, options: &Options) {
+    let write = |name, path: &Path, report: String| match std::fs::write(path, report) {
+        Ok(()) => info!("Wrote {} to {:?}", name, path),
+        Err(e) => error!("Failed to write {} to {:?}: {}", name, path, e),
+    };
+    if let (Some(coverage), Some(path)) = (emulator.coverage(), &options.coverage) {
+        let report = coverage.report(emulator.memory_bus().rom(), emulator.symbols());
+        write("coverage report", path, report);
+    }
+    if let (Some(stats), Some(path)) = (emulator.memory_bus().access_stats(), &options.access_stats)
+    {
+        write("access stats", path, stats.report(emulator.symbols()));
+    }
+}
+
+/// CRC-32 of the frame, stable across builds so it can be compared between runs
+pub fn frame_hash(frame: &ppu::FrameBuffer) -> u32 {
+    png::crc32(&[&frame[..]])
 }
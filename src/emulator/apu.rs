@@ -0,0 +1,550 @@
+//! Sound, which isn't played yet
+//!
+//! Every register at 0xFF10-0xFF3F can be written and read back, the bus sets the bits that
+//! always read as 1. None of the channels make sound, but everything games can see of them
+//! runs: which are on in NR52, and where the [wave channel](wave) is through wave RAM. The
+//! duty cycles and the noise channel's LFSR run as well, so [`Apu::output`] has what each
+//! channel would be playing, and a [`Scope`] can record it for the debugger.
+//!
+//! Clearing NR52 bit 7 powers the APU off, which clears every register up to NR51 and turns
+//! the channels off. Until it's powered on again only NR52 and wave RAM can be written, except
+//! that a DMG still loads length counters from NRx1. Color models clear the length counters
+//! when powering off as well. Powering on starts the frame sequencer over from step 0.
+//!
+//! The frame sequencer steps each time bit 4 of DIV falls, which is 512 times a second unless
+//! the game writes DIV. That can make a step come early, since resetting the divider drops the
+//! bit if it was set. Length counters are clocked on even steps, channel 1's sweep on steps 2
+//! and 6 and envelopes on step 7. Double speed mode, which would move it to bit 5, isn't
+//! emulated, and neither is STOP resetting the divider.
+use std::ops::RangeInclusive;
+
+use bit_field::BitField;
+use tracing::trace;
+
+use crate::emulator::{
+    hardware::HardwareModel,
+    memory_bus::{mmio::MmioDevice, Interrupt},
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub mod envelope;
+pub mod length;
+pub mod scope;
+pub mod sweep;
+pub mod view;
+pub mod wave;
+
+use envelope::Envelope;
+use length::Length;
+use scope::Scope;
+use sweep::{Sweep, Swept};
+use wave::Wave;
+
+pub const NR10: u16 = 0xFF10;
+pub const NR11: u16 = 0xFF11;
+pub const NR12: u16 = 0xFF12;
+pub const NR13: u16 = 0xFF13;
+pub const NR14: u16 = 0xFF14;
+pub const NR21: u16 = 0xFF16;
+pub const NR30: u16 = 0xFF1A;
+pub const NR31: u16 = 0xFF1B;
+pub const NR32: u16 = 0xFF1C;
+pub const NR33: u16 = 0xFF1D;
+pub const NR34: u16 = 0xFF1E;
+pub const NR41: u16 = 0xFF20;
+pub const NR43: u16 = 0xFF22;
+pub const NR50: u16 = 0xFF24;
+pub const NR51: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM: RangeInclusive<u16> = 0xFF30..=0xFF3F;
+
+const FIRST_REGISTER: u16 = 0xFF10;
+/// NR10 to NR52
+const REGISTERS: usize = 0x17;
+/// Which of the 8 steps of each duty cycle in NRx1 are high, step 0 in bit 0
+const DUTY_CYCLES: [u8; 4] = [0b1000_0000, 0b1000_0001, 0b1110_0001, 0b0111_1110];
+/// What the noise channel's LFSR starts from on a trigger
+const LFSR_START: u16 = 0x7FFF;
+
+/// The pulse channels, 1 and 2, or the noise channel, 4, as far as anything but the sound goes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Channel {
+    on: bool,
+    /// NRx2 has anything in its top 5 bits
+    dac: bool,
+    envelope: Envelope,
+    /// T-cycles until the next step of the duty cycle or LFSR
+    timer: u32,
+    /// Which step of the duty cycle a pulse channel is on, 0-7, or the noise channel's LFSR
+    phase: u16,
+}
+
+impl Channel {
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// Turning the DAC off turns the channel off too
+    fn set_dac(&mut self, nrx2: u8) {
+        self.dac = nrx2 & 0xF8 != 0;
+        self.on &= self.dac;
+    }
+
+    fn trigger(&mut self, nrx2: u8, period: u32) {
+        self.on = self.dac;
+        self.envelope.trigger(nrx2);
+        self.timer = period;
+    }
+
+    /// Runs for `cycles` T-cycles with a step every `period`, returning how many steps it took
+    fn steps(&mut self, cycles: u32, period: u32) -> u32 {
+        if cycles < self.timer {
+            self.timer -= cycles;
+            return 0;
+        }
+        let after_first = cycles - self.timer;
+        self.timer = period - after_first % period;
+        1 + after_first / period
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.on);
+        state.bool(self.dac);
+        self.envelope.save_state(state);
+        state.u32(self.timer);
+        state.u16(self.phase);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.on = state.bool()?;
+        self.dac = state.bool()?;
+        self.envelope.load_state(state)?;
+        self.timer = state.u32()?;
+        self.phase = state.u16()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Apu {
+    /// As last written, NR10 to NR52
+    registers: [u8; REGISTERS],
+    pulse: [Channel; 2],
+    sweep: Sweep,
+    wave: Wave,
+    noise: Channel,
+    /// One for each channel
+    lengths: [Length; 4],
+    /// The next frame sequencer step, 0-7
+    step: u8,
+    /// See [`Apu::set_model`]
+    color: bool,
+    /// See [`Apu::set_scope`]
+    scope: Option<Box<Scope>>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            registers: [0; REGISTERS],
+            pulse: Default::default(),
+            sweep: Sweep::default(),
+            wave: Wave::default(),
+            noise: Channel::default(),
+            lengths: Self::LENGTHS,
+            step: 0,
+            color: false,
+            scope: None,
+        }
+    }
+}
+
+impl Apu {
+    const LENGTHS: [Length; 4] = [
+        Length::new(64),
+        Length::new(64),
+        Length::new(256),
+        Length::new(64),
+    ];
+
+    /// Color models leave wave RAM open while the wave channel plays, see [`wave`], and power
+    /// off differently
+    pub fn set_model(&mut self, model: HardwareModel) {
+        self.color = model.is_color();
+        self.wave.set_color(self.color);
+    }
+
+    /// Powers on and plays the two notes of the boot sound like the boot ROM, leaving channel 1
+    /// on. The SGB's doesn't make a sound.
+    pub fn boot(&mut self, model: HardwareModel) {
+        self.write(NR52, 0x80);
+        if matches!(model, HardwareModel::Sgb | HardwareModel::Sgb2) {
+            return;
+        }
+        for (addr, byte) in [
+            (NR11, 0x80),
+            (NR12, 0xF3),
+            (NR51, 0xF3),
+            (NR50, 0x77),
+            (NR13, 0x83),
+            (NR14, 0x87),
+            (NR13, 0xC1),
+            (NR14, 0x87),
+        ] {
+            self.write(addr, byte);
+        }
+    }
+
+    /// NR52 bit 7
+    pub fn powered(&self) -> bool {
+        self.register(NR52).get_bit(7)
+    }
+
+    /// Channel 1 or 2
+    pub fn pulse(&self, channel: usize) -> &Channel {
+        &self.pulse[channel - 1]
+    }
+
+    pub fn wave(&self) -> &Wave {
+        &self.wave
+    }
+
+    pub fn noise(&self) -> &Channel {
+        &self.noise
+    }
+
+    /// Channel 1 to 4's length counter
+    pub fn length(&self, channel: usize) -> &Length {
+        &self.lengths[channel - 1]
+    }
+
+    /// Records what the channels put out for [`Apu::scope`] while enabled, which is only
+    /// worth the time with something showing it
+    pub fn set_scope(&mut self, enabled: bool) {
+        if enabled != self.scope.is_some() {
+            self.scope = enabled.then(Box::default);
+        }
+    }
+
+    pub fn scope(&self) -> Option<&Scope> {
+        self.scope.as_deref()
+    }
+
+    /// What channel 1 to 4 is feeding its DAC, 0-15. Channels that are off give 0.
+    pub fn output(&self, channel: usize) -> u8 {
+        let index = channel - 1;
+        if !self.on(index) {
+            return 0;
+        }
+        match index {
+            0 | 1 => {
+                let pulse = &self.pulse[index];
+                let duty = DUTY_CYCLES[self.registers[index * 5 + 1] as usize >> 6];
+                if duty.get_bit(pulse.phase as usize) {
+                    pulse.envelope.volume()
+                } else {
+                    0
+                }
+            }
+            2 => self.wave.output() >> self.wave_shift(),
+            _ => {
+                if self.noise.phase.get_bit(0) {
+                    0
+                } else {
+                    self.noise.envelope.volume()
+                }
+            }
+        }
+    }
+
+    /// How far NR32 shifts the wave channel's samples right, 4 for silence
+    pub fn wave_shift(&self) -> u8 {
+        match self.register(NR32).get_bits(5..7) {
+            0 => 4,
+            volume => volume - 1,
+        }
+    }
+
+    /// T-cycles between steps of channel 1 or 2's duty cycle
+    fn pulse_period(&self, index: usize) -> u32 {
+        (2048 - self.frequency_of(index) as u32) * 4
+    }
+
+    /// T-cycles between steps of the noise channel's LFSR, which doesn't step at all with a
+    /// shift of 14 or 15
+    pub fn noise_period(&self) -> u32 {
+        let nr43 = self.register(NR43);
+        let divisor = match nr43 & 0b111 {
+            0 => 8,
+            divisor => divisor as u32 * 16,
+        };
+        divisor << (nr43 >> 4)
+    }
+
+    /// 0-3 for channels 1 to 4
+    fn on(&self, index: usize) -> bool {
+        match index {
+            0 | 1 => self.pulse[index].on,
+            2 => self.wave.on(),
+            _ => self.noise.on,
+        }
+    }
+
+    fn turn_off(&mut self, index: usize) {
+        match index {
+            0 | 1 => self.pulse[index].on = false,
+            2 => self.wave.stop(),
+            _ => self.noise.on = false,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            NR52 => {
+                let mut status = self.register(NR52);
+                for index in 0..4 {
+                    status.set_bit(index, self.on(index));
+                }
+                status
+            }
+            0xFF10..=0xFF25 => self.register(addr),
+            0xFF27..=0xFF2F => 0xFF,
+            0xFF30..=0xFF3F => self.wave.read_ram(addr - WAVE_RAM.start()),
+            _ => unreachable!("APU read @{:#X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        trace!(target: "apu", "APU write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            NR52 => self.set_power(byte.get_bit(7)),
+            0xFF10..=0xFF25 if !self.powered() => {
+                if !self.color {
+                    match addr {
+                        NR11 | NR21 | NR41 => {
+                            self.lengths[(addr - NR11) as usize / 5].load(byte & 0x3F)
+                        }
+                        NR31 => self.lengths[2].load(byte),
+                        _ => {}
+                    }
+                }
+            }
+            0xFF10..=0xFF25 => {
+                self.registers[(addr - FIRST_REGISTER) as usize] = byte;
+                if addr < 0xFF24 {
+                    let offset = addr - FIRST_REGISTER;
+                    self.write_channel(offset as usize / 5, offset % 5, byte);
+                }
+            }
+            0xFF27..=0xFF2F => {}
+            0xFF30..=0xFF3F => self.wave.write_ram(addr - WAVE_RAM.start(), byte),
+            _ => unreachable!("APU write @{:#X}", addr),
+        }
+    }
+
+    /// A write to NRxy of channel `index + 1`
+    fn write_channel(&mut self, index: usize, y: u16, byte: u8) {
+        match (index, y) {
+            (0, 0) => {
+                if !self.sweep.write(byte) {
+                    self.pulse[0].on = false;
+                }
+            }
+            (2, 0) => self.wave.set_dac(byte.get_bit(7)),
+            (2, 1) => self.lengths[2].load(byte),
+            (_, 1) => self.lengths[index].load(byte & 0x3F),
+            (0 | 1, 2) => self.pulse[index].set_dac(byte),
+            (3, 2) => self.noise.set_dac(byte),
+            (2, 3 | 4) => self.wave.set_frequency(self.frequency_of(2)),
+            (_, 4) => {}
+            _ => return,
+        }
+        if y == 4 {
+            let trigger = byte.get_bit(7);
+            let next_step_clocks = self.step.is_multiple_of(2);
+            if !self.lengths[index].write_control(byte.get_bit(6), trigger, next_step_clocks) {
+                self.turn_off(index);
+            }
+            if trigger {
+                self.trigger(index);
+            }
+        }
+    }
+
+    /// The 11-bit frequency of channel 1, 2 or 3
+    pub fn frequency(&self, channel: usize) -> u16 {
+        self.frequency_of(channel - 1)
+    }
+
+    /// The 11-bit frequency in NRx3 and NRx4 of channel `index + 1`
+    fn frequency_of(&self, index: usize) -> u16 {
+        let nrx3 = FIRST_REGISTER + index as u16 * 5 + 3;
+        (self.register(nrx3 + 1) as u16 & 0b111) << 8 | self.register(nrx3) as u16
+    }
+
+    fn trigger(&mut self, index: usize) {
+        let nrx2 = self.registers[index * 5 + 2];
+        match index {
+            0 => {
+                let period = self.pulse_period(0);
+                self.pulse[0].trigger(nrx2, period);
+                if !self
+                    .sweep
+                    .trigger(self.register(NR10), self.frequency_of(0))
+                {
+                    self.pulse[0].on = false;
+                }
+            }
+            1 => {
+                let period = self.pulse_period(1);
+                self.pulse[1].trigger(nrx2, period);
+            }
+            2 => self.wave.trigger(),
+            _ => {
+                let period = self.noise_period();
+                self.noise.trigger(nrx2, period);
+                self.noise.phase = LFSR_START;
+            }
+        }
+    }
+
+    fn set_power(&mut self, on: bool) {
+        if self.powered() && !on {
+            let lengths = if self.color {
+                Self::LENGTHS
+            } else {
+                self.lengths
+            };
+            let scope = self.scope.take();
+            *self = Self {
+                wave: self.wave.powered_off(),
+                lengths,
+                color: self.color,
+                scope,
+                ..Self::default()
+            };
+        } else if !self.powered() && on {
+            self.step = 0;
+        }
+        self.registers[(NR52 - FIRST_REGISTER) as usize] = if on { 0x80 } else { 0 };
+    }
+
+    /// Bit 4 of DIV fell
+    pub fn step_frame_sequencer(&mut self) {
+        if !self.powered() {
+            return;
+        }
+        if self.step.is_multiple_of(2) {
+            for index in 0..4 {
+                if !self.lengths[index].clock() {
+                    self.turn_off(index);
+                }
+            }
+        }
+        if self.step == 2 || self.step == 6 {
+            match self.sweep.clock(self.register(NR10)) {
+                Swept::Nothing => {}
+                Swept::Frequency(frequency) => {
+                    self.registers[(NR13 - FIRST_REGISTER) as usize] = frequency as u8;
+                    let nr14 = &mut self.registers[(NR14 - FIRST_REGISTER) as usize];
+                    nr14.set_bits(0..3, (frequency >> 8) as u8);
+                }
+                Swept::Overflow => self.pulse[0].on = false,
+            }
+        }
+        if self.step == 7 {
+            for channel in self.pulse.iter_mut().chain([&mut self.noise]) {
+                channel.envelope.clock();
+            }
+        }
+        self.step = (self.step + 1) % 8;
+    }
+
+    /// Steps the pulse channels through their duty cycles and the noise channel's LFSR
+    fn tick_channels(&mut self, cycles: u32) {
+        for index in 0..2 {
+            if self.pulse[index].on {
+                let period = self.pulse_period(index);
+                let pulse = &mut self.pulse[index];
+                let steps = pulse.steps(cycles, period);
+                pulse.phase = ((pulse.phase as u32 + steps) % 8) as u16;
+            }
+        }
+        let nr43 = self.register(NR43);
+        if self.noise.on && nr43 >> 4 < 14 {
+            let period = self.noise_period();
+            for _ in 0..self.noise.steps(cycles, period) {
+                let lfsr = &mut self.noise.phase;
+                let bit = (*lfsr ^ *lfsr >> 1) & 1;
+                *lfsr = *lfsr >> 1 | bit << 14;
+                if nr43.get_bit(3) {
+                    lfsr.set_bit(6, bit == 1);
+                }
+            }
+        }
+    }
+
+    fn register(&self, addr: u16) -> u8 {
+        self.registers[(addr - FIRST_REGISTER) as usize]
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.registers);
+        for channel in &self.pulse {
+            channel.save_state(state);
+        }
+        self.sweep.save_state(state);
+        self.wave.save_state(state);
+        self.noise.save_state(state);
+        for length in &self.lengths {
+            length.save_state(state);
+        }
+        state.u8(self.step);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.registers.copy_from_slice(state.bytes(REGISTERS)?);
+        for channel in &mut self.pulse {
+            channel.load_state(state)?;
+        }
+        self.sweep.load_state(state)?;
+        self.wave.load_state(state)?;
+        self.noise.load_state(state)?;
+        for length in &mut self.lengths {
+            length.load_state(state)?;
+        }
+        self.step = state.u8()? % 8;
+        Ok(())
+    }
+}
+
+impl MmioDevice for Apu {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[0xFF10..=0xFF3F]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        Apu::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        Apu::write(self, addr, byte);
+        None
+    }
+
+    fn tick(&mut self, cycles: u32) -> Option<Interrupt> {
+        self.wave.tick(cycles);
+        self.tick_channels(cycles);
+        if self.scope.is_some() {
+            let outputs = [1, 2, 3, 4].map(|channel| self.output(channel));
+            if let Some(scope) = self.scope.as_mut() {
+                scope.record(cycles, outputs);
+            }
+        }
+        None
+    }
+}
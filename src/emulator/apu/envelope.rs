@@ -0,0 +1,72 @@
+//! Volume envelopes of the pulse and noise channels
+//!
+//! NRx2 has the starting volume in the top nibble, then the direction (bit 3, set for up) and
+//! how many envelope steps of the [frame sequencer](super) go by per change of volume. They
+//! only take effect on a trigger. A period of 0 leaves the volume where it starts.
+use bit_field::BitField;
+
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Envelope {
+    volume: u8,
+    up: bool,
+    period: u8,
+    /// Steps until the next change
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Getting louder rather than quieter
+    pub fn up(&self) -> bool {
+        self.up
+    }
+
+    /// Envelope steps per change of volume, 0 for none
+    pub fn period(&self) -> u8 {
+        self.period
+    }
+
+    pub fn trigger(&mut self, nrx2: u8) {
+        self.volume = nrx2 >> 4;
+        self.up = nrx2.get_bit(3);
+        self.period = nrx2 & 0b111;
+        self.timer = self.period;
+    }
+
+    /// A frame sequencer step that clocks envelopes
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period;
+        if self.up && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.up && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.volume);
+        state.bool(self.up);
+        state.u8(self.period);
+        state.u8(self.timer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.volume = state.u8()? & 0x0F;
+        self.up = state.bool()?;
+        self.period = state.u8()? & 0b111;
+        self.timer = state.u8()?.min(7);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Length counters, which turn a channel off after a set time
+//!
+//! NRx1 loads how many [frame sequencer](super) steps that clock length are left, counted down
+//! only while NRx4 bit 6 is set. Enabling the counter, or triggering with it enabled, while the
+//! next step won't clock length counts one straight away.
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Length {
+    /// 64, or 256 for the wave channel
+    max: u16,
+    remaining: u16,
+    enabled: bool,
+}
+
+impl Length {
+    pub const fn new(max: u16) -> Self {
+        Self {
+            max,
+            remaining: 0,
+            enabled: false,
+        }
+    }
+
+    pub fn remaining(&self) -> u16 {
+        self.remaining
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// An NRx1 write, of just the length bits
+    pub fn load(&mut self, value: u8) {
+        self.remaining = self.max - value as u16;
+    }
+
+    /// An NRx4 write. Returns false if the extra clock ran the counter out, which turns the
+    /// channel off unless it's also being triggered.
+    pub fn write_control(&mut self, enable: bool, trigger: bool, next_step_clocks: bool) -> bool {
+        let extra_clock = enable && !self.enabled && !next_step_clocks;
+        self.enabled = enable;
+        let mut running = true;
+        if extra_clock && self.remaining > 0 {
+            self.remaining -= 1;
+            running = self.remaining > 0 || trigger;
+        }
+        if trigger && self.remaining == 0 {
+            self.remaining = self.max;
+            if enable && !next_step_clocks {
+                self.remaining -= 1;
+            }
+        }
+        running
+    }
+
+    /// A frame sequencer step that clocks length. Returns false when the counter runs out.
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.remaining == 0 {
+            return true;
+        }
+        self.remaining -= 1;
+        self.remaining > 0
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.remaining);
+        state.bool(self.enabled);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.remaining = state.u16()?.min(self.max);
+        self.enabled = state.bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+//! What the channels have been putting out lately, for showing as waveforms
+//!
+//! Every [`SAMPLE_CYCLES`] the [output](super::Apu::output) of each channel is recorded, keeping
+//! the last [`SAMPLES`]. The APU only ticks at the end of each instruction, so samples taken
+//! during a long one, or while the CPU is halted, all see where the channels ended up.
+use std::collections::VecDeque;
+
+/// T-cycles between samples, 64 kHz
+pub const SAMPLE_CYCLES: u32 = 64;
+/// How many it keeps, about 8ms worth
+pub const SAMPLES: usize = 512;
+
+#[derive(Clone, Debug)]
+pub struct Scope {
+    /// Oldest first
+    channels: [VecDeque<u8>; 4],
+    /// T-cycles since the last sample
+    elapsed: u32,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            channels: std::array::from_fn(|_| VecDeque::from(vec![0; SAMPLES])),
+            elapsed: 0,
+        }
+    }
+}
+
+impl Scope {
+    /// Channel 1 to 4's last [`SAMPLES`] output levels, oldest first
+    pub fn samples(&self, channel: usize) -> impl Iterator<Item = u8> + '_ {
+        self.channels[channel - 1].iter().copied()
+    }
+
+    /// The channels put out `outputs` at the end of `cycles` T-cycles
+    pub fn record(&mut self, cycles: u32, outputs: [u8; 4]) {
+        self.elapsed += cycles;
+        // More than a whole buffer's worth only has the same samples over again
+        let count = (self.elapsed / SAMPLE_CYCLES).min(SAMPLES as u32);
+        self.elapsed %= SAMPLE_CYCLES;
+        for (samples, output) in self.channels.iter_mut().zip(outputs) {
+            for _ in 0..count {
+                samples.pop_front();
+                samples.push_back(output);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Channel 1's frequency sweep
+//!
+//! NR10 has the sweep period in bits 4-6, the direction in bit 3 (set to go down) and a shift
+//! in bits 0-2. Each time the period runs out the frequency changes by itself shifted right by
+//! the shift, on a copy taken at the trigger. Going past 2047 turns the channel off, which is
+//! checked once more with the new frequency straight after a change, and on a trigger if the
+//! shift isn't 0. Clearing the direction bit after a calculation went down since the trigger
+//! turns the channel off too.
+use bit_field::BitField;
+
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+/// What a step of the sweep did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Swept {
+    Nothing,
+    /// Frequency for NR13 and NR14
+    Frequency(u16),
+    Overflow,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sweep {
+    /// The frequency the sweep works on
+    shadow: u16,
+    /// Steps until the next change
+    timer: u8,
+    enabled: bool,
+    /// A calculation went down since the trigger
+    negated: bool,
+}
+
+fn period(nr10: u8) -> u8 {
+    nr10.get_bits(4..7)
+}
+
+fn shift(nr10: u8) -> u8 {
+    nr10 & 0b111
+}
+
+impl Sweep {
+    /// Returns false if the first calculation overflows
+    pub fn trigger(&mut self, nr10: u8, frequency: u16) -> bool {
+        self.shadow = frequency;
+        self.reload(nr10);
+        self.enabled = period(nr10) != 0 || shift(nr10) != 0;
+        self.negated = false;
+        shift(nr10) == 0 || self.calculate(nr10) <= 2047
+    }
+
+    /// Returns false if the write turns the channel off
+    pub fn write(&mut self, nr10: u8) -> bool {
+        !self.negated || nr10.get_bit(3)
+    }
+
+    /// A frame sequencer step that clocks the sweep
+    pub fn clock(&mut self, nr10: u8) -> Swept {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return Swept::Nothing;
+        }
+        self.reload(nr10);
+        if !self.enabled || period(nr10) == 0 {
+            return Swept::Nothing;
+        }
+        let frequency = self.calculate(nr10);
+        if frequency > 2047 {
+            return Swept::Overflow;
+        }
+        if shift(nr10) == 0 {
+            return Swept::Nothing;
+        }
+        self.shadow = frequency;
+        if self.calculate(nr10) > 2047 {
+            return Swept::Overflow;
+        }
+        Swept::Frequency(frequency)
+    }
+
+    /// A period of 0 still counts down from 8
+    fn reload(&mut self, nr10: u8) {
+        self.timer = match period(nr10) {
+            0 => 8,
+            period => period,
+        };
+    }
+
+    fn calculate(&mut self, nr10: u8) -> u16 {
+        let delta = self.shadow >> shift(nr10);
+        if nr10.get_bit(3) {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.shadow);
+        state.u8(self.timer);
+        state.bool(self.enabled);
+        state.bool(self.negated);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.shadow = state.u16()? & 0x7FF;
+        self.timer = state.u8()?.min(8);
+        self.enabled = state.bool()?;
+        self.negated = state.bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! What the audio panel shows of the APU
+use crate::emulator::{
+    apu::{envelope::Envelope, length::Length, Apu, FIRST_REGISTER, NR52, REGISTERS},
+    memory_bus::MemoryBus,
+    stats::CLOCK_HZ,
+};
+
+#[derive(Clone, Debug)]
+pub struct ChannelView {
+    pub on: bool,
+    /// What it's playing at, a whole cycle of the duty cycle or wave RAM, or the rate the noise
+    /// channel's LFSR steps at
+    pub hz: f64,
+    /// 0-15, for the wave channel how loud NR32 lets a sample of 15 through
+    pub volume: u8,
+    /// `None` for the wave channel, which hasn't got one
+    pub envelope: Option<Envelope>,
+    pub length: Length,
+    /// From the [scope](super::scope), empty without one
+    pub samples: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ApuView {
+    pub powered: bool,
+    pub channels: [ChannelView; 4],
+    /// NR10 to NR52 as the CPU reads them
+    pub registers: [u8; REGISTERS],
+    /// All of it, whether or not the wave channel lets the CPU at it
+    pub wave_ram: [u8; 16],
+}
+
+impl ApuView {
+    pub fn new(bus: &MemoryBus) -> Self {
+        let apu = bus.apu();
+        let mut registers = [0; REGISTERS];
+        for (addr, register) in (FIRST_REGISTER..=NR52).zip(&mut registers) {
+            *register = bus.peek(addr);
+        }
+        Self {
+            powered: apu.powered(),
+            channels: [1, 2, 3, 4].map(|channel| channel_view(apu, channel)),
+            registers,
+            wave_ram: *apu.wave().ram(),
+        }
+    }
+}
+
+fn channel_view(apu: &Apu, channel: usize) -> ChannelView {
+    let (on, envelope) = match channel {
+        1 | 2 => (
+            apu.pulse(channel).on(),
+            Some(*apu.pulse(channel).envelope()),
+        ),
+        3 => (apu.wave().on(), None),
+        _ => (apu.noise().on(), Some(*apu.noise().envelope())),
+    };
+    let hz = match channel {
+        1 | 2 => CLOCK_HZ / (apu.pulse_period(channel - 1) * 8) as f64,
+        3 => CLOCK_HZ / ((2048 - apu.frequency(3) as u32) * 64) as f64,
+        _ => CLOCK_HZ / apu.noise_period() as f64,
+    };
+    ChannelView {
+        on,
+        hz,
+        volume: envelope.map_or(15 >> apu.wave_shift(), |envelope| envelope.volume()),
+        envelope,
+        length: *apu.length(channel),
+        samples: apu
+            .scope()
+            .map(|scope| scope.samples(channel).collect())
+            .unwrap_or_default(),
+    }
+}
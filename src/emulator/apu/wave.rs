@@ -0,0 +1,170 @@
+//! The wave channel, channel 3
+//!
+//! It plays the 32 4-bit samples in wave RAM, high nibble first, stepping to the next one every
+//! `(2048 - frequency) * 2` T-cycles. Each step reads the byte the sample is in, and a trigger
+//! starts over from sample 0 without reading, so the first sample played is whatever was read
+//! last.
+//!
+//! While the channel is on the CPU can't pick which byte of wave RAM it gets. Every access goes
+//! to the byte the channel is playing instead, and on a DMG only if the channel is reading it
+//! at that moment: otherwise reads give 0xFF and writes are lost. Color models let every access
+//! through to the playing byte. The moment is as of the last [`Wave::tick`], which is the end
+//! of the last instruction.
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+/// How long after reading a byte a DMG still lets the CPU at it, one tick of the APU's 2MHz
+/// clock
+const READ_WINDOW: u32 = 2;
+/// T-cycles a trigger adds before the first step
+const TRIGGER_DELAY: u32 = 6;
+
+#[derive(Debug, Default)]
+pub struct Wave {
+    ram: [u8; 16],
+    /// Playing, turned on by a trigger with the DAC on
+    on: bool,
+    /// NR30 bit 7
+    dac: bool,
+    /// 11 bits from NR33 and NR34
+    frequency: u16,
+    /// T-cycles until the next step
+    timer: u32,
+    /// Which sample is playing, 0-31
+    position: u8,
+    /// The byte of wave RAM last read
+    sample: u8,
+    /// T-cycles since it was read
+    since_read: u32,
+    /// See [`Wave::set_color`]
+    color: bool,
+}
+
+impl Wave {
+    /// Whether wave RAM can be accessed whenever the channel is on, like on color models
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    pub fn frequency(&self) -> u16 {
+        self.frequency
+    }
+
+    pub fn position(&self) -> u8 {
+        self.position
+    }
+
+    /// The 4-bit sample playing
+    pub fn output(&self) -> u8 {
+        if self.position.is_multiple_of(2) {
+            self.sample >> 4
+        } else {
+            self.sample & 0x0F
+        }
+    }
+
+    pub fn ram(&self) -> &[u8; 16] {
+        &self.ram
+    }
+
+    /// T-cycles between steps
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    pub fn set_frequency(&mut self, frequency: u16) {
+        self.frequency = frequency & 0x7FF;
+    }
+
+    /// Turning the DAC off turns the channel off too
+    pub fn set_dac(&mut self, dac: bool) {
+        self.dac = dac;
+        self.on &= dac;
+    }
+
+    /// What's left after the APU powers off, only wave RAM
+    pub fn powered_off(&self) -> Self {
+        Self {
+            ram: self.ram,
+            color: self.color,
+            ..Self::default()
+        }
+    }
+
+    /// Its length counter ran out
+    pub fn stop(&mut self) {
+        self.on = false;
+    }
+
+    pub fn trigger(&mut self) {
+        self.on = self.dac;
+        self.position = 0;
+        self.timer = self.period() + TRIGGER_DELAY;
+        self.since_read = READ_WINDOW;
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if !self.on {
+            return;
+        }
+        if cycles < self.timer {
+            self.timer -= cycles;
+            self.since_read = self.since_read.saturating_add(cycles);
+            return;
+        }
+        let period = self.period();
+        let after_first = cycles - self.timer;
+        let steps = 1 + after_first / period;
+        self.position = ((self.position as u32 + steps) % 32) as u8;
+        self.sample = self.ram[self.position as usize / 2];
+        self.since_read = after_first % period;
+        self.timer = period - self.since_read;
+    }
+
+    /// Which byte of wave RAM an access to `offset` goes to, `None` if it's shut out
+    fn ram_index(&self, offset: u16) -> Option<usize> {
+        if !self.on {
+            Some(offset as usize)
+        } else if self.color || self.since_read < READ_WINDOW {
+            Some(self.position as usize / 2)
+        } else {
+            None
+        }
+    }
+
+    pub fn read_ram(&self, offset: u16) -> u8 {
+        self.ram_index(offset).map_or(0xFF, |index| self.ram[index])
+    }
+
+    pub fn write_ram(&mut self, offset: u16, byte: u8) {
+        if let Some(index) = self.ram_index(offset) {
+            self.ram[index] = byte;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.ram);
+        state.bool(self.on);
+        state.bool(self.dac);
+        state.u16(self.frequency);
+        state.u32(self.timer);
+        state.u8(self.position);
+        state.u8(self.sample);
+        state.u32(self.since_read);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.ram.copy_from_slice(state.bytes(16)?);
+        self.on = state.bool()?;
+        self.dac = state.bool()?;
+        self.frequency = state.u16()? & 0x7FF;
+        self.timer = state.u32()?;
+        self.position = state.u8()? % 32;
+        self.sample = state.u8()?;
+        self.since_read = state.u32()?;
+        Ok(())
+    }
+}
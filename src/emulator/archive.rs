@@ -0,0 +1,266 @@
+//! Zipped and gzipped ROMs
+//!
+//! Most ROM collections are kept compressed. [`extract`] takes a file's contents and gives back
+//! the ROM inside, going by the magic number at the start and the file's extension if that's
+//! not there. A ZIP file has to have exactly one ROM in it, stored or deflated. Anything that
+//! isn't an archive is taken to be a ROM already.
+use std::{fmt, path::Path};
+
+use crate::emulator::png::crc32;
+
+pub mod inflate;
+
+/// What ROMs are called inside ZIP files
+pub const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "sgb"];
+pub const ARCHIVE_EXTENSIONS: [&str; 2] = ["zip", "gz"];
+
+const ZIP_LOCAL_HEADER: &[u8; 4] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: &[u8; 4] = b"PK\x01\x02";
+const ZIP_END: &[u8; 4] = b"PK\x05\x06";
+const GZIP_MAGIC: &[u8; 2] = b"\x1F\x8B";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    NoRom,
+    /// Which one to run isn't clear, these are their names
+    SeveralRoms(Vec<String>),
+    /// Encrypted, ZIP64 or compressed some other way than deflate
+    Unsupported(String),
+    Corrupt(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::NoRom => write!(
+                f,
+                "The archive has no ROM in it (.{})",
+                ROM_EXTENSIONS.join(", .")
+            ),
+            ArchiveError::SeveralRoms(names) => write!(
+                f,
+                "The archive has more than one ROM in it: {}",
+                names.join(", ")
+            ),
+            ArchiveError::Unsupported(what) => write!(f, "Unsupported archive: {}", what),
+            ArchiveError::Corrupt(what) => write!(f, "Corrupt archive: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Whether `path` looks like a ROM or an archive that might have one in it
+pub fn is_rom_file(path: &Path) -> bool {
+    has_extension(path, &ROM_EXTENSIONS) || is_archive(path)
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    has_extension(path, &ARCHIVE_EXTENSIONS)
+}
+
+/// The ROM in `bytes`, read from `path`. See the [module docs](self).
+pub fn extract(path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, ArchiveError> {
+    if bytes.starts_with(ZIP_LOCAL_HEADER)
+        || bytes.starts_with(ZIP_END)
+        || has_extension(path, &["zip"])
+    {
+        unzip(&bytes)
+    } else if bytes.starts_with(GZIP_MAGIC) || has_extension(path, &["gz"]) {
+        gunzip(&bytes)
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn corrupt(what: &str) -> ArchiveError {
+    ArchiveError::Corrupt(what.to_string())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, ArchiveError> {
+    match bytes.get(offset..offset + 2) {
+        Some(field) => Ok(u16::from_le_bytes([field[0], field[1]])),
+        None => Err(corrupt("it ends early")),
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, ArchiveError> {
+    match bytes.get(offset..offset + 4) {
+        Some(field) => Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]])),
+        None => Err(corrupt("it ends early")),
+    }
+}
+
+/// An entry in a ZIP file's central directory
+struct ZipEntry {
+    name: String,
+    flags: u16,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    local_header: u32,
+}
+
+impl ZipEntry {
+    /// ROMs, but not the metadata macOS puts next to them with the same name
+    fn is_rom(&self) -> bool {
+        let file_name = self.name.rsplit('/').next().unwrap_or_default();
+        !self.name.starts_with("__MACOSX/")
+            && !file_name.starts_with("._")
+            && has_extension(Path::new(file_name), &ROM_EXTENSIONS)
+    }
+}
+
+/// Everything in the central directory, which is at the end of the file
+fn zip_entries(zip: &[u8]) -> Result<Vec<ZipEntry>, ArchiveError> {
+    // The end record is followed by a comment of up to 64K
+    let search_from = zip.len().saturating_sub(22 + 0xFFFF);
+    let end = zip[search_from..]
+        .windows(4)
+        .rposition(|window| window == ZIP_END)
+        .map(|pos| search_from + pos)
+        .ok_or_else(|| corrupt("no end of central directory record"))?;
+    let count = u16_at(zip, end + 10)?;
+    let mut pos = u32_at(zip, end + 16)? as usize;
+    if count == 0xFFFF || pos == 0xFFFF_FFFF {
+        return Err(ArchiveError::Unsupported("ZIP64".into()));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if zip.get(pos..pos + 4) != Some(ZIP_CENTRAL_HEADER) {
+            return Err(corrupt("bad central directory entry"));
+        }
+        let name_length = u16_at(zip, pos + 28)? as usize;
+        let name = zip
+            .get(pos + 46..pos + 46 + name_length)
+            .ok_or_else(|| corrupt("it ends early"))?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            flags: u16_at(zip, pos + 8)?,
+            method: u16_at(zip, pos + 10)?,
+            crc: u32_at(zip, pos + 16)?,
+            compressed_size: u32_at(zip, pos + 20)?,
+            size: u32_at(zip, pos + 24)?,
+            local_header: u32_at(zip, pos + 42)?,
+        });
+        pos += 46 + name_length + u16_at(zip, pos + 30)? as usize + u16_at(zip, pos + 32)? as usize;
+    }
+    Ok(entries)
+}
+
+fn unzip(zip: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    let mut roms: Vec<ZipEntry> = zip_entries(zip)?
+        .into_iter()
+        .filter(ZipEntry::is_rom)
+        .collect();
+    let entry = match roms.len() {
+        0 => return Err(ArchiveError::NoRom),
+        1 => roms.remove(0),
+        _ => {
+            return Err(ArchiveError::SeveralRoms(
+                roms.into_iter().map(|entry| entry.name).collect(),
+            ))
+        }
+    };
+    if entry.flags & 1 != 0 {
+        return Err(ArchiveError::Unsupported(format!(
+            "{} is encrypted",
+            entry.name
+        )));
+    }
+    if entry.size == 0xFFFF_FFFF || entry.compressed_size == 0xFFFF_FFFF {
+        return Err(ArchiveError::Unsupported("ZIP64".into()));
+    }
+
+    let header = entry.local_header as usize;
+    if zip.get(header..header + 4) != Some(ZIP_LOCAL_HEADER) {
+        return Err(corrupt("bad local header"));
+    }
+    let start =
+        header + 30 + u16_at(zip, header + 26)? as usize + u16_at(zip, header + 28)? as usize;
+    let data = zip
+        .get(start..start + entry.compressed_size as usize)
+        .ok_or_else(|| corrupt("it ends early"))?;
+    let rom = match entry.method {
+        0 => data.to_vec(),
+        8 => inflate::inflate(data).map_err(corrupt)?.0,
+        method => {
+            return Err(ArchiveError::Unsupported(format!(
+                "{} is compressed with method {}, only deflate is supported",
+                entry.name, method
+            )))
+        }
+    };
+    if rom.len() != entry.size as usize || crc32(&[&rom]) != entry.crc {
+        return Err(corrupt("the ROM's checksum doesn't match"));
+    }
+    Ok(rom)
+}
+
+/// Flags in the gzip header
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+
+/// A gzip file can be several members one after the other, which decompress to all of their
+/// contents joined together
+fn gunzip(mut gz: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    let mut out = Vec::new();
+    loop {
+        if !gz.starts_with(GZIP_MAGIC) {
+            return Err(corrupt("not a gzip file"));
+        }
+        let method = *gz.get(2).ok_or_else(|| corrupt("it ends early"))?;
+        if method != 8 {
+            return Err(ArchiveError::Unsupported(format!(
+                "gzip compression method {}, only deflate is supported",
+                method
+            )));
+        }
+        let flags = *gz.get(3).ok_or_else(|| corrupt("it ends early"))?;
+        let mut pos = 10;
+        if flags & FEXTRA != 0 {
+            pos += 2 + u16_at(gz, pos)? as usize;
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let length = gz
+                    .get(pos..)
+                    .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                    .ok_or_else(|| corrupt("it ends early"))?;
+                pos += length + 1;
+            }
+        }
+        if flags & FHCRC != 0 {
+            pos += 2;
+        }
+
+        let data = gz.get(pos..).ok_or_else(|| corrupt("it ends early"))?;
+        let (member, used) = inflate::inflate(data).map_err(corrupt)?;
+        let trailer = pos + used;
+        let (crc, size) = (u32_at(gz, trailer)?, u32_at(gz, trailer + 4)?);
+        if crc32(&[&member]) != crc || member.len() as u32 != size {
+            return Err(corrupt("the ROM's checksum doesn't match"));
+        }
+        out.extend_from_slice(&member);
+
+        gz = &gz[trailer + 8..];
+        // Some tools pad the end with zeros
+        if gz.iter().all(|&byte| byte == 0) {
+            return Ok(out);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! DEFLATE decompression (RFC 1951), what ZIP and gzip files compress with
+//!
+//! Canonical Huffman codes are decoded a bit at a time from the count of codes of each length,
+//! like zlib's `puff`. That's slower than table lookups but ROMs are small.
+
+/// Most bits a code can have
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths come in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads bits least significant first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, &'static str> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or("Compressed data ends early")?;
+        let bit = (byte as u32 >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(bit)
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u32, &'static str> {
+        (0..count).try_fold(0, |value, i| Ok(value | self.bit()? << i))
+    }
+
+    /// Skips to the next byte boundary
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+struct Huffman {
+    /// How many codes there are of each length
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// From the code length of each symbol, 0 for symbols that aren't used
+    fn new(lengths: &[u8]) -> Result<Self, &'static str> {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        // Over-subscribed codes can't be decoded, incomplete ones are fine until they're hit
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err("Bad Huffman code");
+            }
+        }
+        let mut offsets = [0; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, &'static str> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bit()? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Bad Huffman code")
+    }
+}
+
+/// Decompresses raw DEFLATE data, returning it and how many bytes of `data` it took up
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), &'static str> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => stored(&mut bits, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                codes(&mut bits, &mut out, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &lengths, &distances)?;
+            }
+            _ => return Err("Bad block type"),
+        }
+        if last {
+            break;
+        }
+    }
+    bits.align();
+    Ok((out, bits.pos))
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> Result<(), &'static str> {
+    bits.align();
+    let header = bits
+        .data
+        .get(bits.pos..bits.pos + 4)
+        .ok_or("Compressed data ends early")?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    if length != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err("Stored block length doesn't match its complement");
+    }
+    let start = bits.pos + 4;
+    let block = bits
+        .data
+        .get(start..start + length as usize)
+        .ok_or("Compressed data ends early")?;
+    out.extend_from_slice(block);
+    bits.pos = start + length as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), &'static str> {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), &'static str> {
+    let length_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+    if length_count > 286 || distance_count > 30 {
+        return Err("Too many codes");
+    }
+
+    let mut code_lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; length_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_lengths.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or("Repeat with nothing before it")?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        let run = lengths
+            .get_mut(i..i + repeat)
+            .ok_or("Code lengths run past the end")?;
+        run.fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err("No end of block code");
+    }
+    Ok((
+        Huffman::new(&lengths[..length_count])?,
+        Huffman::new(&lengths[length_count..])?,
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), &'static str> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("Bad length code");
+                }
+                let length = LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index])? as usize;
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("Bad distance code");
+                }
+                let distance =
+                    DISTANCE_BASE[index] as usize + bits.bits(DISTANCE_EXTRA[index])? as usize;
+                if distance > out.len() {
+                    return Err("Distance reaches back before the start");
+                }
+                // The copy can overlap what it's writing
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! What runs before the cartridge
+//!
+//! By default nothing does, the emulator starts at 0x100 with everything set up the way the
+//! model's boot ROM would leave it. A dump of the real boot ROM can be run instead, or
+//! [`open_boot_rom`] for those without one: it's written from scratch to do what the DMG's
+//! does, scrolling the logo from the cartridge header down the screen, playing the two notes,
+//! locking up unless the logo and header checksum are right and handing over with the same
+//! registers. Timing isn't the same, so DIV and the PPU are somewhere else by then. Either way
+//! it's mapped over the start of ROM until it writes FF50, see
+//! [`MemoryBus::set_boot_rom`](crate::emulator::memory_bus::MemoryBus::set_boot_rom).
+//!
+//! On color models the boot ROM runs in CGB mode whatever the cartridge is. For a DMG game
+//! the real one fills in palette RAM with the colors for it (or the ones picked with the
+//! buttons) and sets KEY0 to leave the game in compatibility mode, see
+//! [`MemoryBus::cgb_mode`](crate::emulator::memory_bus::MemoryBus::cgb_mode).
+use tracing::info;
+
+use crate::emulator::{hardware::HardwareModel, rom::NINTENDO_LOGO};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Boot {
+    /// Start at 0x100 with the registers and hardware as the boot ROM leaves them
+    #[default]
+    Skip,
+    /// [`open_boot_rom`]
+    Open,
+    /// A dump of the real one, [`HardwareModel::boot_rom`]'s size
+    Dump(Vec<u8>),
+}
+
+impl Boot {
+    /// Checks `rom` is a dump of `model`'s boot ROM going by its size. The CGB's is 2K, some
+    /// dumps are just that and others have the 0x100-0x1FF it leaves to the cartridge in
+    /// there too, those are padded to match.
+    pub fn dump(mut rom: Vec<u8>, model: HardwareModel) -> Result<Self, String> {
+        let expected = model.boot_rom();
+        if expected.size > 0x100 && rom.len() == expected.size - 0x100 {
+            rom.splice(0x100..0x100, [0; 0x100]);
+        }
+        if rom.len() != expected.size {
+            return Err(format!(
+                "It's {} bytes, the {} boot ROM ({}) is {}",
+                rom.len(),
+                model,
+                expected.file_name,
+                expected.size
+            ));
+        }
+        Ok(Boot::Dump(rom))
+    }
+
+    /// The ROM to run on `model`, `None` to skip it. Open only has one for the DMG family,
+    /// color models skip it.
+    pub fn rom(&self, model: HardwareModel) -> Option<Vec<u8>> {
+        match self {
+            Boot::Skip => None,
+            Boot::Open => {
+                let rom = open_boot_rom(model);
+                if rom.is_none() {
+                    info!(
+                        "There's no open boot ROM for the {} yet, skipping it",
+                        model
+                    );
+                }
+                rom
+            }
+            Boot::Dump(rom) => Some(rom.clone()),
+        }
+    }
+}
+
+/// Where [`CODE`] ends and the logo it compares the cartridge's with starts
+const LOGO: usize = 0xB0;
+/// Where [`HANDOVER`] goes, so the FF50 write is the last instruction
+const HANDOVER_AT: usize = 0x100 - HANDOVER.len();
+/// The `CALL note`s, which the SGB's boot ROM doesn't make
+const NOTES: [usize; 2] = [0x61, 0x68];
+/// `wait`, which waits for B frames like `note` without the sound
+const WAIT: u8 = 0xA0;
+
+/// Everything up to the logo, hand assembled
+#[rustfmt::skip]
+const CODE: [u8; LOGO] = [
+    0x31, 0xFE, 0xFF,       // 00 LD SP, $FFFE
+    0xAF,                   // 03 XOR A
+    0x21, 0xFF, 0x9F,       // 04 LD HL, $9FFF
+    // .clear
+    0x32,                   // 07 LD [HL-], A
+    0xCB, 0x7C,             // 08 BIT 7, H
+    0x20, 0xFB,             // 0A JR NZ, .clear
+    // Sound on, set up for the notes
+    0x3E, 0x80,             // 0C LD A, $80
+    0xE0, 0x26,             // 0E LDH [NR52], A
+    0xE0, 0x11,             // 10 LDH [NR11], A
+    0x3E, 0xF3,             // 12 LD A, $F3
+    0xE0, 0x12,             // 14 LDH [NR12], A
+    0xE0, 0x25,             // 16 LDH [NR51], A
+    0x3E, 0x77,             // 18 LD A, $77
+    0xE0, 0x24,             // 1A LDH [NR50], A
+    0x3E, 0xFC,             // 1C LD A, $FC
+    0xE0, 0x47,             // 1E LDH [BGP], A
+    // Each nibble of the header's logo is a row of a tile, doubled in both directions
+    0x11, 0x04, 0x01,       // 20 LD DE, $0104
+    0x21, 0x10, 0x80,       // 23 LD HL, $8010
+    // .tiles
+    0x1A,                   // 26 LD A, [DE]
+    0xCB, 0x37,             // 27 SWAP A
+    0xCD, 0x8A, 0x00,       // 29 CALL double
+    0x1A,                   // 2C LD A, [DE]
+    0xCD, 0x8A, 0x00,       // 2D CALL double
+    0x13,                   // 30 INC DE
+    0x7B,                   // 31 LD A, E
+    0xFE, 0x34,             // 32 CP $34
+    0x20, 0xF0,             // 34 JR NZ, .tiles
+    // Tiles 1-12 above 13-24 in the middle of the map
+    0x3E, 0x01,             // 36 LD A, 1
+    0x21, 0x04, 0x99,       // 38 LD HL, $9904
+    // .top_row
+    0x22,                   // 3B LD [HL+], A
+    0x3C,                   // 3C INC A
+    0xFE, 0x0D,             // 3D CP 13
+    0x20, 0xFA,             // 3F JR NZ, .top_row
+    0x2E, 0x24,             // 41 LD L, $24
+    // .bottom_row
+    0x22,                   // 43 LD [HL+], A
+    0x3C,                   // 44 INC A
+    0xFE, 0x19,             // 45 CP 25
+    0x20, 0xFA,             // 47 JR NZ, .bottom_row
+    // Scroll it down from off the top, a line every other frame
+    0x3E, 0x64,             // 49 LD A, $64
+    0xE0, 0x42,             // 4B LDH [SCY], A
+    0x3E, 0x91,             // 4D LD A, $91
+    0xE0, 0x40,             // 4F LDH [LCDC], A
+    // .scroll
+    0x06, 0x02,             // 51 LD B, 2
+    0xCD, 0xA0, 0x00,       // 53 CALL wait
+    0xF0, 0x42,             // 56 LDH A, [SCY]
+    0x3D,                   // 58 DEC A
+    0xE0, 0x42,             // 59 LDH [SCY], A
+    0x20, 0xF4,             // 5B JR NZ, .scroll
+    0x3E, 0x83,             // 5D LD A, $83
+    0x06, 0x08,             // 5F LD B, 8
+    0xCD, 0x9A, 0x00,       // 61 CALL note
+    0x3E, 0xC1,             // 64 LD A, $C1
+    0x06, 0x3C,             // 66 LD B, 60
+    0xCD, 0x9A, 0x00,       // 68 CALL note
+    // Lock up unless the logo's right
+    0x11, 0xB0, 0x00,       // 6B LD DE, logo
+    0x21, 0x04, 0x01,       // 6E LD HL, $0104
+    // .compare
+    0x1A,                   // 71 LD A, [DE]
+    0xBE,                   // 72 CP [HL]
+    // .locked
+    0x20, 0xFE,             // 73 JR NZ, .locked
+    0x13,                   // 75 INC DE
+    0x23,                   // 76 INC HL
+    0x7D,                   // 77 LD A, L
+    0xFE, 0x34,             // 78 CP $34
+    0x20, 0xF5,             // 7A JR NZ, .compare
+    // And the header checksum, 0 - each byte - 1 for 0x134-0x14C
+    0x06, 0x19,             // 7C LD B, $19
+    0xAF,                   // 7E XOR A
+    // .sum
+    0x37,                   // 7F SCF
+    0x9E,                   // 80 SBC A, [HL]
+    0x23,                   // 81 INC HL
+    0x05,                   // 82 DEC B
+    0x20, 0xFA,             // 83 JR NZ, .sum
+    0xBE,                   // 85 CP [HL]
+    0x20, 0xEB,             // 86 JR NZ, .locked
+    0x18, HANDOVER_AT as u8 - 0x8A, // 88 JR handover
+    // double: A's low nibble with every bit twice, into two rows of the tile at HL
+    0x4F,                   // 8A LD C, A
+    0x06, 0x04,             // 8B LD B, 4
+    // .bit
+    0xCB, 0x19,             // 8D RR C
+    0x1F,                   // 8F RRA
+    0xCB, 0x2F,             // 90 SRA A
+    0x05,                   // 92 DEC B
+    0x20, 0xF8,             // 93 JR NZ, .bit
+    0x22,                   // 95 LD [HL+], A
+    0x23,                   // 96 INC HL
+    0x22,                   // 97 LD [HL+], A
+    0x23,                   // 98 INC HL
+    0xC9,                   // 99 RET
+    // note: plays A on channel 1 then waits B frames
+    0xE0, 0x13,             // 9A LDH [NR13], A
+    0x3E, 0x87,             // 9C LD A, $87
+    0xE0, 0x14,             // 9E LDH [NR14], A
+    // wait: B frames, each one over once LY moves past 144
+    0xF0, 0x44,             // A0 LDH A, [LY]
+    0xFE, 0x90,             // A2 CP 144
+    0x20, 0xFA,             // A4 JR NZ, wait
+    // .vblank
+    0xF0, 0x44,             // A6 LDH A, [LY]
+    0xFE, 0x90,             // A8 CP 144
+    0x28, 0xFA,             // AA JR Z, .vblank
+    0x05,                   // AC DEC B
+    0x20, 0xF1,             // AD JR NZ, wait
+    0xC9,                   // AF RET
+];
+
+/// Loads the registers from the table after the logo, with its second AF if the header
+/// checksum is 0, and unmaps the boot ROM
+#[rustfmt::skip]
+const HANDOVER: [u8; 17] = [
+    0x7E,                   // EF LD A, [HL]
+    0xA7,                   // F0 AND A
+    0x31, (LOGO + NINTENDO_LOGO.len()) as u8, 0x00, // F1 LD SP, registers
+    0xC1,                   // F4 POP BC
+    0xD1,                   // F5 POP DE
+    0xE1,                   // F6 POP HL
+    0x20, 0x01,             // F7 JR NZ, .nonzero
+    0xF1,                   // F9 POP AF
+    // .nonzero
+    0xF1,                   // FA POP AF
+    0x31, 0xFE, 0xFF,       // FB LD SP, $FFFE
+    0xE0, 0x50,             // FE LDH [BOOT], A
+];
+
+/// A freely usable stand-in for the DMG family's boot ROMs, `None` for color models. See the
+/// [module docs](self).
+pub fn open_boot_rom(model: HardwareModel) -> Option<Vec<u8>> {
+    if model.is_color() {
+        return None;
+    }
+    let mut rom = CODE.to_vec();
+    rom.extend_from_slice(&NINTENDO_LOGO);
+    // BC, DE and HL are the same whatever the checksum is
+    let nonzero = model.post_boot_registers(1);
+    let zero = model.post_boot_registers(0);
+    rom.extend_from_slice(&[
+        nonzero.c, nonzero.b, nonzero.e, nonzero.d, nonzero.l, nonzero.h, nonzero.f, nonzero.a,
+        zero.f, zero.a,
+    ]);
+    rom.resize(HANDOVER_AT, 0);
+    rom.extend_from_slice(&HANDOVER);
+    if matches!(model, HardwareModel::Sgb | HardwareModel::Sgb2) {
+        for call in NOTES {
+            rom[call + 1] = WAIT;
+        }
+    }
+    Some(rom)
+}
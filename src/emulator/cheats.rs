@@ -0,0 +1,278 @@
+//! Cheat codes
+//!
+//! Game Genie codes patch ROM reads: `ABC-DEF-GHI`, where `AB` is the new byte, `FCDE` the
+//! address (with `F` inverted), and `GI` the byte that has to be in ROM for the patch to apply.
+//! The compare byte is stored XORed with 0xBA and then rotated left by 2, so decoding rotates
+//! `GI` right by 2 and then XORs it with 0xBA. `H` is ignored by the hardware. The short form
+//! `ABC-DEF` patches unconditionally.
+//!
+//! GameShark codes write RAM once per frame, at the start of V-blank: `ABCDEFGH`, where `AB`
+//! is the RAM bank, `CD` the new byte and `GHEF` the address. For cartridge RAM the bank is
+//! the bank number, for WRAM `8X`/`9X` selects CGB bank `X` and anything else (usually `01`)
+//! writes whatever is mapped.
+//!
+//! Cheats are saved per game as one `<code> <on|off>` line each.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use tracing::trace;
+
+use crate::emulator::{paths::Paths, save_file};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatCode {
+    GameGenie {
+        address: u16,
+        new_data: u8,
+        compare: Option<u8>,
+    },
+    GameShark {
+        bank: u8,
+        new_data: u8,
+        address: u16,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheatError {
+    /// Code isn't made of 6, 8 or 9 hex digits
+    BadLength(usize),
+    BadDigit(char),
+    /// Game Genie codes can only patch ROM
+    NotRom(u16),
+    /// GameShark codes can only write cartridge RAM and WRAM
+    NotRam(u16),
+    /// Line in a cheat file that isn't `<code> <on|off>`
+    BadLine(String),
+}
+
+impl std::fmt::Display for CheatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheatError::BadLength(len) => {
+                write!(
+                    f,
+                    "Expected 6 or 9 (Game Genie) or 8 (GameShark) hex digits, found {}",
+                    len
+                )
+            }
+            CheatError::BadDigit(digit) => write!(f, "'{}' is not a hex digit", digit),
+            CheatError::NotRom(addr) => write!(f, "{:#06X} is outside of ROM", addr),
+            CheatError::NotRam(addr) => write!(f, "{:#06X} is outside of RAM", addr),
+            CheatError::BadLine(line) => write!(f, "Bad cheat line '{}'", line),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+fn hex_digits(code: &str) -> Result<Vec<u8>, CheatError> {
+    code.chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| {
+            c.to_digit(16)
+                .map(|d| d as u8)
+                .ok_or(CheatError::BadDigit(c))
+        })
+        .collect()
+}
+
+impl CheatCode {
+    /// Picks the format from the number of digits
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        match hex_digits(code)?.len() {
+            8 => Self::parse_game_shark(code),
+            _ => Self::parse_game_genie(code),
+        }
+    }
+
+    pub fn parse_game_genie(code: &str) -> Result<Self, CheatError> {
+        let digits = hex_digits(code)?;
+        if digits.len() != 6 && digits.len() != 9 {
+            return Err(CheatError::BadLength(digits.len()));
+        }
+
+        let new_data = digits[0] << 4 | digits[1];
+        let address = ((digits[5] ^ 0xF) as u16) << 12
+            | (digits[2] as u16) << 8
+            | (digits[3] as u16) << 4
+            | digits[4] as u16;
+        if address > 0x7FFF {
+            return Err(CheatError::NotRom(address));
+        }
+
+        let compare =
+            (digits.len() == 9).then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+
+        Ok(CheatCode::GameGenie {
+            address,
+            new_data,
+            compare,
+        })
+    }
+
+    pub fn parse_game_shark(code: &str) -> Result<Self, CheatError> {
+        let digits = hex_digits(code)?;
+        if digits.len() != 8 {
+            return Err(CheatError::BadLength(digits.len()));
+        }
+
+        let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+        let address = u16::from_le_bytes([byte(4), byte(6)]);
+        if !(0xA000..=0xDFFF).contains(&address) {
+            return Err(CheatError::NotRam(address));
+        }
+
+        Ok(CheatCode::GameShark {
+            bank: byte(0),
+            new_data: byte(2),
+            address,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    /// What the user typed in
+    pub code: String,
+    pub enabled: bool,
+    parsed: CheatCode,
+}
+
+impl Cheat {
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        Ok(Self {
+            code: code.trim().to_uppercase(),
+            enabled: true,
+            parsed: CheatCode::parse(code)?,
+        })
+    }
+
+    pub fn parsed(&self) -> CheatCode {
+        self.parsed
+    }
+}
+
+/// A GameShark write, see [`CheatCode::GameShark`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RamWrite {
+    pub bank: u8,
+    pub address: u16,
+    pub value: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    /// Enabled ROM patches, kept separately so ROM reads don't have to look at everything
+    rom_patches: Vec<(u16, u8, Option<u8>)>,
+    ram_writes: Vec<RamWrite>,
+}
+
+impl Cheats {
+    pub fn set(&mut self, cheats: Vec<Cheat>) {
+        self.rom_patches.clear();
+        self.ram_writes.clear();
+        for cheat in cheats.iter().filter(|cheat| cheat.enabled) {
+            match cheat.parsed {
+                CheatCode::GameGenie {
+                    address,
+                    new_data,
+                    compare,
+                } => self.rom_patches.push((address, new_data, compare)),
+                CheatCode::GameShark {
+                    bank,
+                    new_data,
+                    address,
+                } => self.ram_writes.push(RamWrite {
+                    bank,
+                    address,
+                    value: new_data,
+                }),
+            }
+        }
+        self.cheats = cheats;
+    }
+
+    /// Enabled GameShark codes, applied by [`MemoryBus::apply_ram_cheats`]
+    ///
+    /// [`MemoryBus::apply_ram_cheats`]: crate::emulator::memory_bus::MemoryBus::apply_ram_cheats
+    pub fn ram_writes(&self) -> &[RamWrite] {
+        &self.ram_writes
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Applies Game Genie patches to a byte read from ROM
+    #[inline]
+    pub fn patch_rom(&self, addr: u16, value: u8) -> u8 {
+        if self.rom_patches.is_empty() {
+            return value;
+        }
+
+        for &(address, new_data, compare) in &self.rom_patches {
+            if address == addr && compare.is_none_or(|compare| compare == value) {
+                trace!(
+                    "Game Genie patched @{:#X}: {:#X} -> {:#X}",
+                    addr,
+                    value,
+                    new_data
+                );
+                return new_data;
+            }
+        }
+        value
+    }
+}
+
+/// Where a game's cheats are saved, keyed by title and global checksum
+pub fn cheat_file(paths: &Paths, title: &str, checksum: u16) -> PathBuf {
+    save_file::game_file(&paths.cheats, title, checksum, "txt")
+}
+
+pub fn parse_cheat_file(contents: &str) -> Result<Vec<Cheat>, CheatError> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (code, enabled) = match line.rsplit_once(' ') {
+                Some((code, "on")) => (code, true),
+                Some((code, "off")) => (code, false),
+                _ => return Err(CheatError::BadLine(line.to_string())),
+            };
+            let mut cheat = Cheat::parse(code)?;
+            cheat.enabled = enabled;
+            Ok(cheat)
+        })
+        .collect()
+}
+
+pub fn format_cheat_file(cheats: &[Cheat]) -> String {
+    cheats
+        .iter()
+        .map(|cheat| {
+            let state = if cheat.enabled { "on" } else { "off" };
+            format!("{} {}\n", cheat.code, state)
+        })
+        .collect()
+}
+
+/// A missing file just means no cheats yet
+pub fn load_cheats(path: &Path) -> io::Result<Vec<Cheat>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            parse_cheat_file(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_cheats(path: &Path, cheats: &[Cheat]) -> io::Result<()> {
+    save_file::write_atomic(path, format_cheat_file(cheats).as_bytes())
+}
@@ -0,0 +1,173 @@
+//! Execution coverage
+//!
+//! Counts every opcode the CPU runs and marks the ROM bytes of every instruction it runs, so
+//! a report can show which opcodes a game actually relies on and which parts of the ROM are
+//! code that was reached. Instructions run from RAM count towards the opcodes, but have no
+//! ROM bytes to mark.
+use std::fmt::Write;
+
+use crate::emulator::{
+    instructions::Instruction,
+    rom::{CodeMap, Location, BANK_SIZE},
+    symbols::Symbols,
+};
+
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    instructions: u64,
+    outside_rom: u64,
+    opcodes: [u64; 256],
+    /// Second byte of CB-prefixed instructions
+    cb_opcodes: [u64; 256],
+    /// Per byte of the ROM file, whether it's part of an instruction that ran
+    executed: Vec<bool>,
+}
+
+impl Coverage {
+    pub fn new(rom_len: usize) -> Self {
+        Self {
+            instructions: 0,
+            outside_rom: 0,
+            opcodes: [0; 256],
+            cb_opcodes: [0; 256],
+            executed: vec![false; rom_len],
+        }
+    }
+
+    /// An instruction made of `bytes` ran from `location`
+    pub fn record(&mut self, location: Location, bytes: &[u8; 4]) {
+        self.instructions += 1;
+        self.opcodes[bytes[0] as usize] += 1;
+        if bytes[0] == 0xCB {
+            self.cb_opcodes[bytes[1] as usize] += 1;
+        }
+        let len = Instruction::parse(bytes).map_or(1, |(_, instruction)| instruction.byte_len());
+        match location
+            .offset()
+            .filter(|&offset| offset < self.executed.len())
+        {
+            Some(offset) => {
+                let end = (offset + len as usize).min(self.executed.len());
+                self.executed[offset..end].fill(true);
+            }
+            None => self.outside_rom += 1,
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn opcode_count(&self, opcode: u8) -> u64 {
+        self.opcodes[opcode as usize]
+    }
+
+    pub fn cb_opcode_count(&self, opcode: u8) -> u64 {
+        self.cb_opcodes[opcode as usize]
+    }
+
+    /// Whether the byte at `offset` in the ROM file was part of an instruction that ran
+    pub fn is_executed(&self, offset: usize) -> bool {
+        self.executed.get(offset).copied().unwrap_or(false)
+    }
+
+    pub fn executed_bytes(&self) -> usize {
+        self.executed.iter().filter(|&&executed| executed).count()
+    }
+
+    /// Runs of executed bytes as (start, end exclusive) offsets, split at bank boundaries
+    pub fn executed_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (offset, _) in self.executed.iter().enumerate().filter(|(_, &e)| e) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end == offset && offset % BANK_SIZE != 0 => *end += 1,
+                _ => ranges.push((offset, offset + 1)),
+            }
+        }
+        ranges
+    }
+
+    /// A plain text report, with `symbols` naming the executed ranges
+    pub fn report(&self, rom: &[u8], symbols: &Symbols) -> String {
+        let mut out = String::new();
+        let used = |counts: &[u64; 256]| counts.iter().filter(|&&count| count > 0).count();
+        let legal: Vec<u8> = (0..=255)
+            .filter(|&opcode| Instruction::parse(&[opcode, 0, 0, 0]).is_ok())
+            .collect();
+        let code_map = CodeMap::analyze(rom);
+        let found: Vec<Location> = code_map
+            .instructions(rom)
+            .map(|(location, _)| location)
+            .collect();
+        let reached = found
+            .iter()
+            .filter_map(|location| location.offset())
+            .filter(|&offset| self.is_executed(offset))
+            .count();
+
+        // Writing to a String can't fail
+        let _ = writeln!(
+            out,
+            "Instructions executed: {} ({} outside ROM)",
+            self.instructions, self.outside_rom
+        );
+        let _ = writeln!(
+            out,
+            "Opcodes used: {} of {}, CB opcodes used: {} of 256",
+            // 0xCB itself is always legal but only a prefix
+            used(&self.opcodes) - (self.opcodes[0xCB] > 0) as usize,
+            legal.len() - 1,
+            used(&self.cb_opcodes)
+        );
+        let _ = writeln!(
+            out,
+            "ROM bytes executed: {} of {} ({:.1}%)",
+            self.executed_bytes(),
+            rom.len(),
+            100.0 * self.executed_bytes() as f64 / rom.len().max(1) as f64
+        );
+        let _ = writeln!(
+            out,
+            "Statically found instructions executed: {} of {}",
+            reached,
+            found.len()
+        );
+
+        let mut counts: Vec<(String, u64)> = (0..=255u8)
+            .filter(|&opcode| opcode != 0xCB)
+            .map(|opcode| (format!("{:02X}", opcode), self.opcode_count(opcode)))
+            .chain(
+                (0..=255u8)
+                    .map(|opcode| (format!("CB {:02X}", opcode), self.cb_opcode_count(opcode))),
+            )
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        // Most used first, ties in opcode order
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let _ = writeln!(out, "\nOpcodes by use:");
+        for (opcode, count) in counts {
+            let _ = writeln!(out, "  {:<6} {}", opcode, count);
+        }
+
+        let unused: Vec<String> = legal
+            .iter()
+            .filter(|&&opcode| opcode != 0xCB && self.opcode_count(opcode) == 0)
+            .map(|opcode| format!("{:02X}", opcode))
+            .collect();
+        let _ = writeln!(out, "\nNever executed:");
+        for line in unused.chunks(16) {
+            let _ = writeln!(out, "  {}", line.join(" "));
+        }
+
+        let _ = writeln!(out, "\nExecuted ROM ranges:");
+        for (start, end) in self.executed_ranges() {
+            let first = Location::from_offset(start);
+            let last = Location::from_offset(end - 1);
+            let _ = match symbols.describe(first) {
+                Some(label) => writeln!(out, "  {}-{:04X}  {}", first, last.addr, label),
+                None => writeln!(out, "  {}-{:04X}", first, last.addr),
+            };
+        }
+        out
+    }
+}
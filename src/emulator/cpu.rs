@@ -1,19 +1,31 @@
-use bit_field::BitField;
 use tracing::warn;
 #[allow(unused_imports)]
-use tracing::{debug, error, event, info, trace};
+use tracing::{debug, error, event, info, trace, trace_span};
 
 use crate::emulator::{
-    instructions::{Instruction, Register16Indirect, Register16Stack, Register8},
+    error::EmulatorError,
+    hardware::HardwareModel,
+    instructions::{Instruction, Register16Indirect, Register8},
     memory_bus::MemoryBus,
+    state::{StateError, StateReader, StateWriter},
 };
 
 pub mod alu;
 pub use alu::ALU;
+pub mod backend;
+#[cfg(feature = "cached-interpreter")]
+pub mod block_cache;
+#[cfg(feature = "cached-interpreter")]
+use block_cache::BlockCache;
+pub mod call_stack;
+use call_stack::{CallStack, FrameKind};
 pub mod control_flow;
 pub mod helpers;
 pub mod loads;
+pub mod registers;
+use registers::{Reg16, Reg8};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Flag {
     /// Zero flag
     Z,
@@ -42,6 +54,11 @@ pub struct CPU {
     pub stop: bool,
     pub halted: bool,
     pub IME: bool,
+    /// Not part of the hardware or save states, see [`CallStack`]
+    pub call_stack: CallStack,
+    /// Where the instruction the last [`CPU::tick`] ran was, `None` if it was halted or
+    /// dispatched an interrupt instead. Not saved either.
+    pub last_instruction: Option<u16>,
 }
 
 impl Default for CPU {
@@ -60,27 +77,106 @@ impl Default for CPU {
             stop: false,
             halted: false,
             IME: false,
+            call_stack: CallStack::default(),
+            last_instruction: None,
         }
     }
 }
 
 impl CPU {
+    /// Where `model`'s boot ROM leaves everything when it jumps to the cartridge at 0x100, not
+    /// halted or stopped and with interrupts off. `header_checksum` is the byte at 0x14D, see
+    /// [`HardwareModel::post_boot_registers`].
+    pub fn reset(&mut self, model: HardwareModel, header_checksum: u8) {
+        let registers = model.post_boot_registers(header_checksum);
+        *self = Self {
+            Accumulator: registers.a,
+            Flags: registers.f,
+            B: registers.b,
+            C: registers.c,
+            D: registers.d,
+            E: registers.e,
+            H: registers.h,
+            L: registers.l,
+            ..Self::default()
+        };
+    }
+
+    /// How it starts before any boot ROM has run, with every register 0
+    pub fn power_on(&mut self) {
+        *self = Self {
+            SP: 0,
+            PC: 0,
+            ..Self::default()
+        };
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for register in Reg8::ALL {
+            state.u8(self.read_reg(register));
+        }
+        state.u16(self.SP);
+        state.u16(self.PC);
+        state.bool(self.stop);
+        state.bool(self.halted);
+        state.bool(self.IME);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for register in Reg8::ALL {
+            self.write_reg(register, state.u8()?);
+        }
+        self.SP = state.u16()?;
+        self.PC = state.u16()?;
+        self.stop = state.bool()?;
+        self.halted = state.bool()?;
+        self.IME = state.bool()?;
+        // Whatever was being called is meaningless now
+        self.call_stack.clear();
+        Ok(())
+    }
+
     /// Ticks in M-cycles (4 T-cycles)
     pub fn tick(&mut self, memory_bus: &mut MemoryBus) -> u32 {
+        self.tick_with(memory_bus, |cpu, memory_bus| {
+            cpu.next_instruction(memory_bus)
+        })
+    }
+
+    /// Like [`CPU::tick`], getting instructions out of `cache` instead of decoding them
+    #[cfg(feature = "cached-interpreter")]
+    pub fn tick_cached(&mut self, memory_bus: &mut MemoryBus, cache: &mut BlockCache) -> u32 {
+        self.tick_with(memory_bus, |cpu, memory_bus| {
+            cache.next_instruction(cpu, memory_bus)
+        })
+    }
+
+    /// `fetch` gets the instruction at PC and moves PC past it
+    fn tick_with(
+        &mut self,
+        memory_bus: &mut MemoryBus,
+        fetch: impl FnOnce(&mut Self, &mut MemoryBus) -> Option<Instruction>,
+    ) -> u32 {
+        let _span = trace_span!(target: "cpu", "instruction", pc = self.PC).entered();
+        self.call_stack.start_instruction();
+        self.last_instruction = None;
         match self.handle_interrupt(memory_bus) {
             0 => {}
             n => return n as u32,
         };
 
         if self.halted {
-            trace!("Pausing due to HALT");
+            trace!(target: "cpu", "Pausing due to HALT");
             return 1;
         }
 
         let old_pc = self.PC;
-        let instr = self.next_instruction(memory_bus);
-        debug!("Executing instruction {} at {:#X}", instr, old_pc);
-        trace!(
+        let Some(instr) = fetch(self, memory_bus) else {
+            return 1;
+        };
+        self.last_instruction = Some(old_pc);
+        debug!(target: "cpu", "Executing instruction {} at {:#X}", instr, old_pc);
+        trace!(target: "cpu",
             "Registers before: BC: {:#X} DE: {:#X} HL: {:#X} SP: {:#X}",
             self.get_bc(),
             self.get_de(),
@@ -105,55 +201,14 @@ impl CPU {
             Instruction::Stop => {
                 self.stop = true;
             }
-            Instruction::Push(register) => match register {
-                Register16Stack::BC => {
-                    let value = self.get_bc();
-                    memory_bus.write_stack_16(&mut self.SP, value);
-                }
-                Register16Stack::DE => {
-                    let value = self.get_de();
-                    memory_bus.write_stack_16(&mut self.SP, value);
-                }
-                Register16Stack::HL => {
-                    let value = self.get_hl();
-                    memory_bus.write_stack_16(&mut self.SP, value);
-                }
-                Register16Stack::AF => {
-                    let value = self.get_af();
-                    memory_bus.write_stack_16(&mut self.SP, value);
-                }
-            },
-            Instruction::Pop(register) => match register {
-                Register16Stack::BC => {
-                    ALU::write_16(
-                        &mut self.B,
-                        &mut self.C,
-                        memory_bus.read_stack_16(&mut self.SP),
-                    );
-                }
-                Register16Stack::DE => {
-                    ALU::write_16(
-                        &mut self.D,
-                        &mut self.E,
-                        memory_bus.read_stack_16(&mut self.SP),
-                    );
-                }
-                Register16Stack::HL => {
-                    ALU::write_16(
-                        &mut self.H,
-                        &mut self.L,
-                        memory_bus.read_stack_16(&mut self.SP),
-                    );
-                }
-                Register16Stack::AF => {
-                    ALU::write_16(
-                        &mut self.Accumulator,
-                        &mut self.Flags,
-                        memory_bus.read_stack_16(&mut self.SP),
-                    );
-                    self.Flags &= 0xF0;
-                }
-            },
+            Instruction::Push(register) => {
+                let value = self.read_reg(Reg16::from(register));
+                memory_bus.write_stack_16(&mut self.SP, value);
+            }
+            Instruction::Pop(register) => {
+                let value = memory_bus.read_stack_16(&mut self.SP);
+                self.write_reg(Reg16::from(register), value);
+            }
             Instruction::DisableInterrupts => {
                 self.IME = false;
             }
@@ -161,20 +216,21 @@ impl CPU {
                 self.IME = true;
             }
             Instruction::Halt => {
-                warn!("Encountered HALT, halting");
+                warn!(target: "cpu", "Encountered HALT, halting");
                 self.halted = true;
             }
             _ => {
-                error!("Instruction not implemented: {}", instr);
-
-                // error!("High Ram Dump");
+                // error!(target: "cpu", "High Ram Dump");
                 // memory_bus.hram_dump();
 
-                unimplemented!("Instruction not implemented: {}", instr)
+                memory_bus.fault(EmulatorError::UnimplementedInstruction {
+                    pc: old_pc,
+                    instruction: instr.to_string(),
+                });
             }
         }
 
-        trace!(
+        trace!(target: "cpu",
             "Registers after: BC: {:#X} DE: {:#X} HL: {:#X}",
             self.get_bc(),
             self.get_de(),
@@ -191,43 +247,14 @@ impl CPU {
             Register16Indirect::DE => self.get_de(),
             Register16Indirect::HLI => {
                 let addr = self.get_hl();
-                trace!(
-                    "Reading indirect address HL: {:#X} (H: {:#X}, L: {:#X})",
-                    addr,
-                    self.H,
-                    self.L
-                );
-                let addr1 = addr.wrapping_add(1);
-                trace!("Added 1 to HL: {:#X}", addr1);
-                self.H = addr1.get_bits(8..16) as u8;
-                self.L = addr1.get_bits(0..8) as u8;
-                trace!(
-                    "Wroteback address to HL: {:#X} (H: {:#X}, L: {:#X})",
-                    addr1,
-                    self.H,
-                    self.L
-                );
-                trace!("Returning addr {:#X}", addr);
+                self.set_hl(addr.wrapping_add(1));
+                trace!(target: "cpu", "Read HL {:#X} and incremented it", addr);
                 addr
             }
             Register16Indirect::HLD => {
                 let addr = self.get_hl();
-                trace!(
-                    "Reading indirect address HL: {:#X} (H: {:#X}, L: {:#X})",
-                    addr,
-                    self.H,
-                    self.L
-                );
-                let addr1 = addr.wrapping_sub(1);
-                trace!("Subtracted 1 from HL: {:#X}", addr1);
-                self.H = addr1.get_bits(8..16) as u8;
-                self.L = addr1.get_bits(0..8) as u8;
-                trace!(
-                    "Wroteback address to HL: {:#X} (H: {:#X}, L: {:#X})",
-                    addr1,
-                    self.H,
-                    self.L
-                );
+                self.set_hl(addr.wrapping_sub(1));
+                trace!(target: "cpu", "Read HL {:#X} and decremented it", addr);
                 addr
             }
         }
@@ -235,7 +262,7 @@ impl CPU {
 
     fn write_register(&mut self, target: Register8, source: Register8, memory_bus: &mut MemoryBus) {
         let read = self.read_register(source, memory_bus);
-        trace!("Writing {:?} -> {:?}", source, target);
+        trace!(target: "cpu", "Writing {:?} -> {:?}", source, target);
         self.write_register_immediate(target, read, memory_bus);
     }
 
@@ -245,29 +272,17 @@ impl CPU {
         immediate: u8,
         memory_bus: &mut MemoryBus,
     ) {
-        trace!("Writing {:#X} -> {:?}", immediate, target);
-        match target {
-            Register8::B => self.B = immediate,
-            Register8::C => self.C = immediate,
-            Register8::D => self.D = immediate,
-            Register8::E => self.E = immediate,
-            Register8::H => self.H = immediate,
-            Register8::L => self.L = immediate,
-            Register8::IndirectHL => memory_bus.write_u8(self.get_hl(), immediate),
-            Register8::A => self.Accumulator = immediate,
+        trace!(target: "cpu", "Writing {:#X} -> {:?}", immediate, target);
+        match Reg8::try_from(target) {
+            Ok(register) => self.write_reg(register, immediate),
+            Err(()) => memory_bus.write_u8(self.get_hl(), immediate),
         }
     }
 
     fn read_register(&self, register: Register8, memory_bus: &MemoryBus) -> u8 {
-        match register {
-            Register8::B => self.B,
-            Register8::C => self.C,
-            Register8::D => self.D,
-            Register8::E => self.E,
-            Register8::H => self.H,
-            Register8::L => self.L,
-            Register8::IndirectHL => memory_bus.read_u8(self.get_hl()),
-            Register8::A => self.Accumulator,
+        match Reg8::try_from(register) {
+            Ok(register) => self.read_reg(register),
+            Err(()) => memory_bus.read_u8(self.get_hl()),
         }
     }
 
@@ -281,7 +296,7 @@ impl CPU {
             None => return 0,
         };
 
-        debug!(
+        debug!(target: "cpu",
             "Un-halting because interrupt {:#?} was found",
             next_interrupt
         );
@@ -293,8 +308,10 @@ impl CPU {
 
         memory_bus.reset_interrupt(next_interrupt);
         memory_bus.write_stack_16(&mut self.SP, self.PC);
+        self.enter(FrameKind::Interrupt(next_interrupt), next_interrupt.addr());
         self.PC = next_interrupt.addr();
 
-        4
+        // Two idle cycles, pushing PC and the jump
+        5
     }
 }
@@ -2,11 +2,11 @@ use bit_field::BitField;
 use tracing::trace;
 
 use crate::emulator::{
-    instructions::{AccumulatorFlagOp, AluOp, BitwiseOp, Instruction, Register16, Register8},
+    instructions::{AccumulatorFlagOp, AluOp, BitwiseOp, Instruction, Register8},
     memory_bus::MemoryBus,
 };
 
-use super::{Flag, CPU};
+use super::{registers::Reg16, Flag, CPU};
 
 pub fn handle_instruction(
     cpu: &mut CPU,
@@ -59,162 +59,48 @@ pub fn handle_instruction(
             ALU::handle_bitwise(cpu, op, register, memory_bus);
         }
         // 8-bit INC/DEC
-        Instruction::Increment(reg) => match reg {
-            Register8::B => cpu.B = ALU::increment(cpu, cpu.B),
-            Register8::C => cpu.C = ALU::increment(cpu, cpu.C),
-            Register8::D => cpu.D = ALU::increment(cpu, cpu.D),
-            Register8::E => cpu.E = ALU::increment(cpu, cpu.E),
-            Register8::H => cpu.H = ALU::increment(cpu, cpu.H),
-            Register8::L => cpu.L = ALU::increment(cpu, cpu.L),
-            Register8::IndirectHL => {
-                let addr = cpu.get_hl();
-                memory_bus.write_u8(addr, ALU::increment(cpu, memory_bus.read_u8(addr)));
-            }
-            Register8::A => cpu.Accumulator = ALU::increment(cpu, cpu.Accumulator),
-        },
-        Instruction::Decrement(reg) => match reg {
-            Register8::B => cpu.B = ALU::decrement(cpu, cpu.B),
-            Register8::C => cpu.C = ALU::decrement(cpu, cpu.C),
-            Register8::D => cpu.D = ALU::decrement(cpu, cpu.D),
-            Register8::E => cpu.E = ALU::decrement(cpu, cpu.E),
-            Register8::H => cpu.H = ALU::decrement(cpu, cpu.H),
-            Register8::L => cpu.L = ALU::decrement(cpu, cpu.L),
-            Register8::IndirectHL => {
-                let addr = cpu.get_hl();
-                memory_bus.write_u8(addr, ALU::decrement(cpu, memory_bus.read_u8(addr)));
-            }
-            Register8::A => cpu.Accumulator = ALU::decrement(cpu, cpu.Accumulator),
-        },
+        Instruction::Increment(reg) => {
+            let value = cpu.read_register(reg, memory_bus);
+            let value = ALU::increment(cpu, value);
+            cpu.write_register_immediate(reg, value, memory_bus);
+        }
+        Instruction::Decrement(reg) => {
+            let value = cpu.read_register(reg, memory_bus);
+            let value = ALU::decrement(cpu, value);
+            cpu.write_register_immediate(reg, value, memory_bus);
+        }
         // 16 bit ALU operations
         Instruction::AddHLRegister(reg) => {
-            let val = ALU::add_16(cpu, cpu.read_16(reg));
-            cpu.H = val.get_bits(8..16) as u8;
-            cpu.L = val.get_bits(0..8) as u8;
+            let val = ALU::add_16(cpu, cpu.read_reg(Reg16::from(reg)));
+            cpu.set_hl(val);
         }
         // 16-bit INC/DEC
         Instruction::Increment16(register) => {
-            if matches!(register, Register16::SP) {
-                cpu.SP = cpu.SP.wrapping_add(1);
-            } else {
-                let (upper, lower) = match register {
-                    Register16::BC => (&mut cpu.B, &mut cpu.C),
-                    Register16::DE => (&mut cpu.D, &mut cpu.E),
-                    Register16::HL => (&mut cpu.H, &mut cpu.L),
-                    _ => unreachable!(),
-                };
-
-                trace!(
-                    "Before Increment {:#?}: {:#X}",
-                    register,
-                    (*upper as u16) << 8 | *lower as u16
-                );
-                ALU::increment_16(upper, lower);
-                trace!(
-                    "After Increment {:#?}: {:#X}",
-                    register,
-                    (*upper as u16) << 8 | *lower as u16
-                );
-            }
+            let register = Reg16::from(register);
+            let value = cpu.read_reg(register);
+            memory_bus.oam_bug(value);
+            trace!(target: "cpu", "Incrementing {:?}: {:#X}", register, value);
+            cpu.write_reg(register, value.wrapping_add(1));
         }
         Instruction::Decrement16(register) => {
-            if matches!(register, Register16::SP) {
-                cpu.SP = cpu.SP.wrapping_sub(1);
-            } else {
-                let (upper, lower) = match register {
-                    Register16::BC => (&mut cpu.B, &mut cpu.C),
-                    Register16::DE => (&mut cpu.D, &mut cpu.E),
-                    Register16::HL => (&mut cpu.H, &mut cpu.L),
-                    _ => unreachable!(),
-                };
-
-                trace!(
-                    "Before Decrement {:#?}: {:#X}",
-                    register,
-                    (*upper as u16) << 8 | *lower as u16
-                );
-                ALU::decrement_16(upper, lower);
-                trace!(
-                    "After Decrement {:#?}: {:#X}",
-                    register,
-                    (*upper as u16) << 8 | *lower as u16
-                );
-            }
+            let register = Reg16::from(register);
+            let value = cpu.read_reg(register);
+            memory_bus.oam_bug(value);
+            trace!(target: "cpu", "Decrementing {:?}: {:#X}", register, value);
+            cpu.write_reg(register, value.wrapping_sub(1));
         }
         // Bitwise Ops
         Instruction::Bit(bit, reg) => {
-            cpu.set_flag(
-                Flag::Z,
-                !match reg {
-                    Register8::B => cpu.B.get_bit(bit as usize),
-                    Register8::C => cpu.C.get_bit(bit as usize),
-                    Register8::D => cpu.D.get_bit(bit as usize),
-                    Register8::E => cpu.E.get_bit(bit as usize),
-                    Register8::H => cpu.H.get_bit(bit as usize),
-                    Register8::L => cpu.L.get_bit(bit as usize),
-                    Register8::IndirectHL => memory_bus.read_u8(cpu.get_hl()).get_bit(bit as usize),
-                    Register8::A => cpu.Accumulator.get_bit(bit as usize),
-                },
-            );
-
+            let value = cpu.read_register(reg, memory_bus);
+            cpu.set_flag(Flag::Z, !value.get_bit(bit as usize));
             cpu.set_flag(Flag::N, false);
             cpu.set_flag(Flag::H, true);
         }
-        Instruction::SetBit(bit, reg) => match reg {
-            Register8::B => {
-                cpu.B.set_bit(bit as usize, true);
-            }
-            Register8::C => {
-                cpu.C.set_bit(bit as usize, true);
-            }
-            Register8::D => {
-                cpu.D.set_bit(bit as usize, true);
-            }
-            Register8::E => {
-                cpu.E.set_bit(bit as usize, true);
-            }
-            Register8::H => {
-                cpu.H.set_bit(bit as usize, true);
-            }
-            Register8::L => {
-                cpu.L.set_bit(bit as usize, true);
-            }
-            Register8::IndirectHL => {
-                let mut number = memory_bus.read_u8(cpu.get_hl());
-                number.set_bit(bit as usize, true);
-                memory_bus.write_u8(cpu.get_hl(), number);
-            }
-            Register8::A => {
-                cpu.Accumulator.set_bit(bit as usize, true);
-            }
-        },
-        Instruction::ResetBit(bit, reg) => match reg {
-            Register8::B => {
-                cpu.B.set_bit(bit as usize, false);
-            }
-            Register8::C => {
-                cpu.C.set_bit(bit as usize, false);
-            }
-            Register8::D => {
-                cpu.D.set_bit(bit as usize, false);
-            }
-            Register8::E => {
-                cpu.E.set_bit(bit as usize, false);
-            }
-            Register8::H => {
-                cpu.H.set_bit(bit as usize, false);
-            }
-            Register8::L => {
-                cpu.L.set_bit(bit as usize, false);
-            }
-            Register8::IndirectHL => {
-                let mut number = memory_bus.read_u8(cpu.get_hl());
-                number.set_bit(bit as usize, false);
-                memory_bus.write_u8(cpu.get_hl(), number);
-            }
-            Register8::A => {
-                cpu.Accumulator.set_bit(bit as usize, false);
-            }
-        },
+        Instruction::SetBit(bit, reg) | Instruction::ResetBit(bit, reg) => {
+            let mut value = cpu.read_register(reg, memory_bus);
+            value.set_bit(bit as usize, matches!(instr, Instruction::SetBit(..)));
+            cpu.write_register_immediate(reg, value, memory_bus);
+        }
         _ => return None,
     }
 
@@ -488,29 +374,6 @@ impl ALU {
         new_value
     }
 
-    pub fn increment_16(upper: &mut u8, lower: &mut u8) {
-        let mut addr = 0u16;
-        addr.set_bits(0..8, *lower as u16);
-        addr.set_bits(8..16, *upper as u16);
-        addr = addr.wrapping_add(1);
-        *lower = addr.get_bits(0..8) as u8;
-        *upper = addr.get_bits(8..16) as u8;
-    }
-
-    pub fn decrement_16(upper: &mut u8, lower: &mut u8) {
-        let mut addr = 0u16;
-        addr.set_bits(0..8, *lower as u16);
-        addr.set_bits(8..16, *upper as u16);
-        addr = addr.wrapping_sub(1);
-        *lower = addr.get_bits(0..8) as u8;
-        *upper = addr.get_bits(8..16) as u8;
-    }
-
-    pub fn write_16(upper: &mut u8, lower: &mut u8, value: u16) {
-        *lower = value.get_bits(0..8) as u8;
-        *upper = value.get_bits(8..16) as u8;
-    }
-
     pub fn add_rel(addr: u16, rel: i8) -> u16 {
         if rel.is_negative() {
             addr.wrapping_sub(rel.wrapping_abs() as u16)
@@ -0,0 +1,110 @@
+//! Execution backends
+//!
+//! [`Emulator`](crate::emulator::Emulator) schedules everything around a [`CpuCore`] and doesn't
+//! care how it runs the code. Every core keeps its registers in a [`CPU`], so frontends and the
+//! debugger look at the same thing whichever one is running, and switching hands the registers
+//! from one to the next.
+use std::str::FromStr;
+
+use crate::emulator::{
+    cpu::CPU,
+    memory_bus::{Interrupt, MemoryBus},
+    state::{StateError, StateReader, StateWriter},
+};
+
+/// How instructions are fetched and decoded, see
+/// [`Emulator::set_backend`](crate::emulator::Emulator::set_backend). Either way they run the
+/// same, with the same timing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Decodes every instruction as it gets to it
+    #[default]
+    Interpreter,
+    /// Decodes each basic block once and reuses it, see [`block_cache`](super::block_cache)
+    #[cfg(feature = "cached-interpreter")]
+    Cached,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interpreter" => Ok(Backend::Interpreter),
+            #[cfg(feature = "cached-interpreter")]
+            "cached" => Ok(Backend::Cached),
+            #[cfg(not(feature = "cached-interpreter"))]
+            "cached" => Err("The cached backend needs the cached-interpreter feature".into()),
+            _ => Err(format!(
+                "Unknown backend '{}', expected interpreter or cached",
+                s
+            )),
+        }
+    }
+}
+
+impl Backend {
+    pub fn core(self, cpu: CPU) -> Box<dyn CpuCore> {
+        match self {
+            Backend::Interpreter => Box::new(cpu),
+            #[cfg(feature = "cached-interpreter")]
+            Backend::Cached => Box::new(super::block_cache::CachedCore::new(cpu)),
+        }
+    }
+}
+
+/// Something that runs SM83 code
+pub trait CpuCore: Send {
+    /// Runs one instruction (or interrupt dispatch), returns how many M-cycles it took
+    fn step(&mut self, memory_bus: &mut MemoryBus) -> u32;
+
+    fn registers(&self) -> &CPU;
+
+    fn registers_mut(&mut self) -> &mut CPU;
+
+    /// Gives up the registers, for switching to another core
+    fn into_registers(self: Box<Self>) -> CPU;
+
+    /// `None` for anything that isn't built in
+    fn backend(&self) -> Option<Backend> {
+        None
+    }
+
+    /// Requests `interrupt` like the hardware that raises it would, the next
+    /// [`CpuCore::step`] dispatches it if it's enabled
+    fn inject_interrupt(&mut self, memory_bus: &mut MemoryBus, interrupt: Interrupt) {
+        memory_bus.request_interrupt(interrupt);
+    }
+
+    /// Only the registers are saved, so any core can load what another saved
+    fn save_state(&self, state: &mut StateWriter) {
+        self.registers().save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.registers_mut().load_state(state)
+    }
+}
+
+/// The interpreter
+impl CpuCore for CPU {
+    fn step(&mut self, memory_bus: &mut MemoryBus) -> u32 {
+        self.tick(memory_bus)
+    }
+
+    fn registers(&self) -> &CPU {
+        self
+    }
+
+    fn registers_mut(&mut self) -> &mut CPU {
+        self
+    }
+
+    fn into_registers(self: Box<Self>) -> CPU {
+        *self
+    }
+
+    fn backend(&self) -> Option<Backend> {
+        Some(Backend::Interpreter)
+    }
+}
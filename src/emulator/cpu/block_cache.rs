@@ -0,0 +1,184 @@
+//! Cached interpreter, behind the `cached-interpreter` feature
+//!
+//! Most code runs over and over, but the interpreter decodes every instruction from its bytes
+//! each time it gets to it. [`BlockCache`] decodes a basic block (everything up to and including
+//! the next jump, call, return or RST) the first time PC lands on its start and hands out the
+//! decoded instructions after that. They're still run by the interpreter one at a time, so the
+//! timing and everything the emulator does in between instructions is exactly the same.
+//!
+//! A block is thrown away as soon as any of the memory it was decoded from is written, see
+//! [`PageSet`], which covers self-modifying code and the routines games copy into RAM. Only
+//! ROM, WRAM and HRAM are cached. Code anywhere else is decoded every time.
+use std::collections::HashMap;
+
+use crate::emulator::{
+    cpu::{
+        backend::{Backend, CpuCore},
+        CPU,
+    },
+    instructions::Instruction,
+    memory_bus::{written_pages::PageSet, MemoryBus},
+};
+
+/// Long runs of straight-line code are split up, so a write in the middle of a table of
+/// instructions doesn't throw all of it away
+const MAX_LEN: usize = 64;
+
+#[derive(Debug)]
+struct Block {
+    /// Where each one starts
+    instructions: Vec<(u16, Instruction)>,
+    pages: PageSet,
+}
+
+#[derive(Debug, Default)]
+pub struct BlockCache {
+    /// By where they start
+    blocks: HashMap<u16, Block>,
+    /// Every page a block was decoded from
+    pages: PageSet,
+    /// Start of the block being run and the index of the next instruction in it
+    current: Option<(u16, usize)>,
+}
+
+impl BlockCache {
+    /// Number of blocks decoded and still valid
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The instruction at PC, moving PC past it like [`CPU::next_instruction`]
+    pub fn next_instruction(
+        &mut self,
+        cpu: &mut CPU,
+        memory_bus: &mut MemoryBus,
+    ) -> Option<Instruction> {
+        self.invalidate(&memory_bus.take_written_pages());
+        let pc = cpu.PC;
+        if !cacheable(pc) {
+            self.current = None;
+            return cpu.next_instruction(memory_bus);
+        }
+
+        let continues = self.current.filter(|&(start, index)| {
+            self.blocks
+                .get(&start)
+                .and_then(|block| block.instructions.get(index))
+                .is_some_and(|&(addr, _)| addr == pc)
+        });
+        let (start, index) = match continues {
+            Some(current) => current,
+            None if self.blocks.contains_key(&pc) => (pc, 0),
+            None => match decode_block(memory_bus, pc) {
+                Some(block) => {
+                    self.pages.extend(&block.pages);
+                    self.blocks.insert(pc, block);
+                    (pc, 0)
+                }
+                // Not even one instruction, let the interpreter report it
+                None => {
+                    self.current = None;
+                    return cpu.next_instruction(memory_bus);
+                }
+            },
+        };
+        let (_, instruction) = self.blocks[&start].instructions[index];
+        cpu.PC = pc.wrapping_add(instruction.byte_len());
+        self.current = Some((start, index + 1));
+        Some(instruction)
+    }
+
+    /// Forgets every block decoded from one of `written`
+    fn invalidate(&mut self, written: &PageSet) {
+        if !self.pages.overlaps(written) {
+            return;
+        }
+        self.blocks
+            .retain(|_, block| !block.pages.overlaps(written));
+        self.pages = PageSet::EMPTY;
+        for block in self.blocks.values() {
+            self.pages.extend(&block.pages);
+        }
+    }
+}
+
+fn cacheable(addr: u16) -> bool {
+    matches!(addr, 0x0000..=0x7FFF | 0xC000..=0xDFFF | 0xFF80..=0xFFFE)
+}
+
+/// Whether nothing after `instruction` runs straight after it
+fn ends_block(instruction: Instruction) -> bool {
+    instruction.is_control_flow() || matches!(instruction, Instruction::Halt | Instruction::Stop)
+}
+
+/// `None` if there isn't a legal instruction at `start`
+fn decode_block(memory_bus: &MemoryBus, start: u16) -> Option<Block> {
+    let mut block = Block {
+        instructions: Vec::new(),
+        pages: PageSet::EMPTY,
+    };
+    let mut addr = start;
+    while block.instructions.len() < MAX_LEN {
+        let Ok((_, instruction)) = Instruction::parse(&memory_bus.get_instr(addr)) else {
+            break;
+        };
+        let last = addr.wrapping_add(instruction.byte_len() - 1);
+        // All of it has to be somewhere writes are tracked
+        if !cacheable(last) || last < addr {
+            break;
+        }
+        block.instructions.push((addr, instruction));
+        block.pages.insert_range(addr, last);
+        addr = last.wrapping_add(1);
+        if ends_block(instruction) || !cacheable(addr) {
+            break;
+        }
+    }
+    (!block.instructions.is_empty()).then_some(block)
+}
+
+/// [`Backend::Cached`], the interpreter with a [`BlockCache`]
+#[derive(Debug)]
+pub struct CachedCore {
+    cpu: CPU,
+    cache: BlockCache,
+}
+
+impl CachedCore {
+    pub fn new(cpu: CPU) -> Self {
+        Self {
+            cpu,
+            cache: BlockCache::default(),
+        }
+    }
+
+    pub fn cache(&self) -> &BlockCache {
+        &self.cache
+    }
+}
+
+impl CpuCore for CachedCore {
+    fn step(&mut self, memory_bus: &mut MemoryBus) -> u32 {
+        self.cpu.tick_cached(memory_bus, &mut self.cache)
+    }
+
+    fn registers(&self) -> &CPU {
+        &self.cpu
+    }
+
+    fn registers_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    fn into_registers(self: Box<Self>) -> CPU {
+        self.cpu
+    }
+
+    fn backend(&self) -> Option<Backend> {
+        Some(Backend::Cached)
+    }
+}
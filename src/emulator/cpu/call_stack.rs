@@ -0,0 +1,92 @@
+//! Shadow call stack for the debugger
+//!
+//! Games don't always return the way they called: some pop the return address to read inline
+//! data, jump through a pushed address with RET or reset SP from the main loop. Frames are
+//! matched by where their return address was pushed, so one whose slot is above SP again
+//! (popped some other way) is dropped, and a RET that doesn't pop a frame's slot isn't a return.
+use crate::emulator::memory_bus::Interrupt;
+
+/// Deeper than anything sane, stops a game that never returns from growing it forever
+const MAX_DEPTH: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Call,
+    /// RST to this vector
+    Rst(u16),
+    Interrupt(Interrupt),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /// Where it jumped to
+    pub target: u16,
+    pub return_addr: u16,
+    /// SP after the return address was pushed
+    pub sp: u16,
+}
+
+impl Frame {
+    /// Where the CALL or RST was, or the instruction that got interrupted
+    pub fn call_site(&self) -> u16 {
+        match self.kind {
+            FrameKind::Call => self.return_addr.wrapping_sub(3),
+            FrameKind::Rst(_) => self.return_addr.wrapping_sub(1),
+            FrameKind::Interrupt(_) => self.return_addr,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    /// Whether the last instruction pushed the top frame
+    entered: bool,
+}
+
+impl CallStack {
+    pub fn push(&mut self, frame: Frame) {
+        // Anything at or below this slot was abandoned when SP moved back up past it
+        self.discard_below(frame.sp.wrapping_add(1));
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+        self.entered = true;
+    }
+
+    /// A RET with SP at `sp`, before it pops the return address
+    pub fn ret(&mut self, sp: u16) {
+        self.discard_below(sp);
+        if self.frames.last().is_some_and(|frame| frame.sp == sp) {
+            self.frames.pop();
+        }
+    }
+
+    fn discard_below(&mut self, sp: u16) {
+        while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+            self.frames.pop();
+        }
+    }
+
+    /// Outermost first
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The frame the last instruction or interrupt dispatch pushed
+    pub fn entered(&self) -> Option<&Frame> {
+        self.frames.last().filter(|_| self.entered)
+    }
+
+    /// Called before each instruction
+    pub fn start_instruction(&mut self) {
+        self.entered = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.entered = false;
+    }
+}
@@ -2,7 +2,7 @@ use tracing::trace;
 
 use crate::emulator::{instructions::Instruction, memory_bus::MemoryBus};
 
-use super::CPU;
+use super::{call_stack::FrameKind, CPU};
 
 pub fn handle_instruction(
     cpu: &mut CPU,
@@ -38,8 +38,9 @@ pub fn handle_instruction(
         }
         // Calls/Rets
         Instruction::Call(imm) => {
-            trace!("Writing {:#X} to stack @ {:#X}", cpu.PC, cpu.SP);
+            trace!(target: "cpu", "Writing {:#X} to stack @ {:#X}", cpu.PC, cpu.SP);
             memory_bus.write_stack_16(&mut cpu.SP, cpu.PC);
+            cpu.enter(FrameKind::Call, imm);
             cpu.PC = imm;
         }
         Instruction::CallConditional(condition, addr) => {
@@ -47,12 +48,14 @@ pub fn handle_instruction(
             if jump {
                 action_taken = true;
                 memory_bus.write_stack_16(&mut cpu.SP, cpu.PC);
+                cpu.enter(FrameKind::Call, addr);
                 cpu.PC = addr;
             }
         }
         Instruction::Ret => {
+            cpu.call_stack.ret(cpu.SP);
             let addr = memory_bus.read_stack_16(&mut cpu.SP);
-            trace!("Read {:#X} from stack @ {:#X}", addr, cpu.SP);
+            trace!(target: "cpu", "Read {:#X} from stack @ {:#X}", addr, cpu.SP);
             cpu.PC = addr;
         }
         Instruction::RetConditional(condition) => {
@@ -60,20 +63,24 @@ pub fn handle_instruction(
 
             if condition {
                 action_taken = true;
+                cpu.call_stack.ret(cpu.SP);
                 cpu.PC = memory_bus.read_stack_16(&mut cpu.SP);
-                trace!("Read {:#X} from stack @ {:#X}", cpu.PC, cpu.SP);
+                trace!(target: "cpu", "Read {:#X} from stack @ {:#X}", cpu.PC, cpu.SP);
             }
         }
         Instruction::RetInterrupt => {
+            cpu.call_stack.ret(cpu.SP);
             let addr = memory_bus.read_stack_16(&mut cpu.SP);
-            trace!("Read {:#X} from stack @ {:#X}", addr, cpu.SP);
+            trace!(target: "cpu", "Read {:#X} from stack @ {:#X}", addr, cpu.SP);
             cpu.PC = addr;
             cpu.IME = true;
         }
         // Reset Vectors
         Instruction::Reset(offset) => {
             memory_bus.write_stack_16(&mut cpu.SP, cpu.PC);
-            cpu.PC = offset as u16;
+            let vector = (offset as u16) << 3;
+            cpu.enter(FrameKind::Rst(vector), vector);
+            cpu.PC = vector;
         }
         _ => return None,
     }
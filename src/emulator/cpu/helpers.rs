@@ -1,11 +1,15 @@
 use bit_field::BitField;
 
 use crate::emulator::{
-    instructions::{Condition, Instruction, Register16},
+    error::EmulatorError,
+    instructions::{Condition, Instruction},
     memory_bus::MemoryBus,
 };
 
-use super::{Flag, CPU};
+use super::{
+    call_stack::{Frame, FrameKind},
+    Flag, CPU,
+};
 
 impl std::fmt::Display for CPU {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -21,8 +25,8 @@ impl std::fmt::Display for CPU {
         writeln!(f, "\t\tC: {:#X}", self.C)?;
         writeln!(f, "\t\tD: {:#X}", self.D)?;
         writeln!(f, "\t\tE: {:#X}", self.E)?;
-        writeln!(f, "\t\tL: {:#X}", self.H)?;
-        writeln!(f, "\t\tH: {:#X}", self.L)?;
+        writeln!(f, "\t\tH: {:#X}", self.H)?;
+        writeln!(f, "\t\tL: {:#X}", self.L)?;
         writeln!(f)?;
         writeln!(f, "\t\tSP: {:#X}", self.SP)?;
         writeln!(f, "\t\tPC: {:#X}", self.PC)?;
@@ -34,12 +38,28 @@ impl std::fmt::Display for CPU {
 
 impl CPU {
     // Instruction Fetcher
-    pub fn next_instruction(&mut self, memory_bus: &MemoryBus) -> Instruction {
+    /// An illegal opcode is reported to the bus and leaves PC on it, like the hardware locking up
+    pub fn next_instruction(&mut self, memory_bus: &MemoryBus) -> Option<Instruction> {
         let instr = memory_bus.get_instr(self.PC);
-        let (_, actual_instr) = Instruction::parse(&instr)
-            .unwrap_or_else(|_| panic!("Instruction parsing failed at {:#X}", self.PC));
+        let Ok((_, actual_instr)) = Instruction::parse(&instr) else {
+            memory_bus.fault(EmulatorError::IllegalInstruction {
+                pc: self.PC,
+                opcode: instr[0],
+            });
+            return None;
+        };
         self.PC = self.PC.wrapping_add(actual_instr.byte_len());
-        actual_instr
+        Some(actual_instr)
+    }
+
+    /// Records a CALL, RST or interrupt once the return address (PC) has been pushed
+    pub fn enter(&mut self, kind: FrameKind, target: u16) {
+        self.call_stack.push(Frame {
+            kind,
+            target,
+            return_addr: self.PC,
+            sp: self.SP,
+        });
     }
 
     // 16 bit helpers
@@ -59,15 +79,6 @@ impl CPU {
         ((self.H as u16) << 8) | (self.L as u16)
     }
 
-    pub fn read_16(&self, register: Register16) -> u16 {
-        match register {
-            Register16::BC => self.get_bc(),
-            Register16::DE => self.get_de(),
-            Register16::HL => self.get_hl(),
-            Register16::SP => self.SP,
-        }
-    }
-
     // Flags
     pub fn get_flag(&self, flag: Flag) -> bool {
         match flag {
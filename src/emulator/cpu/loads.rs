@@ -1,12 +1,9 @@
 use bit_field::BitField;
 use tracing::{debug, trace};
 
-use crate::emulator::{
-    instructions::{Instruction, Register16, Register8},
-    memory_bus::MemoryBus,
-};
+use crate::emulator::{instructions::Instruction, memory_bus::MemoryBus};
 
-use super::{Flag, ALU, CPU};
+use super::{registers::Reg16, Flag, ALU, CPU};
 
 pub fn handle_instruction(
     cpu: &mut CPU,
@@ -21,43 +18,15 @@ pub fn handle_instruction(
             // }
             cpu.write_register(reg1, reg2, memory_bus);
         }
-        Instruction::LoadImmediate(register, immediate) => match register {
-            Register8::B => {
-                cpu.B = immediate;
-            }
-            Register8::C => {
-                cpu.C = immediate;
-            }
-            Register8::D => {
-                cpu.D = immediate;
-            }
-            Register8::E => {
-                cpu.E = immediate;
-            }
-            Register8::H => {
-                cpu.H = immediate;
-            }
-            Register8::L => {
-                cpu.L = immediate;
-            }
-            Register8::IndirectHL => {
-                trace!(
-                    "Writing to IndirectHL @{:#X}: {:#X}",
-                    cpu.get_hl(),
-                    immediate
-                );
-                memory_bus.write_u8(cpu.get_hl(), immediate);
-            }
-            Register8::A => {
-                cpu.Accumulator = immediate;
-            }
-        },
+        Instruction::LoadImmediate(register, immediate) => {
+            cpu.write_register_immediate(register, immediate, memory_bus);
+        }
         Instruction::LoadIndirectImmediateA(addr) => {
-            trace!("Writing to Indirect @{:#X}: {:#X}", addr, cpu.Accumulator);
+            trace!(target: "cpu", "Writing to Indirect @{:#X}: {:#X}", addr, cpu.Accumulator);
             memory_bus.write_u8(addr, cpu.Accumulator);
         }
         Instruction::LoadAIndirectImmediate(addr) => {
-            trace!(
+            trace!(target: "cpu",
                 "Reading Indirect @{:#X}: {:#X}",
                 addr,
                 memory_bus.read_u8(addr)
@@ -67,7 +36,7 @@ pub fn handle_instruction(
         Instruction::LoadHighPageAImmediate(offset) => {
             let real_address = 0xFF00 + (offset as u16);
             memory_bus.write_u8(real_address, cpu.Accumulator);
-            trace!(
+            trace!(target: "cpu",
                 "LoadHighPageA loaded A ({:#X}) into @{:#X}",
                 cpu.Accumulator,
                 real_address
@@ -76,7 +45,7 @@ pub fn handle_instruction(
         Instruction::LoadAHighPageImmediate(offset) => {
             let real_address = 0xFF00 + (offset as u16);
             cpu.Accumulator = memory_bus.read_u8(real_address);
-            trace!(
+            trace!(target: "cpu",
                 "LoadAHighPage loaded @{:#X} into A ({:#X})",
                 real_address,
                 cpu.Accumulator
@@ -93,27 +62,12 @@ pub fn handle_instruction(
             cpu.Accumulator = memory_bus.read_u8(real_address);
         }
         // 16-bit loads
-        Instruction::LoadImmediate16(register, immediate) => match register {
-            Register16::BC => {
-                cpu.C = immediate.get_bits(0..8) as u8;
-                cpu.B = immediate.get_bits(8..16) as u8;
-            }
-            Register16::DE => {
-                cpu.E = immediate.get_bits(0..8) as u8;
-                cpu.D = immediate.get_bits(8..16) as u8;
-                trace!("Loaded DE with {:#X}", cpu.get_de());
-            }
-            Register16::HL => {
-                cpu.L = immediate.get_bits(0..8) as u8;
-                cpu.H = immediate.get_bits(8..16) as u8;
-            }
-            Register16::SP => {
-                cpu.SP = immediate;
-            }
-        },
+        Instruction::LoadImmediate16(register, immediate) => {
+            cpu.write_reg(Reg16::from(register), immediate);
+        }
         Instruction::LoadAIndirect(reg_with_addr) => {
             let get_indirect_addr = cpu.get_indirect(reg_with_addr);
-            trace!(
+            trace!(target: "cpu",
                 "Loading A from {:#?} with address {:#X}",
                 reg_with_addr,
                 get_indirect_addr
@@ -130,14 +84,13 @@ pub fn handle_instruction(
             memory_bus.write_u8(addr + 1, cpu.SP.get_bits(8..16) as u8);
         }
         Instruction::LoadSPHL => {
-            debug!("Put {:#X} into SP", cpu.get_hl());
+            debug!(target: "cpu", "Put {:#X} into SP", cpu.get_hl());
             cpu.SP = cpu.get_hl();
         }
         Instruction::LoadHLSP(offset) => {
             let offset = offset as i16 as u16;
             let new_value = cpu.SP.wrapping_add(offset);
-            cpu.H = new_value.get_bits(8..16) as u8;
-            cpu.L = new_value.get_bits(0..8) as u8;
+            cpu.set_hl(new_value);
             cpu.set_flag(Flag::Z, false);
             cpu.set_flag(Flag::N, false);
             cpu.set_flag(Flag::H, ALU::test_add_carry_bit(3, cpu.SP, offset));
@@ -0,0 +1,216 @@
+//! Registers by name
+//!
+//! The instruction set's own operands ([`Register8`], [`Register16`] and friends) only cover what an opcode can encode, and `Register8` includes
+//! `[HL]`. [`Reg8`] and [`Reg16`] name every
+//! register and pair, for the debugger, save states and anything else that picks one at
+//! runtime. Pairs are big endian like the hardware's, `B` is the high byte of `BC`.
+use crate::emulator::instructions::{Register16, Register16Stack, Register8};
+
+use super::CPU;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reg8 {
+    A,
+    /// Only the top 4 bits exist, writes clear the rest
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+}
+
+impl Reg8 {
+    /// In save state order
+    pub const ALL: [Reg8; 8] = [
+        Reg8::A,
+        Reg8::F,
+        Reg8::B,
+        Reg8::C,
+        Reg8::D,
+        Reg8::E,
+        Reg8::H,
+        Reg8::L,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Reg8::A => "A",
+            Reg8::F => "F",
+            Reg8::B => "B",
+            Reg8::C => "C",
+            Reg8::D => "D",
+            Reg8::E => "E",
+            Reg8::H => "H",
+            Reg8::L => "L",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reg16 {
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Reg16 {
+    pub const ALL: [Reg16; 6] = [
+        Reg16::AF,
+        Reg16::BC,
+        Reg16::DE,
+        Reg16::HL,
+        Reg16::SP,
+        Reg16::PC,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Reg16::AF => "AF",
+            Reg16::BC => "BC",
+            Reg16::DE => "DE",
+            Reg16::HL => "HL",
+            Reg16::SP => "SP",
+            Reg16::PC => "PC",
+        }
+    }
+}
+
+/// Fails for `[HL]`, which is memory
+impl TryFrom<Register8> for Reg8 {
+    type Error = ();
+
+    fn try_from(register: Register8) -> Result<Self, ()> {
+        match register {
+            Register8::A => Ok(Reg8::A),
+            Register8::B => Ok(Reg8::B),
+            Register8::C => Ok(Reg8::C),
+            Register8::D => Ok(Reg8::D),
+            Register8::E => Ok(Reg8::E),
+            Register8::H => Ok(Reg8::H),
+            Register8::L => Ok(Reg8::L),
+            Register8::IndirectHL => Err(()),
+        }
+    }
+}
+
+impl From<Register16> for Reg16 {
+    fn from(register: Register16) -> Self {
+        match register {
+            Register16::BC => Reg16::BC,
+            Register16::DE => Reg16::DE,
+            Register16::HL => Reg16::HL,
+            Register16::SP => Reg16::SP,
+        }
+    }
+}
+
+impl From<Register16Stack> for Reg16 {
+    fn from(register: Register16Stack) -> Self {
+        match register {
+            Register16Stack::BC => Reg16::BC,
+            Register16Stack::DE => Reg16::DE,
+            Register16Stack::HL => Reg16::HL,
+            Register16Stack::AF => Reg16::AF,
+        }
+    }
+}
+
+/// [`Reg8`] or [`Reg16`], see [`CPU::read_reg`]
+pub trait Reg: Copy {
+    type Value;
+
+    fn read(self, cpu: &CPU) -> Self::Value;
+
+    fn write(self, cpu: &mut CPU, value: Self::Value);
+}
+
+impl Reg for Reg8 {
+    type Value = u8;
+
+    fn read(self, cpu: &CPU) -> u8 {
+        match self {
+            Reg8::A => cpu.Accumulator,
+            Reg8::F => cpu.Flags,
+            Reg8::B => cpu.B,
+            Reg8::C => cpu.C,
+            Reg8::D => cpu.D,
+            Reg8::E => cpu.E,
+            Reg8::H => cpu.H,
+            Reg8::L => cpu.L,
+        }
+    }
+
+    fn write(self, cpu: &mut CPU, value: u8) {
+        match self {
+            Reg8::A => cpu.Accumulator = value,
+            Reg8::F => cpu.Flags = value & 0xF0,
+            Reg8::B => cpu.B = value,
+            Reg8::C => cpu.C = value,
+            Reg8::D => cpu.D = value,
+            Reg8::E => cpu.E = value,
+            Reg8::H => cpu.H = value,
+            Reg8::L => cpu.L = value,
+        }
+    }
+}
+
+impl Reg for Reg16 {
+    type Value = u16;
+
+    fn read(self, cpu: &CPU) -> u16 {
+        match self {
+            Reg16::AF => cpu.get_af(),
+            Reg16::BC => cpu.get_bc(),
+            Reg16::DE => cpu.get_de(),
+            Reg16::HL => cpu.get_hl(),
+            Reg16::SP => cpu.SP,
+            Reg16::PC => cpu.PC,
+        }
+    }
+
+    fn write(self, cpu: &mut CPU, value: u16) {
+        match self {
+            Reg16::AF => cpu.set_af(value),
+            Reg16::BC => cpu.set_bc(value),
+            Reg16::DE => cpu.set_de(value),
+            Reg16::HL => cpu.set_hl(value),
+            Reg16::SP => cpu.SP = value,
+            Reg16::PC => cpu.PC = value,
+        }
+    }
+}
+
+impl CPU {
+    /// `cpu.read_reg(Reg8::A)` is a `u8`, `cpu.read_reg(Reg16::HL)` a `u16`
+    pub fn read_reg<R: Reg>(&self, register: R) -> R::Value {
+        register.read(self)
+    }
+
+    pub fn write_reg<R: Reg>(&mut self, register: R, value: R::Value) {
+        register.write(self, value)
+    }
+
+    /// The low 4 bits of F don't exist, so they're dropped
+    pub fn set_af(&mut self, value: u16) {
+        let [a, f] = value.to_be_bytes();
+        self.Accumulator = a;
+        self.Flags = f & 0xF0;
+    }
+
+    pub fn set_bc(&mut self, value: u16) {
+        [self.B, self.C] = value.to_be_bytes();
+    }
+
+    pub fn set_de(&mut self, value: u16) {
+        [self.D, self.E] = value.to_be_bytes();
+    }
+
+    pub fn set_hl(&mut self, value: u16) {
+        [self.H, self.L] = value.to_be_bytes();
+    }
+}
@@ -0,0 +1,395 @@
+//! Pausing and stepping the emulator thread
+//!
+//! The GUI sends [`DebugCommand`]s as [`Command::Debug`](crate::emulator::Command::Debug),
+//! which are applied on frame boundaries like every other command. While paused the thread
+//! waits for commands instead of running frames. Every time it stops it sends a [`DebugView`]
+//! back, so the GUI never has to look at the emulator directly.
+//!
+//! Step over, step out and run to work with a temporary stop: the thread keeps running frames,
+//! checking it after every instruction, and pauses once it's reached. Breakpoints are checked
+//! the same way, with their condition evaluated when PC gets to them. [`BreakOn`] pauses at the
+//! start of an interrupt handler or RST vector, with whatever got there on the call stack.
+//! [`Watchpoint`]s pause right after the instruction that touched the memory they watch.
+use std::sync::mpsc::Sender;
+
+use crate::emulator::{
+    cpu::{call_stack::FrameKind, Flag},
+    error::Crash,
+    instructions::Instruction,
+    memory_bus::{
+        access_stats::{Accesses, Region},
+        Interrupt,
+    },
+    Emulator,
+};
+
+pub mod expression;
+use expression::Expression;
+pub mod watchpoints;
+use watchpoints::{Watchpoint, Watchpoints};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebugCommand {
+    Pause,
+    Continue,
+    /// Runs one instruction, pausing first if needed
+    Step,
+    /// Like [`DebugCommand::Step`], but runs a CALL or RST until it returns
+    StepOver,
+    /// Runs until the innermost frame of the call stack returns
+    StepOut,
+    /// Runs until PC is this address
+    RunTo(u16),
+    /// Replaces every breakpoint
+    SetBreakpoints(Vec<Breakpoint>),
+    SetBreakOn(BreakOn),
+    /// Replaces every watchpoint
+    SetWatchpoints(Vec<Watchpoint>),
+    /// Writes these bytes into memory, see [`MemoryBus::patch_bytes`](crate::emulator::memory_bus::MemoryBus::patch_bytes)
+    Patch {
+        addr: u16,
+        bytes: Vec<u8>,
+    },
+    /// Replaces the instruction at this address with NOPs
+    NopOut(u16),
+}
+
+/// Control flow to pause on, right after it happens
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BreakOn {
+    /// When these are serviced
+    pub interrupts: Vec<Interrupt>,
+    /// When an RST to one of these runs
+    pub rst_vectors: Vec<u16>,
+}
+
+impl BreakOn {
+    fn is_empty(&self) -> bool {
+        self.interrupts.is_empty() && self.rst_vectors.is_empty()
+    }
+
+    fn hit(&self, emulator: &Emulator) -> bool {
+        match emulator.cpu().call_stack.entered().map(|frame| frame.kind) {
+            Some(FrameKind::Interrupt(interrupt)) => self.interrupts.contains(&interrupt),
+            Some(FrameKind::Rst(vector)) => self.rst_vectors.contains(&vector),
+            Some(FrameKind::Call) | None => false,
+        }
+    }
+}
+
+/// Pauses before the instruction at `addr` runs, if `condition` is true then
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub condition: Option<Expression>,
+}
+
+impl Breakpoint {
+    fn hit(&self, emulator: &Emulator) -> bool {
+        emulator.cpu().PC == self.addr
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.is_true(emulator))
+    }
+}
+
+/// Where a temporary stop is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stop {
+    /// At `addr` with no more than `depth` frames, so a recursive call doesn't count
+    At { addr: u16, depth: usize },
+    /// When the call stack gets shallower than `depth`
+    Return { depth: usize },
+}
+
+impl Stop {
+    fn reached(self, emulator: &Emulator) -> bool {
+        let depth = emulator.cpu().call_stack.frames().len();
+        match self {
+            Stop::At { addr, depth: max } => emulator.cpu().PC == addr && depth <= max,
+            Stop::Return { depth: start } => depth < start,
+        }
+    }
+}
+
+/// What the debugger shows while paused
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugView {
+    pub paused: bool,
+    /// Where the CPU is and what it runs next
+    pub next: String,
+    pub registers: String,
+    /// Innermost first, starting with where the CPU is
+    pub backtrace: Vec<String>,
+    /// Only with access stats enabled
+    pub access: Option<AccessView>,
+    /// The access that paused it, if a watchpoint did
+    pub watch_hit: Option<String>,
+}
+
+/// A summary of [`AccessStats`](crate::emulator::memory_bus::access_stats::AccessStats)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessView {
+    pub regions: Vec<(Region, Accesses)>,
+    /// Most accessed first, with their labels
+    pub hottest: Vec<String>,
+    /// See [`AccessStats::heatmap`](crate::emulator::memory_bus::access_stats::AccessStats::heatmap)
+    pub heatmap: Vec<u8>,
+}
+
+impl DebugView {
+    pub fn new(emulator: &Emulator, paused: bool) -> Self {
+        let pc = emulator.cpu().PC;
+        let next = match Instruction::parse(&emulator.memory_bus().get_instr(pc)) {
+            Ok((_, instruction)) => format!("{}: {}", describe(emulator, pc), instruction),
+            Err(_) => format!("{}: illegal instruction", describe(emulator, pc)),
+        };
+        Self {
+            paused,
+            next,
+            registers: registers(emulator),
+            backtrace: backtrace(emulator),
+            watch_hit: emulator
+                .memory_bus()
+                .hook::<Watchpoints>()
+                .and_then(|watchpoints| watchpoints.hit)
+                .map(|hit| hit.to_string()),
+            access: emulator
+                .memory_bus()
+                .access_stats()
+                .map(|stats| AccessView {
+                    regions: Region::ALL
+                        .into_iter()
+                        .map(|region| (region, stats.region(region)))
+                        .collect(),
+                    hottest: stats
+                        .hottest(8)
+                        .into_iter()
+                        .map(|(addr, accesses)| {
+                            // Only exact labels, IO registers aren't part of the variable before
+                            let label = emulator.symbols().get(emulator.location(addr));
+                            let line = format!(
+                                "{:#06X} R {} W {} {}",
+                                addr,
+                                accesses.reads,
+                                accesses.writes,
+                                label.unwrap_or_default()
+                            );
+                            line.trim_end().to_string()
+                        })
+                        .collect(),
+                    heatmap: stats.heatmap(),
+                }),
+        }
+    }
+}
+
+/// `0x0150 Main+0x3`, or just the address without a label
+pub fn describe(emulator: &Emulator, addr: u16) -> String {
+    match emulator.symbols().describe(emulator.location(addr)) {
+        Some(symbol) => format!("{:#06X} {}", addr, symbol),
+        None => format!("{:#06X}", addr),
+    }
+}
+
+fn registers(emulator: &Emulator) -> String {
+    let cpu = emulator.cpu();
+    let flags: String = [
+        (Flag::Z, 'Z'),
+        (Flag::N, 'N'),
+        (Flag::H, 'H'),
+        (Flag::C, 'C'),
+    ]
+    .into_iter()
+    .map(|(flag, name)| if cpu.get_flag(flag) { name } else { '-' })
+    .collect();
+    format!(
+        "AF {:04X}  BC {:04X}  DE {:04X}  HL {:04X}\nSP {:04X}  PC {:04X}  {}  IME {}{}",
+        cpu.get_af(),
+        cpu.get_bc(),
+        cpu.get_de(),
+        cpu.get_hl(),
+        cpu.SP,
+        cpu.PC,
+        flags,
+        cpu.IME as u8,
+        if cpu.halted { "  HALT" } else { "" },
+    )
+}
+
+/// One line per frame of the shadow call stack, innermost first
+pub fn backtrace(emulator: &Emulator) -> Vec<String> {
+    let frames = emulator.cpu().call_stack.frames();
+    let current = format!("#0 {}", describe(emulator, emulator.cpu().PC));
+    let callers = frames.iter().rev().enumerate().map(|(i, frame)| {
+        let entry = match frame.kind {
+            FrameKind::Call => "called".to_string(),
+            FrameKind::Rst(vector) => format!("RST {:02X}", vector),
+            FrameKind::Interrupt(interrupt) => format!("{:?} interrupt", interrupt),
+        };
+        format!(
+            "#{} {} ({} {})",
+            i + 1,
+            describe(emulator, frame.call_site()),
+            entry,
+            describe(emulator, frame.target)
+        )
+    });
+    std::iter::once(current).chain(callers).collect()
+}
+
+/// The emulator thread's side of the debugger
+pub struct Debugger {
+    paused: bool,
+    stop: Option<Stop>,
+    breakpoints: Vec<Breakpoint>,
+    break_on: BreakOn,
+    /// Whether the bus has a [`Watchpoints`] hook
+    watching: bool,
+    views: Sender<DebugView>,
+}
+
+impl Debugger {
+    pub fn new(views: Sender<DebugView>) -> Self {
+        Self {
+            paused: false,
+            stop: None,
+            breakpoints: Vec::new(),
+            break_on: BreakOn::default(),
+            watching: false,
+            views,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns true if a step finished a frame
+    pub fn apply(&mut self, command: DebugCommand, emulator: &mut Emulator) -> Result<bool, Crash> {
+        let depth = emulator.cpu().call_stack.frames().len();
+        let frame_done = match command {
+            DebugCommand::Pause => {
+                self.paused = true;
+                self.stop = None;
+                false
+            }
+            DebugCommand::Continue => {
+                self.paused = false;
+                self.stop = None;
+                false
+            }
+            DebugCommand::StepOver => {
+                match Instruction::parse(&emulator.memory_bus().get_instr(emulator.cpu().PC)) {
+                    Ok((_, instruction)) if instruction.is_call() => {
+                        let addr = emulator.cpu().PC.wrapping_add(instruction.byte_len());
+                        self.run_to(Stop::At { addr, depth })
+                    }
+                    _ => self.step(emulator)?,
+                }
+            }
+            DebugCommand::StepOut if depth > 0 => self.run_to(Stop::Return { depth }),
+            // Nothing to return from
+            DebugCommand::StepOut | DebugCommand::Step => self.step(emulator)?,
+            DebugCommand::RunTo(addr) => self.run_to(Stop::At {
+                addr,
+                depth: usize::MAX,
+            }),
+            // None of these cancel a step over or step out
+            DebugCommand::SetBreakpoints(breakpoints) => {
+                self.breakpoints = breakpoints;
+                false
+            }
+            DebugCommand::SetBreakOn(break_on) => {
+                self.break_on = break_on;
+                false
+            }
+            DebugCommand::SetWatchpoints(watchpoints) => {
+                let memory_bus = emulator.memory_bus_mut();
+                self.watching = !watchpoints.is_empty();
+                if self.watching {
+                    memory_bus.add_hook(Watchpoints {
+                        watchpoints,
+                        hit: None,
+                    });
+                } else {
+                    memory_bus.remove_hook::<Watchpoints>();
+                }
+                false
+            }
+            DebugCommand::Patch { addr, bytes } => {
+                emulator.memory_bus_mut().patch_bytes(addr, &bytes);
+                false
+            }
+            DebugCommand::NopOut(addr) => {
+                let memory_bus = emulator.memory_bus_mut();
+                let len = Instruction::parse(&memory_bus.get_instr(addr))
+                    .map_or(1, |(_, instruction)| instruction.byte_len());
+                memory_bus.patch(addr, &vec![Instruction::Nop; len as usize]);
+                false
+            }
+        };
+        self.send_view(emulator);
+        Ok(frame_done)
+    }
+
+    fn step(&mut self, emulator: &mut Emulator) -> Result<bool, Crash> {
+        self.paused = true;
+        self.stop = None;
+        clear_watch_hit(emulator);
+        emulator.step_catching()
+    }
+
+    fn run_to(&mut self, stop: Stop) -> bool {
+        self.paused = false;
+        self.stop = Some(stop);
+        false
+    }
+
+    fn should_pause(&self, emulator: &Emulator) -> bool {
+        self.stop.is_some_and(|stop| stop.reached(emulator))
+            || self
+                .breakpoints
+                .iter()
+                .any(|breakpoint| breakpoint.hit(emulator))
+            || self.break_on.hit(emulator)
+            || self.watching
+                && emulator
+                    .memory_bus()
+                    .hook::<Watchpoints>()
+                    .is_some_and(|watchpoints| watchpoints.hit.is_some())
+    }
+
+    /// Runs the rest of the frame while not paused, or until a breakpoint or temporary stop is
+    /// reached. Returns true if the frame finished.
+    pub fn run_frame(&mut self, emulator: &mut Emulator) -> Result<bool, Crash> {
+        clear_watch_hit(emulator);
+        if self.stop.is_none()
+            && self.breakpoints.is_empty()
+            && self.break_on.is_empty()
+            && !self.watching
+        {
+            return emulator.run_frame_catching().map(|_| true);
+        }
+        let frame_done = emulator.run_until_catching(|emulator| self.should_pause(emulator))?;
+        if self.should_pause(emulator) {
+            self.stop = None;
+            self.paused = true;
+            self.send_view(emulator);
+        }
+        Ok(frame_done)
+    }
+
+    fn send_view(&self, emulator: &Emulator) {
+        // Nobody listening is fine, the GUI might not have a debugger open
+        let _ = self.views.send(DebugView::new(emulator, self.paused));
+    }
+}
+
+/// Forgets what a watchpoint paused on, so it can pause again
+fn clear_watch_hit(emulator: &mut Emulator) {
+    if let Some(watchpoints) = emulator.memory_bus_mut().hook_mut::<Watchpoints>() {
+        watchpoints.hit = None;
+    }
+}
@@ -0,0 +1,246 @@
+//! Breakpoint conditions
+//!
+//! A C-like expression over the registers, flags and memory, like `A == 0x3C && Z` or
+//! `[0xC123] != 0`. Everything is a 16 bit value and anything nonzero is true.
+//!
+//! - Numbers are decimal, or hex with `0x` or `$`
+//! - Registers are `A B C D E H L F AF BC DE HL SP PC`
+//! - Flags are `Z N` or `ZF NF HF CF`, since `C` and `H` are registers
+//! - `[addr]` reads a byte from the bus
+//! - Operators are `! == != < <= > >= && ||` and parentheses, with the usual precedence
+use std::fmt;
+
+use crate::emulator::{
+    cpu::{
+        registers::{Reg16, Reg8},
+        Flag,
+    },
+    Emulator,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpressionError {
+    /// Byte offset into the expression
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    Number(u16),
+    Reg8(Reg8),
+    Reg16(Reg16),
+    Flag(Flag),
+    /// A byte read from the bus
+    Memory(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser { text, position: 0 };
+        let expression = parser.or()?;
+        parser.skip_whitespace();
+        if parser.position != text.len() {
+            return Err(parser.error("Unexpected input"));
+        }
+        Ok(expression)
+    }
+
+    pub fn eval(&self, emulator: &Emulator) -> u16 {
+        let cpu = emulator.cpu();
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Reg8(register) => cpu.read_reg(*register) as u16,
+            Expression::Reg16(register) => cpu.read_reg(*register),
+            Expression::Flag(flag) => cpu.get_flag(*flag) as u16,
+            Expression::Memory(addr) => emulator.memory_bus().peek(addr.eval(emulator)) as u16,
+            Expression::Not(value) => (value.eval(emulator) == 0) as u16,
+            Expression::Binary(op, left, right) => {
+                let left = left.eval(emulator);
+                // Short circuit so `[addr]` on the right isn't read for nothing
+                match op {
+                    BinaryOp::And if left == 0 => return 0,
+                    BinaryOp::Or if left != 0 => return 1,
+                    _ => {}
+                }
+                let right = right.eval(emulator);
+                let result = match op {
+                    BinaryOp::Equal => left == right,
+                    BinaryOp::NotEqual => left != right,
+                    BinaryOp::Less => left < right,
+                    BinaryOp::LessEqual => left <= right,
+                    BinaryOp::Greater => left > right,
+                    BinaryOp::GreaterEqual => left >= right,
+                    BinaryOp::And | BinaryOp::Or => right != 0,
+                };
+                result as u16
+            }
+        }
+    }
+
+    pub fn is_true(&self, emulator: &Emulator) -> bool {
+        self.eval(emulator) != 0
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ExpressionError {
+        ExpressionError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if it's next
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expression::Binary(BinaryOp::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.comparison()?;
+        while self.eat("&&") {
+            left = Expression::Binary(BinaryOp::And, Box::new(left), Box::new(self.comparison()?));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let left = self.unary()?;
+        // Longest first so `<=` isn't read as `<`
+        const OPERATORS: [(&str, BinaryOp); 6] = [
+            ("==", BinaryOp::Equal),
+            ("!=", BinaryOp::NotEqual),
+            ("<=", BinaryOp::LessEqual),
+            (">=", BinaryOp::GreaterEqual),
+            ("<", BinaryOp::Less),
+            (">", BinaryOp::Greater),
+        ];
+        for (token, op) in OPERATORS {
+            if self.eat(token) {
+                return Ok(Expression::Binary(
+                    op,
+                    Box::new(left),
+                    Box::new(self.unary()?),
+                ));
+            }
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.eat("!") {
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let inner = self.or()?;
+            return match self.eat(")") {
+                true => Ok(inner),
+                false => Err(self.error("Expected ')'")),
+            };
+        }
+        if self.eat("[") {
+            let addr = self.or()?;
+            return match self.eat("]") {
+                true => Ok(Expression::Memory(Box::new(addr))),
+                false => Err(self.error("Expected ']'")),
+            };
+        }
+
+        self.skip_whitespace();
+        let start = self.position;
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '$'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("Expected a value"));
+        }
+        let word = &self.rest()[..len];
+        let atom = atom(word).ok_or(ExpressionError {
+            position: start,
+            message: "Unknown name or bad number",
+        })?;
+        self.position += len;
+        Ok(atom)
+    }
+}
+
+fn atom(word: &str) -> Option<Expression> {
+    let number = if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix('$')) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+        word.parse().ok()
+    } else {
+        None
+    };
+    if let Some(number) = number {
+        return Some(Expression::Number(number));
+    }
+
+    let name = word.to_ascii_uppercase();
+    match name.as_str() {
+        "Z" | "ZF" => return Some(Expression::Flag(Flag::Z)),
+        "N" | "NF" => return Some(Expression::Flag(Flag::N)),
+        "HF" => return Some(Expression::Flag(Flag::H)),
+        "CF" => return Some(Expression::Flag(Flag::C)),
+        _ => {}
+    }
+    let byte = Reg8::ALL
+        .into_iter()
+        .find(|register| register.name() == name);
+    let pair = Reg16::ALL
+        .into_iter()
+        .find(|register| register.name() == name);
+    byte.map(Expression::Reg8)
+        .or_else(|| pair.map(Expression::Reg16))
+}
@@ -0,0 +1,95 @@
+//! Pausing when the CPU reads or writes memory
+//!
+//! [`Watchpoints`] is a [`BusHook`], so it only sees the CPU's data accesses. The first hit is
+//! remembered and the debugger pauses once the instruction that made it has finished.
+use std::{fmt, ops::RangeInclusive};
+
+use crate::emulator::memory_bus::hooks::BusHook;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match (self.read, self.write) {
+            (true, true) => "RW",
+            (true, false) => "R",
+            (false, _) => "W",
+        };
+        if self.range.start() == self.range.end() {
+            write!(f, "{:#06X} {}", self.range.start(), kind)
+        } else {
+            write!(
+                f,
+                "{:#06X}-{:#06X} {}",
+                self.range.start(),
+                self.range.end(),
+                kind
+            )
+        }
+    }
+}
+
+impl Watchpoint {
+    /// Whether a write, or a read if not, to `addr` hits it
+    pub fn watches(&self, addr: u16, write: bool) -> bool {
+        let kind = if write { self.write } else { self.read };
+        kind && self.range.contains(&addr)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,
+    /// What was read, or what was about to be written
+    pub value: u8,
+    pub write: bool,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.write {
+            write!(f, "Write of {:#04X} to {:#06X}", self.value, self.addr)
+        } else {
+            write!(f, "Read of {:#04X} from {:#06X}", self.value, self.addr)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    pub watchpoints: Vec<Watchpoint>,
+    /// The first access since this was last cleared
+    pub hit: Option<WatchHit>,
+}
+
+impl Watchpoints {
+    fn check(&mut self, addr: u16, value: u8, write: bool) {
+        if self.hit.is_some() {
+            return;
+        }
+        let watched = self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.watches(addr, write));
+        if watched {
+            self.hit = Some(WatchHit { addr, value, write });
+        }
+    }
+}
+
+impl BusHook for Watchpoints {
+    fn read(&mut self, addr: u16, value: u8) -> u8 {
+        self.check(addr, value, false);
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Option<u8> {
+        self.check(addr, value, true);
+        Some(value)
+    }
+}
@@ -0,0 +1,77 @@
+//! Things that stop emulation
+use std::fmt;
+
+use crate::emulator::watchdog::Hang;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmulatorError {
+    /// Read from memory that isn't emulated
+    UnmappedRead(u16),
+    /// Write to memory that isn't emulated
+    UnmappedWrite { addr: u16, value: u8 },
+    /// Opcode that doesn't exist, real hardware locks up on these
+    IllegalInstruction { pc: u16, opcode: u8 },
+    /// Valid instruction the CPU doesn't handle yet
+    UnimplementedInstruction { pc: u16, instruction: String },
+    /// The [watchdog](crate::emulator::watchdog) gave up
+    Hung {
+        hang: Hang,
+        /// T-cycles it's been that way
+        cycles: u64,
+        pc: u16,
+        ie: u8,
+        if_: u8,
+        ime: bool,
+    },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::UnmappedRead(addr) => {
+                write!(f, "Read from unmapped memory {:#06X}", addr)
+            }
+            EmulatorError::UnmappedWrite { addr, value } => {
+                write!(
+                    f,
+                    "Write of {:#04X} to unmapped memory {:#06X}",
+                    value, addr
+                )
+            }
+            EmulatorError::IllegalInstruction { pc, opcode } => {
+                write!(f, "Illegal instruction {:#04X} at {:#06X}", opcode, pc)
+            }
+            EmulatorError::UnimplementedInstruction { pc, instruction } => {
+                write!(
+                    f,
+                    "Instruction {} at {:#06X} isn't implemented",
+                    instruction, pc
+                )
+            }
+            EmulatorError::Hung {
+                hang,
+                cycles,
+                pc,
+                ie,
+                if_,
+                ime,
+            } => {
+                write!(
+                    f,
+                    "Emulation is stuck, {} for {} cycles at {:#06X} (IE {:#04X} IF {:#04X} IME {})",
+                    hang, cycles, pc, ie, if_, *ime as u8
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {}
+
+/// Why the emulator thread stopped, for the frontend to show
+#[derive(Clone, Debug)]
+pub struct Crash {
+    pub message: String,
+    /// The CPU's `Display` output when it happened, and the last instructions it ran
+    pub cpu_dump: String,
+}
@@ -0,0 +1,165 @@
+//! Differences between Game Boy models
+use std::{fmt, str::FromStr};
+
+/// CPU registers as each model's boot ROM leaves them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PostBootRegisters {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+}
+
+/// The boot ROM dump a model runs at power on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BootRom {
+    /// What the dump is usually called
+    pub file_name: &'static str,
+    pub size: usize,
+}
+
+/// Which console is being emulated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HardwareModel {
+    /// The first Game Boy CPU revision, only sold in Japan
+    Dmg0,
+    #[default]
+    Dmg,
+    /// Game Boy Pocket and Light
+    Mgb,
+    Sgb,
+    Sgb2,
+    /// Game Boy Color, revision E
+    Cgb,
+    /// Game Boy Advance
+    Agb,
+}
+
+impl HardwareModel {
+    pub const ALL: [HardwareModel; 7] = [
+        HardwareModel::Dmg0,
+        HardwareModel::Dmg,
+        HardwareModel::Mgb,
+        HardwareModel::Sgb,
+        HardwareModel::Sgb2,
+        HardwareModel::Cgb,
+        HardwareModel::Agb,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HardwareModel::Dmg0 => "dmg0",
+            HardwareModel::Dmg => "dmg",
+            HardwareModel::Mgb => "mgb",
+            HardwareModel::Sgb => "sgb",
+            HardwareModel::Sgb2 => "sgb2",
+            HardwareModel::Cgb => "cgb",
+            HardwareModel::Agb => "agb",
+        }
+    }
+
+    /// Registers when the boot ROM hands over to the cartridge at 0x100. `header_checksum`
+    /// is the byte at 0x14D, the DMG and MGB boot ROMs leave H and C set unless it's 0.
+    pub fn post_boot_registers(self, header_checksum: u8) -> PostBootRegisters {
+        let checksum_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+        let (a, f, b, c, d, e, h, l) = match self {
+            HardwareModel::Dmg0 => (0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03),
+            HardwareModel::Dmg => (0x01, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            HardwareModel::Mgb => (0xFF, checksum_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            HardwareModel::Sgb => (0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            HardwareModel::Sgb2 => (0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            HardwareModel::Cgb => (0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+            HardwareModel::Agb => (0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D),
+        };
+        PostBootRegisters {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+        }
+    }
+
+    pub fn boot_rom(self) -> BootRom {
+        let (file_name, size) = match self {
+            HardwareModel::Dmg0 => ("dmg0_boot.bin", 0x100),
+            HardwareModel::Dmg => ("dmg_boot.bin", 0x100),
+            HardwareModel::Mgb => ("mgb_boot.bin", 0x100),
+            HardwareModel::Sgb => ("sgb_boot.bin", 0x100),
+            HardwareModel::Sgb2 => ("sgb2_boot.bin", 0x100),
+            // Both have 0x100-0x1FF unmapped for the cartridge header
+            HardwareModel::Cgb => ("cgb_boot.bin", 0x900),
+            HardwareModel::Agb => ("agb_boot.bin", 0x900),
+        };
+        BootRom { file_name, size }
+    }
+
+    /// Whether 16-bit INC/DEC of an OAM address during mode 2 corrupt OAM, see
+    /// [`MemoryBus::oam_bug`](crate::emulator::memory_bus::MemoryBus::oam_bug)
+    pub fn has_oam_bug(self) -> bool {
+        !matches!(self, HardwareModel::Cgb | HardwareModel::Agb)
+    }
+
+    /// Whether writing STAT briefly enables every STAT interrupt source, see
+    /// [`MemoryBus::write_u8`](crate::emulator::memory_bus::MemoryBus::write_u8)
+    pub fn has_stat_write_bug(self) -> bool {
+        !matches!(self, HardwareModel::Cgb | HardwareModel::Agb)
+    }
+
+    /// Game Boy Color and Advance, which run games that support it in CGB mode, see
+    /// [`MemoryBus::cgb_mode`](crate::emulator::memory_bus::MemoryBus::cgb_mode)
+    pub fn is_color(self) -> bool {
+        matches!(self, HardwareModel::Cgb | HardwareModel::Agb)
+    }
+
+    /// What reading the prohibited area at FEA0-FEFF gives, writes there are always ignored.
+    /// CGB revisions before E have a small RAM there instead, which isn't emulated.
+    pub fn prohibited_read(self, addr: u16, oam_blocked: bool) -> u8 {
+        if oam_blocked {
+            return 0xFF;
+        }
+        match self {
+            HardwareModel::Dmg0
+            | HardwareModel::Dmg
+            | HardwareModel::Mgb
+            | HardwareModel::Sgb
+            | HardwareModel::Sgb2 => 0x00,
+            // The high nibble of the address' low byte, twice
+            HardwareModel::Cgb | HardwareModel::Agb => {
+                let nibble = (addr as u8) >> 4;
+                nibble << 4 | nibble
+            }
+        }
+    }
+}
+
+impl fmt::Display for HardwareModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HardwareModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|model| model.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|model| model.name()).collect();
+                format!(
+                    "Unknown hardware model '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
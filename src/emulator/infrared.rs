@@ -0,0 +1,214 @@
+//! Infrared port of the CGB
+//!
+//! RP has an LED the game switches on and off and a sensor it can read. There's no protocol in
+//! hardware, games time the pulses themselves, so an [`InfraredLink`] is told about every
+//! change of the LED along with the T-cycle it happened on. The other side's changes are
+//! played back with the same spacing, see [`Incoming`]. RP is only mapped in
+//! [CGB mode](crate::emulator::memory_bus::MemoryBus::cgb_mode).
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    ops::RangeInclusive,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use bit_field::BitField;
+use tracing::trace;
+
+use crate::emulator::{
+    memory_bus::{mmio::MmioDevice, Interrupt},
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub mod file;
+pub mod tcp;
+
+pub const RP: u16 = 0xFF56;
+
+/// Changes sent further apart than a frame are taken as a new burst, played from when it arrives
+pub const BURST_GAP: u64 = 70224;
+
+/// Whatever the port is pointed at
+pub trait InfraredLink: Send + Debug {
+    /// The LED was switched on or off at `cycle`
+    fn led(&mut self, cycle: u64, on: bool);
+
+    /// Whether light reaches the sensor at `cycle`, asked after every instruction whether the
+    /// game is reading or not
+    fn light(&mut self, cycle: u64) -> bool;
+}
+
+#[derive(Debug, Default)]
+pub struct Infrared {
+    /// RP bit 0
+    led: bool,
+    /// RP bits 6-7, the sensor only reads anything with both set
+    read_enable: u8,
+    /// What the link said last tick, only seen while reading
+    lit: bool,
+    /// T-cycles since power on, it carries on over loading a state
+    cycle: u64,
+    link: Option<Box<dyn InfraredLink>>,
+}
+
+impl Infrared {
+    pub fn connect(&mut self, link: Box<dyn InfraredLink>) {
+        self.link = Some(link);
+    }
+
+    /// Back to how it powers on, still pointed at the same link
+    pub fn reset(&mut self) {
+        *self = Self {
+            link: self.link.take(),
+            ..Self::default()
+        };
+    }
+
+    fn reading(&self) -> bool {
+        self.read_enable == 0b11
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.led);
+        state.u8(self.read_enable);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.led = state.bool()?;
+        self.read_enable = state.u8()? & 0b11;
+        Ok(())
+    }
+}
+
+impl MmioDevice for Infrared {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[RP..=RP]
+    }
+
+    /// Bit 1 is clear while light is coming in, bits 2-5 aren't used
+    fn read(&self, _addr: u16) -> u8 {
+        let receiving = self.reading() && self.lit;
+        self.read_enable << 6 | 0b0011_1100 | (!receiving as u8) << 1 | self.led as u8
+    }
+
+    fn write(&mut self, _addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "serial", "RP write: {:#X}", byte);
+        self.read_enable = byte.get_bits(6..8);
+        let led = byte.get_bit(0);
+        if led != self.led {
+            self.led = led;
+            if let Some(link) = self.link.as_mut() {
+                link.led(self.cycle, led);
+            }
+        }
+        None
+    }
+
+    fn tick(&mut self, cycles: u32) -> Option<Interrupt> {
+        self.cycle += cycles as u64;
+        self.lit = match self.link.as_mut() {
+            Some(link) => link.light(self.cycle),
+            None => false,
+        };
+        None
+    }
+}
+
+/// Sees its own LED, like holding the port up to a mirror
+#[derive(Debug, Default)]
+pub struct Loopback {
+    on: bool,
+}
+
+impl InfraredLink for Loopback {
+    fn led(&mut self, _cycle: u64, on: bool) {
+        self.on = on;
+    }
+
+    fn light(&mut self, _cycle: u64) -> bool {
+        self.on
+    }
+}
+
+/// The other side's LED as it reaches us. The two sides count cycles from different points,
+/// so each burst of changes starts when its first one is seen and the rest keep the spacing
+/// they were sent with.
+#[derive(Debug, Default)]
+pub struct Incoming {
+    /// Their cycle and what the LED changed to
+    pending: VecDeque<(u64, bool)>,
+    /// Our cycle minus theirs for the current burst
+    offset: Option<u64>,
+    /// Their cycle of the last change played
+    last: u64,
+    lit: bool,
+}
+
+impl Incoming {
+    pub fn push(&mut self, cycle: u64, on: bool) {
+        self.pending.push_back((cycle, on));
+    }
+
+    /// Plays every change due by `cycle`
+    pub fn light(&mut self, cycle: u64) -> bool {
+        while let Some(&(theirs, on)) = self.pending.front() {
+            let offset = self
+                .offset
+                .filter(|offset| {
+                    let due = theirs.wrapping_add(*offset);
+                    // Far ahead means a state was loaded on one side or the other
+                    theirs.wrapping_sub(self.last) < BURST_GAP
+                        && due.saturating_sub(cycle) < BURST_GAP
+                })
+                .unwrap_or_else(|| cycle.wrapping_sub(theirs));
+            if theirs.wrapping_add(offset) > cycle {
+                break;
+            }
+            self.pending.pop_front();
+            self.offset = Some(offset);
+            self.last = theirs;
+            self.lit = on;
+        }
+        self.lit
+    }
+}
+
+/// One end of an infrared link between two emulators in the same process
+#[derive(Debug)]
+pub struct ChannelLink {
+    sender: Sender<(u64, bool)>,
+    receiver: Receiver<(u64, bool)>,
+    incoming: Incoming,
+}
+
+/// Two ports pointed at each other
+pub fn link_pair() -> (ChannelLink, ChannelLink) {
+    let (a_sender, b_receiver) = mpsc::channel();
+    let (b_sender, a_receiver) = mpsc::channel();
+    (
+        ChannelLink {
+            sender: a_sender,
+            receiver: a_receiver,
+            incoming: Incoming::default(),
+        },
+        ChannelLink {
+            sender: b_sender,
+            receiver: b_receiver,
+            incoming: Incoming::default(),
+        },
+    )
+}
+
+impl InfraredLink for ChannelLink {
+    fn led(&mut self, cycle: u64, on: bool) {
+        // Nobody's looking once the other side is gone
+        let _ = self.sender.send((cycle, on));
+    }
+
+    fn light(&mut self, cycle: u64) -> bool {
+        for (theirs, on) in self.receiver.try_iter() {
+            self.incoming.push(theirs, on);
+        }
+        self.incoming.light(cycle)
+    }
+}
@@ -0,0 +1,97 @@
+//! Infrared to and from files
+//!
+//! A file has a line for each change of the LED, the cycle it happened on and 1 for on or 0
+//! for off, like `1234567 1`. [`FileLink::record`] writes what the game sends and
+//! [`FileLink::play`] shines a recording back at it on the same cycles, so it lines up with a
+//! run that got there the same way, like playing the movie it was recorded with.
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use tracing::error;
+
+use super::InfraredLink;
+
+#[derive(Debug)]
+pub struct FileLink {
+    recording: Option<BufWriter<File>>,
+    playback: VecDeque<(u64, bool)>,
+    lit: bool,
+}
+
+impl FileLink {
+    /// Writes changes to `path` and sees nothing
+    pub fn record(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            recording: Some(BufWriter::new(File::create(path)?)),
+            playback: VecDeque::new(),
+            lit: false,
+        })
+    }
+
+    /// Plays back a file written by [`FileLink::record`]
+    pub fn play(path: &Path) -> io::Result<Self> {
+        let changes = parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            recording: None,
+            playback: changes.into(),
+            lit: false,
+        })
+    }
+}
+
+/// The changes in a recording, they have to be in order
+pub fn parse(text: &str) -> Result<Vec<(u64, bool)>, String> {
+    let mut changes: Vec<(u64, bool)> = Vec::new();
+    for (i, line) in text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+    {
+        let change = match line.split_once(' ') {
+            Some((cycle, "0")) => cycle.parse().ok().map(|cycle| (cycle, false)),
+            Some((cycle, "1")) => cycle.parse().ok().map(|cycle| (cycle, true)),
+            _ => None,
+        };
+        match change {
+            Some(change) if changes.last().is_none_or(|last| last.0 <= change.0) => {
+                changes.push(change)
+            }
+            Some(_) => return Err(format!("Line {} goes back in time", i + 1)),
+            None => {
+                return Err(format!(
+                    "Line {} isn't a cycle and 0 or 1: '{}'",
+                    i + 1,
+                    line
+                ))
+            }
+        }
+    }
+    Ok(changes)
+}
+
+impl InfraredLink for FileLink {
+    fn led(&mut self, cycle: u64, on: bool) {
+        if let Some(recording) = self.recording.as_mut() {
+            if let Err(e) = writeln!(recording, "{} {}", cycle, on as u8) {
+                error!(target: "serial", "Failed to record infrared, stopping: {}", e);
+                self.recording = None;
+            }
+        }
+    }
+
+    fn light(&mut self, cycle: u64) -> bool {
+        while let Some(&(recorded, on)) = self.playback.front() {
+            if recorded > cycle {
+                break;
+            }
+            self.playback.pop_front();
+            self.lit = on;
+        }
+        self.lit
+    }
+}
@@ -0,0 +1,104 @@
+//! Infrared over TCP
+//!
+//! After connecting both sides send `GBIR` and a version byte. From then on each change of the
+//! LED is 9 bytes, the cycle it happened on (big endian u64) and 1 for on or 0 for off. Like
+//! the [link cable](crate::emulator::serial::tcp) the socket is bridged to a [`ChannelLink`]
+//! by two threads.
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    thread,
+};
+
+use tracing::{info, warn};
+
+use super::{ChannelLink, Incoming};
+use crate::emulator::serial::tcp::LinkError;
+
+pub const MAGIC: &[u8; 4] = b"GBIR";
+pub const VERSION: u8 = 1;
+
+/// Waits for one instance to connect on `addr`
+pub fn host<A: ToSocketAddrs>(addr: A) -> Result<ChannelLink, LinkError> {
+    accept(&TcpListener::bind(addr)?)
+}
+
+pub fn accept(listener: &TcpListener) -> Result<ChannelLink, LinkError> {
+    info!(target: "serial", "Waiting for infrared partner on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    info!(target: "serial", "Infrared partner connected from {}", peer);
+    bridge(stream)
+}
+
+pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<ChannelLink, LinkError> {
+    let stream = TcpStream::connect(addr)?;
+    info!(target: "serial", "Connected to infrared partner {}", stream.peer_addr()?);
+    bridge(stream)
+}
+
+fn handshake(stream: &mut TcpStream) -> Result<(), LinkError> {
+    let mut ours = [0; 5];
+    ours[0..4].copy_from_slice(MAGIC);
+    ours[4] = VERSION;
+    stream.write_all(&ours)?;
+
+    let mut theirs = [0; 5];
+    stream.read_exact(&mut theirs)?;
+    if &theirs[0..4] != MAGIC {
+        return Err(LinkError::BadMagic);
+    }
+    if theirs[4] != VERSION {
+        return Err(LinkError::UnsupportedVersion(theirs[4]));
+    }
+    Ok(())
+}
+
+fn bridge(mut stream: TcpStream) -> Result<ChannelLink, LinkError> {
+    handshake(&mut stream)?;
+    // Pulses are timed on arrival, don't let Nagle bunch them up
+    stream.set_nodelay(true)?;
+
+    let (sender, remote_receiver) = mpsc::channel();
+    let (remote_sender, receiver) = mpsc::channel();
+
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || loop {
+        let mut message = [0; 9];
+        if let Err(e) = reader.read_exact(&mut message) {
+            warn!(target: "serial", "Infrared partner disconnected: {}", e);
+            break;
+        }
+        let cycle = u64::from_be_bytes(message[0..8].try_into().unwrap());
+        let on = match message[8] {
+            0 => false,
+            1 => true,
+            byte => {
+                warn!(target: "serial", "Bad infrared message {:#X}, disconnecting", byte);
+                break;
+            }
+        };
+        if remote_sender.send((cycle, on)).is_err() {
+            break;
+        }
+    });
+
+    let mut writer = stream;
+    thread::spawn(move || {
+        for (cycle, on) in remote_receiver {
+            let mut message = [0; 9];
+            message[0..8].copy_from_slice(&u64::to_be_bytes(cycle));
+            message[8] = on as u8;
+            if writer.write_all(&message).is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    Ok(ChannelLink {
+        sender,
+        receiver,
+        incoming: Incoming::default(),
+    })
+}
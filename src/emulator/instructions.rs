@@ -6,6 +6,9 @@ use nom::{
     IResult,
 };
 
+pub mod encode;
+pub mod operands;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Condition {
     NZ,
@@ -461,11 +464,13 @@ impl Instruction {
         }
     }
 
-    /// Ticks in M-cycles (4 T-cycles)
+    /// Ticks in M-cycles (4 T-cycles). `action_taken` is whether a conditional jump, call or
+    /// return went ahead, it doesn't matter for anything else. Checked against blargg's
+    /// instr_timing tables in `unit_tests/instructions/timing.rs`.
     pub fn ticks(&self, action_taken: bool) -> u32 {
         match self {
             Instruction::Nop => 1,
-            Instruction::LoadIndirectSP(_) => 3,
+            Instruction::LoadIndirectSP(_) => 5,
             Instruction::Stop => 1,
             Instruction::JumpRelative(_) => 3,
             Instruction::JumpRelativeConditional(_, _) => {
@@ -481,7 +486,9 @@ impl Instruction {
             Instruction::LoadAIndirect(_) => 2,
             Instruction::Increment16(_) => 2,
             Instruction::Decrement16(_) => 2,
+            Instruction::Increment(Register8::IndirectHL) => 3,
             Instruction::Increment(_) => 1,
+            Instruction::Decrement(Register8::IndirectHL) => 3,
             Instruction::Decrement(_) => 1,
             Instruction::LoadImmediate(Register8::IndirectHL, _) => 3,
             Instruction::LoadImmediate(_, _) => 2,
@@ -503,7 +510,7 @@ impl Instruction {
             Instruction::AddSp(_) => 4,
             Instruction::LoadAHighPageImmediate(_) => 3,
             Instruction::LoadHLSP(_) => 3,
-            Instruction::Pop(_) => 4,
+            Instruction::Pop(_) => 3,
             Instruction::Ret => 4,
             Instruction::RetInterrupt => 4,
             Instruction::JumpHL => 1,
@@ -694,7 +701,7 @@ impl Instruction {
                 let (rest, immediate) = u8(rest)?;
                 Ok((rest, Instruction::AluImmediate(opcode.into(), immediate)))
             }
-            (0b11, exp, 0b111) => Ok((rest, Instruction::Reset(exp << 3))),
+            (0b11, exp, 0b111) => Ok((rest, Instruction::Reset(exp))),
             (a, b, c) => {
                 eprintln!(
                     "Illegal instruction {:#X?} ({:#04b}, {:#05b}, {:#05b})",
@@ -716,7 +723,7 @@ impl Instruction {
             (0b01, bit, register) => Instruction::Bit(bit, register.into()),
             (0b10, bit, register) => Instruction::ResetBit(bit, register.into()),
             (0b11, bit, register) => Instruction::SetBit(bit, register.into()),
-            _ => unimplemented!(),
+            _ => unreachable!(), // Only 2 bits are given
         }
     }
 }
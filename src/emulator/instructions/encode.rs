@@ -0,0 +1,78 @@
+//! Turning instructions back into bytes, the other way around from [`Instruction::parse`]
+//!
+//! Every operand enum is declared in the order of its bits in the opcode, so `as u8` is its
+//! encoding.
+use super::Instruction;
+
+impl Instruction {
+    /// Opcode first, then the operands little endian. `byte_len` bytes long.
+    ///
+    /// A few values can't come out of [`Instruction::parse`] and don't have an encoding of
+    /// their own: `LD [HL], [HL]` is HALT's opcode, and bits or RST vectors above 7 only keep
+    /// their low 3 bits.
+    pub fn encode(&self) -> Vec<u8> {
+        let op = |p3: u8, p2: u8, p1: u8| p3 << 6 | (p2 & 7) << 3 | p1;
+        let with_u16 = |opcode: u8, value: u16| {
+            let [low, high] = value.to_le_bytes();
+            vec![opcode, low, high]
+        };
+        match *self {
+            Instruction::Nop => vec![0x00],
+            Instruction::LoadIndirectSP(addr) => with_u16(0x08, addr),
+            // The byte after it is skipped
+            Instruction::Stop => vec![0x10, 0x00],
+            Instruction::JumpRelative(offset) => vec![0x18, offset as u8],
+            Instruction::JumpRelativeConditional(condition, offset) => {
+                vec![op(0, 0b100 | condition as u8, 0), offset as u8]
+            }
+            Instruction::LoadImmediate16(register, value) => {
+                with_u16(op(0, (register as u8) << 1, 1), value)
+            }
+            Instruction::AddHLRegister(register) => vec![op(0, (register as u8) << 1 | 1, 1)],
+            Instruction::LoadIndirectA(register) => vec![op(0, (register as u8) << 1, 2)],
+            Instruction::LoadAIndirect(register) => vec![op(0, (register as u8) << 1 | 1, 2)],
+            Instruction::Increment16(register) => vec![op(0, (register as u8) << 1, 3)],
+            Instruction::Decrement16(register) => vec![op(0, (register as u8) << 1 | 1, 3)],
+            Instruction::Increment(register) => vec![op(0, register as u8, 4)],
+            Instruction::Decrement(register) => vec![op(0, register as u8, 5)],
+            Instruction::LoadImmediate(register, value) => vec![op(0, register as u8, 6), value],
+            Instruction::AccumulatorFlag(flag_op) => vec![op(0, flag_op as u8, 7)],
+            Instruction::Halt => vec![0x76],
+            Instruction::Load(to, from) => vec![op(1, to as u8, from as u8)],
+            Instruction::Alu(alu_op, register) => vec![op(2, alu_op as u8, register as u8)],
+            Instruction::RetConditional(condition) => vec![op(3, condition as u8, 0)],
+            Instruction::LoadHighPageAImmediate(offset) => vec![0xE0, offset],
+            Instruction::AddSp(offset) => vec![0xE8, offset as u8],
+            Instruction::LoadAHighPageImmediate(offset) => vec![0xF0, offset],
+            Instruction::LoadHLSP(offset) => vec![0xF8, offset as u8],
+            Instruction::Pop(register) => vec![op(3, (register as u8) << 1, 1)],
+            Instruction::Ret => vec![0xC9],
+            Instruction::RetInterrupt => vec![0xD9],
+            Instruction::JumpHL => vec![0xE9],
+            Instruction::LoadSPHL => vec![0xF9],
+            Instruction::JumpConditional(condition, addr) => {
+                with_u16(op(3, condition as u8, 2), addr)
+            }
+            Instruction::LoadHighPageIndirectA => vec![0xE2],
+            Instruction::LoadAHighPageIndirect => vec![0xF2],
+            Instruction::LoadIndirectImmediateA(addr) => with_u16(0xEA, addr),
+            Instruction::LoadAIndirectImmediate(addr) => with_u16(0xFA, addr),
+            Instruction::Jump(addr) => with_u16(0xC3, addr),
+            Instruction::DisableInterrupts => vec![0xF3],
+            Instruction::EnableInterrupts => vec![0xFB],
+            Instruction::CallConditional(condition, addr) => {
+                with_u16(op(3, condition as u8, 4), addr)
+            }
+            Instruction::Call(addr) => with_u16(0xCD, addr),
+            Instruction::Push(register) => vec![op(3, (register as u8) << 1, 5)],
+            Instruction::AluImmediate(alu_op, value) => vec![op(3, alu_op as u8, 6), value],
+            Instruction::Reset(exp) => vec![op(3, exp, 7)],
+            Instruction::Bitwise(bitwise_op, register) => {
+                vec![0xCB, op(0, bitwise_op as u8, register as u8)]
+            }
+            Instruction::Bit(bit, register) => vec![0xCB, op(1, bit, register as u8)],
+            Instruction::ResetBit(bit, register) => vec![0xCB, op(2, bit, register as u8)],
+            Instruction::SetBit(bit, register) => vec![0xCB, op(3, bit, register as u8)],
+        }
+    }
+}
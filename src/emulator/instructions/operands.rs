@@ -0,0 +1,205 @@
+//! What an instruction works on, for tooling
+//!
+//! The ROM analyzer, the debugger and the block cache all want to know things like where an
+//! instruction jumps or whether it touches memory. These answer that once, so they don't each
+//! need their own match over every [`Instruction`].
+use super::{Condition, Instruction, Register16, Register16Indirect, Register16Stack, Register8};
+
+/// One of an [`Instruction`]'s operands, see [`Instruction::operands`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// Includes `[HL]`, which is memory
+    Register8(Register8),
+    Register16(Register16),
+    Stack(Register16Stack),
+    /// `[BC]`, `[DE]`, `[HL+]` or `[HL-]`
+    Indirect(Register16Indirect),
+    Immediate8(u8),
+    Immediate16(u16),
+    /// Signed, relative to the next instruction for JR and to SP otherwise
+    Offset(i8),
+    /// The byte or word at a fixed address
+    Address(u16),
+    /// `[0xFF00 + u8]`
+    HighPage(u8),
+    /// `[0xFF00 + C]`
+    HighPageC,
+    Condition(Condition),
+    /// Which bit BIT, RES and SET work on
+    Bit(u8),
+    /// Where an RST calls
+    Vector(u16),
+}
+
+impl Instruction {
+    /// In assembly order, so the destination comes first. Implicit operands like the
+    /// accumulator of `ADD A, B` or the flags are left out, except for loads and 16 bit
+    /// arithmetic where assembly spells them out.
+    pub fn operands(&self) -> Vec<Operand> {
+        let a = Operand::Register8(Register8::A);
+        let hl = Operand::Register16(Register16::HL);
+        let sp = Operand::Register16(Register16::SP);
+        match *self {
+            Instruction::Nop
+            | Instruction::Stop
+            | Instruction::Halt
+            | Instruction::AccumulatorFlag(_)
+            | Instruction::Ret
+            | Instruction::RetInterrupt
+            | Instruction::DisableInterrupts
+            | Instruction::EnableInterrupts => vec![],
+            Instruction::LoadIndirectSP(addr) => vec![Operand::Address(addr), sp],
+            Instruction::JumpRelative(offset) => vec![Operand::Offset(offset)],
+            Instruction::JumpRelativeConditional(condition, offset) => {
+                vec![Operand::Condition(condition), Operand::Offset(offset)]
+            }
+            Instruction::LoadImmediate16(register, value) => {
+                vec![Operand::Register16(register), Operand::Immediate16(value)]
+            }
+            Instruction::AddHLRegister(register) => vec![hl, Operand::Register16(register)],
+            Instruction::LoadIndirectA(register) => vec![Operand::Indirect(register), a],
+            Instruction::LoadAIndirect(register) => vec![a, Operand::Indirect(register)],
+            Instruction::Increment16(register) | Instruction::Decrement16(register) => {
+                vec![Operand::Register16(register)]
+            }
+            Instruction::Increment(register)
+            | Instruction::Decrement(register)
+            | Instruction::Alu(_, register)
+            | Instruction::Bitwise(_, register) => vec![Operand::Register8(register)],
+            Instruction::LoadImmediate(register, value) => {
+                vec![Operand::Register8(register), Operand::Immediate8(value)]
+            }
+            Instruction::Load(to, from) => vec![Operand::Register8(to), Operand::Register8(from)],
+            Instruction::RetConditional(condition) => vec![Operand::Condition(condition)],
+            Instruction::LoadHighPageAImmediate(offset) => vec![Operand::HighPage(offset), a],
+            Instruction::AddSp(offset) => vec![sp, Operand::Offset(offset)],
+            Instruction::LoadAHighPageImmediate(offset) => vec![a, Operand::HighPage(offset)],
+            Instruction::LoadHLSP(offset) => vec![hl, sp, Operand::Offset(offset)],
+            Instruction::Pop(register) | Instruction::Push(register) => {
+                vec![Operand::Stack(register)]
+            }
+            Instruction::JumpHL => vec![hl],
+            Instruction::LoadSPHL => vec![sp, hl],
+            Instruction::JumpConditional(condition, addr)
+            | Instruction::CallConditional(condition, addr) => {
+                vec![Operand::Condition(condition), Operand::Immediate16(addr)]
+            }
+            Instruction::LoadHighPageIndirectA => vec![Operand::HighPageC, a],
+            Instruction::LoadAHighPageIndirect => vec![a, Operand::HighPageC],
+            Instruction::LoadIndirectImmediateA(addr) => vec![Operand::Address(addr), a],
+            Instruction::LoadAIndirectImmediate(addr) => vec![a, Operand::Address(addr)],
+            Instruction::Jump(addr) | Instruction::Call(addr) => vec![Operand::Immediate16(addr)],
+            Instruction::AluImmediate(_, value) => vec![Operand::Immediate8(value)],
+            Instruction::Reset(vector) => vec![Operand::Vector((vector as u16) << 3)],
+            Instruction::Bit(bit, register)
+            | Instruction::ResetBit(bit, register)
+            | Instruction::SetBit(bit, register) => {
+                vec![Operand::Bit(bit), Operand::Register8(register)]
+            }
+        }
+    }
+
+    /// Whether it reads memory as data, instruction fetches don't count. Conditional returns
+    /// count even though they only pop when taken.
+    pub fn reads_memory(&self) -> bool {
+        match *self {
+            Instruction::Load(_, from) => from == Register8::IndirectHL,
+            Instruction::Increment(register)
+            | Instruction::Decrement(register)
+            | Instruction::Alu(_, register)
+            | Instruction::Bitwise(_, register)
+            | Instruction::Bit(_, register)
+            | Instruction::ResetBit(_, register)
+            | Instruction::SetBit(_, register) => register == Register8::IndirectHL,
+            Instruction::LoadAIndirect(_)
+            | Instruction::LoadAHighPageImmediate(_)
+            | Instruction::LoadAHighPageIndirect
+            | Instruction::LoadAIndirectImmediate(_)
+            | Instruction::Pop(_)
+            | Instruction::Ret
+            | Instruction::RetConditional(_)
+            | Instruction::RetInterrupt => true,
+            _ => false,
+        }
+    }
+
+    /// Whether it writes memory, including pushing onto the stack. Conditional calls count
+    /// even though they only push when taken.
+    pub fn writes_memory(&self) -> bool {
+        match *self {
+            Instruction::Load(to, _) | Instruction::LoadImmediate(to, _) => {
+                to == Register8::IndirectHL
+            }
+            Instruction::Increment(register)
+            | Instruction::Decrement(register)
+            | Instruction::Bitwise(_, register)
+            | Instruction::ResetBit(_, register)
+            | Instruction::SetBit(_, register) => register == Register8::IndirectHL,
+            Instruction::LoadIndirectSP(_)
+            | Instruction::LoadIndirectA(_)
+            | Instruction::LoadHighPageAImmediate(_)
+            | Instruction::LoadHighPageIndirectA
+            | Instruction::LoadIndirectImmediateA(_)
+            | Instruction::Push(_)
+            | Instruction::Call(_)
+            | Instruction::CallConditional(..)
+            | Instruction::Reset(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Jumps, calls, returns and RSTs, anything that can send PC somewhere other than the next
+    /// instruction
+    pub fn is_control_flow(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Jump(_)
+                | Instruction::JumpConditional(..)
+                | Instruction::JumpRelative(_)
+                | Instruction::JumpRelativeConditional(..)
+                | Instruction::JumpHL
+                | Instruction::Call(_)
+                | Instruction::CallConditional(..)
+                | Instruction::Ret
+                | Instruction::RetConditional(_)
+                | Instruction::RetInterrupt
+                | Instruction::Reset(_)
+        )
+    }
+
+    /// Calls and RSTs, which come back to the next instruction
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Call(_) | Instruction::CallConditional(..) | Instruction::Reset(_)
+        )
+    }
+
+    /// Whether the next instruction can run after this one, counting coming back from a call
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Jump(_)
+                | Instruction::JumpRelative(_)
+                | Instruction::JumpHL
+                | Instruction::Ret
+                | Instruction::RetInterrupt
+        )
+    }
+
+    /// Where it jumps or calls to if it's at `pc`. `None` for everything else, including
+    /// returns and `JP HL`, which only know where they're going when they run.
+    pub fn branch_target(&self, pc: u16) -> Option<u16> {
+        match *self {
+            Instruction::Jump(addr)
+            | Instruction::JumpConditional(_, addr)
+            | Instruction::Call(addr)
+            | Instruction::CallConditional(_, addr) => Some(addr),
+            Instruction::JumpRelative(offset) | Instruction::JumpRelativeConditional(_, offset) => {
+                Some(pc.wrapping_add(self.byte_len()).wrapping_add(offset as u16))
+            }
+            Instruction::Reset(vector) => Some((vector as u16) << 3),
+            _ => None,
+        }
+    }
+}
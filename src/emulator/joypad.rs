@@ -0,0 +1,221 @@
+use std::ops::RangeInclusive;
+
+use bit_field::BitField;
+use tracing::trace;
+
+use crate::emulator::{
+    memory_bus::{mmio::MmioDevice, Interrupt, JOYP},
+    state::{StateError, StateReader, StateWriter},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    /// Bit used in [`Joypad`]'s button masks.
+    /// Directions occupy the lower nibble and actions the upper one, so shifting the mask
+    /// right by 4 lines the action buttons up with P1 bits 0-3.
+    fn mask(&self) -> u8 {
+        match self {
+            Button::Right => 1 << 0,
+            Button::Left => 1 << 1,
+            Button::Up => 1 << 2,
+            Button::Down => 1 << 3,
+            Button::A => 1 << 4,
+            Button::B => 1 << 5,
+            Button::Select => 1 << 6,
+            Button::Start => 1 << 7,
+        }
+    }
+}
+
+/// Autofire rate, counted in emulated frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TurboConfig {
+    pub frames_on: u8,
+    pub frames_off: u8,
+}
+
+impl Default for TurboConfig {
+    fn default() -> Self {
+        Self {
+            frames_on: 2,
+            frames_off: 2,
+        }
+    }
+}
+
+impl TurboConfig {
+    fn period(&self) -> u16 {
+        (self.frames_on as u16 + self.frames_off as u16).max(1)
+    }
+}
+
+/// P1/JOYP
+///
+/// The joypad interrupt is requested when one of P10-P13 goes from high to low, so holding a
+/// button down only requests it once. A button only pulls its line low while its group is
+/// selected: pressing one in the other group does nothing, selecting a group while one of its
+/// buttons is held is a press as far as the interrupt goes, and a line another selected button
+/// already holds low doesn't fall again.
+#[derive(Debug, Default)]
+pub struct Joypad {
+    /// Bits 4-5 as last written, active low
+    select: u8,
+    /// Buttons held down normally, 1 = pressed
+    held: u8,
+    /// Buttons held down through a turbo binding, 1 = pressed
+    turbo_held: u8,
+    turbo_config: TurboConfig,
+    /// Frames since a turbo binding was first held
+    turbo_frame: u16,
+    /// P10-P13 as of the last change, 1 = low
+    low: u8,
+    /// A line fell since the last [`MmioDevice::tick`], which requests the interrupt
+    fell: bool,
+}
+
+impl Joypad {
+    pub fn read(&self) -> u8 {
+        let mut num = 0b1100_0000 | (self.select & 0b0011_0000);
+        num.set_bits(0..4, !self.lines_low() & 0x0F);
+        num
+    }
+
+    /// P10-P13 with whatever's selected held down pulling them low, 1 = low
+    fn lines_low(&self) -> u8 {
+        let pressed = self.pressed();
+        let mut lines = 0;
+        if !self.select.get_bit(4) {
+            lines |= pressed & 0x0F;
+        }
+        if !self.select.get_bit(5) {
+            lines |= pressed >> 4;
+        }
+        lines
+    }
+
+    /// Looks for lines falling after anything that can change them, returns whether one did
+    fn update_lines(&mut self) -> bool {
+        let low = self.lines_low();
+        let fell = low & !self.low != 0;
+        self.low = low;
+        self.fell |= fell;
+        fell
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        trace!(target: "joypad", "P1 write: {:#X}", byte);
+        self.select = byte & 0b0011_0000;
+        self.update_lines();
+    }
+
+    /// Buttons currently seen by the game, 1 = pressed
+    pub fn pressed(&self) -> u8 {
+        if self.turbo_active() {
+            self.held | self.turbo_held
+        } else {
+            self.held
+        }
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.held |= button.mask();
+        } else {
+            self.held &= !button.mask();
+        }
+        self.update_lines();
+    }
+
+    /// Replaces all held buttons at once, used for movie playback
+    pub fn set_pressed(&mut self, pressed: u8) {
+        self.held = pressed;
+        self.turbo_held = 0;
+        self.update_lines();
+    }
+
+    pub fn set_turbo(&mut self, button: Button, pressed: bool) {
+        if self.turbo_held == 0 && pressed {
+            self.turbo_frame = 0;
+        }
+        if pressed {
+            self.turbo_held |= button.mask();
+        } else {
+            self.turbo_held &= !button.mask();
+        }
+        self.update_lines();
+    }
+
+    pub fn turbo_config(&self) -> TurboConfig {
+        self.turbo_config
+    }
+
+    pub fn set_turbo_config(&mut self, config: TurboConfig) {
+        self.turbo_config = config;
+        self.turbo_frame %= config.period();
+        self.update_lines();
+    }
+
+    /// Must be called once per emulated frame, after the frame has finished
+    pub fn frame_tick(&mut self) {
+        if self.turbo_held != 0 {
+            self.turbo_frame = (self.turbo_frame + 1) % self.turbo_config.period();
+            self.update_lines();
+        }
+    }
+
+    /// Only the select lines, held buttons belong to whoever is playing
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.select);
+    }
+
+    /// Whatever the new select lines pull low was already low, loading doesn't press anything
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.select = state.u8()? & 0b0011_0000;
+        self.low = self.lines_low();
+        self.fell = false;
+        Ok(())
+    }
+
+    fn turbo_active(&self) -> bool {
+        self.turbo_frame < self.turbo_config.frames_on as u16
+    }
+}
+
+impl MmioDevice for Joypad {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[JOYP..=JOYP]
+    }
+
+    fn read(&self, _addr: u16) -> u8 {
+        Joypad::read(self)
+    }
+
+    fn write(&mut self, _addr: u16, byte: u8) -> Option<Interrupt> {
+        Joypad::write(self, byte);
+        std::mem::take(&mut self.fell).then_some(Interrupt::Joypad)
+    }
+
+    /// Buttons change between instructions, the interrupt goes out with the next tick
+    fn tick(&mut self, _cycles: u32) -> Option<Interrupt> {
+        std::mem::take(&mut self.fell).then_some(Interrupt::Joypad)
+    }
+
+    fn cycles_until_interrupt(&self) -> u32 {
+        if self.fell {
+            0
+        } else {
+            u32::MAX
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Memory bank controllers
+//!
+//! The cartridge header's type byte at 0x147 says what's on the cartridge besides the ROM.
+//! Only MBC1, MBC3, MBC7 and the Game Boy Camera are emulated so far, everything else is treated
+//! as a plain 32K ROM with bank 1 always at 0x4000 and nothing at 0xA000-0xBFFF.
+use std::path::PathBuf;
+
+use crate::emulator::{
+    paths::Paths,
+    rom::{self, BANK_SIZE},
+    rtc, save_file,
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub mod camera;
+pub mod eeprom;
+pub mod mbc1;
+pub mod mbc3;
+pub mod mbc7;
+
+use camera::Camera;
+use mbc1::Mbc1;
+use mbc3::Mbc3;
+use mbc7::{Accelerometer, Mbc7};
+
+/// Where a game's battery backed save is kept
+pub fn battery_file(paths: &Paths, title: &str, checksum: u16) -> PathBuf {
+    save_file::game_file(&paths.saves, title, checksum, "sav")
+}
+
+#[derive(Debug)]
+pub enum Mbc {
+    None,
+    Mbc1(Box<Mbc1>),
+    Mbc3(Box<Mbc3>),
+    Mbc7(Box<Mbc7>),
+    Camera(Box<Camera>),
+}
+
+impl Mbc {
+    pub fn for_rom(rom: &[u8]) -> Self {
+        let mbc3 = |clock, battery| {
+            Mbc::Mbc3(Box::new(Mbc3::new(
+                rom.len(),
+                rom::ram_size(rom),
+                clock,
+                battery,
+            )))
+        };
+        match rom.get(0x147) {
+            Some(&cartridge_type @ 0x01..=0x03) => Mbc::Mbc1(Box::new(Mbc1::new(
+                rom.len(),
+                rom::ram_size(rom),
+                cartridge_type == 0x03,
+                rom::is_mbc1_multicart(rom),
+            ))),
+            Some(0x0F | 0x10) => mbc3(true, true),
+            Some(0x11 | 0x12) => mbc3(false, false),
+            Some(0x13) => mbc3(false, true),
+            Some(0x22) => Mbc::Mbc7(Box::new(Mbc7::new(rom.len()))),
+            Some(0xFC) => Mbc::Camera(Box::new(Camera::new(rom.len()))),
+            _ => Mbc::None,
+        }
+    }
+
+    /// The bank at 0x4000-0x7FFF
+    pub fn rom_bank(&self) -> usize {
+        match self {
+            Mbc::None => 1,
+            Mbc::Mbc1(mbc) => mbc.rom_bank(),
+            Mbc::Mbc3(mbc) => mbc.rom_bank(),
+            Mbc::Mbc7(mbc) => mbc.rom_bank(),
+            Mbc::Camera(camera) => camera.rom_bank(),
+        }
+    }
+
+    /// The bank at 0x0000-0x3FFF, only MBC1 ever maps anything but 0 there
+    pub fn low_rom_bank(&self) -> usize {
+        match self {
+            Mbc::Mbc1(mbc) => mbc.low_rom_bank(),
+            _ => 0,
+        }
+    }
+
+    /// Where `addr` in 0x0000-0x7FFF is in the ROM
+    pub fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => self.low_rom_bank() * BANK_SIZE + addr as usize,
+            _ => self.rom_bank() * BANK_SIZE + (addr as usize - 0x4000),
+        }
+    }
+
+    /// A write to 0x0000-0x7FFF, returns whether another ROM bank is mapped now. `None` if
+    /// there's nothing to write.
+    pub fn write_rom(&mut self, addr: u16, byte: u8) -> Option<bool> {
+        let bank = self.rom_bank();
+        match self {
+            Mbc::None => return None,
+            Mbc::Mbc1(mbc) => mbc.write_rom(addr, byte),
+            Mbc::Mbc3(mbc) => mbc.write_rom(addr, byte),
+            Mbc::Mbc7(mbc) => mbc.write_rom(addr, byte),
+            Mbc::Camera(camera) => camera.write_rom(addr, byte),
+        }
+        Some(self.rom_bank() != bank)
+    }
+
+    /// 0xA000-0xBFFF, `None` if there's nothing there
+    pub fn read_ram(&self, addr: u16) -> Option<u8> {
+        match self {
+            Mbc::None => None,
+            Mbc::Mbc1(mbc) => Some(mbc.read_ram(addr)),
+            Mbc::Mbc3(mbc) => Some(mbc.read_ram(addr)),
+            Mbc::Mbc7(mbc) => Some(mbc.read_ram(addr)),
+            Mbc::Camera(camera) => Some(camera.read_ram(addr)),
+        }
+    }
+
+    /// Returns false if there's nothing there
+    pub fn write_ram(&mut self, addr: u16, byte: u8) -> bool {
+        match self {
+            Mbc::None => return false,
+            Mbc::Mbc1(mbc) => mbc.write_ram(addr, byte),
+            Mbc::Mbc3(mbc) => mbc.write_ram(addr, byte),
+            Mbc::Mbc7(mbc) => mbc.write_ram(addr, byte),
+            Mbc::Camera(camera) => camera.write_ram(addr, byte),
+        }
+        true
+    }
+
+    /// A write to 0xA000-0xBFFF in RAM bank `bank` rather than the mapped one. Mappers
+    /// without RAM banks take it as a normal write. Returns false if there's nothing there.
+    pub fn write_ram_bank(&mut self, bank: u8, addr: u16, byte: u8) -> bool {
+        match self {
+            Mbc::Mbc1(mbc) => {
+                mbc.poke_ram(bank, addr, byte);
+                true
+            }
+            Mbc::Mbc3(mbc) => {
+                mbc.poke_ram(bank, addr, byte);
+                true
+            }
+            Mbc::Camera(camera) => {
+                camera.poke_ram(bank, addr, byte);
+                true
+            }
+            _ => self.write_ram(addr, byte),
+        }
+    }
+
+    /// Whether cartridge RAM takes writes, games write 0x0A to 0x0000-0x1FFF to enable it
+    pub fn ram_enabled(&self) -> bool {
+        match self {
+            Mbc::None => false,
+            Mbc::Mbc1(mbc) => mbc.ram_enabled(),
+            Mbc::Mbc3(mbc) => mbc.ram_enabled(),
+            Mbc::Mbc7(mbc) => mbc.ram_enabled(),
+            Mbc::Camera(camera) => camera.ram_enabled(),
+        }
+    }
+
+    /// Catches up with the CPU, called after every instruction
+    pub fn tick(&mut self, cycles: u32) {
+        match self {
+            Mbc::Mbc3(mbc) => mbc.tick(cycles),
+            Mbc::Camera(camera) => camera.tick(cycles),
+            _ => {}
+        }
+    }
+
+    /// Back to how it powers on, battery backed memory and inputs stay
+    pub fn reset(&mut self) {
+        match self {
+            Mbc::None => {}
+            Mbc::Mbc1(mbc) => mbc.reset(),
+            Mbc::Mbc3(mbc) => mbc.reset(),
+            Mbc::Mbc7(mbc) => mbc.reset(),
+            Mbc::Camera(camera) => camera.reset(),
+        }
+    }
+
+    /// What a battery keeps when the power's off, `None` without one
+    pub fn battery(&self) -> Option<Vec<u8>> {
+        match self {
+            Mbc::None => None,
+            Mbc::Mbc1(mbc) => mbc.battery(),
+            Mbc::Mbc3(mbc) => mbc.battery(rtc::now()),
+            Mbc::Mbc7(mbc) => Some(mbc.eeprom().save()),
+            Mbc::Camera(camera) => Some(camera.ram().to_vec()),
+        }
+    }
+
+    pub fn load_battery(&mut self, bytes: &[u8]) -> Result<(), String> {
+        match self {
+            Mbc::None => Err("This cartridge has no battery".into()),
+            Mbc::Mbc1(mbc) => mbc.load_battery(bytes),
+            Mbc::Mbc3(mbc) => mbc.load_battery(bytes, rtc::now()),
+            Mbc::Mbc7(mbc) => mbc.eeprom_mut().load(bytes),
+            Mbc::Camera(camera) => camera.load_ram(bytes),
+        }
+    }
+
+    /// The tilt sensor, only MBC7 cartridges have one
+    pub fn accelerometer_mut(&mut self) -> Option<&mut Accelerometer> {
+        match self {
+            Mbc::Mbc7(mbc) => Some(mbc.accelerometer_mut()),
+            _ => None,
+        }
+    }
+
+    /// Only the Game Boy Camera has one
+    pub fn camera_mut(&mut self) -> Option<&mut Camera> {
+        match self {
+            Mbc::Camera(camera) => Some(camera),
+            _ => None,
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        match self {
+            Mbc::None => {}
+            Mbc::Mbc1(mbc) => mbc.save_state(state),
+            Mbc::Mbc3(mbc) => mbc.save_state(state),
+            Mbc::Mbc7(mbc) => mbc.save_state(state),
+            Mbc::Camera(camera) => camera.save_state(state),
+        }
+    }
+
+    /// Has to be loaded into the same kind, which the ROM checksum in the header makes sure of
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        match self {
+            Mbc::None => Ok(()),
+            Mbc::Mbc1(mbc) => mbc.load_state(state),
+            Mbc::Mbc3(mbc) => mbc.load_state(state),
+            Mbc::Mbc7(mbc) => mbc.load_state(state),
+            Mbc::Camera(camera) => camera.load_state(state),
+        }
+    }
+}
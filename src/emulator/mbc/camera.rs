@@ -0,0 +1,300 @@
+//! The Game Boy Camera's MAC-GBD mapper and its M64282FP image sensor
+//!
+//! 0x2000-0x3FFF picks the ROM bank at 0x4000. 0x4000-0x5FFF picks one of 16 8K RAM banks at
+//! 0xA000, or the sensor's registers instead if bit 4 is set. The RAM is battery backed and only
+//! takes writes after 0x0A is written to 0x0000-0x1FFF, the registers always do. They repeat
+//! every 0x80 bytes:
+//!
+//! | Register  | What it does                                                     |
+//! |-----------|------------------------------------------------------------------|
+//! | A000      | Writing bit 0 starts a capture, it reads 1 until it's done       |
+//! | A001      | N in bit 7, which makes captures a little quicker                |
+//! | A002-A003 | Exposure time, big endian                                        |
+//! | A004      | Bit 3 inverts the picture                                        |
+//! | A006-A035 | 4x4 thresholds, 3 for each pixel, that turn it into the 4 shades |
+//!
+//! Only A000 reads back, the rest read 0. A capture takes `32446 + (N ? 0 : 512) + 16 *
+//! exposure` M-cycles, during which RAM reads 0, then the picture appears in bank 0 at
+//! 0xA100-0xAEFF as 16x14 tiles. The sensor's gain and edge enhancement aren't emulated, the
+//! camera's software still gets a usable picture by picking the exposure for it.
+use std::{fmt::Debug, fs, io, path::Path};
+
+use bit_field::BitField;
+use tracing::trace;
+
+use crate::emulator::{
+    rom::BANK_SIZE,
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub mod netpbm;
+
+/// Size of the picture the cartridge keeps
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 112;
+pub const RAM_SIZE: usize = 0x20000;
+const RAM_BANK_SIZE: usize = 0x2000;
+const REGISTERS: usize = 0x36;
+const IMAGE: usize = 0x100;
+/// The exposure time that shows what the sensor sees as it is
+const NEUTRAL_EXPOSURE: u32 = 0x0300;
+
+/// A grayscale picture, 0 is black
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Picture {
+    width: usize,
+    height: usize,
+    /// Row-major
+    pixels: Vec<u8>,
+}
+
+impl Picture {
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// [`WIDTH`] by [`HEIGHT`], cropping the middle out of pictures that are another shape
+    pub fn fit(&self) -> Vec<u8> {
+        // Whichever way has to be cropped, the other one fits exactly
+        let scale = (self.width as f32 / WIDTH as f32).min(self.height as f32 / HEIGHT as f32);
+        let left = (self.width as f32 - WIDTH as f32 * scale) / 2.0;
+        let top = (self.height as f32 - HEIGHT as f32 * scale) / 2.0;
+        let mut fitted = Vec::with_capacity(WIDTH * HEIGHT);
+        for y in 0..HEIGHT {
+            let source_y = ((top + (y as f32 + 0.5) * scale) as usize).min(self.height - 1);
+            for x in 0..WIDTH {
+                let source_x = ((left + (x as f32 + 0.5) * scale) as usize).min(self.width - 1);
+                fitted.push(self.pixels[source_y * self.width + source_x]);
+            }
+        }
+        fitted
+    }
+}
+
+/// What the lens is pointed at
+pub trait CameraSource: Send + Debug {
+    /// Asked as each capture finishes. `None` if there's nothing to see, which comes out black.
+    fn capture(&mut self) -> Option<Picture>;
+}
+
+/// The same picture every time
+#[derive(Debug)]
+pub struct StillImage(Picture);
+
+impl StillImage {
+    pub fn new(picture: Picture) -> Self {
+        Self(picture)
+    }
+
+    /// A PGM or PPM file, see [`netpbm`]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let picture = netpbm::parse(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self(picture))
+    }
+}
+
+impl CameraSource for StillImage {
+    fn capture(&mut self) -> Option<Picture> {
+        Some(self.0.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct Camera {
+    rom_banks: usize,
+    rom_bank: u8,
+    ram_bank: u8,
+    /// The registers are at 0xA000 instead of RAM
+    registers_mapped: bool,
+    /// 0x0A written to 0x0000-0x1FFF
+    ram_enabled: bool,
+    ram: Box<[u8; RAM_SIZE]>,
+    registers: [u8; REGISTERS],
+    /// T-cycles until the capture finishes, 0 when there isn't one
+    capturing: u32,
+    source: Option<Box<dyn CameraSource>>,
+}
+
+impl Camera {
+    pub fn new(rom_len: usize) -> Self {
+        Self {
+            rom_banks: rom_len.div_ceil(BANK_SIZE).max(2),
+            rom_bank: 1,
+            ram_bank: 0,
+            registers_mapped: false,
+            ram_enabled: false,
+            ram: Box::new([0; RAM_SIZE]),
+            registers: [0; REGISTERS],
+            capturing: 0,
+            source: None,
+        }
+    }
+
+    pub fn connect(&mut self, source: Box<dyn CameraSource>) {
+        self.source = Some(source);
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.rom_bank as usize % self.rom_banks
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    pub fn write_rom(&mut self, addr: u16, byte: u8) {
+        trace!(target: "bus", "Camera mapper write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_mapped = byte.get_bit(4);
+                self.ram_bank = byte & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        self.ram_bank as usize * RAM_BANK_SIZE + (addr as usize - 0xA000)
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if self.registers_mapped {
+            match addr & 0x7F {
+                0 => self.registers[0] & 0b110 | (self.capturing > 0) as u8,
+                _ => 0x00,
+            }
+        } else if self.capturing > 0 {
+            0x00
+        } else {
+            self.ram[self.ram_offset(addr)]
+        }
+    }
+
+    pub fn write_ram(&mut self, addr: u16, byte: u8) {
+        if self.registers_mapped {
+            trace!(target: "bus", "Camera register write @{:#X}: {:#X}", addr, byte);
+            match (addr & 0x7F) as usize {
+                0 => {
+                    self.registers[0] = byte & 0b111;
+                    self.capturing = match (byte.get_bit(0), self.capturing) {
+                        (true, 0) => self.capture_time(),
+                        // Clearing it stops the capture
+                        (false, _) => 0,
+                        (true, left) => left,
+                    };
+                }
+                register @ 1..REGISTERS => self.registers[register] = byte,
+                _ => {}
+            }
+        } else if self.ram_enabled && self.capturing == 0 {
+            self.ram[self.ram_offset(addr)] = byte;
+        }
+    }
+
+    /// Writes RAM bank `bank` whatever's mapped or enabled, for GameShark codes
+    pub fn poke_ram(&mut self, bank: u8, addr: u16, byte: u8) {
+        self.ram[(bank & 0x0F) as usize * RAM_BANK_SIZE + (addr as usize - 0xA000)] = byte;
+    }
+
+    fn exposure(&self) -> u32 {
+        u16::from_be_bytes([self.registers[2], self.registers[3]]) as u32
+    }
+
+    fn capture_time(&self) -> u32 {
+        let n = if self.registers[1].get_bit(7) { 0 } else { 512 };
+        (32446 + n + 16 * self.exposure()) * 4
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if self.capturing == 0 {
+            return;
+        }
+        self.capturing = self.capturing.saturating_sub(cycles);
+        if self.capturing == 0 {
+            self.registers[0].set_bit(0, false);
+            self.develop();
+        }
+    }
+
+    /// Puts what the source sees into RAM as tiles
+    fn develop(&mut self) {
+        let seen = match self.source.as_mut().and_then(|source| source.capture()) {
+            Some(picture) => picture.fit(),
+            None => vec![0; WIDTH * HEIGHT],
+        };
+        let invert = self.registers[4].get_bit(3);
+        let exposure = self.exposure();
+        let image = &mut self.ram[IMAGE..IMAGE + WIDTH * HEIGHT / 4];
+        image.fill(0);
+        for (i, &light) in seen.iter().enumerate() {
+            let (x, y) = (i % WIDTH, i / WIDTH);
+            let light = if invert { 255 - light } else { light };
+            let value = (light as u32 * exposure / NEUTRAL_EXPOSURE).min(255) as u8;
+            let matrix = 6 + ((y % 4) * 4 + x % 4) * 3;
+            let thresholds = &self.registers[matrix..matrix + 3];
+            let shade = thresholds
+                .iter()
+                .position(|&threshold| value < threshold)
+                .map_or(0, |darker| 3 - darker as u8);
+            let row = ((y / 8) * (WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+            let bit = 7 - x % 8;
+            image[row] |= (shade & 1) << bit;
+            image[row + 1] |= (shade >> 1) << bit;
+        }
+    }
+
+    /// The RAM and whatever the lens is pointed at stay
+    pub fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.registers_mapped = false;
+        self.ram_enabled = false;
+        self.registers = [0; REGISTERS];
+        self.capturing = 0;
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..]
+    }
+
+    pub fn load_ram(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if bytes.len() != RAM_SIZE {
+            return Err(format!(
+                "Game Boy Camera saves are {} bytes, not {}",
+                RAM_SIZE,
+                bytes.len()
+            ));
+        }
+        self.ram.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.bool(self.registers_mapped);
+        state.bool(self.ram_enabled);
+        state.bytes(&self.ram[..]);
+        state.bytes(&self.registers);
+        state.u32(self.capturing);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.rom_bank = state.u8()? & 0x3F;
+        self.ram_bank = state.u8()? & 0x0F;
+        self.registers_mapped = state.bool()?;
+        self.ram_enabled = state.bool()?;
+        state.fill(&mut self.ram[..])?;
+        state.fill(&mut self.registers)?;
+        self.capturing = state.u32()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+//! Reading PGM and PPM images, the simplest formats anything can save pictures as
+//!
+//! Both the plain (P2, P3) and binary (P5, P6) kinds are read, with up to 8 bits per sample.
+//! Colors are turned into gray the way an analog TV would.
+use super::Picture;
+
+/// Luma of an RGB pixel, BT.601 weights
+pub fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Picture, String> {
+    let (channels, binary) = match bytes.get(..2) {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return Err("Not a PGM or PPM image".into()),
+    };
+    let mut pos = 2;
+    let mut header = [0; 3];
+    for field in &mut header {
+        *field = number(bytes, &mut pos).ok_or("Truncated header")?;
+    }
+    let [width, height, max] = header;
+    if width == 0 || height == 0 {
+        return Err("The image is empty".into());
+    }
+    if !(1..=255).contains(&max) {
+        return Err(format!(
+            "Only up to 8 bits per sample, not a maximum of {}",
+            max
+        ));
+    }
+    let count = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(channels))
+        .ok_or("The image is too big")?;
+    let samples: Vec<usize> = if binary {
+        // Exactly one whitespace byte after the maximum
+        let start = pos + 1;
+        bytes
+            .get(start..)
+            .and_then(|data| data.get(..count))
+            .ok_or("Truncated pixel data")?
+            .iter()
+            .map(|&sample| sample as usize)
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| number(bytes, &mut pos).ok_or("Truncated pixel data"))
+            .collect::<Result<_, _>>()?
+    };
+    let scale = |sample: usize| (sample.min(max) * 255 / max) as u8;
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|pixel| match *pixel {
+            [r, g, b] => luma(scale(r), scale(g), scale(b)),
+            _ => scale(pixel[0]),
+        })
+        .collect();
+    Ok(Picture::new(width, height, pixels))
+}
+
+/// The next decimal number from `pos` on, skipping whitespace and `#` comments
+fn number(bytes: &[u8], pos: &mut usize) -> Option<usize> {
+    loop {
+        match bytes.get(*pos)? {
+            b'#' => {
+                while bytes.get(*pos).is_some_and(|&byte| byte != b'\n') {
+                    *pos += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos]).ok()?.parse().ok()
+}
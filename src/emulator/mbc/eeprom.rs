@@ -0,0 +1,228 @@
+//! The 93LC56 serial EEPROM on MBC7 cartridges
+//!
+//! 128 16-bit words, talked to a bit at a time. While chip select is high every rising clock
+//! edge shifts in a bit from DI: a 1 to start, then a 2 bit opcode and an 8 bit address (the
+//! top bit is ignored). Reads shift the word out on DO, MSB first after a dummy 0, carrying on
+//! into the next word for as long as it's clocked. Writes and erases only do anything after
+//! EWEN, and finish instantly, so DO always says it's ready.
+use bit_field::BitField;
+
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+pub const WORDS: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    /// Waiting for the start bit
+    Idle,
+    /// Shifting in the opcode and address, this many bits so far
+    Command(u8),
+    /// Shifting out the addressed word, this many bits so far
+    Reading(u8),
+    /// Shifting in a word to write, this many bits so far. All of them for WRAL.
+    Writing { bits: u8, all: bool },
+}
+
+#[derive(Debug)]
+pub struct Eeprom {
+    words: [u16; WORDS],
+    stage: Stage,
+    /// Bits shifted in so far
+    shift: u16,
+    address: usize,
+    write_enabled: bool,
+    select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+}
+
+impl Default for Eeprom {
+    /// Erased, every bit set
+    fn default() -> Self {
+        Self {
+            words: [0xFFFF; WORDS],
+            stage: Stage::Idle,
+            shift: 0,
+            address: 0,
+            write_enabled: false,
+            select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+}
+
+impl Eeprom {
+    /// CS in bit 7, CLK in bit 6 and DI in bit 1, with DO in bit 0
+    pub fn read(&self) -> u8 {
+        (self.select as u8) << 7
+            | (self.clock as u8) << 6
+            | (self.data_in as u8) << 1
+            | self.data_out as u8
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        let (select, clock, data_in) = (byte.get_bit(7), byte.get_bit(6), byte.get_bit(1));
+        if !select {
+            self.stage = Stage::Idle;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.clock_in(data_in);
+        }
+        self.select = select;
+        self.clock = clock;
+        self.data_in = data_in;
+    }
+
+    /// Back to how it powers on, the words stay
+    pub fn reset(&mut self) {
+        *self = Self {
+            words: self.words,
+            ..Self::default()
+        };
+    }
+
+    fn clock_in(&mut self, bit: bool) {
+        match self.stage {
+            Stage::Idle => {
+                if bit {
+                    self.shift = 0;
+                    self.stage = Stage::Command(0);
+                }
+            }
+            Stage::Command(bits) => {
+                self.shift = self.shift << 1 | bit as u16;
+                if bits + 1 == 10 {
+                    self.execute();
+                } else {
+                    self.stage = Stage::Command(bits + 1);
+                }
+            }
+            Stage::Reading(bits) => {
+                let bits = if bits == 16 {
+                    self.address = (self.address + 1) % WORDS;
+                    0
+                } else {
+                    bits
+                };
+                self.data_out = self.words[self.address].get_bit(15 - bits as usize);
+                self.stage = Stage::Reading(bits + 1);
+            }
+            Stage::Writing { bits, all } => {
+                self.shift = self.shift << 1 | bit as u16;
+                if bits + 1 < 16 {
+                    self.stage = Stage::Writing {
+                        bits: bits + 1,
+                        all,
+                    };
+                    return;
+                }
+                if self.write_enabled {
+                    if all {
+                        self.words = [self.shift; WORDS];
+                    } else {
+                        self.words[self.address] = self.shift;
+                    }
+                }
+                self.stage = Stage::Idle;
+                self.data_out = true;
+            }
+        }
+    }
+
+    fn execute(&mut self) {
+        let address = self.shift as usize % WORDS;
+        self.address = address;
+        self.stage = Stage::Idle;
+        match self.shift.get_bits(8..10) {
+            0b10 => {
+                self.data_out = false;
+                self.stage = Stage::Reading(0);
+            }
+            0b01 => {
+                self.shift = 0;
+                self.stage = Stage::Writing {
+                    bits: 0,
+                    all: false,
+                };
+            }
+            0b11 if self.write_enabled => self.words[address] = 0xFFFF,
+            0b11 => {}
+            _ => match self.shift.get_bits(6..8) {
+                0b11 => self.write_enabled = true,
+                0b00 => self.write_enabled = false,
+                0b10 if self.write_enabled => self.words = [0xFFFF; WORDS],
+                0b10 => {}
+                _ => {
+                    self.shift = 0;
+                    self.stage = Stage::Writing { bits: 0, all: true };
+                }
+            },
+        }
+    }
+
+    /// What goes in a battery save, each word little endian
+    pub fn save(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    pub fn load(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if bytes.len() != WORDS * 2 {
+            return Err(format!(
+                "EEPROM saves are {} bytes, not {}",
+                WORDS * 2,
+                bytes.len()
+            ));
+        }
+        for (word, bytes) in self.words.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(())
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.save());
+        let (stage, bits) = match self.stage {
+            Stage::Idle => (0, 0),
+            Stage::Command(bits) => (1, bits),
+            Stage::Reading(bits) => (2, bits),
+            Stage::Writing { bits, all: false } => (3, bits),
+            Stage::Writing { bits, all: true } => (4, bits),
+        };
+        state.u8(stage);
+        state.u8(bits);
+        state.u16(self.shift);
+        state.u8(self.address as u8);
+        state.u8(self.read() | (self.write_enabled as u8) << 2);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let words = state.bytes(WORDS * 2)?;
+        self.load(words).expect("read the right length");
+        let stage = state.u8()?;
+        let bits = state.u8()?.min(16);
+        self.stage = match stage {
+            1 => Stage::Command(bits.min(9)),
+            2 => Stage::Reading(bits),
+            3 | 4 => Stage::Writing {
+                bits: bits.min(15),
+                all: stage == 4,
+            },
+            _ => Stage::Idle,
+        };
+        self.shift = state.u16()?;
+        self.address = state.u8()? as usize % WORDS;
+        let pins = state.u8()?;
+        self.select = pins.get_bit(7);
+        self.clock = pins.get_bit(6);
+        self.write_enabled = pins.get_bit(2);
+        self.data_in = pins.get_bit(1);
+        self.data_out = pins.get_bit(0);
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! MBC1, and the MBC1M multicarts wired up from it
+//!
+//! 0x2000-0x3FFF writes the 5 bit BANK1 register, 0 meaning 1, and 0x4000-0x5FFF the 2 bit
+//! BANK2 register. The bank at 0x4000 is BANK2 above BANK1. In mode 1, picked by bit 0 of a
+//! write to 0x6000-0x7FFF, BANK2 also picks the bank at 0x0000 (without BANK1) and the 8K RAM
+//! bank at 0xA000. RAM only takes writes, and only reads back, after 0x0A is written to
+//! 0x0000-0x1FFF.
+//!
+//! MBC1M carts leave BANK1's top bit unconnected and wire BANK2 one bit lower, so BANK2 picks
+//! one of the four 256K games and mode 1 maps its first bank at 0x0000, headers and all. BANK1
+//! still counts as 0 only when all of its 5 bits are, so writing 0x10 maps a game's bank 0 at
+//! 0x4000.
+use tracing::trace;
+
+use crate::emulator::{
+    rom::BANK_SIZE,
+    state::{StateError, StateReader, StateWriter},
+};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+#[derive(Debug)]
+pub struct Mbc1 {
+    rom_banks: usize,
+    bank1: u8,
+    bank2: u8,
+    /// Mode 1, BANK2 reaches 0x0000-0x3FFF and RAM too
+    mode: bool,
+    ram_enabled: bool,
+    ram: Vec<u8>,
+    battery: bool,
+    /// Wired as an MBC1M, see the module docs
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom_len: usize, ram_size: usize, battery: bool, multicart: bool) -> Self {
+        Self {
+            rom_banks: rom_len.div_ceil(BANK_SIZE).max(2),
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            ram_enabled: false,
+            ram: vec![0; ram_size],
+            battery,
+            multicart,
+        }
+    }
+
+    /// How far up BANK2 goes in the bank number
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        let bank1 = if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        };
+        ((self.bank2 as usize) << self.bank2_shift() | bank1 as usize) % self.rom_banks
+    }
+
+    /// The bank at 0x0000-0x3FFF
+    pub fn low_rom_bank(&self) -> usize {
+        if self.mode {
+            ((self.bank2 as usize) << self.bank2_shift()) % self.rom_banks
+        } else {
+            0
+        }
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    pub fn write_rom(&mut self, addr: u16, byte: u8) {
+        trace!(target: "bus", "MBC1 write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.bank1 = (byte & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = byte & 0x03,
+            _ => self.mode = byte & 0x01 != 0,
+        }
+    }
+
+    /// `None` for RAM that isn't there
+    fn ram_offset(&self, bank: u8, addr: u16) -> Option<usize> {
+        let offset = bank as usize * RAM_BANK_SIZE + (addr as usize - 0xA000);
+        (!self.ram.is_empty()).then(|| offset % self.ram.len())
+    }
+
+    fn ram_bank(&self) -> u8 {
+        if self.mode {
+            self.bank2
+        } else {
+            0
+        }
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        self.ram_offset(self.ram_bank(), addr)
+            .map_or(0xFF, |offset| self.ram[offset])
+    }
+
+    pub fn write_ram(&mut self, addr: u16, byte: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(offset) = self.ram_offset(self.ram_bank(), addr) {
+            self.ram[offset] = byte;
+        }
+    }
+
+    /// Writes RAM bank `bank` whatever's mapped or enabled, for GameShark codes
+    pub fn poke_ram(&mut self, bank: u8, addr: u16, byte: u8) {
+        if let Some(offset) = self.ram_offset(bank & 0x03, addr) {
+            self.ram[offset] = byte;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.bank1 = 1;
+        self.bank2 = 0;
+        self.mode = false;
+        self.ram_enabled = false;
+    }
+
+    /// `None` without a battery
+    pub fn battery(&self) -> Option<Vec<u8>> {
+        self.battery.then(|| self.ram.clone())
+    }
+
+    pub fn load_battery(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if !self.battery {
+            return Err("This cartridge has no battery".into());
+        }
+        if bytes.len() != self.ram.len() {
+            return Err(format!(
+                "This cartridge has {} bytes of RAM, not {}",
+                self.ram.len(),
+                bytes.len()
+            ));
+        }
+        self.ram.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank1);
+        state.u8(self.bank2);
+        state.bool(self.mode);
+        state.bool(self.ram_enabled);
+        state.bytes(&self.ram);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.bank1 = (state.u8()? & 0x1F).max(1);
+        self.bank2 = state.u8()? & 0x03;
+        self.mode = state.bool()?;
+        self.ram_enabled = state.bool()?;
+        state.fill(&mut self.ram)
+    }
+}
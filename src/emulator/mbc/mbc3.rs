@@ -0,0 +1,258 @@
+//! MBC3, with or without its real time clock
+//!
+//! 0x2000-0x3FFF picks the ROM bank at 0x4000, 0 meaning 1. 0x4000-0x5FFF picks one of 4 8K RAM
+//! banks at 0xA000, or 0x08-0x0C one of the clock's S, M, H, DL and DH registers instead.
+//! Writing 0 and then 1 to 0x6000-0x7FFF latches the clock, which is what its registers read.
+//! Writes go to the clock itself. RAM and the clock only take writes, and only read back, after
+//! 0x0A is written to 0x0000-0x1FFF.
+//!
+//! The clock counts emulated time while the game runs. Battery saves have the clock in the
+//! footer described in [`rtc`], which catches it up with the time spent switched off when the
+//! save is loaded.
+use tracing::trace;
+
+use crate::emulator::{
+    rom::BANK_SIZE,
+    rtc::{self, RtcFooter, RtcRegisters, DH_CARRY, DH_DAY_HIGH, DH_HALT},
+    state::{StateError, StateReader, StateWriter},
+};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+/// The clock's 32768 Hz crystal divided down to seconds, in T-cycles
+const CYCLES_PER_SECOND: u32 = 4_194_304;
+
+#[derive(Debug, Default)]
+struct Clock {
+    registers: RtcRegisters,
+    latched: RtcRegisters,
+    /// 0 was written to 0x6000-0x7FFF, a 1 now latches
+    latch_armed: bool,
+    /// T-cycles into the current second
+    cycles: u32,
+}
+
+impl Clock {
+    fn tick(&mut self, cycles: u32) {
+        if self.registers.halted() {
+            return;
+        }
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_SECOND {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.registers.advance(1);
+        }
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        let latched = self.latched;
+        match register {
+            0x08 => latched.seconds,
+            0x09 => latched.minutes,
+            0x0A => latched.hours,
+            0x0B => latched.day_low,
+            _ => latched.day_high,
+        }
+    }
+
+    fn write(&mut self, register: u8, byte: u8) {
+        let registers = &mut self.registers;
+        match register {
+            0x08 => {
+                registers.seconds = byte & 0x3F;
+                // It starts counting the second again
+                self.cycles = 0;
+            }
+            0x09 => registers.minutes = byte & 0x3F,
+            0x0A => registers.hours = byte & 0x1F,
+            0x0B => registers.day_low = byte,
+            _ => registers.day_high = byte & (DH_DAY_HIGH | DH_HALT | DH_CARRY),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Mbc3 {
+    rom_banks: usize,
+    rom_bank: u8,
+    /// RAM bank, or clock register from 0x08
+    ram_bank: u8,
+    ram_enabled: bool,
+    ram: Vec<u8>,
+    /// `None` without one
+    clock: Option<Clock>,
+    battery: bool,
+}
+
+impl Mbc3 {
+    pub fn new(rom_len: usize, ram_size: usize, clock: bool, battery: bool) -> Self {
+        Self {
+            rom_banks: rom_len.div_ceil(BANK_SIZE).max(2),
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            ram: vec![0; ram_size],
+            clock: clock.then(Clock::default),
+            battery,
+        }
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.rom_bank.max(1) as usize % self.rom_banks
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    pub fn write_rom(&mut self, addr: u16, byte: u8) {
+        trace!(target: "bus", "MBC3 write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x0F,
+            _ => {
+                if let Some(clock) = &mut self.clock {
+                    if clock.latch_armed && byte == 0x01 {
+                        clock.latched = clock.registers;
+                    }
+                    clock.latch_armed = byte == 0x00;
+                }
+            }
+        }
+    }
+
+    /// `None` for RAM that isn't there
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        let offset = (self.ram_bank & 0x03) as usize * RAM_BANK_SIZE + (addr as usize - 0xA000);
+        (!self.ram.is_empty()).then(|| offset % self.ram.len())
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.ram_bank, &self.clock) {
+            (0x08..=0x0C, Some(clock)) => clock.read(self.ram_bank),
+            (0x00..=0x07, _) => self
+                .ram_offset(addr)
+                .map_or(0xFF, |offset| self.ram[offset]),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_ram(&mut self, addr: u16, byte: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        match (self.ram_bank, &mut self.clock) {
+            (0x08..=0x0C, Some(clock)) => clock.write(self.ram_bank, byte),
+            (0x00..=0x07, _) => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    self.ram[offset] = byte;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes RAM bank `bank` whatever's mapped or enabled, for GameShark codes
+    pub fn poke_ram(&mut self, bank: u8, addr: u16, byte: u8) {
+        if !self.ram.is_empty() {
+            let offset = (bank & 0x03) as usize * RAM_BANK_SIZE + (addr as usize - 0xA000);
+            let len = self.ram.len();
+            self.ram[offset % len] = byte;
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if let Some(clock) = &mut self.clock {
+            clock.tick(cycles);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.ram_enabled = false;
+        if let Some(clock) = &mut self.clock {
+            clock.latch_armed = false;
+        }
+    }
+
+    /// RAM and the clock footer as they'd be saved at `now`, `None` without a battery
+    pub fn battery(&self, now: u64) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        Some(match &self.clock {
+            Some(clock) => rtc::join_sav(
+                &self.ram,
+                &RtcFooter {
+                    registers: clock.registers,
+                    latched: clock.latched,
+                    timestamp: now,
+                },
+            ),
+            None => self.ram.clone(),
+        })
+    }
+
+    /// A save from [`Mbc3::battery`] or another emulator, the footer is optional
+    pub fn load_battery(&mut self, bytes: &[u8], now: u64) -> Result<(), String> {
+        if !self.battery {
+            return Err("This cartridge has no battery".into());
+        }
+        let (ram, footer) = rtc::split_sav(bytes);
+        if ram.len() != self.ram.len() {
+            return Err(format!(
+                "This cartridge has {} bytes of RAM, not {}",
+                self.ram.len(),
+                ram.len()
+            ));
+        }
+        self.ram.copy_from_slice(ram);
+        if let (Some(clock), Some(footer)) = (&mut self.clock, footer) {
+            clock.registers = footer.registers_at(now);
+            clock.latched = footer.latched;
+            clock.cycles = 0;
+        }
+        Ok(())
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.bool(self.ram_enabled);
+        state.bytes(&self.ram);
+        if let Some(clock) = &self.clock {
+            for registers in [clock.registers, clock.latched] {
+                state.u8(registers.seconds);
+                state.u8(registers.minutes);
+                state.u8(registers.hours);
+                state.u8(registers.day_low);
+                state.u8(registers.day_high);
+            }
+            state.bool(clock.latch_armed);
+            state.u32(clock.cycles);
+        }
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.rom_bank = state.u8()? & 0x7F;
+        self.ram_bank = state.u8()? & 0x0F;
+        self.ram_enabled = state.bool()?;
+        state.fill(&mut self.ram)?;
+        if let Some(clock) = &mut self.clock {
+            for registers in [&mut clock.registers, &mut clock.latched] {
+                registers.seconds = state.u8()?;
+                registers.minutes = state.u8()?;
+                registers.hours = state.u8()?;
+                registers.day_low = state.u8()?;
+                registers.day_high = state.u8()?;
+            }
+            clock.latch_armed = state.bool()?;
+            clock.cycles = state.u32()? % CYCLES_PER_SECOND;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+//! MBC7, with a two axis accelerometer and an [`Eeprom`] instead of RAM
+//!
+//! 0x2000-0x3FFF picks the ROM bank at 0x4000. The registers at 0xA000-0xAFFF only answer
+//! after 0x0A is written to 0x0000-0x1FFF and 0x40 to 0x4000-0x5FFF; they repeat every 0x100
+//! bytes and are picked by bits 4-7 of the address:
+//!
+//! | Register | Read                 | Write                                     |
+//! |----------|----------------------|-------------------------------------------|
+//! | 0        | 0xFF                 | 0x55 clears the latched tilt to 0x8000    |
+//! | 1        | 0xFF                 | 0xAA latches the tilt, once after a clear |
+//! | 2, 3     | Latched X, low/high  |                                           |
+//! | 4, 5     | Latched Y, low/high  |                                           |
+//! | 6        | 0x00                 |                                           |
+//! | 7        | 0xFF                 |                                           |
+//! | 8        | EEPROM pins          | EEPROM pins, see [`Eeprom`]               |
+//!
+//! Level is 0x81D0 on both axes, and tilting the cartridge all the way (1g) one way moves it by
+//! about 0x70. Everything else reads 0xFF.
+use bit_field::BitField;
+use tracing::trace;
+
+use super::eeprom::Eeprom;
+use crate::emulator::{
+    rom::BANK_SIZE,
+    state::{StateError, StateReader, StateWriter},
+};
+
+const LEVEL: u16 = 0x81D0;
+/// How far 1g moves a reading
+const ONE_G: f32 = 0x70 as f32;
+
+/// Which edge of the cartridge goes down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TiltDirection {
+    Left,
+    Right,
+    /// The top, away from the player
+    Forward,
+    /// The bottom, towards the player
+    Back,
+}
+
+impl TiltDirection {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How the player is holding the cartridge, from keys pressed for each direction and from an
+/// analog stick, added up
+#[derive(Debug, Default)]
+pub struct Accelerometer {
+    held: [bool; 4],
+    /// -1 to 1 on each axis, right and forward are positive
+    analog: (f32, f32),
+    /// X and Y as the game last latched them
+    latched: (u16, u16),
+    /// Cleared and waiting to latch
+    ready: bool,
+}
+
+impl Accelerometer {
+    /// A key for `direction` went down or up, it counts as tilting all the way
+    pub fn set_held(&mut self, direction: TiltDirection, held: bool) {
+        self.held[direction.index()] = held;
+    }
+
+    /// From an analog stick, -1 to 1 with right and forward positive
+    pub fn set_analog(&mut self, x: f32, y: f32) {
+        self.analog = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+    }
+
+    /// -1 to 1 on each axis, right and forward positive
+    pub fn tilt(&self) -> (f32, f32) {
+        let axis = |negative: TiltDirection, positive: TiltDirection, analog: f32| {
+            let keys = self.held[positive.index()] as i8 - self.held[negative.index()] as i8;
+            (analog + keys as f32).clamp(-1.0, 1.0)
+        };
+        (
+            axis(TiltDirection::Left, TiltDirection::Right, self.analog.0),
+            axis(TiltDirection::Back, TiltDirection::Forward, self.analog.1),
+        )
+    }
+
+    /// What the sensor reads now. Tipping an edge down reads lower on that axis.
+    pub fn reading(&self) -> (u16, u16) {
+        let (x, y) = self.tilt();
+        let axis = |tilt: f32| (LEVEL as f32 - tilt * ONE_G).round() as u16;
+        (axis(x), axis(y))
+    }
+
+    fn clear(&mut self) {
+        self.latched = (0x8000, 0x8000);
+        self.ready = true;
+    }
+
+    fn latch(&mut self) {
+        if self.ready {
+            self.latched = self.reading();
+            self.ready = false;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Mbc7 {
+    rom_banks: usize,
+    rom_bank: u8,
+    /// 0x0A written to 0x0000-0x1FFF
+    enabled: bool,
+    /// 0x40 written to 0x4000-0x5FFF
+    registers_enabled: bool,
+    accelerometer: Accelerometer,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new(rom_len: usize) -> Self {
+        Self {
+            rom_banks: rom_len.div_ceil(BANK_SIZE).max(2),
+            rom_bank: 1,
+            enabled: false,
+            registers_enabled: false,
+            accelerometer: Accelerometer::default(),
+            eeprom: Eeprom::default(),
+        }
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.rom_bank as usize % self.rom_banks
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn write_rom(&mut self, addr: u16, byte: u8) {
+        trace!(target: "bus", "MBC7 write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            0x0000..=0x1FFF => self.enabled = byte == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.registers_enabled = byte == 0x40,
+            _ => {}
+        }
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        if !self.enabled || !self.registers_enabled || addr >= 0xB000 {
+            return 0xFF;
+        }
+        let (x, y) = self.accelerometer.latched;
+        match addr.get_bits(4..8) {
+            2 => x as u8,
+            3 => (x >> 8) as u8,
+            4 => y as u8,
+            5 => (y >> 8) as u8,
+            6 => 0x00,
+            8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_ram(&mut self, addr: u16, byte: u8) {
+        trace!(target: "bus", "MBC7 register write @{:#X}: {:#X}", addr, byte);
+        if !self.enabled || !self.registers_enabled || addr >= 0xB000 {
+            return;
+        }
+        match (addr.get_bits(4..8), byte) {
+            (0, 0x55) => self.accelerometer.clear(),
+            (1, 0xAA) => self.accelerometer.latch(),
+            (8, _) => self.eeprom.write(byte),
+            _ => {}
+        }
+    }
+
+    /// The EEPROM and the player's tilt stay
+    pub fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.enabled = false;
+        self.registers_enabled = false;
+        self.accelerometer.latched = (0, 0);
+        self.accelerometer.ready = false;
+        self.eeprom.reset();
+    }
+
+    pub fn eeprom(&self) -> &Eeprom {
+        &self.eeprom
+    }
+
+    pub fn eeprom_mut(&mut self) -> &mut Eeprom {
+        &mut self.eeprom
+    }
+
+    pub fn accelerometer_mut(&mut self) -> &mut Accelerometer {
+        &mut self.accelerometer
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.rom_bank);
+        state.bool(self.enabled);
+        state.bool(self.registers_enabled);
+        state.u16(self.accelerometer.latched.0);
+        state.u16(self.accelerometer.latched.1);
+        state.bool(self.accelerometer.ready);
+        self.eeprom.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.rom_bank = state.u8()? & 0x7F;
+        self.enabled = state.bool()?;
+        self.registers_enabled = state.bool()?;
+        self.accelerometer.latched = (state.u16()?, state.u16()?);
+        self.accelerometer.ready = state.bool()?;
+        self.eeprom.load_state(state)
+    }
+}
@@ -1,18 +1,105 @@
-use std::io::Read;
+use std::{
+    any::Any,
+    cell::{Ref, RefCell},
+    io::Read,
+    ops::RangeInclusive,
+};
 
 use bit_field::BitField;
 use tracing::{debug, error, trace, warn};
 
+use crate::emulator::{
+    apu::Apu,
+    cheats::{Cheats, RamWrite},
+    error::EmulatorError,
+    hardware::HardwareModel,
+    infrared::Infrared,
+    instructions::Instruction,
+    joypad::Joypad,
+    mbc::{camera::Camera, mbc7::Accelerometer, Mbc},
+    ppu::{
+        compat_palettes::{self, CompatPalette},
+        palettes::{ObjectPriority, Palettes},
+    },
+    rom,
+    serial::Serial,
+    state::{StateError, StateReader, StateWriter},
+    timer::Timer,
+};
+
+pub mod access_stats;
+use access_stats::AccessStats;
+pub mod dump;
+use dump::MemoryDump;
+pub mod hooks;
+use hooks::{BusHook, LyStub};
+pub mod mmio;
+use mmio::{IoMap, MmioDevice, Slot};
+pub mod tile_cache;
+pub mod undocumented;
+use undocumented::Undocumented;
+pub mod vram;
+use vram::{TileAttributes, Vram};
+pub mod wram;
+use wram::Wram;
+pub mod written_pages;
+use written_pages::PageSet;
+
+pub const JOYP: u16 = 0xFF00;
 pub const LCDC: u16 = 0xFF40;
 pub const STAT: u16 = 0xFF41;
 pub const SCROLL_Y: u16 = 0xFF42;
 pub const SCROLL_X: u16 = 0xFF43;
 pub const LCD_Y: u16 = 0xFF44;
 pub const LCD_YC: u16 = 0xFF45;
+/// Copies a page into OAM, see [`MemoryBus::oam_dma`]
+pub const DMA: u16 = 0xFF46;
 pub const PALLETE: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+pub const WINDOW_Y: u16 = 0xFF4A;
+pub const WINDOW_X: u16 = 0xFF4B;
+/// Bit 2 puts a color model in compatibility mode, only the boot ROM can write it
+pub const KEY0: u16 = 0xFF4C;
+pub const VBK: u16 = 0xFF4F;
+pub const BCPS: u16 = 0xFF68;
+pub const BCPD: u16 = 0xFF69;
+pub const OCPS: u16 = 0xFF6A;
+pub const OCPD: u16 = 0xFF6B;
+pub const OPRI: u16 = 0xFF6C;
+pub const SVBK: u16 = 0xFF70;
+pub const PCM12: u16 = 0xFF76;
+pub const PCM34: u16 = 0xFF77;
 pub const IF: u16 = 0xFF0F;
 pub const IE: u16 = 0xFFFF;
+/// Unmaps the boot ROM when bit 0 is written, it can't be mapped back
+pub const BOOT: u16 = 0xFF50;
+
+/// Bits of each IO register (0xFF00-0xFF7F) that read as 1 on a DMG whatever was written,
+/// either because they're unused or because the register is write only. SC bit 1 is left to
+/// [`Serial`], it's only there in CGB mode.
+#[rustfmt::skip]
+const IO_UNUSED_BITS: [u8; 0x80] = [
+    // P1    SB    SC    --    DIV   TIMA  TMA   TAC   --    --    --    --    --    --    --    IF
+    0xC0, 0x00, 0x7C, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE0,
+    // NR10  NR11  NR12  NR13  NR14  --    NR21  NR22  NR23  NR24  NR30  NR31  NR32  NR33  NR34  --
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF,
+    // NR41  NR42  NR43  NR44  NR50  NR51  NR52  --    --    --    --    --    --    --    --    --
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    // Wave RAM
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // LCDC  STAT  SCY   SCX   LY    LYC   DMA   BGP   OBP0  OBP1  WY    WX    --    --    --    --
+    0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+    // Color Game Boy registers, nothing on a DMG
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
 
+/// Registers that are only there in [CGB mode](MemoryBus::cgb_mode)
+const CGB_SLOTS: [Slot; 4] = [Slot::Vram, Slot::Wram, Slot::Palettes, Slot::Infrared];
+
+/// The LCD registers, including STAT
 #[derive(Debug)]
 #[allow(clippy::upper_case_acronyms)]
 struct LCD {
@@ -26,12 +113,21 @@ struct LCD {
     pub lcd_y: u8,
     /// LYC
     pub lcd_y_cmp: u8,
+    /// The last page [DMA](MemoryBus::oam_dma) copied
+    pub dma: u8,
     /// BGP
     pub background_pallete: u8,
+    /// OBP0
+    pub object_pallete_0: u8,
+    /// OBP1
+    pub object_pallete_1: u8,
     /// WY
     pub window_y: u8,
     /// WX
     pub window_x: u8,
+    pub stat: LCDStatus,
+    /// See [`HardwareModel::has_stat_write_bug`]
+    pub has_stat_write_bug: bool,
 }
 
 impl Default for LCD {
@@ -42,14 +138,138 @@ impl Default for LCD {
             scroll_x: 0,
             lcd_y: 0,
             lcd_y_cmp: 0,
+            dma: 0,
             background_pallete: 0,
+            object_pallete_0: 0,
+            object_pallete_1: 0,
             window_y: 0,
             window_x: 0,
+            stat: LCDStatus::default(),
+            has_stat_write_bug: HardwareModel::default().has_stat_write_bug(),
+        }
+    }
+}
+
+impl LCD {
+    /// The PPU has OAM to itself during modes 2 and 3
+    fn oam_blocked(&self) -> bool {
+        self.lcd_control.get_bit(7) && self.stat.mode >= 2
+    }
+
+    /// Returns true if that requests the STAT interrupt, like [`LCD::update_stat_line`]
+    fn compare_ly(&mut self) -> bool {
+        self.stat.ly_compare = self.stat.compare_ly == Some(self.lcd_y_cmp);
+        self.update_stat_line()
+    }
+
+    /// On DMGs a STAT write acts like 0xFF was written for a cycle before the real value,
+    /// so it interrupts during H-blank, V-blank or LY=LYC whatever sources were enabled.
+    /// Some games (Road Rash, Zerd no Densetsu) need this to boot.
+    fn stat_write_bug(&mut self) -> bool {
+        if !self.lcd_control.get_bit(7) {
+            return false;
+        }
+        let line = self.stat.ly_compare || self.stat.mode <= 1;
+        let rising = line && !self.stat.line;
+        if rising {
+            debug!(target: "bus", "STAT write bug requested an interrupt");
+        }
+        self.stat.line = line;
+        rising
+    }
+
+    /// Returns true on the rising edge of the STAT interrupt line
+    fn update_stat_line(&mut self) -> bool {
+        let line = self.stat.signal();
+        let rising = line && !self.stat.line;
+        self.stat.line = line;
+        rising
+    }
+}
+
+impl MmioDevice for LCD {
+    /// DMA is only read here, [`MemoryBus::store`] does the copying
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[LCDC..=WINDOW_X]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        trace!(target: "bus", "LCD register read @{:#X}", addr);
+        match addr {
+            LCDC => self.lcd_control,
+            STAT => self.stat.read(),
+            SCROLL_Y => self.scroll_y,
+            SCROLL_X => self.scroll_x,
+            LCD_Y => self.lcd_y,
+            LCD_YC => self.lcd_y_cmp,
+            DMA => self.dma,
+            PALLETE => self.background_pallete,
+            OBP0 => self.object_pallete_0,
+            OBP1 => self.object_pallete_1,
+            WINDOW_Y => self.window_y,
+            WINDOW_X => self.window_x,
+            _ => 0xFF,
         }
     }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "bus", "LCD register write @{:#X}: {:#X}", addr, byte);
+        let requested = match addr {
+            LCDC => {
+                self.lcd_control = byte;
+                if !self.lcd_control.get_bit(7) {
+                    self.lcd_y = 0;
+                }
+                false
+            }
+            STAT => {
+                let bug = self.has_stat_write_bug && self.stat_write_bug();
+                self.stat.write(byte);
+                self.update_stat_line() || bug
+            }
+            SCROLL_Y => {
+                self.scroll_y = byte;
+                false
+            }
+            SCROLL_X => {
+                self.scroll_x = byte;
+                false
+            }
+            LCD_Y => {
+                trace!(target: "bus", "Ignoring write to read only LY: {:#X}", byte);
+                false
+            }
+            LCD_YC => {
+                self.lcd_y_cmp = byte;
+                self.compare_ly()
+            }
+            PALLETE => {
+                self.background_pallete = byte;
+                false
+            }
+            OBP0 => {
+                self.object_pallete_0 = byte;
+                false
+            }
+            OBP1 => {
+                self.object_pallete_1 = byte;
+                false
+            }
+            WINDOW_Y => {
+                self.window_y = byte;
+                false
+            }
+            WINDOW_X => {
+                self.window_x = byte;
+                false
+            }
+            _ => false,
+        };
+        requested.then_some(Interrupt::LCDStat)
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Interrupt {
     /// INT 40
     VBlank,
@@ -64,6 +284,15 @@ pub enum Interrupt {
 }
 
 impl Interrupt {
+    /// Highest priority first
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LCDStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
     pub fn addr(&self) -> u16 {
         match self {
             Interrupt::VBlank => 0x0040,
@@ -98,6 +327,9 @@ struct Interrupts {
     pub serial_requested: bool,
     /// INT 60
     pub joypad_requested: bool,
+
+    /// IE is a full byte of storage, only the low 5 bits do anything
+    pub enable_unused: u8,
 }
 
 impl Interrupts {
@@ -108,10 +340,11 @@ impl Interrupts {
         new_number.set_bit(2, self.timer_enabled);
         new_number.set_bit(3, self.serial_enabled);
         new_number.set_bit(4, self.joypad_enabled);
-        new_number
+        new_number | self.enable_unused
     }
 
     fn set_interrupt_enable(&mut self, byte: u8) {
+        self.enable_unused = byte & 0b1110_0000;
         self.vblank_enabled = byte.get_bit(0);
         self.lcd_stat_enabled = byte.get_bit(1);
         self.timer_enabled = byte.get_bit(2);
@@ -119,8 +352,9 @@ impl Interrupts {
         self.joypad_enabled = byte.get_bit(4);
     }
 
+    /// The top 3 bits aren't connected to anything and read as 1
     fn get_interrupt_flag(&self) -> u8 {
-        let mut new_number = 0;
+        let mut new_number = 0b1110_0000;
         new_number.set_bit(0, self.vblank_requested);
         new_number.set_bit(1, self.lcd_stat_requested);
         new_number.set_bit(2, self.timer_requested);
@@ -136,6 +370,41 @@ impl Interrupts {
         self.serial_requested = byte.get_bit(3);
         self.joypad_requested = byte.get_bit(4);
     }
+
+    fn request(&mut self, interrupt: Interrupt) {
+        match interrupt {
+            Interrupt::VBlank => self.vblank_requested = true,
+            Interrupt::LCDStat => self.lcd_stat_requested = true,
+            Interrupt::Timer => self.timer_requested = true,
+            Interrupt::Serial => self.serial_requested = true,
+            Interrupt::Joypad => self.joypad_requested = true,
+        }
+    }
+}
+
+/// IF and IE
+impl MmioDevice for Interrupts {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[IF..=IF, IE..=IE]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let val = match addr {
+            IF => self.get_interrupt_flag(),
+            _ => self.get_interrupt_enable(),
+        };
+        trace!(target: "bus", "Interrupt register read @{:#X}: {:#X}", addr, val);
+        val
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "bus", "Interrupt register write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            IF => self.set_interrupt_flag(byte),
+            _ => self.set_interrupt_enable(byte),
+        }
+        None
+    }
 }
 
 #[derive(Debug, Default)]
@@ -146,6 +415,10 @@ struct LCDStatus {
     mode_1_vblank_interrupt: bool,
     mode_2_oam_interrupt: bool,
     ly_compare_interrupt: bool,
+    /// What the PPU is comparing against LYC this M-cycle, `None` while LY is changing
+    compare_ly: Option<u8>,
+    /// All enabled sources ORed together, the interrupt fires on its rising edge
+    line: bool,
 }
 
 impl LCDStatus {
@@ -155,265 +428,668 @@ impl LCDStatus {
         num.set_bit(2, self.ly_compare);
         num.set_bit(3, self.mode_0_hblank_interrupt);
         num.set_bit(4, self.mode_1_vblank_interrupt);
-        num.set_bit(4, self.mode_2_oam_interrupt);
-        num.set_bit(5, self.ly_compare_interrupt);
+        num.set_bit(5, self.mode_2_oam_interrupt);
+        num.set_bit(6, self.ly_compare_interrupt);
         num
     }
 
+    /// The mode and coincidence flag are read only
     fn write(&mut self, byte: u8) {
-        self.mode = byte.get_bits(0..2);
-        self.ly_compare = byte.get_bit(2);
         self.mode_0_hblank_interrupt = byte.get_bit(3);
         self.mode_1_vblank_interrupt = byte.get_bit(4);
         self.mode_2_oam_interrupt = byte.get_bit(5);
         self.ly_compare_interrupt = byte.get_bit(6);
     }
+
+    fn signal(&self) -> bool {
+        (self.ly_compare_interrupt && self.ly_compare)
+            || (self.mode_0_hblank_interrupt && self.mode == 0)
+            || (self.mode_1_vblank_interrupt && self.mode == 1)
+            || (self.mode_2_oam_interrupt && self.mode == 2)
+    }
 }
 
 #[derive(Debug)]
 pub struct MemoryBus {
     program: Vec<u8>,
-    /// HACK: TODO: Remove when doing MBC
-    // fake_cartram: [u8; 0xBFFF - 0xA000 + 1],
-    wram1: [u8; 0xCFFF - 0xC000 + 1],
-    wram2: [u8; 0xDFFF - 0xD000 + 1],
-    vram: [u8; 0x1FFF + 1],
+    /// See [`MemoryBus::set_boot_rom`]
+    boot_rom: Option<Vec<u8>>,
+    boot_rom_mapped: bool,
+    /// [`KEY0`] as the boot ROM left it, `None` if it was skipped. See
+    /// [`MemoryBus::cgb_mode`].
+    key0: Option<u8>,
+    mbc: Mbc,
+    /// See [`MemoryBus::take_ram_disabled`]
+    ram_disabled: bool,
+    wram: Wram,
+    vram: Vram,
+    /// See [`MemoryBus::take_written_pages`]
+    written: PageSet,
     oam: [u8; 0xFE9F - 0xFE00 + 1],
     hram: [u8; 0xFFFE - 0xFF80 + 1],
     lcd: LCD,
-    lcd_stat: LCDStatus,
+    palettes: Palettes,
+    undocumented: Undocumented,
     interrupts: Interrupts,
-    console_buffer: String,
+    joypad: Joypad,
+    cheats: Cheats,
+    serial: Serial,
+    infrared: Infrared,
+    timer: Timer,
+    apu: Apu,
+    /// See [`MemoryBus::attach`]
+    attached: Vec<Box<dyn MmioDevice>>,
+    io_map: IoMap,
+    /// First error since [`MemoryBus::take_fault`], reads can't return one directly
+    fault: RefCell<Option<EmulatorError>>,
+    /// Unmapped accesses are errors instead of reading 0xFF and ignoring writes
+    strict: bool,
+    model: HardwareModel,
+    /// See [`MemoryBus::cgb_mode`]
+    cgb_mode: bool,
+    /// See [`MemoryBus::set_compat_palette`]
+    compat_palette: CompatPalette,
+    /// Emulate the OAM corruption bug on models that have it
+    oam_bug: bool,
+    /// OAM row the PPU is reading during mode 2
+    oam_scan_row: Option<u8>,
+    /// See [`hooks`], at most one of each type. Reads need to call them without `&mut`.
+    hooks: RefCell<Vec<Box<dyn BusHook>>>,
+    /// Whether there are any, so the usual case doesn't touch the `RefCell`
+    hooked: bool,
 }
 
 impl MemoryBus {
     pub fn new<R: Read>(mut reader: R) -> Self {
         let mut vec = Vec::new();
         reader.read_to_end(&mut vec).unwrap();
-        Self {
+        let vec = rom::pad(vec);
+        let mut bus = Self {
+            mbc: Mbc::for_rom(&vec),
             program: vec,
-            // fake_cartram: [0; 0xBFFF - 0xA000 + 1],
-            wram1: [0; 0xCFFF - 0xC000 + 1],
-            wram2: [0; 0xDFFF - 0xD000 + 1],
-            vram: [0; 0x1FFF + 1],
+            ram_disabled: false,
+            boot_rom: None,
+            boot_rom_mapped: false,
+            key0: None,
+            wram: Wram::default(),
+            vram: Vram::default(),
+            written: PageSet::ALL,
             oam: [0; 0xFE9F - 0xFE00 + 1],
             hram: [0; 0xFFFE - 0xFF80 + 1],
             lcd: LCD::default(),
-            lcd_stat: LCDStatus::default(),
+            palettes: Palettes::default(),
+            undocumented: Undocumented::default(),
             interrupts: Interrupts::default(),
-            console_buffer: String::new(),
+            joypad: Joypad::default(),
+            cheats: Cheats::default(),
+            serial: Serial::default(),
+            infrared: Infrared::default(),
+            timer: Timer::default(),
+            apu: Apu::default(),
+            attached: Vec::new(),
+            io_map: IoMap::default(),
+            fault: RefCell::new(None),
+            strict: false,
+            model: HardwareModel::default(),
+            cgb_mode: false,
+            compat_palette: compat_palettes::DEFAULT,
+            oam_bug: true,
+            oam_scan_row: None,
+            hooks: RefCell::default(),
+            hooked: false,
+        };
+        for slot in [
+            Slot::Joypad,
+            Slot::Serial,
+            Slot::Timer,
+            Slot::Interrupts,
+            Slot::Apu,
+            Slot::Lcd,
+        ] {
+            let ranges = bus.device(slot).ranges().to_vec();
+            bus.io_map.insert(&ranges, slot);
+        }
+        bus
+    }
+
+    /// Plugs in extra hardware, which takes over any registers it shares with what's
+    /// already there. Staying connected through [`MemoryBus::reset`] like the link cable.
+    pub fn attach(&mut self, device: Box<dyn MmioDevice>) {
+        let slot = Slot::Attached(self.attached.len());
+        self.io_map.insert(device.ranges(), slot);
+        self.attached.push(device);
+    }
+
+    fn device(&self, slot: Slot) -> &dyn MmioDevice {
+        match slot {
+            Slot::Joypad => &self.joypad,
+            Slot::Serial => &self.serial,
+            Slot::Timer => &self.timer,
+            Slot::Interrupts => &self.interrupts,
+            Slot::Apu => &self.apu,
+            Slot::Lcd => &self.lcd,
+            Slot::Vram => &self.vram,
+            Slot::Wram => &self.wram,
+            Slot::Palettes => &self.palettes,
+            Slot::Infrared => &self.infrared,
+            Slot::Undocumented => &self.undocumented,
+            Slot::Attached(i) => self.attached[i].as_ref(),
         }
     }
 
+    fn device_mut(&mut self, slot: Slot) -> &mut dyn MmioDevice {
+        match slot {
+            Slot::Joypad => &mut self.joypad,
+            Slot::Serial => &mut self.serial,
+            Slot::Timer => &mut self.timer,
+            Slot::Interrupts => &mut self.interrupts,
+            Slot::Apu => &mut self.apu,
+            Slot::Lcd => &mut self.lcd,
+            Slot::Vram => &mut self.vram,
+            Slot::Wram => &mut self.wram,
+            Slot::Palettes => &mut self.palettes,
+            Slot::Infrared => &mut self.infrared,
+            Slot::Undocumented => &mut self.undocumented,
+            Slot::Attached(i) => self.attached[i].as_mut(),
+        }
+    }
+
+    pub fn set_model(&mut self, model: HardwareModel) {
+        self.model = model;
+        self.lcd.has_stat_write_bug = model.has_stat_write_bug();
+        self.cgb_mode = model.is_color()
+            && match self.key0 {
+                Some(key0) => self.boot_rom_mapped || !key0.get_bit(2),
+                None => rom::supports_cgb(&self.program),
+            };
+        self.serial.set_cgb_mode(self.cgb_mode);
+        self.apu.set_model(model);
+        for slot in CGB_SLOTS {
+            let ranges = self.device(slot).ranges().to_vec();
+            if self.cgb_mode {
+                self.io_map.insert(&ranges, slot);
+            } else {
+                self.io_map.remove(&ranges, slot);
+            }
+        }
+        // On any color model, except FF74 outside CGB mode
+        let ranges = self.undocumented.ranges().to_vec();
+        if !model.is_color() {
+            self.io_map.remove(&ranges, Slot::Undocumented);
+        } else if self.cgb_mode {
+            self.io_map.insert(&ranges, Slot::Undocumented);
+        } else {
+            self.io_map.insert(&ranges, Slot::Undocumented);
+            self.io_map.remove(&[0xFF74..=0xFF74], Slot::Undocumented);
+        }
+        if !self.cgb_mode {
+            self.vram.unbank();
+            self.wram.unbank();
+            self.written = PageSet::ALL;
+        }
+        // Otherwise the boot ROM already has
+        if model.is_color() && !self.cgb_mode && self.key0.is_none() {
+            self.palettes.load_compat(&self.compat_palette);
+        }
+    }
+
+    /// Colors for a DMG game on a color model, like holding buttons during the CGB boot logo.
+    /// Palette RAM only gets them in compatibility mode, but they're kept for later models. A
+    /// boot ROM that runs picks its own instead.
+    pub fn set_compat_palette(&mut self, palette: CompatPalette) {
+        self.compat_palette = palette;
+        self.set_model(self.model);
+    }
+
+    /// A color model running a game with the CGB flag set, the only time the Game Boy Color's
+    /// registers and extra memory are there. Everything else runs in compatibility mode like
+    /// it would on a DMG. A boot ROM runs in CGB mode itself and decides for the game, clearing
+    /// [`KEY0`] bit 2 for CGB mode, which takes effect once it's unmapped.
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    /// The CGB's palette RAM, only the CPU can change it and only in CGB mode
+    pub fn palettes(&self) -> &Palettes {
+        &self.palettes
+    }
+
+    /// In CGB mode what OPRI says, otherwise the DMG's order. For a DMG game on a CGB the boot
+    /// ROM sets OPRI that way before it's locked.
+    pub fn object_priority(&self) -> ObjectPriority {
+        if self.cgb_mode {
+            self.palettes.object_priority()
+        } else {
+            ObjectPriority::XCoordinate
+        }
+    }
+
+    /// Clears memory and every register back to how [`MemoryBus::new`] leaves them. The ROM,
+    /// boot ROM (unmapped), battery backed cartridge memory, cheats, whatever's plugged into
+    /// the link or infrared port or [attached](MemoryBus::attach), the joypad and tilt (buttons
+    /// are still held down) and settings like the model stay.
+    pub fn reset(&mut self) {
+        let mut old = std::mem::replace(self, Self::new(&[][..]));
+        self.program = std::mem::take(&mut old.program);
+        self.boot_rom = old.boot_rom.take();
+        self.mbc = old.mbc;
+        self.mbc.reset();
+        self.cheats = std::mem::take(&mut old.cheats);
+        self.joypad = old.joypad;
+        self.serial = old.serial;
+        self.serial.reset();
+        self.infrared = old.infrared;
+        self.infrared.reset();
+        self.apu.set_scope(old.apu.scope().is_some());
+        for device in old.attached {
+            self.attach(device);
+        }
+        self.strict = old.strict;
+        self.compat_palette = old.compat_palette;
+        self.set_model(old.model);
+        self.oam_bug = old.oam_bug;
+        self.hooks = old.hooks;
+        self.hooked = old.hooked;
+    }
+
+    pub fn model(&self) -> HardwareModel {
+        self.model
+    }
+
+    /// The OAM bug is on by default, turning it off lets homebrew get away with code that
+    /// would trash sprites on real hardware
+    pub fn set_oam_bug(&mut self, enabled: bool) {
+        self.oam_bug = enabled;
+    }
+
+    /// Set by the PPU each M-cycle, `None` outside of mode 2
+    pub fn set_oam_scan_row(&mut self, row: Option<u8>) {
+        self.oam_scan_row = row;
+    }
+
+    /// 16-bit INC/DEC put their register on the address bus. On DMGs, if that points into
+    /// FE00-FEFF while the PPU is scanning OAM, the row it's reading gets corrupted like it
+    /// would by a write: the first word becomes `((a ^ c) & (b ^ c)) ^ c` of itself (a) and
+    /// the previous row's first (b) and third (c) words, the other three are copied from the
+    /// previous row. The first row is never affected.
+    pub fn oam_bug(&mut self, addr: u16) {
+        if !self.oam_bug || !self.model.has_oam_bug() || !(0xFE00..=0xFEFF).contains(&addr) {
+            return;
+        }
+        let Some(row @ 1..) = self.oam_scan_row else {
+            return;
+        };
+        warn!(target: "bus",
+            "OAM bug: 16-bit INC/DEC of {:#06X} corrupted OAM row {}",
+            addr, row
+        );
+        let current = row as usize * 8;
+        let previous = current - 8;
+        for i in 0..2 {
+            let a = self.oam[current + i];
+            let b = self.oam[previous + i];
+            let c = self.oam[previous + 4 + i];
+            self.oam[current + i] = ((a ^ c) & (b ^ c)) ^ c;
+        }
+        self.oam
+            .copy_within(previous + 2..previous + 8, current + 2);
+    }
+
+    /// Makes the CPU always read `value` from LY, like the emulators Game Boy Doctor's logs
+    /// come from. The PPU still sees the real line.
+    pub fn stub_ly(&mut self, value: Option<u8>) {
+        match value {
+            Some(ly) => self.add_hook(LyStub(ly)),
+            None => {
+                self.remove_hook::<LyStub>();
+            }
+        }
+    }
+
+    /// Strict mode makes unmapped accesses stop emulation, which helps when writing games
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Open bus, nothing drives the data lines
+    fn unmapped_read(&self, addr: u16) -> u8 {
+        if self.strict {
+            self.fault(EmulatorError::UnmappedRead(addr));
+        } else {
+            debug!(target: "bus", "Unmapped read @{:#X}", addr);
+        }
+        0xFF
+    }
+
+    fn unmapped_write(&self, addr: u16, value: u8) {
+        if self.strict {
+            self.fault(EmulatorError::UnmappedWrite { addr, value });
+        } else {
+            debug!(target: "bus", "Unmapped write @{:#X}: {:#X}", addr, value);
+        }
+    }
+
+    /// Records an error for [`Emulator::step`](crate::emulator::Emulator::step) to return
+    pub fn fault(&self, error: EmulatorError) {
+        error!(target: "bus", "{}", error);
+        self.fault.borrow_mut().get_or_insert(error);
+    }
+
+    pub fn take_fault(&mut self) -> Option<EmulatorError> {
+        self.fault.get_mut().take()
+    }
+
+    /// Forgets a recorded error without needing `&mut`
+    pub fn clear_fault(&self) {
+        self.fault.take();
+    }
+
+    /// Starts counting reads and writes from here on
+    pub fn enable_access_stats(&mut self) {
+        self.add_hook(AccessStats::default());
+    }
+
+    pub fn access_stats(&self) -> Option<Ref<'_, AccessStats>> {
+        self.hook()
+    }
+
+    /// Runs `hook` on every CPU read and write from now on, replacing the one of the same type
+    /// if there was one. Hooks run in the order they were added.
+    pub fn add_hook<T: BusHook>(&mut self, hook: T) {
+        let hooks = self.hooks.get_mut();
+        match hooks.iter_mut().find(|existing| is::<T>(existing.as_ref())) {
+            Some(existing) => *existing = Box::new(hook),
+            None => hooks.push(Box::new(hook)),
+        }
+        self.hooked = true;
+    }
+
+    pub fn remove_hook<T: BusHook>(&mut self) -> Option<T> {
+        let hooks = self.hooks.get_mut();
+        let index = hooks.iter().position(|hook| is::<T>(hook.as_ref()))?;
+        let hook: Box<dyn Any> = hooks.remove(index);
+        self.hooked = !hooks.is_empty();
+        Some(*hook.downcast().unwrap())
+    }
+
+    pub fn hook<T: BusHook>(&self) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.hooks.borrow(), |hooks| {
+            hooks
+                .iter()
+                .find_map(|hook| (hook.as_ref() as &dyn Any).downcast_ref())
+        })
+        .ok()
+    }
+
+    pub fn hook_mut<T: BusHook>(&mut self) -> Option<&mut T> {
+        self.hooks
+            .get_mut()
+            .iter_mut()
+            .find_map(|hook| (hook.as_mut() as &mut dyn Any).downcast_mut())
+    }
+
+    /// A read by the CPU, which [hooks](MemoryBus::add_hook) see
     pub fn read_u8(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        if !self.hooked {
+            return value;
+        }
+        self.hooks
+            .borrow_mut()
+            .iter_mut()
+            .fold(value, |value, hook| hook.read(addr, value))
+    }
+
+    /// Reads a byte like [`MemoryBus::read_u8`] without counting it, for everything that isn't
+    /// the CPU
+    pub fn peek(&self, addr: u16) -> u8 {
+        let value = self.read_unmasked(addr);
+        match addr {
+            // The table only knows about the DMG's registers, and whatever isn't there
+            0xFF00..=0xFF7F if self.io_map.get(addr).is_none_or(Slot::masked) => {
+                value | IO_UNUSED_BITS[addr as usize - 0xFF00]
+            }
+            _ => value,
+        }
+    }
+
+    /// Everything but the [`IO_UNUSED_BITS`]
+    fn read_unmasked(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x7FFF => {
-                trace!("PROG read @{:#X}", addr);
-                self.program[addr as usize]
-            }
-            0x8000..=0x9FFF => self.vram[addr as usize - 0x8000],
-            // TODO: Remove when MBC
-            // 0xA000..=0xBFFF => self.fake_cartram[addr as usize - 0xA000],
-            // WRAM 1
-            0xC000..=0xCFFF => {
-                let val = self.wram1[addr as usize - 0xC000];
-                trace!("WRAM read @{:#X}: {:#X}", addr, val);
-                val
+                if let Some(byte) = self.boot_rom_byte(addr) {
+                    return byte;
+                }
+                trace!(target: "bus", "PROG read @{:#X}", addr);
+                // Past the end of a ROM that isn't a whole number of banks
+                let byte = self.program.get(self.mbc.rom_offset(addr));
+                self.cheats.patch_rom(addr, byte.copied().unwrap_or(0xFF))
             }
-            // WRAM 2
-            0xD000..=0xDFFF => {
-                let val = self.wram2[addr as usize - 0xD000];
-                trace!("WRAM read @{:#X}: {:#X}", addr, val);
+            0x8000..=0x9FFF => self.vram.read(addr),
+            0xA000..=0xBFFF => match self.mbc.read_ram(addr) {
+                Some(val) => {
+                    trace!(target: "bus", "Cartridge RAM read @{:#X}: {:#X}", addr, val);
+                    val
+                }
+                None => self.unmapped_read(addr),
+            },
+            0xC000..=0xDFFF => {
+                let val = self.wram.read(addr);
+                trace!(target: "bus", "WRAM read @{:#X}: {:#X}", addr, val);
                 val
             }
             // ECHO RAM
             0xE000..=0xFDFF => {
-                warn!(
+                warn!(target: "bus",
                     "(continuing) Illegal read from ECHO RAM @{:#X} Reroute: {:#X}",
                     addr,
                     addr - 0x2000
                 );
-                self.read_u8(addr - 0x2000)
+                self.read_unmasked(addr - 0x2000)
             }
             // OAM
             0xFE00..=0xFE9F => {
                 let val = self.oam[addr as usize - 0xFE00];
-                trace!("OAM read @{:#X}: {:#X}", addr, val);
-                val
-            }
-            // Joypad
-            0xFF00 => 0b1111_1111,
-            0xFF40..=0xFF4B => {
-                trace!("LCD register read @{:#X}", addr);
-                match addr {
-                    LCDC => self.lcd.lcd_control,
-                    STAT => self.lcd_stat.read(),
-                    SCROLL_Y => self.lcd.scroll_y,
-                    SCROLL_X => self.lcd.scroll_x,
-                    LCD_Y => self.lcd.lcd_y,
-                    LCD_YC => self.lcd.lcd_y_cmp,
-                    PALLETE => self.lcd.background_pallete,
-                    0xFF4A => self.lcd.window_y,
-                    0xFF4B => self.lcd.window_x,
-                    _ => {
-                        error!("Attempted to read unimplemented LCD register {:#X}", addr);
-                        unimplemented!()
-                    }
-                }
-            }
-            // Interrupt Flag (IF)
-            IF => {
-                let val = self.interrupts.get_interrupt_flag();
-                trace!("IF read @{:#X}: {:#X}", addr, val);
-                val
-            }
-            // Interrupt Enable (IE)
-            IE => {
-                let val = self.interrupts.get_interrupt_enable();
-                trace!("IE read @{:#X}: {:#X}", addr, val);
+                trace!(target: "bus", "OAM read @{:#X}: {:#X}", addr, val);
                 val
             }
-            // Sound Controller
-            0xFF10..=0xFF3F => {
-                debug!("Sound Register Read @{:#X}", addr);
-                0x00
-            }
-            0xFF01..=0xFF7F => {
-                warn!("Unimplemented IO register read @{:#X}", addr);
-                0x00
-            }
+            0xFEA0..=0xFEFF => self.model.prohibited_read(addr, self.lcd.oam_blocked()),
             0xFF80..=0xFFFE => {
                 let val = self.hram[addr as usize - 0xFF80];
-                trace!("HRAM read @{:#X}: {:#X}", addr, val);
+                trace!(target: "bus", "HRAM read @{:#X}: {:#X}", addr, val);
                 val
             }
-            other => {
-                error!("Attempt to read unimplemented memory {:#X}", other);
-                unimplemented!()
-            }
+            BOOT => 0xFF,
+            0xFF00..=0xFF7F | IE => match self.io_map.get(addr) {
+                Some(slot) => self.device(slot).read(addr),
+                None => self.unmapped_read(addr),
+            },
         }
     }
 
-    #[allow(clippy::identity_op)]
+    /// The instruction at `addr`, read through the bus like the CPU would so code can run
+    /// from any region. Only the bytes the opcode needs are read, the rest are 0, so running
+    /// up to the end of a region doesn't touch what comes after. Wraps at 0xFFFF.
     pub fn get_instr(&self, addr: u16) -> [u8; 4] {
-        [
-            self.read_u8(addr + 0),
-            self.read_u8(addr + 1),
-            self.read_u8(addr + 2),
-            self.read_u8(addr + 3),
-        ]
+        let mut bytes = [self.peek(addr), 0, 0, 0];
+        // The length only depends on the opcode, illegal ones are a single byte
+        let len = Instruction::parse(&bytes).map_or(1, |(_, instruction)| instruction.byte_len());
+        for (offset, byte) in (1..).zip(&mut bytes[1..len as usize]) {
+            *byte = self.peek(addr.wrapping_add(offset));
+        }
+        bytes
+    }
+
+    /// Writes `bytes` from `addr` on, for trainers, debugging and building test programs. ROM
+    /// is changed too, although the CPU can't write it, and none of it is counted as a CPU
+    /// access. Anything else goes through the usual write handling, so patching I/O registers
+    /// does what writing them would. Wraps at 0xFFFF.
+    pub fn patch_bytes(&mut self, addr: u16, bytes: &[u8]) {
+        for (offset, &byte) in (0..).zip(bytes) {
+            let addr = addr.wrapping_add(offset);
+            match self.program.get_mut(self.mbc.rom_offset(addr)) {
+                Some(rom) if addr <= 0x7FFF => {
+                    *rom = byte;
+                    self.written.insert(addr);
+                }
+                _ => self.store(addr, byte),
+            }
+        }
+    }
+
+    /// Assembles `instructions` into memory from `addr` on like [`MemoryBus::patch_bytes`].
+    /// Returns the address right after the last one.
+    pub fn patch(&mut self, addr: u16, instructions: &[Instruction]) -> u16 {
+        let bytes: Vec<u8> = instructions
+            .iter()
+            .flat_map(|instruction| instruction.encode())
+            .collect();
+        self.patch_bytes(addr, &bytes);
+        addr.wrapping_add(bytes.len() as u16)
+    }
+
+    /// Color IDs of the tile row at `addr` in VRAM bank `bank`, which has to be in
+    /// 0x8000-0x97FF, leftmost pixel first. What the PPU uses instead of the two bytes of VRAM
+    /// there.
+    pub fn tile_row(&self, bank: usize, addr: u16) -> &[u8; 8] {
+        self.vram.tile_row(bank, addr)
+    }
+
+    /// A byte from VRAM bank `bank`, whichever one VBK has mapped
+    pub fn vram(&self, bank: usize, addr: u16) -> u8 {
+        self.vram.get(bank, addr)
     }
 
+    /// The 40 objects, 4 bytes each: Y + 16, X + 8, the tile and its attributes
+    pub fn oam(&self) -> &[u8; 0xFE9F - 0xFE00 + 1] {
+        &self.oam
+    }
+
+    /// Copies the 160 bytes at `page` * 0x100 into OAM. It's done all at once rather than a
+    /// byte per M-cycle, and the CPU can still get at the rest of the bus meanwhile. Pages
+    /// from 0xE0 up are WRAM again, like on a DMG.
+    fn oam_dma(&mut self, page: u8) {
+        trace!(target: "bus", "OAM DMA from {:#X}00", page);
+        self.lcd.dma = page;
+        let source = (if page >= 0xE0 { page - 0x20 } else { page }) as u16 * 0x100;
+        for i in 0..self.oam.len() as u16 {
+            self.oam[i as usize] = self.read_unmasked(source + i);
+        }
+    }
+
+    /// How the tile at `addr` in a tile map is drawn, the default outside of CGB mode
+    pub fn tile_attributes(&self, addr: u16) -> TileAttributes {
+        if self.cgb_mode {
+            self.vram.get(1, addr).into()
+        } else {
+            TileAttributes::default()
+        }
+    }
+
+    /// A write by the CPU, which [hooks](MemoryBus::add_hook) see
     pub fn write_u8(&mut self, addr: u16, byte: u8) {
+        let byte = if self.hooked {
+            let hooks = self.hooks.get_mut();
+            match hooks
+                .iter_mut()
+                .try_fold(byte, |byte, hook| hook.write(addr, byte))
+            {
+                Some(byte) => byte,
+                None => return,
+            }
+        } else {
+            byte
+        };
+        self.store(addr, byte);
+    }
+
+    fn store(&mut self, addr: u16, byte: u8) {
+        self.written.insert(addr);
         match addr {
             0x0000..=0x7FFF => {
-                warn!(
-                    "(continuing) Illegal write to ROM @{:#X}: {:#X}",
-                    addr, byte
-                );
-                // Allow it anyways
+                let ram_enabled = self.mbc.ram_enabled();
+                let low_rom_bank = self.mbc.low_rom_bank();
+                match self.mbc.write_rom(addr, byte) {
+                    // Another bank is there now
+                    Some(true) => self.written.insert_range(0x4000, 0x7FFF),
+                    Some(false) => {}
+                    None => {
+                        warn!(target: "bus",
+                            "(continuing) Illegal write to ROM @{:#X}: {:#X}",
+                            addr, byte
+                        );
+                        // Allow it anyways
+                    }
+                }
+                if self.mbc.low_rom_bank() != low_rom_bank {
+                    self.written.insert_range(0x0000, 0x3FFF);
+                }
+                self.ram_disabled |= ram_enabled && !self.mbc.ram_enabled();
             }
             // VRAM!
             0x8000..=0x9FFF => {
-                trace!("VRAM write @{:#X}: {:#X} '{}'", addr, byte, byte as char);
-                self.vram[addr as usize - 0x8000] = byte
+                trace!(target: "bus", "VRAM write @{:#X}: {:#X} '{}'", addr, byte, byte as char);
+                self.vram.write(addr, byte);
             }
-            // TODO: Remove when MBC
-            // 0xA000..=0xBFFF => self.fake_cartram[addr as usize - 0xA000] = byte,
-            // WRAM 1
-            0xC000..=0xCFFF => {
-                trace!("WRAM write @{:#X}: {:#X}", addr, byte);
-                self.wram1[addr as usize - 0xC000] = byte
+            0xA000..=0xBFFF => {
+                if !self.mbc.write_ram(addr, byte) {
+                    self.unmapped_write(addr, byte)
+                }
             }
-            // WRAM 2
-            0xD000..=0xDFFF => {
-                trace!("WRAM write @{:#X}: {:#X}", addr, byte);
-                self.wram2[addr as usize - 0xD000] = byte
+            0xC000..=0xDFFF => {
+                trace!(target: "bus", "WRAM write @{:#X}: {:#X}", addr, byte);
+                self.wram.write(addr, byte)
             }
             // ECHO RAM
             0xE000..=0xFDFF => {
-                warn!(
+                warn!(target: "bus",
                     "(continuing) Illegal write to ECHO RAM @{:#X}: {:#X} Reroute: {:#X}",
                     addr,
                     byte,
                     addr - 0x2000
                 );
-                self.write_u8(addr - 0x2000, byte)
+                self.store(addr - 0x2000, byte)
             }
             // OAM
             0xFE00..=0xFE9F => {
-                trace!("OAM write @{:#X}: {:#X}", addr, byte);
+                trace!(target: "bus", "OAM write @{:#X}: {:#X}", addr, byte);
                 self.oam[addr as usize - 0xFE00] = byte
             }
             0xFEA0..=0xFEFF => {
-                warn!(
+                warn!(target: "bus",
                     "(continuing) Illegal write to prohibited zone @{:#X}: {:#X}",
                     addr, byte
                 );
             }
-            // Joypad
-            0xFF00 => {
-                // Do nothing
-            }
-            // Serial
-            0xFF01 => {
-                let byte = byte as char;
-                if byte == '\n' {
-                    println!("{}", self.console_buffer);
-                    self.console_buffer.clear();
-                } else {
-                    self.console_buffer.push(byte);
+            // High Ram
+            0xFF80..=0xFFFE => {
+                trace!(target: "bus", "HRAM write @{:#X}: {:#X}", addr, byte);
+                self.hram[addr as usize - 0xFF80] = byte
+            }
+            BOOT => {
+                if byte.get_bit(0) {
+                    self.unmap_boot_rom();
                 }
             }
-            // Serial Flush
-            0xFF02 => {}
-            // LCD
-            0xFF40..=0xFF4B => {
-                trace!("LCD register write @{:#X}: {:#X}", addr, byte);
-                match addr {
-                    LCDC => {
-                        self.lcd.lcd_control = byte;
-                        if !self.lcd.lcd_control.get_bit(7) {
-                            self.lcd.lcd_y = 0;
+            KEY0 if self.boot_rom_mapped && self.model.is_color() => self.key0 = Some(byte),
+            DMA => self.oam_dma(byte),
+            0xFF00..=0xFF7F | IE => match self.io_map.get(addr) {
+                Some(slot) => {
+                    if let Some(interrupt) = self.device_mut(slot).write(addr, byte) {
+                        self.request_interrupt(interrupt);
+                    }
+                    // Another bank is there now
+                    match slot {
+                        Slot::Vram => self.written.insert_range(0x8000, 0x9FFF),
+                        Slot::Wram => {
+                            self.written.insert_range(0xD000, 0xDFFF);
+                            self.written.insert_range(0xF000, 0xFDFF);
                         }
+                        _ => {}
                     }
-                    STAT => self.lcd_stat.write(byte),
-                    SCROLL_Y => self.lcd.scroll_y = byte,
-                    SCROLL_X => self.lcd.scroll_x = byte,
-                    LCD_Y => self.lcd.lcd_y = byte,
-                    LCD_YC => self.lcd.lcd_y_cmp = byte,
-                    PALLETE => self.lcd.background_pallete = byte,
-                    0xFF4A => self.lcd.window_y = byte,
-                    0xFF4B => self.lcd.window_x = byte,
-                    _ => {}
                 }
-            }
-            // Interrupt Flag (IF)
-            0xFF0F => {
-                trace!("IF register write @{:#X}: {:#X}", addr, byte);
-                self.interrupts.set_interrupt_flag(byte);
-            }
-            // Interrupt Enable (IE)
-            0xFFFF => {
-                trace!("IE register write @{:#X}: {:#X}", addr, byte);
-                self.interrupts.set_interrupt_enable(byte);
-            }
-            // Sound Controller
-            0xFF10..=0xFF3F => {
-                debug!("Sound Register write @{:#X}: {:#X}", addr, byte);
-            }
-            // I/O registers
-            0xFF03..=0xFF7F => {
-                warn!("Unimplemented IO register write @{:#X}: {:#X}", addr, byte);
-            }
-            // High Ram
-            0xFF80..=0xFFFE => {
-                trace!("HRAM write @{:#X}: {:#X}", addr, byte);
-                self.hram[addr as usize - 0xFF80] = byte
-            }
-            #[allow(unreachable_patterns)]
-            // During periods where we remove some memory
-            _ => unimplemented!("Unimplemented memory write at {:#X}", addr),
+                None => self.unmapped_write(addr, byte),
+            },
         }
     }
 
@@ -440,16 +1116,47 @@ impl MemoryBus {
         self.write_u8(*sp, byte);
     }
 
-    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
-        match interrupt {
-            Interrupt::VBlank => self.interrupts.vblank_requested = true,
-            Interrupt::LCDStat => self.interrupts.lcd_stat_requested = true,
-            Interrupt::Timer => self.interrupts.timer_requested = true,
-            Interrupt::Serial => self.interrupts.serial_requested = true,
-            Interrupt::Joypad => self.interrupts.joypad_requested = true,
+    /// Advances components that count cycles on their own
+    pub fn tick(&mut self, cycles: u32) {
+        self.mbc.tick(cycles);
+        let Self {
+            joypad,
+            serial,
+            infrared,
+            timer,
+            apu,
+            attached,
+            interrupts,
+            ..
+        } = self;
+        let devices = [joypad as &mut dyn MmioDevice, serial, infrared, timer, apu]
+            .into_iter()
+            .chain(attached.iter_mut().map(|device| device.as_mut()));
+        for device in devices {
+            if let Some(interrupt) = device.tick(cycles) {
+                interrupts.request(interrupt);
+            }
+        }
+        for _ in 0..self.timer.take_div_apu_steps() {
+            self.apu.step_frame_sequencer();
         }
     }
 
+    /// T-cycles until [`MemoryBus::tick`] might request an interrupt, if the CPU doesn't
+    /// write anything in between
+    pub fn cycles_until_interrupt(&self) -> u32 {
+        [&self.timer as &dyn MmioDevice, &self.serial, &self.joypad]
+            .into_iter()
+            .chain(self.attached.iter().map(|device| device.as_ref()))
+            .map(|device| device.cycles_until_interrupt())
+            .min()
+            .unwrap()
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.request(interrupt);
+    }
+
     pub fn reset_interrupt(&mut self, interrupt: Interrupt) {
         match interrupt {
             Interrupt::VBlank => self.interrupts.vblank_requested = false,
@@ -479,21 +1186,356 @@ impl MemoryBus {
         None
     }
 
+    pub fn joypad(&self) -> &Joypad {
+        &self.joypad
+    }
+
+    pub fn joypad_mut(&mut self) -> &mut Joypad {
+        &mut self.joypad
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn serial_mut(&mut self) -> &mut Serial {
+        &mut self.serial
+    }
+
+    pub fn infrared_mut(&mut self) -> &mut Infrared {
+        &mut self.infrared
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        // Game Genie codes change what the ROM reads as
+        self.written = PageSet::ALL;
+        &mut self.cheats
+    }
+
+    /// Applies GameShark codes, should be called once per frame at the start of V-blank.
+    /// Writes go straight to memory so they don't trigger any side effects.
+    pub fn apply_ram_cheats(&mut self) {
+        for write in self.cheats.ram_writes() {
+            let RamWrite {
+                bank,
+                address,
+                value,
+            } = *write;
+            self.written.insert(address);
+            match address {
+                0xC000..=0xCFFF => self.wram.write(address, value),
+                0xD000..=0xDFFF => {
+                    // 8X and 9X pick a bank, anything else means whichever is mapped
+                    let bank = match bank & 0xF0 {
+                        0x80 | 0x90 => (bank as usize & 0x07).max(1),
+                        _ => self.wram.mapped_bank(),
+                    };
+                    if bank > 1 && !self.cgb_mode {
+                        trace!(target: "bus", "GameShark: skipping write to WRAM bank {}", bank);
+                        continue;
+                    }
+                    self.wram.banks_mut()[bank][address as usize - 0xD000] = value
+                }
+                _ if self.mbc.write_ram_bank(bank, address, value) => {}
+                _ => trace!(target: "bus", "GameShark: no cartridge RAM for write @{:#X}", address),
+            }
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.wram.save_banks(state);
+        self.vram.save_banks(state);
+        state.bytes(&self.oam);
+        state.bytes(&self.hram);
+        self.save_registers(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.written = PageSet::ALL;
+        self.wram.load_banks(state)?;
+        self.vram.load_banks(state)?;
+        state.fill(&mut self.oam)?;
+        state.fill(&mut self.hram)?;
+        self.load_registers(state)
+    }
+
+    /// Everything in a save state but RAM
+    fn save_registers(&self, state: &mut StateWriter) {
+        let lcd = &self.lcd;
+        for register in [
+            lcd.lcd_control,
+            lcd.scroll_y,
+            lcd.scroll_x,
+            lcd.lcd_y,
+            lcd.lcd_y_cmp,
+            lcd.dma,
+            lcd.background_pallete,
+            lcd.object_pallete_0,
+            lcd.object_pallete_1,
+            lcd.window_y,
+            lcd.window_x,
+        ] {
+            state.u8(register);
+        }
+
+        let stat = &self.lcd.stat;
+        state.u8(stat.mode);
+        for flag in [
+            stat.ly_compare,
+            stat.mode_0_hblank_interrupt,
+            stat.mode_1_vblank_interrupt,
+            stat.mode_2_oam_interrupt,
+            stat.ly_compare_interrupt,
+            stat.compare_ly.is_some(),
+            stat.line,
+        ] {
+            state.bool(flag);
+        }
+        state.u8(stat.compare_ly.unwrap_or(0));
+
+        state.bool(self.oam_scan_row.is_some());
+        state.u8(self.oam_scan_row.unwrap_or(0));
+        state.u8(self.interrupts.get_interrupt_enable());
+        state.u8(self.interrupts.get_interrupt_flag());
+        self.joypad.save_state(state);
+        self.serial.save_state(state);
+        self.timer.save_state(state);
+        self.apu.save_state(state);
+        self.vram.save_bank_register(state);
+        self.wram.save_bank_register(state);
+        self.palettes.save_state(state);
+        self.infrared.save_state(state);
+        self.undocumented.save_state(state);
+        self.mbc.save_state(state);
+        state.bool(self.boot_rom_mapped);
+        state.bool(self.key0.is_some());
+        state.u8(self.key0.unwrap_or(0));
+    }
+
+    fn load_registers(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let lcd = &mut self.lcd;
+        for register in [
+            &mut lcd.lcd_control,
+            &mut lcd.scroll_y,
+            &mut lcd.scroll_x,
+            &mut lcd.lcd_y,
+            &mut lcd.lcd_y_cmp,
+            &mut lcd.dma,
+            &mut lcd.background_pallete,
+            &mut lcd.object_pallete_0,
+            &mut lcd.object_pallete_1,
+            &mut lcd.window_y,
+            &mut lcd.window_x,
+        ] {
+            *register = state.u8()?;
+        }
+
+        let stat = &mut self.lcd.stat;
+        stat.mode = state.u8()? & 0b11;
+        for flag in [
+            &mut stat.ly_compare,
+            &mut stat.mode_0_hblank_interrupt,
+            &mut stat.mode_1_vblank_interrupt,
+            &mut stat.mode_2_oam_interrupt,
+            &mut stat.ly_compare_interrupt,
+        ] {
+            *flag = state.bool()?;
+        }
+        let comparing = state.bool()?;
+        stat.line = state.bool()?;
+        let compare_ly = state.u8()?;
+        stat.compare_ly = comparing.then_some(compare_ly);
+
+        let scanning = state.bool()?;
+        let row = state.u8()?.min(19);
+        self.oam_scan_row = scanning.then_some(row);
+        self.interrupts.set_interrupt_enable(state.u8()?);
+        self.interrupts.set_interrupt_flag(state.u8()?);
+        self.joypad.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.timer.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.vram.load_bank_register(state)?;
+        self.wram.load_bank_register(state)?;
+        self.palettes.load_state(state)?;
+        self.infrared.load_state(state)?;
+        self.undocumented.load_state(state)?;
+        self.mbc.load_state(state)?;
+        // A state saved during a boot ROM that isn't here now carries on into the cartridge
+        let mapped = state.bool()? && self.boot_rom.is_some();
+        let booted = state.bool()?;
+        let key0 = state.u8()?;
+        let key0 = booted.then_some(key0);
+        if (mapped, key0) != (self.boot_rom_mapped, self.key0) {
+            self.boot_rom_mapped = mapped;
+            self.key0 = key0;
+            self.set_model(self.model);
+        }
+        Ok(())
+    }
+
+    /// Copies RAM and every register, see [`dump`]
+    pub fn dump(&self) -> MemoryDump {
+        let mut registers = StateWriter::default();
+        self.save_registers(&mut registers);
+        MemoryDump {
+            wram: Box::new(*self.wram.banks()),
+            wram_bank: self.wram.mapped_bank(),
+            vram: Box::new(*self.vram.banks()),
+            vram_bank: self.vram.mapped_bank(),
+            oam: self.oam,
+            hram: self.hram,
+            io: std::array::from_fn(|i| self.peek(0xFF00 + i as u16)),
+            interrupt_enable: self.interrupts.get_interrupt_enable(),
+            registers: registers.finish(),
+        }
+    }
+
+    /// Puts back everything [`MemoryBus::dump`] copied, like loading a state
+    pub fn restore(&mut self, dump: &MemoryDump) {
+        self.written = PageSet::ALL;
+        *self.wram.banks_mut() = *dump.wram;
+        self.vram.set_banks(&dump.vram);
+        self.oam = dump.oam;
+        self.hram = dump.hram;
+        let mut registers = StateReader::headerless(&dump.registers);
+        self.load_registers(&mut registers)
+            .and_then(|()| registers.finish())
+            .expect("dumps have every register");
+    }
+
+    /// Pages written since the last call, starting with everything. Every kind of write counts,
+    /// including loading a state and changing cheats.
+    pub fn take_written_pages(&mut self) -> PageSet {
+        std::mem::take(&mut self.written)
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// What runs before the cartridge after each power on, see [`boot`](crate::emulator::boot).
+    /// It's only mapped by [`MemoryBus::map_boot_rom`], until bit 0 of [`BOOT`] is written. One
+    /// the size of a CGB's leaves 0x100-0x1FF to the cartridge header.
+    pub fn set_boot_rom(&mut self, rom: Option<Vec<u8>>) {
+        self.boot_rom = rom;
+        self.unmap_boot_rom();
+    }
+
+    /// Maps the boot ROM in and switches the LCD off like at power on, ready for it to run
+    /// from 0. Returns false if there isn't one.
+    pub fn map_boot_rom(&mut self) -> bool {
+        if self.boot_rom.is_none() {
+            return false;
+        }
+        self.boot_rom_mapped = true;
+        self.key0 = Some(0);
+        self.written.insert_range(0x0000, 0x08FF);
+        self.lcd.lcd_control = 0;
+        self.set_model(self.model);
+        true
+    }
+
+    /// Whether the boot ROM is still running, or at least hasn't written [`BOOT`] yet
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom_mapped
+    }
+
+    fn unmap_boot_rom(&mut self) {
+        if self.boot_rom_mapped {
+            self.boot_rom_mapped = false;
+            self.written.insert_range(0x0000, 0x08FF);
+            self.set_model(self.model);
+        }
+    }
+
+    fn boot_rom_byte(&self, addr: u16) -> Option<u8> {
+        if !self.boot_rom_mapped || (0x100..=0x1FF).contains(&addr) {
+            return None;
+        }
+        self.boot_rom.as_ref()?.get(addr as usize).copied()
+    }
+
+    /// The ROM bank at 0x4000-0x7FFF
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+
+    /// What the cartridge's battery keeps, `None` if it doesn't have one
+    pub fn battery(&self) -> Option<Vec<u8>> {
+        self.mbc.battery()
+    }
+
+    /// Whether the game has disabled cartridge RAM since the last call, which games do once
+    /// they're done saving
+    pub fn take_ram_disabled(&mut self) -> bool {
+        std::mem::take(&mut self.ram_disabled)
+    }
+
+    /// Puts back what [`MemoryBus::battery`] returned
+    pub fn load_battery(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.mbc.load_battery(bytes)
+    }
+
+    /// The cartridge's tilt sensor, `None` if it doesn't have one
+    pub fn accelerometer_mut(&mut self) -> Option<&mut Accelerometer> {
+        self.mbc.accelerometer_mut()
+    }
+
+    /// The Game Boy Camera's sensor, `None` on any other cartridge
+    pub fn camera_mut(&mut self) -> Option<&mut Camera> {
+        self.mbc.camera_mut()
+    }
+
+    /// Title from the cartridge header
+    pub fn rom_title(&self) -> String {
+        rom::header_title(&self.program)
+    }
+
+    /// Header checksum from the cartridge header
+    pub fn header_checksum(&self) -> u8 {
+        self.program[0x014D]
+    }
+
+    /// Global checksum from the cartridge header
+    pub fn rom_checksum(&self) -> u16 {
+        u16::from_be_bytes([self.program[0x014E], self.program[0x014F]])
+    }
+
     pub fn get_lcd_mode(&self) -> u8 {
-        self.lcd_stat.mode
+        self.lcd.stat.mode
     }
 
     pub fn set_lcd_mode(&mut self, mode: u8) {
-        self.lcd_stat.mode = mode;
+        self.lcd.stat.mode = mode;
+        if self.lcd.update_stat_line() {
+            self.request_interrupt(Interrupt::LCDStat);
+        }
+    }
+
+    /// Only the PPU can change LY
+    pub fn set_lcd_y(&mut self, ly: u8) {
+        self.lcd.lcd_y = ly;
     }
 
-    pub fn update_lcd_stat(&mut self) {
-        let ly = self.read_u8(LCD_Y);
-        let lyc = self.read_u8(LCD_YC);
-        self.lcd_stat.ly_compare = ly == lyc;
+    /// Sets the value compared against LYC from now on, `None` for the M-cycle where the
+    /// comparison is blanked while LY changes
+    pub fn set_compare_ly(&mut self, ly: Option<u8>) {
+        self.lcd.stat.compare_ly = ly;
+        if self.lcd.compare_ly() {
+            self.request_interrupt(Interrupt::LCDStat);
+        }
     }
 
     pub fn hram_dump(&self) {
-        error!("{:#X?}", self.hram);
+        error!(target: "bus", "{:#X?}", self.hram);
     }
 }
+
+fn is<T: BusHook>(hook: &dyn BusHook) -> bool {
+    (hook as &dyn Any).is::<T>()
+}
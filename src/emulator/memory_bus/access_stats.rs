@@ -0,0 +1,219 @@
+//! Read and write counters for every address
+//!
+//! Only what the CPU reads and writes as data is counted. Instruction fetches are left to
+//! [`Coverage`](crate::emulator::coverage::Coverage), and the PPU and debugger look at memory
+//! with [`MemoryBus::peek`](super::MemoryBus::peek) so they don't drown everything else out.
+use std::fmt::Write;
+
+use crate::emulator::{memory_bus::hooks::BusHook, rom::Location, symbols::Symbols};
+
+const ADDRESSES: usize = 0x10000;
+
+/// The memory map as the CPU sees it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Rom0,
+    RomX,
+    Vram,
+    Sram,
+    Wram,
+    Echo,
+    Oam,
+    Unusable,
+    Io,
+    Hram,
+    Ie,
+}
+
+impl Region {
+    pub const ALL: [Region; 11] = [
+        Region::Rom0,
+        Region::RomX,
+        Region::Vram,
+        Region::Sram,
+        Region::Wram,
+        Region::Echo,
+        Region::Oam,
+        Region::Unusable,
+        Region::Io,
+        Region::Hram,
+        Region::Ie,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Rom0 => "ROM0",
+            Region::RomX => "ROMX",
+            Region::Vram => "VRAM",
+            Region::Sram => "SRAM",
+            Region::Wram => "WRAM",
+            Region::Echo => "ECHO",
+            Region::Oam => "OAM",
+            Region::Unusable => "UNUSABLE",
+            Region::Io => "IO",
+            Region::Hram => "HRAM",
+            Region::Ie => "IE",
+        }
+    }
+
+    /// Inclusive
+    pub fn range(self) -> (u16, u16) {
+        match self {
+            Region::Rom0 => (0x0000, 0x3FFF),
+            Region::RomX => (0x4000, 0x7FFF),
+            Region::Vram => (0x8000, 0x9FFF),
+            Region::Sram => (0xA000, 0xBFFF),
+            Region::Wram => (0xC000, 0xDFFF),
+            Region::Echo => (0xE000, 0xFDFF),
+            Region::Oam => (0xFE00, 0xFE9F),
+            Region::Unusable => (0xFEA0, 0xFEFF),
+            Region::Io => (0xFF00, 0xFF7F),
+            Region::Hram => (0xFF80, 0xFFFE),
+            Region::Ie => (0xFFFF, 0xFFFF),
+        }
+    }
+}
+
+/// Totals for one address or region
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accesses {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl Accesses {
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AccessStats {
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl Default for AccessStats {
+    fn default() -> Self {
+        Self {
+            reads: vec![0; ADDRESSES],
+            writes: vec![0; ADDRESSES],
+        }
+    }
+}
+
+impl AccessStats {
+    pub fn record_read(&mut self, addr: u16) {
+        let count = &mut self.reads[addr as usize];
+        *count = count.saturating_add(1);
+    }
+
+    pub fn record_write(&mut self, addr: u16) {
+        let count = &mut self.writes[addr as usize];
+        *count = count.saturating_add(1);
+    }
+
+    pub fn get(&self, addr: u16) -> Accesses {
+        Accesses {
+            reads: self.reads[addr as usize] as u64,
+            writes: self.writes[addr as usize] as u64,
+        }
+    }
+
+    /// Everything between `start` and `end`, inclusive
+    pub fn sum(&self, start: u16, end: u16) -> Accesses {
+        (start..=end).fold(Accesses::default(), |total, addr| {
+            let accesses = self.get(addr);
+            Accesses {
+                reads: total.reads + accesses.reads,
+                writes: total.writes + accesses.writes,
+            }
+        })
+    }
+
+    pub fn region(&self, region: Region) -> Accesses {
+        let (start, end) = region.range();
+        self.sum(start, end)
+    }
+
+    /// The `count` most accessed addresses, most first
+    pub fn hottest(&self, count: usize) -> Vec<(u16, Accesses)> {
+        let mut used: Vec<(u16, Accesses)> = (0..=0xFFFF)
+            .map(|addr| (addr, self.get(addr)))
+            .filter(|(_, accesses)| accesses.total() > 0)
+            .collect();
+        used.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then(a.0.cmp(&b.0)));
+        used.truncate(count);
+        used
+    }
+
+    /// One byte per 256 byte page, log scaled so 255 is the busiest page and 0 is untouched
+    pub fn heatmap(&self) -> Vec<u8> {
+        let pages: Vec<u64> = (0..=0xFF)
+            .map(|page: u16| self.sum(page << 8, (page << 8) | 0xFF).total())
+            .collect();
+        let scale = |count: u64| (count as f64 + 1.0).ln();
+        let max = pages.iter().copied().max().unwrap_or(0);
+        if max == 0 {
+            return vec![0; pages.len()];
+        }
+        pages
+            .iter()
+            .map(|&count| match count {
+                0 => 0,
+                // Anything touched at all shows up
+                count => (scale(count) / scale(max) * 254.0) as u8 + 1,
+            })
+            .collect()
+    }
+
+    /// A plain text report: totals per region, the hottest addresses named from `symbols`,
+    /// then `addr reads writes` for every address that was touched
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let mut out = String::new();
+        // Writing to a String can't fail
+        let _ = writeln!(out, "{:<10} {:>12} {:>12}", "Region", "Reads", "Writes");
+        for region in Region::ALL {
+            let accesses = self.region(region);
+            let _ = writeln!(
+                out,
+                "{:<10} {:>12} {:>12}",
+                region.name(),
+                accesses.reads,
+                accesses.writes
+            );
+        }
+
+        let _ = writeln!(out, "\nHottest addresses:");
+        for (addr, accesses) in self.hottest(32) {
+            // RAM labels are in bank 0 in RGBDS symbol files
+            let label = symbols.get(Location { bank: 0, addr }).unwrap_or_default();
+            let line = format!(
+                "  {:04X} {:>12} {:>12}  {}",
+                addr, accesses.reads, accesses.writes, label
+            );
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+
+        let _ = writeln!(out, "\nAll addresses:");
+        for addr in 0..=0xFFFF {
+            let accesses = self.get(addr);
+            if accesses.total() > 0 {
+                let _ = writeln!(out, "{:04X} {} {}", addr, accesses.reads, accesses.writes);
+            }
+        }
+        out
+    }
+}
+
+impl BusHook for AccessStats {
+    fn read(&mut self, addr: u16, value: u8) -> u8 {
+        self.record_read(addr);
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Option<u8> {
+        self.record_write(addr);
+        Some(value)
+    }
+}
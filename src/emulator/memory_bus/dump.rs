@@ -0,0 +1,61 @@
+//! Copies of everything the bus holds
+//!
+//! A [`MemoryDump`] from [`MemoryBus::dump`](super::MemoryBus::dump) has all of RAM and the
+//! state of every register, including what they don't show when read (the timer's internal
+//! counter, the STAT interrupt line), so [`MemoryBus::restore`](super::MemoryBus::restore)
+//! puts the bus back exactly, including the cartridge's mapper and whatever memory it has. The
+//! ROM, cheats, hooks and attached devices aren't included.
+use super::{vram, wram};
+
+/// See the [module docs](self)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryDump {
+    /// Every WRAM bank, bank 0 is at 0xC000
+    pub wram: Box<[[u8; wram::BANK_SIZE]; wram::BANKS]>,
+    /// The one at 0xD000
+    pub wram_bank: usize,
+    /// Both VRAM banks
+    pub vram: Box<[[u8; vram::BANK_SIZE]; vram::BANKS]>,
+    /// The one the CPU sees at 0x8000
+    pub vram_bank: usize,
+    /// 0xFE00-0xFE9F
+    pub oam: [u8; 0xA0],
+    /// 0xFF80-0xFFFE
+    pub hram: [u8; 0x7F],
+    /// What 0xFF00-0xFF7F read as when it was taken
+    pub io: [u8; 0x80],
+    /// IE
+    pub interrupt_enable: u8,
+    /// The registers as they are in a save state
+    pub(super) registers: Vec<u8>,
+}
+
+impl MemoryDump {
+    /// What the CPU would have read from `addr`, `None` for the ROM and whatever else isn't
+    /// included
+    pub fn get(&self, addr: u16) -> Option<u8> {
+        let addr = addr as usize;
+        match addr {
+            0x8000..=0x9FFF => Some(self.vram[self.vram_bank][addr - 0x8000]),
+            0xC000..=0xCFFF | 0xE000..=0xEFFF => Some(self.wram[0][addr & 0xFFF]),
+            0xD000..=0xDFFF | 0xF000..=0xFDFF => Some(self.wram[self.wram_bank][addr & 0xFFF]),
+            0xFE00..=0xFE9F => Some(self.oam[addr - 0xFE00]),
+            0xFF00..=0xFF7F => Some(self.io[addr - 0xFF00]),
+            0xFF80..=0xFFFE => Some(self.hram[addr - 0xFF80]),
+            0xFFFF => Some(self.interrupt_enable),
+            _ => None,
+        }
+    }
+
+    /// Every address that reads differently in `later`, lowest first, with the byte before and
+    /// after. Echo RAM isn't repeated.
+    pub fn changes<'a>(&'a self, later: &'a Self) -> impl Iterator<Item = (u16, u8, u8)> + 'a {
+        (0x8000..=0xFFFF)
+            .filter(|addr| !(0xE000..=0xFDFF).contains(addr))
+            .filter_map(move |addr| {
+                let before = self.get(addr)?;
+                let after = later.get(addr)?;
+                (before != after).then_some((addr, before, after))
+            })
+    }
+}
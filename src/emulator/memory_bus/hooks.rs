@@ -0,0 +1,37 @@
+//! Watching and changing the CPU's memory accesses
+//!
+//! A [`BusHook`] sees every byte the CPU reads or writes as data, the same accesses
+//! [`AccessStats`](super::access_stats::AccessStats) counts, and can change what's read or
+//! written. Instruction fetches and everything that uses [`MemoryBus::peek`](super::MemoryBus::peek)
+//! don't go through them. Nothing is installed normally, and then the bus only checks a flag,
+//! so anything built on hooks (access stats, watchpoints, the LY stub for traces) costs
+//! nothing until it's turned on.
+use std::{any::Any, fmt::Debug};
+
+use super::LCD_Y;
+
+pub trait BusHook: Any + Debug + Send {
+    /// `value` is what's there, returns what the CPU gets
+    fn read(&mut self, _addr: u16, value: u8) -> u8 {
+        value
+    }
+
+    /// Returns what gets written instead, `None` drops the write
+    fn write(&mut self, _addr: u16, value: u8) -> Option<u8> {
+        Some(value)
+    }
+}
+
+/// Makes LY always read the same, see [`MemoryBus::stub_ly`](super::MemoryBus::stub_ly)
+#[derive(Debug)]
+pub struct LyStub(pub u8);
+
+impl BusHook for LyStub {
+    fn read(&mut self, addr: u16, value: u8) -> u8 {
+        if addr == LCD_Y {
+            self.0
+        } else {
+            value
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Memory mapped I/O
+//!
+//! Every register in 0xFF00-0xFF7F, and IE at 0xFFFF, belongs to an [`MmioDevice`]. The bus
+//! finds the one an address belongs to in an [`IoMap`] built from each device's
+//! [`MmioDevice::ranges`], so new hardware is a type implementing the trait and a call to
+//! [`MemoryBus::attach`](super::MemoryBus::attach), not more arms in the bus's own matches.
+//! Addresses no device takes read as 0 and ignore writes.
+use std::{fmt::Debug, ops::RangeInclusive};
+
+use super::Interrupt;
+
+pub trait MmioDevice: Debug + Send {
+    /// The registers it answers for, only 0xFF00-0xFF7F and 0xFFFF can be mapped
+    fn ranges(&self) -> &[RangeInclusive<u16>];
+
+    /// `addr` is always in one of [`MmioDevice::ranges`]. The bus sets the unused bits of the
+    /// built in registers afterwards, attached devices have to set their own.
+    fn read(&self, addr: u16) -> u8;
+
+    /// Returns an interrupt to request, if the write caused one
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt>;
+
+    /// `cycles` T-cycles have passed, returns an interrupt to request
+    fn tick(&mut self, _cycles: u32) -> Option<Interrupt> {
+        None
+    }
+
+    /// T-cycles [`MmioDevice::tick`] can be put off for before it might request an interrupt,
+    /// if nothing is written in between
+    fn cycles_until_interrupt(&self) -> u32 {
+        u32::MAX
+    }
+}
+
+/// Which device each register belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Slot {
+    Joypad,
+    Serial,
+    Timer,
+    Interrupts,
+    Apu,
+    Lcd,
+    Vram,
+    Wram,
+    Palettes,
+    Infrared,
+    Undocumented,
+    /// Index into the devices added with [`MemoryBus::attach`](super::MemoryBus::attach)
+    Attached(usize),
+}
+
+impl Slot {
+    /// Whether the bus sets the unused bits, it only knows about the DMG's registers
+    pub fn masked(self) -> bool {
+        matches!(
+            self,
+            Slot::Joypad | Slot::Serial | Slot::Timer | Slot::Interrupts | Slot::Apu | Slot::Lcd
+        )
+    }
+}
+
+/// 0xFF00-0xFFFF to the [`Slot`] of whatever is there. HRAM is never looked up here.
+#[derive(Debug)]
+pub(super) struct IoMap([Option<Slot>; 0x100]);
+
+impl Default for IoMap {
+    fn default() -> Self {
+        Self([None; 0x100])
+    }
+}
+
+impl IoMap {
+    /// Later devices take over addresses earlier ones had
+    pub fn insert(&mut self, ranges: &[RangeInclusive<u16>], slot: Slot) {
+        for addr in ranges.iter().cloned().flatten() {
+            assert!(
+                matches!(addr, 0xFF00..=0xFF7F | 0xFFFF),
+                "{:?} can't be mapped at {:#06X}",
+                slot,
+                addr
+            );
+            self.0[addr as usize - 0xFF00] = Some(slot);
+        }
+    }
+
+    /// Unmaps whatever in `ranges` still belongs to `slot`
+    pub fn remove(&mut self, ranges: &[RangeInclusive<u16>], slot: Slot) {
+        for addr in ranges.iter().cloned().flatten() {
+            let entry = &mut self.0[addr as usize - 0xFF00];
+            if *entry == Some(slot) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn get(&self, addr: u16) -> Option<Slot> {
+        self.0[addr as usize - 0xFF00]
+    }
+}
@@ -0,0 +1,51 @@
+//! Decoded tile data
+//!
+//! Each row of a tile is two bytes, one with the low bit of every pixel and one with the high
+//! bit. Rows are decoded into one color ID per pixel as they're written, so the PPU can look
+//! pixels up instead of picking bits out of VRAM for every one of them.
+use bit_field::BitField;
+
+use super::vram::BANKS;
+
+/// 0x8000-0x97FF, 384 tiles of 8 rows
+const ROWS: usize = 384 * 8;
+
+#[derive(Debug)]
+pub struct TileCache {
+    /// Color IDs, leftmost pixel first, for each VRAM bank
+    rows: Box<[[[u8; 8]; ROWS]; BANKS]>,
+}
+
+impl Default for TileCache {
+    fn default() -> Self {
+        Self {
+            rows: Box::new([[[0; 8]; ROWS]; BANKS]),
+        }
+    }
+}
+
+impl TileCache {
+    /// Updates the row `offset` (into `bank`'s VRAM) is part of
+    pub fn write(&mut self, bank: usize, vram: &[u8], offset: usize) {
+        let row = offset / 2;
+        if row < ROWS {
+            self.rows[bank][row] = decode(vram[row * 2], vram[row * 2 + 1]);
+        }
+    }
+
+    /// Decodes all of `bank` again from its VRAM
+    pub fn rebuild(&mut self, bank: usize, vram: &[u8]) {
+        for (row, bytes) in self.rows[bank].iter_mut().zip(vram.chunks_exact(2)) {
+            *row = decode(bytes[0], bytes[1]);
+        }
+    }
+
+    /// The row starting at `offset` into `bank`, which has to be in tile data
+    pub fn row(&self, bank: usize, offset: usize) -> &[u8; 8] {
+        &self.rows[bank][offset / 2]
+    }
+}
+
+fn decode(low: u8, high: u8) -> [u8; 8] {
+    std::array::from_fn(|x| (high.get_bit(7 - x) as u8) << 1 | low.get_bit(7 - x) as u8)
+}
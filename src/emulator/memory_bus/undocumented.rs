@@ -0,0 +1,53 @@
+//! The CGB's undocumented registers at 0xFF72-0xFF77
+//!
+//! Nothing is known to use FF72-FF75 for anything, they just hold what's written: all 8 bits
+//! of FF72 and FF73, FF74 too but only in [CGB mode](super::MemoryBus::cgb_mode), and bits
+//! 4-6 of FF75. PCM12 and PCM34 read back the current output of sound channels 1 and 2 and 3
+//! and 4, a nibble each. Sound isn't emulated, so they read 0 like silent channels. There's
+//! none of this on a DMG.
+use std::ops::RangeInclusive;
+
+use tracing::trace;
+
+use super::{mmio::MmioDevice, Interrupt, PCM12, PCM34};
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+/// FF72-FF75 as written
+#[derive(Debug, Default)]
+pub struct Undocumented([u8; 4]);
+
+impl Undocumented {
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.0);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.fill(&mut self.0)?;
+        self.0[3] &= 0b0111_0000;
+        Ok(())
+    }
+}
+
+impl MmioDevice for Undocumented {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[0xFF72..=PCM34]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF75 => self.0[3] | 0b1000_1111,
+            PCM12 | PCM34 => 0x00,
+            _ => self.0[addr as usize - 0xFF72],
+        }
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "bus", "Undocumented register write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            0xFF75 => self.0[3] = byte & 0b0111_0000,
+            PCM12 | PCM34 => {}
+            _ => self.0[addr as usize - 0xFF72] = byte,
+        }
+        None
+    }
+}
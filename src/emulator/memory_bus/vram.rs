@@ -0,0 +1,149 @@
+//! Video RAM at 0x8000-0x9FFF
+//!
+//! In CGB mode there are two banks and VBK picks the one the CPU sees. The PPU reads both
+//! whatever VBK says: tile maps are in bank 0, with each tile's [`TileAttributes`] at the same
+//! address in bank 1, and tile data can come from either.
+use std::ops::RangeInclusive;
+
+use bit_field::BitField;
+use tracing::trace;
+
+use super::{mmio::MmioDevice, tile_cache::TileCache, Interrupt, VBK};
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+pub const BANK_SIZE: usize = 0x2000;
+pub const BANKS: usize = 2;
+
+#[derive(Debug)]
+pub struct Vram {
+    banks: [[u8; BANK_SIZE]; BANKS],
+    tile_cache: TileCache,
+    /// VBK's bank bit
+    bank: u8,
+}
+
+impl Default for Vram {
+    fn default() -> Self {
+        Self {
+            banks: [[0; BANK_SIZE]; BANKS],
+            tile_cache: TileCache::default(),
+            bank: 0,
+        }
+    }
+}
+
+impl Vram {
+    /// The bank the CPU sees
+    pub fn mapped_bank(&self) -> usize {
+        self.bank as usize
+    }
+
+    /// `addr` has to be in 0x8000-0x9FFF
+    pub fn read(&self, addr: u16) -> u8 {
+        self.get(self.mapped_bank(), addr)
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        let bank = self.mapped_bank();
+        let offset = addr as usize - 0x8000;
+        self.banks[bank][offset] = byte;
+        self.tile_cache.write(bank, &self.banks[bank], offset);
+    }
+
+    /// What's at `addr` in `bank`, whichever is mapped
+    pub fn get(&self, bank: usize, addr: u16) -> u8 {
+        self.banks[bank][addr as usize - 0x8000]
+    }
+
+    /// See [`MemoryBus::tile_row`](super::MemoryBus::tile_row)
+    pub fn tile_row(&self, bank: usize, addr: u16) -> &[u8; 8] {
+        self.tile_cache.row(bank, addr as usize - 0x8000)
+    }
+
+    pub fn banks(&self) -> &[[u8; BANK_SIZE]; BANKS] {
+        &self.banks
+    }
+
+    /// Replaces every bank
+    pub fn set_banks(&mut self, banks: &[[u8; BANK_SIZE]; BANKS]) {
+        self.banks = *banks;
+        self.rebuild();
+    }
+
+    /// Back to bank 0, outside of CGB mode
+    pub fn unbank(&mut self) {
+        self.bank = 0;
+    }
+
+    fn rebuild(&mut self) {
+        for (bank, vram) in self.banks.iter().enumerate() {
+            self.tile_cache.rebuild(bank, vram);
+        }
+    }
+
+    pub fn save_banks(&self, state: &mut StateWriter) {
+        for bank in &self.banks {
+            state.bytes(bank);
+        }
+    }
+
+    pub fn load_banks(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for bank in &mut self.banks {
+            state.fill(bank)?;
+        }
+        self.rebuild();
+        Ok(())
+    }
+
+    /// VBK goes with the other registers, see [`MemoryBus::dump`](super::MemoryBus::dump)
+    pub fn save_bank_register(&self, state: &mut StateWriter) {
+        state.u8(self.bank);
+    }
+
+    pub fn load_bank_register(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.bank = state.u8()? & 1;
+        Ok(())
+    }
+}
+
+/// VBK, only mapped in CGB mode
+impl MmioDevice for Vram {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[VBK..=VBK]
+    }
+
+    fn read(&self, _addr: u16) -> u8 {
+        self.bank | 0b1111_1110
+    }
+
+    fn write(&mut self, _addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "bus", "VBK write: {:#X}", byte);
+        self.bank = byte & 1;
+        None
+    }
+}
+
+/// The byte in bank 1 for each tile in a tile map
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TileAttributes {
+    /// Which of the 8 background palettes
+    pub palette: u8,
+    /// Which VRAM bank the tile data is in
+    pub bank: usize,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    /// Drawn over objects whatever their own priority says
+    pub priority: bool,
+}
+
+impl From<u8> for TileAttributes {
+    fn from(byte: u8) -> Self {
+        Self {
+            palette: byte.get_bits(0..3),
+            bank: byte.get_bit(3) as usize,
+            x_flip: byte.get_bit(5),
+            y_flip: byte.get_bit(6),
+            priority: byte.get_bit(7),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Work RAM at 0xC000-0xDFFF
+//!
+//! There are 8 banks of 4K. Bank 0 is always at 0xC000, and in
+//! [CGB mode](super::MemoryBus::cgb_mode) SVBK picks the one at 0xD000. Otherwise it's always
+//! bank 1 there.
+use std::ops::RangeInclusive;
+
+use tracing::trace;
+
+use super::{mmio::MmioDevice, Interrupt, SVBK};
+use crate::emulator::state::{StateError, StateReader, StateWriter};
+
+pub const BANK_SIZE: usize = 0x1000;
+pub const BANKS: usize = 8;
+
+#[derive(Debug)]
+pub struct Wram {
+    banks: [[u8; BANK_SIZE]; BANKS],
+    /// SVBK's bank bits, as written
+    bank: u8,
+}
+
+impl Default for Wram {
+    fn default() -> Self {
+        Self {
+            banks: [[0; BANK_SIZE]; BANKS],
+            bank: 0,
+        }
+    }
+}
+
+impl Wram {
+    /// The bank at 0xD000, selecting bank 0 gives bank 1
+    pub fn mapped_bank(&self) -> usize {
+        (self.bank as usize).max(1)
+    }
+
+    /// `addr` has to be in 0xC000-0xDFFF
+    pub fn read(&self, addr: u16) -> u8 {
+        let (bank, offset) = self.locate(addr);
+        self.banks[bank][offset]
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        let (bank, offset) = self.locate(addr);
+        self.banks[bank][offset] = byte;
+    }
+
+    fn locate(&self, addr: u16) -> (usize, usize) {
+        let offset = addr as usize & (BANK_SIZE - 1);
+        match addr {
+            0xC000..=0xCFFF => (0, offset),
+            _ => (self.mapped_bank(), offset),
+        }
+    }
+
+    pub fn banks(&self) -> &[[u8; BANK_SIZE]; BANKS] {
+        &self.banks
+    }
+
+    pub fn banks_mut(&mut self) -> &mut [[u8; BANK_SIZE]; BANKS] {
+        &mut self.banks
+    }
+
+    /// Back to bank 1, outside of CGB mode
+    pub fn unbank(&mut self) {
+        self.bank = 0;
+    }
+
+    pub fn save_banks(&self, state: &mut StateWriter) {
+        for bank in &self.banks {
+            state.bytes(bank);
+        }
+    }
+
+    pub fn load_banks(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for bank in &mut self.banks {
+            state.fill(bank)?;
+        }
+        Ok(())
+    }
+
+    /// SVBK goes with the other registers, see
+    /// [`MemoryBus::dump`](super::MemoryBus::dump)
+    pub fn save_bank_register(&self, state: &mut StateWriter) {
+        state.u8(self.bank);
+    }
+
+    pub fn load_bank_register(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.bank = state.u8()? & 0b111;
+        Ok(())
+    }
+}
+
+/// SVBK, only mapped in CGB mode
+impl MmioDevice for Wram {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[SVBK..=SVBK]
+    }
+
+    fn read(&self, _addr: u16) -> u8 {
+        self.bank | 0b1111_1000
+    }
+
+    fn write(&mut self, _addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "bus", "SVBK write: {:#X}", byte);
+        self.bank = byte & 0b111;
+        None
+    }
+}
@@ -0,0 +1,66 @@
+//! Which parts of memory have been written to
+//!
+//! Anything that keeps its own decoded copy of memory, like the cached interpreter's blocks,
+//! needs to know when that copy goes stale. The bus marks the 64 byte page every write lands
+//! in, and whoever cares takes the set every so often with
+//! [`MemoryBus::take_written_pages`](super::MemoryBus::take_written_pages).
+
+pub const PAGE_SIZE: u16 = 64;
+const PAGES: usize = 0x10000 / PAGE_SIZE as usize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageSet {
+    bits: [u64; PAGES / 64],
+}
+
+impl Default for PageSet {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl PageSet {
+    pub const EMPTY: Self = Self {
+        bits: [0; PAGES / 64],
+    };
+    pub const ALL: Self = Self {
+        bits: [u64::MAX; PAGES / 64],
+    };
+
+    /// Marks the page `addr` is in
+    pub fn insert(&mut self, addr: u16) {
+        let page = (addr / PAGE_SIZE) as usize;
+        self.bits[page / 64] |= 1 << (page % 64);
+    }
+
+    /// Marks every page from the one `start` is in to the one `end` is in
+    pub fn insert_range(&mut self, start: u16, end: u16) {
+        for page in start / PAGE_SIZE..=end / PAGE_SIZE {
+            self.insert(page * PAGE_SIZE);
+        }
+    }
+
+    /// Adds every page in `other`
+    pub fn extend(&mut self, other: &Self) {
+        for (ours, theirs) in self.bits.iter_mut().zip(&other.bits) {
+            *ours |= theirs;
+        }
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        let page = (addr / PAGE_SIZE) as usize;
+        self.bits[page / 64] & (1 << (page % 64)) != 0
+    }
+
+    /// Whether any page is in both
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.bits
+            .iter()
+            .zip(&other.bits)
+            .any(|(ours, theirs)| ours & theirs != 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&bits| bits == 0)
+    }
+}
@@ -0,0 +1,179 @@
+//! Input movies
+//!
+//! The format is a small header followed by one byte per frame:
+//!
+//! | Offset | Size | Contents                                           |
+//! |--------|------|----------------------------------------------------|
+//! | 0      | 4    | `GBMV`                                             |
+//! | 4      | 1    | Format version                                     |
+//! | 5      | 1    | Start point, see [`MovieStart`]                    |
+//! | 6      | 2    | Global checksum of the ROM the movie was made with |
+//! | 8      | ...  | Joypad state for every frame, see below            |
+//!
+//! Each frame byte is the set of buttons the game saw (after turbo) while running that frame,
+//! 1 = pressed: Right, Left, Up, Down, A, B, Select, Start from bit 0 to bit 7.
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+pub const MAGIC: &[u8; 4] = b"GBMV";
+pub const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieStart {
+    /// Recording started from a freshly powered on console
+    PowerOn,
+}
+
+impl MovieStart {
+    fn to_byte(self) -> u8 {
+        match self {
+            MovieStart::PowerOn => 0,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(MovieStart::PowerOn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownStart(u8),
+}
+
+impl std::fmt::Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovieError::Io(e) => write!(f, "{}", e),
+            MovieError::BadMagic => write!(f, "Not a movie file"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported movie version {}", version)
+            }
+            MovieError::UnknownStart(start) => write!(f, "Unknown movie start point {}", start),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        MovieError::Io(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub start: MovieStart,
+    pub rom_checksum: u16,
+}
+
+impl MovieHeader {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.start.to_byte()])?;
+        writer.write_all(&self.rom_checksum.to_be_bytes())
+    }
+
+    fn parse(bytes: &[u8; HEADER_LEN]) -> Result<Self, MovieError> {
+        if &bytes[0..4] != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        if bytes[4] != VERSION {
+            return Err(MovieError::UnsupportedVersion(bytes[4]));
+        }
+        let start = MovieStart::from_byte(bytes[5]).ok_or(MovieError::UnknownStart(bytes[5]))?;
+        Ok(Self {
+            start,
+            rom_checksum: u16::from_be_bytes([bytes[6], bytes[7]]),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub frames: Vec<u8>,
+}
+
+impl Movie {
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, MovieError> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let header = MovieHeader::parse(&header)?;
+        let mut frames = Vec::new();
+        reader.read_to_end(&mut frames)?;
+        Ok(Self { header, frames })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.header.write(&mut writer)?;
+        writer.write_all(&self.frames)
+    }
+}
+
+/// Streams frames out as they happen, so a crash only loses what's still buffered
+pub struct MovieRecorder<W: Write> {
+    writer: W,
+    frames: usize,
+}
+
+impl<W: Write> MovieRecorder<W> {
+    pub fn new(mut writer: W, header: MovieHeader) -> io::Result<Self> {
+        header.write(&mut writer)?;
+        Ok(Self { writer, frames: 0 })
+    }
+
+    pub fn record_frame(&mut self, buttons: u8) -> io::Result<()> {
+        self.frames += 1;
+        self.writer.write_all(&[buttons])
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub struct MoviePlayer {
+    movie: Movie,
+    position: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, position: 0 }
+    }
+
+    pub fn header(&self) -> MovieHeader {
+        self.movie.header
+    }
+
+    /// Input for the next frame, or None once the movie has ended
+    pub fn next_frame(&mut self) -> Option<u8> {
+        let frame = self.movie.frames.get(self.position).copied();
+        if frame.is_some() {
+            self.position += 1;
+        }
+        frame
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MovieMode {
+    Record(PathBuf),
+    Play(PathBuf),
+}
@@ -0,0 +1,84 @@
+//! Where everything the emulator keeps between runs goes
+//!
+//! By default that's the platform's usual places: `$XDG_CONFIG_HOME` and `$XDG_DATA_HOME` (or
+//! `~/.config` and `~/.local/share`) on Linux and the BSDs, `%APPDATA%` on Windows and
+//! `~/Library/Application Support` on macOS, each with a `gameboy_emulator` directory in it.
+//! Everything used to go in a `config` directory next to the executable, if there's one there
+//! it's still used, which also makes for portable installs.
+//!
+//! | Directory   | What's in it                                               |
+//! |-------------|------------------------------------------------------------|
+//! | config      | The recently opened ROMs                                   |
+//! | saves       | Battery backed cartridge RAM, see [`crate::emulator::mbc`] |
+//! | states      | States saved by `--resume`, see [`crate::emulator::state`] |
+//! | cheats      | Each game's cheats, see [`crate::emulator::cheats`]        |
+//! | screenshots | Pictures of the screen, nothing takes any yet              |
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+const APP_DIR: &str = "gameboy_emulator";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Paths {
+    pub config: PathBuf,
+    pub saves: PathBuf,
+    pub states: PathBuf,
+    pub cheats: PathBuf,
+    pub screenshots: PathBuf,
+}
+
+impl Paths {
+    /// Everything in `dir`, with the per-game files in a directory each
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::split(dir, dir)
+    }
+
+    /// Settings in `config`, everything else in directories in `data`
+    fn split(config: &Path, data: &Path) -> Self {
+        Self {
+            config: config.to_path_buf(),
+            saves: data.join("saves"),
+            states: data.join("states"),
+            cheats: data.join("cheats"),
+            screenshots: data.join("screenshots"),
+        }
+    }
+
+    /// The platform's usual places, `None` if there's no home directory to put them in
+    pub fn platform() -> Option<Self> {
+        let var = |name| {
+            env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        if cfg!(windows) {
+            let app_data = var("APPDATA")?.join(APP_DIR);
+            Some(Self::in_dir(&app_data))
+        } else if cfg!(target_os = "macos") {
+            let support = var("HOME")?
+                .join("Library/Application Support")
+                .join(APP_DIR);
+            Some(Self::in_dir(&support))
+        } else {
+            let home = var("HOME");
+            let config = var("XDG_CONFIG_HOME").or_else(|| Some(home.as_ref()?.join(".config")))?;
+            let data = var("XDG_DATA_HOME").or_else(|| Some(home?.join(".local/share")))?;
+            Some(Self::split(&config.join(APP_DIR), &data.join(APP_DIR)))
+        }
+    }
+
+    /// The `config` directory next to the executable if there is one, otherwise
+    /// [`Paths::platform`]
+    pub fn find() -> Option<Self> {
+        let portable = env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join("config")))
+            .filter(|dir| dir.is_dir());
+        match portable {
+            Some(dir) => Some(Self::in_dir(&dir)),
+            None => Self::platform(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Minimal PNG encoder
+//!
+//! Only 8-bit grayscale, and the image data goes into uncompressed deflate blocks. The files
+//! end up bigger than they need to be, but any viewer can open them.
+use std::io::{self, Write};
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+/// Biggest stored deflate block
+const MAX_BLOCK: usize = 0xFFFF;
+
+/// `pixels` is row-major, one byte per pixel, 0 = black
+pub fn write_grayscale<W: Write>(
+    mut writer: W,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize);
+    writer.write_all(SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits, grayscale, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_chunk(&mut writer, b"IHDR", &header)?;
+
+    // Every scanline starts with its filter type, 0 = none
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width.max(1) as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(&mut writer, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(&mut writer, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(&[kind.as_slice(), data]);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len() / MAX_BLOCK + 1;
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    // 32K window, no dictionary, check bits so the header is a multiple of 31
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
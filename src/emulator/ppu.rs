@@ -1,72 +1,375 @@
 use bit_field::BitField;
 
-use tracing::{debug, trace};
+use tracing::{debug, trace, trace_span};
 
 use crate::emulator::{
-    memory_bus::{MemoryBus, LCDC, LCD_Y, PALLETE, SCROLL_X, SCROLL_Y},
+    memory_bus::{
+        MemoryBus, LCDC, LCD_Y, OBP0, OBP1, PALLETE, SCROLL_X, SCROLL_Y, STAT, WINDOW_X, WINDOW_Y,
+    },
+    state::{StateError, StateReader, StateWriter},
     GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
 };
 
 use super::memory_bus::Interrupt;
+use palettes::ObjectPriority;
+
+pub mod compat_palettes;
+pub mod flash_filter;
+pub mod frame_blend;
+pub mod palettes;
 
 pub type FrameBuffer = [u8; GAMEBOY_HEIGHT * GAMEBOY_WIDTH];
+/// RGB555 like palette RAM, what CGB games draw
+pub type ColorFrameBuffer = [u16; GAMEBOY_HEIGHT * GAMEBOY_WIDTH];
+
+/// T-cycles per scanline
+const LINE_CYCLES: u32 = 456;
+/// T-cycles per frame, 144 visible lines and 10 of V-blank
+pub const FRAME_CYCLES: u32 = LINE_CYCLES * 154;
+
+/// What each of the four colors in a palette looks like in the frame buffer, lightest first
+const SHADES: [u8; 4] = [255, 192, 95, 0];
+const WHITE: u16 = 0x7FFF;
+
+/// Where mode 3 starts
+const MODE_3_START: u32 = 84;
+/// Where mode 3 ends when nothing makes it longer, see [`PPU::mode_3_penalty`]
+const MODE_3_END: u32 = 256;
+/// The only points in a line where LY, the LYC comparison or the mode can change, besides
+/// wherever mode 3 ends
+const LINE_EVENTS: [u32; 5] = [4, 8, 12, MODE_3_START, LINE_CYCLES];
+/// OAM entries mode 3 fetches at most, the first ten on the line
+const LINE_OBJECTS: usize = 10;
+/// How much of the window's left edge is cut off at WX 0 by each SCX % 8. It starts while
+/// those are being thrown away, earlier the more there are, rather than 7 pixels in like it
+/// otherwise would at 0.
+const WX_0_SKIP: [usize; 8] = [7, 9, 10, 11, 12, 13, 14, 14];
+
+/// See [`PPU::color_frame`]
+#[derive(Debug)]
+struct ColorFrame(Box<ColorFrameBuffer>);
+
+impl Default for ColorFrame {
+    fn default() -> Self {
+        Self(Box::new([WHITE; GAMEBOY_HEIGHT * GAMEBOY_WIDTH]))
+    }
+}
+
+/// A background or window pixel before it's looked up in a palette
+#[derive(Clone, Copy, Debug, Default)]
+struct TilePixel {
+    color_id: u8,
+    /// The CGB palette from the tile's attributes
+    palette: u8,
+    /// The tile's attributes put it over objects
+    priority: bool,
+}
+
+/// An object's pixel, drawn over the background unless one of them says otherwise
+#[derive(Clone, Copy, Debug)]
+struct ObjectPixel {
+    /// Never 0, that's see-through
+    color_id: u8,
+    /// OBP0 or OBP1, or the CGB palette
+    palette: u8,
+    /// Only drawn over background color 0
+    behind_bg: bool,
+}
+
+/// Whichever of the background and objects ends up on screen
+#[derive(Clone, Copy, Debug)]
+enum Pixel {
+    Tile(TilePixel),
+    Object(ObjectPixel),
+}
+
+/// How bright an RGB555 color is, for the shades that go with colors
+fn brightness(color: u16) -> u8 {
+    let channel = |shift: u16| (color >> shift & 0x1F) as u32;
+    let luma = channel(0) * 299 + channel(5) * 587 + channel(10) * 114;
+    (luma * 255 / (31 * 1000)) as u8
+}
 
 #[derive(Debug, Default)]
 pub struct PPU {
-    pub updated: bool,
     mode_clock: u32,
     hblanking: bool,
+    /// The line being drawn, which isn't always what LY reads
+    line: u8,
+    lcd_off: bool,
+    /// T-cycles into the current blank frame while the LCD is off
+    off_clock: u32,
+    /// How much longer than usual mode 3 is on this line, worked out as it starts
+    mode_3_penalty: u32,
+    /// SCX's low 3 bits as mode 3 started, the pixels it throws away. The rest of SCX is
+    /// read as each tile is fetched.
+    fine_scroll_x: u8,
+    /// Whether WY has matched LY since V-blank, no window until it has
+    window_y_triggered: bool,
+    /// The window's own LY, which only counts the lines it's drawn on
+    window_line: u8,
+    color_frame: ColorFrame,
 }
 
 impl PPU {
-    /// Ticks in T-cycles
-    pub fn tick(&mut self, memory_bus: &mut MemoryBus, frame_buffer: &mut FrameBuffer, ticks: u32) {
-        let lcd_control = memory_bus.read_u8(LCDC);
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u32(self.mode_clock);
+        state.bool(self.hblanking);
+        state.u8(self.line);
+        state.bool(self.lcd_off);
+        state.u32(self.off_clock);
+        state.u32(self.mode_3_penalty);
+        state.u8(self.fine_scroll_x);
+        state.bool(self.window_y_triggered);
+        state.u8(self.window_line);
+        for &color in self.color_frame.0.iter() {
+            state.u16(color);
+        }
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.mode_clock = state.u32()? % LINE_CYCLES;
+        self.hblanking = state.bool()?;
+        self.line = state.u8()? % 154;
+        self.lcd_off = state.bool()?;
+        self.off_clock = state.u32()? % FRAME_CYCLES;
+        self.mode_3_penalty = state.u32()? % (LINE_CYCLES - MODE_3_END);
+        self.fine_scroll_x = state.u8()? & 0x07;
+        self.window_y_triggered = state.bool()?;
+        self.window_line = state.u8()? % 144;
+        for color in self.color_frame.0.iter_mut() {
+            *color = state.u16()? & WHITE;
+        }
+        Ok(())
+    }
+
+    /// Whether the LCD is switched off, frames are blank then
+    pub fn lcd_off(&self) -> bool {
+        self.lcd_off
+    }
+
+    /// What the frame buffer has in color, which only color models draw. The frame buffer gets
+    /// each color's brightness then.
+    pub fn color_frame(&self) -> &ColorFrameBuffer {
+        &self.color_frame.0
+    }
+
+    /// Ticks in T-cycles. Returns true if that finished a frame, either by starting V-blank or
+    /// a blank frame's worth of time with the LCD off.
+    pub fn tick(
+        &mut self,
+        memory_bus: &mut MemoryBus,
+        frame_buffer: &mut FrameBuffer,
+        ticks: u32,
+    ) -> bool {
+        let _span = trace_span!(target: "ppu", "tick", line = self.line).entered();
+        let lcd_control = memory_bus.peek(LCDC);
         if !lcd_control.get_bit(7) {
-            trace!("LCD control disabled, skipping tick: {:#X}", lcd_control);
-            return;
+            trace!(target: "ppu", "LCD control disabled, skipping tick: {:#X}", lcd_control);
+            if !self.lcd_off {
+                // The screen goes white as soon as it's off, not whatever was drawn last
+                self.lcd_off = true;
+                self.off_clock = 0;
+                frame_buffer.fill(255);
+                self.color_frame.0.fill(WHITE);
+            }
+            self.line = 0;
+            self.mode_clock = 0;
+            self.window_y_triggered = false;
+            self.window_line = 0;
+            // Blank frames keep coming at the usual rate, so whatever waits for frames doesn't
+            // stall while a game leaves the screen off
+            self.off_clock += ticks;
+            if self.off_clock < FRAME_CYCLES {
+                return false;
+            }
+            self.off_clock -= FRAME_CYCLES;
+            return true;
         }
+        self.lcd_off = false;
 
         self.hblanking = false;
 
-        let mut ticks_left = ticks;
-        let mut lcd_y = memory_bus.read_u8(LCD_Y);
-        debug!("Running at {:#X} for {:#X} ticks", lcd_y, ticks_left);
-        while ticks_left > 0 {
-            let cur_ticks = ticks_left.min(80);
-            self.mode_clock += cur_ticks;
-            ticks_left -= cur_ticks;
-
-            if self.mode_clock >= 456 {
-                self.mode_clock -= 456;
-                lcd_y = (lcd_y + 1) % 154;
-                memory_bus.write_u8(LCD_Y, lcd_y);
-                memory_bus.update_lcd_stat();
-
-                if lcd_y >= 144 {
-                    self.change_mode_if_necessary(1, memory_bus, frame_buffer);
+        debug!(target: "ppu", "Running at {:#X} for {:#X} ticks", self.line, ticks);
+        let mut frame_done = false;
+        // M-cycles are as fine as LY and STAT changes get, and nothing changes between
+        // LINE_EVENTS so those stretches are skipped in one go
+        let mut remaining = ticks.div_ceil(4) * 4;
+        while remaining > 0 {
+            let step = (self.next_event() - self.mode_clock).min(remaining);
+            remaining -= step;
+            self.mode_clock += step;
+            if self.mode_clock >= LINE_CYCLES {
+                self.mode_clock -= LINE_CYCLES;
+                self.line = (self.line + 1) % 154;
+
+                if self.line == 144 {
+                    frame_done |= self.change_mode_if_necessary(1, memory_bus, frame_buffer);
                 }
             }
+            self.update_ly(memory_bus);
+
+            if self.line < 144 {
+                if self.mode_clock == MODE_3_START {
+                    // Whenever it matches, so moving WY to a line that's already been drawn
+                    // doesn't bring it up until the next frame
+                    if memory_bus.peek(WINDOW_Y) == self.line {
+                        self.window_y_triggered = true;
+                    }
+                    self.fine_scroll_x = memory_bus.peek(SCROLL_X) & 0x07;
+                    self.mode_3_penalty = self.mode_3_penalty(memory_bus);
+                }
+                let mode = match self.mode_clock {
+                    0..MODE_3_START => 2,
+                    clock if clock < self.mode_3_end() => 3,
+                    _ => 0,
+                };
+                self.change_mode_if_necessary(mode, memory_bus, frame_buffer);
+            }
+        }
+        if ticks > 0 {
+            let scanning = self.line < 144 && self.mode_clock < 80;
+            memory_bus.set_oam_scan_row(scanning.then_some((self.mode_clock / 4) as u8));
+        }
+        frame_done
+    }
+
+    /// How many T-cycles [`PPU::tick`] can be put off for without an interrupt or a finished
+    /// frame coming late. Everything else it does only shows through the LCD registers, so
+    /// catching up before the CPU touches those (or VRAM and OAM) is just as good.
+    pub fn cycles_until_event(&self, memory_bus: &MemoryBus) -> u32 {
+        // Switching on or off, that needs handling straight away
+        if memory_bus.peek(LCDC).get_bit(7) == self.lcd_off {
+            return 4;
+        }
+        if self.lcd_off {
+            return FRAME_CYCLES - self.off_clock;
+        }
+        // Any STAT interrupt source could go off at the next change
+        if memory_bus.peek(STAT) & 0b0111_1000 != 0 {
+            return self.next_event() - self.mode_clock;
+        }
+        // Otherwise it's just V-blank
+        let lines = match self.line {
+            0..=143 => 144 - self.line as u32,
+            _ => 154 - self.line as u32 + 144,
+        };
+        lines * LINE_CYCLES - self.mode_clock
+    }
+
+    /// The first of [`LINE_EVENTS`] or the end of mode 3 after `mode_clock`
+    fn next_event(&self) -> u32 {
+        LINE_EVENTS
+            .into_iter()
+            .chain([self.mode_3_end()])
+            .filter(|&event| event > self.mode_clock)
+            .min()
+            .unwrap_or(LINE_CYCLES)
+    }
 
-            if lcd_y < 144 {
-                match self.mode_clock {
-                    0..=80 => self.change_mode_if_necessary(2, memory_bus, frame_buffer),
-                    81..=252 => self.change_mode_if_necessary(3, memory_bus, frame_buffer),
-                    _ => self.change_mode_if_necessary(0, memory_bus, frame_buffer),
+    fn mode_3_end(&self) -> u32 {
+        MODE_3_END + self.mode_3_penalty
+    }
+
+    /// How many extra T-cycles mode 3 takes on this line, rounded up to an M-cycle since
+    /// that's as fine as STAT changes get. Throwing away the first SCX % 8 pixels costs a
+    /// T-cycle each, and the window starting costs 6 for the fetcher to start over. Each of
+    /// the objects on the line costs 6 to fetch, plus up to 5 waiting for the background or
+    /// window tile its left edge is in to be fetched: none when it's in the last 3 pixels, and
+    /// only for the first object in a tile. One at X 0 always waits the full 5. DMGs don't
+    /// fetch objects while they're turned off, color models still do.
+    fn mode_3_penalty(&self, memory_bus: &MemoryBus) -> u32 {
+        let lcd_control = memory_bus.peek(LCDC);
+        let window_x = memory_bus.peek(WINDOW_X);
+        let window = self.window_visible(memory_bus);
+        let mut penalty = self.fine_scroll_x as u32;
+        if window {
+            penalty += 6;
+        }
+
+        if lcd_control.get_bit(1) || memory_bus.model().is_color() {
+            let scroll_x = memory_bus.peek(SCROLL_X);
+            // Which background or window tile each one waited for
+            let mut waited = Vec::with_capacity(LINE_OBJECTS);
+            let objects = self
+                .line_objects(memory_bus, lcd_control)
+                .into_iter()
+                .map(|i| memory_bus.oam()[i * 4 + 1]);
+            for x in objects {
+                // Off the right edge, the fetcher never gets to it
+                if x >= 168 {
+                    continue;
+                }
+                penalty += 6;
+                if x == 0 {
+                    penalty += 5;
+                    continue;
+                }
+                // Where its left edge is in the background or window, 8 pixels to the right
+                let (in_window, layer_x) = if window && x > window_x {
+                    (true, x as u16 + 7 - window_x as u16)
+                } else {
+                    (false, x as u16 + scroll_x as u16)
+                };
+                let tile = (in_window, layer_x / 8);
+                if !waited.contains(&tile) {
+                    waited.push(tile);
+                    penalty += 5u32.saturating_sub(layer_x as u32 % 8);
                 }
             }
         }
+        penalty.next_multiple_of(4)
+    }
+
+    /// OAM indexes of the objects on the current line, only the first ten count
+    fn line_objects(&self, memory_bus: &MemoryBus, lcd_control: u8) -> Vec<usize> {
+        let height = object_height(lcd_control);
+        let top = self.line as u16 + 16;
+        let oam = memory_bus.oam();
+        (0..40)
+            .filter(|&i| {
+                let y = oam[i * 4] as u16;
+                (y..y + height).contains(&top)
+            })
+            .take(LINE_OBJECTS)
+            .collect()
+    }
+
+    /// LY changes at the start of each line, but the LYC comparison is blanked for the first
+    /// M-cycle of it. Line 153 is odd: LY only reads 153 for one M-cycle before wrapping early,
+    /// so LYC can match 153 very briefly and then matches 0 for the rest of the line and
+    /// the whole of line 0.
+    fn update_ly(&self, memory_bus: &mut MemoryBus) {
+        match (self.line, self.mode_clock) {
+            // Already 0 since line 153
+            (0, 0) => memory_bus.set_lcd_y(0),
+            (line, 0) => {
+                memory_bus.set_lcd_y(line);
+                memory_bus.set_compare_ly(None);
+            }
+            (153, 4) => {
+                memory_bus.set_lcd_y(0);
+                memory_bus.set_compare_ly(Some(153));
+            }
+            (153, 8) => memory_bus.set_compare_ly(None),
+            (153, 12) => memory_bus.set_compare_ly(Some(0)),
+            (line, 4) => memory_bus.set_compare_ly(Some(line)),
+            _ => {}
+        }
     }
 
+    /// Returns true if the mode changed
     fn change_mode_if_necessary(
         &mut self,
         mode: u8,
         memory_bus: &mut MemoryBus,
         frame_buffer: &mut FrameBuffer,
-    ) {
-        if memory_bus.get_lcd_mode() != mode {
+    ) -> bool {
+        let changed = memory_bus.get_lcd_mode() != mode;
+        if changed {
             self.change_mode(mode, memory_bus, frame_buffer);
         }
+        changed
     }
 
     fn change_mode(
@@ -84,112 +387,263 @@ impl PPU {
             }
             1 => {
                 memory_bus.request_interrupt(Interrupt::VBlank);
-                self.updated = true;
+                self.window_y_triggered = false;
+                self.window_line = 0;
             }
             _ => {}
         }
     }
 
+    /// Whether the window's drawn on this line: it's turned on, WY has matched and WX isn't
+    /// past the right edge. At WX 166 only its first pixel shows, but that still counts.
+    fn window_visible(&self, memory_bus: &MemoryBus) -> bool {
+        self.window_y_triggered
+            && memory_bus.peek(LCDC).get_bit(5)
+            && memory_bus.peek(WINDOW_X) <= 166
+    }
+
+    /// Draws the whole of the current line a tile row at a time, then puts the objects on it
     fn render_scanline(&mut self, memory_bus: &MemoryBus, frame_buffer: &mut FrameBuffer) {
-        for x in 0..GAMEBOY_WIDTH {
-            self.set_color(x, 255, memory_bus, frame_buffer);
+        let mut line = [TilePixel::default(); GAMEBOY_WIDTH];
+        let mut objects = [None; GAMEBOY_WIDTH];
+        let lcd_control = memory_bus.peek(LCDC);
+        let window = self.window_visible(memory_bus);
+        // In CGB mode the background and window are still drawn, they just lose their
+        // priority over objects. DMG games on color models get the DMG's blank line.
+        let blank = !lcd_control.get_bit(0) && !memory_bus.cgb_mode();
+        if blank {
+            trace!(target: "ppu", "Skipping Background due to LCDC0");
+        } else {
+            self.draw_bg(memory_bus, lcd_control, &mut line);
+            if window {
+                self.draw_window(memory_bus, lcd_control, &mut line);
+            }
+        }
+        // Turning it off for a few lines or moving it off screen picks up where it left off
+        if window {
+            self.window_line += 1;
+        }
+        if lcd_control.get_bit(1) {
+            self.draw_objects(memory_bus, lcd_control, &mut objects);
+        }
+        // What's left once the background and objects have been mixed. Objects always win in
+        // CGB mode with LCDC bit 0 clear, whatever either of them says.
+        let bg_priority = lcd_control.get_bit(0) || !memory_bus.cgb_mode();
+        let pixels = line
+            .iter()
+            .zip(&objects)
+            .map(|(tile, object)| match object {
+                Some(object)
+                    if tile.color_id == 0
+                        || !(bg_priority && (object.behind_bg || tile.priority)) =>
+                {
+                    Pixel::Object(*object)
+                }
+                _ => Pixel::Tile(*tile),
+            });
+
+        let start = memory_bus.peek(LCD_Y) as usize * GAMEBOY_WIDTH;
+        let shades = &mut frame_buffer[start..start + GAMEBOY_WIDTH];
+        if memory_bus.cgb_mode() {
+            let palettes = memory_bus.palettes();
+            let colors = &mut self.color_frame.0[start..start + GAMEBOY_WIDTH];
+            for ((shade, color), pixel) in shades.iter_mut().zip(colors).zip(pixels) {
+                *color = match pixel {
+                    Pixel::Tile(tile) => palettes.background_color(tile.palette, tile.color_id),
+                    Pixel::Object(object) => palettes.object_color(object.palette, object.color_id),
+                } & WHITE;
+                *shade = brightness(*color);
+            }
+        } else {
+            // A blank line is color 0, but it's white whatever BGP says
+            let pallete = if blank { 0 } else { memory_bus.peek(PALLETE) };
+            let object_palletes = [memory_bus.peek(OBP0), memory_bus.peek(OBP1)];
+            // Color models look the DMG's colors up in palette RAM, background palette 0 and
+            // object palettes 0 and 1, however the boot ROM or the compatibility palette left
+            // them
+            let palettes = memory_bus.model().is_color().then(|| memory_bus.palettes());
+            let colors = &mut self.color_frame.0[start..start + GAMEBOY_WIDTH];
+            for ((shade, color), pixel) in shades.iter_mut().zip(colors).zip(pixels) {
+                let (pallete, color_id, object_palette) = match pixel {
+                    Pixel::Tile(tile) => (pallete, tile.color_id as usize, None),
+                    Pixel::Object(object) => (
+                        object_palletes[object.palette as usize],
+                        object.color_id as usize,
+                        Some(object.palette),
+                    ),
+                };
+                let dmg_color = pallete.get_bits(color_id * 2..color_id * 2 + 2);
+                match palettes {
+                    Some(palettes) => {
+                        *color = match object_palette {
+                            None => palettes.background_color(0, dmg_color),
+                            Some(palette) => palettes.object_color(palette, dmg_color),
+                        } & WHITE;
+                        *shade = brightness(*color);
+                    }
+                    None => *shade = SHADES[dmg_color as usize],
+                }
+            }
         }
-        self.draw_bg(memory_bus, frame_buffer);
     }
 
-    fn set_color(
-        &mut self,
-        x: usize,
-        color: u8,
-        memory_bus: &MemoryBus,
-        frame_buffer: &mut FrameBuffer,
-    ) {
-        frame_buffer[memory_bus.read_u8(LCD_Y) as usize * GAMEBOY_WIDTH as usize + x] = color;
+    fn draw_bg(&self, memory_bus: &MemoryBus, lcd_control: u8, line: &mut [TilePixel]) {
+        let bg_y = memory_bus
+            .peek(SCROLL_Y)
+            .wrapping_add(memory_bus.peek(LCD_Y));
+        let scroll_x = (memory_bus.peek(SCROLL_X) & !0x07 | self.fine_scroll_x) as usize;
+        let tile_map_base = if lcd_control.get_bit(3) {
+            0x9C00
+        } else {
+            0x9800
+        };
+        trace!(target: "ppu", "BGY: {:#X}, SCX: {:#X}, TMB: {:#X}", bg_y, scroll_x, tile_map_base);
+        // The first and last tiles are cut short when SCX isn't a multiple of 8, by however
+        // much it was as mode 3 started
+        self.draw_tiles(memory_bus, lcd_control, tile_map_base, scroll_x, bg_y, line);
     }
 
-    fn draw_bg(&mut self, memory_bus: &MemoryBus, frame_buffer: &mut FrameBuffer) {
-        let lcd_control = memory_bus.read_u8(LCDC);
-        if !lcd_control.get_bit(0) {
-            trace!("Skipping Background due to LCDC0");
-            return;
-        }
-
-        let lcd_y = memory_bus.read_u8(LCD_Y);
-
-        let bg_y = memory_bus.read_u8(SCROLL_Y).wrapping_add(lcd_y);
-        let bg_tile_y = (bg_y as u16 >> 3) & 31;
-
-        for x in 0..GAMEBOY_WIDTH {
-            let bg_x = memory_bus.read_u8(SCROLL_X) as u32 + x as u32;
-            trace!("X: {:#X}, BGX: {:#X}", x, bg_x);
-
-            let (tile_map_base, tile_y, tile_x, pixel_y, pixel_x) = {
-                let base_addr = if lcd_control.get_bit(3) {
-                    0x9C00
-                } else {
-                    0x9800
-                };
+    /// Draws the window over the background from WX - 7 on, with its left edge cut off below
+    /// WX 7 (and by [`WX_0_SKIP`] at 0)
+    fn draw_window(&self, memory_bus: &MemoryBus, lcd_control: u8, line: &mut [TilePixel]) {
+        let window_x = memory_bus.peek(WINDOW_X) as usize;
+        let skip = match window_x {
+            0 => WX_0_SKIP[self.fine_scroll_x as usize],
+            1..7 => 7 - window_x,
+            _ => 0,
+        };
+        let tile_map_base = if lcd_control.get_bit(6) {
+            0x9C00
+        } else {
+            0x9800
+        };
+        trace!(target: "ppu", "WX: {:#X}, window line: {:#X}", window_x, self.window_line);
+        let pixels = &mut line[window_x.saturating_sub(7)..];
+        self.draw_tiles(
+            memory_bus,
+            lcd_control,
+            tile_map_base,
+            skip,
+            self.window_line,
+            pixels,
+        );
+    }
 
-                (
-                    base_addr,
-                    bg_tile_y,
-                    (bg_x as u16 >> 3) & 31,
-                    bg_y as u16 & 0x07,
-                    bg_x as u16 & 0x07,
-                )
+    /// Fills `pixels` with the background or window using the tile map at `tile_map_base`,
+    /// from (`x`, `y`) in it on
+    fn draw_tiles(
+        &self,
+        memory_bus: &MemoryBus,
+        lcd_control: u8,
+        tile_map_base: u16,
+        x: usize,
+        y: u8,
+        pixels: &mut [TilePixel],
+    ) {
+        let tile_y = (y as u16 >> 3) & 31;
+        let pixel_y = y as u16 & 0x07;
+
+        let mut drawn = 0;
+        while drawn < pixels.len() {
+            let bg_x = x + drawn;
+            let tile_x = (bg_x as u16 >> 3) & 31;
+            let map_address = tile_map_base + tile_y * 32 + tile_x;
+            // Tile maps are always in bank 0, whatever VBK says
+            let tile_number = memory_bus.vram(0, map_address);
+            let attributes = memory_bus.tile_attributes(map_address);
+            let tile_address = if lcd_control.get_bit(4) {
+                0x8000 + tile_number as u16 * 16
+            } else {
+                0x8800 + (tile_number as i8 as i16 + 128) as u16 * 16
             };
-
-            trace!(
-                "TMB: {:#X}, TY: {:#X}, TX: {:#X}, IDX: {:#X}",
-                tile_map_base,
-                tile_y,
-                tile_x,
-                tile_map_base + tile_y * 32 + tile_x
-            );
-
-            let tile_number = memory_bus.read_u8(tile_map_base + tile_y * 32 + tile_x);
-
-            let tile_address = {
-                let base_address = if lcd_control.get_bit(4) {
-                    0x8000
-                } else {
-                    0x8800
-                };
-
-                let address_offset = if base_address == 0x8000 {
-                    tile_number as u16 * 16
-                } else {
-                    (tile_number as i8 as i16 + 128) as u16 * 16
-                };
-
-                (base_address + address_offset) as u16
+            let row_y = if attributes.y_flip {
+                7 - pixel_y
+            } else {
+                pixel_y
             };
+            let mut row = *memory_bus.tile_row(attributes.bank, tile_address + row_y * 2);
+            if attributes.x_flip {
+                row.reverse();
+            }
 
-            let tile_pixel = tile_address + (pixel_y * 2);
-            let lsb_byte = memory_bus.read_u8(tile_pixel);
-            let msb_byte = memory_bus.read_u8(tile_pixel + 1);
+            let skip = bg_x & 0x07;
+            let len = (8 - skip).min(pixels.len() - drawn);
+            for (pixel, &color_id) in pixels[drawn..drawn + len].iter_mut().zip(&row[skip..]) {
+                *pixel = TilePixel {
+                    color_id,
+                    palette: attributes.palette,
+                    priority: attributes.priority,
+                };
+            }
+            drawn += len;
+        }
+    }
 
-            let color_id = (msb_byte.get_bit(7 - pixel_x as usize) as u8) << 1
-                | lsb_byte.get_bit(7 - pixel_x as usize) as u8;
+    /// Fills in this line's objects. Where they overlap the one with priority is there, even
+    /// if the background then covers it and the other one wouldn't have been.
+    fn draw_objects(
+        &self,
+        memory_bus: &MemoryBus,
+        lcd_control: u8,
+        line: &mut [Option<ObjectPixel>],
+    ) {
+        let height = object_height(lcd_control);
+        let oam = memory_bus.oam();
+        let cgb_mode = memory_bus.cgb_mode();
+        let mut objects = self.line_objects(memory_bus, lcd_control);
+        if memory_bus.object_priority() == ObjectPriority::XCoordinate {
+            // Stable, so the earlier one in OAM wins a tie
+            objects.sort_by_key(|&i| oam[i * 4 + 1]);
+        }
 
-            let pallete = memory_bus.read_u8(PALLETE);
-            let remap = match color_id {
-                0 => pallete.get_bits(0..2),
-                1 => pallete.get_bits(2..4),
-                2 => pallete.get_bits(4..6),
-                3 => pallete.get_bits(6..8),
-                _ => unreachable!(),
+        for i in objects {
+            let [y, x, tile, attributes] = [0, 1, 2, 3].map(|byte| oam[i * 4 + byte]);
+            let mut row = self.line as u16 + 16 - y as u16;
+            if attributes.get_bit(6) {
+                row = height - 1 - row;
+            }
+            // The bottom half of a tall object is always the next tile
+            let tile = if height == 16 { tile & !1 } else { tile };
+            let bank = if cgb_mode {
+                attributes.get_bit(3) as usize
+            } else {
+                0
             };
-
-            let color = match remap {
-                0 => 255,
-                1 => 192,
-                2 => 95,
-                3 => 0,
-                _ => unreachable!(),
+            let mut pixels = *memory_bus.tile_row(bank, 0x8000 + tile as u16 * 16 + row * 2);
+            if attributes.get_bit(5) {
+                pixels.reverse();
+            }
+            let palette = if cgb_mode {
+                attributes.get_bits(0..3)
+            } else {
+                attributes.get_bit(4) as u8
             };
 
-            self.set_color(x, color, memory_bus, frame_buffer);
+            for (pixel_x, &color_id) in pixels.iter().enumerate() {
+                // X is 8 pixels to the right, so objects can go off the left edge
+                let Some(pixel) = (x as usize + pixel_x)
+                    .checked_sub(8)
+                    .and_then(|screen_x| line.get_mut(screen_x))
+                else {
+                    continue;
+                };
+                if color_id != 0 && pixel.is_none() {
+                    *pixel = Some(ObjectPixel {
+                        color_id,
+                        palette,
+                        behind_bg: attributes.get_bit(7),
+                    });
+                }
+            }
         }
     }
 }
+
+/// LCDC bit 2 makes objects 8x16
+fn object_height(lcd_control: u8) -> u16 {
+    if lcd_control.get_bit(2) {
+        16
+    } else {
+        8
+    }
+}
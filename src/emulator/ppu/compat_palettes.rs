@@ -0,0 +1,119 @@
+//! Colors the CGB gives DMG games
+//!
+//! A game without the CGB flag runs in compatibility mode, where BGP, OBP0 and OBP1 pick from
+//! background palette 0 and object palettes 0 and 1. The boot ROM fills those in first: with
+//! one of the combinations below if a direction (and maybe A or B) is held during the logo, or
+//! else the one it has for the game. It recognizes a list of Nintendo's own games by a hash of
+//! the title, which isn't reproduced here, so everything gets [`DEFAULT`] unless one is picked.
+use std::str::FromStr;
+
+/// Lightest color first, as `0xRRGGBB`
+pub type Colors = [u32; 4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompatPalette {
+    pub name: &'static str,
+    /// What's held during the boot logo to get it, like `up+a`
+    pub buttons: &'static str,
+    pub background: Colors,
+    pub objects: [Colors; 2],
+}
+
+const fn same(name: &'static str, buttons: &'static str, colors: Colors) -> CompatPalette {
+    CompatPalette {
+        name,
+        buttons,
+        background: colors,
+        objects: [colors, colors],
+    }
+}
+
+const BROWN: Colors = [0xFFFFFF, 0xFFAD63, 0x843100, 0x000000];
+const RED: Colors = [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000];
+const GREEN: Colors = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+const BLUE: Colors = [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000];
+
+impl CompatPalette {
+    /// The ones the boot ROM lets players pick, in the order of [`CompatPalette::buttons`]
+    pub const ALL: [CompatPalette; 12] = [
+        same("Brown", "up", BROWN),
+        CompatPalette {
+            name: "Red",
+            buttons: "up+a",
+            background: RED,
+            objects: [GREEN, BLUE],
+        },
+        same(
+            "Dark brown",
+            "up+b",
+            [0xFFE6C5, 0xCE9C84, 0x846B29, 0x5A3108],
+        ),
+        CompatPalette {
+            name: "Blue",
+            buttons: "left",
+            background: BLUE,
+            objects: [RED, GREEN],
+        },
+        CompatPalette {
+            name: "Dark blue",
+            buttons: "left+a",
+            background: [0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000],
+            objects: [RED, BROWN],
+        },
+        same(
+            "Grayscale",
+            "left+b",
+            [0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000],
+        ),
+        same("Pastel", "down", [0xFFFFA5, 0xFF9494, 0x9494FF, 0x000000]),
+        same("Orange", "down+a", [0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000]),
+        CompatPalette {
+            name: "Yellow",
+            buttons: "down+b",
+            background: [0xFFFFFF, 0xFFFF00, 0x7B4A00, 0x000000],
+            objects: [BLUE, GREEN],
+        },
+        same("Green", "right", [0xFFFFFF, 0x52FF00, 0xFF4200, 0x000000]),
+        CompatPalette {
+            name: "Dark green",
+            buttons: "right+a",
+            background: [0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000],
+            objects: [RED, RED],
+        },
+        same(
+            "Inverted",
+            "right+b",
+            [0x000000, 0x008484, 0xFFDE00, 0xFFFFFF],
+        ),
+    ];
+}
+
+/// What the boot ROM picks for games it doesn't know
+pub const DEFAULT: CompatPalette = CompatPalette::ALL[10];
+
+/// `up+a` style or the name, any case
+impl FromStr for CompatPalette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|palette| {
+                palette.buttons.eq_ignore_ascii_case(s) || palette.name.eq_ignore_ascii_case(s)
+            })
+            .ok_or_else(|| {
+                let buttons: Vec<_> = Self::ALL.iter().map(|palette| palette.buttons).collect();
+                format!(
+                    "Unknown CGB palette '{}', expected one of {}",
+                    s,
+                    buttons.join(", ")
+                )
+            })
+    }
+}
+
+/// `0xRRGGBB` as the CGB's RGB555, which keeps the top 5 bits of each channel
+pub fn rgb555(rgb: u32) -> u16 {
+    let channel = |shift: u32| ((rgb >> shift) & 0xFF) as u16 >> 3;
+    channel(16) | channel(8) << 5 | channel(0) << 10
+}
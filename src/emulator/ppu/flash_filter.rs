@@ -0,0 +1,56 @@
+//! Reduced flashing
+//!
+//! Plenty of games flash the whole screen white or black for hits, explosions and screen
+//! transitions, which can be a problem for photosensitive players. When the average brightness
+//! changes by more than [`MAX_STEP`] from one frame to the next, every pixel's change is scaled
+//! down so the average only moves by that much. Anything that stays changed still gets there
+//! a few frames later, a flash that's gone again the next frame never really shows.
+use crate::emulator::ppu::FrameBuffer;
+
+/// Most the average shade may change by in one frame, out of 255
+pub const MAX_STEP: u32 = 24;
+
+pub struct FlashFilter {
+    enabled: bool,
+    shown: Box<FrameBuffer>,
+    primed: bool,
+}
+
+impl Default for FlashFilter {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shown: Box::new([0; std::mem::size_of::<FrameBuffer>()]),
+            primed: false,
+        }
+    }
+}
+
+fn average(frame: &FrameBuffer) -> u32 {
+    frame.iter().map(|&shade| shade as u32).sum::<u32>() / frame.len() as u32
+}
+
+impl FlashFilter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Limits how far `frame` is from the last one returned and returns the result
+    pub fn apply(&mut self, frame: &FrameBuffer) -> &FrameBuffer {
+        let step = average(frame).abs_diff(average(&self.shown));
+        if !self.enabled || !self.primed || step <= MAX_STEP {
+            self.shown.copy_from_slice(frame);
+            self.primed = true;
+            return &self.shown;
+        }
+        for (shown, &new) in self.shown.iter_mut().zip(frame.iter()) {
+            let delta = new as i32 - *shown as i32;
+            *shown = (*shown as i32 + delta * MAX_STEP as i32 / step as i32) as u8;
+        }
+        &self.shown
+    }
+}
@@ -0,0 +1,58 @@
+//! LCD ghosting
+//!
+//! DMG pixels take a few frames to change, so anything that flickers every other frame looks
+//! half transparent, and some games rely on that. Each finished frame is mixed with what was
+//! shown before it, so a pixel drifts towards its new shade instead of jumping there.
+use crate::emulator::ppu::FrameBuffer;
+
+/// Fixed point, out of this
+const ONE: u32 = 256;
+
+pub struct FrameBlend {
+    /// How much of the previous output stays, out of [`ONE`]
+    persistence: u32,
+    shown: Box<FrameBuffer>,
+    /// Whether `shown` has a frame in it yet
+    primed: bool,
+}
+
+impl Default for FrameBlend {
+    fn default() -> Self {
+        Self {
+            persistence: 0,
+            shown: Box::new([0; std::mem::size_of::<FrameBuffer>()]),
+            primed: false,
+        }
+    }
+}
+
+impl FrameBlend {
+    /// 0 turns it off, closer to 1 smears more. Clamped to 0.95 so pixels still get there.
+    pub fn set_persistence(&mut self, persistence: f32) {
+        self.persistence = (persistence.clamp(0.0, 0.95) * ONE as f32).round() as u32;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.persistence > 0
+    }
+
+    /// Mixes `frame` into what was shown and returns the result
+    pub fn apply(&mut self, frame: &FrameBuffer) -> &FrameBuffer {
+        if !self.primed || !self.enabled() {
+            self.shown.copy_from_slice(frame);
+            self.primed = true;
+            return &self.shown;
+        }
+        for (shown, &new) in self.shown.iter_mut().zip(frame.iter()) {
+            let old = *shown as u32;
+            let mixed = (new as u32 * (ONE - self.persistence) + old * self.persistence) / ONE;
+            // Rounding down would stop one short of white forever
+            *shown = if mixed == old && new as u32 > old {
+                old as u8 + 1
+            } else {
+                mixed as u8
+            };
+        }
+        &self.shown
+    }
+}
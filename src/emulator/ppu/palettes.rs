@@ -0,0 +1,187 @@
+//! CGB palette RAM
+//!
+//! There are 8 background and 8 object palettes of 4 colors, each color two bytes of little
+//! endian RGB555. The CPU gets at them a byte at a time: BCPS/OCPS pick the byte and BCPD/OCPD
+//! read or write it, moving on to the next byte after a write if bit 7 of the index register
+//! is set. OPRI picks how overlapping objects are ordered. These registers are only mapped in
+//! [CGB mode](crate::emulator::memory_bus::MemoryBus::cgb_mode).
+use std::ops::RangeInclusive;
+
+use bit_field::BitField;
+use tracing::trace;
+
+use crate::emulator::{
+    memory_bus::{mmio::MmioDevice, Interrupt, BCPD, BCPS, OCPD, OCPS, OPRI},
+    ppu::compat_palettes::{self, Colors, CompatPalette},
+    state::{StateError, StateReader, StateWriter},
+};
+
+/// 8 palettes of 4 colors of 2 bytes
+const PALETTE_RAM: usize = 8 * 4 * 2;
+
+/// Which of two overlapping objects is drawn on top
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectPriority {
+    /// The one earlier in OAM, how CGB games are drawn
+    OamIndex,
+    /// The one further left, then the one earlier in OAM, like on a DMG
+    XCoordinate,
+}
+
+/// One set of palettes and the index register for it
+#[derive(Debug)]
+struct PaletteRam {
+    bytes: [u8; PALETTE_RAM],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl PaletteRam {
+    fn new(fill: u8) -> Self {
+        Self {
+            bytes: [fill; PALETTE_RAM],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+
+    /// Bit 6 isn't used
+    fn read_index(&self) -> u8 {
+        self.index | 0b0100_0000 | (self.auto_increment as u8) << 7
+    }
+
+    fn write_index(&mut self, byte: u8) {
+        self.index = byte.get_bits(0..6);
+        self.auto_increment = byte.get_bit(7);
+    }
+
+    fn read_data(&self) -> u8 {
+        self.bytes[self.index as usize]
+    }
+
+    fn write_data(&mut self, byte: u8) {
+        self.bytes[self.index as usize] = byte;
+        if self.auto_increment {
+            self.index = (self.index + 1) % PALETTE_RAM as u8;
+        }
+    }
+
+    fn set_colors(&mut self, palette: usize, colors: &Colors) {
+        for (i, &rgb) in colors.iter().enumerate() {
+            let offset = (palette * 4 + i) * 2;
+            self.bytes[offset..offset + 2]
+                .copy_from_slice(&compat_palettes::rgb555(rgb).to_le_bytes());
+        }
+    }
+
+    fn color(&self, palette: u8, color_id: u8) -> u16 {
+        let offset = (palette as usize % 8 * 4 + color_id as usize % 4) * 2;
+        u16::from_le_bytes([self.bytes[offset], self.bytes[offset + 1]])
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.bytes);
+        state.u8(self.index);
+        state.bool(self.auto_increment);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.fill(&mut self.bytes)?;
+        self.index = state.u8()? % PALETTE_RAM as u8;
+        self.auto_increment = state.bool()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Palettes {
+    background: PaletteRam,
+    objects: PaletteRam,
+    /// OPRI's bit, set means [`ObjectPriority::XCoordinate`]
+    x_priority: bool,
+}
+
+/// How the CGB boot ROM leaves them for a CGB game, background palettes all white. Object
+/// palettes aren't set and power on as garbage, 0 here.
+impl Default for Palettes {
+    fn default() -> Self {
+        Self {
+            background: PaletteRam::new(0xFF),
+            objects: PaletteRam::new(0x00),
+            x_priority: false,
+        }
+    }
+}
+
+impl Palettes {
+    /// RGB555 color `color_id` of background palette `palette`
+    pub fn background_color(&self, palette: u8, color_id: u8) -> u16 {
+        self.background.color(palette, color_id)
+    }
+
+    /// RGB555 color `color_id` of object palette `palette`
+    pub fn object_color(&self, palette: u8, color_id: u8) -> u16 {
+        self.objects.color(palette, color_id)
+    }
+
+    /// What the boot ROM does for a DMG game, the first background palette and the first two
+    /// object palettes get its colors
+    pub fn load_compat(&mut self, palette: &CompatPalette) {
+        self.background.set_colors(0, &palette.background);
+        for (i, colors) in palette.objects.iter().enumerate() {
+            self.objects.set_colors(i, colors);
+        }
+    }
+
+    /// What OPRI says, see [`MemoryBus::object_priority`] for outside CGB mode
+    ///
+    /// [`MemoryBus::object_priority`]: crate::emulator::memory_bus::MemoryBus::object_priority
+    pub fn object_priority(&self) -> ObjectPriority {
+        if self.x_priority {
+            ObjectPriority::XCoordinate
+        } else {
+            ObjectPriority::OamIndex
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.background.save_state(state);
+        self.objects.save_state(state);
+        state.bool(self.x_priority);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.background.load_state(state)?;
+        self.objects.load_state(state)?;
+        self.x_priority = state.bool()?;
+        Ok(())
+    }
+}
+
+impl MmioDevice for Palettes {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[BCPS..=OPRI]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            BCPS => self.background.read_index(),
+            BCPD => self.background.read_data(),
+            OCPS => self.objects.read_index(),
+            OCPD => self.objects.read_data(),
+            _ => self.x_priority as u8 | 0b1111_1110,
+        }
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        trace!(target: "ppu", "Palette register write @{:#X}: {:#X}", addr, byte);
+        match addr {
+            BCPS => self.background.write_index(byte),
+            BCPD => self.background.write_data(byte),
+            OCPS => self.objects.write_index(byte),
+            OCPD => self.objects.write_data(byte),
+            _ => self.x_priority = byte.get_bit(0),
+        }
+        None
+    }
+}
@@ -0,0 +1,400 @@
+//! Static analysis of ROM images
+//!
+//! Decoding a ROM front to back mistakes data for code, so [`CodeMap::analyze`] follows
+//! control flow instead. Starting from the entry point and the RST/interrupt vectors it decodes
+//! instructions, following jumps, calls and fall-through, and marks every byte it reaches as
+//! an opcode or an operand. Everything it doesn't reach is assumed to be data.
+//!
+//! The MBC state isn't known statically, so jumps into 0x4000-0x7FFF from bank 0 are a guess:
+//! if the code right before wrote an immediate to the bank register at 0x2000-0x3FFF it's that
+//! bank, otherwise bank 1. Code in a switchable bank is assumed to stay in its own bank. Jumps
+//! into RAM aren't followed, that code only exists once the game copies it there.
+use std::{collections::BTreeSet, fmt};
+
+use bit_field::BitField;
+
+use crate::emulator::instructions::{Instruction, Register8};
+
+pub const BANK_SIZE: usize = 0x4000;
+/// Anything shorter isn't a ROM, it doesn't even have a whole header. Anything longer runs,
+/// see [`pad`].
+pub const MIN_SIZE: usize = 0x150;
+
+/// What the boot ROM checks for at 0x104, every cartridge has it
+pub const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// Where the interrupt handlers are, RST vectors are every 8 bytes below these
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+const ENTRY_POINT: u16 = 0x100;
+
+/// Title from the cartridge header, as much of it as there is
+pub fn header_title(rom: &[u8]) -> String {
+    rom.get(0x0134..)
+        .unwrap_or_default()
+        .iter()
+        .take(0x10)
+        .take_while(|&&byte| byte != 0)
+        .map(|&byte| byte as char)
+        .collect()
+}
+
+/// Whether the header's CGB flag says the game can use the Game Boy Color's features, whether
+/// or not it also runs on a DMG
+pub fn supports_cgb(rom: &[u8]) -> bool {
+    rom.get(0x0143).is_some_and(|flag| flag.get_bit(7))
+}
+
+/// Cartridge RAM from the header's size byte at 0x149, 0 if there isn't any
+pub fn ram_size(rom: &[u8]) -> usize {
+    match rom.get(0x149) {
+        Some(0x01) => 0x800,
+        Some(0x02) => 0x2000,
+        Some(0x03) => 0x8000,
+        Some(0x04) => 0x20000,
+        Some(0x05) => 0x10000,
+        _ => 0,
+    }
+}
+
+/// Whether it's an MBC1M multicart, which wires the MBC1's upper bank bits one lower so each
+/// of its 1MB's four 256K games has a header of its own. There's nothing in the menu's header
+/// to say so, but the second game's logo at bank 0x10 gives it away. [`Mbc::for_rom`] wires
+/// these up as one.
+///
+/// [`Mbc::for_rom`]: crate::emulator::mbc::Mbc::for_rom
+pub fn is_mbc1_multicart(rom: &[u8]) -> bool {
+    let logo = |bank: usize| {
+        rom.get(bank * BANK_SIZE + 0x104..)
+            .and_then(|rest| rest.get(..0x30))
+    };
+    rom.len() == 0x10_0000
+        && matches!(rom[0x147], 0x01..=0x03)
+        && logo(0x10) == Some(&NINTENDO_LOGO[..])
+}
+
+/// Something wrong with a ROM's header, usually a bad dump or a hacked ROM rather than anything
+/// the emulator did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RomProblem {
+    /// The boot ROM locks up on this
+    Logo,
+    /// The boot ROM locks up on this too
+    HeaderChecksum { stored: u8, computed: u8 },
+    /// Shorter than the header says, see [`pad`]
+    Truncated { size: usize, declared: usize },
+    /// Nothing on the Game Boy checks this one, but a dump with a bad byte anywhere fails it
+    GlobalChecksum { stored: u16, computed: u16 },
+}
+
+impl fmt::Display for RomProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomProblem::Logo => write!(
+                f,
+                "The Nintendo logo in the header is wrong, a Game Boy wouldn't boot this ROM"
+            ),
+            RomProblem::HeaderChecksum { stored, computed } => write!(
+                f,
+                "The header checksum is {:#04X} but the header adds up to {:#04X}, \
+                 a Game Boy wouldn't boot this ROM",
+                stored, computed
+            ),
+            RomProblem::Truncated { size, declared } => write!(
+                f,
+                "The ROM is {} bytes but its header says {}, the rest reads 0xFF",
+                size, declared
+            ),
+            RomProblem::GlobalChecksum { stored, computed } => write!(
+                f,
+                "The global checksum is {:#06X} but the ROM adds up to {:#06X}, \
+                 it may be a bad dump",
+                stored, computed
+            ),
+        }
+    }
+}
+
+/// How big the header at 0x148 says the ROM is, `None` if it doesn't say
+pub fn declared_size(rom: &[u8]) -> Option<usize> {
+    match *rom.get(0x148)? {
+        code @ 0x00..=0x08 => Some(0x8000 << code),
+        // Only a few unlicensed games use these
+        0x52 => Some(72 * BANK_SIZE),
+        0x53 => Some(80 * BANK_SIZE),
+        0x54 => Some(96 * BANK_SIZE),
+        _ => None,
+    }
+}
+
+/// `rom` filled out with 0xFF to the size its header gives, and to at least 32K so there's
+/// something at every address in 0x0000-0x7FFF. That's what a cartridge reads where it has no
+/// ROM, so a truncated dump runs until it gets to the missing part.
+pub fn pad(mut rom: Vec<u8>) -> Vec<u8> {
+    let size = declared_size(&rom).unwrap_or(0).max(2 * BANK_SIZE);
+    if rom.len() < size {
+        rom.resize(size, 0xFF);
+    }
+    rom
+}
+
+/// What the header checksum at 0x14D should be, `None` if the ROM is too short to have a header
+pub fn header_checksum(rom: &[u8]) -> Option<u8> {
+    let header = rom.get(0x134..0x14D)?;
+    Some(
+        header
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1)),
+    )
+}
+
+/// What the global checksum at 0x14E should be, every byte but its own two added up
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|&(offset, _)| offset != 0x14E && offset != 0x14F)
+        .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16))
+}
+
+/// Everything that doesn't add up in the header, nothing for a good dump
+pub fn check_integrity(rom: &[u8]) -> Vec<RomProblem> {
+    let mut problems = Vec::new();
+    if rom.get(0x104..0x134) != Some(&NINTENDO_LOGO[..]) {
+        problems.push(RomProblem::Logo);
+    }
+    let Some(computed) = header_checksum(rom) else {
+        return problems;
+    };
+    let stored = rom[0x14D];
+    if stored != computed {
+        problems.push(RomProblem::HeaderChecksum { stored, computed });
+    }
+    if let Some(declared) = declared_size(rom).filter(|&declared| rom.len() < declared) {
+        problems.push(RomProblem::Truncated {
+            size: rom.len(),
+            declared,
+        });
+    }
+    if let Some(&[high, low]) = rom.get(0x14E..0x150) {
+        let stored = u16::from_be_bytes([high, low]);
+        let computed = global_checksum(rom);
+        if stored != computed {
+            problems.push(RomProblem::GlobalChecksum { stored, computed });
+        }
+    }
+    problems
+}
+
+/// An address in a particular ROM bank
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub bank: u16,
+    pub addr: u16,
+}
+
+impl Location {
+    /// Where the byte at `offset` in the ROM file shows up
+    pub fn from_offset(offset: usize) -> Self {
+        match offset / BANK_SIZE {
+            0 => Self {
+                bank: 0,
+                addr: offset as u16,
+            },
+            bank => Self {
+                bank: bank as u16,
+                addr: (BANK_SIZE + offset % BANK_SIZE) as u16,
+            },
+        }
+    }
+
+    /// What the CPU sees at `addr` with `switched` in the 0x4000-0x7FFF window,
+    /// `None` outside of ROM
+    pub fn mapped(addr: u16, switched: u16) -> Option<Self> {
+        match addr {
+            0x0000..=0x3FFF => Some(Self { bank: 0, addr }),
+            0x4000..=0x7FFF => Some(Self {
+                bank: switched,
+                addr,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Offset in the ROM file, `None` if the address isn't somewhere the bank is mapped
+    pub fn offset(self) -> Option<usize> {
+        match (self.bank, self.addr) {
+            (0, 0x0000..=0x3FFF) => Some(self.addr as usize),
+            (1.., 0x4000..=0x7FFF) => {
+                Some(self.bank as usize * BANK_SIZE + self.addr as usize - BANK_SIZE)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Like RGBDS shows them, `01:4000`
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.addr)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteKind {
+    Data,
+    /// First byte of an instruction
+    Opcode,
+    /// Immediate or CB-prefixed opcode
+    Operand,
+}
+
+/// Which bytes of a ROM are code
+#[derive(Debug)]
+pub struct CodeMap {
+    kinds: Vec<ByteKind>,
+    /// Entry points and everything jumped to or called
+    labels: BTreeSet<Location>,
+}
+
+impl CodeMap {
+    pub fn analyze(rom: &[u8]) -> Self {
+        let mut map = Self {
+            kinds: vec![ByteKind::Data; rom.len()],
+            labels: BTreeSet::new(),
+        };
+        let vectors = (0..8).map(|rst| rst * 8).chain(INTERRUPT_VECTORS);
+        let mut pending: Vec<(Location, u16)> = vectors
+            .chain([ENTRY_POINT])
+            .map(|addr| (Location { bank: 0, addr }, 1))
+            .collect();
+        map.labels
+            .extend(pending.iter().map(|&(location, _)| location));
+
+        while let Some((start, switched)) = pending.pop() {
+            map.follow(rom, start, switched, &mut pending);
+        }
+        map
+    }
+
+    /// Decodes straight-line code from `start` until it can't continue, queueing whatever it
+    /// jumps to. `switched` is the bank assumed to be in 0x4000-0x7FFF for bank 0 code.
+    fn follow(
+        &mut self,
+        rom: &[u8],
+        start: Location,
+        mut switched: u16,
+        pending: &mut Vec<(Location, u16)>,
+    ) {
+        let mut location = start;
+        let mut immediate_a = None;
+        loop {
+            let Some(offset) = location.offset().filter(|&offset| offset < rom.len()) else {
+                return;
+            };
+            // Already been here, or it's in the middle of another instruction
+            if self.kinds[offset] != ByteKind::Data {
+                return;
+            }
+            let Some(instruction) = decode(rom, offset) else {
+                return;
+            };
+            let len = instruction.byte_len();
+            let end = location.addr as usize + len as usize;
+            let region_end = if location.bank == 0 { 0x4000 } else { 0x8000 };
+            if end > region_end || offset + len as usize > rom.len() {
+                return;
+            }
+            self.kinds[offset] = ByteKind::Opcode;
+            for kind in &mut self.kinds[offset + 1..offset + len as usize] {
+                if *kind == ByteKind::Data {
+                    *kind = ByteKind::Operand;
+                }
+            }
+
+            let bank = if location.bank == 0 {
+                switched
+            } else {
+                location.bank
+            };
+            let next = location.addr.wrapping_add(len);
+            if let Some(target) = instruction
+                .branch_target(location.addr)
+                .and_then(|addr| Location::mapped(addr, bank))
+            {
+                self.labels.insert(target);
+                pending.push((target, switched));
+            }
+            if !instruction.falls_through() {
+                return;
+            }
+
+            // `ld a, BANK(x)` then `ld [$2000], a`
+            if let (Instruction::LoadIndirectImmediateA(0x2000..=0x3FFF), Some(value)) =
+                (instruction, immediate_a)
+            {
+                if location.bank == 0 {
+                    switched = (value as u16).max(1);
+                }
+            }
+            immediate_a = match instruction {
+                Instruction::LoadImmediate(Register8::A, value) => Some(value),
+                _ => None,
+            };
+
+            match Location::mapped(next, bank) {
+                Some(next) => location = next,
+                None => return,
+            }
+        }
+    }
+
+    /// [`ByteKind::Data`] past the end of the ROM
+    pub fn kind(&self, offset: usize) -> ByteKind {
+        self.kinds.get(offset).copied().unwrap_or(ByteKind::Data)
+    }
+
+    /// Whether an instruction starts at `location`
+    pub fn is_code(&self, location: Location) -> bool {
+        location.offset().map(|offset| self.kind(offset)) == Some(ByteKind::Opcode)
+    }
+
+    /// Bytes that are part of an instruction
+    pub fn code_bytes(&self) -> usize {
+        self.kinds
+            .iter()
+            .filter(|&&kind| kind != ByteKind::Data)
+            .count()
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = Location> + '_ {
+        self.labels.iter().copied()
+    }
+
+    /// Every instruction that was found, in ROM order
+    pub fn instructions<'a>(
+        &'a self,
+        rom: &'a [u8],
+    ) -> impl Iterator<Item = (Location, Instruction)> + 'a {
+        self.kinds
+            .iter()
+            .enumerate()
+            .filter(|&(_, &kind)| kind == ByteKind::Opcode)
+            .filter_map(move |(offset, _)| {
+                Some((Location::from_offset(offset), decode(rom, offset)?))
+            })
+    }
+}
+
+/// The instruction at `offset`, padded with zeros if it runs off the end
+fn decode(rom: &[u8], offset: usize) -> Option<Instruction> {
+    let mut bytes = [0; 4];
+    for (byte, &value) in bytes.iter_mut().zip(&rom[offset..]) {
+        *byte = value;
+    }
+    Instruction::parse(&bytes)
+        .ok()
+        .map(|(_, instruction)| instruction)
+}
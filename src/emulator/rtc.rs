@@ -0,0 +1,152 @@
+//! MBC3 real time clock state in `.sav` files
+//!
+//! The clock has to survive being saved, and other emulators agree on how. BGB and VBA append a footer to the cartridge RAM: the five clock registers, then the five
+//! latched ones, each as a little endian u32, then the Unix time the save was made. VBA wrote
+//! that as 32 bits (44 bytes total), BGB as 64 (48 bytes). Both are read, 48 is written.
+//!
+//! Cartridge RAM is always a multiple of 2 KiB, so whatever is left over is the footer.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The day counter's top bit, halt and day carry are all in DH
+pub const DH_DAY_HIGH: u8 = 0x01;
+pub const DH_HALT: u8 = 0x40;
+pub const DH_CARRY: u8 = 0x80;
+
+const SHORT_FOOTER: usize = 44;
+const LONG_FOOTER: usize = 48;
+/// Every cartridge RAM size is a multiple of this
+pub const RAM_GRANULE: usize = 0x800;
+
+/// RTC S, M, H, DL and DH
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub day_low: u8,
+    pub day_high: u8,
+}
+
+impl RtcRegisters {
+    /// 0-511
+    pub fn days(&self) -> u16 {
+        (((self.day_high & DH_DAY_HIGH) as u16) << 8) | self.day_low as u16
+    }
+
+    pub fn halted(&self) -> bool {
+        self.day_high & DH_HALT != 0
+    }
+
+    /// Runs the clock forward, setting the carry if the day counter wraps. A halted clock
+    /// doesn't move.
+    pub fn advance(&mut self, seconds: u64) {
+        if self.halted() || seconds == 0 {
+            return;
+        }
+        // Games can write values out of range, real hardware counts those up to the wraparound
+        // first but clamping is close enough for time spent switched off
+        let total = seconds
+            + self.seconds.min(59) as u64
+            + self.minutes.min(59) as u64 * 60
+            + self.hours.min(23) as u64 * 3600;
+        let days = self.days() as u64 + total / 86400;
+        let time = total % 86400;
+        self.seconds = (time % 60) as u8;
+        self.minutes = (time / 60 % 60) as u8;
+        self.hours = (time / 3600) as u8;
+        self.day_low = days as u8;
+        self.day_high = (self.day_high & !DH_DAY_HIGH) | ((days >> 8) & 1) as u8;
+        if days > 511 {
+            self.day_high |= DH_CARRY;
+        }
+    }
+
+    fn to_array(self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+    }
+
+    fn from_words(words: &[u8]) -> Self {
+        let byte = |i: usize| words[i * 4];
+        Self {
+            seconds: byte(0),
+            minutes: byte(1),
+            hours: byte(2),
+            day_low: byte(3),
+            day_high: byte(4),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RtcFooter {
+    pub registers: RtcRegisters,
+    /// What the game last latched
+    pub latched: RtcRegisters,
+    /// Unix time in seconds when it was saved
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    /// The footer in BGB's 48 byte form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(LONG_FOOTER);
+        for value in self
+            .registers
+            .to_array()
+            .into_iter()
+            .chain(self.latched.to_array())
+        {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    /// Catches the clock up to `now`, for loading a save made at [`RtcFooter::timestamp`].
+    /// Time going backwards leaves it as it is.
+    pub fn registers_at(&self, now: u64) -> RtcRegisters {
+        let mut registers = self.registers;
+        registers.advance(now.saturating_sub(self.timestamp));
+        registers
+    }
+}
+
+/// Seconds since the Unix epoch, what [`RtcFooter::timestamp`] counts
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Splits a `.sav` into cartridge RAM and the RTC footer, if it has one
+pub fn split_sav(sav: &[u8]) -> (&[u8], Option<RtcFooter>) {
+    let footer_len = match sav.len() % RAM_GRANULE {
+        SHORT_FOOTER => SHORT_FOOTER,
+        LONG_FOOTER => LONG_FOOTER,
+        _ => return (sav, None),
+    };
+    let (ram, footer) = sav.split_at(sav.len() - footer_len);
+    let timestamp = match footer_len {
+        SHORT_FOOTER => u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64,
+        _ => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+    };
+    let footer = RtcFooter {
+        registers: RtcRegisters::from_words(&footer[..20]),
+        latched: RtcRegisters::from_words(&footer[20..40]),
+        timestamp,
+    };
+    (ram, Some(footer))
+}
+
+/// Cartridge RAM followed by the footer
+pub fn join_sav(ram: &[u8], footer: &RtcFooter) -> Vec<u8> {
+    let mut sav = ram.to_vec();
+    sav.extend_from_slice(&footer.to_bytes());
+    sav
+}
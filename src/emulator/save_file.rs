@@ -0,0 +1,114 @@
+//! Writing files that can't be half written
+//!
+//! Everything is written to a `.tmp` next to the file, synced, then renamed over it, so a crash
+//! or power cut leaves either the old file or the new one. The file being replaced is kept as
+//! `.bak1`, pushing older backups along to `.bak2` and so on, in case the new contents were bad
+//! in the first place. Battery saves go through here too, see
+//! [`battery_file`](crate::emulator::mbc::battery_file).
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where a per-game file goes in `dir`, named after the game's title and global checksum so
+/// different versions of a game don't share it
+pub fn game_file(dir: &Path, title: &str, checksum: u16, extension: &str) -> PathBuf {
+    let title: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dir.join(format!("{}-{:04X}.{}", title, checksum, extension))
+}
+
+/// How many old versions are kept
+pub const BACKUPS: usize = 2;
+
+/// `path` with `suffix` added to the whole file name, `game.sav` to `game.sav.tmp`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Where the `n`th most recent backup of `path` is, starting at 1
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".bak{}", n))
+}
+
+/// Replaces `path` with `bytes`, creating the directory it's in if needed
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = with_suffix(path, ".tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        rotate_backups(path)?;
+    }
+    fs::rename(&temp, path)?;
+    sync_dir(path);
+    Ok(())
+}
+
+/// Moves each backup one along, dropping the oldest, and makes `path` the newest. `path` stays
+/// where it is until the rename replaces it.
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    for n in (1..BACKUPS).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    let newest = backup_path(path, 1);
+    // A hard link is free and keeps `path` in place, not every filesystem has them though
+    if fs::hard_link(path, &newest).is_err() {
+        fs::copy(path, &newest)?;
+    }
+    Ok(())
+}
+
+/// Makes the rename itself durable. Only possible on Unix, and the file is already safe without
+/// it, so failing is fine.
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let dir = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Reads `path`, or the newest backup `valid` accepts if it's missing or `valid` rejects it.
+/// `None` if there's nothing usable at all.
+pub fn read_with_backups(
+    path: &Path,
+    valid: impl Fn(&[u8]) -> bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let candidates =
+        std::iter::once(path.to_path_buf()).chain((1..=BACKUPS).map(|n| backup_path(path, n)));
+    for candidate in candidates {
+        match fs::read(&candidate) {
+            Ok(bytes) if valid(&bytes) => return Ok(Some(bytes)),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,319 @@
+//! Serial port (link cable)
+//!
+//! Transfers are a byte each way, clocked by whichever side has SC bit 0 set. The clocking
+//! side swaps bytes with the other end through a [`SerialLink`] once the 8 bits have been
+//! shifted out, the other side picks the exchange up whenever it next polls its link. A
+//! transfer on the external clock waits for as long as it takes the other side to start one,
+//! with nothing plugged in that's forever.
+//!
+//! With nothing on the other end the input line is pulled high, so a transfer on the internal
+//! clock still finishes on time and shifts in 0xFF. That's how games notice nobody's there.
+//!
+//! Every byte the Game Boy sends is kept for [`Serial::take_sent`] as well, since test ROMs
+//! and homebrew print their debug output that way. [`console::Lines`] turns it into text.
+//!
+//! In CGB mode SC bit 1 picks the fast internal clock, 262144Hz instead of 8192Hz. Double
+//! speed mode isn't emulated, so neither is it doubling the clock again.
+use std::{
+    fmt::Debug,
+    ops::RangeInclusive,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    time::Duration,
+};
+
+use bit_field::BitField;
+use tracing::{trace, warn};
+
+use crate::emulator::{
+    memory_bus::{mmio::MmioDevice, Interrupt},
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub mod console;
+pub mod partner;
+pub mod printer;
+pub mod tcp;
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+/// 8 bits at 8192Hz
+pub const CYCLES_PER_TRANSFER: u32 = 8 * 512;
+/// 8 bits at 262144Hz, with SC bit 1 set in CGB mode
+pub const CYCLES_PER_FAST_TRANSFER: u32 = 8 * 16;
+/// Most bytes [`Serial::take_sent`] holds on to, the oldest go first when nothing takes them
+pub const SENT_LIMIT: usize = 4096;
+
+/// The other end of the link cable
+pub trait SerialLink: Send + Debug {
+    /// Clocks `out` over to the other side and returns what came back, None if nothing did
+    fn exchange(&mut self, out: u8) -> Option<u8>;
+
+    /// Answers a transfer clocked by the other side with `reply`, returning the byte it sent
+    fn poll(&mut self, reply: u8) -> Option<u8>;
+
+    /// `cycles` T-cycles went by since the last call, for partners that clock transfers on
+    /// their own schedule
+    fn tick(&mut self, _cycles: u32) {}
+}
+
+#[derive(Debug, Default)]
+pub struct Serial {
+    /// SB
+    data: u8,
+    /// SC, bits 0 and 7, and 1 in CGB mode
+    control: u8,
+    /// See [`Serial::set_cgb_mode`]
+    cgb_mode: bool,
+    /// Cycles until an internal clock transfer is done shifting
+    remaining: Option<u32>,
+    link: Option<Box<dyn SerialLink>>,
+    /// See [`Serial::take_sent`]
+    sent: Vec<u8>,
+}
+
+impl Serial {
+    pub fn connect(&mut self, link: Box<dyn SerialLink>) {
+        self.link = Some(link);
+    }
+
+    /// Whether anything's plugged in
+    pub fn connected(&self) -> bool {
+        self.link.is_some()
+    }
+
+    /// Back to how it powers on, still plugged into the same link
+    pub fn reset(&mut self) {
+        *self = Self {
+            link: self.link.take(),
+            ..Self::default()
+        };
+    }
+
+    /// Whether SC bit 1 is there, which only it is in
+    /// [CGB mode](crate::emulator::memory_bus::MemoryBus::cgb_mode)
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.control &= self.control_mask();
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            SB => self.data,
+            SC => self.control | !self.control_mask(),
+            _ => unreachable!("Serial read @{:#X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        match addr {
+            SB => self.data = byte,
+            SC => {
+                trace!(target: "serial", "SC write: {:#X}", byte);
+                self.control = byte & self.control_mask();
+                if self.transfer_requested() && self.internal_clock() {
+                    self.remaining = Some(self.cycles_per_transfer());
+                    self.record_sent(self.data);
+                } else {
+                    self.remaining = None;
+                }
+            }
+            _ => unreachable!("Serial write @{:#X}", addr),
+        }
+    }
+
+    /// T-cycles [`Serial::tick`] can be put off for. A link has to be polled all the time,
+    /// without one only transfers on the internal clock do anything.
+    pub fn cycles_until_interrupt(&self) -> u32 {
+        match self.link {
+            Some(_) => 4,
+            None => self.remaining.unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Returns true when a transfer finished and the serial interrupt should be requested
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        if let Some(link) = self.link.as_mut() {
+            link.tick(cycles);
+        }
+
+        if let Some(remaining) = self.remaining {
+            if remaining > cycles {
+                self.remaining = Some(remaining - cycles);
+                return false;
+            }
+            self.remaining = None;
+            let byte = match self.link.as_mut().map(|link| link.exchange(self.data)) {
+                Some(Some(byte)) => byte,
+                Some(None) => {
+                    warn!(target: "serial", "Link partner didn't answer the transfer");
+                    0xFF
+                }
+                None => 0xFF,
+            };
+            trace!(target: "serial", "Serial sent {:#X}, received {:#X}", self.data, byte);
+            self.data = byte;
+            self.control.set_bit(7, false);
+            return true;
+        }
+
+        let link = match self.link.as_mut() {
+            Some(link) => link,
+            // Without a partner nothing ever clocks an external transfer
+            None => return false,
+        };
+
+        // Nothing is shifted out unless a transfer is waiting, the line just idles high
+        let waiting = self.control.get_bit(7) && !self.control.get_bit(0);
+        let reply = if waiting { self.data } else { 0xFF };
+        match link.poll(reply) {
+            Some(byte) if waiting => {
+                trace!(target: "serial", "Serial received {:#X}, sent {:#X}", byte, self.data);
+                self.record_sent(self.data);
+                self.data = byte;
+                self.control.set_bit(7, false);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.data);
+        state.u8(self.control);
+        state.bool(self.remaining.is_some());
+        state.u32(self.remaining.unwrap_or(0));
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.data = state.u8()?;
+        self.control = state.u8()? & self.control_mask();
+        let transferring = state.bool()?;
+        let remaining = state.u32()?;
+        self.remaining = transferring.then_some(remaining);
+        Ok(())
+    }
+
+    fn control_mask(&self) -> u8 {
+        if self.cgb_mode {
+            0b1000_0011
+        } else {
+            0b1000_0001
+        }
+    }
+
+    /// How long the internal clock takes to shift a byte out at the speed SC asks for
+    pub fn cycles_per_transfer(&self) -> u32 {
+        if self.control.get_bit(1) {
+            CYCLES_PER_FAST_TRANSFER
+        } else {
+            CYCLES_PER_TRANSFER
+        }
+    }
+
+    fn transfer_requested(&self) -> bool {
+        self.control.get_bit(7)
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.control.get_bit(0)
+    }
+
+    /// What the Game Boy has sent since the last call, oldest first. Internal clock transfers
+    /// count when they start, external clock ones once the other side clocks them.
+    pub fn take_sent(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.sent)
+    }
+
+    fn record_sent(&mut self, byte: u8) {
+        if self.sent.len() == SENT_LIMIT {
+            self.sent.remove(0);
+        }
+        self.sent.push(byte);
+    }
+}
+
+impl MmioDevice for Serial {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[SB..=SC]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        Serial::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        Serial::write(self, addr, byte);
+        None
+    }
+
+    fn tick(&mut self, cycles: u32) -> Option<Interrupt> {
+        Serial::tick(self, cycles).then_some(Interrupt::Serial)
+    }
+
+    fn cycles_until_interrupt(&self) -> u32 {
+        Serial::cycles_until_interrupt(self)
+    }
+}
+
+#[derive(Debug)]
+enum LinkMessage {
+    /// A transfer clocked by the sender
+    Clock(u8),
+    /// The answer to a [`LinkMessage::Clock`]
+    Reply(u8),
+}
+
+/// How long to wait for the other side before giving up on a transfer
+const CHANNEL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// One end of a link cable between two emulators in the same process
+#[derive(Debug)]
+pub struct ChannelLink {
+    sender: Sender<LinkMessage>,
+    receiver: Receiver<LinkMessage>,
+}
+
+/// Both ends of an in-process link cable
+pub fn link_pair() -> (ChannelLink, ChannelLink) {
+    let (a_sender, b_receiver) = mpsc::channel();
+    let (b_sender, a_receiver) = mpsc::channel();
+    (
+        ChannelLink {
+            sender: a_sender,
+            receiver: a_receiver,
+        },
+        ChannelLink {
+            sender: b_sender,
+            receiver: b_receiver,
+        },
+    )
+}
+
+impl SerialLink for ChannelLink {
+    fn exchange(&mut self, out: u8) -> Option<u8> {
+        self.sender.send(LinkMessage::Clock(out)).ok()?;
+        loop {
+            match self.receiver.recv_timeout(CHANNEL_TIMEOUT) {
+                Ok(LinkMessage::Reply(byte)) => return Some(byte),
+                // Both sides are clocking, answer theirs while waiting for ours
+                Ok(LinkMessage::Clock(_)) => self.sender.send(LinkMessage::Reply(out)).ok()?,
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    fn poll(&mut self, reply: u8) -> Option<u8> {
+        match self.receiver.try_recv() {
+            Ok(LinkMessage::Clock(byte)) => {
+                self.sender.send(LinkMessage::Reply(reply)).ok()?;
+                Some(byte)
+            }
+            Ok(LinkMessage::Reply(byte)) => {
+                warn!(target: "serial", "Dropping late link reply {:#X}", byte);
+                None
+            }
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Debug output from test ROMs and homebrew, which write it a byte at a time through SB
+//!
+//! Each byte is a character (Latin-1, so really ASCII) and lines end with `\n`, as blargg's
+//! test ROMs do it.
+
+/// Splits up bytes from [`Serial::take_sent`](super::Serial::take_sent) into lines
+#[derive(Debug, Default)]
+pub struct Lines {
+    /// What's come since the last line break
+    partial: String,
+}
+
+impl Lines {
+    /// Adds `bytes`, returning the lines they finish without their line breaks
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for &byte in bytes {
+            match byte {
+                b'\n' => lines.push(std::mem::take(&mut self.partial)),
+                b'\r' => {}
+                _ => self.partial.push(byte as char),
+            }
+        }
+        lines
+    }
+
+    /// The line still being written
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+
+    /// Whatever's left without a line break at the end, `None` if nothing is
+    pub fn finish(&mut self) -> Option<String> {
+        Some(std::mem::take(&mut self.partial)).filter(|line| !line.is_empty())
+    }
+}
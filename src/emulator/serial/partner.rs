@@ -0,0 +1,54 @@
+//! A link partner that clocks transfers itself
+//!
+//! Plenty of link cable games have one side run on the internal clock and the other wait on
+//! the external clock, and work out which is which when they connect. [`Partner`] stands in for
+//! the side with the clock. It sends the same byte every time, as often as its clock speed
+//! allows, whether the game is waiting for a transfer or not: one it isn't ready for goes into
+//! the idle line and is lost, like it would be with a real cable. Transfers the game clocks are
+//! answered with the same byte.
+use tracing::trace;
+
+use super::{tcp::SERIAL_CLOCK_HZ, SerialLink, CYCLES_PER_TRANSFER};
+
+#[derive(Debug)]
+pub struct Partner {
+    byte: u8,
+    /// T-cycles between the transfers it clocks
+    period: u32,
+    /// T-cycles since the last one
+    elapsed: u32,
+}
+
+impl Partner {
+    /// Sends `byte` with its clock at `hz` bits a second, a Game Boy's is
+    /// [`SERIAL_CLOCK_HZ`] or 32 times that with the fast clock
+    pub fn new(byte: u8, hz: u32) -> Self {
+        let period = CYCLES_PER_TRANSFER as u64 * SERIAL_CLOCK_HZ as u64 / hz.max(1) as u64;
+        Self {
+            byte,
+            period: period.clamp(1, u32::MAX as u64) as u32,
+            elapsed: 0,
+        }
+    }
+}
+
+impl SerialLink for Partner {
+    fn exchange(&mut self, out: u8) -> Option<u8> {
+        trace!(target: "serial", "Partner received {:#X}", out);
+        Some(self.byte)
+    }
+
+    fn poll(&mut self, reply: u8) -> Option<u8> {
+        if self.elapsed < self.period {
+            return None;
+        }
+        self.elapsed -= self.period;
+        trace!(target: "serial", "Partner clocked {:#X} in, got {:#X}", self.byte, reply);
+        Some(self.byte)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        // A partner that's kept waiting doesn't make up for it by clocking several at once
+        self.elapsed = (self.elapsed + cycles).min(self.period);
+    }
+}
@@ -0,0 +1,313 @@
+//! Game Boy Printer
+//!
+//! Games talk to the printer in packets, always clocking the transfer themselves:
+//!
+//! | Bytes | Contents                                      | Printer answers   |
+//! |-------|-----------------------------------------------|-------------------|
+//! | 2     | Magic, `88 33`                                | `00`              |
+//! | 1     | Command, see below                            | `00`              |
+//! | 1     | 1 if the data is compressed                   | `00`              |
+//! | 2     | Data length, little endian                    | `00`              |
+//! | ...   | Data                                          | `00`              |
+//! | 2     | Sum of command to data, little endian         | `00`              |
+//! | 2     | `00 00`                                       | `81`, then status |
+//!
+//! Commands are 1 (initialize), 2 (print), 4 (image data), 8 (cancel) and F (status).
+//! Image data is tiles in the usual 2bpp format, 20 to a row. Printed images are kept as one
+//! long strip of paper until a print asks for a margin after it, then saved as a PNG.
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+};
+
+use bit_field::BitField;
+use tracing::{debug, info, warn};
+
+use super::SerialLink;
+use crate::emulator::png;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+/// Answered to the first byte after the checksum
+pub const DEVICE_ID: u8 = 0x81;
+const WIDTH: usize = 160;
+const TILES_PER_ROW: usize = WIDTH / 8;
+/// A row of tiles, 8 pixels high
+const TILE_ROW_BYTES: usize = TILES_PER_ROW * 16;
+/// Image memory fits 9 data packets of 2 tile rows each
+const BUFFER_SIZE: usize = 9 * 2 * TILE_ROW_BYTES;
+/// How many status requests report the printer as busy after a print
+const BUSY_POLLS: u8 = 4;
+
+pub mod status {
+    pub const CHECKSUM_ERROR: u8 = 1 << 0;
+    pub const PRINTING: u8 = 1 << 1;
+    pub const IMAGE_DATA_FULL: u8 = 1 << 2;
+    pub const UNPROCESSED_DATA: u8 = 1 << 3;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    Magic(usize),
+    Command,
+    Compression,
+    Length(usize),
+    Data,
+    Checksum(usize),
+    Alive,
+    Status,
+}
+
+#[derive(Debug)]
+pub struct Printer {
+    output_dir: PathBuf,
+    stage: Stage,
+
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+
+    /// Decompressed tile data waiting to be printed
+    buffer: Vec<u8>,
+    /// Printed so far, one byte per pixel
+    paper: Vec<u8>,
+    status: u8,
+    busy_polls: u8,
+}
+
+impl Printer {
+    /// Printouts are saved in `output_dir`
+    pub fn new(output_dir: PathBuf) -> Self {
+        Self {
+            output_dir,
+            stage: Stage::Magic(0),
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            buffer: Vec::new(),
+            paper: Vec::new(),
+            status: 0,
+            busy_polls: 0,
+        }
+    }
+
+    /// Feeds one byte from the game to the printer, returning what it shifts back
+    pub fn transfer(&mut self, byte: u8) -> u8 {
+        match self.stage {
+            Stage::Magic(i) => {
+                self.stage = if byte == MAGIC[i] {
+                    if i == 0 {
+                        Stage::Magic(1)
+                    } else {
+                        Stage::Command
+                    }
+                } else if byte == MAGIC[0] {
+                    Stage::Magic(1)
+                } else {
+                    Stage::Magic(0)
+                };
+            }
+            Stage::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                self.stage = Stage::Compression;
+            }
+            Stage::Compression => {
+                self.compressed = byte & 1 != 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.stage = Stage::Length(0);
+            }
+            Stage::Length(i) => {
+                self.length.set_bits((i * 8)..(i * 8 + 8), byte as u16);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.clear();
+                self.stage = match (i, self.length) {
+                    (0, _) => Stage::Length(1),
+                    (_, 0) => Stage::Checksum(0),
+                    _ => Stage::Data,
+                };
+            }
+            Stage::Data => {
+                self.data.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.data.len() == self.length as usize {
+                    self.stage = Stage::Checksum(0);
+                }
+            }
+            Stage::Checksum(0) => {
+                self.checksum ^= byte as u16;
+                self.stage = Stage::Checksum(1);
+            }
+            Stage::Checksum(_) => {
+                self.checksum ^= (byte as u16) << 8;
+                self.stage = Stage::Alive;
+                if self.checksum == 0 {
+                    self.status.set_bit(0, false);
+                    self.run_command();
+                } else {
+                    warn!(target: "serial", "Printer packet checksum mismatch");
+                    self.status |= status::CHECKSUM_ERROR;
+                }
+            }
+            Stage::Alive => {
+                self.stage = Stage::Status;
+                return DEVICE_ID;
+            }
+            Stage::Status => {
+                self.stage = Stage::Magic(0);
+                return self.status;
+            }
+        }
+        0x00
+    }
+
+    fn run_command(&mut self) {
+        debug!(target: "serial",
+            "Printer command {:#X}, {} bytes",
+            self.command,
+            self.data.len()
+        );
+        match self.command {
+            0x01 => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            }
+            0x02 => self.print(),
+            0x04 => {
+                let data = if self.compressed {
+                    decompress(&self.data)
+                } else {
+                    std::mem::take(&mut self.data)
+                };
+                let room = BUFFER_SIZE - self.buffer.len();
+                if data.len() > room {
+                    warn!(target: "serial",
+                        "Printer buffer overflow, dropping {} bytes",
+                        data.len() - room
+                    );
+                }
+                self.buffer.extend_from_slice(&data[..data.len().min(room)]);
+                self.status.set_bit(2, self.buffer.len() >= BUFFER_SIZE);
+                self.status.set_bit(3, !self.buffer.is_empty());
+            }
+            0x08 => {
+                self.buffer.clear();
+                self.status &= !(status::IMAGE_DATA_FULL | status::UNPROCESSED_DATA);
+            }
+            0x0F => {
+                if self.busy_polls > 0 {
+                    self.busy_polls -= 1;
+                    self.status.set_bit(1, self.busy_polls > 0);
+                }
+            }
+            other => warn!(target: "serial", "Unknown printer command {:#X}", other),
+        }
+    }
+
+    fn print(&mut self) {
+        let (sheets, margins, palette) = match self.data[..] {
+            [sheets, margins, palette, _exposure] => (sheets, margins, palette),
+            _ => {
+                warn!(target: "serial", "Print command with {} bytes of options", self.data.len());
+                return;
+            }
+        };
+        // A lot of games send 0 meaning the default palette
+        let palette = if palette == 0 { 0xE4 } else { palette };
+
+        if sheets > 0 {
+            self.render(palette);
+        }
+        self.buffer.clear();
+        self.status &= !(status::IMAGE_DATA_FULL | status::UNPROCESSED_DATA);
+        self.status |= status::PRINTING;
+        self.busy_polls = BUSY_POLLS;
+
+        if margins & 0x0F != 0 {
+            self.cut();
+        }
+    }
+
+    /// Appends the buffered tiles to the paper
+    fn render(&mut self, palette: u8) {
+        const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+        for tile_row in self.buffer.chunks_exact(TILE_ROW_BYTES) {
+            for line in 0..8 {
+                for x in 0..WIDTH {
+                    let tile = &tile_row[(x / 8) * 16..];
+                    let bit = 7 - (x % 8);
+                    let low = tile[line * 2].get_bit(bit) as u8;
+                    let high = tile[line * 2 + 1].get_bit(bit) as u8;
+                    let color = palette >> ((high << 1 | low) * 2) & 0b11;
+                    self.paper.push(SHADES[color as usize]);
+                }
+            }
+        }
+    }
+
+    /// Saves whatever's on the paper
+    fn cut(&mut self) {
+        if self.paper.is_empty() {
+            return;
+        }
+        let paper = std::mem::take(&mut self.paper);
+        match self.save(&paper) {
+            Ok(path) => info!(target: "serial", "Printed {:?}", path),
+            Err(e) => warn!(target: "serial", "Failed to save printout: {}", e),
+        }
+    }
+
+    fn save(&self, paper: &[u8]) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(&self.output_dir)?;
+        let path = (1..)
+            .map(|i| self.output_dir.join(format!("print-{:04}.png", i)))
+            .find(|path| !path.exists())
+            .expect("Ran out of printout names");
+        let height = (paper.len() / WIDTH) as u32;
+        png::write_grayscale(
+            BufWriter::new(File::create(&path)?),
+            WIDTH as u32,
+            height,
+            paper,
+        )?;
+        Ok(path)
+    }
+}
+
+impl Drop for Printer {
+    fn drop(&mut self) {
+        self.cut();
+    }
+}
+
+impl SerialLink for Printer {
+    fn exchange(&mut self, out: u8) -> Option<u8> {
+        Some(self.transfer(out))
+    }
+
+    /// The printer never drives the clock
+    fn poll(&mut self, _reply: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// Run-length decoding: bit 7 set repeats the next byte (n & 0x7F) + 2 times, otherwise the
+/// next n + 1 bytes are copied as is
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut bytes = data.iter().copied();
+    while let Some(control) = bytes.next() {
+        if control.get_bit(7) {
+            let Some(byte) = bytes.next() else { break };
+            out.extend(std::iter::repeat_n(byte, (control & 0x7F) as usize + 2));
+        } else {
+            out.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+    out
+}
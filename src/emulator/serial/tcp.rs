@@ -0,0 +1,146 @@
+//! Link cable over TCP
+//!
+//! After connecting both sides send a handshake: `GBLK`, a version byte and the serial clock
+//! they run at in Hz (big endian u32). The link only comes up if both agree. From then on
+//! every [`LinkMessage`] is two bytes, a tag (0 = clock, 1 = reply) and the data byte.
+//!
+//! The socket is bridged to a [`ChannelLink`] by two threads, so polling from the emulator
+//! never touches the socket.
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+};
+
+use tracing::{info, warn};
+
+use super::{link_pair, ChannelLink, LinkMessage, CYCLES_PER_TRANSFER};
+
+pub const MAGIC: &[u8; 4] = b"GBLK";
+pub const VERSION: u8 = 1;
+const HANDSHAKE_LEN: usize = 9;
+
+/// Bit rate of the internal serial clock
+pub const SERIAL_CLOCK_HZ: u32 = 4_194_304 / (CYCLES_PER_TRANSFER / 8);
+
+#[derive(Debug)]
+pub enum LinkError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+    ClockMismatch { ours: u32, theirs: u32 },
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::Io(e) => write!(f, "{}", e),
+            LinkError::BadMagic => write!(f, "Other side isn't a link cable"),
+            LinkError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported link version {}", version)
+            }
+            LinkError::ClockMismatch { ours, theirs } => write!(
+                f,
+                "Serial clocks don't match (ours {}Hz, theirs {}Hz)",
+                ours, theirs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<io::Error> for LinkError {
+    fn from(e: io::Error) -> Self {
+        LinkError::Io(e)
+    }
+}
+
+/// Waits for one instance to connect on `addr`
+pub fn host<A: ToSocketAddrs>(addr: A) -> Result<ChannelLink, LinkError> {
+    accept(&TcpListener::bind(addr)?)
+}
+
+pub fn accept(listener: &TcpListener) -> Result<ChannelLink, LinkError> {
+    info!(target: "serial", "Waiting for link partner on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    info!(target: "serial", "Link partner connected from {}", peer);
+    bridge(stream)
+}
+
+pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<ChannelLink, LinkError> {
+    let stream = TcpStream::connect(addr)?;
+    info!(target: "serial", "Connected to link partner {}", stream.peer_addr()?);
+    bridge(stream)
+}
+
+fn handshake(stream: &mut TcpStream) -> Result<(), LinkError> {
+    let mut ours = [0; HANDSHAKE_LEN];
+    ours[0..4].copy_from_slice(MAGIC);
+    ours[4] = VERSION;
+    ours[5..9].copy_from_slice(&SERIAL_CLOCK_HZ.to_be_bytes());
+    stream.write_all(&ours)?;
+
+    let mut theirs = [0; HANDSHAKE_LEN];
+    stream.read_exact(&mut theirs)?;
+    if &theirs[0..4] != MAGIC {
+        return Err(LinkError::BadMagic);
+    }
+    if theirs[4] != VERSION {
+        return Err(LinkError::UnsupportedVersion(theirs[4]));
+    }
+    let clock = u32::from_be_bytes([theirs[5], theirs[6], theirs[7], theirs[8]]);
+    if clock != SERIAL_CLOCK_HZ {
+        return Err(LinkError::ClockMismatch {
+            ours: SERIAL_CLOCK_HZ,
+            theirs: clock,
+        });
+    }
+    Ok(())
+}
+
+fn bridge(mut stream: TcpStream) -> Result<ChannelLink, LinkError> {
+    handshake(&mut stream)?;
+    // Every transfer is a round trip, don't let Nagle sit on them
+    stream.set_nodelay(true)?;
+
+    let (link, remote) = link_pair();
+    let ChannelLink { sender, receiver } = remote;
+
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || loop {
+        let mut message = [0; 2];
+        if let Err(e) = reader.read_exact(&mut message) {
+            warn!(target: "serial", "Link partner disconnected: {}", e);
+            break;
+        }
+        let message = match message {
+            [0, byte] => LinkMessage::Clock(byte),
+            [1, byte] => LinkMessage::Reply(byte),
+            [tag, _] => {
+                warn!(target: "serial", "Unknown link message {:#X}, disconnecting", tag);
+                break;
+            }
+        };
+        if sender.send(message).is_err() {
+            break;
+        }
+    });
+
+    let mut writer = stream;
+    thread::spawn(move || {
+        for message in receiver {
+            let bytes = match message {
+                LinkMessage::Clock(byte) => [0, byte],
+                LinkMessage::Reply(byte) => [1, byte],
+            };
+            if writer.write_all(&bytes).is_err() {
+                break;
+            }
+        }
+        // The emulator went away, let the other side know instead of having it time out
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    Ok(link)
+}
@@ -0,0 +1,167 @@
+//! Save states
+//!
+//! A `GBST` magic, a version byte and the ROM's global checksum, followed by each component's
+//! fields in a fixed order. Multi-byte values are little endian. There's no framing between
+//! components, so any change to what gets saved needs a version bump.
+use std::{fmt, path::PathBuf};
+
+use crate::emulator::{paths::Paths, save_file};
+
+pub const MAGIC: &[u8; 4] = b"GBST";
+pub const VERSION: u8 = 25;
+
+/// Where the state saved on exit for resuming is kept
+pub fn resume_file(paths: &Paths, title: &str, checksum: u16) -> PathBuf {
+    save_file::game_file(&paths.states, title, checksum, "resume.gbst")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// Made with another game
+    WrongRom {
+        expected: u16,
+        found: u16,
+    },
+    /// Ran out of data partway through
+    Truncated,
+    /// Data left over after everything was read
+    TrailingData(usize),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version {}", version)
+            }
+            StateError::WrongRom { expected, found } => write!(
+                f,
+                "Save state is for another ROM (checksum {:#06X}, loaded {:#06X})",
+                found, expected
+            ),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::TrailingData(len) => {
+                write!(
+                    f,
+                    "{} bytes of unexpected data at the end of save state",
+                    len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new(rom_checksum: u16) -> Self {
+        let mut writer = Self::default();
+        writer.bytes(MAGIC);
+        writer.u8(VERSION);
+        writer.u16(rom_checksum);
+        writer
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Checks the header against the loaded ROM
+    pub fn new(bytes: &'a [u8], rom_checksum: u16) -> Result<Self, StateError> {
+        let mut reader = Self { bytes };
+        if reader.bytes(4).map_err(|_| StateError::BadMagic)? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let found = reader.u16()?;
+        if found != rom_checksum {
+            return Err(StateError::WrongRom {
+                expected: rom_checksum,
+                found,
+            });
+        }
+        Ok(reader)
+    }
+
+    /// For bytes from a [`StateWriter::default`], which has no header
+    pub fn headerless(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    /// Fills `out` completely
+    pub fn fill(&mut self, out: &mut [u8]) -> Result<(), StateError> {
+        out.copy_from_slice(self.bytes(out.len())?);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), StateError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            len => Err(StateError::TrailingData(len)),
+        }
+    }
+}
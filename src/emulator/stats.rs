@@ -0,0 +1,152 @@
+//! How fast emulation is going, for frontends to show
+//!
+//! [`StatsMeter`] is told about every frame the emulation loop finishes and works out
+//! [`Stats`] over windows of [`WINDOW`], so the numbers stay readable instead of jittering with
+//! every frame.
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// T-cycles a Game Boy runs a second
+pub const CLOCK_HZ: f64 = 4_194_304.0;
+/// How long each set of [`Stats`] is averaged over
+pub const WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Emulated frames per real second, 0 while paused
+    pub fps: f64,
+    /// Percentage of a real Game Boy's speed, 0 while paused
+    pub speed: f64,
+    /// Average T-cycles per frame, 70224 unless frames are being cut short
+    pub cycles_per_frame: f64,
+    /// How full the audio buffer is from 0 to 1, `None` without sound output, which there
+    /// isn't yet
+    pub audio_buffer: Option<f64>,
+    /// Frames since emulation started
+    pub frames: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} FPS, {:.0}% speed, {:.0} cycles/frame",
+            self.fps, self.speed, self.cycles_per_frame
+        )?;
+        if let Some(buffer) = self.audio_buffer {
+            write!(f, ", audio buffer {:.0}% full", buffer * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// How much was emulated in how long, from power on. What `--bench` prints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Throughput {
+    pub frames: u64,
+    pub cycles: u64,
+    pub instructions: u64,
+    /// Real time
+    pub elapsed: Duration,
+}
+
+impl Throughput {
+    /// Seconds a Game Boy would have taken
+    pub fn emulated_seconds(&self) -> f64 {
+        self.cycles as f64 / CLOCK_HZ
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Averaged over the whole run
+    pub fn stats(&self) -> Stats {
+        let seconds = self.elapsed.as_secs_f64();
+        Stats {
+            fps: self.frames as f64 / seconds,
+            speed: self.emulated_seconds() / seconds * 100.0,
+            cycles_per_frame: self.cycles as f64 / self.frames as f64,
+            audio_buffer: None,
+            frames: self.frames,
+        }
+    }
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        writeln!(f, "{} frames in {:.3}s", self.frames, seconds)?;
+        writeln!(
+            f,
+            "{:.2} emulated seconds per second ({:.1}s emulated)",
+            self.emulated_seconds() / seconds,
+            self.emulated_seconds()
+        )?;
+        writeln!(
+            f,
+            "{:.2}M instructions per second",
+            self.instructions_per_second() / 1e6
+        )?;
+        write!(f, "{}", self.stats())
+    }
+}
+
+#[derive(Debug)]
+pub struct StatsMeter {
+    stats: Stats,
+    window_start: Instant,
+    /// [`Emulator::cycles`](crate::emulator::Emulator::cycles) when the window started
+    window_cycles: u64,
+    window_frames: u32,
+}
+
+impl StatsMeter {
+    pub fn new(now: Instant, cycles: u64) -> Self {
+        Self {
+            stats: Stats::default(),
+            window_start: now,
+            window_cycles: cycles,
+            window_frames: 0,
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// A frame finished at `now`, with the emulator's cycle count at `cycles`. Returns the new
+    /// stats once a window's worth of frames are in.
+    pub fn frame(&mut self, now: Instant, cycles: u64) -> Option<Stats> {
+        self.stats.frames += 1;
+        self.window_frames += 1;
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed < WINDOW {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let emulated = cycles.saturating_sub(self.window_cycles) as f64;
+        self.stats.fps = self.window_frames as f64 / seconds;
+        self.stats.speed = emulated / CLOCK_HZ / seconds * 100.0;
+        self.stats.cycles_per_frame = emulated / self.window_frames as f64;
+        self.restart(now, cycles);
+        Some(self.stats)
+    }
+
+    /// Nothing's running, the next window starts whenever it does. Returns the stats with
+    /// the speed at 0.
+    pub fn pause(&mut self, now: Instant, cycles: u64) -> Stats {
+        self.stats.fps = 0.0;
+        self.stats.speed = 0.0;
+        self.restart(now, cycles);
+        self.stats
+    }
+
+    fn restart(&mut self, now: Instant, cycles: u64) {
+        self.window_start = now;
+        self.window_cycles = cycles;
+        self.window_frames = 0;
+    }
+}
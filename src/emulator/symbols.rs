@@ -0,0 +1,102 @@
+//! Symbol files
+//!
+//! RGBDS (and most other assemblers with `-n`) write one `BB:AAAA Name` line per label, with
+//! the bank and address in hex and `;` starting a comment. Section headers like `[labels]`
+//! from other tools are skipped. Only ROM and fixed-bank labels are useful for now, but RAM
+//! labels are kept too so `[wPlayerX]` can be looked up by name.
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::emulator::rom::Location;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolError {
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bad symbol on line {}: '{}'", self.line, self.text)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    by_location: BTreeMap<Location, String>,
+}
+
+impl Symbols {
+    pub fn parse(contents: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Self::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let error = || SymbolError {
+                line: i + 1,
+                text: line.to_string(),
+            };
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let (bank, addr) = location.split_once(':').ok_or_else(error)?;
+            let location = Location {
+                bank: u16::from_str_radix(bank, 16).map_err(|_| error())?,
+                addr: u16::from_str_radix(addr, 16).map_err(|_| error())?,
+            };
+            // The first label at an address is usually the global one, keep that
+            symbols
+                .by_location
+                .entry(location)
+                .or_insert_with(|| name.trim().to_string());
+        }
+        Ok(symbols)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_location.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_location.len()
+    }
+
+    /// The label exactly at `location`
+    pub fn get(&self, location: Location) -> Option<&str> {
+        self.by_location.get(&location).map(String::as_str)
+    }
+
+    /// The closest label at or before `location` in the same bank, with how far past it
+    /// `location` is
+    pub fn lookup(&self, location: Location) -> Option<(&str, u16)> {
+        let start = Location {
+            bank: location.bank,
+            addr: 0,
+        };
+        let (found, name) = self.by_location.range(start..=location).next_back()?;
+        Some((name, location.addr - found.addr))
+    }
+
+    /// Where the label called `name` is
+    pub fn find(&self, name: &str) -> Option<Location> {
+        self.by_location
+            .iter()
+            .find(|(_, symbol)| *symbol == name)
+            .map(|(&location, _)| location)
+    }
+
+    /// `Main_Loop` or `Main_Loop+0x3`, `None` without a label before it
+    pub fn describe(&self, location: Location) -> Option<String> {
+        match self.lookup(location)? {
+            (name, 0) => Some(name.to_string()),
+            (name, offset) => Some(format!("{}+{:#X}", name, offset)),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Divider and timer
+//!
+//! DIV is the top byte of a 16-bit counter that runs at the CPU clock. TIMA counts the falling
+//! edges of one of the counter's bits (picked by TAC) while the timer is enabled, so anything
+//! that drops that bit early also increments TIMA: writing DIV, which resets the counter, or
+//! changing TAC.
+//!
+//! The APU's frame sequencer counts the falling edges of DIV bit 4 the same way, see
+//! [`Timer::take_div_apu_steps`].
+//!
+//! An overflow leaves TIMA at 0 for one M-cycle before TMA is loaded and the interrupt is
+//! requested. Writing TIMA during that cycle cancels the reload, while during the reload cycle
+//! itself a TIMA write is lost and a TMA write goes through to TIMA as well.
+use bit_field::BitField;
+use tracing::trace;
+
+use std::ops::RangeInclusive;
+
+use crate::emulator::{
+    memory_bus::{mmio::MmioDevice, Interrupt},
+    state::{StateError, StateReader, StateWriter},
+};
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+/// The counter bit the APU's frame sequencer counts, DIV bit 4
+const DIV_APU_BIT: usize = 12;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Reload {
+    #[default]
+    Idle,
+    /// Overflowed in the last M-cycle, TIMA reads 0
+    Pending,
+    /// TMA was loaded in the last M-cycle
+    Reloading,
+}
+
+impl Reload {
+    fn to_u8(self) -> u8 {
+        match self {
+            Reload::Idle => 0,
+            Reload::Pending => 1,
+            Reload::Reloading => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Reload::Pending,
+            2 => Reload::Reloading,
+            _ => Reload::Idle,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Timer {
+    /// DIV is the top 8 bits
+    counter: u16,
+    /// TIMA
+    counter_value: u8,
+    /// TMA
+    modulo: u8,
+    /// TAC, bits 0-2
+    control: u8,
+    reload: Reload,
+    /// Falling edges of [`DIV_APU_BIT`] since the APU last took them
+    div_apu_steps: u32,
+}
+
+impl Timer {
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.counter_value,
+            TMA => self.modulo,
+            TAC => self.control,
+            _ => unreachable!("Timer read @{:#X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        trace!(target: "timer", "Timer write @{:#X}: {:#X}", addr, byte);
+        let before = self.input();
+        match addr {
+            DIV => {
+                if self.counter.get_bit(DIV_APU_BIT) {
+                    self.div_apu_steps += 1;
+                }
+                self.counter = 0;
+            }
+            TIMA => match self.reload {
+                Reload::Idle => self.counter_value = byte,
+                Reload::Pending => {
+                    self.counter_value = byte;
+                    self.reload = Reload::Idle;
+                }
+                // TMA is being copied in at the same time and wins
+                Reload::Reloading => {}
+            },
+            TMA => {
+                self.modulo = byte;
+                if self.reload == Reload::Reloading {
+                    self.counter_value = byte;
+                }
+            }
+            TAC => self.control = byte & 0b111,
+            _ => unreachable!("Timer write @{:#X}", addr),
+        }
+        if before && !self.input() {
+            self.increment();
+        }
+    }
+
+    /// Runs for `cycles` T-cycles, returns true if the timer interrupt should be requested
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let mut interrupt = false;
+        // The counter goes up a whole M-cycle at a time
+        for _ in 0..cycles / 4 {
+            self.reload = match self.reload {
+                Reload::Pending => {
+                    self.counter_value = self.modulo;
+                    interrupt = true;
+                    Reload::Reloading
+                }
+                _ => Reload::Idle,
+            };
+
+            let before = self.input();
+            let div_apu = self.counter.get_bit(DIV_APU_BIT);
+            self.counter = self.counter.wrapping_add(4);
+            if before && !self.input() {
+                self.increment();
+            }
+            if div_apu && !self.counter.get_bit(DIV_APU_BIT) {
+                self.div_apu_steps += 1;
+            }
+        }
+        interrupt
+    }
+
+    /// T-cycles until [`Timer::tick`] returns true, if nothing's written in between.
+    /// `u32::MAX` with the timer off.
+    pub fn cycles_until_interrupt(&self) -> u32 {
+        if self.reload == Reload::Pending {
+            return 4;
+        }
+        if !self.control.get_bit(2) {
+            return u32::MAX;
+        }
+        // TIMA goes up every time the counter passes a multiple of this
+        let period = 2 << self.input_bit();
+        let to_increment = period - self.counter as u32 % period;
+        // Plus the M-cycle before the reload
+        to_increment + (255 - self.counter_value as u32) * period + 4
+    }
+
+    /// How many times the frame sequencer should step, since the last call
+    pub fn take_div_apu_steps(&mut self) -> u32 {
+        std::mem::take(&mut self.div_apu_steps)
+    }
+
+    /// The counter bit TIMA counts
+    fn input_bit(&self) -> usize {
+        match self.control & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        }
+    }
+
+    /// The counter bit TIMA counts, gated by the enable bit
+    fn input(&self) -> bool {
+        self.control.get_bit(2) && self.counter.get_bit(self.input_bit())
+    }
+
+    /// An overflow leaves TIMA at 0 until the reload
+    fn increment(&mut self) {
+        let (value, overflowed) = self.counter_value.overflowing_add(1);
+        self.counter_value = value;
+        if overflowed {
+            self.reload = Reload::Pending;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.counter);
+        state.u8(self.counter_value);
+        state.u8(self.modulo);
+        state.u8(self.control);
+        state.u8(self.reload.to_u8());
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.counter = state.u16()?;
+        self.counter_value = state.u8()?;
+        self.modulo = state.u8()?;
+        self.control = state.u8()? & 0b111;
+        self.reload = Reload::from_u8(state.u8()?);
+        Ok(())
+    }
+}
+
+impl MmioDevice for Timer {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[DIV..=TAC]
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        Timer::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) -> Option<Interrupt> {
+        Timer::write(self, addr, byte);
+        None
+    }
+
+    fn tick(&mut self, cycles: u32) -> Option<Interrupt> {
+        Timer::tick(self, cycles).then_some(Interrupt::Timer)
+    }
+
+    fn cycles_until_interrupt(&self) -> u32 {
+        Timer::cycles_until_interrupt(self)
+    }
+}
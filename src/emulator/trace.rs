@@ -0,0 +1,342 @@
+//! Running in lockstep with another emulator's trace log
+//!
+//! Game Boy Doctor logs the registers before every instruction, one line each:
+//! `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`. BGB's logs
+//! show pairs instead, `AF:01B0 BC:0013 DE:00D8 HL:014D SP:FFFE`, usually after the bank and
+//! address (`ROM0:0100`) with flags as letters (`F:Z-HC`). [`diverge`] runs the ROM
+//! alongside one of them and stops at the first line that doesn't match what the emulator has,
+//! which turns a crash minutes into a game into the instruction that went wrong. Whatever a
+//! log leaves out isn't compared.
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::emulator::{cpu::registers::Reg8, error::Crash, instructions::Instruction, Emulator};
+
+/// Doctor's logs come from emulators where LY always reads this, see
+/// [`MemoryBus::stub_ly`](crate::emulator::memory_bus::MemoryBus::stub_ly)
+pub const DOCTOR_LY: u8 = 0x90;
+
+/// How many matching lines a [`Divergence`] shows
+const HISTORY: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Separate 8 bit registers
+    #[default]
+    Doctor,
+    /// Register pairs
+    Bgb,
+}
+
+/// CPU state before an instruction, from a log or the emulator
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceLine {
+    /// In [`Reg8::ALL`] order
+    pub registers: [Option<u8>; 8],
+    pub sp: Option<u16>,
+    pub pc: Option<u16>,
+    /// The 4 bytes from PC on
+    pub pcmem: Option<[u8; 4]>,
+    pub format: TraceFormat,
+}
+
+impl TraceLine {
+    /// Everything a Doctor log would have
+    pub fn of(emulator: &Emulator) -> Self {
+        let cpu = emulator.cpu();
+        let peek = |offset| emulator.memory_bus().peek(cpu.PC.wrapping_add(offset));
+        Self {
+            registers: Reg8::ALL.map(|register| Some(cpu.read_reg(register))),
+            sp: Some(cpu.SP),
+            pc: Some(cpu.PC),
+            pcmem: Some([peek(0), peek(1), peek(2), peek(3)]),
+            format: TraceFormat::Doctor,
+        }
+    }
+
+    /// Fields are `KEY:VALUE` in hex, separated by spaces or `;`. Unknown ones are skipped, so a
+    /// line with nothing in it parses as an empty [`TraceLine`].
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let fields = line
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .filter(|field| !field.is_empty());
+        for (i, field) in fields.enumerate() {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            let bad = || format!("Bad {} in '{}'", key, field);
+            let byte = || u8::from_str_radix(value, 16).map_err(|_| bad());
+            let word = || u16::from_str_radix(value, 16).map_err(|_| bad());
+            let key = key.to_ascii_uppercase();
+            if let Some(register) = index(&key) {
+                parsed.registers[register] = Some(match key.as_str() {
+                    "F" if value.len() == 4 => flag_letters(value).ok_or_else(bad)?,
+                    "F" => byte()? & 0xF0,
+                    _ => byte()?,
+                });
+                continue;
+            }
+            match key.as_str() {
+                "AF" | "BC" | "DE" | "HL" => {
+                    let [high, low] = word()?.to_be_bytes();
+                    parsed.registers[index(&key[..1]).unwrap()] = Some(high);
+                    parsed.registers[index(&key[1..]).unwrap()] =
+                        Some(if key == "AF" { low & 0xF0 } else { low });
+                    parsed.format = TraceFormat::Bgb;
+                }
+                "SP" => parsed.sp = Some(word()?),
+                "PC" => parsed.pc = Some(word()?),
+                "PCMEM" => {
+                    let bytes: Result<Vec<u8>, _> = value
+                        .split(',')
+                        .map(|byte| u8::from_str_radix(byte, 16))
+                        .collect();
+                    parsed.pcmem =
+                        Some(bytes.ok().and_then(|b| b.try_into().ok()).ok_or_else(bad)?);
+                }
+                // BGB starts with where it is, like ROM0:0100
+                _ if i == 0 && value.len() == 4 => parsed.pc = Some(word()?),
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.iter().all(Option::is_none)
+            && self.sp.is_none()
+            && self.pc.is_none()
+            && self.pcmem.is_none()
+    }
+
+    /// Names of the fields this has that `actual` disagrees with
+    pub fn mismatches(&self, actual: &Self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for (i, register) in Reg8::ALL.into_iter().enumerate() {
+            if self.registers[i].is_some() && self.registers[i] != actual.registers[i] {
+                names.push(register.name());
+            }
+        }
+        if self.sp.is_some() && self.sp != actual.sp {
+            names.push("SP");
+        }
+        if self.pc.is_some() && self.pc != actual.pc {
+            names.push("PC");
+        }
+        if self.pcmem.is_some() && self.pcmem != actual.pcmem {
+            names.push("PCMEM");
+        }
+        names
+    }
+
+    /// `name`'s value as text, `-` if this doesn't have it
+    fn field(&self, name: &str) -> String {
+        let hex = |value: Option<u16>, width| match value {
+            Some(value) => format!("{:0width$X}", value, width = width),
+            None => "-".into(),
+        };
+        match name {
+            "SP" => hex(self.sp, 4),
+            "PC" => hex(self.pc, 4),
+            "PCMEM" => match self.pcmem {
+                Some(bytes) => bytes.map(|byte| format!("{:02X}", byte)).join(","),
+                None => "-".into(),
+            },
+            _ => hex(self.registers[index(name).unwrap()].map(u16::from), 2),
+        }
+    }
+}
+
+/// Where the register called `name` is in [`TraceLine::registers`]
+fn index(name: &str) -> Option<usize> {
+    Reg8::ALL
+        .iter()
+        .position(|register| register.name() == name)
+}
+
+/// `Z-HC` style flags, any letter but the right one means clear
+fn flag_letters(value: &str) -> Option<u8> {
+    let mut flags = 0;
+    for (bit, (letter, expected)) in value.chars().zip("ZNHC".chars()).enumerate() {
+        if !letter.is_ascii_alphabetic() && letter != '-' {
+            return None;
+        }
+        if letter == expected {
+            flags |= 0x80 >> bit;
+        }
+    }
+    Some(flags)
+}
+
+/// Doctor's format, missing fields are left out
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = Vec::new();
+        for name in Reg8::ALL
+            .map(Reg8::name)
+            .into_iter()
+            .chain(["SP", "PC", "PCMEM"])
+        {
+            let value = self.field(name);
+            if value != "-" {
+                fields.push(format!("{}:{}", name, value));
+            }
+        }
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+/// The first line of a log the emulator didn't match
+#[derive(Debug)]
+pub struct Divergence {
+    /// From 1
+    pub line: usize,
+    pub expected: TraceLine,
+    pub actual: TraceLine,
+    /// The lines before it that did match, oldest first, with the instruction at each
+    pub history: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mismatches = self.expected.mismatches(&self.actual);
+        writeln!(
+            f,
+            "Diverged at line {}: {}",
+            self.line,
+            mismatches.join(", ")
+        )?;
+        writeln!(f, "{:<6}{:<14}actual", "", "expected")?;
+        for name in Reg8::ALL
+            .map(Reg8::name)
+            .into_iter()
+            .chain(["SP", "PC", "PCMEM"])
+        {
+            let expected = self.expected.field(name);
+            if expected == "-" {
+                continue;
+            }
+            let marker = if mismatches.contains(&name) {
+                "  <"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "{:<6}{:<14}{}{}",
+                name,
+                expected,
+                self.actual.field(name),
+                marker
+            )?;
+        }
+        if self.history.is_empty() {
+            return write!(f, "No lines matched before it");
+        }
+        write!(f, "Matched before it, oldest first:")?;
+        for line in &self.history {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// The line number, from 1, and what's wrong with it
+    Parse(usize, String),
+    /// The emulator stopped on a line that had matched so far
+    Crash(usize, Crash),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "Failed to read the trace: {}", e),
+            TraceError::Parse(line, e) => write!(f, "Line {} of the trace: {}", line, e),
+            TraceError::Crash(line, crash) => write!(
+                f,
+                "Crashed after line {} of the trace: {}\n{}",
+                line, crash.message, crash.cpu_dump
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+/// How [`diverge`] finished
+#[derive(Debug)]
+pub enum Outcome {
+    /// Every line did, this many of them
+    Matched(usize),
+    Diverged(Box<Divergence>),
+}
+
+/// Steps `emulator` through `log` one instruction per line, comparing before each one. Doctor
+/// logs get LY stubbed to [`DOCTOR_LY`], going by the first line.
+pub fn diverge(emulator: &mut Emulator, log: impl BufRead) -> Result<Outcome, TraceError> {
+    let mut history = VecDeque::with_capacity(HISTORY);
+    let mut matched = 0;
+    for (i, line) in log.lines().enumerate() {
+        let line_number = i + 1;
+        let text = line.map_err(TraceError::Io)?;
+        let expected = TraceLine::parse(&text).map_err(|e| TraceError::Parse(line_number, e))?;
+        if expected.is_empty() {
+            continue;
+        }
+        if matched == 0 {
+            if expected.format == TraceFormat::Doctor {
+                emulator.memory_bus_mut().stub_ly(Some(DOCTOR_LY));
+            }
+        } else {
+            next_instruction(emulator)
+                .map_err(|crash| TraceError::Crash(line_number - 1, crash))?;
+        }
+
+        let actual = TraceLine::of(emulator);
+        if !expected.mismatches(&actual).is_empty() {
+            return Ok(Outcome::Diverged(Box::new(Divergence {
+                line: line_number,
+                expected,
+                actual,
+                history: history.into(),
+            })));
+        }
+        if history.len() == HISTORY {
+            history.pop_front();
+        }
+        history.push_back(format!(
+            "{:>8}  {}  {}",
+            line_number,
+            text.trim(),
+            disassemble(emulator)
+        ));
+        matched += 1;
+    }
+    Ok(Outcome::Matched(matched))
+}
+
+/// Steps until an instruction has run, logs don't have lines for being halted or dispatching an
+/// interrupt
+fn next_instruction(emulator: &mut Emulator) -> Result<(), Crash> {
+    loop {
+        emulator.step_catching()?;
+        if emulator.cpu().last_instruction.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+fn disassemble(emulator: &Emulator) -> String {
+    let bytes = emulator.memory_bus().get_instr(emulator.cpu().PC);
+    match Instruction::parse(&bytes) {
+        Ok((_, instruction)) => instruction.to_string(),
+        Err(_) => format!("illegal {:#04X}", bytes[0]),
+    }
+}
@@ -0,0 +1,146 @@
+//! Handing finished frames from the emulator thread to the renderer
+//!
+//! Three frames: the one being written, the one being shown and a spare in between. Publishing
+//! swaps the written frame with the spare and marks it fresh, showing swaps the spare with the
+//! shown frame if it's fresh. Those swaps are single atomic operations on the frame indexes, so
+//! each frame belongs to exactly one side at a time and neither side ever waits on the other.
+//! The renderer always shows the newest complete frame. Frames from CGB mode carry their colors
+//! along with the shades.
+//!
+//! That only holds with one thread on each side. Publishing from two threads at once, or asking
+//! for the latest frame while the last one is still being shown, panics instead.
+use std::{
+    cell::UnsafeCell,
+    ops::Deref,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::emulator::{
+    ppu::{ColorFrameBuffer, FrameBuffer},
+    GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
+};
+
+/// Set on `spare` when it's newer than what's shown
+const FRESH: usize = 0b100;
+const INDEX: usize = 0b011;
+
+struct Frame {
+    shades: FrameBuffer,
+    colors: ColorFrameBuffer,
+    in_color: bool,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            shades: [0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH],
+            colors: [0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH],
+            in_color: false,
+        }
+    }
+}
+
+/// Meant for one thread publishing and one showing, like the emulator thread and renderer
+pub struct TripleBuffer {
+    frames: [UnsafeCell<Frame>; 3],
+    /// Only touched by the publishing side
+    back: AtomicUsize,
+    spare: AtomicUsize,
+    /// Only touched by the showing side
+    front: AtomicUsize,
+    /// Someone's in [`TripleBuffer::publish`]
+    publishing: AtomicBool,
+    /// A [`Shown`] frame is still around
+    showing: AtomicBool,
+}
+
+// SAFETY: `back` and `front` are each only used by their own side, which `publishing` and
+// `showing` keep to one thread at a time, and `spare` only changes hands through atomic swaps.
+// So no frame is ever read and written at once.
+unsafe impl Sync for TripleBuffer {}
+
+impl Default for TripleBuffer {
+    fn default() -> Self {
+        Self {
+            frames: Default::default(),
+            back: AtomicUsize::new(0),
+            spare: AtomicUsize::new(1),
+            front: AtomicUsize::new(2),
+            publishing: AtomicBool::new(false),
+            showing: AtomicBool::new(false),
+        }
+    }
+}
+
+impl TripleBuffer {
+    /// Makes `frame` the one [`TripleBuffer::latest`] returns next, with `colors` if it has them
+    pub fn publish(&self, frame: &FrameBuffer, colors: Option<&ColorFrameBuffer>) {
+        assert!(
+            !self.publishing.swap(true, Ordering::Acquire),
+            "Frames can only be published from one thread at a time"
+        );
+        let back = self.back.load(Ordering::Relaxed);
+        // SAFETY: only the publishing side has `back`, see the `Sync` impl
+        let back_frame = unsafe { &mut *self.frames[back].get() };
+        back_frame.shades.copy_from_slice(frame);
+        back_frame.in_color = colors.is_some();
+        if let Some(colors) = colors {
+            back_frame.colors.copy_from_slice(colors);
+        }
+        let spare = self.spare.swap(back | FRESH, Ordering::AcqRel);
+        self.back.store(spare & INDEX, Ordering::Relaxed);
+        self.publishing.store(false, Ordering::Release);
+    }
+
+    /// The newest published frame, or the one returned last time if nothing's been published
+    /// since. The last one has to be dropped first.
+    pub fn latest(&self) -> Shown<'_> {
+        assert!(
+            !self.showing.swap(true, Ordering::Acquire),
+            "The last frame is still being shown"
+        );
+        let mut front = self.front.load(Ordering::Relaxed);
+        if self.spare.load(Ordering::Acquire) & FRESH != 0 {
+            front = self.spare.swap(front, Ordering::AcqRel) & INDEX;
+            self.front.store(front, Ordering::Relaxed);
+        }
+        Shown {
+            buffer: self,
+            front,
+        }
+    }
+}
+
+/// The frame being shown, the publishing side won't touch it until it's dropped and
+/// [`TripleBuffer::latest`] has swapped it out
+pub struct Shown<'a> {
+    buffer: &'a TripleBuffer,
+    front: usize,
+}
+
+impl Shown<'_> {
+    fn frame(&self) -> &Frame {
+        // SAFETY: only the showing side has `front`, see the `Sync` impl
+        unsafe { &*self.buffer.frames[self.front].get() }
+    }
+
+    /// The frame's colors, if it was published with them
+    pub fn colors(&self) -> Option<&ColorFrameBuffer> {
+        let frame = self.frame();
+        frame.in_color.then_some(&frame.colors)
+    }
+}
+
+impl Deref for Shown<'_> {
+    type Target = FrameBuffer;
+
+    fn deref(&self) -> &FrameBuffer {
+        &self.frame().shades
+    }
+}
+
+impl Drop for Shown<'_> {
+    fn drop(&mut self) {
+        self.buffer.showing.store(false, Ordering::Release);
+    }
+}
@@ -10,5 +10,45 @@ macro_rules! test_success {
     };
 }
 
+pub mod access_stats;
 pub mod alu;
+pub mod apu;
+pub mod archive;
+#[macro_use]
+pub mod asm;
+#[cfg(feature = "cached-interpreter")]
+pub mod block_cache;
+pub mod boot;
+pub mod capi_header;
+pub mod cheats;
+pub mod core;
+pub mod coverage;
+pub mod debugger;
+pub mod execution;
+pub mod flash_filter;
+pub mod frame_blend;
+pub mod hardware;
+pub mod infrared;
 pub mod instructions;
+pub mod joypad;
+pub mod mbc;
+pub mod memory_bus;
+pub mod movie;
+pub mod paths;
+pub mod png;
+pub mod ppu;
+pub mod printer;
+pub mod registers;
+pub mod rom;
+pub mod rtc;
+pub mod save_file;
+pub mod serial;
+pub mod serial_tcp;
+pub mod state;
+pub mod stats;
+pub mod symbols;
+pub mod tile_cache;
+pub mod timer;
+pub mod trace;
+pub mod triple_buffer;
+pub mod watchdog;
@@ -0,0 +1,86 @@
+use crate::emulator::{
+    memory_bus::{
+        access_stats::{Accesses, Region},
+        MemoryBus,
+    },
+    symbols::Symbols,
+    Emulator,
+};
+
+/// Copies 0xC000 to 0xC001 forever
+fn copy_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x108].copy_from_slice(&[0xFA, 0x00, 0xC0, 0xEA, 0x01, 0xC0, 0x18, 0xF8]);
+    rom
+}
+
+fn accesses(reads: u64, writes: u64) -> Accesses {
+    Accesses { reads, writes }
+}
+
+#[test]
+fn counts_cpu_reads_and_writes() {
+    let mut emulator = Emulator::new(&copy_rom());
+    emulator.memory_bus_mut().enable_access_stats();
+    for _ in 0..3 {
+        emulator.step().unwrap();
+    }
+    let stats = emulator.memory_bus().access_stats().unwrap();
+    assert_eq!(stats.get(0xC000), accesses(1, 0));
+    assert_eq!(stats.get(0xC001), accesses(0, 1));
+    assert_eq!(stats.region(Region::Wram), accesses(1, 1));
+    // Instruction fetches aren't data reads
+    assert_eq!(stats.region(Region::Rom0), accesses(0, 0));
+}
+
+#[test]
+fn ppu_reads_are_not_counted() {
+    let mut emulator = Emulator::new(&copy_rom());
+    emulator.memory_bus_mut().enable_access_stats();
+    emulator.run_frame().unwrap();
+    let stats = emulator.memory_bus().access_stats().unwrap();
+    assert_eq!(stats.region(Region::Vram), accesses(0, 0));
+    assert_eq!(stats.region(Region::Io), accesses(0, 0));
+    assert!(stats.get(0xC000).reads > 1000);
+}
+
+#[test]
+fn echo_writes_count_once() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    memory_bus.enable_access_stats();
+    memory_bus.write_u8(0xE000, 1);
+    assert_eq!(memory_bus.read_u8(0xC000), 1);
+    let stats = memory_bus.access_stats().unwrap();
+    assert_eq!(stats.get(0xE000), accesses(0, 1));
+    assert_eq!(stats.get(0xC000), accesses(1, 0));
+}
+
+#[test]
+fn off_by_default() {
+    let mut emulator = Emulator::new(&copy_rom());
+    emulator.step().unwrap();
+    assert!(emulator.memory_bus().access_stats().is_none());
+}
+
+#[test]
+fn heatmap_and_report() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    memory_bus.enable_access_stats();
+    for _ in 0..100 {
+        memory_bus.read_u8(0xC000);
+    }
+    memory_bus.write_u8(0xFF80, 0);
+    let stats = memory_bus.access_stats().unwrap();
+
+    let heatmap = stats.heatmap();
+    assert_eq!(heatmap.len(), 256);
+    assert_eq!(heatmap[0xC0], 255);
+    assert!(heatmap[0xFF] > 0 && heatmap[0xFF] < 255);
+    assert_eq!(heatmap[0xC1], 0);
+
+    let symbols = Symbols::parse("00:C000 wCounter\n").unwrap();
+    let report = stats.report(&symbols);
+    assert!(report.contains("\nWRAM                100            0\n"));
+    assert!(report.contains("\n  C000          100            0  wCounter\n"));
+    assert!(report.ends_with("C000 100 0\nFF80 0 1\n"));
+}
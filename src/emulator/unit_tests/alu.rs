@@ -1,5 +1,9 @@
 #![allow(clippy::bool_assert_comparison)]
-use crate::emulator::cpu::{Flag, ALU, CPU};
+use crate::emulator::{
+    cpu::{alu, Flag, ALU, CPU},
+    instructions::{AccumulatorFlagOp, AluOp, BitwiseOp, Instruction, Register8},
+    memory_bus::MemoryBus,
+};
 
 #[test]
 fn test_sra() {
@@ -30,3 +34,321 @@ fn test_srl() {
     assert_eq!(actual, expected);
     assert_eq!(dummy.get_flag(Flag::C), true);
 }
+
+// Everything below checks every input against a reference written from the Pan Docs
+// descriptions, separately from the ALU. 8 bit inputs are few enough to try them all instead
+// of sampling.
+
+const Z: u8 = 0x80;
+const N: u8 = 0x40;
+const H: u8 = 0x20;
+const C: u8 = 0x10;
+
+fn flags(z: bool, n: bool, h: bool, c: bool) -> u8 {
+    (z as u8) << 7 | (n as u8) << 6 | (h as u8) << 5 | (c as u8) << 4
+}
+
+/// A and F after `op` with `value`
+fn reference_alu(op: AluOp, a: u8, value: u8, carry: bool) -> (u8, u8) {
+    let (a, value, carry) = (a as i32, value as i32, carry as i32);
+    let (result, n, h, c) = match op {
+        AluOp::Add => (
+            a + value,
+            false,
+            (a & 0xF) + (value & 0xF) > 0xF,
+            a + value > 0xFF,
+        ),
+        AluOp::AddWithCarry => (
+            a + value + carry,
+            false,
+            (a & 0xF) + (value & 0xF) + carry > 0xF,
+            a + value + carry > 0xFF,
+        ),
+        AluOp::Subtract | AluOp::Compare => (a - value, true, (a & 0xF) < (value & 0xF), a < value),
+        AluOp::SubtractWithCarry => (
+            a - value - carry,
+            true,
+            (a & 0xF) - (value & 0xF) - carry < 0,
+            a - value - carry < 0,
+        ),
+        AluOp::And => (a & value, false, true, false),
+        AluOp::Xor => (a ^ value, false, false, false),
+        AluOp::Or => (a | value, false, false, false),
+    };
+    let result = result as u8;
+    let f = flags(result == 0, n, h, c);
+    match op {
+        AluOp::Compare => (a as u8, f),
+        _ => (result, f),
+    }
+}
+
+/// The value and F after `op`, for the CB versions
+fn reference_bitwise(op: BitwiseOp, value: u8, carry: bool) -> (u8, u8) {
+    let (result, c) = match op {
+        BitwiseOp::RotateLeftCarry => (value.rotate_left(1), value & 0x80 != 0),
+        BitwiseOp::RotateRightCarry => (value.rotate_right(1), value & 1 != 0),
+        BitwiseOp::RotateLeft => (value << 1 | carry as u8, value & 0x80 != 0),
+        BitwiseOp::RotateRight => (value >> 1 | (carry as u8) << 7, value & 1 != 0),
+        BitwiseOp::ShiftLeftArithmetic => (value << 1, value & 0x80 != 0),
+        BitwiseOp::ShiftRightArithmetic => (value >> 1 | value & 0x80, value & 1 != 0),
+        BitwiseOp::Swap => (value.rotate_left(4), false),
+        BitwiseOp::ShiftRightLogical => (value >> 1, value & 1 != 0),
+    };
+    (result, flags(result == 0, false, false, c))
+}
+
+/// A and F after DAA
+fn reference_daa(a: u8, f: u8) -> (u8, u8) {
+    let (n, h, mut c) = (f & N != 0, f & H != 0, f & C != 0);
+    let mut a = a;
+    if !n {
+        if c || a > 0x99 {
+            a = a.wrapping_add(0x60);
+            c = true;
+        }
+        if h || a & 0x0F > 0x09 {
+            a = a.wrapping_add(0x06);
+        }
+    } else {
+        if c {
+            a = a.wrapping_sub(0x60);
+        }
+        if h {
+            a = a.wrapping_sub(0x06);
+        }
+    }
+    (a, flags(a == 0, n, false, c))
+}
+
+fn run(cpu: &mut CPU, memory_bus: &mut MemoryBus, instruction: Instruction) {
+    alu::handle_instruction(cpu, instruction, memory_bus).expect("not an ALU instruction");
+}
+
+const OPS: [AluOp; 8] = [
+    AluOp::Add,
+    AluOp::AddWithCarry,
+    AluOp::Subtract,
+    AluOp::SubtractWithCarry,
+    AluOp::And,
+    AluOp::Xor,
+    AluOp::Or,
+    AluOp::Compare,
+];
+
+const BITWISE_OPS: [BitwiseOp; 8] = [
+    BitwiseOp::RotateLeftCarry,
+    BitwiseOp::RotateRightCarry,
+    BitwiseOp::RotateLeft,
+    BitwiseOp::RotateRight,
+    BitwiseOp::ShiftLeftArithmetic,
+    BitwiseOp::ShiftRightArithmetic,
+    BitwiseOp::Swap,
+    BitwiseOp::ShiftRightLogical,
+];
+
+#[test]
+fn alu_ops_match_the_reference() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    for op in OPS {
+        for a in 0..=0xFF {
+            for value in 0..=0xFF {
+                // The other flags are only ever outputs
+                for f in [0x00, 0xF0] {
+                    let mut cpu = CPU {
+                        Accumulator: a,
+                        B: value,
+                        Flags: f,
+                        ..CPU::default()
+                    };
+                    run(
+                        &mut cpu,
+                        &mut memory_bus,
+                        Instruction::Alu(op, Register8::B),
+                    );
+                    assert_eq!(
+                        (cpu.Accumulator, cpu.Flags),
+                        reference_alu(op, a, value, f & C != 0),
+                        "{:?} {:02X} {:02X} with flags {:02X}",
+                        op,
+                        a,
+                        value,
+                        f
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn bitwise_ops_match_the_reference() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    for op in BITWISE_OPS {
+        for value in 0..=0xFF {
+            for f in [0x00, 0xF0] {
+                let mut cpu = CPU {
+                    B: value,
+                    Flags: f,
+                    ..CPU::default()
+                };
+                run(
+                    &mut cpu,
+                    &mut memory_bus,
+                    Instruction::Bitwise(op, Register8::B),
+                );
+                assert_eq!(
+                    (cpu.B, cpu.Flags),
+                    reference_bitwise(op, value, f & C != 0),
+                    "{:?} {:02X} with flags {:02X}",
+                    op,
+                    value,
+                    f
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn accumulator_rotates_always_clear_z() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    let ops = [
+        (
+            AccumulatorFlagOp::RotateLeftCarryA,
+            BitwiseOp::RotateLeftCarry,
+        ),
+        (
+            AccumulatorFlagOp::RotateRightCarryA,
+            BitwiseOp::RotateRightCarry,
+        ),
+        (AccumulatorFlagOp::RotateLeftA, BitwiseOp::RotateLeft),
+        (AccumulatorFlagOp::RotateRightA, BitwiseOp::RotateRight),
+    ];
+    for (op, prefixed) in ops {
+        for a in 0..=0xFF {
+            for f in [0x00, 0xF0] {
+                let mut cpu = CPU {
+                    Accumulator: a,
+                    Flags: f,
+                    ..CPU::default()
+                };
+                run(&mut cpu, &mut memory_bus, Instruction::AccumulatorFlag(op));
+                let (result, flags) = reference_bitwise(prefixed, a, f & C != 0);
+                assert_eq!(
+                    (cpu.Accumulator, cpu.Flags),
+                    (result, flags & !Z),
+                    "{:?} {:02X}",
+                    op,
+                    a
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn increment_and_decrement_match_the_reference() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    for value in 0..=0xFF {
+        for f in (0..0x10).map(|flags| flags << 4) {
+            let mut cpu = CPU {
+                B: value,
+                Flags: f,
+                ..CPU::default()
+            };
+            run(
+                &mut cpu,
+                &mut memory_bus,
+                Instruction::Increment(Register8::B),
+            );
+            let result = value.wrapping_add(1);
+            let expected = flags(result == 0, false, value & 0xF == 0xF, f & C != 0);
+            assert_eq!((cpu.B, cpu.Flags), (result, expected), "INC {:02X}", value);
+
+            cpu = CPU {
+                B: value,
+                Flags: f,
+                ..CPU::default()
+            };
+            run(
+                &mut cpu,
+                &mut memory_bus,
+                Instruction::Decrement(Register8::B),
+            );
+            let result = value.wrapping_sub(1);
+            let expected = flags(result == 0, true, value & 0xF == 0, f & C != 0);
+            assert_eq!((cpu.B, cpu.Flags), (result, expected), "DEC {:02X}", value);
+        }
+    }
+}
+
+#[test]
+fn daa_matches_the_reference() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    for a in 0..=0xFF {
+        for f in (0..0x10).map(|flags| flags << 4) {
+            let mut cpu = CPU {
+                Accumulator: a,
+                Flags: f,
+                ..CPU::default()
+            };
+            run(
+                &mut cpu,
+                &mut memory_bus,
+                Instruction::AccumulatorFlag(AccumulatorFlagOp::DecimalAdjustAfterAddition),
+            );
+            assert_eq!(
+                (cpu.Accumulator, cpu.Flags),
+                reference_daa(a, f),
+                "DAA {:02X} with flags {:02X}",
+                a,
+                f
+            );
+        }
+    }
+}
+
+#[test]
+fn bit_set_and_reset_match_the_reference() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    for bit in 0..8 {
+        for value in 0..=0xFF {
+            for f in [0x00, 0xF0] {
+                let mut cpu = CPU {
+                    B: value,
+                    Flags: f,
+                    ..CPU::default()
+                };
+                run(
+                    &mut cpu,
+                    &mut memory_bus,
+                    Instruction::Bit(bit, Register8::B),
+                );
+                let z = value & 1 << bit == 0;
+                assert_eq!(
+                    cpu.Flags,
+                    flags(z, false, true, f & C != 0),
+                    "BIT {} {:02X}",
+                    bit,
+                    value
+                );
+
+                run(
+                    &mut cpu,
+                    &mut memory_bus,
+                    Instruction::SetBit(bit, Register8::B),
+                );
+                assert_eq!(cpu.B, value | 1 << bit);
+                run(
+                    &mut cpu,
+                    &mut memory_bus,
+                    Instruction::ResetBit(bit, Register8::B),
+                );
+                assert_eq!(cpu.B, value & !(1 << bit));
+                // Neither touches the flags
+                assert_eq!(cpu.Flags, flags(z, false, true, f & C != 0));
+            }
+        }
+    }
+}
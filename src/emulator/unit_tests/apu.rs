@@ -0,0 +1,268 @@
+use crate::emulator::{
+    apu::{
+        scope::SAMPLES, view::ApuView, Apu, NR10, NR11, NR12, NR13, NR14, NR21, NR30, NR31, NR33,
+        NR34, NR41, NR43, NR50, NR52,
+    },
+    hardware::HardwareModel,
+    memory_bus::{mmio::MmioDevice, MemoryBus},
+};
+
+const NR22: u16 = 0xFF17;
+const NR23: u16 = 0xFF18;
+const NR24: u16 = 0xFF19;
+const NR42: u16 = 0xFF21;
+const NR44: u16 = 0xFF23;
+
+fn powered_on(model: HardwareModel) -> Apu {
+    let mut apu = Apu::default();
+    apu.set_model(model);
+    apu.write(NR52, 0x80);
+    apu
+}
+
+/// Wave RAM with each byte its own offset, playing at 32 T-cycles a sample
+fn playing(model: HardwareModel) -> Apu {
+    let mut apu = powered_on(model);
+    for offset in 0..16 {
+        apu.write(0xFF30 + offset, offset as u8 * 0x11);
+    }
+    apu.write(NR30, 0x80);
+    apu.write(NR33, 0xF0);
+    apu.write(NR34, 0x87);
+    apu
+}
+
+#[test]
+fn registers_read_back_with_unused_bits_set() {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    bus.write_u8(NR52, 0x8F);
+    assert_eq!(bus.read_u8(NR52), 0xF0);
+    assert_eq!(bus.read_u8(NR30), 0x7F);
+    bus.write_u8(NR31, 0x12);
+    assert_eq!(bus.read_u8(NR31), 0xFF);
+    bus.write_u8(NR11, 0x12);
+    assert_eq!(bus.read_u8(NR11), 0x3F);
+    bus.write_u8(NR50, 0x77);
+    assert_eq!(bus.read_u8(NR50), 0x77);
+    assert_eq!(bus.read_u8(0xFF27), 0xFF);
+}
+
+#[test]
+fn booting_leaves_channel_1_on() {
+    let mut apu = Apu::default();
+    apu.boot(HardwareModel::Dmg);
+    assert_eq!(apu.read(NR52), 0x81);
+    assert_eq!(apu.read(NR50), 0x77);
+    let mut sgb = Apu::default();
+    sgb.boot(HardwareModel::Sgb);
+    assert_eq!(sgb.read(NR52), 0x80);
+}
+
+#[test]
+fn powering_off_clears_registers_and_ignores_writes() {
+    let mut apu = playing(HardwareModel::Dmg);
+    apu.write(NR50, 0x77);
+    apu.write(NR41, 0x3F);
+    apu.write(NR52, 0x00);
+    assert_eq!(apu.read(NR52), 0x00);
+    assert_eq!(apu.read(NR50), 0x00);
+    assert_eq!(apu.read(NR30), 0x00);
+    // Wave RAM stays, and can still be written
+    assert_eq!(apu.read(0xFF31), 0x11);
+    apu.write(0xFF31, 0x99);
+    assert_eq!(apu.read(0xFF31), 0x99);
+
+    apu.write(NR50, 0x77);
+    apu.write(NR12, 0xF0);
+    apu.write(NR14, 0x80);
+    assert_eq!(apu.read(NR50), 0x00);
+    assert_eq!(apu.read(NR52), 0x00);
+    // Except for the DMG's length counters, which powering off doesn't touch either
+    assert_eq!(apu.length(4).remaining(), 1);
+    apu.write(NR11, 0xFE);
+    assert_eq!(apu.length(1).remaining(), 2);
+    assert_eq!(apu.read(NR11), 0x00);
+    apu.write(NR52, 0x80);
+    apu.write(NR50, 0x77);
+    assert_eq!(apu.read(NR50), 0x77);
+}
+
+#[test]
+fn color_models_clear_lengths_and_ignore_length_writes_when_off() {
+    let mut apu = powered_on(HardwareModel::Cgb);
+    apu.write(NR21, 0x3F);
+    apu.write(NR52, 0x00);
+    assert_eq!(apu.length(2).remaining(), 0);
+    apu.write(NR21, 0x3E);
+    assert_eq!(apu.length(2).remaining(), 0);
+}
+
+#[test]
+fn dmg_wave_ram_is_only_open_while_the_channel_reads_it() {
+    let mut apu = playing(HardwareModel::Dmg);
+    assert_eq!(apu.read(NR52) & 0b100, 0b100);
+    // Triggering doesn't read anything
+    assert_eq!(apu.read(0xFF35), 0xFF);
+
+    // The first step reads the byte with samples 0 and 1
+    apu.tick(32 + 6);
+    assert_eq!(apu.wave().position(), 1);
+    assert_eq!(apu.read(0xFF35), 0x00);
+    apu.tick(4);
+    assert_eq!(apu.read(0xFF35), 0xFF);
+    apu.write(0xFF35, 0xAB);
+
+    apu.tick(28);
+    assert_eq!(apu.wave().position(), 2);
+    assert_eq!(apu.read(0xFF3F), 0x11);
+    apu.write(0xFF3F, 0xCD);
+
+    // Once it stops every byte is where it was, except the one written in time
+    apu.write(NR30, 0x00);
+    assert_eq!(apu.read(NR52) & 0b100, 0);
+    assert_eq!(apu.read(0xFF30), 0x00);
+    assert_eq!(apu.read(0xFF31), 0xCD);
+    assert_eq!(apu.read(0xFF35), 0x55);
+}
+
+#[test]
+fn cgb_wave_ram_always_gives_the_playing_byte() {
+    let mut apu = playing(HardwareModel::Cgb);
+    apu.tick(32 + 6 + 32 + 10);
+    assert_eq!(apu.wave().position(), 2);
+    assert_eq!(apu.wave().output(), 0x1);
+    assert_eq!(apu.read(0xFF30), 0x11);
+    apu.write(0xFF30, 0xEF);
+    assert_eq!(apu.wave().ram()[1], 0xEF);
+}
+
+#[test]
+fn length_counters_turn_channels_off() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    apu.write(NR12, 0xF0);
+    apu.write(NR11, 0x3E);
+    apu.write(NR14, 0xC0);
+    assert_eq!(apu.read(NR52) & 1, 1);
+    apu.step_frame_sequencer();
+    assert_eq!(apu.length(1).remaining(), 1);
+    apu.step_frame_sequencer();
+    assert_eq!(apu.read(NR52) & 1, 1);
+    apu.step_frame_sequencer();
+    assert_eq!(apu.read(NR52) & 1, 0);
+}
+
+#[test]
+fn enabling_length_before_a_step_that_skips_it_clocks_it() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    // The next step doesn't clock length
+    apu.step_frame_sequencer();
+    apu.write(NR22, 0xF0);
+    apu.write(NR21, 0x3F);
+    apu.write(NR24, 0x80);
+    assert_eq!(apu.read(NR52) & 0b10, 0b10);
+    apu.write(NR24, 0x40);
+    assert_eq!(apu.read(NR52) & 0b10, 0);
+
+    // Triggering with length at 0 reloads it, minus the extra clock
+    apu.write(NR24, 0xC0);
+    assert_eq!(apu.length(2).remaining(), 63);
+    assert_eq!(apu.read(NR52) & 0b10, 0b10);
+}
+
+#[test]
+fn envelopes_step_on_step_7() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    apu.write(NR12, 0xF1);
+    apu.write(NR14, 0x80);
+    for _ in 0..7 {
+        apu.step_frame_sequencer();
+    }
+    assert_eq!(apu.pulse(1).envelope().volume(), 15);
+    apu.step_frame_sequencer();
+    assert_eq!(apu.pulse(1).envelope().volume(), 14);
+}
+
+#[test]
+fn sweep_changes_the_frequency_until_it_overflows() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    apu.write(NR12, 0xF0);
+    // Up by a quarter every step
+    apu.write(NR10, 0x12);
+    apu.write(NR13, 0x00);
+    apu.write(NR14, 0x84);
+    for _ in 0..3 {
+        apu.step_frame_sequencer();
+    }
+    // 0x400 + 0x100
+    assert_eq!(apu.read(NR52) & 1, 1);
+    assert_eq!(apu.frequency(1), 0x500);
+    for _ in 0..4 {
+        apu.step_frame_sequencer();
+    }
+    assert_eq!(apu.frequency(1), 0x640);
+    // 0x7D0 next, but the check after it sees 0x9C4
+    for _ in 0..4 {
+        apu.step_frame_sequencer();
+    }
+    assert_eq!(apu.read(NR52) & 1, 0);
+
+    // Going past 2047 on the trigger turns it straight back off
+    apu.write(NR10, 0x11);
+    apu.write(NR14, 0x87);
+    assert_eq!(apu.read(NR52) & 1, 0);
+}
+
+#[test]
+fn scope_records_the_duty_cycle() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    apu.set_scope(true);
+    // Half high, stepping every 1024 T-cycles, so a cycle is 128 samples
+    apu.write(NR21, 0x80);
+    apu.write(NR22, 0xF0);
+    apu.write(NR23, 0x00);
+    apu.write(NR24, 0x87);
+    for _ in 0..SAMPLES * 16 {
+        apu.tick(4);
+    }
+    let samples: Vec<u8> = apu.scope().unwrap().samples(2).collect();
+    assert_eq!(
+        samples.iter().filter(|&&sample| sample == 15).count(),
+        SAMPLES / 2
+    );
+    assert!(samples.iter().all(|&sample| sample == 0 || sample == 15));
+    assert!(apu.scope().unwrap().samples(1).all(|sample| sample == 0));
+}
+
+#[test]
+fn short_noise_repeats_every_127_steps() {
+    let mut apu = powered_on(HardwareModel::Dmg);
+    apu.write(NR42, 0xF0);
+    // Stepping every 8 T-cycles in 7-bit mode
+    apu.write(NR43, 0x08);
+    apu.write(NR44, 0x80);
+    let outputs: Vec<u8> = (0..254)
+        .map(|_| {
+            apu.tick(8);
+            apu.output(4)
+        })
+        .collect();
+    assert_eq!(outputs[..127], outputs[127..]);
+    assert!(outputs.contains(&0) && outputs.contains(&15));
+}
+
+#[test]
+fn view_shows_the_boot_sound() {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    bus.apu_mut().boot(HardwareModel::Dmg);
+    let view = ApuView::new(&bus);
+    let channel = &view.channels[0];
+    assert!(channel.on);
+    assert!((channel.hz - 131072.0 / 63.0).abs() < 0.01);
+    assert_eq!(channel.volume, 15);
+    let envelope = channel.envelope.unwrap();
+    assert!(!envelope.up());
+    assert_eq!(envelope.period(), 3);
+    assert!(channel.samples.is_empty());
+    assert!(!view.channels[1].on);
+    assert_eq!(view.registers[(NR52 - NR10) as usize], 0xF1);
+}
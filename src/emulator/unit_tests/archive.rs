@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::emulator::{
+    archive::{extract, inflate::inflate, ArchiveError},
+    png::crc32,
+};
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// What zlib made of [`fixed_data`] with fixed Huffman codes
+const FIXED: &str = "73f47075747274777276f670767571747573757673777771771c62e200";
+/// What zlib made of [`DYNAMIC_DATA`], with its own Huffman codes
+const DYNAMIC: &str = "2d8c810dc04008025771b503f79fa1f096442108223c0416a8f353961c7fc2756c79efc66\
+    a4a8e48a881eba1e5d2ad3bf2056ab1cf5bfa33f800";
+const DYNAMIC_DATA: &[u8] = b"abac aaaacbaabbaabaabbaabbbabcaaa bababbaccbcdaabbaaadb baaacadbab\
+    cbabaaabaababdaababbabbbacabadbabcababadabbbadacaaaaaa";
+
+fn fixed_data() -> Vec<u8> {
+    (0..200usize)
+        .map(|i| b"ABCDEFGH"[(i * i * 7 + i / 3) % 8])
+        .collect()
+}
+
+/// A ZIP file with `entries` stored as they are
+fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut file = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in entries {
+        let offset = file.len() as u32;
+        let mut fields = Vec::new();
+        // Version, flags, method, time and date
+        fields.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        fields.extend_from_slice(&crc32(&[data]).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // No extra field
+        fields.extend_from_slice(&[0, 0]);
+
+        file.extend_from_slice(b"PK\x03\x04");
+        file.extend_from_slice(&fields);
+        file.extend_from_slice(name.as_bytes());
+        file.extend_from_slice(data);
+
+        directory.extend_from_slice(b"PK\x01\x02\x14\x00");
+        directory.extend_from_slice(&fields);
+        // No comment, disk 0, no attributes
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let start = file.len() as u32;
+    let count = (entries.len() as u16).to_le_bytes();
+    file.extend_from_slice(&directory);
+    file.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    file.extend_from_slice(&count);
+    file.extend_from_slice(&count);
+    file.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    file.extend_from_slice(&start.to_le_bytes());
+    file.extend_from_slice(&[0, 0]);
+    file
+}
+
+#[test]
+fn inflates_every_block_type() {
+    let fixed = hex(FIXED);
+    assert_eq!(inflate(&fixed), Ok((fixed_data(), fixed.len())));
+    let dynamic = hex(DYNAMIC);
+    assert_eq!(
+        inflate(&dynamic),
+        Ok((DYNAMIC_DATA.to_vec(), dynamic.len()))
+    );
+    // A final stored block
+    assert_eq!(
+        inflate(b"\x01\x03\x00\xFC\xFFabc"),
+        Ok((b"abc".to_vec(), 8))
+    );
+    assert!(inflate(&fixed[..10]).is_err());
+}
+
+#[test]
+fn gunzips() {
+    let mut gz = vec![0x1F, 0x8B, 8, 0x08, 0, 0, 0, 0, 0, 3];
+    gz.extend_from_slice(b"game.gb\0");
+    gz.extend_from_slice(&hex(FIXED));
+    gz.extend_from_slice(&crc32(&[&fixed_data()]).to_le_bytes());
+    gz.extend_from_slice(&200u32.to_le_bytes());
+    assert_eq!(
+        extract(Path::new("game.gb.gz"), gz.clone()),
+        Ok(fixed_data())
+    );
+
+    let last = gz.len() - 5;
+    gz[last] ^= 1;
+    assert!(matches!(
+        extract(Path::new("game.gb.gz"), gz),
+        Err(ArchiveError::Corrupt(_))
+    ));
+}
+
+#[test]
+fn unzips_the_only_rom() {
+    let rom = zip(&[
+        ("readme.txt", b"hi"),
+        ("__MACOSX/._game.gb", b"metadata"),
+        ("Game (World).gb", b"rom"),
+    ]);
+    assert_eq!(extract(Path::new("game.zip"), rom), Ok(b"rom".to_vec()));
+
+    let none = zip(&[("readme.txt", b"hi")]);
+    assert_eq!(extract(Path::new("x.zip"), none), Err(ArchiveError::NoRom));
+    let several = zip(&[("a.gb", b"a"), ("b.GBC", b"b")]);
+    assert_eq!(
+        extract(Path::new("x.zip"), several),
+        Err(ArchiveError::SeveralRoms(vec![
+            "a.gb".into(),
+            "b.GBC".into()
+        ]))
+    );
+}
+
+#[test]
+fn leaves_roms_alone() {
+    let rom = vec![0x00, 0xC3, 0x50, 0x01];
+    assert_eq!(extract(Path::new("game.gb"), rom.clone()), Ok(rom));
+    assert!(extract(Path::new("game.zip"), b"not a zip".to_vec()).is_err());
+}
@@ -0,0 +1,229 @@
+//! A tiny assembler for writing test programs
+//!
+//! `asm![LD A, 0x12; ADD A, B; HALT]` is an array of [`Instruction`]s in RGBDS-like syntax. Every
+//! operand has to be a single token, so negative numbers go in parentheses (`JR (-2)`), and
+//! `LD HL, SP + e` is spelled `LDHL SP, e` like the old Sharp mnemonic. RST takes the address,
+//! `RST 0x38`. `Instruction` has to be in scope where it's used.
+use crate::emulator::{cpu::CPU, instructions::Instruction, memory_bus::MemoryBus};
+
+macro_rules! asm {
+    (@munch [$($done:expr,)*]) => { [$($done),*] };
+    (@munch [$($done:expr,)*] $mnemonic:ident $a:tt, $b:tt $(; $($rest:tt)*)?) => {
+        asm!(@munch [$($done,)* inst!($mnemonic $a, $b),] $($($rest)*)?)
+    };
+    (@munch [$($done:expr,)*] $mnemonic:ident $a:tt $(; $($rest:tt)*)?) => {
+        asm!(@munch [$($done,)* inst!($mnemonic $a),] $($($rest)*)?)
+    };
+    (@munch [$($done:expr,)*] $mnemonic:ident $(; $($rest:tt)*)?) => {
+        asm!(@munch [$($done,)* inst!($mnemonic),] $($($rest)*)?)
+    };
+    ($($program:tt)*) => { asm!(@munch [] $($program)*) };
+}
+
+macro_rules! inst {
+    (NOP) => { Instruction::Nop };
+    (STOP) => { Instruction::Stop };
+    (HALT) => { Instruction::Halt };
+    (DI) => { Instruction::DisableInterrupts };
+    (EI) => { Instruction::EnableInterrupts };
+    (RLCA) => { inst!(@flag RotateLeftCarryA) };
+    (RRCA) => { inst!(@flag RotateRightCarryA) };
+    (RLA) => { inst!(@flag RotateLeftA) };
+    (RRA) => { inst!(@flag RotateRightA) };
+    (DAA) => { inst!(@flag DecimalAdjustAfterAddition) };
+    (CPL) => { inst!(@flag ComplementAccumulator) };
+    (SCF) => { inst!(@flag SetCarryFlag) };
+    (CCF) => { inst!(@flag ComplementCarryFlag) };
+    (@flag $op:ident) => {
+        Instruction::AccumulatorFlag($crate::emulator::instructions::AccumulatorFlagOp::$op)
+    };
+
+    // Control flow
+    (JP HL) => { Instruction::JumpHL };
+    (JP $cc:ident, $addr:expr) => { Instruction::JumpConditional(cond!($cc), $addr) };
+    (JP $addr:expr) => { Instruction::Jump($addr) };
+    (JR $cc:ident, $offset:expr) => { Instruction::JumpRelativeConditional(cond!($cc), $offset) };
+    (JR $offset:expr) => { Instruction::JumpRelative($offset) };
+    (CALL $cc:ident, $addr:expr) => { Instruction::CallConditional(cond!($cc), $addr) };
+    (CALL $addr:expr) => { Instruction::Call($addr) };
+    (RET) => { Instruction::Ret };
+    (RET $cc:ident) => { Instruction::RetConditional(cond!($cc)) };
+    (RETI) => { Instruction::RetInterrupt };
+    (RST $addr:expr) => { Instruction::Reset(($addr as u8) >> 3) };
+
+    // 16 bit
+    (LD SP, HL) => { Instruction::LoadSPHL };
+    (LD BC, $value:expr) => { Instruction::LoadImmediate16(r16!(BC), $value) };
+    (LD DE, $value:expr) => { Instruction::LoadImmediate16(r16!(DE), $value) };
+    (LD HL, $value:expr) => { Instruction::LoadImmediate16(r16!(HL), $value) };
+    (LD SP, $value:expr) => { Instruction::LoadImmediate16(r16!(SP), $value) };
+    (LDHL SP, $offset:expr) => { Instruction::LoadHLSP($offset) };
+    (ADD HL, $rr:ident) => { Instruction::AddHLRegister(r16!($rr)) };
+    (ADD SP, $offset:expr) => { Instruction::AddSp($offset) };
+    (INC BC) => { Instruction::Increment16(r16!(BC)) };
+    (INC DE) => { Instruction::Increment16(r16!(DE)) };
+    (INC HL) => { Instruction::Increment16(r16!(HL)) };
+    (INC SP) => { Instruction::Increment16(r16!(SP)) };
+    (DEC BC) => { Instruction::Decrement16(r16!(BC)) };
+    (DEC DE) => { Instruction::Decrement16(r16!(DE)) };
+    (DEC HL) => { Instruction::Decrement16(r16!(HL)) };
+    (DEC SP) => { Instruction::Decrement16(r16!(SP)) };
+    (PUSH $rr:ident) => {
+        Instruction::Push($crate::emulator::instructions::Register16Stack::$rr)
+    };
+    (POP $rr:ident) => {
+        Instruction::Pop($crate::emulator::instructions::Register16Stack::$rr)
+    };
+
+    // Loads through memory
+    (LD [BC], A) => { Instruction::LoadIndirectA(indirect!(BC)) };
+    (LD [DE], A) => { Instruction::LoadIndirectA(indirect!(DE)) };
+    (LD [HL+], A) => { Instruction::LoadIndirectA(indirect!(HLI)) };
+    (LD [HL-], A) => { Instruction::LoadIndirectA(indirect!(HLD)) };
+    (LD A, [BC]) => { Instruction::LoadAIndirect(indirect!(BC)) };
+    (LD A, [DE]) => { Instruction::LoadAIndirect(indirect!(DE)) };
+    (LD A, [HL+]) => { Instruction::LoadAIndirect(indirect!(HLI)) };
+    (LD A, [HL-]) => { Instruction::LoadAIndirect(indirect!(HLD)) };
+    (LDH [C], A) => { Instruction::LoadHighPageIndirectA };
+    (LDH A, [C]) => { Instruction::LoadAHighPageIndirect };
+    (LDH [$offset:expr], A) => { Instruction::LoadHighPageAImmediate($offset) };
+    (LDH A, [$offset:expr]) => { Instruction::LoadAHighPageImmediate($offset) };
+    (LD [HL], $src:tt) => { inst!(@ld [HL], $src) };
+    (LD A, [HL]) => { inst!(@ld A, [HL]) };
+    (LD [$addr:expr], A) => { Instruction::LoadIndirectImmediateA($addr) };
+    (LD [$addr:expr], SP) => { Instruction::LoadIndirectSP($addr) };
+    (LD A, [$addr:expr]) => { Instruction::LoadAIndirectImmediate($addr) };
+    (LD $dst:tt, $src:tt) => { inst!(@ld $dst, $src) };
+    (@ld $dst:tt, A) => { Instruction::Load(r8!($dst), r8!(A)) };
+    (@ld $dst:tt, B) => { Instruction::Load(r8!($dst), r8!(B)) };
+    (@ld $dst:tt, C) => { Instruction::Load(r8!($dst), r8!(C)) };
+    (@ld $dst:tt, D) => { Instruction::Load(r8!($dst), r8!(D)) };
+    (@ld $dst:tt, E) => { Instruction::Load(r8!($dst), r8!(E)) };
+    (@ld $dst:tt, H) => { Instruction::Load(r8!($dst), r8!(H)) };
+    (@ld $dst:tt, L) => { Instruction::Load(r8!($dst), r8!(L)) };
+    (@ld $dst:tt, [HL]) => { Instruction::Load(r8!($dst), r8!([HL])) };
+    (@ld $dst:tt, $value:expr) => { Instruction::LoadImmediate(r8!($dst), $value) };
+
+    // 8 bit arithmetic
+    (INC $r:tt) => { Instruction::Increment(r8!($r)) };
+    (DEC $r:tt) => { Instruction::Decrement(r8!($r)) };
+    (ADD A, $src:tt) => { inst!(@alu Add, $src) };
+    (ADC A, $src:tt) => { inst!(@alu AddWithCarry, $src) };
+    (SUB A, $src:tt) => { inst!(@alu Subtract, $src) };
+    (SBC A, $src:tt) => { inst!(@alu SubtractWithCarry, $src) };
+    (AND A, $src:tt) => { inst!(@alu And, $src) };
+    (XOR A, $src:tt) => { inst!(@alu Xor, $src) };
+    (OR A, $src:tt) => { inst!(@alu Or, $src) };
+    (CP A, $src:tt) => { inst!(@alu Compare, $src) };
+    (@alu $op:ident, A) => { Instruction::Alu(alu!($op), r8!(A)) };
+    (@alu $op:ident, B) => { Instruction::Alu(alu!($op), r8!(B)) };
+    (@alu $op:ident, C) => { Instruction::Alu(alu!($op), r8!(C)) };
+    (@alu $op:ident, D) => { Instruction::Alu(alu!($op), r8!(D)) };
+    (@alu $op:ident, E) => { Instruction::Alu(alu!($op), r8!(E)) };
+    (@alu $op:ident, H) => { Instruction::Alu(alu!($op), r8!(H)) };
+    (@alu $op:ident, L) => { Instruction::Alu(alu!($op), r8!(L)) };
+    (@alu $op:ident, [HL]) => { Instruction::Alu(alu!($op), r8!([HL])) };
+    (@alu $op:ident, $value:expr) => { Instruction::AluImmediate(alu!($op), $value) };
+
+    // CB prefixed
+    (RLC $r:tt) => { inst!(@bitwise RotateLeftCarry, $r) };
+    (RRC $r:tt) => { inst!(@bitwise RotateRightCarry, $r) };
+    (RL $r:tt) => { inst!(@bitwise RotateLeft, $r) };
+    (RR $r:tt) => { inst!(@bitwise RotateRight, $r) };
+    (SLA $r:tt) => { inst!(@bitwise ShiftLeftArithmetic, $r) };
+    (SRA $r:tt) => { inst!(@bitwise ShiftRightArithmetic, $r) };
+    (SWAP $r:tt) => { inst!(@bitwise Swap, $r) };
+    (SRL $r:tt) => { inst!(@bitwise ShiftRightLogical, $r) };
+    (@bitwise $op:ident, $r:tt) => {
+        Instruction::Bitwise($crate::emulator::instructions::BitwiseOp::$op, r8!($r))
+    };
+    (BIT $bit:expr, $r:tt) => { Instruction::Bit($bit, r8!($r)) };
+    (RES $bit:expr, $r:tt) => { Instruction::ResetBit($bit, r8!($r)) };
+    (SET $bit:expr, $r:tt) => { Instruction::SetBit($bit, r8!($r)) };
+}
+
+macro_rules! r8 {
+    ([HL]) => {
+        $crate::emulator::instructions::Register8::IndirectHL
+    };
+    ($r:ident) => {
+        $crate::emulator::instructions::Register8::$r
+    };
+}
+
+macro_rules! r16 {
+    ($rr:ident) => {
+        $crate::emulator::instructions::Register16::$rr
+    };
+}
+
+macro_rules! indirect {
+    ($rr:ident) => {
+        $crate::emulator::instructions::Register16Indirect::$rr
+    };
+}
+
+macro_rules! cond {
+    ($cc:ident) => {
+        $crate::emulator::instructions::Condition::$cc
+    };
+}
+
+macro_rules! alu {
+    ($op:ident) => {
+        $crate::emulator::instructions::AluOp::$op
+    };
+}
+
+/// A 32K ROM image with each piece of code at its address, for [`Emulator::new`](crate::emulator::Emulator::new)
+pub fn rom(code: &[(u16, &[Instruction])]) -> Vec<u8> {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    for &(addr, instructions) in code {
+        bus.patch(addr, instructions);
+    }
+    bus.rom().to_vec()
+}
+
+/// Where [`Machine::new`] puts the program
+pub const START: u16 = 0x100;
+
+/// A CPU and a bus with a program in an otherwise empty ROM
+pub struct Machine {
+    pub cpu: CPU,
+    pub bus: MemoryBus,
+}
+
+impl Machine {
+    /// `program` at [`START`], with the CPU about to run it and SP at the top of WRAM
+    pub fn new(program: &[Instruction]) -> Self {
+        let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+        bus.patch(START, program);
+        let cpu = CPU {
+            SP: 0xE000,
+            ..CPU::default()
+        };
+        Self { cpu, bus }
+    }
+
+    /// Also copies `code` to `addr`, for things to jump to
+    pub fn with(mut self, addr: u16, code: &[Instruction]) -> Self {
+        self.bus.patch(addr, code);
+        self
+    }
+
+    /// Runs until the CPU halts, returning how many M-cycles that took. The HALT itself
+    /// counts.
+    pub fn run(&mut self) -> u32 {
+        let mut cycles = 0;
+        for _ in 0..10_000 {
+            cycles += self.cpu.tick(&mut self.bus);
+            if let Some(fault) = self.bus.take_fault() {
+                panic!("{} at {:#06X}", fault, self.cpu.PC);
+            }
+            if self.cpu.halted {
+                return cycles;
+            }
+        }
+        panic!("Never halted, PC is {:#06X}", self.cpu.PC);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::emulator::{
+    cpu::backend::Backend,
+    memory_bus::written_pages::{PageSet, PAGE_SIZE},
+    Emulator,
+};
+
+/// Writes `LD A, B; RET` to 0xC000 and calls it, then makes it `LD A, C` and calls it again
+fn self_modifying_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    #[rustfmt::skip]
+    let code = [
+        0x06, 0x11, 0x0E, 0x22, // LD B, $11, LD C, $22
+        0x21, 0x00, 0xC0, // LD HL, $C000
+        0x36, 0x78, 0x23, 0x36, 0xC9, // LD [HL], $78 (LD A, B), INC HL, LD [HL], $C9 (RET)
+        0xCD, 0x00, 0xC0, // CALL $C000
+        0x57, // LD D, A
+        0x3E, 0x79, 0xEA, 0x00, 0xC0, // LD A, $79 (LD A, C), LD [$C000], A
+        0xCD, 0x00, 0xC0, // CALL $C000
+        0x5F, // LD E, A
+        0x18, 0xFE, // JR -2
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom
+}
+
+fn cached(rom: &[u8]) -> Emulator {
+    let mut emulator = Emulator::new(rom);
+    emulator.set_backend(Backend::Cached);
+    emulator
+}
+
+#[test]
+fn reruns_code_after_it_changes() {
+    let mut emulator = cached(&self_modifying_rom());
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu().D, 0x11);
+    assert_eq!(emulator.cpu().E, 0x22);
+}
+
+#[test]
+fn runs_the_same_as_the_interpreter() {
+    let mut interpreted = Emulator::new(&self_modifying_rom());
+    let mut cached = cached(&self_modifying_rom());
+    for _ in 0..200 {
+        interpreted.step().unwrap();
+        cached.step().unwrap();
+        assert_eq!(interpreted.cpu().PC, cached.cpu().PC);
+        assert_eq!(interpreted.cpu().get_af(), cached.cpu().get_af());
+        assert_eq!(interpreted.cpu().get_de(), cached.cpu().get_de());
+    }
+}
+
+#[test]
+fn switching_back_keeps_the_registers() {
+    let mut emulator = cached(&self_modifying_rom());
+    emulator.run_frame().unwrap();
+    emulator.set_backend(Backend::Interpreter);
+    assert_eq!(emulator.backend(), Some(Backend::Interpreter));
+    assert_eq!(emulator.cpu().D, 0x11);
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cpu().E, 0x22);
+}
+
+#[test]
+fn page_set_ranges_cover_whole_pages() {
+    let mut pages = PageSet::EMPTY;
+    pages.insert_range(PAGE_SIZE - 1, PAGE_SIZE);
+    assert!(pages.contains(0));
+    assert!(pages.contains(2 * PAGE_SIZE - 1));
+    assert!(!pages.contains(2 * PAGE_SIZE));
+
+    let mut other = PageSet::EMPTY;
+    other.insert(0xFFFF);
+    assert!(!pages.overlaps(&other));
+    pages.extend(&other);
+    assert!(pages.overlaps(&other));
+}
@@ -0,0 +1,187 @@
+use crate::emulator::{
+    boot::{open_boot_rom, Boot},
+    hardware::HardwareModel,
+    instructions::Instruction,
+    memory_bus::{MemoryBus, BOOT, LCDC, SCROLL_Y},
+    rom::{self, NINTENDO_LOGO},
+    Emulator,
+};
+
+/// A cartridge the boot ROM is happy with, spinning at the entry point
+fn good_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+    rom[0x134..0x138].copy_from_slice(b"BOOT");
+    rom[0x14D] = rom::header_checksum(&rom).unwrap();
+    rom
+}
+
+/// Stands in for a CGB dump: sets the first background color to red and puts a byte in VRAM
+/// bank 1, from the part past the header, then sets KEY0 to `key0` and hands over
+fn cgb_boot_rom(key0: u8) -> Vec<u8> {
+    let mut rom = vec![0; 0x900];
+    let setup = asm![
+        LD A, 0x80; LDH [0x68], A; LD A, 0x1F; LDH [0x69], A; XOR A, A; LDH [0x69], A;
+        LD A, 1; LDH [0x4F], A; LD A, 0x42; LD [0x8000], A;
+        LD A, key0; LDH [0x4C], A; JP 0x00FC
+    ];
+    for (addr, code) in [
+        (0x0000, &asm![JP 0x0200][..]),
+        (0x0200, &setup[..]),
+        (0x00FC, &asm![LD A, 0x11; LDH [0x50], A][..]),
+    ] {
+        let bytes: Vec<u8> = code.iter().flat_map(Instruction::encode).collect();
+        rom[addr..addr + bytes.len()].copy_from_slice(&bytes);
+    }
+    rom
+}
+
+/// Runs frames until the CPU gets to `pc` or `frames` have gone by, returning how many ran.
+/// Only checks between frames, so `pc` has to be somewhere it spins.
+fn run_to(emulator: &mut Emulator, pc: u16, frames: u32) -> u32 {
+    let mut done = 0;
+    while emulator.cpu().PC != pc && done < frames {
+        emulator.run_frame().unwrap();
+        done += 1;
+    }
+    done
+}
+
+#[test]
+fn open_boot_rom_hands_over_like_the_real_one() {
+    for model in [HardwareModel::Dmg, HardwareModel::Sgb] {
+        let rom = good_rom();
+        let mut emulator = Emulator::new(&rom);
+        emulator.set_model(model);
+        emulator.set_boot_rom(open_boot_rom(model));
+        assert_eq!(emulator.cpu().PC, 0);
+        assert_eq!(emulator.memory_bus().peek(LCDC), 0);
+
+        let frames = run_to(&mut emulator, 0x100, 600);
+        // The logo takes a couple of seconds to scroll down
+        assert!((200..600).contains(&frames), "{} frames", frames);
+        let cpu = emulator.cpu();
+        let expected = model.post_boot_registers(rom[0x14D]);
+        assert_eq!(
+            [
+                cpu.Accumulator,
+                cpu.Flags,
+                cpu.B,
+                cpu.C,
+                cpu.D,
+                cpu.E,
+                cpu.H,
+                cpu.L
+            ],
+            [
+                expected.a, expected.f, expected.b, expected.c, expected.d, expected.e, expected.h,
+                expected.l
+            ],
+            "{}",
+            model
+        );
+        assert_eq!(cpu.SP, 0xFFFE);
+        let bus = emulator.memory_bus();
+        assert!(!bus.boot_rom_mapped());
+        assert_eq!(bus.peek(0x0000), 0);
+        assert_eq!([bus.peek(LCDC), bus.peek(SCROLL_Y)], [0x91, 0]);
+        // The logo's first tile is in the map, the top of an N
+        assert_eq!(bus.peek(0x9904), 1);
+        assert_ne!(bus.peek(0x8010), 0);
+        assert!(bus.apu().powered());
+    }
+}
+
+#[test]
+fn open_boot_rom_locks_up_on_a_bad_header() {
+    let mut bad_logo = good_rom();
+    bad_logo[0x110] ^= 1;
+    let mut bad_checksum = good_rom();
+    bad_checksum[0x14D] ^= 1;
+    for rom in [bad_logo, bad_checksum] {
+        let mut emulator = Emulator::new(&rom);
+        emulator.set_watchdog(None);
+        emulator.set_boot_rom(open_boot_rom(HardwareModel::Dmg));
+        assert_eq!(run_to(&mut emulator, 0x100, 400), 400);
+        assert!(emulator.memory_bus().boot_rom_mapped());
+        assert_eq!(emulator.cpu().PC, 0x73);
+    }
+}
+
+#[test]
+fn boot_roms_are_mapped_until_ff50() {
+    let mut bus = MemoryBus::new(&good_rom()[..]);
+    bus.set_model(HardwareModel::Cgb);
+    assert!(!bus.map_boot_rom());
+    // A CGB one leaves a hole for the header
+    bus.set_boot_rom(Some(vec![0xAA; 0x900]));
+    assert!(bus.map_boot_rom());
+    for addr in [0x0000, 0x00FF, 0x0200, 0x08FF] {
+        assert_eq!(bus.peek(addr), 0xAA, "{:#06X}", addr);
+    }
+    assert_eq!(bus.peek(0x104), NINTENDO_LOGO[0]);
+    assert_eq!(bus.peek(0x900), 0);
+    // Only bit 0 counts
+    bus.write_u8(BOOT, 0xFE);
+    assert!(bus.boot_rom_mapped());
+    assert!(bus.take_written_pages().contains(0x0000));
+    bus.write_u8(BOOT, 0x11);
+    assert!(!bus.boot_rom_mapped());
+    assert!(bus.take_written_pages().contains(0x0000));
+    assert_eq!([bus.peek(0x0000), bus.peek(BOOT)], [0, 0xFF]);
+
+    // It's still there for the next power on
+    bus.reset();
+    assert!(bus.map_boot_rom());
+    assert_eq!(bus.peek(0x0000), 0xAA);
+}
+
+#[test]
+fn cgb_boot_rom_picks_the_mode() {
+    let mut cgb_game = good_rom();
+    cgb_game[0x143] = 0x80;
+    cgb_game[0x14D] = rom::header_checksum(&cgb_game).unwrap();
+    for (rom, key0, cgb_mode) in [(good_rom(), 0x04, false), (cgb_game, 0x80, true)] {
+        let mut emulator = Emulator::new(&rom);
+        emulator.set_model(HardwareModel::Cgb);
+        let mut dump = cgb_boot_rom(key0);
+        // A 2K dump without the hole
+        dump.drain(0x100..0x200);
+        let Ok(Boot::Dump(dump)) = Boot::dump(dump, HardwareModel::Cgb) else {
+            panic!("not a CGB boot ROM");
+        };
+        assert_eq!(dump, cgb_boot_rom(key0));
+        emulator.set_boot_rom(Some(dump));
+        assert!(emulator.memory_bus().cgb_mode());
+
+        run_to(&mut emulator, 0x100, 10);
+        let bus = emulator.memory_bus();
+        assert!(!bus.boot_rom_mapped());
+        assert_eq!(bus.cgb_mode(), cgb_mode);
+        // Left as the boot ROM set them, not the compatibility palette
+        assert_eq!(bus.palettes().background_color(0, 0), 0x001F);
+        assert_eq!(bus.vram(1, 0x8000), 0x42);
+        assert_eq!(bus.peek(0x8000), if cgb_mode { 0x42 } else { 0 });
+        // And drawn in them, DMG games included
+        emulator.memory_bus_mut().write_u8(LCDC, 0x91);
+        for _ in 0..2 {
+            emulator.run_frame().unwrap();
+        }
+        let colors = emulator.color_frame().unwrap();
+        assert_eq!([colors[0], colors[143 * 160 + 159]], [0x001F; 2]);
+    }
+}
+
+#[test]
+fn boot_picks_a_rom_for_the_model() {
+    assert_eq!(Boot::Skip.rom(HardwareModel::Dmg), None);
+    assert_eq!(Boot::Open.rom(HardwareModel::Cgb), None);
+    let open = Boot::Open.rom(HardwareModel::Dmg0).unwrap();
+    assert_eq!(open.len(), HardwareModel::Dmg0.boot_rom().size);
+    assert_eq!(&open[0xB0..0xE0], &NINTENDO_LOGO[..]);
+    assert_eq!(
+        Boot::Dump(vec![1, 2]).rom(HardwareModel::Cgb),
+        Some(vec![1, 2])
+    );
+}
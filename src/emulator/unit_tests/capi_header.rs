@@ -0,0 +1,21 @@
+//! The C header is written by hand, make sure it keeps up with the exports
+
+#[test]
+fn header_declares_every_export() {
+    let source = include_str!("../../capi.rs");
+    let header = include_str!("../../../include/gameboy_emulator.h");
+
+    let exports: Vec<&str> = source
+        .lines()
+        .filter_map(|line| line.split("extern \"C\" fn ").nth(1))
+        .map(|rest| rest.split('(').next().unwrap())
+        .collect();
+    assert!(!exports.is_empty());
+    for export in exports {
+        assert!(
+            header.contains(&format!(" *{}(", export)) || header.contains(&format!(" {}(", export)),
+            "{} is missing from gameboy_emulator.h",
+            export
+        );
+    }
+}
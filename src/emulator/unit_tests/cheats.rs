@@ -0,0 +1,154 @@
+use crate::emulator::{
+    cheats::{self, Cheat, CheatCode, CheatError, Cheats},
+    hardware::HardwareModel,
+    memory_bus::{MemoryBus, SVBK},
+};
+
+#[test]
+fn game_genie_decode() {
+    assert_eq!(
+        CheatCode::parse_game_genie("00A-17B-C49"),
+        Ok(CheatCode::GameGenie {
+            address: 0x4A17,
+            new_data: 0x00,
+            compare: Some(0xC8),
+        })
+    );
+}
+
+#[test]
+fn game_genie_short_form() {
+    assert_eq!(
+        CheatCode::parse_game_genie("3ef-13f"),
+        Ok(CheatCode::GameGenie {
+            address: 0x0F13,
+            new_data: 0x3E,
+            compare: None,
+        })
+    );
+}
+
+#[test]
+fn game_genie_bad_input() {
+    assert_eq!(
+        CheatCode::parse_game_genie("00A-17B-C4"),
+        Err(CheatError::BadLength(8))
+    );
+    assert_eq!(
+        CheatCode::parse_game_genie("00A-17B-C4X"),
+        Err(CheatError::BadDigit('X'))
+    );
+    // F = 7 puts the address at 0x8A17
+    assert_eq!(
+        CheatCode::parse_game_genie("00A-177"),
+        Err(CheatError::NotRom(0x8A17))
+    );
+}
+
+#[test]
+fn patch_rom_checks_compare_byte() {
+    let mut cheats = Cheats::default();
+    cheats.set(vec![
+        Cheat::parse("00A-17B-C49").unwrap(),
+        Cheat::parse("3EF-13F").unwrap(),
+    ]);
+
+    assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0x00);
+    assert_eq!(cheats.patch_rom(0x4A17, 0x12), 0x12);
+    assert_eq!(cheats.patch_rom(0x0F13, 0x12), 0x3E);
+    assert_eq!(cheats.patch_rom(0x0F14, 0x12), 0x12);
+}
+
+#[test]
+fn disabled_cheats_dont_patch() {
+    let mut cheat = Cheat::parse("3EF-13F").unwrap();
+    cheat.enabled = false;
+    let mut cheats = Cheats::default();
+    cheats.set(vec![cheat]);
+    assert_eq!(cheats.patch_rom(0x0F13, 0x12), 0x12);
+    assert_eq!(cheats.cheats().len(), 1);
+}
+
+#[test]
+fn game_shark_decode() {
+    assert_eq!(
+        CheatCode::parse("010238CD"),
+        Ok(CheatCode::GameShark {
+            bank: 0x01,
+            new_data: 0x02,
+            address: 0xCD38,
+        })
+    );
+    assert_eq!(
+        CheatCode::parse_game_shark("01020040"),
+        Err(CheatError::NotRam(0x4000))
+    );
+}
+
+#[test]
+fn game_shark_writes_ram() {
+    let mut memory_bus = MemoryBus::new([0; 0x8000].as_slice());
+    memory_bus.cheats_mut().set(vec![
+        Cheat::parse("010238CD").unwrap(),
+        Cheat::parse("01FF00D8").unwrap(),
+        // WRAM bank 3 doesn't exist on DMG
+        Cheat::parse("83AA01D8").unwrap(),
+    ]);
+    memory_bus.apply_ram_cheats();
+    assert_eq!(memory_bus.read_u8(0xCD38), 0x02);
+    assert_eq!(memory_bus.read_u8(0xD800), 0xFF);
+    assert_eq!(memory_bus.read_u8(0xD801), 0x00);
+
+    // It does in CGB mode, whichever is mapped
+    let mut rom = [0; 0x8000];
+    rom[0x143] = 0x80;
+    let mut memory_bus = MemoryBus::new(&rom[..]);
+    memory_bus.set_model(HardwareModel::Cgb);
+    memory_bus
+        .cheats_mut()
+        .set(vec![Cheat::parse("83AA01D8").unwrap()]);
+    memory_bus.apply_ram_cheats();
+    assert_eq!(memory_bus.read_u8(0xD801), 0x00);
+    memory_bus.write_u8(SVBK, 0x03);
+    assert_eq!(memory_bus.read_u8(0xD801), 0xAA);
+}
+
+#[test]
+fn game_shark_writes_the_named_cartridge_ram_bank() {
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0xFC;
+    let mut memory_bus = MemoryBus::new(&rom[..]);
+    memory_bus
+        .cheats_mut()
+        .set(vec![Cheat::parse("034223A1").unwrap()]);
+    // Even with RAM disabled and another bank mapped
+    memory_bus.apply_ram_cheats();
+    assert_eq!(memory_bus.battery().unwrap()[3 * 0x2000 + 0x123], 0x42);
+    memory_bus.write_u8(0x0000, 0x0A);
+    assert_eq!(memory_bus.read_u8(0xA123), 0x00);
+    memory_bus.write_u8(0x4000, 0x03);
+    assert_eq!(memory_bus.read_u8(0xA123), 0x42);
+
+    // Without cartridge RAM there's nothing to write
+    let mut memory_bus = MemoryBus::new([0; 0x8000].as_slice());
+    memory_bus
+        .cheats_mut()
+        .set(vec![Cheat::parse("034223A1").unwrap()]);
+    memory_bus.apply_ram_cheats();
+    assert_eq!(memory_bus.read_u8(0xA123), 0xFF);
+}
+
+#[test]
+fn cheat_file_roundtrip() {
+    let mut off = Cheat::parse("010238CD").unwrap();
+    off.enabled = false;
+    let list = vec![Cheat::parse("00a-17b-c49").unwrap(), off];
+
+    let contents = cheats::format_cheat_file(&list);
+    assert_eq!(contents, "00A-17B-C49 on\n010238CD off\n");
+    assert_eq!(cheats::parse_cheat_file(&contents), Ok(list));
+    assert_eq!(
+        cheats::parse_cheat_file("010238CD maybe"),
+        Err(CheatError::BadLine("010238CD maybe".to_string()))
+    );
+}
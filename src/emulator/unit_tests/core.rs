@@ -0,0 +1,508 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+};
+
+use crate::emulator::{
+    cpu::{backend::CpuCore, CPU},
+    error::EmulatorError,
+    frame_hash,
+    hardware::HardwareModel,
+    memory_bus::{mmio::MmioDevice, Interrupt, MemoryBus},
+    print_headless, run_headless, Emulator, HeadlessOutput, Options, GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
+};
+
+/// 32K of NOPs with `JR -2` at the entry point, so the CPU spins while the PPU runs
+fn spin_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100] = 0x18;
+    rom[0x101] = 0xFE;
+    rom
+}
+
+/// Sends `O` over serial once and spins
+fn serial_rom() -> Vec<u8> {
+    let mut rom = spin_rom();
+    // LD A,'O'; LDH (SB),A; LD A,0x81; LDH (SC),A; JR -2
+    rom[0x100..0x10A]
+        .copy_from_slice(&[0x3E, b'O', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]);
+    rom
+}
+
+#[test]
+fn step_reports_frames() {
+    let mut emulator = Emulator::new(&spin_rom());
+    let mut steps = 0;
+    while !emulator.step().unwrap() {
+        steps += 1;
+    }
+    assert!(steps > 0);
+
+    // JR takes 3 M-cycles and a frame is 70224 / 4 of them
+    let mut steps = 1;
+    while !emulator.step().unwrap() {
+        steps += 1;
+    }
+    assert_eq!(steps, (70224 / 4 + 2) / 3);
+    assert_eq!(
+        emulator.frame_buffer().len(),
+        GAMEBOY_WIDTH * GAMEBOY_HEIGHT
+    );
+}
+
+#[test]
+fn run_frame_stops_at_each_frame() {
+    let mut emulator = Emulator::new(&spin_rom());
+    emulator.run_frame().unwrap();
+
+    // Right after a frame, so the next one is a whole frame of stepping away
+    let mut steps = 1;
+    while !emulator.step().unwrap() {
+        steps += 1;
+    }
+    assert_eq!(steps, (70224 / 4 + 2) / 3);
+    emulator.run_frame().unwrap();
+    assert_eq!(
+        emulator.run_frame().unwrap().len(),
+        GAMEBOY_WIDTH * GAMEBOY_HEIGHT
+    );
+}
+
+#[test]
+fn frame_callback_gets_every_frame() {
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let mut emulator = Emulator::new(&spin_rom());
+    {
+        let frames = frames.clone();
+        emulator.set_frame_callback(move |frame| frames.lock().unwrap().push(frame_hash(frame)));
+    }
+    let expected: Vec<_> = (0..3)
+        .map(|_| frame_hash(emulator.run_frame().unwrap()))
+        .collect();
+    assert_eq!(*frames.lock().unwrap(), expected);
+
+    emulator.clear_frame_callback();
+    emulator.run_frame().unwrap();
+    assert_eq!(frames.lock().unwrap().len(), 3);
+}
+
+/// Enables LY=LYC and H-blank interrupts, logging LY to C000 onwards from the handler
+fn stat_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    #[rustfmt::skip]
+    let code = [
+        0x21, 0x00, 0xC0, // LD HL, $C000
+        0x3E, 0x48, 0xE0, 0x41, // LD A, $48, LDH [STAT], A
+        0x3E, 0x0A, 0xE0, 0x45, // LD A, 10, LDH [LYC], A
+        0x3E, 0x03, 0xE0, 0xFF, // LD A, 3, LDH [IE], A
+        0xFB, // EI
+        0x0C, 0x18, 0xFD, // INC C, JR -3
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    // V-blank: INC D, RETI
+    rom[0x40..0x42].copy_from_slice(&[0x14, 0xD9]);
+    // STAT: LDH A, [LY], LD [HL+], A, RETI
+    rom[0x48..0x4C].copy_from_slice(&[0xF0, 0x44, 0x22, 0xD9]);
+    rom
+}
+
+#[test]
+fn lazy_ppu_sees_palette_writes_in_time() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = 0x80;
+    #[rustfmt::skip]
+    let code = [
+        0x3E, 0x80, 0xE0, 0x68, // LD A, $80, LDH [BCPS], A
+        0x3E, 0x1F, 0xE0, 0x69, 0x3E, 0x00, 0xE0, 0x69, // Red in BCPD
+        // Half a frame without touching the PPU: LD BC, 1254, DEC BC, LD A, B, OR C, JR NZ, -5
+        0x01, 0xE6, 0x04, 0x0B, 0x78, 0xB1, 0x20, 0xFB,
+        0x3E, 0x80, 0xE0, 0x68, // LD A, $80, LDH [BCPS], A
+        0x3E, 0x00, 0xE0, 0x69, 0x3E, 0x7C, 0xE0, 0x69, // Blue in BCPD
+        0x18, 0xFE, // JR -2
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    let mut emulator = Emulator::new(&rom);
+    emulator.set_model(HardwareModel::Cgb);
+    emulator.run_frame().unwrap();
+    let colors = emulator.color_frame().unwrap();
+    // Lines drawn before the write keep the old color
+    assert_eq!(colors[8 * GAMEBOY_WIDTH], 0x001F);
+    assert_eq!(colors[143 * GAMEBOY_WIDTH], 0x7C00);
+}
+
+#[test]
+fn lazy_ppu_matches_stepping() {
+    let mut lazy = Emulator::new(&stat_rom());
+    let mut stepped = Emulator::new(&stat_rom());
+    for _ in 0..4 {
+        let frame = frame_hash(lazy.run_frame().unwrap());
+        while !stepped.step().unwrap() {}
+        assert_eq!(frame, frame_hash(stepped.frame_buffer()));
+
+        let (lazy_cpu, stepped_cpu) = (lazy.cpu(), stepped.cpu());
+        assert_eq!(lazy_cpu.PC, stepped_cpu.PC);
+        assert_eq!(lazy_cpu.get_bc(), stepped_cpu.get_bc());
+        assert_eq!(lazy_cpu.get_de(), stepped_cpu.get_de());
+        assert_eq!(lazy_cpu.get_hl(), stepped_cpu.get_hl());
+        for addr in (0xC000..0xD000).chain(0xFF40..=0xFF4B).chain([0xFF0F]) {
+            assert_eq!(
+                lazy.memory_bus().peek(addr),
+                stepped.memory_bus().peek(addr),
+                "{:#06X}",
+                addr
+            );
+        }
+    }
+    // The handler really did run, once for LYC and once per H-blank
+    assert_eq!(lazy.memory_bus().peek(0xC00A), 9);
+    assert!(lazy.cpu().get_hl() > 0xC000 + 4 * 144);
+}
+
+/// Halts until the timer interrupt increments D, forever
+fn halt_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    #[rustfmt::skip]
+    let code = [
+        0x3E, 0x05, 0xE0, 0x07, // LD A, 0b101, LDH [TAC], A
+        0x3E, 0x04, 0xE0, 0xFF, // LD A, 4, LDH [IE], A
+        0xFB, // EI
+        0x76, 0x0C, 0x18, 0xFC, // HALT, INC C, JR -4
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    // Timer: INC D, RETI
+    rom[0x50..0x52].copy_from_slice(&[0x14, 0xD9]);
+    rom
+}
+
+#[test]
+fn halted_cpu_skips_ahead_to_the_interrupt() {
+    let mut lazy = Emulator::new(&halt_rom());
+    let mut stepped = Emulator::new(&halt_rom());
+    for _ in 0..2 {
+        lazy.run_frame().unwrap();
+        while !stepped.step().unwrap() {}
+        let (lazy_cpu, stepped_cpu) = (lazy.cpu(), stepped.cpu());
+        assert_eq!(lazy_cpu.PC, stepped_cpu.PC);
+        assert_eq!(lazy_cpu.get_bc(), stepped_cpu.get_bc());
+        assert_eq!(lazy_cpu.get_de(), stepped_cpu.get_de());
+        assert_eq!(
+            lazy.memory_bus().peek(0xFF05),
+            stepped.memory_bus().peek(0xFF05)
+        );
+    }
+    // 4096 T-cycles per overflow, so about 17 a frame
+    assert!((30..=35).contains(&(lazy.cpu().get_de() >> 8)));
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut emulator = Emulator::new(&stat_rom());
+    emulator.set_model(HardwareModel::Mgb);
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+    }
+    emulator.reset();
+    let mut fresh = Emulator::new(&stat_rom());
+    fresh.set_model(HardwareModel::Mgb);
+    assert_eq!(emulator.save_state(), fresh.save_state());
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+        fresh.run_frame().unwrap();
+    }
+    assert_eq!(emulator.save_state(), fresh.save_state());
+}
+
+#[test]
+fn run_headless_reports_every_frame() {
+    let options = Options {
+        rom: Some(spin_rom()),
+        ..Default::default()
+    };
+    let mut hashes = Vec::new();
+    run_headless(options, 3, |frame, buffer, _| {
+        hashes.push((frame, frame_hash(buffer)));
+        true
+    })
+    .unwrap();
+
+    let mut emulator = Emulator::new(&spin_rom());
+    let expected: Vec<_> = (1..=3)
+        .map(|frame| (frame, frame_hash(emulator.run_frame().unwrap())))
+        .collect();
+    assert_eq!(hashes, expected);
+}
+
+#[test]
+fn run_headless_passes_on_serial_output_and_can_stop() {
+    let options = Options {
+        rom: Some(serial_rom()),
+        ..Default::default()
+    };
+    let mut sent = Vec::new();
+    let throughput = run_headless(options, 3, |_, _, bytes| {
+        sent.extend_from_slice(bytes);
+        sent.is_empty()
+    })
+    .unwrap();
+    assert_eq!(sent, b"O");
+    assert_eq!(throughput.frames, 1);
+}
+
+#[test]
+fn print_headless_prints_only_hashes_unless_asked() {
+    let mut emulator = Emulator::new(&serial_rom());
+    let hashes: Vec<_> = (0..3)
+        .map(|_| frame_hash(emulator.run_frame().unwrap()))
+        .collect();
+    for serial in [false, true] {
+        let options = Options {
+            rom: Some(serial_rom()),
+            ..Default::default()
+        };
+        let output = HeadlessOutput {
+            hash_every: Some(2),
+            serial,
+        };
+        let mut out = Vec::new();
+        assert!(!print_headless(options, 3, output, |_| false, &mut out).unwrap());
+        let mut expected = format!("2 {:08x}\n3 {:08x}\n", hashes[1], hashes[2]);
+        if serial {
+            // It never sent a line break
+            expected.push_str("O\n");
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    let options = Options {
+        rom: Some(serial_rom()),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    let output = HeadlessOutput::default();
+    assert!(print_headless(options, 3, output, |line| line == "O", &mut out).unwrap());
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!("1 {:08x}\n", hashes[0])
+    );
+}
+
+#[test]
+fn frame_hash_is_stable() {
+    // Pinned so a change to the hash doesn't silently invalidate recorded hashes
+    let frame = [0xFF; GAMEBOY_WIDTH * GAMEBOY_HEIGHT];
+    assert_eq!(frame_hash(&frame), 0x73925A63);
+}
+
+#[test]
+fn illegal_instruction_is_an_error() {
+    let mut rom = spin_rom();
+    rom[0x100] = 0xD3;
+    let mut emulator = Emulator::new(&rom);
+    let error = emulator.step().unwrap_err();
+    assert_eq!(
+        error,
+        EmulatorError::IllegalInstruction {
+            pc: 0x100,
+            opcode: 0xD3
+        }
+    );
+
+    let crash = emulator.crash(&error);
+    assert_eq!(crash.message, "Illegal instruction 0xD3 at 0x0100");
+    assert!(crash.cpu_dump.contains("PC: 0x100"));
+}
+
+/// LD A, (0xA000) with no cartridge RAM behind it, then LD (0xA000), A
+fn unmapped_rom() -> Vec<u8> {
+    let mut rom = spin_rom();
+    rom[0x100..0x106].copy_from_slice(&[0xFA, 0x00, 0xA0, 0xEA, 0x00, 0xA0]);
+    rom[0x106..0x108].copy_from_slice(&[0x18, 0xFE]);
+    rom
+}
+
+#[test]
+fn unmapped_read_is_open_bus() {
+    let mut emulator = Emulator::new(&unmapped_rom());
+    assert_eq!(emulator.step(), Ok(false));
+    assert_eq!(emulator.step(), Ok(false));
+    assert_eq!(emulator.memory_bus().read_u8(0xA000), 0xFF);
+}
+
+#[test]
+fn strict_memory_reports_unmapped_accesses() {
+    let mut emulator = Emulator::new(&unmapped_rom());
+    emulator.memory_bus_mut().set_strict(true);
+    assert_eq!(emulator.step(), Err(EmulatorError::UnmappedRead(0xA000)));
+    assert_eq!(
+        emulator.step(),
+        Err(EmulatorError::UnmappedWrite {
+            addr: 0xA000,
+            value: 0xFF
+        })
+    );
+    // Only reported once
+    assert_eq!(emulator.step(), Ok(false));
+}
+
+#[test]
+fn run_headless_stops_on_error() {
+    let mut rom = spin_rom();
+    rom[0x100] = 0xD3;
+    let options = Options {
+        rom: Some(rom),
+        ..Default::default()
+    };
+    let mut frames = 0;
+    let crash = run_headless(options, 3, |_, _, _| {
+        frames += 1;
+        true
+    })
+    .unwrap_err();
+    assert_eq!(frames, 0);
+    assert!(crash.message.starts_with("Illegal instruction"));
+}
+
+/// Panics whenever it's read
+#[derive(Debug)]
+struct Broken;
+
+impl MmioDevice for Broken {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[0xFF70..=0xFF70]
+    }
+
+    fn read(&self, _addr: u16) -> u8 {
+        panic!("broken device")
+    }
+
+    fn write(&mut self, _addr: u16, _byte: u8) -> Option<Interrupt> {
+        None
+    }
+}
+
+#[test]
+fn panics_are_caught() {
+    // Running from a register that panics when the instruction is fetched
+    let mut emulator = Emulator::new(&jump_rom(0xFF70));
+    emulator.memory_bus_mut().attach(Box::new(Broken));
+    let crash = emulator.run_frame_catching().unwrap_err();
+    assert!(
+        crash
+            .message
+            .starts_with("Panicked running an unreadable instruction at 0xFF70: broken device"),
+        "{}",
+        crash.message
+    );
+    assert!(crash.cpu_dump.contains("PC: 0xFF70"), "{}", crash.cpu_dump);
+}
+
+#[test]
+fn truncated_roms_run() {
+    // Too short to hold the entry point, the rest reads 0xFF which is RST 0x38
+    let mut emulator = Emulator::new(&[0; 0x100]);
+    assert_eq!(emulator.memory_bus().rom().len(), 0x8000);
+    emulator.run_frame_catching().unwrap();
+    assert_eq!(emulator.memory_bus().read_u8(0x7FFF), 0xFF);
+}
+
+/// `JP addr` at the entry point
+fn jump_rom(addr: u16) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, addr as u8, (addr >> 8) as u8]);
+    rom
+}
+
+#[test]
+fn runs_code_from_hram() {
+    let mut emulator = Emulator::new(&jump_rom(0xFF80));
+    for (addr, byte) in (0xFF80..).zip([0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xFE]) {
+        emulator.memory_bus_mut().write_u8(addr, byte);
+    }
+    for _ in 0..4 {
+        emulator.step().unwrap();
+    }
+    assert_eq!(emulator.memory_bus().read_u8(0xC000), 0x42);
+    assert_eq!(emulator.cpu().PC, 0xFF85);
+}
+
+#[test]
+fn instruction_fetch_wraps_at_ffff() {
+    let mut emulator = Emulator::new(&jump_rom(0xFFFE));
+    // LD A, n with n in IE
+    emulator.memory_bus_mut().write_u8(0xFFFE, 0x3E);
+    emulator.memory_bus_mut().write_u8(0xFFFF, 0x07);
+    assert_eq!(emulator.memory_bus().get_instr(0xFFFE), [0x3E, 0x07, 0, 0]);
+    emulator.step().unwrap();
+    emulator.step().unwrap();
+    assert_eq!(emulator.cpu().Accumulator, 0x07);
+    assert_eq!(emulator.cpu().PC, 0x0000);
+}
+
+#[test]
+fn instruction_fetch_stays_in_the_instruction() {
+    // A NOP on the last byte of VRAM, with unmapped cartridge RAM right after it
+    let mut emulator = Emulator::new(&jump_rom(0x9FFF));
+    emulator.memory_bus_mut().set_strict(true);
+    assert_eq!(emulator.step(), Ok(false));
+    assert_eq!(emulator.step(), Ok(false));
+    assert_eq!(emulator.cpu().PC, 0xA000);
+    assert_eq!(emulator.memory_bus().get_instr(0x9FFD), [0x00, 0, 0, 0]);
+}
+
+/// The interpreter, counting how many times it's stepped
+struct CountingCore {
+    cpu: CPU,
+    steps: Arc<Mutex<u32>>,
+}
+
+impl CpuCore for CountingCore {
+    fn step(&mut self, memory_bus: &mut MemoryBus) -> u32 {
+        *self.steps.lock().unwrap() += 1;
+        self.cpu.tick(memory_bus)
+    }
+
+    fn registers(&self) -> &CPU {
+        &self.cpu
+    }
+
+    fn registers_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    fn into_registers(self: Box<Self>) -> CPU {
+        self.cpu
+    }
+}
+
+#[test]
+fn custom_cores_take_over_the_registers() {
+    let mut emulator = Emulator::new(&spin_rom());
+    emulator.run_frame().unwrap();
+    let pc = emulator.cpu().PC;
+    let steps = Arc::new(Mutex::new(0));
+    let counted = Arc::clone(&steps);
+    emulator.set_core(|cpu| {
+        Box::new(CountingCore {
+            cpu,
+            steps: counted,
+        })
+    });
+    assert_eq!(emulator.cpu().PC, pc);
+    assert_eq!(emulator.backend(), None);
+    emulator.step().unwrap();
+    assert_eq!(*steps.lock().unwrap(), 1);
+}
+
+#[test]
+fn injected_interrupts_are_dispatched() {
+    let mut rom = spin_rom();
+    // EI first, the handler at 0x50 spins too
+    rom[0x100..0x103].copy_from_slice(&[0xFB, 0x18, 0xFE]);
+    rom[0x50..0x52].copy_from_slice(&[0x18, 0xFE]);
+    let mut emulator = Emulator::new(&rom);
+    emulator.memory_bus_mut().write_u8(0xFFFF, 0x04);
+    emulator.step().unwrap();
+    emulator.inject_interrupt(Interrupt::Timer);
+    emulator.step().unwrap();
+    assert_eq!(emulator.cpu().PC, 0x50);
+}
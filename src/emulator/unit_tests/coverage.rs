@@ -0,0 +1,61 @@
+use crate::emulator::{symbols::Symbols, Emulator};
+
+/// JP to a loop of LD A, 1 and SWAP A
+fn loop_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    rom[0x150..0x156].copy_from_slice(&[0x3E, 0x01, 0xCB, 0x37, 0x18, 0xFA]);
+    rom
+}
+
+fn run(rom: &[u8], steps: usize) -> Emulator {
+    let mut emulator = Emulator::new(rom);
+    emulator.enable_coverage();
+    for _ in 0..steps {
+        emulator.step().unwrap();
+    }
+    emulator
+}
+
+#[test]
+fn counts_opcodes() {
+    let emulator = run(&loop_rom(), 7);
+    let coverage = emulator.coverage().unwrap();
+    assert_eq!(coverage.instructions(), 7);
+    assert_eq!(coverage.opcode_count(0xC3), 1);
+    assert_eq!(coverage.opcode_count(0x3E), 2);
+    assert_eq!(coverage.opcode_count(0xCB), 2);
+    assert_eq!(coverage.cb_opcode_count(0x37), 2);
+    assert_eq!(coverage.opcode_count(0x18), 2);
+}
+
+#[test]
+fn marks_executed_rom_bytes() {
+    let emulator = run(&loop_rom(), 7);
+    let coverage = emulator.coverage().unwrap();
+    assert_eq!(coverage.executed_ranges(), [(0x100, 0x103), (0x150, 0x156)]);
+    assert_eq!(coverage.executed_bytes(), 9);
+    assert!(!coverage.is_executed(0x156));
+}
+
+#[test]
+fn halting_is_not_an_instruction() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100] = 0x76;
+    let emulator = run(&rom, 5);
+    assert_eq!(emulator.coverage().unwrap().instructions(), 1);
+}
+
+#[test]
+fn report() {
+    let rom = loop_rom();
+    let emulator = run(&rom, 7);
+    let symbols = Symbols::parse("00:0100 Start\n00:0150 Loop\n").unwrap();
+    let report = emulator.coverage().unwrap().report(&rom, &symbols);
+    assert!(report.starts_with("Instructions executed: 7 (0 outside ROM)\n"));
+    assert!(report.contains("Opcodes used: 3 of 244, CB opcodes used: 1 of 256\n"));
+    assert!(report.contains("ROM bytes executed: 9 of 32768"));
+    assert!(report.contains("\n  3E     2\n"));
+    assert!(report.contains("\n  CB 37  2\n"));
+    assert!(report.contains("\n  00:0100-0102  Start\n  00:0150-0155  Loop\n"));
+}
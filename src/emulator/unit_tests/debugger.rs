@@ -0,0 +1,411 @@
+use std::sync::mpsc::channel;
+
+use crate::emulator::{
+    cpu::call_stack::{CallStack, Frame, FrameKind},
+    cpu::registers::{Reg16, Reg8},
+    cpu::Flag,
+    debugger::{
+        expression::{BinaryOp, Expression, ExpressionError},
+        watchpoints::Watchpoint,
+        BreakOn, Breakpoint, DebugCommand, Debugger,
+    },
+    instructions::Instruction,
+    memory_bus::Interrupt,
+    symbols::Symbols,
+    unit_tests::asm,
+    Emulator,
+};
+
+fn frame(kind: FrameKind, sp: u16) -> Frame {
+    Frame {
+        kind,
+        target: 0x200,
+        return_addr: 0x153,
+        sp,
+    }
+}
+
+#[test]
+fn ret_pops_the_matching_frame() {
+    let mut stack = CallStack::default();
+    stack.push(frame(FrameKind::Call, 0xFFFC));
+    stack.push(frame(FrameKind::Interrupt(Interrupt::VBlank), 0xFFFA));
+    stack.ret(0xFFFA);
+    assert_eq!(stack.frames(), &[frame(FrameKind::Call, 0xFFFC)]);
+    stack.ret(0xFFFC);
+    assert!(stack.frames().is_empty());
+}
+
+#[test]
+fn manual_stack_manipulation_is_tolerated() {
+    let mut stack = CallStack::default();
+    stack.push(frame(FrameKind::Call, 0xFFFC));
+    // Push an address and RET to it, that's a jump
+    stack.ret(0xFFFA);
+    assert_eq!(stack.frames().len(), 1);
+
+    // The inner routine pops its return address and returns from the outer one
+    stack.push(frame(FrameKind::Call, 0xFFFA));
+    stack.ret(0xFFFC);
+    assert!(stack.frames().is_empty());
+
+    // SP reset from the main loop, the old frames are gone
+    stack.push(frame(FrameKind::Call, 0xFFF0));
+    stack.push(frame(FrameKind::Call, 0xFFFC));
+    assert_eq!(stack.frames(), &[frame(FrameKind::Call, 0xFFFC)]);
+}
+
+#[test]
+fn call_sites() {
+    assert_eq!(frame(FrameKind::Call, 0).call_site(), 0x150);
+    assert_eq!(frame(FrameKind::Rst(0x38), 0).call_site(), 0x152);
+    assert_eq!(
+        frame(FrameKind::Interrupt(Interrupt::Timer), 0).call_site(),
+        0x153
+    );
+}
+
+/// 0x150 calls 0x200, which does RST 38 and spins there
+fn call_rom() -> Vec<u8> {
+    asm::rom(&[
+        (0x38, &asm![JR(-2)]),
+        (0x100, &asm![JP 0x150]),
+        (0x150, &asm![CALL 0x200; JR (-2)]),
+        (0x200, &asm![RST 0x38; RET]),
+    ])
+}
+
+#[test]
+fn calls_and_rsts_are_tracked() {
+    let mut emulator = Emulator::new(&call_rom());
+    for _ in 0..3 {
+        emulator.step().unwrap();
+    }
+    let frames = emulator.cpu().call_stack.frames();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].kind, FrameKind::Call);
+    assert_eq!(frames[0].target, 0x200);
+    assert_eq!(frames[0].call_site(), 0x150);
+    assert_eq!(frames[1].kind, FrameKind::Rst(0x38));
+    assert_eq!(frames[1].call_site(), 0x200);
+}
+
+#[test]
+fn step_sends_a_backtrace() {
+    let mut emulator = Emulator::new(&call_rom());
+    emulator.set_symbols(Symbols::parse("00:0150 Main\n00:0200 Helper\n").unwrap());
+    let (sender, views) = channel();
+    let mut debugger = Debugger::new(sender);
+
+    debugger.apply(DebugCommand::Pause, &mut emulator).unwrap();
+    assert!(debugger.paused());
+    for _ in 0..3 {
+        debugger.apply(DebugCommand::Step, &mut emulator).unwrap();
+    }
+    let view = views.try_iter().last().unwrap();
+    assert!(view.paused);
+    assert_eq!(view.next, "0x0038: JumpRelative(0xFE)");
+    assert_eq!(
+        view.backtrace,
+        [
+            "#0 0x0038",
+            "#1 0x0200 Helper (RST 38 0x0038)",
+            "#2 0x0150 Main (called 0x0200 Helper)",
+        ]
+    );
+
+    debugger
+        .apply(DebugCommand::Continue, &mut emulator)
+        .unwrap();
+    assert!(!debugger.paused());
+    assert!(!views.try_recv().unwrap().paused);
+}
+
+#[test]
+fn nop_out_replaces_the_whole_instruction() {
+    let mut emulator = Emulator::new(&call_rom());
+    let mut debugger = paused_debugger(&mut emulator);
+    debugger
+        .apply(DebugCommand::NopOut(0x150), &mut emulator)
+        .unwrap();
+    assert_eq!(emulator.memory_bus().get_instr(0x150), [0; 4]);
+    assert_eq!(emulator.memory_bus().get_instr(0x153), [0x18, 0xFE, 0, 0]);
+    debugger
+        .apply(
+            DebugCommand::Patch {
+                addr: 0x153,
+                bytes: vec![0x76],
+            },
+            &mut emulator,
+        )
+        .unwrap();
+    assert_eq!(emulator.memory_bus().peek(0x153), 0x76);
+}
+
+/// 0x150 calls 0x200, which calls 0x300, both return
+fn nested_rom() -> Vec<u8> {
+    asm::rom(&[
+        (0x100, &asm![JP 0x150]),
+        (0x150, &asm![CALL 0x200; JR (-2)]),
+        (0x200, &asm![CALL 0x300; RET]),
+        (0x300, &asm![NOP; RET]),
+    ])
+}
+
+fn paused_debugger(emulator: &mut Emulator) -> Debugger {
+    let (sender, _) = channel();
+    let mut debugger = Debugger::new(sender);
+    debugger.apply(DebugCommand::Pause, emulator).unwrap();
+    debugger
+}
+
+#[test]
+fn step_over_runs_the_whole_call() {
+    let mut emulator = Emulator::new(&nested_rom());
+    let mut debugger = paused_debugger(&mut emulator);
+    // Just a step when it isn't a call
+    debugger
+        .apply(DebugCommand::StepOver, &mut emulator)
+        .unwrap();
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x150);
+
+    debugger
+        .apply(DebugCommand::StepOver, &mut emulator)
+        .unwrap();
+    assert!(!debugger.paused());
+    assert!(!debugger.run_frame(&mut emulator).unwrap());
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x153);
+    assert!(emulator.cpu().call_stack.frames().is_empty());
+}
+
+#[test]
+fn step_out_returns_from_the_innermost_frame() {
+    let mut emulator = Emulator::new(&nested_rom());
+    let mut debugger = paused_debugger(&mut emulator);
+    for _ in 0..3 {
+        debugger.apply(DebugCommand::Step, &mut emulator).unwrap();
+    }
+    assert_eq!(emulator.cpu().PC, 0x300);
+
+    debugger
+        .apply(DebugCommand::StepOut, &mut emulator)
+        .unwrap();
+    debugger.run_frame(&mut emulator).unwrap();
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x203);
+    assert_eq!(emulator.cpu().call_stack.frames().len(), 1);
+}
+
+#[test]
+fn run_to_stops_at_the_address() {
+    let mut emulator = Emulator::new(&nested_rom());
+    let mut debugger = paused_debugger(&mut emulator);
+    debugger
+        .apply(DebugCommand::RunTo(0x301), &mut emulator)
+        .unwrap();
+    debugger.run_frame(&mut emulator).unwrap();
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x301);
+
+    // Never reached, frames keep going
+    debugger
+        .apply(DebugCommand::RunTo(0x4000), &mut emulator)
+        .unwrap();
+    assert!(debugger.run_frame(&mut emulator).unwrap());
+    assert!(!debugger.paused());
+}
+
+#[test]
+fn parses_conditions() {
+    assert_eq!(
+        Expression::parse("A == 0x3C && Z").unwrap(),
+        Expression::Binary(
+            BinaryOp::And,
+            Box::new(Expression::Binary(
+                BinaryOp::Equal,
+                Box::new(Expression::Reg8(Reg8::A)),
+                Box::new(Expression::Number(0x3C))
+            )),
+            Box::new(Expression::Flag(Flag::Z))
+        )
+    );
+    assert_eq!(
+        Expression::parse("[$C123] != 0").unwrap(),
+        Expression::Binary(
+            BinaryOp::NotEqual,
+            Box::new(Expression::Memory(Box::new(Expression::Number(0xC123)))),
+            Box::new(Expression::Number(0))
+        )
+    );
+    assert_eq!(
+        Expression::parse("!(hl >= 512) || CF").unwrap(),
+        Expression::Binary(
+            BinaryOp::Or,
+            Box::new(Expression::Not(Box::new(Expression::Binary(
+                BinaryOp::GreaterEqual,
+                Box::new(Expression::Reg16(Reg16::HL)),
+                Box::new(Expression::Number(512))
+            )))),
+            Box::new(Expression::Flag(Flag::C))
+        )
+    );
+}
+
+#[test]
+fn bad_conditions() {
+    let error = |position, message| Err(ExpressionError { position, message });
+    assert_eq!(Expression::parse("A =="), error(4, "Expected a value"));
+    assert_eq!(Expression::parse("A ! B"), error(2, "Unexpected input"));
+    assert_eq!(Expression::parse("[0xC000"), error(7, "Expected ']'"));
+    assert_eq!(
+        Expression::parse("A == X"),
+        error(5, "Unknown name or bad number")
+    );
+}
+
+#[test]
+fn evaluates_against_the_emulator() {
+    let mut emulator = Emulator::new(&nested_rom());
+    emulator.memory_bus_mut().write_u8(0xC123, 7);
+    let eval = |text| Expression::parse(text).unwrap().eval(&emulator);
+    // Post-boot DMG registers
+    assert_eq!(eval("A == 1 && Z && !N"), 1);
+    assert_eq!(eval("PC"), 0x100);
+    assert_eq!(eval("[0xC123]"), 7);
+    assert_eq!(eval("[0xC123] < 7 || C == 0x13"), 1);
+}
+
+#[test]
+fn conditional_breakpoints() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    // INC A, JR back to it
+    rom[0x150..0x153].copy_from_slice(&[0x3C, 0x18, 0xFD]);
+    let mut emulator = Emulator::new(&rom);
+    let (sender, views) = channel();
+    let mut debugger = Debugger::new(sender);
+    let breakpoint = Breakpoint {
+        addr: 0x150,
+        condition: Some(Expression::parse("A >= 5").unwrap()),
+    };
+    debugger
+        .apply(
+            DebugCommand::SetBreakpoints(vec![breakpoint]),
+            &mut emulator,
+        )
+        .unwrap();
+    assert!(!debugger.paused());
+
+    assert!(!debugger.run_frame(&mut emulator).unwrap());
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x150);
+    assert_eq!(emulator.cpu().Accumulator, 5);
+    assert!(views.try_iter().last().unwrap().paused);
+
+    // Continuing doesn't stop again straight away
+    debugger
+        .apply(DebugCommand::Continue, &mut emulator)
+        .unwrap();
+    debugger.run_frame(&mut emulator).unwrap();
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().Accumulator, 6);
+}
+
+#[test]
+fn break_on_rst() {
+    let mut emulator = Emulator::new(&call_rom());
+    let (sender, _) = channel();
+    let mut debugger = Debugger::new(sender);
+    let break_on = BreakOn {
+        rst_vectors: vec![0x38],
+        ..Default::default()
+    };
+    debugger
+        .apply(DebugCommand::SetBreakOn(break_on), &mut emulator)
+        .unwrap();
+    debugger.run_frame(&mut emulator).unwrap();
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x38);
+    assert_eq!(
+        emulator.cpu().call_stack.entered().unwrap().call_site(),
+        0x200
+    );
+
+    // Spinning at the vector isn't another RST
+    debugger
+        .apply(DebugCommand::Continue, &mut emulator)
+        .unwrap();
+    assert!(debugger.run_frame(&mut emulator).unwrap());
+    assert!(!debugger.paused());
+}
+
+#[test]
+fn break_on_interrupt() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x40] = 0xD9;
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    // Enable VBlank and wait for it
+    rom[0x150..0x156].copy_from_slice(&[0x3E, 0x01, 0xE0, 0xFF, 0xFB, 0x18]);
+    rom[0x156] = 0xFE;
+    let run = |interrupts| {
+        let mut emulator = Emulator::new(&rom);
+        let (sender, _) = channel();
+        let mut debugger = Debugger::new(sender);
+        let break_on = BreakOn {
+            interrupts,
+            ..Default::default()
+        };
+        debugger
+            .apply(DebugCommand::SetBreakOn(break_on), &mut emulator)
+            .unwrap();
+        for _ in 0..2 {
+            if !debugger.paused() {
+                debugger.run_frame(&mut emulator).unwrap();
+            }
+        }
+        (debugger.paused(), emulator.cpu().PC)
+    };
+    assert_eq!(run(vec![Interrupt::VBlank]), (true, 0x40));
+    assert!(!run(vec![Interrupt::Timer]).0);
+}
+
+#[test]
+fn watchpoints_pause_after_the_access() {
+    let rom = asm::rom(&[(
+        0x100,
+        &asm![LD A, [0xC000]; LD A, 0x12; LD [0xC000], A; JR (-2)],
+    )]);
+    let mut emulator = Emulator::new(&rom);
+    let (sender, views) = channel();
+    let mut debugger = Debugger::new(sender);
+    let watchpoint = Watchpoint {
+        range: 0xC000..=0xC0FF,
+        read: false,
+        write: true,
+    };
+    assert_eq!(watchpoint.to_string(), "0xC000-0xC0FF W");
+    debugger
+        .apply(
+            DebugCommand::SetWatchpoints(vec![watchpoint]),
+            &mut emulator,
+        )
+        .unwrap();
+
+    // The read doesn't count
+    assert!(!debugger.run_frame(&mut emulator).unwrap());
+    assert!(debugger.paused());
+    assert_eq!(emulator.cpu().PC, 0x108);
+    let view = views.try_iter().last().unwrap();
+    assert_eq!(view.watch_hit.as_deref(), Some("Write of 0x12 to 0xC000"));
+
+    // Removing them lets it run
+    debugger
+        .apply(DebugCommand::SetWatchpoints(Vec::new()), &mut emulator)
+        .unwrap();
+    debugger
+        .apply(DebugCommand::Continue, &mut emulator)
+        .unwrap();
+    assert!(debugger.run_frame(&mut emulator).unwrap());
+}
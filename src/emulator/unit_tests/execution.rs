@@ -0,0 +1,401 @@
+//! Running instructions, written with [`asm!`](super::asm)
+use crate::emulator::{
+    cpu::CPU,
+    instructions::Instruction,
+    unit_tests::asm::{Machine, START},
+};
+
+const Z: u8 = 0x80;
+const N: u8 = 0x40;
+const H: u8 = 0x20;
+const C: u8 = 0x10;
+
+/// Runs `program` from `cpu`'s registers, PC and SP aside
+fn run(cpu: CPU, program: &[Instruction]) -> Machine {
+    let mut machine = Machine::new(program);
+    machine.cpu = CPU {
+        PC: START,
+        SP: machine.cpu.SP,
+        ..cpu
+    };
+    machine.run();
+    machine
+}
+
+fn with_a(a: u8, flags: u8) -> CPU {
+    CPU {
+        Accumulator: a,
+        Flags: flags,
+        ..CPU::default()
+    }
+}
+
+/// OP A, B, then OP A, 0x0F, then OP A, [HL]
+macro_rules! alu_forms {
+    ($op:ident) => {
+        [
+            asm![$op A, B; HALT],
+            asm![$op A, 0x0F; HALT],
+            asm![$op A, [HL]; HALT],
+        ]
+    };
+}
+
+#[test]
+fn alu_register_immediate_and_memory_agree() {
+    // With 0x0F as the operand: A, flags before, A after, flags after
+    let cases = [
+        (alu_forms!(ADD), 0xF1, 0, 0x00, Z | H | C),
+        (alu_forms!(ADD), 0x70, C, 0x7F, 0),
+        (alu_forms!(ADC), 0x01, C, 0x11, H),
+        (alu_forms!(SUB), 0x10, 0, 0x01, N | H),
+        (alu_forms!(SUB), 0x0F, 0, 0x00, Z | N),
+        (alu_forms!(SBC), 0x0F, C, 0xFF, N | H | C),
+        (alu_forms!(AND), 0xF0, C, 0x00, Z | H),
+        (alu_forms!(XOR), 0xFF, C, 0xF0, 0),
+        (alu_forms!(OR), 0x30, C, 0x3F, 0),
+        (alu_forms!(OR), 0x00, 0, 0x0F, 0),
+        (alu_forms!(CP), 0x0F, 0, 0x0F, Z | N),
+        (alu_forms!(CP), 0x0E, 0, 0x0E, N | H | C),
+    ];
+    for (programs, a, flags, expected_a, expected_flags) in cases {
+        for program in programs {
+            let mut machine = Machine::new(&program);
+            machine.cpu.Accumulator = a;
+            machine.cpu.Flags = flags;
+            machine.cpu.B = 0x0F;
+            machine.cpu.set_hl(0xC000);
+            machine.bus.write_u8(0xC000, 0x0F);
+            machine.run();
+            assert_eq!(
+                (machine.cpu.Accumulator, machine.cpu.Flags),
+                (expected_a, expected_flags),
+                "{} with A {:02X} and flags {:02X}",
+                program[0],
+                a,
+                flags
+            );
+        }
+    }
+}
+
+#[test]
+fn increment_and_decrement() {
+    let machine = run(
+        CPU {
+            B: 0xFF,
+            C: 0x10,
+            Flags: C,
+            ..CPU::default()
+        },
+        &asm![INC B; DEC C; HALT],
+    );
+    assert_eq!((machine.cpu.B, machine.cpu.C), (0x00, 0x0F));
+    // Carry is left alone, these are DEC C's
+    assert_eq!(machine.cpu.Flags, N | H | C);
+
+    let mut machine = Machine::new(&asm![LD HL, 0xC000; INC [HL]; INC [HL]; DEC [HL]; HALT]);
+    machine.bus.write_u8(0xC000, 0x0F);
+    machine.run();
+    assert_eq!(machine.bus.peek(0xC000), 0x10);
+    assert_eq!(machine.cpu.Flags, N);
+
+    let machine = run(
+        CPU::default(),
+        &asm![LD BC, 0x00FF; INC BC; LD DE, 0x0000; DEC DE; LD SP, 0xFFFF; INC SP; HALT],
+    );
+    assert_eq!(machine.cpu.get_bc(), 0x0100);
+    assert_eq!(machine.cpu.get_de(), 0xFFFF);
+    assert_eq!(machine.cpu.SP, 0x0000);
+    // 16 bit ones don't touch the flags
+    assert_eq!(machine.cpu.Flags, 0);
+}
+
+#[test]
+fn sixteen_bit_arithmetic() {
+    let machine = run(
+        with_a(0, Z),
+        &asm![LD HL, 0x0FFF; LD BC, 0x0001; ADD HL, BC; HALT],
+    );
+    assert_eq!(machine.cpu.get_hl(), 0x1000);
+    // Z is kept
+    assert_eq!(machine.cpu.Flags, Z | H);
+
+    let machine = run(with_a(0, 0), &asm![LD HL, 0x8000; ADD HL, HL; HALT]);
+    assert_eq!(machine.cpu.get_hl(), 0x0000);
+    assert_eq!(machine.cpu.Flags, C);
+
+    // Flags come from the low byte
+    let machine = run(with_a(0, Z | N), &asm![LD SP, 0x00FF; ADD SP, 1; HALT]);
+    assert_eq!(machine.cpu.SP, 0x0100);
+    assert_eq!(machine.cpu.Flags, H | C);
+
+    let machine = run(with_a(0, 0), &asm![LD SP, 0x1000; LDHL SP, (-1); HALT]);
+    assert_eq!(machine.cpu.get_hl(), 0x0FFF);
+    assert_eq!(machine.cpu.SP, 0x1000);
+    assert_eq!(machine.cpu.Flags, 0);
+}
+
+#[test]
+fn accumulator_ops() {
+    let cases: [(Vec<Instruction>, u8, u8, u8, u8); 9] = [
+        (asm![RLCA; HALT].to_vec(), 0x85, 0, 0x0B, C),
+        (asm![RRCA; HALT].to_vec(), 0x01, 0, 0x80, C),
+        (asm![RLA; HALT].to_vec(), 0x80, 0, 0x00, C),
+        (asm![RRA; HALT].to_vec(), 0x00, C, 0x80, 0),
+        (asm![CPL; HALT].to_vec(), 0x35, 0, 0xCA, N | H),
+        (asm![SCF; HALT].to_vec(), 0x00, Z | N | H, 0x00, Z | C),
+        (asm![CCF; HALT].to_vec(), 0x00, C | H, 0x00, 0),
+        // 0x19 + 0x28 in BCD
+        (asm![ADD A, 0x28; DAA; HALT].to_vec(), 0x19, 0, 0x47, 0),
+        // 0x47 - 0x28 in BCD
+        (asm![SUB A, 0x28; DAA; HALT].to_vec(), 0x47, 0, 0x19, N),
+    ];
+    for (program, a, flags, expected_a, expected_flags) in cases {
+        let machine = run(with_a(a, flags), &program);
+        assert_eq!(
+            (machine.cpu.Accumulator, machine.cpu.Flags),
+            (expected_a, expected_flags),
+            "{}",
+            program[program.len() - 2]
+        );
+    }
+}
+
+#[test]
+fn prefixed_ops_on_registers_and_memory() {
+    let cases = [
+        (asm![RLC B; HALT], asm![RLC [HL]; HALT], 0x80, 0, 0x01, C),
+        (asm![RRC B; HALT], asm![RRC [HL]; HALT], 0x00, 0, 0x00, Z),
+        (asm![RL B; HALT], asm![RL [HL]; HALT], 0x00, C, 0x01, 0),
+        (asm![RR B; HALT], asm![RR [HL]; HALT], 0x01, 0, 0x00, Z | C),
+        (asm![SLA B; HALT], asm![SLA [HL]; HALT], 0xC0, 0, 0x80, C),
+        (asm![SRA B; HALT], asm![SRA [HL]; HALT], 0x81, 0, 0xC0, C),
+        (asm![SWAP B; HALT], asm![SWAP [HL]; HALT], 0x12, C, 0x21, 0),
+        (asm![SRL B; HALT], asm![SRL [HL]; HALT], 0x81, 0, 0x40, C),
+        (
+            asm![SET 3, B; HALT],
+            asm![SET 3, [HL]; HALT],
+            0x00,
+            Z,
+            0x08,
+            Z,
+        ),
+        (
+            asm![RES 7, B; HALT],
+            asm![RES 7, [HL]; HALT],
+            0xFF,
+            0,
+            0x7F,
+            0,
+        ),
+    ];
+    for (register, memory, value, flags, expected, expected_flags) in cases {
+        let machine = run(
+            CPU {
+                B: value,
+                Flags: flags,
+                ..CPU::default()
+            },
+            &register,
+        );
+        assert_eq!(
+            (machine.cpu.B, machine.cpu.Flags),
+            (expected, expected_flags),
+            "{}",
+            register[0]
+        );
+
+        let mut machine = Machine::new(&memory);
+        machine.cpu.Flags = flags;
+        machine.cpu.set_hl(0xC000);
+        machine.bus.write_u8(0xC000, value);
+        machine.run();
+        assert_eq!(
+            (machine.bus.peek(0xC000), machine.cpu.Flags),
+            (expected, expected_flags),
+            "{}",
+            memory[0]
+        );
+    }
+}
+
+#[test]
+fn bit_tests_set_z_and_keep_carry() {
+    let machine = run(
+        CPU {
+            B: 0x80,
+            Flags: C,
+            ..CPU::default()
+        },
+        &asm![BIT 7, B; HALT],
+    );
+    assert_eq!(machine.cpu.Flags, H | C);
+    let mut machine = Machine::new(&asm![LD HL, 0xC000; BIT 0, [HL]; HALT]);
+    machine.bus.write_u8(0xC000, 0xFE);
+    machine.run();
+    assert_eq!(machine.cpu.Flags, Z | H);
+}
+
+#[test]
+fn register_and_immediate_loads() {
+    let machine = run(
+        CPU::default(),
+        &asm![
+            LD A, 0x11; LD B, A; LD C, B; LD D, C; LD E, D; LD H, E; LD L, H;
+            LD A, 0x22; LD H, 0xC0; LD L, 0x00; LD [HL], A; LD [HL], 0x33; LD E, [HL];
+            HALT
+        ],
+    );
+    assert_eq!(
+        (machine.cpu.B, machine.cpu.C, machine.cpu.D),
+        (0x11, 0x11, 0x11)
+    );
+    assert_eq!(machine.cpu.E, 0x33);
+    assert_eq!(machine.cpu.get_hl(), 0xC000);
+    assert_eq!(machine.bus.peek(0xC000), 0x33);
+}
+
+#[test]
+fn indirect_loads() {
+    let mut machine = Machine::new(&asm![
+        LD BC, 0xC000; LD DE, 0xC001; LD HL, 0xC002;
+        LD A, 0x01; LD [BC], A; LD A, 0x02; LD [DE], A;
+        LD A, 0x03; LD [HL+], A; LD A, 0x04; LD [HL-], A;
+        LD A, [HL-]; LD B, A; LD A, [HL+]; LD C, A;
+        LD A, [DE]; LD D, A; LD A, [0xC003]; LD E, A;
+        HALT
+    ]);
+    machine.run();
+    assert_eq!(
+        [0xC000, 0xC001, 0xC002, 0xC003].map(|addr| machine.bus.peek(addr)),
+        [0x01, 0x02, 0x03, 0x04]
+    );
+    assert_eq!(
+        (machine.cpu.B, machine.cpu.C, machine.cpu.D, machine.cpu.E),
+        (0x03, 0x02, 0x02, 0x04)
+    );
+    assert_eq!(machine.cpu.get_hl(), 0xC002);
+}
+
+#[test]
+fn absolute_and_high_page_loads() {
+    let mut machine = Machine::new(&asm![
+        LD A, 0x5A; LD [0xC100], A; LDH [0x80], A;
+        LD C, 0x81; LD A, 0xA5; LDH [C], A;
+        LD A, [0xC100]; LD B, A; LDH A, [0x81]; LD D, A; LD C, 0x80; LDH A, [C];
+        LD SP, 0xBEEF; LD [0xC200], SP; LD HL, 0xD000; LD SP, HL;
+        HALT
+    ]);
+    machine.run();
+    assert_eq!(machine.bus.peek(0xC100), 0x5A);
+    assert_eq!(machine.bus.peek(0xFF80), 0x5A);
+    assert_eq!(machine.bus.peek(0xFF81), 0xA5);
+    assert_eq!(
+        (machine.cpu.B, machine.cpu.D, machine.cpu.Accumulator),
+        (0x5A, 0xA5, 0x5A)
+    );
+    assert_eq!(
+        [machine.bus.peek(0xC200), machine.bus.peek(0xC201)],
+        [0xEF, 0xBE]
+    );
+    assert_eq!(machine.cpu.SP, 0xD000);
+}
+
+#[test]
+fn push_and_pop() {
+    let mut machine = Machine::new(&asm![
+        LD BC, 0x1234; PUSH BC; POP DE;
+        LD HL, 0x56FF; PUSH HL; POP AF;
+        PUSH AF; POP BC;
+        HALT
+    ]);
+    machine.run();
+    assert_eq!(machine.cpu.get_de(), 0x1234);
+    // The low bits of F don't exist
+    assert_eq!(machine.cpu.get_af(), 0x56F0);
+    assert_eq!(machine.cpu.get_bc(), 0x56F0);
+    assert_eq!(machine.cpu.SP, 0xE000);
+    // Pushed high byte first
+    assert_eq!(
+        [machine.bus.peek(0xDFFE), machine.bus.peek(0xDFFF)],
+        [0xF0, 0x56]
+    );
+}
+
+#[test]
+fn jumps() {
+    let mut machine = Machine::new(&asm![JP 0x200]).with(0x200, &asm![LD HL, 0x300; JP HL]);
+    machine = machine.with(0x300, &asm![JR 2; HALT; HALT; LD A, 0x42; HALT]);
+    machine.run();
+    assert_eq!(machine.cpu.Accumulator, 0x42);
+
+    let mut machine = Machine::new(&asm![JR 1; HALT; JR (-3)]);
+    machine.run();
+    assert_eq!(machine.cpu.PC, START + 3);
+}
+
+#[test]
+fn conditions() {
+    for (flags, expected) in [
+        (0, [true, false, true, false]),
+        (Z | C, [false, true, false, true]),
+    ] {
+        let programs = [
+            asm![JP NZ, 0x200; HALT],
+            asm![JP Z, 0x200; HALT],
+            asm![JR NC, 0x7E; HALT],
+            asm![JR C, 0x7E; HALT],
+        ];
+        for (program, taken) in programs.iter().zip(expected) {
+            let mut machine = Machine::new(program)
+                .with(0x180, &asm![HALT])
+                .with(0x200, &asm![HALT]);
+            machine.cpu.Flags = flags;
+            machine.run();
+            let jumped = machine.cpu.PC != START + program[0].byte_len() + 1;
+            assert_eq!(jumped, taken, "{} with flags {:02X}", program[0], flags);
+        }
+    }
+}
+
+#[test]
+fn calls_and_returns() {
+    let mut machine = Machine::new(&asm![CALL 0x200; LD B, A; HALT])
+        .with(0x200, &asm![LD A, 0x10; CALL Z, 0x300; CALL NZ, 0x300; RET])
+        .with(0x300, &asm![INC A; RET NZ; HALT]);
+    machine.run();
+    assert_eq!(machine.cpu.B, 0x11);
+    assert_eq!(machine.cpu.SP, 0xE000);
+    assert_eq!(machine.cpu.PC, START + 5);
+
+    // RET Z doesn't return with Z clear
+    let mut machine = Machine::new(&asm![CALL 0x200; HALT]).with(0x200, &asm![RET Z; LD A, 1; RET]);
+    machine.run();
+    assert_eq!(machine.cpu.Accumulator, 1);
+}
+
+#[test]
+fn rst_calls_its_vector() {
+    let mut machine = Machine::new(&asm![RST 0x28; HALT]).with(0x28, &asm![LD A, 0x28; RET]);
+    machine.run();
+    assert_eq!(machine.cpu.Accumulator, 0x28);
+    assert_eq!(machine.cpu.PC, START + 2);
+}
+
+#[test]
+fn interrupt_enable_and_reti() {
+    let mut machine = Machine::new(&asm![EI; DI; CALL 0x200; HALT]).with(0x200, &asm![RETI]);
+    machine.run();
+    assert!(machine.cpu.IME);
+    let mut machine = Machine::new(&asm![EI; DI; HALT]);
+    machine.run();
+    assert!(!machine.cpu.IME);
+}
+
+#[test]
+fn cycles_add_up() {
+    let mut machine =
+        Machine::new(&asm![NOP; LD A, 1; LD [0xC000], A; CALL 0x200; HALT]).with(0x200, &asm![RET]);
+    assert_eq!(machine.run(), 1 + 2 + 4 + 6 + 4 + 1);
+}
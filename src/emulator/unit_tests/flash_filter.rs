@@ -0,0 +1,68 @@
+use crate::emulator::ppu::{
+    flash_filter::{FlashFilter, MAX_STEP},
+    FrameBuffer,
+};
+
+fn filled(shade: u8) -> Box<FrameBuffer> {
+    Box::new([shade; std::mem::size_of::<FrameBuffer>()])
+}
+
+fn enabled() -> FlashFilter {
+    let mut filter = FlashFilter::default();
+    filter.set_enabled(true);
+    filter
+}
+
+#[test]
+fn off_by_default() {
+    let mut filter = FlashFilter::default();
+    assert!(!filter.enabled());
+    filter.apply(&filled(0));
+    assert_eq!(filter.apply(&filled(255))[0], 255);
+}
+
+#[test]
+fn first_frame_isnt_filtered() {
+    assert_eq!(enabled().apply(&filled(255))[0], 255);
+}
+
+#[test]
+fn small_changes_go_through() {
+    let mut filter = enabled();
+    filter.apply(&filled(100));
+    assert_eq!(filter.apply(&filled(100 + MAX_STEP as u8))[0], 124);
+}
+
+#[test]
+fn flash_is_dampened() {
+    let mut filter = enabled();
+    filter.apply(&filled(0));
+    assert_eq!(filter.apply(&filled(255))[0], MAX_STEP as u8);
+    // And gone again the next frame
+    assert_eq!(filter.apply(&filled(0))[0], 0);
+}
+
+#[test]
+fn only_the_average_is_limited() {
+    let mut filter = enabled();
+    let mut frame = filled(0);
+    filter.apply(&frame);
+    // Half the screen goes white, so the average moves by 127
+    let half = frame.len() / 2;
+    frame[half..].fill(254);
+    let shown = filter.apply(&frame);
+    assert_eq!(shown[0], 0);
+    assert_eq!(shown[half], (254 * MAX_STEP / 127) as u8);
+}
+
+#[test]
+fn settles_on_the_new_frame() {
+    let mut filter = enabled();
+    filter.apply(&filled(0));
+    let white = filled(255);
+    let mut last = 0;
+    for _ in 0..20 {
+        last = filter.apply(&white)[0];
+    }
+    assert_eq!(last, 255);
+}
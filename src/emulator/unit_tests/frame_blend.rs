@@ -0,0 +1,48 @@
+use crate::emulator::ppu::{frame_blend::FrameBlend, FrameBuffer};
+
+fn filled(shade: u8) -> Box<FrameBuffer> {
+    Box::new([shade; std::mem::size_of::<FrameBuffer>()])
+}
+
+#[test]
+fn off_by_default() {
+    let mut blend = FrameBlend::default();
+    assert!(!blend.enabled());
+    blend.apply(&filled(0));
+    assert_eq!(blend.apply(&filled(200))[0], 200);
+}
+
+#[test]
+fn first_frame_isnt_blended() {
+    let mut blend = FrameBlend::default();
+    blend.set_persistence(0.5);
+    assert_eq!(blend.apply(&filled(200))[0], 200);
+}
+
+#[test]
+fn flicker_looks_half_transparent() {
+    let mut blend = FrameBlend::default();
+    blend.set_persistence(0.5);
+    blend.apply(&filled(0));
+    assert_eq!(blend.apply(&filled(255))[0], 127);
+    assert_eq!(blend.apply(&filled(0))[0], 63);
+    assert_eq!(blend.apply(&filled(255))[0], 159);
+}
+
+#[test]
+fn settles_on_the_new_shade() {
+    let mut blend = FrameBlend::default();
+    blend.set_persistence(1.0);
+    blend.apply(&filled(0));
+    let white = filled(255);
+    let mut last = 0;
+    for _ in 0..1000 {
+        last = blend.apply(&white)[0];
+    }
+    assert_eq!(last, 255);
+    let black = filled(0);
+    for _ in 0..1000 {
+        last = blend.apply(&black)[0];
+    }
+    assert_eq!(last, 0);
+}
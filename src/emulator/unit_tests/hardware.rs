@@ -0,0 +1,314 @@
+use crate::emulator::{
+    hardware::{HardwareModel, PostBootRegisters},
+    memory_bus::{MemoryBus, BCPD, BCPS, OCPD, OCPS, OPRI, PCM12, PCM34, SVBK, VBK},
+    ppu::{
+        compat_palettes::{self, CompatPalette},
+        palettes::ObjectPriority,
+    },
+    Emulator,
+};
+
+fn bus(model: HardwareModel) -> MemoryBus {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    bus.set_model(model);
+    bus
+}
+
+/// A CGB game on a CGB
+fn cgb_bus() -> MemoryBus {
+    let mut rom = [0; 0x8000];
+    rom[0x143] = 0x80;
+    let mut bus = MemoryBus::new(&rom[..]);
+    bus.set_model(HardwareModel::Cgb);
+    assert!(bus.cgb_mode());
+    bus
+}
+
+#[test]
+fn prohibited_area_reads_zero_on_dmg() {
+    for model in [HardwareModel::Dmg, HardwareModel::Mgb, HardwareModel::Sgb2] {
+        let bus = bus(model);
+        assert_eq!(bus.read_u8(0xFEA0), 0x00);
+        assert_eq!(bus.read_u8(0xFEFF), 0x00);
+    }
+}
+
+#[test]
+fn prohibited_area_repeats_nibble_on_cgb() {
+    let bus = bus(HardwareModel::Cgb);
+    assert_eq!(bus.read_u8(0xFEA3), 0xAA);
+    assert_eq!(bus.read_u8(0xFEB0), 0xBB);
+    assert_eq!(bus.read_u8(0xFEFF), 0xFF);
+}
+
+#[test]
+fn prohibited_area_reads_ff_while_oam_is_blocked() {
+    for model in HardwareModel::ALL {
+        assert_eq!(model.prohibited_read(0xFEC0, true), 0xFF);
+    }
+    assert_eq!(HardwareModel::Dmg.prohibited_read(0xFEC0, false), 0x00);
+    assert_eq!(HardwareModel::Agb.prohibited_read(0xFEC0, false), 0xCC);
+}
+
+#[test]
+fn prohibited_area_ignores_writes() {
+    let mut bus = bus(HardwareModel::Dmg);
+    bus.set_strict(true);
+    bus.write_u8(0xFEA0, 0x12);
+    assert_eq!(bus.read_u8(0xFEA0), 0x00);
+    assert_eq!(bus.take_fault(), None);
+}
+
+#[test]
+fn model_names_round_trip() {
+    for model in HardwareModel::ALL {
+        assert_eq!(model.to_string().parse(), Ok(model));
+    }
+    assert_eq!("CGB".parse(), Ok(HardwareModel::Cgb));
+    assert!("gba".parse::<HardwareModel>().is_err());
+}
+
+#[test]
+fn post_boot_flags_depend_on_header_checksum() {
+    for model in [HardwareModel::Dmg, HardwareModel::Mgb] {
+        assert_eq!(model.post_boot_registers(0x00).f, 0x80);
+        assert_eq!(model.post_boot_registers(0x42).f, 0xB0);
+    }
+    assert_eq!(HardwareModel::Cgb.post_boot_registers(0x42).f, 0x80);
+}
+
+#[test]
+fn accumulator_identifies_the_model() {
+    // Games check A to see what they're running on
+    let a = |model: HardwareModel| model.post_boot_registers(0).a;
+    assert_eq!(a(HardwareModel::Dmg), 0x01);
+    assert_eq!(a(HardwareModel::Sgb), 0x01);
+    assert_eq!(a(HardwareModel::Mgb), 0xFF);
+    assert_eq!(a(HardwareModel::Sgb2), 0xFF);
+    assert_eq!(a(HardwareModel::Cgb), 0x11);
+    // B bit 0 tells a GBA from a GBC
+    assert_eq!(HardwareModel::Agb.post_boot_registers(0).b & 1, 1);
+    assert_eq!(HardwareModel::Cgb.post_boot_registers(0).b & 1, 0);
+}
+
+#[test]
+fn emulator_starts_with_post_boot_registers() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x14D] = 0x99;
+    let mut emulator = Emulator::new(&rom);
+    let cpu = emulator.cpu();
+    assert_eq!(
+        (
+            cpu.Accumulator,
+            cpu.Flags,
+            cpu.get_bc(),
+            cpu.get_de(),
+            cpu.get_hl()
+        ),
+        (0x01, 0xB0, 0x0013, 0x00D8, 0x014D)
+    );
+
+    emulator.set_model(HardwareModel::Sgb);
+    assert_eq!(emulator.model(), HardwareModel::Sgb);
+    let PostBootRegisters { a, h, l, .. } = HardwareModel::Sgb.post_boot_registers(0x99);
+    let cpu = emulator.cpu();
+    assert_eq!((cpu.Accumulator, cpu.H, cpu.L), (a, h, l));
+}
+
+#[test]
+fn quirks_follow_the_model() {
+    for model in HardwareModel::ALL {
+        let color = matches!(model, HardwareModel::Cgb | HardwareModel::Agb);
+        assert_eq!(model.has_oam_bug(), !color, "{}", model);
+        assert_eq!(model.has_stat_write_bug(), !color, "{}", model);
+        assert_eq!(model.is_color(), color, "{}", model);
+        let boot_rom = model.boot_rom();
+        assert!(boot_rom.file_name.starts_with(model.name()));
+        assert_eq!(boot_rom.size, if color { 0x900 } else { 0x100 });
+    }
+}
+
+#[test]
+fn svbk_switches_wram_banks_in_cgb_mode() {
+    let mut bus = cgb_bus();
+    assert_eq!(bus.read_u8(SVBK), 0xF8);
+    bus.write_u8(0xD000, 0x11);
+    bus.write_u8(SVBK, 0x03);
+    assert_eq!(bus.read_u8(SVBK), 0xFB);
+    assert_eq!(bus.read_u8(0xD000), 0x00);
+    bus.write_u8(0xD000, 0x33);
+    bus.write_u8(0xC000, 0xCC);
+    // Echo RAM follows, bank 0 doesn't move
+    assert_eq!(bus.read_u8(0xF000), 0x33);
+    bus.write_u8(SVBK, 0xF1);
+    assert_eq!(bus.read_u8(0xD000), 0x11);
+    assert_eq!(bus.read_u8(0xC000), 0xCC);
+
+    // Bank 0 means bank 1
+    bus.write_u8(SVBK, 0x00);
+    assert_eq!(bus.read_u8(SVBK), 0xF8);
+    assert_eq!(bus.read_u8(0xD000), 0x11);
+    bus.write_u8(SVBK, 0x03);
+    assert!(bus.take_written_pages().contains(0xD000));
+    assert_eq!(bus.read_u8(0xD000), 0x33);
+}
+
+#[test]
+fn nothing_is_banked_outside_cgb_mode() {
+    // A DMG game on a CGB is in compatibility mode
+    for model in [HardwareModel::Dmg, HardwareModel::Cgb] {
+        let mut bus = bus(model);
+        assert!(!bus.cgb_mode());
+        bus.write_u8(0xD000, 0x11);
+        bus.write_u8(0x8000, 0x22);
+        bus.write_u8(SVBK, 0x03);
+        bus.write_u8(VBK, 0x01);
+        assert_eq!(bus.read_u8(SVBK), 0xFF);
+        assert_eq!(bus.read_u8(VBK), 0xFF);
+        assert_eq!(bus.read_u8(0xD000), 0x11);
+        assert_eq!(bus.read_u8(0x8000), 0x22);
+    }
+
+    // Going back to a DMG puts the first banks back
+    let mut bus = cgb_bus();
+    bus.write_u8(0xD000, 0x11);
+    bus.write_u8(SVBK, 0x03);
+    bus.write_u8(VBK, 0x01);
+    bus.set_model(HardwareModel::Dmg);
+    assert_eq!(bus.read_u8(0xD000), 0x11);
+    assert_eq!(bus.vram(0, 0x8000), bus.read_u8(0x8000));
+}
+
+#[test]
+fn vbk_switches_vram_banks_in_cgb_mode() {
+    let mut bus = cgb_bus();
+    assert_eq!(bus.read_u8(VBK), 0xFE);
+    bus.write_u8(0x9800, 0x01);
+    bus.write_u8(VBK, 0xFF);
+    assert_eq!(bus.read_u8(VBK), 0xFF);
+    assert!(bus.take_written_pages().contains(0x9800));
+    assert_eq!(bus.read_u8(0x9800), 0x00);
+    bus.write_u8(0x9800, 0b0100_1101);
+    bus.write_u8(0x8000, 0xFF);
+    assert_eq!(bus.tile_row(1, 0x8000), &[1; 8]);
+    assert_eq!(bus.tile_row(0, 0x8000), &[0; 8]);
+
+    // The PPU sees both whichever is mapped
+    bus.write_u8(VBK, 0x00);
+    assert_eq!(bus.read_u8(0x9800), 0x01);
+    assert_eq!(bus.vram(1, 0x9800), 0b0100_1101);
+    let attributes = bus.tile_attributes(0x9800);
+    assert_eq!(attributes.palette, 5);
+    assert_eq!(attributes.bank, 1);
+    assert!(!attributes.x_flip);
+    assert!(attributes.y_flip);
+    assert!(!attributes.priority);
+}
+
+#[test]
+fn palette_data_auto_increments_on_writes() {
+    let mut bus = cgb_bus();
+    assert_eq!(bus.read_u8(BCPS), 0x40);
+    // Backgrounds start white
+    assert_eq!(bus.palettes().background_color(7, 3), 0xFFFF);
+
+    bus.write_u8(BCPS, 0x80 | 0x3E);
+    assert_eq!(bus.read_u8(BCPS), 0xFE);
+    bus.write_u8(BCPD, 0x1F);
+    bus.write_u8(BCPD, 0x00);
+    assert_eq!(bus.palettes().background_color(7, 3), 0x001F);
+    // Wraps around to the first byte, reads stay put
+    assert_eq!(bus.read_u8(BCPS), 0xC0);
+    assert_eq!(bus.read_u8(BCPD), 0xFF);
+    assert_eq!(bus.read_u8(BCPS), 0xC0);
+
+    // Without bit 7 the index doesn't move
+    bus.write_u8(OCPS, 0x0A);
+    bus.write_u8(OCPD, 0xE0);
+    bus.write_u8(OCPD, 0x03);
+    assert_eq!(bus.read_u8(OCPS), 0x4A);
+    assert_eq!(bus.read_u8(OCPD), 0x03);
+    assert_eq!(bus.palettes().object_color(1, 1), 0x0003);
+    assert_eq!(bus.palettes().background_color(1, 1), 0xFFFF);
+}
+
+#[test]
+fn object_priority_depends_on_the_game() {
+    let mut cgb = cgb_bus();
+    assert_eq!(cgb.read_u8(OPRI), 0xFE);
+    assert_eq!(cgb.object_priority(), ObjectPriority::OamIndex);
+    cgb.write_u8(OPRI, 0x01);
+    assert_eq!(cgb.read_u8(OPRI), 0xFF);
+    assert_eq!(cgb.object_priority(), ObjectPriority::XCoordinate);
+
+    // DMG games order them like a DMG would, on a CGB too
+    for model in [HardwareModel::Dmg, HardwareModel::Cgb] {
+        let mut bus = bus(model);
+        bus.write_u8(OPRI, 0x00);
+        assert_eq!(bus.read_u8(OPRI), 0xFF);
+        assert_eq!(bus.object_priority(), ObjectPriority::XCoordinate);
+    }
+}
+
+#[test]
+fn dmg_games_on_a_cgb_get_compat_colors() {
+    let mut bus = bus(HardwareModel::Cgb);
+    let palettes = bus.palettes();
+    assert_eq!(
+        palettes.background_color(0, 1),
+        compat_palettes::rgb555(0x7BFF31)
+    );
+    assert_eq!(
+        palettes.object_color(1, 1),
+        compat_palettes::rgb555(0xFF8484)
+    );
+
+    let red = "UP+A".parse().unwrap();
+    bus.set_compat_palette(red);
+    assert_eq!(bus.palettes().background_color(0, 1), 0x421F);
+    assert_eq!(bus.palettes().object_color(1, 2), 0x7C00);
+    // Kept over a reset
+    bus.reset();
+    assert_eq!(bus.palettes().object_color(1, 2), 0x7C00);
+
+    let mut cgb = cgb_bus();
+    cgb.set_compat_palette(red);
+    assert_eq!(cgb.palettes().background_color(0, 1), 0xFFFF);
+}
+
+#[test]
+fn compat_palettes_parse_by_buttons_or_name() {
+    assert_eq!("dark green".parse(), Ok(compat_palettes::DEFAULT));
+    assert_eq!("right+a".parse(), Ok(compat_palettes::DEFAULT));
+    assert_eq!("Left+B".parse::<CompatPalette>().unwrap().name, "Grayscale");
+    assert!("up+start".parse::<CompatPalette>().is_err());
+}
+
+#[test]
+fn undocumented_registers_hold_what_is_written() {
+    let mut cgb = cgb_bus();
+    for (addr, byte, read) in [
+        (0xFF72, 0x5A, 0x5A),
+        (0xFF73, 0xA5, 0xA5),
+        (0xFF74, 0x3C, 0x3C),
+        (0xFF75, 0xFF, 0xFF),
+        (0xFF75, 0x00, 0x8F),
+        (PCM12, 0x12, 0x00),
+        (PCM34, 0x34, 0x00),
+    ] {
+        cgb.write_u8(addr, byte);
+        assert_eq!(cgb.read_u8(addr), read, "{:#06X}", addr);
+    }
+
+    // FF74 is locked for DMG games, the rest are still there
+    let mut bus = bus(HardwareModel::Cgb);
+    bus.write_u8(0xFF72, 0x5A);
+    bus.write_u8(0xFF74, 0x3C);
+    assert_eq!(bus.read_u8(0xFF72), 0x5A);
+    assert_eq!(bus.read_u8(0xFF74), 0xFF);
+
+    let mut dmg = self::bus(HardwareModel::Dmg);
+    dmg.write_u8(0xFF72, 0x5A);
+    assert_eq!(dmg.read_u8(0xFF72), 0xFF);
+    assert_eq!(dmg.read_u8(PCM12), 0xFF);
+}
@@ -0,0 +1,90 @@
+use std::net::TcpListener;
+
+use crate::emulator::{
+    hardware::HardwareModel,
+    infrared::{file, link_pair, tcp, Incoming, Infrared, InfraredLink, Loopback, BURST_GAP, RP},
+    memory_bus::{mmio::MmioDevice, MemoryBus},
+};
+
+#[test]
+fn rp_is_only_there_in_cgb_mode() {
+    let mut rom = [0; 0x8000];
+    rom[0x143] = 0x80;
+    let mut bus = MemoryBus::new(&rom[..]);
+    bus.set_model(HardwareModel::Cgb);
+    assert_eq!(bus.read_u8(RP), 0x3E);
+    bus.write_u8(RP, 0xC1);
+    assert_eq!(bus.read_u8(RP), 0xFF);
+
+    let mut dmg = MemoryBus::new(&rom[..]);
+    dmg.write_u8(RP, 0xC1);
+    assert_eq!(dmg.read_u8(RP), 0xFF);
+}
+
+#[test]
+fn sensor_only_reads_when_enabled() {
+    let mut infrared = Infrared::default();
+    infrared.connect(Box::new(Loopback::default()));
+    infrared.write(RP, 0x01);
+    infrared.tick(4);
+    assert_eq!(infrared.read(RP), 0x3F);
+
+    infrared.write(RP, 0xC1);
+    infrared.tick(4);
+    assert_eq!(infrared.read(RP), 0xFD);
+    infrared.write(RP, 0xC0);
+    infrared.tick(4);
+    assert_eq!(infrared.read(RP), 0xFE);
+}
+
+#[test]
+fn channel_link_keeps_the_spacing() {
+    let (mut a, mut b) = link_pair();
+    a.led(100, true);
+    a.led(300, false);
+    // Their clock is far behind ours, the burst starts when it's seen
+    assert!(b.light(10_000));
+    assert!(b.light(10_199));
+    assert!(!b.light(10_200));
+    assert!(!a.light(10_200));
+
+    // A long pause starts over from whenever the next one arrives
+    a.led(300 + BURST_GAP, true);
+    assert!(b.light(50_000_000));
+}
+
+#[test]
+fn loaded_states_dont_stall_incoming() {
+    let mut incoming = Incoming::default();
+    incoming.push(0, true);
+    incoming.push(1000, false);
+    assert!(incoming.light(5_000_000));
+    // Our side went back in time, the next change shouldn't wait until it catches up
+    assert!(!incoming.light(4000));
+}
+
+#[test]
+fn recordings_parse() {
+    assert_eq!(
+        file::parse("10 1\n\n25 0\n"),
+        Ok(vec![(10, true), (25, false)])
+    );
+    assert!(file::parse("10 on").is_err());
+    assert!(file::parse("10 1\n5 0").is_err());
+}
+
+#[test]
+fn tcp_link_sends_changes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let host = std::thread::spawn(move || tcp::accept(&listener).unwrap());
+    let mut guest = tcp::connect(addr).unwrap();
+    let mut host = host.join().unwrap();
+
+    guest.led(7, true);
+    let mut cycle = 0;
+    while !host.light(cycle) {
+        cycle += 4;
+        std::thread::yield_now();
+    }
+}
@@ -8,7 +8,11 @@ use paste::paste;
 
 pub mod alu;
 pub mod bitwise;
+pub mod encode;
 pub mod load;
+pub mod opcodes;
+pub mod operands;
+pub mod timing;
 
 test_success!(nop, [0x00] => Instruction::Nop);
 test_success!(load_sp, [0x08, 0xAD, 0xDE] => Instruction::LoadIndirectSP(0xDEAD));
@@ -129,3 +133,17 @@ test_success!(reset20, [0xE7] => Instruction::Reset(0b100));
 test_success!(reset28, [0xEF] => Instruction::Reset(0b101));
 test_success!(reset30, [0xF7] => Instruction::Reset(0b110));
 test_success!(reset38, [0xFF] => Instruction::Reset(0b111));
+
+#[test]
+fn reset_calls_its_vector() {
+    use crate::emulator::{cpu::CPU, memory_bus::MemoryBus};
+
+    let mut rom = vec![0; 0x8000];
+    // RST 0x28
+    rom[0x100] = 0xEF;
+    let mut memory_bus = MemoryBus::new(&rom[..]);
+    let mut cpu = CPU::default();
+    cpu.tick(&mut memory_bus);
+    assert_eq!(cpu.PC, 0x28);
+    assert_eq!(memory_bus.read_stack_16(&mut cpu.SP), 0x101);
+}
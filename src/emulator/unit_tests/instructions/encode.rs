@@ -0,0 +1,42 @@
+use crate::emulator::instructions::{Instruction, Register8};
+
+#[test]
+fn encoding_gives_back_the_parsed_bytes() {
+    let plain = (0..=0xFF).map(|opcode| vec![opcode, 0x34, 0x12]);
+    let cb = (0..=0xFF).map(|opcode| vec![0xCB, opcode]);
+    for bytes in plain.chain(cb) {
+        let Ok((_, instruction)) = Instruction::parse(&bytes) else {
+            continue;
+        };
+        let encoded = instruction.encode();
+        assert_eq!(
+            encoded.len(),
+            instruction.byte_len() as usize,
+            "{}",
+            instruction
+        );
+        assert_eq!(
+            Instruction::parse(&encoded).unwrap().1,
+            instruction,
+            "{:02X?}",
+            encoded
+        );
+        if instruction != Instruction::Stop {
+            assert_eq!(encoded, bytes[..encoded.len()], "{}", instruction);
+        }
+    }
+}
+
+#[test]
+fn signed_operands_are_twos_complement() {
+    assert_eq!(Instruction::JumpRelative(-2).encode(), [0x18, 0xFE]);
+    assert_eq!(Instruction::LoadHLSP(-128).encode(), [0xF8, 0x80]);
+}
+
+#[test]
+fn hl_to_hl_is_halt() {
+    assert_eq!(
+        Instruction::Load(Register8::IndirectHL, Register8::IndirectHL).encode(),
+        Instruction::Halt.encode()
+    );
+}
@@ -0,0 +1,574 @@
+//! Every opcode against a table transcribed from the Game Boy opcode reference (gbdev's
+//! optables), written with [`inst!`](super::super::asm) so it doesn't share any code with the
+//! decoder. Immediates are read from `34 12`. Cycles are M-cycles with conditions not taken.
+use crate::emulator::instructions::Instruction;
+
+/// Opcode, what it decodes to, length, cycles. `None` for the CB prefix and the illegal ones.
+#[rustfmt::skip]
+const OPCODES: [(u8, Option<Instruction>, u16, u32); 256] = [
+    (0x00, Some(inst!(NOP)), 1, 1),
+    (0x01, Some(inst!(LD BC, 0x1234)), 3, 3),
+    (0x02, Some(inst!(LD [BC], A)), 1, 2),
+    (0x03, Some(inst!(INC BC)), 1, 2),
+    (0x04, Some(inst!(INC B)), 1, 1),
+    (0x05, Some(inst!(DEC B)), 1, 1),
+    (0x06, Some(inst!(LD B, 0x34)), 2, 2),
+    (0x07, Some(inst!(RLCA)), 1, 1),
+    (0x08, Some(inst!(LD [0x1234], SP)), 3, 5),
+    (0x09, Some(inst!(ADD HL, BC)), 1, 2),
+    (0x0A, Some(inst!(LD A, [BC])), 1, 2),
+    (0x0B, Some(inst!(DEC BC)), 1, 2),
+    (0x0C, Some(inst!(INC C)), 1, 1),
+    (0x0D, Some(inst!(DEC C)), 1, 1),
+    (0x0E, Some(inst!(LD C, 0x34)), 2, 2),
+    (0x0F, Some(inst!(RRCA)), 1, 1),
+    (0x10, Some(inst!(STOP)), 2, 1),
+    (0x11, Some(inst!(LD DE, 0x1234)), 3, 3),
+    (0x12, Some(inst!(LD [DE], A)), 1, 2),
+    (0x13, Some(inst!(INC DE)), 1, 2),
+    (0x14, Some(inst!(INC D)), 1, 1),
+    (0x15, Some(inst!(DEC D)), 1, 1),
+    (0x16, Some(inst!(LD D, 0x34)), 2, 2),
+    (0x17, Some(inst!(RLA)), 1, 1),
+    (0x18, Some(inst!(JR 0x34)), 2, 3),
+    (0x19, Some(inst!(ADD HL, DE)), 1, 2),
+    (0x1A, Some(inst!(LD A, [DE])), 1, 2),
+    (0x1B, Some(inst!(DEC DE)), 1, 2),
+    (0x1C, Some(inst!(INC E)), 1, 1),
+    (0x1D, Some(inst!(DEC E)), 1, 1),
+    (0x1E, Some(inst!(LD E, 0x34)), 2, 2),
+    (0x1F, Some(inst!(RRA)), 1, 1),
+    (0x20, Some(inst!(JR NZ, 0x34)), 2, 2),
+    (0x21, Some(inst!(LD HL, 0x1234)), 3, 3),
+    (0x22, Some(inst!(LD [HL+], A)), 1, 2),
+    (0x23, Some(inst!(INC HL)), 1, 2),
+    (0x24, Some(inst!(INC H)), 1, 1),
+    (0x25, Some(inst!(DEC H)), 1, 1),
+    (0x26, Some(inst!(LD H, 0x34)), 2, 2),
+    (0x27, Some(inst!(DAA)), 1, 1),
+    (0x28, Some(inst!(JR Z, 0x34)), 2, 2),
+    (0x29, Some(inst!(ADD HL, HL)), 1, 2),
+    (0x2A, Some(inst!(LD A, [HL+])), 1, 2),
+    (0x2B, Some(inst!(DEC HL)), 1, 2),
+    (0x2C, Some(inst!(INC L)), 1, 1),
+    (0x2D, Some(inst!(DEC L)), 1, 1),
+    (0x2E, Some(inst!(LD L, 0x34)), 2, 2),
+    (0x2F, Some(inst!(CPL)), 1, 1),
+    (0x30, Some(inst!(JR NC, 0x34)), 2, 2),
+    (0x31, Some(inst!(LD SP, 0x1234)), 3, 3),
+    (0x32, Some(inst!(LD [HL-], A)), 1, 2),
+    (0x33, Some(inst!(INC SP)), 1, 2),
+    (0x34, Some(inst!(INC [HL])), 1, 3),
+    (0x35, Some(inst!(DEC [HL])), 1, 3),
+    (0x36, Some(inst!(LD [HL], 0x34)), 2, 3),
+    (0x37, Some(inst!(SCF)), 1, 1),
+    (0x38, Some(inst!(JR C, 0x34)), 2, 2),
+    (0x39, Some(inst!(ADD HL, SP)), 1, 2),
+    (0x3A, Some(inst!(LD A, [HL-])), 1, 2),
+    (0x3B, Some(inst!(DEC SP)), 1, 2),
+    (0x3C, Some(inst!(INC A)), 1, 1),
+    (0x3D, Some(inst!(DEC A)), 1, 1),
+    (0x3E, Some(inst!(LD A, 0x34)), 2, 2),
+    (0x3F, Some(inst!(CCF)), 1, 1),
+    (0x40, Some(inst!(LD B, B)), 1, 1),
+    (0x41, Some(inst!(LD B, C)), 1, 1),
+    (0x42, Some(inst!(LD B, D)), 1, 1),
+    (0x43, Some(inst!(LD B, E)), 1, 1),
+    (0x44, Some(inst!(LD B, H)), 1, 1),
+    (0x45, Some(inst!(LD B, L)), 1, 1),
+    (0x46, Some(inst!(LD B, [HL])), 1, 2),
+    (0x47, Some(inst!(LD B, A)), 1, 1),
+    (0x48, Some(inst!(LD C, B)), 1, 1),
+    (0x49, Some(inst!(LD C, C)), 1, 1),
+    (0x4A, Some(inst!(LD C, D)), 1, 1),
+    (0x4B, Some(inst!(LD C, E)), 1, 1),
+    (0x4C, Some(inst!(LD C, H)), 1, 1),
+    (0x4D, Some(inst!(LD C, L)), 1, 1),
+    (0x4E, Some(inst!(LD C, [HL])), 1, 2),
+    (0x4F, Some(inst!(LD C, A)), 1, 1),
+    (0x50, Some(inst!(LD D, B)), 1, 1),
+    (0x51, Some(inst!(LD D, C)), 1, 1),
+    (0x52, Some(inst!(LD D, D)), 1, 1),
+    (0x53, Some(inst!(LD D, E)), 1, 1),
+    (0x54, Some(inst!(LD D, H)), 1, 1),
+    (0x55, Some(inst!(LD D, L)), 1, 1),
+    (0x56, Some(inst!(LD D, [HL])), 1, 2),
+    (0x57, Some(inst!(LD D, A)), 1, 1),
+    (0x58, Some(inst!(LD E, B)), 1, 1),
+    (0x59, Some(inst!(LD E, C)), 1, 1),
+    (0x5A, Some(inst!(LD E, D)), 1, 1),
+    (0x5B, Some(inst!(LD E, E)), 1, 1),
+    (0x5C, Some(inst!(LD E, H)), 1, 1),
+    (0x5D, Some(inst!(LD E, L)), 1, 1),
+    (0x5E, Some(inst!(LD E, [HL])), 1, 2),
+    (0x5F, Some(inst!(LD E, A)), 1, 1),
+    (0x60, Some(inst!(LD H, B)), 1, 1),
+    (0x61, Some(inst!(LD H, C)), 1, 1),
+    (0x62, Some(inst!(LD H, D)), 1, 1),
+    (0x63, Some(inst!(LD H, E)), 1, 1),
+    (0x64, Some(inst!(LD H, H)), 1, 1),
+    (0x65, Some(inst!(LD H, L)), 1, 1),
+    (0x66, Some(inst!(LD H, [HL])), 1, 2),
+    (0x67, Some(inst!(LD H, A)), 1, 1),
+    (0x68, Some(inst!(LD L, B)), 1, 1),
+    (0x69, Some(inst!(LD L, C)), 1, 1),
+    (0x6A, Some(inst!(LD L, D)), 1, 1),
+    (0x6B, Some(inst!(LD L, E)), 1, 1),
+    (0x6C, Some(inst!(LD L, H)), 1, 1),
+    (0x6D, Some(inst!(LD L, L)), 1, 1),
+    (0x6E, Some(inst!(LD L, [HL])), 1, 2),
+    (0x6F, Some(inst!(LD L, A)), 1, 1),
+    (0x70, Some(inst!(LD [HL], B)), 1, 2),
+    (0x71, Some(inst!(LD [HL], C)), 1, 2),
+    (0x72, Some(inst!(LD [HL], D)), 1, 2),
+    (0x73, Some(inst!(LD [HL], E)), 1, 2),
+    (0x74, Some(inst!(LD [HL], H)), 1, 2),
+    (0x75, Some(inst!(LD [HL], L)), 1, 2),
+    (0x76, Some(inst!(HALT)), 1, 1),
+    (0x77, Some(inst!(LD [HL], A)), 1, 2),
+    (0x78, Some(inst!(LD A, B)), 1, 1),
+    (0x79, Some(inst!(LD A, C)), 1, 1),
+    (0x7A, Some(inst!(LD A, D)), 1, 1),
+    (0x7B, Some(inst!(LD A, E)), 1, 1),
+    (0x7C, Some(inst!(LD A, H)), 1, 1),
+    (0x7D, Some(inst!(LD A, L)), 1, 1),
+    (0x7E, Some(inst!(LD A, [HL])), 1, 2),
+    (0x7F, Some(inst!(LD A, A)), 1, 1),
+    (0x80, Some(inst!(ADD A, B)), 1, 1),
+    (0x81, Some(inst!(ADD A, C)), 1, 1),
+    (0x82, Some(inst!(ADD A, D)), 1, 1),
+    (0x83, Some(inst!(ADD A, E)), 1, 1),
+    (0x84, Some(inst!(ADD A, H)), 1, 1),
+    (0x85, Some(inst!(ADD A, L)), 1, 1),
+    (0x86, Some(inst!(ADD A, [HL])), 1, 2),
+    (0x87, Some(inst!(ADD A, A)), 1, 1),
+    (0x88, Some(inst!(ADC A, B)), 1, 1),
+    (0x89, Some(inst!(ADC A, C)), 1, 1),
+    (0x8A, Some(inst!(ADC A, D)), 1, 1),
+    (0x8B, Some(inst!(ADC A, E)), 1, 1),
+    (0x8C, Some(inst!(ADC A, H)), 1, 1),
+    (0x8D, Some(inst!(ADC A, L)), 1, 1),
+    (0x8E, Some(inst!(ADC A, [HL])), 1, 2),
+    (0x8F, Some(inst!(ADC A, A)), 1, 1),
+    (0x90, Some(inst!(SUB A, B)), 1, 1),
+    (0x91, Some(inst!(SUB A, C)), 1, 1),
+    (0x92, Some(inst!(SUB A, D)), 1, 1),
+    (0x93, Some(inst!(SUB A, E)), 1, 1),
+    (0x94, Some(inst!(SUB A, H)), 1, 1),
+    (0x95, Some(inst!(SUB A, L)), 1, 1),
+    (0x96, Some(inst!(SUB A, [HL])), 1, 2),
+    (0x97, Some(inst!(SUB A, A)), 1, 1),
+    (0x98, Some(inst!(SBC A, B)), 1, 1),
+    (0x99, Some(inst!(SBC A, C)), 1, 1),
+    (0x9A, Some(inst!(SBC A, D)), 1, 1),
+    (0x9B, Some(inst!(SBC A, E)), 1, 1),
+    (0x9C, Some(inst!(SBC A, H)), 1, 1),
+    (0x9D, Some(inst!(SBC A, L)), 1, 1),
+    (0x9E, Some(inst!(SBC A, [HL])), 1, 2),
+    (0x9F, Some(inst!(SBC A, A)), 1, 1),
+    (0xA0, Some(inst!(AND A, B)), 1, 1),
+    (0xA1, Some(inst!(AND A, C)), 1, 1),
+    (0xA2, Some(inst!(AND A, D)), 1, 1),
+    (0xA3, Some(inst!(AND A, E)), 1, 1),
+    (0xA4, Some(inst!(AND A, H)), 1, 1),
+    (0xA5, Some(inst!(AND A, L)), 1, 1),
+    (0xA6, Some(inst!(AND A, [HL])), 1, 2),
+    (0xA7, Some(inst!(AND A, A)), 1, 1),
+    (0xA8, Some(inst!(XOR A, B)), 1, 1),
+    (0xA9, Some(inst!(XOR A, C)), 1, 1),
+    (0xAA, Some(inst!(XOR A, D)), 1, 1),
+    (0xAB, Some(inst!(XOR A, E)), 1, 1),
+    (0xAC, Some(inst!(XOR A, H)), 1, 1),
+    (0xAD, Some(inst!(XOR A, L)), 1, 1),
+    (0xAE, Some(inst!(XOR A, [HL])), 1, 2),
+    (0xAF, Some(inst!(XOR A, A)), 1, 1),
+    (0xB0, Some(inst!(OR A, B)), 1, 1),
+    (0xB1, Some(inst!(OR A, C)), 1, 1),
+    (0xB2, Some(inst!(OR A, D)), 1, 1),
+    (0xB3, Some(inst!(OR A, E)), 1, 1),
+    (0xB4, Some(inst!(OR A, H)), 1, 1),
+    (0xB5, Some(inst!(OR A, L)), 1, 1),
+    (0xB6, Some(inst!(OR A, [HL])), 1, 2),
+    (0xB7, Some(inst!(OR A, A)), 1, 1),
+    (0xB8, Some(inst!(CP A, B)), 1, 1),
+    (0xB9, Some(inst!(CP A, C)), 1, 1),
+    (0xBA, Some(inst!(CP A, D)), 1, 1),
+    (0xBB, Some(inst!(CP A, E)), 1, 1),
+    (0xBC, Some(inst!(CP A, H)), 1, 1),
+    (0xBD, Some(inst!(CP A, L)), 1, 1),
+    (0xBE, Some(inst!(CP A, [HL])), 1, 2),
+    (0xBF, Some(inst!(CP A, A)), 1, 1),
+    (0xC0, Some(inst!(RET NZ)), 1, 2),
+    (0xC1, Some(inst!(POP BC)), 1, 3),
+    (0xC2, Some(inst!(JP NZ, 0x1234)), 3, 3),
+    (0xC3, Some(inst!(JP 0x1234)), 3, 4),
+    (0xC4, Some(inst!(CALL NZ, 0x1234)), 3, 3),
+    (0xC5, Some(inst!(PUSH BC)), 1, 4),
+    (0xC6, Some(inst!(ADD A, 0x34)), 2, 2),
+    (0xC7, Some(inst!(RST 0x00)), 1, 4),
+    (0xC8, Some(inst!(RET Z)), 1, 2),
+    (0xC9, Some(inst!(RET)), 1, 4),
+    (0xCA, Some(inst!(JP Z, 0x1234)), 3, 3),
+    (0xCB, None, 0, 0), // prefix
+    (0xCC, Some(inst!(CALL Z, 0x1234)), 3, 3),
+    (0xCD, Some(inst!(CALL 0x1234)), 3, 6),
+    (0xCE, Some(inst!(ADC A, 0x34)), 2, 2),
+    (0xCF, Some(inst!(RST 0x08)), 1, 4),
+    (0xD0, Some(inst!(RET NC)), 1, 2),
+    (0xD1, Some(inst!(POP DE)), 1, 3),
+    (0xD2, Some(inst!(JP NC, 0x1234)), 3, 3),
+    (0xD3, None, 0, 0), // illegal
+    (0xD4, Some(inst!(CALL NC, 0x1234)), 3, 3),
+    (0xD5, Some(inst!(PUSH DE)), 1, 4),
+    (0xD6, Some(inst!(SUB A, 0x34)), 2, 2),
+    (0xD7, Some(inst!(RST 0x10)), 1, 4),
+    (0xD8, Some(inst!(RET C)), 1, 2),
+    (0xD9, Some(inst!(RETI)), 1, 4),
+    (0xDA, Some(inst!(JP C, 0x1234)), 3, 3),
+    (0xDB, None, 0, 0), // illegal
+    (0xDC, Some(inst!(CALL C, 0x1234)), 3, 3),
+    (0xDD, None, 0, 0), // illegal
+    (0xDE, Some(inst!(SBC A, 0x34)), 2, 2),
+    (0xDF, Some(inst!(RST 0x18)), 1, 4),
+    (0xE0, Some(inst!(LDH [0x34], A)), 2, 3),
+    (0xE1, Some(inst!(POP HL)), 1, 3),
+    (0xE2, Some(inst!(LDH [C], A)), 1, 2),
+    (0xE3, None, 0, 0), // illegal
+    (0xE4, None, 0, 0), // illegal
+    (0xE5, Some(inst!(PUSH HL)), 1, 4),
+    (0xE6, Some(inst!(AND A, 0x34)), 2, 2),
+    (0xE7, Some(inst!(RST 0x20)), 1, 4),
+    (0xE8, Some(inst!(ADD SP, 0x34)), 2, 4),
+    (0xE9, Some(inst!(JP HL)), 1, 1),
+    (0xEA, Some(inst!(LD [0x1234], A)), 3, 4),
+    (0xEB, None, 0, 0), // illegal
+    (0xEC, None, 0, 0), // illegal
+    (0xED, None, 0, 0), // illegal
+    (0xEE, Some(inst!(XOR A, 0x34)), 2, 2),
+    (0xEF, Some(inst!(RST 0x28)), 1, 4),
+    (0xF0, Some(inst!(LDH A, [0x34])), 2, 3),
+    (0xF1, Some(inst!(POP AF)), 1, 3),
+    (0xF2, Some(inst!(LDH A, [C])), 1, 2),
+    (0xF3, Some(inst!(DI)), 1, 1),
+    (0xF4, None, 0, 0), // illegal
+    (0xF5, Some(inst!(PUSH AF)), 1, 4),
+    (0xF6, Some(inst!(OR A, 0x34)), 2, 2),
+    (0xF7, Some(inst!(RST 0x30)), 1, 4),
+    (0xF8, Some(inst!(LDHL SP, 0x34)), 2, 3),
+    (0xF9, Some(inst!(LD SP, HL)), 1, 2),
+    (0xFA, Some(inst!(LD A, [0x1234])), 3, 4),
+    (0xFB, Some(inst!(EI)), 1, 1),
+    (0xFC, None, 0, 0), // illegal
+    (0xFD, None, 0, 0), // illegal
+    (0xFE, Some(inst!(CP A, 0x34)), 2, 2),
+    (0xFF, Some(inst!(RST 0x38)), 1, 4),
+];
+
+/// Opcode after the CB, what it decodes to, cycles. They're all 2 bytes long.
+#[rustfmt::skip]
+const CB_OPCODES: [(u8, Instruction, u32); 256] = [
+    (0x00, inst!(RLC B), 2),
+    (0x01, inst!(RLC C), 2),
+    (0x02, inst!(RLC D), 2),
+    (0x03, inst!(RLC E), 2),
+    (0x04, inst!(RLC H), 2),
+    (0x05, inst!(RLC L), 2),
+    (0x06, inst!(RLC [HL]), 4),
+    (0x07, inst!(RLC A), 2),
+    (0x08, inst!(RRC B), 2),
+    (0x09, inst!(RRC C), 2),
+    (0x0A, inst!(RRC D), 2),
+    (0x0B, inst!(RRC E), 2),
+    (0x0C, inst!(RRC H), 2),
+    (0x0D, inst!(RRC L), 2),
+    (0x0E, inst!(RRC [HL]), 4),
+    (0x0F, inst!(RRC A), 2),
+    (0x10, inst!(RL B), 2),
+    (0x11, inst!(RL C), 2),
+    (0x12, inst!(RL D), 2),
+    (0x13, inst!(RL E), 2),
+    (0x14, inst!(RL H), 2),
+    (0x15, inst!(RL L), 2),
+    (0x16, inst!(RL [HL]), 4),
+    (0x17, inst!(RL A), 2),
+    (0x18, inst!(RR B), 2),
+    (0x19, inst!(RR C), 2),
+    (0x1A, inst!(RR D), 2),
+    (0x1B, inst!(RR E), 2),
+    (0x1C, inst!(RR H), 2),
+    (0x1D, inst!(RR L), 2),
+    (0x1E, inst!(RR [HL]), 4),
+    (0x1F, inst!(RR A), 2),
+    (0x20, inst!(SLA B), 2),
+    (0x21, inst!(SLA C), 2),
+    (0x22, inst!(SLA D), 2),
+    (0x23, inst!(SLA E), 2),
+    (0x24, inst!(SLA H), 2),
+    (0x25, inst!(SLA L), 2),
+    (0x26, inst!(SLA [HL]), 4),
+    (0x27, inst!(SLA A), 2),
+    (0x28, inst!(SRA B), 2),
+    (0x29, inst!(SRA C), 2),
+    (0x2A, inst!(SRA D), 2),
+    (0x2B, inst!(SRA E), 2),
+    (0x2C, inst!(SRA H), 2),
+    (0x2D, inst!(SRA L), 2),
+    (0x2E, inst!(SRA [HL]), 4),
+    (0x2F, inst!(SRA A), 2),
+    (0x30, inst!(SWAP B), 2),
+    (0x31, inst!(SWAP C), 2),
+    (0x32, inst!(SWAP D), 2),
+    (0x33, inst!(SWAP E), 2),
+    (0x34, inst!(SWAP H), 2),
+    (0x35, inst!(SWAP L), 2),
+    (0x36, inst!(SWAP [HL]), 4),
+    (0x37, inst!(SWAP A), 2),
+    (0x38, inst!(SRL B), 2),
+    (0x39, inst!(SRL C), 2),
+    (0x3A, inst!(SRL D), 2),
+    (0x3B, inst!(SRL E), 2),
+    (0x3C, inst!(SRL H), 2),
+    (0x3D, inst!(SRL L), 2),
+    (0x3E, inst!(SRL [HL]), 4),
+    (0x3F, inst!(SRL A), 2),
+    (0x40, inst!(BIT 0, B), 2),
+    (0x41, inst!(BIT 0, C), 2),
+    (0x42, inst!(BIT 0, D), 2),
+    (0x43, inst!(BIT 0, E), 2),
+    (0x44, inst!(BIT 0, H), 2),
+    (0x45, inst!(BIT 0, L), 2),
+    (0x46, inst!(BIT 0, [HL]), 3),
+    (0x47, inst!(BIT 0, A), 2),
+    (0x48, inst!(BIT 1, B), 2),
+    (0x49, inst!(BIT 1, C), 2),
+    (0x4A, inst!(BIT 1, D), 2),
+    (0x4B, inst!(BIT 1, E), 2),
+    (0x4C, inst!(BIT 1, H), 2),
+    (0x4D, inst!(BIT 1, L), 2),
+    (0x4E, inst!(BIT 1, [HL]), 3),
+    (0x4F, inst!(BIT 1, A), 2),
+    (0x50, inst!(BIT 2, B), 2),
+    (0x51, inst!(BIT 2, C), 2),
+    (0x52, inst!(BIT 2, D), 2),
+    (0x53, inst!(BIT 2, E), 2),
+    (0x54, inst!(BIT 2, H), 2),
+    (0x55, inst!(BIT 2, L), 2),
+    (0x56, inst!(BIT 2, [HL]), 3),
+    (0x57, inst!(BIT 2, A), 2),
+    (0x58, inst!(BIT 3, B), 2),
+    (0x59, inst!(BIT 3, C), 2),
+    (0x5A, inst!(BIT 3, D), 2),
+    (0x5B, inst!(BIT 3, E), 2),
+    (0x5C, inst!(BIT 3, H), 2),
+    (0x5D, inst!(BIT 3, L), 2),
+    (0x5E, inst!(BIT 3, [HL]), 3),
+    (0x5F, inst!(BIT 3, A), 2),
+    (0x60, inst!(BIT 4, B), 2),
+    (0x61, inst!(BIT 4, C), 2),
+    (0x62, inst!(BIT 4, D), 2),
+    (0x63, inst!(BIT 4, E), 2),
+    (0x64, inst!(BIT 4, H), 2),
+    (0x65, inst!(BIT 4, L), 2),
+    (0x66, inst!(BIT 4, [HL]), 3),
+    (0x67, inst!(BIT 4, A), 2),
+    (0x68, inst!(BIT 5, B), 2),
+    (0x69, inst!(BIT 5, C), 2),
+    (0x6A, inst!(BIT 5, D), 2),
+    (0x6B, inst!(BIT 5, E), 2),
+    (0x6C, inst!(BIT 5, H), 2),
+    (0x6D, inst!(BIT 5, L), 2),
+    (0x6E, inst!(BIT 5, [HL]), 3),
+    (0x6F, inst!(BIT 5, A), 2),
+    (0x70, inst!(BIT 6, B), 2),
+    (0x71, inst!(BIT 6, C), 2),
+    (0x72, inst!(BIT 6, D), 2),
+    (0x73, inst!(BIT 6, E), 2),
+    (0x74, inst!(BIT 6, H), 2),
+    (0x75, inst!(BIT 6, L), 2),
+    (0x76, inst!(BIT 6, [HL]), 3),
+    (0x77, inst!(BIT 6, A), 2),
+    (0x78, inst!(BIT 7, B), 2),
+    (0x79, inst!(BIT 7, C), 2),
+    (0x7A, inst!(BIT 7, D), 2),
+    (0x7B, inst!(BIT 7, E), 2),
+    (0x7C, inst!(BIT 7, H), 2),
+    (0x7D, inst!(BIT 7, L), 2),
+    (0x7E, inst!(BIT 7, [HL]), 3),
+    (0x7F, inst!(BIT 7, A), 2),
+    (0x80, inst!(RES 0, B), 2),
+    (0x81, inst!(RES 0, C), 2),
+    (0x82, inst!(RES 0, D), 2),
+    (0x83, inst!(RES 0, E), 2),
+    (0x84, inst!(RES 0, H), 2),
+    (0x85, inst!(RES 0, L), 2),
+    (0x86, inst!(RES 0, [HL]), 4),
+    (0x87, inst!(RES 0, A), 2),
+    (0x88, inst!(RES 1, B), 2),
+    (0x89, inst!(RES 1, C), 2),
+    (0x8A, inst!(RES 1, D), 2),
+    (0x8B, inst!(RES 1, E), 2),
+    (0x8C, inst!(RES 1, H), 2),
+    (0x8D, inst!(RES 1, L), 2),
+    (0x8E, inst!(RES 1, [HL]), 4),
+    (0x8F, inst!(RES 1, A), 2),
+    (0x90, inst!(RES 2, B), 2),
+    (0x91, inst!(RES 2, C), 2),
+    (0x92, inst!(RES 2, D), 2),
+    (0x93, inst!(RES 2, E), 2),
+    (0x94, inst!(RES 2, H), 2),
+    (0x95, inst!(RES 2, L), 2),
+    (0x96, inst!(RES 2, [HL]), 4),
+    (0x97, inst!(RES 2, A), 2),
+    (0x98, inst!(RES 3, B), 2),
+    (0x99, inst!(RES 3, C), 2),
+    (0x9A, inst!(RES 3, D), 2),
+    (0x9B, inst!(RES 3, E), 2),
+    (0x9C, inst!(RES 3, H), 2),
+    (0x9D, inst!(RES 3, L), 2),
+    (0x9E, inst!(RES 3, [HL]), 4),
+    (0x9F, inst!(RES 3, A), 2),
+    (0xA0, inst!(RES 4, B), 2),
+    (0xA1, inst!(RES 4, C), 2),
+    (0xA2, inst!(RES 4, D), 2),
+    (0xA3, inst!(RES 4, E), 2),
+    (0xA4, inst!(RES 4, H), 2),
+    (0xA5, inst!(RES 4, L), 2),
+    (0xA6, inst!(RES 4, [HL]), 4),
+    (0xA7, inst!(RES 4, A), 2),
+    (0xA8, inst!(RES 5, B), 2),
+    (0xA9, inst!(RES 5, C), 2),
+    (0xAA, inst!(RES 5, D), 2),
+    (0xAB, inst!(RES 5, E), 2),
+    (0xAC, inst!(RES 5, H), 2),
+    (0xAD, inst!(RES 5, L), 2),
+    (0xAE, inst!(RES 5, [HL]), 4),
+    (0xAF, inst!(RES 5, A), 2),
+    (0xB0, inst!(RES 6, B), 2),
+    (0xB1, inst!(RES 6, C), 2),
+    (0xB2, inst!(RES 6, D), 2),
+    (0xB3, inst!(RES 6, E), 2),
+    (0xB4, inst!(RES 6, H), 2),
+    (0xB5, inst!(RES 6, L), 2),
+    (0xB6, inst!(RES 6, [HL]), 4),
+    (0xB7, inst!(RES 6, A), 2),
+    (0xB8, inst!(RES 7, B), 2),
+    (0xB9, inst!(RES 7, C), 2),
+    (0xBA, inst!(RES 7, D), 2),
+    (0xBB, inst!(RES 7, E), 2),
+    (0xBC, inst!(RES 7, H), 2),
+    (0xBD, inst!(RES 7, L), 2),
+    (0xBE, inst!(RES 7, [HL]), 4),
+    (0xBF, inst!(RES 7, A), 2),
+    (0xC0, inst!(SET 0, B), 2),
+    (0xC1, inst!(SET 0, C), 2),
+    (0xC2, inst!(SET 0, D), 2),
+    (0xC3, inst!(SET 0, E), 2),
+    (0xC4, inst!(SET 0, H), 2),
+    (0xC5, inst!(SET 0, L), 2),
+    (0xC6, inst!(SET 0, [HL]), 4),
+    (0xC7, inst!(SET 0, A), 2),
+    (0xC8, inst!(SET 1, B), 2),
+    (0xC9, inst!(SET 1, C), 2),
+    (0xCA, inst!(SET 1, D), 2),
+    (0xCB, inst!(SET 1, E), 2),
+    (0xCC, inst!(SET 1, H), 2),
+    (0xCD, inst!(SET 1, L), 2),
+    (0xCE, inst!(SET 1, [HL]), 4),
+    (0xCF, inst!(SET 1, A), 2),
+    (0xD0, inst!(SET 2, B), 2),
+    (0xD1, inst!(SET 2, C), 2),
+    (0xD2, inst!(SET 2, D), 2),
+    (0xD3, inst!(SET 2, E), 2),
+    (0xD4, inst!(SET 2, H), 2),
+    (0xD5, inst!(SET 2, L), 2),
+    (0xD6, inst!(SET 2, [HL]), 4),
+    (0xD7, inst!(SET 2, A), 2),
+    (0xD8, inst!(SET 3, B), 2),
+    (0xD9, inst!(SET 3, C), 2),
+    (0xDA, inst!(SET 3, D), 2),
+    (0xDB, inst!(SET 3, E), 2),
+    (0xDC, inst!(SET 3, H), 2),
+    (0xDD, inst!(SET 3, L), 2),
+    (0xDE, inst!(SET 3, [HL]), 4),
+    (0xDF, inst!(SET 3, A), 2),
+    (0xE0, inst!(SET 4, B), 2),
+    (0xE1, inst!(SET 4, C), 2),
+    (0xE2, inst!(SET 4, D), 2),
+    (0xE3, inst!(SET 4, E), 2),
+    (0xE4, inst!(SET 4, H), 2),
+    (0xE5, inst!(SET 4, L), 2),
+    (0xE6, inst!(SET 4, [HL]), 4),
+    (0xE7, inst!(SET 4, A), 2),
+    (0xE8, inst!(SET 5, B), 2),
+    (0xE9, inst!(SET 5, C), 2),
+    (0xEA, inst!(SET 5, D), 2),
+    (0xEB, inst!(SET 5, E), 2),
+    (0xEC, inst!(SET 5, H), 2),
+    (0xED, inst!(SET 5, L), 2),
+    (0xEE, inst!(SET 5, [HL]), 4),
+    (0xEF, inst!(SET 5, A), 2),
+    (0xF0, inst!(SET 6, B), 2),
+    (0xF1, inst!(SET 6, C), 2),
+    (0xF2, inst!(SET 6, D), 2),
+    (0xF3, inst!(SET 6, E), 2),
+    (0xF4, inst!(SET 6, H), 2),
+    (0xF5, inst!(SET 6, L), 2),
+    (0xF6, inst!(SET 6, [HL]), 4),
+    (0xF7, inst!(SET 6, A), 2),
+    (0xF8, inst!(SET 7, B), 2),
+    (0xF9, inst!(SET 7, C), 2),
+    (0xFA, inst!(SET 7, D), 2),
+    (0xFB, inst!(SET 7, E), 2),
+    (0xFC, inst!(SET 7, H), 2),
+    (0xFD, inst!(SET 7, L), 2),
+    (0xFE, inst!(SET 7, [HL]), 4),
+    (0xFF, inst!(SET 7, A), 2),
+];
+
+#[test]
+fn every_opcode_decodes_like_the_table() {
+    for (i, &(opcode, expected, len, cycles)) in OPCODES.iter().enumerate() {
+        assert_eq!(opcode as usize, i);
+        let bytes = [opcode, 0x34, 0x12];
+        let Some(expected) = expected else {
+            if opcode != 0xCB {
+                assert!(
+                    Instruction::parse(&bytes).is_err(),
+                    "{:02X} is illegal",
+                    opcode
+                );
+            }
+            continue;
+        };
+        let (_, instruction) =
+            Instruction::parse(&bytes).unwrap_or_else(|_| panic!("{:02X} didn't decode", opcode));
+        assert_eq!(instruction, expected, "{:02X}", opcode);
+        assert_eq!(
+            instruction.byte_len(),
+            len,
+            "{:02X} {}",
+            opcode,
+            instruction
+        );
+        assert_eq!(
+            instruction.ticks(false),
+            cycles,
+            "{:02X} {}",
+            opcode,
+            instruction
+        );
+    }
+}
+
+#[test]
+fn every_cb_opcode_decodes_like_the_table() {
+    for (i, &(opcode, expected, cycles)) in CB_OPCODES.iter().enumerate() {
+        assert_eq!(opcode as usize, i);
+        let bytes = [0xCB, opcode];
+        let (rest, instruction) = Instruction::parse(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(instruction, expected, "CB {:02X}", opcode);
+        assert_eq!(instruction.byte_len(), 2, "CB {:02X}", opcode);
+        assert_eq!(instruction.ticks(false), cycles, "CB {:02X}", opcode);
+    }
+}
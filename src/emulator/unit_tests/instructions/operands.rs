@@ -0,0 +1,115 @@
+use crate::emulator::{
+    cpu::CPU,
+    instructions::{
+        operands::Operand, Condition, Instruction, Register16, Register16Indirect, Register8,
+    },
+    memory_bus::MemoryBus,
+};
+
+fn parse(bytes: &[u8]) -> Instruction {
+    Instruction::parse(bytes).unwrap().1
+}
+
+/// Every legal opcode, CB ones included, with zeroed operands
+fn every_instruction() -> impl Iterator<Item = (Vec<u8>, Instruction)> {
+    let plain = (0..=0xFF).map(|opcode| vec![opcode, 0, 0]);
+    let cb = (0..=0xFF).map(|opcode| vec![0xCB, opcode]);
+    plain
+        .chain(cb)
+        .filter_map(|bytes| Some((bytes.clone(), Instruction::parse(&bytes).ok()?.1)))
+}
+
+#[test]
+fn operands_are_in_assembly_order() {
+    assert_eq!(
+        parse(&[0x22]).operands(),
+        [
+            Operand::Indirect(Register16Indirect::HLI),
+            Operand::Register8(Register8::A)
+        ]
+    );
+    assert_eq!(
+        parse(&[0xF8, 0xFE]).operands(),
+        [
+            Operand::Register16(Register16::HL),
+            Operand::Register16(Register16::SP),
+            Operand::Offset(-2)
+        ]
+    );
+    assert_eq!(
+        parse(&[0xCA, 0x34, 0x12]).operands(),
+        [
+            Operand::Condition(Condition::Z),
+            Operand::Immediate16(0x1234)
+        ]
+    );
+    assert_eq!(
+        parse(&[0xCB, 0x7E]).operands(),
+        [Operand::Bit(7), Operand::Register8(Register8::IndirectHL)]
+    );
+    assert_eq!(parse(&[0xFF]).operands(), [Operand::Vector(0x38)]);
+    assert!(parse(&[0xC9]).operands().is_empty());
+}
+
+#[test]
+fn branch_targets() {
+    assert_eq!(parse(&[0xC3, 0x50, 0x01]).branch_target(0x100), Some(0x150));
+    assert_eq!(parse(&[0x18, 0xFE]).branch_target(0x100), Some(0x100));
+    assert_eq!(parse(&[0x38, 0x10]).branch_target(0x100), Some(0x112));
+    assert_eq!(parse(&[0xDF]).branch_target(0x100), Some(0x18));
+    assert_eq!(parse(&[0xE9]).branch_target(0x100), None);
+    assert_eq!(parse(&[0xC9]).branch_target(0x100), None);
+    assert_eq!(parse(&[0x00]).branch_target(0x100), None);
+}
+
+#[test]
+fn calls_and_returns_fall_through() {
+    assert!(parse(&[0xCD, 0, 0]).is_call() && parse(&[0xCD, 0, 0]).falls_through());
+    assert!(parse(&[0xC0]).is_control_flow() && parse(&[0xC0]).falls_through());
+    assert!(!parse(&[0xC9]).falls_through());
+    assert!(!parse(&[0xC3, 0, 0]).falls_through() && !parse(&[0xC3, 0, 0]).is_call());
+    assert!(!parse(&[0x76]).is_control_flow());
+}
+
+/// Whether running `bytes` read and wrote memory other than fetching it, with either set of flags
+fn accesses(bytes: &[u8]) -> (bool, bool) {
+    let (mut read, mut written) = (false, false);
+    for flags in [0x00, 0xF0] {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x100 + bytes.len()].copy_from_slice(bytes);
+        let mut memory_bus = MemoryBus::new(&rom[..]);
+        memory_bus.enable_access_stats();
+        let mut cpu = CPU {
+            Flags: flags,
+            SP: 0xDFF0,
+            ..CPU::default()
+        };
+        cpu.set_bc(0xC000);
+        cpu.set_de(0xC000);
+        cpu.set_hl(0xC000);
+        cpu.tick(&mut memory_bus);
+        let stats = memory_bus.access_stats().unwrap();
+        // Everything but the instruction itself
+        let fetched = stats.sum(0x100, 0x100 + bytes.len() as u16 - 1);
+        let total = stats.sum(0x0000, 0xFFFF);
+        read |= total.reads > fetched.reads;
+        written |= total.writes > 0;
+    }
+    (read, written)
+}
+
+#[test]
+fn memory_accesses_match_running_them() {
+    for (bytes, instruction) in every_instruction() {
+        if matches!(instruction, Instruction::Stop | Instruction::Halt) {
+            continue;
+        }
+        assert_eq!(
+            accesses(&bytes),
+            (instruction.reads_memory(), instruction.writes_memory()),
+            "{:02X?} {}",
+            bytes,
+            instruction
+        );
+    }
+}
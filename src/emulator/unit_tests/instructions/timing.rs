@@ -0,0 +1,170 @@
+//! Instruction timings from blargg's instr_timing, in M-cycles. 0 is something it doesn't time:
+//! STOP, HALT, the CB prefix and the illegal opcodes.
+use crate::emulator::{
+    cpu::CPU,
+    instructions::Instruction,
+    memory_bus::{Interrupt, MemoryBus},
+};
+
+#[rustfmt::skip]
+const NORMAL: [u32; 256] = [
+    1,3,2,2,1,1,2,1,5,2,2,2,1,1,2,1,
+    0,3,2,2,1,1,2,1,3,2,2,2,1,1,2,1,
+    2,3,2,2,1,1,2,1,2,2,2,2,1,1,2,1,
+    2,3,2,2,3,3,3,1,2,2,2,2,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    2,2,2,2,2,2,0,2,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    2,3,3,4,3,4,2,4,2,4,3,0,3,6,2,4,
+    2,3,3,0,3,4,2,4,2,4,3,0,3,0,2,4,
+    3,3,2,0,0,4,2,4,4,1,4,0,0,0,2,4,
+    3,3,2,1,0,4,2,4,3,2,4,1,0,0,2,4,
+];
+
+/// Only the conditional ones differ from [`NORMAL`]
+#[rustfmt::skip]
+const TAKEN: [u32; 256] = [
+    1,3,2,2,1,1,2,1,5,2,2,2,1,1,2,1,
+    0,3,2,2,1,1,2,1,3,2,2,2,1,1,2,1,
+    3,3,2,2,1,1,2,1,3,2,2,2,1,1,2,1,
+    3,3,2,2,3,3,3,1,3,2,2,2,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    2,2,2,2,2,2,0,2,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    1,1,1,1,1,1,2,1,1,1,1,1,1,1,2,1,
+    5,3,4,4,6,4,2,4,5,4,4,0,6,6,2,4,
+    5,3,4,0,6,4,2,4,5,4,4,0,6,0,2,4,
+    3,3,2,0,0,4,2,4,4,1,4,0,0,0,2,4,
+    3,3,2,1,0,4,2,4,3,2,4,1,0,0,2,4,
+];
+
+/// `[HL]` is every 8th, BIT only reads it
+fn cb_ticks(opcode: u8) -> u32 {
+    match (opcode & 7, opcode >> 6) {
+        (6, 1) => 3,
+        (6, _) => 4,
+        _ => 2,
+    }
+}
+
+fn parse(bytes: &[u8]) -> Instruction {
+    Instruction::parse(bytes).unwrap().1
+}
+
+#[test]
+fn ticks_match_the_table() {
+    for opcode in 0..=0xFF {
+        if NORMAL[opcode as usize] == 0 {
+            continue;
+        }
+        let instruction = parse(&[opcode, 0, 0]);
+        assert_eq!(
+            instruction.ticks(false),
+            NORMAL[opcode as usize],
+            "{:02X} {} not taken",
+            opcode,
+            instruction
+        );
+        assert_eq!(
+            instruction.ticks(true),
+            TAKEN[opcode as usize],
+            "{:02X} {} taken",
+            opcode,
+            instruction
+        );
+    }
+}
+
+#[test]
+fn cb_ticks_match_the_table() {
+    for opcode in 0..=0xFF {
+        let instruction = parse(&[0xCB, opcode]);
+        assert_eq!(
+            instruction.ticks(false),
+            cb_ticks(opcode),
+            "CB {:02X} {}",
+            opcode,
+            instruction
+        );
+    }
+}
+
+/// Runs `bytes` at 0x100 with every pair pointing into WRAM, like instr_timing measures them
+fn run(bytes: &[u8], flags: u8) -> u32 {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + bytes.len()].copy_from_slice(bytes);
+    let mut memory_bus = MemoryBus::new(&rom[..]);
+    let mut cpu = CPU {
+        Flags: flags,
+        SP: 0xDFF0,
+        ..CPU::default()
+    };
+    cpu.set_bc(0xC000);
+    cpu.set_de(0xC000);
+    cpu.set_hl(0xC000);
+    cpu.tick(&mut memory_bus)
+}
+
+/// Whether the condition in a conditional opcode holds with `flags`
+fn taken(opcode: u8, flags: u8) -> bool {
+    let (z, c) = (flags & 0x80 != 0, flags & 0x10 != 0);
+    match (opcode >> 3) & 3 {
+        0 => !z,
+        1 => z,
+        2 => !c,
+        _ => c,
+    }
+}
+
+#[test]
+fn executing_takes_as_long_as_the_table_says() {
+    for flags in [0x00, 0xF0] {
+        for opcode in 0..=0xFF {
+            let normal = NORMAL[opcode as usize];
+            if normal == 0 {
+                continue;
+            }
+            let expected = match TAKEN[opcode as usize] {
+                taken_ticks if taken_ticks != normal && taken(opcode, flags) => taken_ticks,
+                _ => normal,
+            };
+            assert_eq!(
+                run(&[opcode, 0, 0], flags),
+                expected,
+                "{:02X} with flags {:02X}",
+                opcode,
+                flags
+            );
+        }
+    }
+    for opcode in 0..=0xFF {
+        assert_eq!(
+            run(&[0xCB, opcode], 0),
+            cb_ticks(opcode),
+            "CB {:02X}",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn interrupt_dispatch_takes_five() {
+    let mut memory_bus = MemoryBus::new(&[0; 0x8000][..]);
+    memory_bus.write_u8(0xFFFF, 0x01);
+    memory_bus.request_interrupt(Interrupt::VBlank);
+    let mut cpu = CPU {
+        IME: true,
+        ..CPU::default()
+    };
+    assert_eq!(cpu.tick(&mut memory_bus), 5);
+    assert_eq!(cpu.PC, 0x40);
+}
@@ -0,0 +1,155 @@
+use crate::emulator::{
+    joypad::{Button, Joypad, TurboConfig},
+    memory_bus::{mmio::MmioDevice, Interrupt},
+    state::{StateReader, StateWriter},
+    Emulator,
+};
+
+const SELECT_ACTION: u8 = 0b0001_0000;
+const SELECT_DIRECTION: u8 = 0b0010_0000;
+
+#[test]
+fn nothing_pressed_reads_high() {
+    let mut joypad = Joypad::default();
+    joypad.write(SELECT_ACTION);
+    assert_eq!(joypad.read(), 0b1101_1111);
+}
+
+#[test]
+fn select_lines() {
+    let mut joypad = Joypad::default();
+    joypad.set_button(Button::A, true);
+    joypad.set_button(Button::Down, true);
+
+    joypad.write(SELECT_ACTION);
+    assert_eq!(joypad.read() & 0x0F, 0b1110);
+
+    joypad.write(SELECT_DIRECTION);
+    assert_eq!(joypad.read() & 0x0F, 0b0111);
+}
+
+#[test]
+fn turbo_toggles_on_frame_boundaries() {
+    let mut joypad = Joypad::default();
+    joypad.set_turbo_config(TurboConfig {
+        frames_on: 2,
+        frames_off: 1,
+    });
+    joypad.set_turbo(Button::B, true);
+
+    let mut seen = vec![];
+    for _ in 0..6 {
+        seen.push(joypad.pressed() & 0b0010_0000 != 0);
+        joypad.frame_tick();
+    }
+    assert_eq!(seen, [true, true, false, true, true, false]);
+}
+
+#[test]
+fn turbo_restarts_on_press() {
+    let mut joypad = Joypad::default();
+    joypad.set_turbo(Button::A, true);
+    joypad.frame_tick();
+    joypad.frame_tick();
+    joypad.set_turbo(Button::A, false);
+    joypad.set_turbo(Button::A, true);
+    assert_ne!(joypad.pressed() & 0b0001_0000, 0);
+}
+
+#[test]
+fn held_button_ignores_turbo_phase() {
+    let mut joypad = Joypad::default();
+    joypad.set_button(Button::A, true);
+    joypad.set_turbo(Button::A, true);
+    for _ in 0..4 {
+        assert_ne!(joypad.pressed() & 0b0001_0000, 0);
+        joypad.frame_tick();
+    }
+}
+
+/// Whether the joypad has an interrupt to request
+fn fired(joypad: &mut Joypad) -> bool {
+    joypad.tick(4) == Some(Interrupt::Joypad)
+}
+
+#[test]
+fn interrupt_fires_on_falling_edges() {
+    let mut joypad = Joypad::default();
+    MmioDevice::write(&mut joypad, 0xFF00, SELECT_ACTION);
+    joypad.set_button(Button::A, true);
+    assert!(fired(&mut joypad));
+    // Held down, released, and pressed in the group that isn't selected
+    assert!(!fired(&mut joypad));
+    joypad.set_button(Button::A, false);
+    assert!(!fired(&mut joypad));
+    joypad.set_button(Button::Left, true);
+    assert!(!fired(&mut joypad));
+
+    // Left was pulling P11 low all along as far as the direction group goes
+    assert_eq!(
+        MmioDevice::write(&mut joypad, 0xFF00, SELECT_DIRECTION),
+        Some(Interrupt::Joypad)
+    );
+    // B shares P11 with Left, which already holds it low once both groups are selected
+    assert_eq!(MmioDevice::write(&mut joypad, 0xFF00, 0), None);
+    joypad.set_button(Button::B, true);
+    assert!(!fired(&mut joypad));
+}
+
+#[test]
+fn loading_a_state_does_not_press_anything() {
+    let mut saved = Joypad::default();
+    saved.write(SELECT_ACTION);
+    let mut state = StateWriter::new(0);
+    saved.save_state(&mut state);
+
+    let mut joypad = Joypad::default();
+    joypad.write(0b0011_0000);
+    joypad.set_button(Button::Start, true);
+    let bytes = state.finish();
+    let mut reader = StateReader::new(&bytes, 0).unwrap();
+    joypad.load_state(&mut reader).unwrap();
+    assert!(!fired(&mut joypad));
+    assert_eq!(joypad.read() & 0x0F, 0b0111);
+}
+
+/// Selects the action buttons and halts until the joypad interrupt increments D, forever
+fn wake_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    #[rustfmt::skip]
+    let code = [
+        0x3E, 0x10, 0xE0, 0x00, // LD A, SELECT_ACTION, LDH [P1], A
+        0x3E, 0x10, 0xE0, 0xFF, // LD A, 0x10, LDH [IE], A
+        0xFB, // EI
+        0x76, 0x0C, 0x18, 0xFC, // HALT, INC C, JR -4
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    // Joypad: INC D, RETI
+    rom[0x60..0x62].copy_from_slice(&[0x14, 0xD9]);
+    rom
+}
+
+#[test]
+fn pressing_a_button_wakes_the_cpu() {
+    let mut emulator = Emulator::new(&wake_rom());
+    let presses = |emulator: &Emulator| emulator.cpu().get_de() >> 8;
+    emulator.run_frame().unwrap();
+    assert!(emulator.cpu().halted);
+
+    emulator
+        .memory_bus_mut()
+        .joypad_mut()
+        .set_button(Button::Up, true);
+    emulator.run_frame().unwrap();
+    assert_eq!(presses(&emulator), 0);
+
+    emulator
+        .memory_bus_mut()
+        .joypad_mut()
+        .set_button(Button::A, true);
+    for _ in 0..2 {
+        emulator.run_frame().unwrap();
+        assert_eq!(presses(&emulator), 1);
+        assert!(emulator.cpu().halted);
+    }
+}
@@ -0,0 +1,386 @@
+use crate::emulator::{
+    mbc::{
+        camera::{netpbm, Picture, StillImage},
+        eeprom::Eeprom,
+        mbc3::Mbc3,
+        mbc7::TiltDirection,
+    },
+    memory_bus::MemoryBus,
+    rom,
+    rtc::{self, RtcFooter, RtcRegisters, DH_HALT},
+    state::{StateReader, StateWriter},
+};
+
+const CS: u8 = 0x80;
+const CLK: u8 = 0x40;
+
+/// Clocks `bits` into the EEPROM MSB first with chip select held high
+fn send(eeprom: &mut Eeprom, value: u16, bits: u32) {
+    for i in (0..bits).rev() {
+        let di = ((value >> i) as u8 & 1) << 1;
+        eeprom.write(CS | di);
+        eeprom.write(CS | CLK | di);
+    }
+}
+
+/// Start bit, opcode and address
+fn command(eeprom: &mut Eeprom, opcode: u16, address: u16) {
+    send(eeprom, 1 << 10 | opcode << 8 | address, 11);
+}
+
+fn receive(eeprom: &mut Eeprom, bits: u32) -> u16 {
+    (0..bits).fold(0, |value, _| {
+        eeprom.write(CS);
+        eeprom.write(CS | CLK);
+        value << 1 | (eeprom.read() & 1) as u16
+    })
+}
+
+/// A 4 bank MBC7 ROM with its bank number at the start of each bank
+fn mbc7_bus() -> MemoryBus {
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0x22;
+    for bank in 1..4 {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    MemoryBus::new(&rom[..])
+}
+
+fn enable_registers(bus: &mut MemoryBus) {
+    bus.write_u8(0x0000, 0x0A);
+    bus.write_u8(0x4000, 0x40);
+}
+
+#[test]
+fn eeprom_writes_only_after_ewen() {
+    let mut eeprom = Eeprom::default();
+    // WRITE to word 3 while it's still protected
+    command(&mut eeprom, 0b01, 3);
+    send(&mut eeprom, 0x1234, 16);
+    eeprom.write(0);
+    command(&mut eeprom, 0b10, 3);
+    assert_eq!(receive(&mut eeprom, 16), 0xFFFF);
+    eeprom.write(0);
+
+    // EWEN
+    command(&mut eeprom, 0b00, 0xC0);
+    eeprom.write(0);
+    command(&mut eeprom, 0b01, 3);
+    send(&mut eeprom, 0x1234, 16);
+    eeprom.write(0);
+    command(&mut eeprom, 0b10, 3);
+    assert_eq!(receive(&mut eeprom, 16), 0x1234);
+    // Reads carry on into the next word
+    assert_eq!(receive(&mut eeprom, 16), 0xFFFF);
+    eeprom.write(0);
+    assert_eq!(&eeprom.save()[6..8], &[0x34, 0x12]);
+}
+
+#[test]
+fn mbc7_switches_rom_banks() {
+    let mut bus = mbc7_bus();
+    assert_eq!(bus.read_u8(0x4000), 1);
+    bus.write_u8(0x2000, 3);
+    assert_eq!(bus.read_u8(0x4000), 3);
+    assert_eq!(bus.rom_bank(), 3);
+    // Past the end wraps around
+    bus.write_u8(0x2000, 6);
+    assert_eq!(bus.read_u8(0x4000), 2);
+}
+
+#[test]
+fn mbc7_latches_tilt() {
+    let mut bus = mbc7_bus();
+    assert_eq!(bus.read_u8(0xA020), 0xFF);
+    enable_registers(&mut bus);
+    bus.accelerometer_mut()
+        .unwrap()
+        .set_held(TiltDirection::Right, true);
+
+    bus.write_u8(0xA000, 0x55);
+    assert_eq!((bus.read_u8(0xA020), bus.read_u8(0xA030)), (0x00, 0x80));
+    bus.write_u8(0xA010, 0xAA);
+    let x = bus.read_u8(0xA020) as u16 | (bus.read_u8(0xA030) as u16) << 8;
+    let y = bus.read_u8(0xA040) as u16 | (bus.read_u8(0xA050) as u16) << 8;
+    assert_eq!((x, y), (0x81D0 - 0x70, 0x81D0));
+
+    // Only once per clear
+    bus.accelerometer_mut()
+        .unwrap()
+        .set_held(TiltDirection::Right, false);
+    bus.write_u8(0xA010, 0xAA);
+    assert_eq!(bus.read_u8(0xA020), 0x60);
+    assert_eq!(bus.read_u8(0xA060), 0x00);
+    assert_eq!(bus.read_u8(0xB020), 0xFF);
+}
+
+#[test]
+fn mbc7_battery_and_state_keep_the_eeprom() {
+    let mut bus = mbc7_bus();
+    let mut battery = bus.battery().unwrap();
+    battery[0] = 0x42;
+    bus.load_battery(&battery).unwrap();
+    assert!(bus.load_battery(&battery[1..]).is_err());
+    bus.reset();
+    assert_eq!(bus.battery().unwrap()[0], 0x42);
+
+    let mut state = StateWriter::default();
+    bus.save_state(&mut state);
+    let state = state.finish();
+    let mut other = mbc7_bus();
+    other
+        .load_state(&mut StateReader::headerless(&state))
+        .unwrap();
+    assert_eq!(other.battery(), bus.battery());
+
+    assert_eq!(MemoryBus::new(&[0; 0x8000][..]).battery(), None);
+}
+
+/// An MBC1 ROM of `banks` banks with its bank number at the start of each bank and 32K of
+/// battery backed RAM
+fn mbc1_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * 0x4000];
+    rom[0x147] = 0x03;
+    rom[0x149] = 0x03;
+    for bank in 1..banks {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom
+}
+
+#[test]
+fn mbc1_switches_rom_and_ram_banks() {
+    let mut bus = MemoryBus::new(&mbc1_rom(0x80)[..]);
+    bus.write_u8(0x2000, 0x00);
+    assert_eq!(bus.read_u8(0x4000), 1);
+    // BANK2 goes above BANK1, and 0x20 can't be reached there
+    bus.write_u8(0x4000, 0x01);
+    bus.write_u8(0x2000, 0x03);
+    assert_eq!(bus.read_u8(0x4000), 0x23);
+    bus.write_u8(0x2000, 0x00);
+    assert_eq!(bus.read_u8(0x4000), 0x21);
+    assert_eq!(bus.read_u8(0x0000), 0);
+    // Mode 1 maps it at 0x0000 too, and picks the RAM bank
+    bus.write_u8(0x6000, 0x01);
+    assert_eq!(bus.read_u8(0x0000), 0x20);
+
+    bus.write_u8(0x0000, 0x0A);
+    for bank in 0..4 {
+        bus.write_u8(0x4000, bank);
+        bus.write_u8(0xA000, 0x10 + bank);
+    }
+    bus.write_u8(0x4000, 0x02);
+    assert_eq!(bus.read_u8(0xA000), 0x12);
+    // Mode 0 only has bank 0
+    bus.write_u8(0x6000, 0x00);
+    assert_eq!(bus.read_u8(0xA000), 0x10);
+    assert_eq!(bus.battery().unwrap()[3 * 0x2000], 0x13);
+}
+
+#[test]
+fn mbc1_multicarts_switch_games_with_bank2() {
+    let mut rom = mbc1_rom(0x40);
+    rom[0x147] = 0x01;
+    for game in 0..4 {
+        rom[game * 0x40000 + 0x104..][..0x30].copy_from_slice(&rom::NINTENDO_LOGO);
+        rom[game * 0x40000 + 0x134] = b'A' + game as u8;
+    }
+    let mut bus = MemoryBus::new(&rom[..]);
+    assert_eq!(bus.read_u8(0x0134), b'A');
+    bus.write_u8(0x2000, 0x02);
+    assert_eq!(bus.read_u8(0x4000), 0x02);
+
+    // The menu's way of starting the third game
+    bus.write_u8(0x6000, 0x01);
+    bus.write_u8(0x4000, 0x02);
+    assert_eq!(bus.read_u8(0x0134), b'C');
+    assert_eq!(bus.read_u8(0x4000), 0x22);
+    // Only 4 bits of BANK1, but 0x10 isn't 0
+    bus.write_u8(0x2000, 0x10);
+    assert_eq!(bus.read_u8(0x4000), 0x20);
+    bus.write_u8(0x2000, 0x00);
+    assert_eq!(bus.read_u8(0x4000), 0x21);
+    assert_eq!(bus.battery(), None);
+}
+
+/// An 8 bank MBC3 ROM with its bank number at the start of each bank, and 32K of RAM
+fn mbc3_bus(cartridge_type: u8) -> MemoryBus {
+    let mut rom = vec![0; 0x20000];
+    rom[0x147] = cartridge_type;
+    rom[0x149] = 0x03;
+    for bank in 1..8 {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    MemoryBus::new(&rom[..])
+}
+
+#[test]
+fn mbc3_switches_rom_and_ram_banks() {
+    let mut bus = mbc3_bus(0x13);
+    bus.write_u8(0x2000, 0x05);
+    assert_eq!(bus.read_u8(0x4000), 5);
+    bus.write_u8(0x2000, 0x00);
+    assert_eq!(bus.read_u8(0x4000), 1);
+    // Bank 9 wraps around to 1
+    bus.write_u8(0x2000, 0x09);
+    assert_eq!(bus.read_u8(0x4000), 1);
+
+    bus.write_u8(0xA000, 0x42);
+    assert_eq!(bus.read_u8(0xA000), 0xFF);
+    bus.write_u8(0x0000, 0x0A);
+    for bank in 0..4 {
+        bus.write_u8(0x4000, bank);
+        bus.write_u8(0xA000, 0x10 + bank);
+    }
+    bus.write_u8(0x4000, 0x02);
+    assert_eq!(bus.read_u8(0xA000), 0x12);
+    // No clock on this one
+    bus.write_u8(0x4000, 0x08);
+    assert_eq!(bus.read_u8(0xA000), 0xFF);
+    assert_eq!(bus.battery().unwrap()[3 * 0x2000], 0x13);
+    assert_eq!(mbc3_bus(0x12).battery(), None);
+}
+
+#[test]
+fn mbc3_clock_counts_and_latches() {
+    let mut bus = mbc3_bus(0x10);
+    bus.write_u8(0x0000, 0x0A);
+    bus.write_u8(0x4000, 0x08);
+    let latch = |bus: &mut MemoryBus| {
+        bus.write_u8(0x6000, 0x00);
+        bus.write_u8(0x6000, 0x01);
+    };
+    bus.write_u8(0xA000, 58);
+    bus.tick(4_194_304 * 3 - 1);
+    latch(&mut bus);
+    assert_eq!(bus.read_u8(0xA000), 0);
+    bus.write_u8(0x4000, 0x09);
+    assert_eq!(bus.read_u8(0xA000), 1);
+
+    // Reads stay latched while the clock moves on
+    bus.write_u8(0x4000, 0x08);
+    bus.tick(4_194_304);
+    assert_eq!(bus.read_u8(0xA000), 0);
+    latch(&mut bus);
+    assert_eq!(bus.read_u8(0xA000), 1);
+
+    // Stopped
+    bus.write_u8(0x4000, 0x0C);
+    bus.write_u8(0xA000, DH_HALT);
+    bus.tick(4_194_304 * 2);
+    latch(&mut bus);
+    bus.write_u8(0x4000, 0x08);
+    assert_eq!(bus.read_u8(0xA000), 1);
+
+    let mut state = StateWriter::default();
+    bus.save_state(&mut state);
+    let state = state.finish();
+    let mut other = mbc3_bus(0x10);
+    other
+        .load_state(&mut StateReader::headerless(&state))
+        .unwrap();
+    assert_eq!(other.read_u8(0xA000), 1);
+}
+
+#[test]
+fn mbc3_battery_has_the_clock_footer() {
+    let mut mbc = Mbc3::new(0x20000, 0x2000, true, true);
+    mbc.write_rom(0x0000, 0x0A);
+    mbc.write_ram(0xA000, 0x42);
+    mbc.write_rom(0x4000, 0x0A);
+    mbc.write_ram(0xA000, 5);
+    let sav = mbc.battery(1000).unwrap();
+    assert_eq!(sav.len(), 0x2000 + 48);
+    let (ram, footer) = rtc::split_sav(&sav);
+    assert_eq!(ram[0], 0x42);
+    assert_eq!(footer.unwrap().registers.hours, 5);
+    assert_eq!(footer.unwrap().timestamp, 1000);
+
+    // An hour and a bit later, from another emulator's save
+    let footer = RtcFooter {
+        registers: RtcRegisters {
+            hours: 5,
+            ..RtcRegisters::default()
+        },
+        latched: RtcRegisters::default(),
+        timestamp: 1000,
+    };
+    let mut other = Mbc3::new(0x20000, 0x2000, true, true);
+    other
+        .load_battery(&rtc::join_sav(ram, &footer), 1000 + 3600 + 30)
+        .unwrap();
+    other.write_rom(0x0000, 0x0A);
+    other.write_rom(0x6000, 0x00);
+    other.write_rom(0x6000, 0x01);
+    other.write_rom(0x4000, 0x0A);
+    assert_eq!(other.read_ram(0xA000), 6);
+    other.write_rom(0x4000, 0x08);
+    assert_eq!(other.read_ram(0xA000), 30);
+    other.write_rom(0x4000, 0x00);
+    assert_eq!(other.read_ram(0xA000), 0x42);
+
+    // Plain RAM loads too, the clock stays as it is
+    other.load_battery(ram, 0).unwrap();
+    assert!(other.load_battery(&ram[1..], 0).is_err());
+}
+
+fn camera_bus(picture: Picture) -> MemoryBus {
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0xFC;
+    let mut bus = MemoryBus::new(&rom[..]);
+    bus.camera_mut()
+        .unwrap()
+        .connect(Box::new(StillImage::new(picture)));
+    bus
+}
+
+#[test]
+fn camera_captures_into_ram() {
+    // The left half is white, the right black
+    let pixels = (0..4 * 2)
+        .map(|i| if i % 4 < 2 { 255 } else { 0 })
+        .collect();
+    let mut bus = camera_bus(Picture::new(4, 2, pixels));
+    bus.write_u8(0x0000, 0x0A);
+    bus.write_u8(0x4000, 0x10);
+    // Exposure that leaves it as it is, and the same thresholds for every pixel
+    bus.write_u8(0xA002, 0x03);
+    bus.write_u8(0xA003, 0x00);
+    for matrix in (0xA006..0xA036).step_by(3) {
+        bus.patch_bytes(matrix, &[0x40, 0x80, 0xC0]);
+    }
+    bus.write_u8(0xA000, 0x01);
+    assert_eq!(bus.read_u8(0xA000), 0x01);
+    bus.tick((32446 + 512 + 16 * 0x300) * 4 - 1);
+    assert_eq!(bus.read_u8(0xA000), 0x01);
+    bus.tick(1);
+    assert_eq!(bus.read_u8(0xA000), 0x00);
+
+    bus.write_u8(0x4000, 0x00);
+    // First tile of the row is white, the last one black
+    assert_eq!((bus.read_u8(0xA100), bus.read_u8(0xA101)), (0x00, 0x00));
+    assert_eq!((bus.read_u8(0xA1F0), bus.read_u8(0xA1F1)), (0xFF, 0xFF));
+    assert_eq!(bus.battery().unwrap().len(), 0x20000);
+}
+
+#[test]
+fn camera_ram_needs_enabling() {
+    let mut bus = camera_bus(Picture::new(1, 1, vec![0]));
+    bus.write_u8(0x4000, 0x03);
+    bus.write_u8(0xA123, 0x42);
+    assert_eq!(bus.read_u8(0xA123), 0x00);
+    bus.write_u8(0x0000, 0x0A);
+    bus.write_u8(0xA123, 0x42);
+    assert_eq!(bus.read_u8(0xA123), 0x42);
+    assert_eq!(bus.battery().unwrap()[3 * 0x2000 + 0x123], 0x42);
+}
+
+#[test]
+fn netpbm_images_parse() {
+    let plain = netpbm::parse(b"P2\n# comment\n2 1\n15\n0 15\n").unwrap();
+    assert_eq!(plain, Picture::new(2, 1, vec![0, 255]));
+    let binary = netpbm::parse(b"P6 1 1 255\n\xFF\x00\x00").unwrap();
+    assert_eq!(binary, Picture::new(1, 1, vec![76]));
+    assert!(netpbm::parse(b"P5 2 2 255\n\x00").is_err());
+    assert!(netpbm::parse(b"GIF89a").is_err());
+}
@@ -0,0 +1,297 @@
+use std::ops::RangeInclusive;
+
+use crate::emulator::{
+    error::EmulatorError,
+    instructions::{Instruction, Register8},
+    memory_bus::{
+        hooks::BusHook, mmio::MmioDevice, Interrupt, MemoryBus, DMA, IE, IF, OBP0, OBP1, STAT,
+    },
+};
+
+fn bus() -> MemoryBus {
+    MemoryBus::new(&[0; 0x8000][..])
+}
+
+#[test]
+fn unused_io_bits_read_high() {
+    let mut bus = bus();
+    bus.write_u8(STAT, 0x00);
+    assert_eq!(bus.read_u8(STAT) & 0x80, 0x80);
+    bus.write_u8(IF, 0x00);
+    assert_eq!(bus.read_u8(IF), 0xE0);
+    // TAC only has 3 bits
+    assert_eq!(bus.read_u8(0xFF07) & 0xF8, 0xF8);
+    // NR52 with the APU off
+    assert_eq!(bus.read_u8(0xFF26), 0x70);
+}
+
+#[test]
+fn unused_io_registers_read_ff() {
+    let bus = bus();
+    for addr in [
+        0xFF03, 0xFF08, 0xFF0E, 0xFF15, 0xFF1F, 0xFF27, 0xFF4C, 0xFF7F,
+    ] {
+        assert_eq!(bus.read_u8(addr), 0xFF, "{:#X}", addr);
+    }
+}
+
+#[test]
+fn strict_memory_reports_unmapped_io_registers() {
+    let mut bus = bus();
+    bus.set_strict(true);
+    assert_eq!(bus.read_u8(0xFF03), 0xFF);
+    assert_eq!(bus.take_fault(), Some(EmulatorError::UnmappedRead(0xFF03)));
+    bus.write_u8(0xFF7F, 0x12);
+    assert_eq!(
+        bus.take_fault(),
+        Some(EmulatorError::UnmappedWrite {
+            addr: 0xFF7F,
+            value: 0x12
+        })
+    );
+    // Registers that are there but have no bits to read aren't unmapped
+    assert_eq!(bus.read_u8(0xFF15), 0xFF);
+    assert_eq!(bus.take_fault(), None);
+}
+
+#[test]
+fn write_only_sound_registers_read_ff() {
+    let mut bus = bus();
+    for addr in [0xFF13, 0xFF18, 0xFF1B, 0xFF1D, 0xFF20] {
+        bus.write_u8(addr, 0x12);
+        assert_eq!(bus.read_u8(addr), 0xFF, "{:#X}", addr);
+    }
+}
+
+#[test]
+fn memory_outside_io_is_unmasked() {
+    let mut bus = bus();
+    bus.write_u8(0xC000, 0x00);
+    assert_eq!(bus.read_u8(0xC000), 0x00);
+    assert_eq!(bus.read_u8(0xE000), 0x00);
+    bus.write_u8(0xFF80, 0x00);
+    assert_eq!(bus.read_u8(0xFF80), 0x00);
+}
+
+#[test]
+fn interrupt_flag_top_bits_read_high() {
+    let mut bus = bus();
+    bus.write_u8(IF, 0xFF);
+    assert_eq!(bus.read_u8(IF), 0xFF);
+    bus.write_u8(IF, 0b0000_0101);
+    assert_eq!(bus.read_u8(IF), 0b1110_0101);
+
+    // Read-modify-write of IF keeps working with the top bits set
+    let value = bus.read_u8(IF) & !0b0000_0001;
+    bus.write_u8(IF, value);
+    assert_eq!(bus.read_u8(IF), 0b1110_0100);
+    bus.write_u8(IE, 0x1F);
+    assert!(matches!(bus.get_next_interrupt(), Some(Interrupt::Timer)));
+}
+
+#[test]
+fn interrupt_enable_keeps_all_bits() {
+    let mut bus = bus();
+    bus.write_u8(IE, 0b1010_0001);
+    assert_eq!(bus.read_u8(IE), 0b1010_0001);
+    bus.write_u8(IE, 0x00);
+    assert_eq!(bus.read_u8(IE), 0x00);
+}
+
+#[test]
+fn unused_interrupt_enable_bits_do_nothing() {
+    let mut bus = bus();
+    bus.write_u8(IE, 0b1110_0000);
+    bus.write_u8(IF, 0xFF);
+    assert!(bus.get_next_interrupt().is_none());
+}
+
+#[test]
+fn timer_overflow_requests_interrupt() {
+    let mut bus = bus();
+    bus.write_u8(IE, 0x1F);
+    bus.write_u8(0xFF05, 0xFF);
+    bus.write_u8(0xFF07, 0b101);
+    bus.tick(16);
+    assert!(bus.get_next_interrupt().is_none());
+    bus.tick(4);
+    assert!(matches!(bus.get_next_interrupt(), Some(Interrupt::Timer)));
+}
+
+#[test]
+fn patches_go_into_rom_and_ram() {
+    let mut bus = bus();
+    bus.patch_bytes(0x150, &[0x18, 0xFE]);
+    assert_eq!(bus.get_instr(0x150), [0x18, 0xFE, 0, 0]);
+    let end = bus.patch(
+        0xC000,
+        &[
+            Instruction::LoadImmediate(Register8::A, 0x12),
+            Instruction::Ret,
+        ],
+    );
+    assert_eq!(end, 0xC003);
+    assert_eq!(
+        [bus.peek(0xC000), bus.peek(0xC001), bus.peek(0xC002)],
+        [0x3E, 0x12, 0xC9]
+    );
+    assert!(bus.take_written_pages().contains(0x150));
+}
+
+/// The CGB's infrared port, requesting the joypad interrupt once it's been ticked long enough
+#[derive(Debug, Default)]
+struct Infrared {
+    rp: u8,
+    cycles: u32,
+}
+
+impl MmioDevice for Infrared {
+    fn ranges(&self) -> &[RangeInclusive<u16>] {
+        &[0xFF56..=0xFF56]
+    }
+
+    fn read(&self, _addr: u16) -> u8 {
+        self.rp
+    }
+
+    fn write(&mut self, _addr: u16, byte: u8) -> Option<Interrupt> {
+        self.rp = byte;
+        (byte == 0xFF).then_some(Interrupt::Serial)
+    }
+
+    fn tick(&mut self, cycles: u32) -> Option<Interrupt> {
+        self.cycles += cycles;
+        (self.cycles >= 100).then_some(Interrupt::Joypad)
+    }
+
+    fn cycles_until_interrupt(&self) -> u32 {
+        100u32.saturating_sub(self.cycles)
+    }
+}
+
+#[test]
+fn attached_devices_get_their_registers() {
+    let mut bus = bus();
+    // Not there on a DMG
+    assert_eq!(bus.read_u8(0xFF56), 0xFF);
+    bus.attach(Box::<Infrared>::default());
+    bus.write_u8(0xFF56, 0x01);
+    assert_eq!(bus.read_u8(0xFF56), 0x01);
+    assert_eq!(bus.get_next_interrupt(), None);
+
+    bus.write_u8(IE, 0x1F);
+    bus.write_u8(0xFF56, 0xFF);
+    assert_eq!(bus.get_next_interrupt(), Some(Interrupt::Serial));
+    bus.reset_interrupt(Interrupt::Serial);
+
+    assert_eq!(bus.cycles_until_interrupt(), 100);
+    bus.tick(100);
+    assert_eq!(bus.get_next_interrupt(), Some(Interrupt::Joypad));
+
+    bus.reset();
+    assert_eq!(bus.read_u8(0xFF56), 0xFF);
+    assert_eq!(bus.read_u8(IE), 0x00);
+}
+
+/// Reads of 0xC000 come back doubled, WRAM past 0xC0FF is read only
+#[derive(Debug, Default)]
+struct Meddler {
+    writes: usize,
+}
+
+impl BusHook for Meddler {
+    fn read(&mut self, addr: u16, value: u8) -> u8 {
+        if addr == 0xC000 {
+            value.wrapping_mul(2)
+        } else {
+            value
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Option<u8> {
+        self.writes += 1;
+        (addr < 0xC100).then_some(value)
+    }
+}
+
+#[test]
+fn hooks_change_reads_and_writes() {
+    let mut bus = bus();
+    bus.add_hook(Meddler::default());
+    bus.write_u8(0xC000, 0x21);
+    bus.write_u8(0xC100, 0x21);
+    assert_eq!(bus.read_u8(0xC000), 0x42);
+    assert_eq!(bus.read_u8(0xC100), 0x00);
+    // Peeking isn't the CPU
+    assert_eq!(bus.peek(0xC000), 0x21);
+    assert_eq!(bus.hook::<Meddler>().unwrap().writes, 2);
+
+    // One of each type
+    bus.add_hook(Meddler::default());
+    assert_eq!(bus.hook::<Meddler>().unwrap().writes, 0);
+    assert_eq!(bus.remove_hook::<Meddler>().unwrap().writes, 0);
+    assert!(bus.hook::<Meddler>().is_none());
+    assert_eq!(bus.read_u8(0xC000), 0x21);
+}
+
+#[test]
+fn restoring_a_dump_puts_everything_back() {
+    let mut bus = bus();
+    bus.write_u8(0xD123, 0x45);
+    bus.write_u8(0x8000, 0xFF);
+    bus.write_u8(IE, 0x1F);
+    bus.write_u8(0xFF05, 0xFF);
+    bus.write_u8(0xFF07, 0b101);
+    bus.tick(8);
+    let dump = bus.dump();
+    assert_eq!(dump.get(0xD123), Some(0x45));
+    assert_eq!(dump.get(0xF123), Some(0x45));
+    assert_eq!(dump.get(0xFF05), Some(0xFF));
+    assert_eq!(dump.get(0x0100), None);
+
+    bus.write_u8(0xD123, 0x00);
+    bus.write_u8(0x8000, 0x00);
+    bus.tick(12);
+    assert_eq!(
+        dump.changes(&bus.dump()).collect::<Vec<_>>(),
+        [
+            (0x8000, 0xFF, 0x00),
+            (0xD123, 0x45, 0x00),
+            // TIMA overflowed
+            (0xFF05, 0xFF, 0x00),
+            (IF, 0xE0, 0xE4)
+        ]
+    );
+
+    bus.restore(&dump);
+    assert_eq!(bus.dump(), dump);
+    assert_eq!(bus.tile_row(0, 0x8000), &[1; 8]);
+    // Part way to the overflow, like before
+    assert!(bus.get_next_interrupt().is_none());
+    bus.tick(12);
+    assert_eq!(bus.get_next_interrupt(), Some(Interrupt::Timer));
+}
+
+#[test]
+fn dma_copies_a_page_into_oam() {
+    let mut bus = bus();
+    for i in 0..0xA0 {
+        bus.write_u8(0xC100 + i, i as u8);
+    }
+    bus.write_u8(DMA, 0xC1);
+    assert_eq!(bus.read_u8(DMA), 0xC1);
+    assert_eq!([0xFE00, 0xFE9F].map(|addr| bus.read_u8(addr)), [0x00, 0x9F]);
+
+    // Past WRAM it's WRAM again
+    bus.write_u8(0xC000, 0x42);
+    bus.write_u8(DMA, 0xE0);
+    assert_eq!(bus.read_u8(0xFE00), 0x42);
+}
+
+#[test]
+fn object_palettes_read_back() {
+    let mut bus = bus();
+    bus.write_u8(OBP0, 0x1B);
+    bus.write_u8(OBP1, 0xE4);
+    assert_eq!([bus.read_u8(OBP0), bus.read_u8(OBP1)], [0x1B, 0xE4]);
+}
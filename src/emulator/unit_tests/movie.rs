@@ -0,0 +1,62 @@
+use crate::emulator::movie::{
+    Movie, MovieError, MovieHeader, MoviePlayer, MovieRecorder, MovieStart,
+};
+
+const HEADER: MovieHeader = MovieHeader {
+    start: MovieStart::PowerOn,
+    rom_checksum: 0xBEEF,
+};
+
+#[test]
+fn recorder_output_reads_back() {
+    let mut recorder = MovieRecorder::new(Vec::new(), HEADER).unwrap();
+    for buttons in [0x00, 0x10, 0x11, 0x80] {
+        recorder.record_frame(buttons).unwrap();
+    }
+    assert_eq!(recorder.frames(), 4);
+    let bytes = recorder.finish().unwrap();
+
+    assert_eq!(&bytes[0..8], b"GBMV\x01\x00\xBE\xEF");
+    let movie = Movie::read(bytes.as_slice()).unwrap();
+    assert_eq!(movie.header, HEADER);
+    assert_eq!(movie.frames, [0x00, 0x10, 0x11, 0x80]);
+}
+
+#[test]
+fn write_roundtrip() {
+    let movie = Movie {
+        header: HEADER,
+        frames: vec![1, 2, 3],
+    };
+    let mut bytes = Vec::new();
+    movie.write(&mut bytes).unwrap();
+    assert_eq!(Movie::read(bytes.as_slice()).unwrap(), movie);
+}
+
+#[test]
+fn rejects_bad_header() {
+    assert!(matches!(
+        Movie::read(b"NOPE\x01\x00\x00\x00".as_slice()),
+        Err(MovieError::BadMagic)
+    ));
+    assert!(matches!(
+        Movie::read(b"GBMV\x02\x00\x00\x00".as_slice()),
+        Err(MovieError::UnsupportedVersion(2))
+    ));
+    assert!(matches!(
+        Movie::read(b"GBMV".as_slice()),
+        Err(MovieError::Io(_))
+    ));
+}
+
+#[test]
+fn player_ends() {
+    let mut player = MoviePlayer::new(Movie {
+        header: HEADER,
+        frames: vec![0x20, 0x40],
+    });
+    assert_eq!(player.next_frame(), Some(0x20));
+    assert_eq!(player.next_frame(), Some(0x40));
+    assert_eq!(player.next_frame(), None);
+    assert_eq!(player.next_frame(), None);
+}
@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use crate::emulator::{cheats, mbc, paths::Paths, state};
+
+#[test]
+fn per_game_files_go_in_their_own_directories() {
+    let mut paths = Paths::in_dir(Path::new("config"));
+    assert_eq!(paths.config, Path::new("config"));
+    assert_eq!(paths.screenshots, Path::new("config/screenshots"));
+    assert_eq!(
+        cheats::cheat_file(&paths, "TETRIS", 0x1234),
+        Path::new("config/cheats/TETRIS-1234.txt")
+    );
+
+    paths.saves = "elsewhere".into();
+    assert_eq!(
+        mbc::battery_file(&paths, "TETRIS", 0x1234),
+        Path::new("elsewhere/TETRIS-1234.sav")
+    );
+    assert_eq!(
+        state::resume_file(&paths, "TETRIS", 0x1234),
+        Path::new("config/states/TETRIS-1234.resume.gbst")
+    );
+}
@@ -0,0 +1,27 @@
+use crate::emulator::png::{adler32, crc32, write_grayscale};
+
+#[test]
+fn checksums() {
+    assert_eq!(crc32(&[b"IEND"]), 0xAE42_6082);
+    assert_eq!(crc32(&[b"IE", b"ND"]), 0xAE42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+}
+
+#[test]
+fn grayscale_layout() {
+    let mut bytes = Vec::new();
+    write_grayscale(&mut bytes, 2, 2, &[0x00, 0x55, 0xAA, 0xFF]).unwrap();
+
+    assert_eq!(&bytes[0..8], b"\x89PNG\r\n\x1a\n");
+    // IHDR: 13 bytes, 2x2, 8-bit grayscale
+    assert_eq!(&bytes[8..16], b"\x00\x00\x00\x0DIHDR");
+    assert_eq!(&bytes[16..29], &[0, 0, 0, 2, 0, 0, 0, 2, 8, 0, 0, 0, 0]);
+    // IDAT: zlib header, one final stored block holding both filtered rows, adler
+    let idat = &bytes[33..];
+    assert_eq!(&idat[0..8], b"\x00\x00\x00\x11IDAT");
+    assert_eq!(
+        &idat[8..19],
+        &[0x78, 0x01, 0x01, 0x06, 0x00, 0xF9, 0xFF, 0x00, 0x00, 0x55, 0x00]
+    );
+    assert!(bytes.ends_with(b"\x00\x00\x00\x00IEND\xAE\x42\x60\x82"));
+}
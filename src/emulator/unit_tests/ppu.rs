@@ -0,0 +1,897 @@
+use crate::emulator::{
+    hardware::HardwareModel,
+    memory_bus::{
+        Interrupt, MemoryBus, BCPD, BCPS, IF, LCDC, LCD_Y, LCD_YC, OBP0, OBP1, OCPD, OCPS, OPRI,
+        PALLETE, SCROLL_X, STAT, VBK, WINDOW_X, WINDOW_Y,
+    },
+    ppu::{FrameBuffer, FRAME_CYCLES, PPU},
+    GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
+};
+
+struct Lcd {
+    ppu: PPU,
+    bus: MemoryBus,
+    frame: Box<FrameBuffer>,
+}
+
+impl Lcd {
+    fn new() -> Self {
+        Self {
+            ppu: PPU::default(),
+            bus: MemoryBus::new(&[0; 0x8000][..]),
+            frame: Box::new([0; GAMEBOY_HEIGHT * GAMEBOY_WIDTH]),
+        }
+    }
+
+    /// Running a CGB game
+    fn cgb() -> Self {
+        let mut rom = [0; 0x8000];
+        rom[0x143] = 0x80;
+        let mut bus = MemoryBus::new(&rom[..]);
+        bus.set_model(HardwareModel::Cgb);
+        Self { bus, ..Self::new() }
+    }
+
+    /// Runs `m_cycles` M-cycles, returns how many frames that finished
+    fn run(&mut self, m_cycles: u32) -> usize {
+        (0..m_cycles)
+            .filter(|_| self.ppu.tick(&mut self.bus, &mut self.frame, 4))
+            .count()
+    }
+
+    /// Runs to the given M-cycle (0-113) of `line`
+    fn run_to(&mut self, line: u32, m_cycle: u32) {
+        self.run(line * 114 + m_cycle);
+    }
+
+    fn coincidence(&self) -> bool {
+        self.bus.read_u8(STAT) & 0b100 != 0
+    }
+
+    /// Fills background palette `palette` in palette RAM, in CGB mode
+    fn bg_palette(&mut self, palette: u8, colors: [u16; 4]) {
+        self.bus.write_u8(BCPS, 0x80 | (palette * 8));
+        for color in colors {
+            self.bus.write_u8(BCPD, color as u8);
+            self.bus.write_u8(BCPD, (color >> 8) as u8);
+        }
+    }
+
+    /// Like [`Lcd::bg_palette`] for object palette `palette`
+    fn obj_palette(&mut self, palette: u8, colors: [u16; 4]) {
+        self.bus.write_u8(OCPS, 0x80 | (palette * 8));
+        for color in colors {
+            self.bus.write_u8(OCPD, color as u8);
+            self.bus.write_u8(OCPD, (color >> 8) as u8);
+        }
+    }
+}
+
+#[test]
+fn ly_counts_lines() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(1, 1);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 1);
+    lcd.run(113);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 2);
+    lcd.run(114 * 150);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 152);
+}
+
+#[test]
+fn ly_wraps_early_on_line_153() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(153, 0);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 153);
+    lcd.run(1);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 0);
+    // Still 0 once line 0 starts
+    lcd.run(113);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 0);
+    lcd.run(114);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 1);
+}
+
+#[test]
+fn coincidence_is_blanked_as_ly_changes() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCD_YC, 5);
+    lcd.run_to(5, 0);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 5);
+    assert!(!lcd.coincidence());
+    lcd.run(1);
+    assert!(lcd.coincidence());
+    lcd.run(113);
+    assert!(!lcd.coincidence());
+}
+
+#[test]
+fn coincidence_on_line_153() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCD_YC, 153);
+    lcd.run_to(153, 0);
+    assert!(!lcd.coincidence());
+    lcd.run(1);
+    assert!(lcd.coincidence());
+    lcd.run(1);
+    assert!(!lcd.coincidence());
+
+    lcd.bus.write_u8(LCD_YC, 0);
+    assert!(!lcd.coincidence());
+    lcd.run(1);
+    assert!(lcd.coincidence());
+    // Line 0 doesn't blank it again
+    lcd.run(111);
+    assert!(lcd.coincidence());
+    lcd.run(114);
+    assert!(!lcd.coincidence());
+}
+
+#[test]
+fn writing_lyc_compares_immediately() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(10, 5);
+    lcd.bus.write_u8(LCD_YC, 10);
+    assert!(lcd.coincidence());
+    lcd.bus.write_u8(LCD_YC, 11);
+    assert!(!lcd.coincidence());
+}
+
+#[test]
+fn ly_is_read_only() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(3, 2);
+    lcd.bus.write_u8(LCD_Y, 0x42);
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 3);
+}
+
+#[test]
+fn lyc_interrupt_fires_once_per_match() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCD_YC, 20);
+    lcd.bus.write_u8(STAT, 0b0100_0000);
+    // Clear what the STAT write bug requested
+    lcd.bus.reset_interrupt(Interrupt::LCDStat);
+    lcd.run_to(20, 0);
+    assert_eq!(lcd.bus.read_u8(IF) & 0b10, 0);
+    lcd.run(1);
+    assert_eq!(lcd.bus.read_u8(IF) & 0b10, 0b10);
+
+    lcd.bus.reset_interrupt(Interrupt::LCDStat);
+    lcd.run(50);
+    assert_eq!(lcd.bus.read_u8(IF) & 0b10, 0);
+}
+
+#[test]
+fn stat_mode_and_coincidence_are_read_only() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCD_YC, 0);
+    lcd.run(1);
+    let stat = lcd.bus.read_u8(STAT);
+    lcd.bus.write_u8(STAT, 0b0111_1000);
+    assert_eq!(lcd.bus.read_u8(STAT), 0b1111_1000 | (stat & 0b111));
+}
+
+impl Lcd {
+    /// OAM filled with each byte's own address
+    fn fill_oam(&mut self) {
+        for i in 0..0xA0 {
+            self.bus.write_u8(0xFE00 + i, i as u8);
+        }
+    }
+
+    fn oam_row(&self, row: u16) -> [u8; 8] {
+        std::array::from_fn(|i| self.bus.read_u8(0xFE00 + row * 8 + i as u16))
+    }
+}
+
+#[test]
+fn oam_bug_corrupts_the_row_being_scanned() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(3, 5);
+    lcd.fill_oam();
+    lcd.bus.oam_bug(0xFE42);
+
+    // a = 0x28, b = 0x20, c = 0x24, then a = 0x29, b = 0x21, c = 0x25
+    assert_eq!(
+        lcd.oam_row(5),
+        [0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27]
+    );
+    lcd.fill_oam();
+    lcd.bus.write_u8(0xFE20, 0b1100);
+    lcd.bus.write_u8(0xFE24, 0b1010);
+    lcd.bus.write_u8(0xFE28, 0b0110);
+    lcd.bus.oam_bug(0xFEFF);
+    assert_eq!(lcd.oam_row(5)[0], 0b1110);
+    assert_eq!(lcd.oam_row(4)[0], 0b1100);
+}
+
+#[test]
+fn oam_bug_only_during_oam_scan() {
+    let mut lcd = Lcd::new();
+    // Row 0
+    lcd.run_to(1, 0);
+    lcd.fill_oam();
+    lcd.bus.oam_bug(0xFE00);
+    assert_eq!(lcd.oam_row(0), [0, 1, 2, 3, 4, 5, 6, 7]);
+    // Mode 3
+    lcd.run(30);
+    lcd.bus.oam_bug(0xFE00);
+    // V-blank
+    lcd.run_to(144, 5);
+    lcd.bus.oam_bug(0xFE00);
+    for row in 0..20 {
+        assert_eq!(lcd.oam_row(row)[0], row as u8 * 8);
+    }
+}
+
+#[test]
+fn oam_bug_needs_an_oam_address() {
+    let mut lcd = Lcd::new();
+    lcd.run_to(0, 10);
+    lcd.fill_oam();
+    lcd.bus.oam_bug(0xFDFF);
+    lcd.bus.oam_bug(0xFF00);
+    assert_eq!(lcd.oam_row(10), [80, 81, 82, 83, 84, 85, 86, 87]);
+}
+
+#[test]
+fn oam_bug_can_be_disabled() {
+    for (model, enabled) in [
+        (HardwareModel::Dmg, false),
+        (HardwareModel::Cgb, true),
+        (HardwareModel::Agb, true),
+    ] {
+        let mut lcd = Lcd::new();
+        lcd.bus.set_model(model);
+        lcd.bus.set_oam_bug(enabled);
+        lcd.run_to(0, 10);
+        lcd.fill_oam();
+        lcd.bus.oam_bug(0xFE00);
+        assert_eq!(
+            lcd.oam_row(10),
+            [80, 81, 82, 83, 84, 85, 86, 87],
+            "{}",
+            model
+        );
+    }
+}
+
+fn stat_requested(lcd: &Lcd) -> bool {
+    lcd.bus.read_u8(IF) & 0b10 != 0
+}
+
+#[test]
+fn stat_write_bug_interrupts_in_hblank_and_vblank() {
+    for (line, m_cycle) in [(10, 100), (150, 50)] {
+        let mut lcd = Lcd::new();
+        lcd.bus.write_u8(LCD_YC, 0xFF);
+        lcd.run_to(line, m_cycle);
+        lcd.bus.write_u8(IF, 0);
+        lcd.bus.write_u8(STAT, 0);
+        assert!(stat_requested(&lcd), "line {}", line);
+    }
+}
+
+#[test]
+fn stat_write_bug_interrupts_on_coincidence() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCD_YC, 10);
+    // Mode 2 of a matching line
+    lcd.run_to(10, 5);
+    lcd.bus.write_u8(IF, 0);
+    lcd.bus.write_u8(STAT, 0);
+    assert!(stat_requested(&lcd));
+}
+
+#[test]
+fn stat_write_bug_not_during_oam_scan_or_drawing() {
+    for m_cycle in [5, 40] {
+        let mut lcd = Lcd::new();
+        lcd.bus.write_u8(LCD_YC, 0xFF);
+        lcd.run_to(10, m_cycle);
+        lcd.bus.write_u8(IF, 0);
+        lcd.bus.write_u8(STAT, 0);
+        assert!(!stat_requested(&lcd), "M-cycle {}", m_cycle);
+    }
+}
+
+#[test]
+fn stat_write_bug_is_dmg_only() {
+    for model in [HardwareModel::Cgb, HardwareModel::Agb] {
+        let mut lcd = Lcd::new();
+        lcd.bus.set_model(model);
+        lcd.run_to(150, 50);
+        lcd.bus.write_u8(IF, 0);
+        lcd.bus.write_u8(STAT, 0);
+        assert!(!stat_requested(&lcd), "{}", model);
+    }
+}
+
+#[test]
+fn lcd_off_shows_blank_frames() {
+    let mut lcd = Lcd::new();
+    lcd.frame.fill(0);
+    lcd.bus.write_u8(LCDC, 0x11);
+    lcd.run(1);
+    assert!(lcd.frame.iter().all(|&shade| shade == 255));
+    assert!(lcd.ppu.lcd_off());
+    // Still one frame a frame's worth of cycles
+    assert_eq!(lcd.run(FRAME_CYCLES / 4 - 2), 0);
+    assert_eq!(lcd.run(1), 1);
+}
+
+#[test]
+fn lcd_back_on_starts_at_line_0() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCDC, 0x11);
+    lcd.run(500);
+    lcd.bus.write_u8(LCDC, 0x91);
+    lcd.run(114);
+    assert!(!lcd.ppu.lcd_off());
+    assert_eq!(lcd.bus.read_u8(LCD_Y), 1);
+}
+
+#[test]
+fn one_long_tick_matches_many_short_ones() {
+    let mut short = Lcd::new();
+    let mut long = Lcd::new();
+    for lcd in [&mut short, &mut long] {
+        lcd.bus.write_u8(STAT, 0x78);
+        lcd.bus.write_u8(LCD_YC, 100);
+    }
+    let frames = short.run(114 * 200 + 37);
+    assert_eq!(
+        long.ppu
+            .tick(&mut long.bus, &mut long.frame, (114 * 200 + 37) * 4),
+        frames == 1
+    );
+    for addr in [IF, STAT, LCD_Y] {
+        assert_eq!(short.bus.read_u8(addr), long.bus.read_u8(addr));
+    }
+    assert_eq!(short.frame, long.frame);
+}
+
+#[test]
+fn background_scrolls_by_the_pixel() {
+    let mut lcd = Lcd::new();
+    // Tile 1 is one row of color IDs 0-3 twice over, every row the same
+    for row in 0..8 {
+        lcd.bus.write_u8(0x8010 + row * 2, 0b0101_0101);
+        lcd.bus.write_u8(0x8011 + row * 2, 0b0011_0011);
+    }
+    // Every other tile in the map, the one at 0x9800 with 0x8000 tile data
+    lcd.bus.write_u8(LCDC, 0x91);
+    for tile in (1..1024).step_by(2) {
+        lcd.bus.write_u8(0x9800 + tile, 1);
+    }
+    lcd.bus.write_u8(SCROLL_X, 5);
+    lcd.bus.write_u8(PALLETE, 0b11_10_01_00);
+    // The pixels thrown away are picked as mode 3 starts, the tiles as they're fetched
+    lcd.run_to(0, 30);
+    lcd.bus.write_u8(SCROLL_X, 2);
+    lcd.run_to(1, 0);
+
+    let shades = [255, 192, 95, 0];
+    let expected: Vec<u8> = (0..GAMEBOY_WIDTH)
+        .map(|x| {
+            let bg_x = x + 5;
+            match (bg_x / 8) % 2 {
+                0 => 255,
+                _ => shades[bg_x % 4],
+            }
+        })
+        .collect();
+    assert_eq!(&lcd.frame[..GAMEBOY_WIDTH], &expected[..]);
+}
+
+#[test]
+fn background_attributes_pick_the_bank_and_flip() {
+    let mut lcd = Lcd::cgb();
+    // Tile 1 in bank 1 has one pixel of color 3, at the top left
+    lcd.bus.write_u8(VBK, 1);
+    lcd.bus.write_u8(0x8010, 0x80);
+    lcd.bus.write_u8(0x8011, 0x80);
+    // Bank 1, then flipped across and down
+    for (tile, attributes) in [0b0000_1000, 0b0010_1000, 0b0100_1000]
+        .into_iter()
+        .enumerate()
+    {
+        lcd.bus.write_u8(0x9800 + tile as u16, attributes);
+    }
+    lcd.bus.write_u8(VBK, 0);
+    for tile in 0..3 {
+        lcd.bus.write_u8(0x9800 + tile, 1);
+    }
+    lcd.bus.write_u8(LCDC, 0x91);
+    lcd.bg_palette(0, [0x7FFF, 0x7FFF, 0x7FFF, 0]);
+    lcd.run_to(8, 0);
+
+    let dark = |line: usize| {
+        let pixels = &lcd.frame[line * GAMEBOY_WIDTH..][..24];
+        (0..24).filter(|&x| pixels[x] == 0).collect::<Vec<_>>()
+    };
+    assert_eq!(dark(0), [0, 15]);
+    assert_eq!(dark(7), [16]);
+}
+
+#[test]
+fn cgb_colors_come_from_the_attribute_palette() {
+    let mut lcd = Lcd::cgb();
+    // Tile 1 is all color 3, drawn with palette 0 then 5
+    for row in 0..16 {
+        lcd.bus.write_u8(0x8010 + row, 0xFF);
+    }
+    lcd.bus.write_u8(VBK, 1);
+    lcd.bus.write_u8(0x9801, 5);
+    lcd.bus.write_u8(VBK, 0);
+    lcd.bus.write_u8(0x9800, 1);
+    lcd.bus.write_u8(0x9801, 1);
+    lcd.bus.write_u8(LCDC, 0x91);
+    // BGP is for DMG games only
+    lcd.bus.write_u8(PALLETE, 0);
+    lcd.bg_palette(0, [0x7FFF, 0x7FFF, 0x7FFF, 0x001F]);
+    lcd.bg_palette(5, [0x7FFF, 0x7FFF, 0x7FFF, 0x7C00]);
+    lcd.run(114);
+
+    let colors = lcd.ppu.color_frame();
+    assert_eq!([colors[0], colors[8], colors[16]], [0x001F, 0x7C00, 0x7FFF]);
+    // The shades are how bright they are
+    assert_eq!(
+        [lcd.pixel(0, 0), lcd.pixel(8, 0), lcd.pixel(16, 0)],
+        [76, 29, 255]
+    );
+}
+
+impl Lcd {
+    /// Puts OAM entry `i` at `x` on line 10, with 8x8 objects
+    fn object(&mut self, i: u16, x: u8) {
+        self.bus.write_u8(0xFE00 + i * 4, 10 + 16);
+        self.bus.write_u8(0xFE01 + i * 4, x);
+    }
+
+    /// The M-cycle mode 3 ends at on line 10
+    fn mode_3_end(&mut self) -> u32 {
+        self.run_to(10, 21);
+        assert_eq!(self.bus.read_u8(STAT) & 0b11, 3);
+        (22..114)
+            .find(|_| {
+                self.run(1);
+                self.bus.read_u8(STAT) & 0b11 == 0
+            })
+            .unwrap()
+    }
+}
+
+#[test]
+fn objects_make_mode_3_longer() {
+    for (xs, scroll_x, end) in [
+        // 172 T-cycles
+        (&[][..], 0, 64),
+        // 11 at X 0, whatever SCX is, and 3 for the pixels SCX throws away
+        (&[0], 3, 68),
+        // 6 for the fetch and 5 - 2 waiting for the tile
+        (&[10], 0, 67),
+        // No waiting in the last 3 pixels of a tile
+        (&[13], 0, 66),
+        (&[10], 3, 67),
+        // Only the first object in a tile waits, 9 + 6
+        (&[10, 11], 0, 68),
+        (&[10, 18], 0, 69),
+        // Off the right edge
+        (&[168], 0, 64),
+        // Only the first 10, 12 M-cycles for 10 waiting the full 5
+        (&[8, 16, 24, 32, 40, 48, 56, 64, 72, 80, 88, 96], 0, 92),
+    ] {
+        let mut lcd = Lcd::new();
+        lcd.bus.write_u8(LCDC, 0x93);
+        lcd.bus.write_u8(SCROLL_X, scroll_x);
+        for (i, &x) in (0..).zip(xs) {
+            lcd.object(i, x);
+        }
+        assert_eq!(lcd.mode_3_end(), end, "{:?} SCX {}", xs, scroll_x);
+    }
+}
+
+#[test]
+fn fine_scroll_makes_mode_3_longer() {
+    for (scroll_x, end) in [(4, 65), (5, 66), (7, 66), (8, 64), (0x0C, 65)] {
+        let mut lcd = Lcd::new();
+        lcd.bus.write_u8(SCROLL_X, scroll_x);
+        assert_eq!(lcd.mode_3_end(), end, "SCX {}", scroll_x);
+    }
+}
+
+#[test]
+fn objects_only_cost_time_while_on_in_dmg_mode() {
+    for (model, end) in [(HardwareModel::Dmg, 64), (HardwareModel::Cgb, 67)] {
+        let mut lcd = Lcd::new();
+        lcd.bus.set_model(model);
+        lcd.bus.write_u8(LCDC, 0x91);
+        lcd.object(0, 0);
+        assert_eq!(lcd.mode_3_end(), end, "{}", model);
+    }
+}
+
+#[test]
+fn window_makes_mode_3_longer() {
+    for (window_y, window_x, end) in [(0, 7, 66), (10, 100, 66), (11, 7, 64), (0, 167, 64)] {
+        let mut lcd = Lcd::new();
+        lcd.bus.write_u8(LCDC, 0xB1);
+        lcd.bus.write_u8(WINDOW_Y, window_y);
+        lcd.bus.write_u8(WINDOW_X, window_x);
+        assert_eq!(lcd.mode_3_end(), end, "WY {} WX {}", window_y, window_x);
+    }
+    // Objects wait for window tiles once it's started, this one's left edge at its pixel 0
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCDC, 0xB3);
+    lcd.bus.write_u8(WINDOW_X, 50);
+    lcd.bus.write_u8(SCROLL_X, 3);
+    lcd.object(0, 51);
+    // 6 + 6 + 5
+    assert_eq!(lcd.mode_3_end(), 69);
+}
+
+#[test]
+fn hblank_interrupt_waits_for_mode_3() {
+    let mut lcd = Lcd::new();
+    lcd.bus.write_u8(LCDC, 0x93);
+    lcd.object(0, 0);
+    lcd.run_to(10, 30);
+    lcd.bus.write_u8(STAT, 0b1000);
+    lcd.bus.write_u8(IF, 0);
+    lcd.run(36);
+    assert!(!stat_requested(&lcd));
+    lcd.run(1);
+    assert!(stat_requested(&lcd));
+}
+
+impl Lcd {
+    /// The window map at 0x9C00, with tile `first` in its first row and 0 below, over a
+    /// background of tile 2. Tile 1 is dark all over and tile 2 the next shade lighter.
+    fn window(&mut self, first: u8) {
+        for row in 0..16 {
+            self.bus.write_u8(0x8010 + row, 0xFF);
+            self.bus
+                .write_u8(0x8020 + row, if row % 2 == 0 { 0xFF } else { 0 });
+        }
+        for tile in 0..32 {
+            self.bus.write_u8(0x9C00 + tile, first);
+        }
+        for tile in 0..1024 {
+            self.bus.write_u8(0x9800 + tile, 2);
+        }
+        self.bus.write_u8(PALLETE, 0b11_10_01_00);
+        self.bus.write_u8(LCDC, 0xF1);
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.frame[y * GAMEBOY_WIDTH + x]
+    }
+}
+
+#[test]
+fn window_line_only_counts_drawn_lines() {
+    for (hide, counted) in [
+        ((LCDC, 0xD1), false),
+        ((WINDOW_X, 167), false),
+        ((WINDOW_X, 166), true),
+    ] {
+        let mut lcd = Lcd::new();
+        lcd.window(1);
+        lcd.bus.write_u8(WINDOW_X, 7);
+        lcd.run(114 * 4);
+        lcd.bus.write_u8(hide.0, hide.1);
+        lcd.run(114 * 10);
+        lcd.bus.write_u8(LCDC, 0xF1);
+        lcd.bus.write_u8(WINDOW_X, 7);
+        lcd.run(114 * 10);
+
+        assert_eq!(lcd.pixel(0, 3), 0, "{:?}", hide);
+        assert_eq!(lcd.pixel(0, 10), 192);
+        // Its second row of tiles by then
+        assert_eq!(lcd.pixel(159, 10), if counted { 255 } else { 192 });
+        // Window lines 4-7 if it wasn't counting, then its second row of tiles
+        for line in 14..18 {
+            assert_eq!(
+                lcd.pixel(0, line),
+                if counted { 255 } else { 0 },
+                "{:?}",
+                hide
+            );
+        }
+        assert_eq!(lcd.pixel(0, 18), 255, "{:?}", hide);
+    }
+}
+
+#[test]
+fn window_waits_for_wy_to_match() {
+    let mut lcd = Lcd::new();
+    lcd.window(1);
+    lcd.bus.write_u8(WINDOW_X, 7);
+    lcd.bus.write_u8(WINDOW_Y, 50);
+    lcd.run(114 * 20);
+    // Already past, so it's the next frame before it shows
+    lcd.bus.write_u8(WINDOW_Y, 10);
+    lcd.run(114 * 134);
+    assert!((0..144).all(|line| lcd.pixel(0, line) == 192));
+    lcd.run(114 * 154);
+    assert_eq!(lcd.pixel(0, 9), 192);
+    assert_eq!(lcd.pixel(0, 10), 0);
+
+    // And once it has, moving WY doesn't take it away
+    let mut lcd = Lcd::new();
+    lcd.window(1);
+    lcd.bus.write_u8(WINDOW_X, 7);
+    lcd.bus.write_u8(WINDOW_Y, 5);
+    lcd.run(114 * 20);
+    lcd.bus.write_u8(WINDOW_Y, 100);
+    lcd.run(114 * 20);
+    assert_eq!(lcd.pixel(0, 4), 192);
+    assert_eq!(lcd.pixel(0, 5), 0);
+    // Window line 8 on, its second row of tiles
+    assert_eq!(lcd.pixel(0, 30), 255);
+}
+
+#[test]
+fn window_left_edge_is_cut_off_below_wx_7() {
+    for (window_x, scroll_x, dark) in [(20, 0, 13), (3, 0, 4), (0, 0, 1), (0, 3, 5), (0, 7, 2)] {
+        let mut lcd = Lcd::new();
+        lcd.window(3);
+        // Only the first pixel of each of its tiles is dark
+        lcd.bus.write_u8(0x8030, 0x80);
+        lcd.bus.write_u8(0x8031, 0x80);
+        lcd.bus.write_u8(WINDOW_X, window_x);
+        lcd.bus.write_u8(SCROLL_X, scroll_x);
+        lcd.run(114);
+        let dark_pixels: Vec<usize> = (0..GAMEBOY_WIDTH)
+            .filter(|&x| lcd.pixel(x, 0) == 0)
+            .take(3)
+            .collect();
+        assert_eq!(
+            dark_pixels,
+            [dark, dark + 8, dark + 16],
+            "WX {} SCX {}",
+            window_x,
+            scroll_x
+        );
+    }
+}
+
+#[test]
+fn lcdc_bit_0_only_blanks_outside_cgb_mode() {
+    let mut compat = Lcd::new();
+    compat.bus.set_model(HardwareModel::Cgb);
+    for (mut lcd, drawn) in [(Lcd::new(), false), (compat, false), (Lcd::cgb(), true)] {
+        lcd.window(1);
+        lcd.bus.write_u8(WINDOW_X, 87);
+        lcd.bus.write_u8(LCDC, 0xF0);
+        if drawn {
+            lcd.bg_palette(0, [0x7FFF, 0x001F, 0x03E0, 0x7C00]);
+        }
+        lcd.run(114);
+        if drawn {
+            let colors = lcd.ppu.color_frame();
+            assert_eq!([colors[0], colors[80]], [0x001F, 0x7C00]);
+        } else {
+            assert_eq!(
+                [lcd.pixel(0, 0), lcd.pixel(80, 0)],
+                [255, 255],
+                "{}",
+                lcd.bus.model()
+            );
+        }
+    }
+}
+
+impl Lcd {
+    /// Tile 1 is all color 3 and tile 2 all color 1, the background is tile 0 and BGP, OBP0
+    /// and OBP1 leave colors as they are
+    fn sprite_tiles(&mut self) {
+        for row in 0..16 {
+            self.bus.write_u8(0x8010 + row, 0xFF);
+            self.bus
+                .write_u8(0x8020 + row, if row % 2 == 0 { 0xFF } else { 0 });
+        }
+        for register in [PALLETE, OBP0, OBP1] {
+            self.bus.write_u8(register, 0b11_10_01_00);
+        }
+        self.bus.write_u8(LCDC, 0x93);
+    }
+
+    /// Puts OAM entry `i` at `x` (the screen's X + 8) on line 0
+    fn sprite(&mut self, i: u16, x: u8, tile: u8, attributes: u8) {
+        for (byte, value) in [16, x, tile, attributes].into_iter().enumerate() {
+            self.bus.write_u8(0xFE00 + i * 4 + byte as u16, value);
+        }
+    }
+
+    fn line(&self, xs: &[usize]) -> Vec<u8> {
+        xs.iter().map(|&x| self.pixel(x, 0)).collect()
+    }
+}
+
+#[test]
+fn objects_use_obp0_and_obp1() {
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    lcd.bus.write_u8(OBP1, 0b01_10_11_00);
+    lcd.sprite(0, 4, 1, 0);
+    lcd.sprite(1, 24, 1, 0x10);
+    lcd.sprite(2, 164, 2, 0x10);
+    lcd.run(114);
+    // Cut off at both edges
+    assert_eq!(lcd.line(&[0, 3, 4, 16, 23, 24]), [0, 0, 255, 192, 192, 255]);
+    assert_eq!(lcd.line(&[155, 156, 159]), [255, 0, 0]);
+
+    // Nothing without LCDC bit 1
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    lcd.bus.write_u8(LCDC, 0x91);
+    lcd.sprite(0, 8, 1, 0);
+    lcd.run(114);
+    assert_eq!(lcd.pixel(0, 0), 255);
+}
+
+#[test]
+fn objects_behind_the_background_only_cover_color_0() {
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    // Color 1 from X 8 on
+    for tile in 1..20 {
+        lcd.bus.write_u8(0x9800 + tile, 2);
+    }
+    lcd.sprite(0, 8, 1, 0x80);
+    lcd.sprite(1, 16, 1, 0x80);
+    lcd.sprite(2, 24, 1, 0);
+    lcd.run(114);
+    assert_eq!(lcd.line(&[0, 8, 16]), [0, 192, 0]);
+}
+
+#[test]
+fn overlapping_objects_follow_the_priority_order() {
+    // The one further left wins on a DMG, the first in OAM in CGB mode unless OPRI says so
+    for (mut lcd, opri, first_wins) in [
+        (Lcd::new(), 0, false),
+        (Lcd::cgb(), 0, true),
+        (Lcd::cgb(), 1, false),
+    ] {
+        lcd.sprite_tiles();
+        lcd.bus.write_u8(OPRI, opri);
+        lcd.obj_palette(0, [0, 0x001F, 0x03E0, 0x7C00]);
+        lcd.sprite(0, 20, 2, 0);
+        lcd.sprite(1, 16, 1, 0);
+        // A see-through object doesn't hide anything, even first in line
+        lcd.sprite(2, 12, 0, 0);
+        lcd.run(114);
+        if lcd.bus.cgb_mode() {
+            let colors = lcd.ppu.color_frame();
+            let overlap = if first_wins { 0x001F } else { 0x7C00 };
+            assert_eq!([colors[8], colors[12]], [0x7C00, overlap], "OPRI {}", opri);
+        } else {
+            assert_eq!(lcd.line(&[8, 12]), [0, 0]);
+        }
+    }
+
+    // Ties go to the first in OAM
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    lcd.sprite(0, 8, 2, 0);
+    lcd.sprite(1, 8, 1, 0);
+    lcd.run(114);
+    assert_eq!(lcd.pixel(0, 0), 192);
+}
+
+#[test]
+fn cgb_objects_pick_their_bank_and_palette() {
+    let mut lcd = Lcd::cgb();
+    lcd.sprite_tiles();
+    // Tile 3 is only there in bank 1
+    lcd.bus.write_u8(VBK, 1);
+    lcd.bus.write_u8(0x8030, 0x80);
+    lcd.bus.write_u8(0x8031, 0x80);
+    // With the priority bit, color 1 from X 8 on
+    for tile in 1..20 {
+        lcd.bus.write_u8(0x9800 + tile, 0x80);
+    }
+    lcd.bus.write_u8(VBK, 0);
+    for tile in 1..20 {
+        lcd.bus.write_u8(0x9800 + tile, 2);
+    }
+    lcd.bg_palette(0, [0x7FFF, 0x0000, 0x0000, 0x0000]);
+    lcd.obj_palette(2, [0, 0x001F, 0x03E0, 0x7C00]);
+    // Flipped across, so its one pixel is on the right
+    lcd.sprite(0, 8, 3, 0x2A);
+    lcd.sprite(1, 16, 1, 0x02);
+    lcd.run(114);
+    let colors = lcd.ppu.color_frame();
+    assert_eq!([colors[0], colors[7]], [0x7FFF, 0x7C00]);
+    // The tile's priority wins over the object's
+    assert_eq!(colors[8], 0x0000);
+}
+
+#[test]
+fn tall_objects_span_two_tiles() {
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    lcd.bus.write_u8(LCDC, 0x97);
+    // The bottom row of tile 3, which is the bottom half of 2 and 3
+    lcd.bus.write_u8(0x803E, 0xFF);
+    lcd.bus.write_u8(0x803F, 0xFF);
+    lcd.sprite(0, 8, 3, 0x40);
+    lcd.sprite(1, 16, 3, 0);
+    lcd.run(114);
+    // Flipped it's the bottom row on top, otherwise tile 2's first row
+    assert_eq!(lcd.line(&[0, 8]), [0, 192]);
+}
+
+#[test]
+fn only_ten_objects_are_drawn_per_line() {
+    let mut lcd = Lcd::new();
+    lcd.sprite_tiles();
+    for i in 0..11 {
+        lcd.sprite(i, 8 + i as u8 * 8, 1, 0);
+    }
+    lcd.run(114);
+    assert_eq!(lcd.line(&[72, 80]), [0, 255]);
+}
+
+#[test]
+fn lcdc_bit_0_clear_puts_objects_over_the_background() {
+    let mut compat = Lcd::new();
+    compat.bus.set_model(HardwareModel::Cgb);
+    for mut lcd in [Lcd::new(), compat, Lcd::cgb()] {
+        lcd.sprite_tiles();
+        // Color 1 everywhere, with the priority bit in CGB mode
+        for tile in 0..32 {
+            lcd.bus.write_u8(0x9800 + tile, 2);
+        }
+        if lcd.bus.cgb_mode() {
+            lcd.bus.write_u8(VBK, 1);
+            for tile in 0..32 {
+                lcd.bus.write_u8(0x9800 + tile, 0x80);
+            }
+            lcd.bus.write_u8(VBK, 0);
+            lcd.bg_palette(0, [0x7FFF, 0x001F, 0x001F, 0x001F]);
+            lcd.obj_palette(0, [0, 0x7C00, 0x7C00, 0x7C00]);
+        }
+        lcd.sprite(0, 8, 1, 0x80);
+        lcd.bus.write_u8(LCDC, 0x92);
+        lcd.run(114);
+        // Blank outside CGB mode, so only the object is left
+        if lcd.bus.cgb_mode() {
+            let colors = lcd.ppu.color_frame();
+            assert_eq!([colors[0], colors[8]], [0x7C00, 0x001F]);
+        } else {
+            assert_eq!(lcd.line(&[0, 8]), [0, 255], "{}", lcd.bus.model());
+        }
+
+        // With it set the background covers the object again
+        lcd.bus.write_u8(LCDC, 0x93);
+        lcd.run(114 * 154);
+        if lcd.bus.cgb_mode() {
+            assert_eq!(lcd.ppu.color_frame()[0], 0x001F);
+        } else {
+            assert_eq!(lcd.line(&[0, 8]), [192, 192], "{}", lcd.bus.model());
+        }
+    }
+}
+
+#[test]
+fn dmg_games_on_color_models_are_drawn_from_palette_ram() {
+    let mut lcd = Lcd::new();
+    lcd.bus.set_model(HardwareModel::Cgb);
+    lcd.sprite_tiles();
+    lcd.sprite(0, 16, 2, 0x10);
+    lcd.run(114);
+
+    let palettes = lcd.bus.palettes();
+    let background: Vec<u16> = (0..4)
+        .map(|color_id| palettes.background_color(0, color_id))
+        .collect();
+    let colors = lcd.ppu.color_frame();
+    assert_eq!(colors[0], background[0]);
+    // OBP1 picks object palette 1, not the background's colors
+    assert_eq!(colors[8], palettes.object_color(1, 1));
+    assert!(!background.contains(&colors[8]));
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use crate::emulator::serial::printer::{decompress, status, Printer, DEVICE_ID};
+
+fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x88, 0x33, command, compressed as u8];
+    packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    packet.extend_from_slice(data);
+    let checksum = packet[2..]
+        .iter()
+        .fold(0u16, |sum, b| sum.wrapping_add(*b as u16));
+    packet.extend_from_slice(&checksum.to_le_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
+
+/// Returns the device ID and status bytes
+fn send(printer: &mut Printer, packet: &[u8]) -> (u8, u8) {
+    let replies: Vec<u8> = packet.iter().map(|b| printer.transfer(*b)).collect();
+    assert!(replies[..replies.len() - 2].iter().all(|b| *b == 0));
+    (replies[replies.len() - 2], replies[replies.len() - 1])
+}
+
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gb-printer-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn decompress_runs_and_literals() {
+    assert_eq!(
+        decompress(&[0x81, 0xAB, 0x01, 0x01, 0x02]),
+        [0xAB, 0xAB, 0xAB, 0x01, 0x02]
+    );
+}
+
+#[test]
+fn init_and_status() {
+    let mut printer = Printer::new(output_dir("status"));
+    assert_eq!(
+        send(&mut printer, &packet(0x01, false, &[])),
+        (DEVICE_ID, 0)
+    );
+    assert_eq!(
+        send(&mut printer, &packet(0x04, false, &[0; 0x280])),
+        (DEVICE_ID, status::UNPROCESSED_DATA)
+    );
+
+    let mut bad = packet(0x0F, false, &[]);
+    bad[6] ^= 1;
+    assert_eq!(send(&mut printer, &bad).1 & status::CHECKSUM_ERROR, 1);
+}
+
+#[test]
+fn print_saves_png() {
+    let dir = output_dir("print");
+    let mut printer = Printer::new(dir.clone());
+    send(&mut printer, &packet(0x01, false, &[]));
+    // 2 tile rows of solid color 3: runs of 129 + 129 + 129 + 129 + 124 bytes
+    send(
+        &mut printer,
+        &packet(
+            0x04,
+            true,
+            &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFA, 0xFF],
+        ),
+    );
+    send(&mut printer, &packet(0x04, false, &[]));
+    let (_, status) = send(&mut printer, &packet(0x02, false, &[1, 0x13, 0xE4, 0x40]));
+    assert_eq!(status, status::PRINTING);
+
+    let png = std::fs::read(dir.join("print-0001.png")).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+    // 160x16
+    assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 16]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
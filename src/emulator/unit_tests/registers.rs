@@ -0,0 +1,66 @@
+use crate::emulator::{
+    cpu::{
+        registers::{Reg16, Reg8},
+        CPU,
+    },
+    hardware::HardwareModel,
+};
+
+#[test]
+fn pair_setters_put_the_high_byte_first() {
+    let mut cpu = CPU::default();
+    cpu.set_bc(0x1234);
+    cpu.set_de(0x5678);
+    cpu.set_hl(0x9ABC);
+    assert_eq!((cpu.B, cpu.C), (0x12, 0x34));
+    assert_eq!((cpu.D, cpu.E), (0x56, 0x78));
+    assert_eq!((cpu.H, cpu.L), (0x9A, 0xBC));
+}
+
+#[test]
+fn low_bits_of_f_stay_clear() {
+    let mut cpu = CPU::default();
+    cpu.set_af(0x12FF);
+    assert_eq!(cpu.get_af(), 0x12F0);
+    cpu.write_reg(Reg8::F, 0x0F);
+    assert_eq!(cpu.Flags, 0);
+}
+
+#[test]
+fn pairs_read_back_their_halves() {
+    let mut cpu = CPU::default();
+    for (i, register) in Reg16::ALL.into_iter().enumerate() {
+        cpu.write_reg(register, 0x1110 * (i as u16 + 1));
+    }
+    assert_eq!(cpu.read_reg(Reg8::A), 0x11);
+    assert_eq!(cpu.read_reg(Reg8::F), 0x10);
+    assert_eq!(cpu.read_reg(Reg8::B), 0x22);
+    assert_eq!(cpu.read_reg(Reg8::L), 0x40);
+    assert_eq!(cpu.read_reg(Reg16::SP), 0x5550);
+    assert_eq!(cpu.PC, 0x6660);
+}
+
+#[test]
+fn reset_loads_the_post_boot_registers() {
+    let mut cpu = CPU {
+        SP: 0xC000,
+        PC: 0x1234,
+        halted: true,
+        stop: true,
+        IME: true,
+        ..CPU::default()
+    };
+    cpu.reset(HardwareModel::Cgb, 0);
+    assert_eq!(cpu.get_af(), 0x1180);
+    assert_eq!(cpu.get_bc(), 0x0000);
+    assert_eq!(cpu.get_de(), 0xFF56);
+    assert_eq!(cpu.get_hl(), 0x000D);
+    assert_eq!((cpu.SP, cpu.PC), (0xFFFE, 0x100));
+    assert!(!cpu.halted && !cpu.stop && !cpu.IME);
+
+    cpu.reset(HardwareModel::Dmg, 0x12);
+    assert_eq!(cpu.get_af(), 0x01B0);
+    assert_eq!(cpu.get_hl(), 0x014D);
+    cpu.reset(HardwareModel::Mgb, 0x12);
+    assert_eq!(cpu.get_af(), 0xFFB0);
+}
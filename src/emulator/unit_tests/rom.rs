@@ -0,0 +1,270 @@
+use crate::emulator::{
+    instructions::{Instruction, Register8},
+    rom::{self, ByteKind, CodeMap, Location, RomProblem},
+};
+
+/// `rom` with `code` copied in at each offset
+fn rom(len: usize, code: &[(usize, &[u8])]) -> Vec<u8> {
+    let mut rom = vec![0; len];
+    for &(offset, bytes) in code {
+        rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+    rom
+}
+
+#[test]
+fn locations_are_bank_qualified() {
+    assert_eq!(
+        Location::from_offset(0x0150),
+        Location {
+            bank: 0,
+            addr: 0x150
+        }
+    );
+    assert_eq!(
+        Location::from_offset(0x4000),
+        Location {
+            bank: 1,
+            addr: 0x4000
+        }
+    );
+    let location = Location::from_offset(0xA123);
+    assert_eq!(
+        location,
+        Location {
+            bank: 2,
+            addr: 0x6123
+        }
+    );
+    assert_eq!(location.offset(), Some(0xA123));
+    assert_eq!(location.to_string(), "02:6123");
+    // Bank 0 isn't in the switchable window
+    assert_eq!(
+        Location {
+            bank: 0,
+            addr: 0x4000
+        }
+        .offset(),
+        None
+    );
+    assert_eq!(Location::mapped(0xC000, 1), None);
+}
+
+#[test]
+fn data_after_jumps_is_not_code() {
+    let header = [0x01; 0x4D];
+    let rom = rom(
+        0x8000,
+        &[
+            (0x100, &[0xC3, 0x50, 0x01]),
+            (0x103, &header),
+            // ld a, 1; call $0200; jr -2
+            (0x150, &[0x3E, 0x01, 0xCD, 0x00, 0x02, 0x18, 0xFE]),
+            // ret, then data
+            (0x200, &[0xC9, 0x3E, 0x00]),
+        ],
+    );
+    let map = CodeMap::analyze(&rom);
+    assert_eq!(map.kind(0x100), ByteKind::Opcode);
+    assert_eq!(map.kind(0x101), ByteKind::Operand);
+    for offset in 0x103..0x150 {
+        assert_eq!(map.kind(offset), ByteKind::Data, "{:#X}", offset);
+    }
+    assert_eq!(map.kind(0x152), ByteKind::Opcode);
+    assert_eq!(map.kind(0x155), ByteKind::Opcode);
+    assert_eq!(map.kind(0x200), ByteKind::Opcode);
+    assert_eq!(map.kind(0x201), ByteKind::Data);
+    assert_eq!(map.kind(0x157), ByteKind::Data);
+    assert!(map.labels().any(|label| label
+        == Location {
+            bank: 0,
+            addr: 0x200
+        }));
+}
+
+#[test]
+fn follows_bank_switches() {
+    let rom = rom(
+        0x10000,
+        &[
+            // ld a, 2; ld [$2000], a; call $4000; jp $4010; ret from bank 2
+            (
+                0x100,
+                &[
+                    0x3E, 0x02, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0xC3, 0x10, 0x40,
+                ],
+            ),
+            (0x8000, &[0xC9]),
+            (0x8010, &[0x18, 0xFE]),
+            // Bank 1 is data
+            (0x4000, &[0xFF; 0x20]),
+        ],
+    );
+    let map = CodeMap::analyze(&rom);
+    assert_eq!(map.kind(0x8000), ByteKind::Opcode);
+    assert_eq!(map.kind(0x8001), ByteKind::Data);
+    assert!(map.is_code(Location {
+        bank: 2,
+        addr: 0x4010
+    }));
+    assert_eq!(map.kind(0x4000), ByteKind::Data);
+    assert_eq!(map.kind(0x4010), ByteKind::Data);
+}
+
+#[test]
+fn defaults_to_bank_1() {
+    let rom = rom(
+        0x8000,
+        &[
+            (0x100, &[0xCD, 0x34, 0x52, 0x76, 0x18, 0xFD]),
+            (0x5234, &[0xC9]),
+        ],
+    );
+    let map = CodeMap::analyze(&rom);
+    assert!(map.is_code(Location {
+        bank: 1,
+        addr: 0x5234
+    }));
+    assert_eq!(map.kind(0x5235), ByteKind::Data);
+}
+
+#[test]
+fn does_not_follow_into_ram() {
+    let rom = rom(0x8000, &[(0x100, &[0xC3, 0x80, 0xFF])]);
+    let map = CodeMap::analyze(&rom);
+    assert_eq!(map.kind(0x103), ByteKind::Data);
+}
+
+#[test]
+fn lists_instructions_in_order() {
+    let rom = rom(
+        0x8000,
+        &[
+            (0x00, &[0xC9; 0x68]),
+            (0x100, &[0x00, 0x3E, 0x07, 0x18, 0xFB]),
+        ],
+    );
+    let map = CodeMap::analyze(&rom);
+    let instructions: Vec<_> = map
+        .instructions(&rom)
+        .filter(|(location, _)| location.addr >= 0x100)
+        .collect();
+    assert_eq!(
+        instructions,
+        [
+            (
+                Location {
+                    bank: 0,
+                    addr: 0x100
+                },
+                Instruction::Nop
+            ),
+            (
+                Location {
+                    bank: 0,
+                    addr: 0x101
+                },
+                Instruction::LoadImmediate(Register8::A, 0x07)
+            ),
+            (
+                Location {
+                    bank: 0,
+                    addr: 0x103
+                },
+                Instruction::JumpRelative(-5)
+            ),
+        ]
+    );
+    // A RET at each RST and interrupt vector
+    assert_eq!(map.code_bytes(), 8 + 5 + 5);
+}
+
+#[test]
+fn header_title() {
+    let mut rom = vec![0; 0x150];
+    rom[0x134..0x13C].copy_from_slice(b"TETRIS\0X");
+    assert_eq!(rom::header_title(&rom), "TETRIS");
+    // All 16 bytes, nothing past the title
+    rom[0x134..0x145].copy_from_slice(b"ABCDEFGHIJKLMNOPQ");
+    assert_eq!(rom::header_title(&rom), "ABCDEFGHIJKLMNOP");
+    assert_eq!(rom::header_title(&rom[..0x136]), "AB");
+    assert_eq!(rom::header_title(&[]), "");
+}
+
+#[test]
+fn detects_mbc1_multicarts() {
+    let mut multicart = rom(0x10_0000, &[(0x40104, &rom::NINTENDO_LOGO)]);
+    multicart[0x147] = 0x01;
+    assert!(rom::is_mbc1_multicart(&multicart));
+
+    // A 1MB MBC1 game only has the one header
+    let mut single = rom(0x10_0000, &[(0x104, &rom::NINTENDO_LOGO)]);
+    single[0x147] = 0x01;
+    assert!(!rom::is_mbc1_multicart(&single));
+    multicart[0x147] = 0x19;
+    assert!(!rom::is_mbc1_multicart(&multicart));
+    assert!(!rom::is_mbc1_multicart(&[0; 0x8000]));
+}
+
+/// A ROM with a good header and a byte of code
+fn good_rom() -> Vec<u8> {
+    let mut good = rom(0x8000, &[(0x104, &rom::NINTENDO_LOGO), (0x150, &[0x18])]);
+    good[0x134..0x13A].copy_from_slice(b"TETRIS");
+    good[0x14D] = rom::header_checksum(&good).unwrap();
+    let checksum = rom::global_checksum(&good).to_be_bytes();
+    good[0x14E..0x150].copy_from_slice(&checksum);
+    good
+}
+
+#[test]
+fn checks_rom_integrity() {
+    let good = good_rom();
+    assert_eq!(rom::check_integrity(&good), []);
+    // 0x134-0x14C are 0x19 zeros
+    assert_eq!(rom::header_checksum(&[0; 0x150]), Some(0xE7));
+    assert_eq!(rom::header_checksum(&[0; 0x14C]), None);
+
+    let mut bad_byte = good.clone();
+    bad_byte[0x4000] = 0xFF;
+    let stored = u16::from_be_bytes([good[0x14E], good[0x14F]]);
+    assert_eq!(
+        rom::check_integrity(&bad_byte),
+        [RomProblem::GlobalChecksum {
+            stored,
+            computed: stored.wrapping_add(0xFF)
+        }]
+    );
+
+    let mut bad_header = good;
+    bad_header[0x104] = 0;
+    bad_header[0x14D] ^= 1;
+    let problems = rom::check_integrity(&bad_header);
+    assert_eq!(problems[0], RomProblem::Logo);
+    assert!(matches!(problems[1], RomProblem::HeaderChecksum { .. }));
+    assert_eq!(rom::check_integrity(&[]), [RomProblem::Logo]);
+}
+
+#[test]
+fn pads_truncated_roms() {
+    let mut truncated = good_rom();
+    // 64K, in 4 banks
+    truncated[0x148] = 0x01;
+    truncated[0x14D] = rom::header_checksum(&truncated).unwrap();
+    assert_eq!(rom::declared_size(&truncated), Some(0x10000));
+    assert!(
+        rom::check_integrity(&truncated).contains(&RomProblem::Truncated {
+            size: 0x8000,
+            declared: 0x10000
+        })
+    );
+    let padded = rom::pad(truncated.clone());
+    assert_eq!(padded[..0x8000], truncated[..]);
+    assert!(padded[0x8000..].iter().all(|&byte| byte == 0xFF));
+    assert_eq!(padded.len(), 0x10000);
+
+    // Bigger than the header says is left alone, and there's always 32K
+    assert_eq!(rom::pad(vec![0; 0x9000]).len(), 0x9000);
+    assert_eq!(rom::pad(Vec::new()), vec![0xFF; 0x8000]);
+    truncated[0x148] = 0x20;
+    assert_eq!(rom::declared_size(&truncated), None);
+}
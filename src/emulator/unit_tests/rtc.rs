@@ -0,0 +1,66 @@
+use crate::emulator::rtc::{join_sav, split_sav, RtcFooter, RtcRegisters, DH_CARRY, DH_HALT};
+
+fn footer() -> RtcFooter {
+    RtcFooter {
+        registers: RtcRegisters {
+            seconds: 30,
+            minutes: 59,
+            hours: 23,
+            day_low: 0xFF,
+            day_high: 0x01,
+        },
+        latched: RtcRegisters {
+            seconds: 1,
+            minutes: 2,
+            hours: 3,
+            day_low: 4,
+            day_high: DH_HALT,
+        },
+        timestamp: 0x1_2345_6789,
+    }
+}
+
+#[test]
+fn round_trips() {
+    let ram = vec![0xAB; 0x2000];
+    let sav = join_sav(&ram, &footer());
+    assert_eq!(sav.len(), 0x2000 + 48);
+    assert_eq!(&sav[0x2000..0x2004], [30, 0, 0, 0]);
+    assert_eq!(split_sav(&sav), (&ram[..], Some(footer())));
+}
+
+#[test]
+fn reads_the_short_footer() {
+    let mut sav = footer().to_bytes();
+    sav.truncate(44);
+    let (ram, found) = split_sav(&sav);
+    assert!(ram.is_empty());
+    assert_eq!(found.unwrap().timestamp, 0x2345_6789);
+}
+
+#[test]
+fn plain_ram_has_no_footer() {
+    let ram = vec![0; 0x8000];
+    assert_eq!(split_sav(&ram), (&ram[..], None));
+}
+
+#[test]
+fn advances_with_day_carry() {
+    let footer = footer();
+    let registers = footer.registers_at(footer.timestamp + 30);
+    assert_eq!(
+        (registers.hours, registers.minutes, registers.seconds),
+        (0, 0, 0)
+    );
+    assert_eq!(registers.days(), 0);
+    assert_eq!(registers.day_high, DH_CARRY);
+    // Clock set back since, nothing happens
+    assert_eq!(footer.registers_at(0), footer.registers);
+}
+
+#[test]
+fn halted_clock_stays_put() {
+    let mut registers = footer().latched;
+    registers.advance(1000);
+    assert_eq!(registers, footer().latched);
+}
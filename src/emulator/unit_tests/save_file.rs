@@ -0,0 +1,91 @@
+use std::{fs, path::PathBuf};
+
+use crate::emulator::{
+    save_file::{backup_path, read_with_backups, write_atomic},
+    BatteryFile, Emulator,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gb-save-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn creates_the_directory() {
+    let dir = temp_dir("create");
+    let path = dir.join("saves").join("game.sav");
+    write_atomic(&path, b"one").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"one");
+    assert!(!backup_path(&path, 1).exists());
+    assert!(!dir.join("saves").join("game.sav.tmp").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rotates_backups() {
+    let dir = temp_dir("rotate");
+    let path = dir.join("game.sav");
+    for contents in ["one", "two", "three", "four"] {
+        write_atomic(&path, contents.as_bytes()).unwrap();
+    }
+    assert_eq!(fs::read(&path).unwrap(), b"four");
+    assert_eq!(backup_path(&path, 1), dir.join("game.sav.bak1"));
+    assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), b"three");
+    assert_eq!(fs::read(backup_path(&path, 2)).unwrap(), b"two");
+    assert!(!backup_path(&path, 3).exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn falls_back_to_a_backup() {
+    let dir = temp_dir("fallback");
+    let path = dir.join("game.sav");
+    assert_eq!(read_with_backups(&path, |_| true).unwrap(), None);
+
+    write_atomic(&path, b"good").unwrap();
+    write_atomic(&path, b"bad").unwrap();
+    let four_bytes = |bytes: &[u8]| bytes.len() == 4;
+    assert_eq!(
+        read_with_backups(&path, four_bytes).unwrap(),
+        Some(b"good".to_vec())
+    );
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        read_with_backups(&path, |_| true).unwrap(),
+        Some(b"good".to_vec())
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn battery_saves_load_backups_and_flush_when_ram_is_disabled() {
+    let dir = temp_dir("battery");
+    let path = dir.join("game.sav");
+    let mut good = vec![0; 0x20000];
+    good[0x123] = 0x42;
+    write_atomic(&path, &good).unwrap();
+    write_atomic(&path, b"too short").unwrap();
+
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0xFC;
+    let mut emulator = Emulator::new(&rom);
+    let mut battery = BatteryFile::load(&mut emulator, path.clone());
+    let memory_bus = emulator.memory_bus_mut();
+    memory_bus.write_u8(0x0000, 0x0A);
+    assert_eq!(memory_bus.read_u8(0xA123), 0x42);
+
+    // Nothing changed, nothing's written
+    battery.frame(&mut emulator);
+    battery.flush(&emulator, true);
+    assert_eq!(fs::read(&path).unwrap(), b"too short");
+
+    // Still enabled, the game might not be done
+    emulator.memory_bus_mut().write_u8(0xA123, 0x43);
+    battery.frame(&mut emulator);
+    assert_eq!(fs::read(&path).unwrap(), b"too short");
+    emulator.memory_bus_mut().write_u8(0x0000, 0x00);
+    battery.frame(&mut emulator);
+    assert_eq!(fs::read(&path).unwrap()[0x123], 0x43);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,189 @@
+use crate::emulator::{
+    hardware::HardwareModel,
+    memory_bus::MemoryBus,
+    serial::{
+        console::Lines, link_pair, partner::Partner, Serial, SerialLink, CYCLES_PER_FAST_TRANSFER,
+        CYCLES_PER_TRANSFER, SB, SC, SENT_LIMIT,
+    },
+};
+
+/// Answers every transfer with the same byte and remembers what it was sent
+#[derive(Debug, Default)]
+struct FixedLink {
+    answer: u8,
+    sent: Vec<u8>,
+    incoming: Option<u8>,
+}
+
+impl SerialLink for FixedLink {
+    fn exchange(&mut self, out: u8) -> Option<u8> {
+        self.sent.push(out);
+        Some(self.answer)
+    }
+
+    fn poll(&mut self, reply: u8) -> Option<u8> {
+        let incoming = self.incoming.take()?;
+        self.sent.push(reply);
+        Some(incoming)
+    }
+}
+
+#[test]
+fn internal_clock_transfer_takes_a_byte() {
+    let mut serial = Serial::default();
+    serial.connect(Box::new(FixedLink {
+        answer: 0x42,
+        ..Default::default()
+    }));
+    serial.write(SB, 0x12);
+    serial.write(SC, 0x81);
+    assert_eq!(serial.read(SC), 0xFF);
+
+    assert!(!serial.tick(CYCLES_PER_TRANSFER - 4));
+    assert!(serial.tick(4));
+    assert_eq!(serial.read(SB), 0x42);
+    assert_eq!(serial.read(SC), 0x7F);
+}
+
+#[test]
+fn unplugged_transfer_shifts_in_ff() {
+    let mut serial = Serial::default();
+    serial.write(SB, 0x12);
+    serial.write(SC, 0x81);
+    assert_eq!(serial.cycles_until_interrupt(), CYCLES_PER_TRANSFER);
+    assert!(!serial.tick(CYCLES_PER_TRANSFER - 4));
+    assert!(serial.tick(4));
+    assert_eq!(serial.read(SB), 0xFF);
+    assert_eq!(serial.read(SC), 0x7F);
+    assert_eq!(serial.cycles_until_interrupt(), u32::MAX);
+
+    // Nothing ever clocks one on the external clock
+    serial.write(SC, 0x80);
+    assert!(!serial.tick(CYCLES_PER_TRANSFER * 2));
+    assert_eq!(serial.read(SC), 0xFE);
+}
+
+#[test]
+fn external_clock_only_answers_when_waiting() {
+    let mut serial = Serial::default();
+    serial.connect(Box::new(FixedLink {
+        incoming: Some(0x55),
+        ..Default::default()
+    }));
+    serial.write(SB, 0x12);
+    // Not waiting for a transfer, the other side sees the line idle
+    assert!(!serial.tick(4));
+    assert_eq!(serial.read(SB), 0x12);
+}
+
+#[test]
+fn channel_link_swaps_bytes() {
+    let (first, second) = link_pair();
+
+    let slave = std::thread::spawn(move || {
+        let mut serial = Serial::default();
+        serial.connect(Box::new(second));
+        serial.write(SB, 0xBB);
+        serial.write(SC, 0x80);
+        while !serial.tick(4) {
+            std::thread::yield_now();
+        }
+        serial.read(SB)
+    });
+
+    let mut master = Serial::default();
+    master.connect(Box::new(first));
+    master.write(SB, 0xAA);
+    master.write(SC, 0x81);
+    assert!(master.tick(CYCLES_PER_TRANSFER));
+    assert_eq!(master.read(SB), 0xBB);
+    assert_eq!(slave.join().unwrap(), 0xAA);
+}
+
+#[test]
+fn fast_clock_is_only_there_in_cgb_mode() {
+    let mut serial = Serial::default();
+    serial.connect(Box::new(FixedLink::default()));
+    serial.write(SC, 0x83);
+    assert_eq!(serial.read(SC), 0xFF);
+    assert_eq!(serial.cycles_per_transfer(), CYCLES_PER_TRANSFER);
+
+    serial.set_cgb_mode(true);
+    serial.write(SC, 0x83);
+    assert_eq!(serial.read(SC), 0xFF);
+    assert_eq!(serial.cycles_per_transfer(), CYCLES_PER_FAST_TRANSFER);
+    assert!(serial.tick(CYCLES_PER_FAST_TRANSFER));
+    assert_eq!(serial.read(SC), 0x7F);
+    serial.write(SC, 0x80);
+    assert_eq!(serial.read(SC), 0xFC);
+
+    let mut rom = [0; 0x8000];
+    rom[0x143] = 0x80;
+    let mut bus = MemoryBus::new(&rom[..]);
+    bus.write_u8(SC, 0x00);
+    assert_eq!(bus.read_u8(SC), 0x7E);
+    bus.set_model(HardwareModel::Cgb);
+    assert_eq!(bus.read_u8(SC), 0x7C);
+}
+
+#[test]
+fn partner_clocks_external_transfers() {
+    let mut serial = Serial::default();
+    // Twice as fast as the usual clock
+    serial.connect(Box::new(Partner::new(0x5A, 16384)));
+    let period = CYCLES_PER_TRANSFER / 2;
+
+    // A byte clocked while nothing's waiting is lost
+    assert!(!serial.tick(period));
+    serial.write(SB, 0x12);
+    serial.write(SC, 0x80);
+    assert!(!serial.tick(period / 2));
+    assert_eq!(serial.read(SB), 0x12);
+    assert!(!serial.tick(period / 2 - 4));
+    assert!(serial.tick(4));
+    assert_eq!(serial.read(SB), 0x5A);
+    assert_eq!(serial.read(SC), 0x7E);
+
+    // It answers transfers the game clocks too
+    serial.write(SC, 0x81);
+    assert!(serial.tick(CYCLES_PER_TRANSFER));
+    assert_eq!(serial.read(SB), 0x5A);
+}
+
+#[test]
+fn sent_bytes_are_kept_until_taken() {
+    let mut serial = Serial::default();
+    for &byte in b"ok\n" {
+        serial.write(SB, byte);
+        serial.write(SC, 0x81);
+        serial.tick(CYCLES_PER_TRANSFER);
+    }
+    assert_eq!(serial.take_sent(), b"ok\n");
+    assert!(serial.take_sent().is_empty());
+
+    // Waiting on the external clock sends nothing until the other side clocks it
+    serial.connect(Box::new(FixedLink::default()));
+    serial.write(SB, 0x12);
+    serial.write(SC, 0x80);
+    serial.tick(4);
+    assert!(serial.take_sent().is_empty());
+
+    let mut serial = Serial::default();
+    for i in 0..=SENT_LIMIT {
+        serial.write(SB, i as u8);
+        serial.write(SC, 0x81);
+    }
+    let sent = serial.take_sent();
+    assert_eq!(sent.len(), SENT_LIMIT);
+    assert_eq!(sent[0], 1);
+}
+
+#[test]
+fn console_splits_output_into_lines() {
+    let mut lines = Lines::default();
+    assert_eq!(lines.push(b"Test\r\nPas"), ["Test"]);
+    assert_eq!(lines.partial(), "Pas");
+    assert_eq!(lines.push(b"sed\n\nDone"), ["Passed", ""]);
+    assert_eq!(lines.finish().as_deref(), Some("Done"));
+    assert_eq!(lines.finish(), None);
+}
@@ -0,0 +1,57 @@
+use std::{io::Write, net::TcpListener};
+
+use crate::emulator::serial::{
+    tcp::{self, LinkError},
+    Serial, SerialLink, CYCLES_PER_TRANSFER, SB, SC,
+};
+
+#[test]
+fn tcp_link_swaps_bytes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let host = std::thread::spawn(move || tcp::accept(&listener).unwrap());
+    let guest = tcp::connect(addr).unwrap();
+    let host = host.join().unwrap();
+
+    let slave = std::thread::spawn(move || {
+        let mut serial = Serial::default();
+        serial.connect(Box::new(guest));
+        serial.write(SB, 0x60);
+        serial.write(SC, 0x80);
+        while !serial.tick(4) {
+            std::thread::yield_now();
+        }
+        serial.read(SB)
+    });
+
+    let mut master = Serial::default();
+    master.connect(Box::new(host));
+    master.write(SB, 0x29);
+    master.write(SC, 0x81);
+    assert!(master.tick(CYCLES_PER_TRANSFER));
+    assert_eq!(master.read(SB), 0x60);
+    assert_eq!(slave.join().unwrap(), 0x29);
+}
+
+#[test]
+fn tcp_link_rejects_strangers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stranger = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"HTTP/1.1 400").unwrap();
+    });
+    assert!(matches!(tcp::connect(addr), Err(LinkError::BadMagic)));
+    stranger.join().unwrap();
+}
+
+#[test]
+fn tcp_link_gives_up_when_partner_leaves() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let host = std::thread::spawn(move || tcp::accept(&listener).unwrap());
+    let guest = tcp::connect(addr).unwrap();
+    let mut host = host.join().unwrap();
+    drop(guest);
+    assert_eq!(host.exchange(0x01), None);
+}
@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use crate::emulator::{
+    hardware::HardwareModel,
+    memory_bus::{SVBK, VBK},
+    paths::Paths,
+    state::{self, StateError},
+    Emulator,
+};
+
+fn spin_rom(checksum: u16) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100] = 0x18;
+    rom[0x101] = 0xFE;
+    rom[0x14E..0x150].copy_from_slice(&checksum.to_be_bytes());
+    rom
+}
+
+#[test]
+fn state_roundtrip() {
+    let mut emulator = Emulator::new(&spin_rom(0x1234));
+    emulator.memory_bus_mut().write_u8(0xC123, 0x42);
+    while !emulator.step().unwrap() {}
+    let state = emulator.save_state();
+    assert_eq!(&state[0..7], b"GBST\x19\x34\x12");
+
+    while !emulator.step().unwrap() {}
+    emulator.memory_bus_mut().write_u8(0xC123, 0x00);
+    assert_ne!(emulator.save_state(), state);
+
+    emulator.load_state(&state).unwrap();
+    assert_eq!(emulator.memory_bus().read_u8(0xC123), 0x42);
+    assert_eq!(emulator.save_state(), state);
+}
+
+#[test]
+fn bad_states_are_rejected() {
+    let mut emulator = Emulator::new(&spin_rom(0x1234));
+    let state = emulator.save_state();
+
+    let mut other = Emulator::new(&spin_rom(0x4321));
+    assert_eq!(
+        other.load_state(&state),
+        Err(StateError::WrongRom {
+            expected: 0x4321,
+            found: 0x1234
+        })
+    );
+    assert_eq!(
+        emulator.load_state(&state[..state.len() - 1]),
+        Err(StateError::Truncated)
+    );
+    assert_eq!(emulator.load_state(b"GB"), Err(StateError::BadMagic));
+
+    let mut long = state.clone();
+    long.push(0);
+    assert_eq!(emulator.load_state(&long), Err(StateError::TrailingData(1)));
+}
+
+#[test]
+fn resume_file_is_per_game() {
+    let path = state::resume_file(&Paths::in_dir(Path::new("config")), "POKEMON RED", 0x91E6);
+    assert_eq!(
+        path,
+        Path::new("config/states/POKEMON_RED-91E6.resume.gbst")
+    );
+}
+
+#[test]
+fn cgb_banks_are_saved() {
+    let mut rom = spin_rom(0x1234);
+    rom[0x143] = 0xC0;
+    let mut emulator = Emulator::new(&rom);
+    emulator.set_model(HardwareModel::Cgb);
+    let bus = emulator.memory_bus_mut();
+    bus.write_u8(SVBK, 0x05);
+    bus.write_u8(0xD123, 0x42);
+    bus.write_u8(VBK, 0x01);
+    bus.write_u8(0x8000, 0xFF);
+    let state = emulator.save_state();
+
+    let bus = emulator.memory_bus_mut();
+    bus.write_u8(0xD123, 0x00);
+    bus.write_u8(SVBK, 0x02);
+    bus.write_u8(0x8000, 0x00);
+    bus.write_u8(VBK, 0x00);
+    emulator.load_state(&state).unwrap();
+    let bus = emulator.memory_bus();
+    assert_eq!(bus.read_u8(SVBK), 0xFD);
+    assert_eq!(bus.read_u8(0xD123), 0x42);
+    assert_eq!(bus.read_u8(VBK), 0xFF);
+    assert_eq!(bus.read_u8(0x8000), 0xFF);
+    assert_eq!(bus.tile_row(1, 0x8000), &[1; 8]);
+}
@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use crate::emulator::{
+    run_headless,
+    stats::{StatsMeter, Throughput, CLOCK_HZ, WINDOW},
+    Emulator, Options,
+};
+
+const FRAME_CYCLES: u64 = 70224;
+
+#[test]
+fn full_speed_is_100_percent() {
+    let start = Instant::now();
+    let mut meter = StatsMeter::new(start, 1000);
+    let frame = Duration::from_secs_f64(FRAME_CYCLES as f64 / CLOCK_HZ);
+    let mut published = None;
+    let mut n = 0;
+    while published.is_none() {
+        n += 1;
+        published = meter.frame(start + frame * n, 1000 + FRAME_CYCLES * n as u64);
+    }
+    let stats = published.unwrap();
+    assert!(frame * n >= WINDOW);
+    assert!((stats.fps - 59.73).abs() < 0.01, "{}", stats.fps);
+    assert!((stats.speed - 100.0).abs() < 0.01, "{}", stats.speed);
+    assert_eq!(stats.cycles_per_frame, FRAME_CYCLES as f64);
+    assert_eq!(stats.frames, n as u64);
+    assert_eq!(stats.audio_buffer, None);
+
+    // A second's worth of cycles in half a second
+    let now = start + frame * n;
+    let cycles = 1000 + FRAME_CYCLES * n as u64;
+    let stats = meter.frame(now + WINDOW, cycles + CLOCK_HZ as u64).unwrap();
+    assert!((stats.speed - 200.0).abs() < 0.01, "{}", stats.speed);
+
+    let paused = meter.pause(now + WINDOW * 2, cycles);
+    assert_eq!((paused.fps, paused.speed), (0.0, 0.0));
+    assert_eq!(paused.frames, n as u64 + 1);
+}
+
+#[test]
+fn cycles_count_up() {
+    let mut rom = vec![0; 0x8000];
+    // JR -2
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    let mut emulator = Emulator::new(&rom);
+    emulator.step().unwrap();
+    assert_eq!(emulator.cycles(), 12);
+    emulator.run_frame().unwrap();
+    let after_frame = emulator.cycles();
+    emulator.run_frame().unwrap();
+    assert_eq!(emulator.cycles() - after_frame, FRAME_CYCLES);
+    emulator.reset();
+    assert_eq!(emulator.cycles(), after_frame + FRAME_CYCLES);
+}
+
+#[test]
+fn headless_runs_report_throughput() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    let options = Options {
+        rom: Some(rom),
+        ..Default::default()
+    };
+    let throughput = run_headless(options, 10, |_, _, _| true).unwrap();
+    assert_eq!(throughput.frames, 10);
+    // The first frame is cut short by where the boot ROM hands over
+    assert!(throughput.cycles > 9 * FRAME_CYCLES && throughput.cycles <= 10 * FRAME_CYCLES);
+    // Every JR takes 12 cycles
+    assert_eq!(throughput.instructions, throughput.cycles / 12);
+
+    let throughput = Throughput {
+        frames: 60,
+        cycles: 60 * FRAME_CYCLES,
+        instructions: 1_000_000,
+        elapsed: Duration::from_millis(500),
+    };
+    assert!((throughput.emulated_seconds() - 1.0046).abs() < 0.001);
+    assert_eq!(throughput.instructions_per_second(), 2_000_000.0);
+    let stats = throughput.stats();
+    assert_eq!(stats.fps, 120.0);
+    assert!((stats.speed - 200.9).abs() < 0.1, "{}", stats.speed);
+    assert!(throughput
+        .to_string()
+        .contains("2.00M instructions per second"));
+}
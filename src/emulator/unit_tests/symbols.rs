@@ -0,0 +1,85 @@
+use crate::emulator::{
+    rom::Location,
+    symbols::{SymbolError, Symbols},
+    Emulator,
+};
+
+const SYM: &str = "\
+; File generated by rgblink
+00:0000 RST_00
+00:0150 Main
+00:0158 Main.loop
+01:4000 Bank1Start
+02:4000 Bank2Start
+00:c000 wPlayerX ; position
+";
+
+fn location(bank: u16, addr: u16) -> Location {
+    Location { bank, addr }
+}
+
+#[test]
+fn parses_rgbds_symbols() {
+    let symbols = Symbols::parse(SYM).unwrap();
+    assert_eq!(symbols.len(), 6);
+    assert_eq!(symbols.get(location(0, 0x150)), Some("Main"));
+    assert_eq!(symbols.get(location(0, 0xC000)), Some("wPlayerX"));
+    assert_eq!(symbols.get(location(0, 0x151)), None);
+    assert_eq!(symbols.find("Main.loop"), Some(location(0, 0x158)));
+    assert_eq!(symbols.find("Nope"), None);
+}
+
+#[test]
+fn skips_sections_and_blank_lines() {
+    let symbols = Symbols::parse("[labels]\n\n  00:0100 Start  \n").unwrap();
+    assert_eq!(symbols.get(location(0, 0x100)), Some("Start"));
+}
+
+#[test]
+fn reports_bad_lines() {
+    assert_eq!(
+        Symbols::parse("00:0100 Start\n0100 NoBank").unwrap_err(),
+        SymbolError {
+            line: 2,
+            text: "0100 NoBank".into()
+        }
+    );
+    assert!(Symbols::parse("zz:0100 Start").is_err());
+    assert!(Symbols::parse("00:0100").is_err());
+}
+
+#[test]
+fn lookup_is_bank_qualified() {
+    let symbols = Symbols::parse(SYM).unwrap();
+    assert_eq!(symbols.lookup(location(0, 0x153)), Some(("Main", 3)));
+    assert_eq!(
+        symbols.lookup(location(2, 0x4010)),
+        Some(("Bank2Start", 0x10))
+    );
+    assert_eq!(
+        symbols.lookup(location(1, 0x4010)),
+        Some(("Bank1Start", 0x10))
+    );
+    assert_eq!(symbols.lookup(location(3, 0x4010)), None);
+    assert_eq!(
+        symbols.describe(location(0, 0x158)).as_deref(),
+        Some("Main.loop")
+    );
+    assert_eq!(
+        symbols.describe(location(0, 0x15A)).as_deref(),
+        Some("Main.loop+0x2")
+    );
+}
+
+#[test]
+fn crashes_name_the_label() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100] = 0xD3;
+    let mut emulator = Emulator::new(&rom);
+    emulator.set_symbols(Symbols::parse("00:00FE Start").unwrap());
+    let crash = emulator.run_frame_catching().unwrap_err();
+    assert_eq!(
+        crash.message,
+        "Illegal instruction 0xD3 at 0x0100 (Start+0x2)"
+    );
+}
@@ -0,0 +1,43 @@
+use crate::emulator::{
+    memory_bus::MemoryBus,
+    state::{StateReader, StateWriter},
+};
+
+#[test]
+fn rows_are_decoded_as_written() {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    assert_eq!(bus.tile_row(0, 0x8010), &[0; 8]);
+    // Low bits, then high bits
+    bus.write_u8(0x8010, 0b1010_0000);
+    assert_eq!(bus.tile_row(0, 0x8010), &[1, 0, 1, 0, 0, 0, 0, 0]);
+    bus.write_u8(0x8011, 0b1100_0001);
+    assert_eq!(bus.tile_row(0, 0x8010), &[3, 2, 1, 0, 0, 0, 0, 2]);
+    // The next row is separate
+    assert_eq!(bus.tile_row(0, 0x8012), &[0; 8]);
+}
+
+#[test]
+fn last_tile_and_tile_maps() {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    bus.write_u8(0x97FF, 0xFF);
+    assert_eq!(bus.tile_row(0, 0x97FE), &[2; 8]);
+    // Tile maps aren't tile data, writing them leaves the cache alone
+    bus.write_u8(0x9800, 0xFF);
+    assert_eq!(bus.tile_row(0, 0x97FE), &[2; 8]);
+}
+
+#[test]
+fn rebuilt_on_load_state() {
+    let mut bus = MemoryBus::new(&[0; 0x8000][..]);
+    bus.write_u8(0x8000, 0xFF);
+    bus.write_u8(0x8001, 0xFF);
+    let mut writer = StateWriter::new(0);
+    bus.save_state(&mut writer);
+    let state = writer.finish();
+
+    let mut other = MemoryBus::new(&[0; 0x8000][..]);
+    other
+        .load_state(&mut StateReader::new(&state, 0).unwrap())
+        .unwrap();
+    assert_eq!(other.tile_row(0, 0x8000), &[3; 8]);
+}
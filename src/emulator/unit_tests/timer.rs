@@ -0,0 +1,176 @@
+use crate::emulator::timer::{Timer, DIV, TAC, TIMA, TMA};
+
+/// Enabled, counting every 16 T-cycles
+const TAC_16: u8 = 0b101;
+
+#[test]
+fn div_counts_at_16384hz() {
+    let mut timer = Timer::default();
+    timer.tick(252);
+    assert_eq!(timer.read(DIV), 0);
+    timer.tick(4);
+    assert_eq!(timer.read(DIV), 1);
+    timer.tick(256 * 255);
+    assert_eq!(timer.read(DIV), 0);
+}
+
+#[test]
+fn writing_div_resets_it() {
+    let mut timer = Timer::default();
+    timer.tick(1024);
+    assert_eq!(timer.read(DIV), 4);
+    timer.write(DIV, 0x42);
+    assert_eq!(timer.read(DIV), 0);
+    timer.tick(252);
+    assert_eq!(timer.read(DIV), 0);
+}
+
+#[test]
+fn tima_counts_at_the_selected_rate() {
+    for (tac, period) in [(0b100, 1024), (0b101, 16), (0b110, 64), (0b111, 256)] {
+        let mut timer = Timer::default();
+        timer.write(TAC, tac);
+        timer.tick(period * 3);
+        assert_eq!(timer.read(TIMA), 3, "TAC {:#b}", tac);
+    }
+}
+
+#[test]
+fn tima_stops_when_disabled() {
+    let mut timer = Timer::default();
+    timer.write(TAC, 0b001);
+    timer.tick(1024);
+    assert_eq!(timer.read(TIMA), 0);
+}
+
+/// TIMA one increment away from overflowing, which happens at the end of the next `tick(16)`
+fn about_to_overflow() -> Timer {
+    let mut timer = Timer::default();
+    timer.write(TMA, 0xF0);
+    timer.write(TIMA, 0xFF);
+    timer.write(TAC, TAC_16);
+    timer
+}
+
+#[test]
+fn tima_overflow_reloads_tma() {
+    let mut timer = Timer::default();
+    timer.write(TMA, 0xF0);
+    timer.write(TIMA, 0xFE);
+    timer.write(TAC, TAC_16);
+    assert!(!timer.tick(16));
+    assert_eq!(timer.read(TIMA), 0xFF);
+    // TIMA sits at 0 for an M-cycle before the reload and interrupt
+    assert!(!timer.tick(16));
+    assert_eq!(timer.read(TIMA), 0x00);
+    assert!(timer.tick(4));
+    assert_eq!(timer.read(TIMA), 0xF0);
+    assert!(!timer.tick(4));
+}
+
+#[test]
+fn tima_write_during_delay_cancels_reload() {
+    let mut timer = about_to_overflow();
+    timer.tick(16);
+    timer.write(TIMA, 0x12);
+    assert!(!timer.tick(4));
+    assert_eq!(timer.read(TIMA), 0x12);
+}
+
+#[test]
+fn tima_write_during_reload_is_ignored() {
+    let mut timer = about_to_overflow();
+    timer.tick(16);
+    assert!(timer.tick(4));
+    timer.write(TIMA, 0x12);
+    assert_eq!(timer.read(TIMA), 0xF0);
+
+    // Only for that one cycle
+    timer.tick(4);
+    timer.write(TIMA, 0x12);
+    assert_eq!(timer.read(TIMA), 0x12);
+}
+
+#[test]
+fn tma_write_during_reload_goes_to_tima() {
+    let mut timer = about_to_overflow();
+    timer.tick(16);
+    timer.tick(4);
+    timer.write(TMA, 0x34);
+    assert_eq!(timer.read(TIMA), 0x34);
+    assert_eq!(timer.read(TMA), 0x34);
+}
+
+#[test]
+fn tma_write_during_delay_is_what_gets_loaded() {
+    let mut timer = about_to_overflow();
+    timer.tick(16);
+    timer.write(TMA, 0x56);
+    timer.tick(4);
+    assert_eq!(timer.read(TIMA), 0x56);
+}
+
+#[test]
+fn div_write_with_the_bit_high_increments_tima() {
+    let mut timer = Timer::default();
+    timer.write(TAC, TAC_16);
+    // Bit 3 of the counter is set halfway through each period
+    timer.tick(8);
+    assert_eq!(timer.read(TIMA), 0);
+    timer.write(DIV, 0);
+    assert_eq!(timer.read(TIMA), 1);
+
+    // With the bit low a reset changes nothing
+    timer.tick(4);
+    timer.write(DIV, 0);
+    assert_eq!(timer.read(TIMA), 1);
+}
+
+#[test]
+fn disabling_with_the_bit_high_increments_tima() {
+    let mut timer = Timer::default();
+    timer.write(TAC, TAC_16);
+    timer.tick(8);
+    timer.write(TAC, 0b001);
+    assert_eq!(timer.read(TIMA), 1);
+}
+
+#[test]
+fn tac_reads_back_three_bits() {
+    let mut timer = Timer::default();
+    timer.write(TAC, 0xFF);
+    assert_eq!(timer.read(TAC), 0b111);
+}
+
+#[test]
+fn cycles_until_interrupt_matches_ticking() {
+    for (tac, tima, ticked) in [(TAC_16, 0xFE, 8), (0b100, 0x80, 100), (0b111, 0, 0)] {
+        let mut timer = Timer::default();
+        timer.write(TAC, tac);
+        timer.write(TIMA, tima);
+        timer.tick(ticked);
+        let cycles = timer.cycles_until_interrupt();
+        assert!(!timer.tick(cycles - 4), "TAC {:#b}", tac);
+        assert!(timer.tick(4), "TAC {:#b}", tac);
+    }
+    let mut timer = Timer::default();
+    timer.write(TAC, 0b001);
+    assert_eq!(timer.cycles_until_interrupt(), u32::MAX);
+}
+
+#[test]
+fn frame_sequencer_steps_when_div_bit_4_falls() {
+    let mut timer = Timer::default();
+    timer.tick(8192 * 3 - 4);
+    assert_eq!(timer.take_div_apu_steps(), 2);
+    assert_eq!(timer.take_div_apu_steps(), 0);
+
+    // Bit 4 is set from DIV 0x10, resetting the divider then drops it early
+    timer.tick(4 + 0x1000);
+    assert_eq!(timer.take_div_apu_steps(), 1);
+    timer.write(DIV, 0);
+    assert_eq!(timer.take_div_apu_steps(), 1);
+    timer.tick(0x0FFC);
+    timer.write(DIV, 0);
+    assert_eq!(timer.take_div_apu_steps(), 0);
+}
@@ -0,0 +1,119 @@
+use crate::emulator::{
+    instructions::Instruction,
+    trace::{self, Outcome, TraceFormat, TraceLine},
+    unit_tests::asm::{self, START},
+    Emulator,
+};
+
+fn counting_rom() -> Vec<u8> {
+    asm::rom(&[(
+        START,
+        &asm![
+            LD A, 0x10;
+            LD HL, 0xC000;
+            INC A;
+            LD [HL], A;
+            JR (-4)
+        ],
+    )])
+}
+
+/// What a Doctor log of the first `lines` instructions looks like
+fn own_log(rom: &[u8], lines: usize) -> Vec<String> {
+    let mut emulator = Emulator::new(rom);
+    let mut log = vec![TraceLine::of(&emulator).to_string()];
+    while log.len() < lines {
+        emulator.step().unwrap();
+        if emulator.cpu().last_instruction.is_some() {
+            log.push(TraceLine::of(&emulator).to_string());
+        }
+    }
+    log
+}
+
+fn diverge(rom: &[u8], log: &[String]) -> Outcome {
+    let mut emulator = Emulator::new(rom);
+    trace::diverge(&mut emulator, log.join("\n").as_bytes()).unwrap()
+}
+
+#[test]
+fn parses_doctor_lines() {
+    let line = TraceLine::parse(
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02",
+    )
+    .unwrap();
+    assert_eq!(
+        line.registers,
+        [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D].map(Some)
+    );
+    assert_eq!(line.sp, Some(0xFFFE));
+    assert_eq!(line.pc, Some(0x100));
+    assert_eq!(line.pcmem, Some([0x00, 0xC3, 0x13, 0x02]));
+    assert_eq!(line.format, TraceFormat::Doctor);
+    assert_eq!(
+        line.to_string(),
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02"
+    );
+}
+
+#[test]
+fn parses_bgb_lines() {
+    let line = TraceLine::parse("ROM0:0150 ;A:01 F:Z-hC BC:0013 DE:00d8 HL:014d SP:fffe").unwrap();
+    assert_eq!(
+        line.registers,
+        [0x01, 0x90, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D].map(Some)
+    );
+    assert_eq!(line.pc, Some(0x150));
+    assert_eq!(line.pcmem, None);
+    assert_eq!(line.format, TraceFormat::Bgb);
+
+    assert!(TraceLine::parse("").unwrap().is_empty());
+    assert!(TraceLine::parse("A:1FF").is_err());
+}
+
+#[test]
+fn own_log_matches() {
+    let rom = counting_rom();
+    let log = own_log(&rom, 50);
+    assert!(matches!(diverge(&rom, &log), Outcome::Matched(50)));
+}
+
+#[test]
+fn stops_at_the_first_difference() {
+    let rom = counting_rom();
+    let mut log = own_log(&rom, 50);
+    let mut line = TraceLine::parse(&log[30]).unwrap();
+    line.registers[0] = line.registers[0].map(|a| a ^ 0xFF);
+    log[30] = line.to_string();
+    let Outcome::Diverged(divergence) = diverge(&rom, &log) else {
+        panic!("didn't diverge");
+    };
+    assert_eq!(divergence.line, 31);
+    assert_eq!(divergence.expected.mismatches(&divergence.actual), ["A"]);
+    assert_eq!(divergence.history.len(), 16);
+    let report = divergence.to_string();
+    assert!(report.starts_with("Diverged at line 31: A\n"), "{}", report);
+}
+
+#[test]
+fn missing_fields_are_not_compared() {
+    let rom = counting_rom();
+    let log: Vec<String> = own_log(&rom, 10)
+        .iter()
+        .map(|line| {
+            let line = TraceLine::parse(line).unwrap();
+            format!("PC:{:04X}", line.pc.unwrap())
+        })
+        .collect();
+    assert!(matches!(diverge(&rom, &log), Outcome::Matched(10)));
+}
+
+#[test]
+fn doctor_logs_read_ly_as_0x90() {
+    let rom = asm::rom(&[(START, &asm![LDH A, [0x44]; HALT])]);
+    let log = [
+        "PC:0100".to_string(),
+        format!("A:{:02X} PC:0102", trace::DOCTOR_LY),
+    ];
+    assert!(matches!(diverge(&rom, &log), Outcome::Matched(2)));
+}
@@ -0,0 +1,84 @@
+use crate::emulator::{
+    ppu::{ColorFrameBuffer, FrameBuffer},
+    triple_buffer::TripleBuffer,
+};
+
+fn filled(shade: u8) -> Box<FrameBuffer> {
+    Box::new([shade; std::mem::size_of::<FrameBuffer>()])
+}
+
+#[test]
+fn shows_the_newest_frame() {
+    let buffer = TripleBuffer::default();
+    assert_eq!(buffer.latest()[0], 0);
+    buffer.publish(&filled(1), None);
+    buffer.publish(&filled(2), None);
+    buffer.publish(&filled(3), None);
+    assert_eq!(buffer.latest()[0], 3);
+}
+
+#[test]
+fn keeps_showing_the_last_frame() {
+    let buffer = TripleBuffer::default();
+    buffer.publish(&filled(1), None);
+    assert_eq!(buffer.latest()[0], 1);
+    assert_eq!(buffer.latest()[0], 1);
+    buffer.publish(&filled(2), None);
+    assert_eq!(buffer.latest()[0], 2);
+}
+
+#[test]
+fn colors_come_with_their_frame() {
+    let buffer = TripleBuffer::default();
+    let colors: Box<ColorFrameBuffer> = Box::new([0x001F; std::mem::size_of::<FrameBuffer>()]);
+    buffer.publish(&filled(1), Some(&colors));
+    assert_eq!(
+        buffer.latest().colors().map(|colors| colors[0]),
+        Some(0x001F)
+    );
+    buffer.publish(&filled(2), None);
+    assert!(buffer.latest().colors().is_none());
+}
+
+#[test]
+fn publishing_doesnt_wait_for_the_renderer() {
+    let buffer = TripleBuffer::default();
+    buffer.publish(&filled(1), None);
+    let shown = buffer.latest();
+    // These can't touch the frame being shown
+    buffer.publish(&filled(2), None);
+    buffer.publish(&filled(3), None);
+    assert_eq!(shown[0], 1);
+    drop(shown);
+    assert_eq!(buffer.latest()[0], 3);
+}
+
+#[test]
+#[should_panic(expected = "still being shown")]
+fn only_one_frame_is_shown_at_a_time() {
+    let buffer = TripleBuffer::default();
+    let _shown = buffer.latest();
+    buffer.publish(&filled(1), None);
+    let _ = buffer.latest();
+}
+
+#[test]
+fn frames_arrive_whole_across_threads() {
+    let buffer = std::sync::Arc::new(TripleBuffer::default());
+    let writer = {
+        let buffer = buffer.clone();
+        std::thread::spawn(move || {
+            for shade in 1..=200 {
+                buffer.publish(&filled(shade), None);
+            }
+        })
+    };
+    let mut last = 0;
+    while last != 200 {
+        let frame = buffer.latest();
+        assert!(frame.iter().all(|&shade| shade == frame[0]));
+        assert!(frame[0] >= last);
+        last = frame[0];
+    }
+    writer.join().unwrap();
+}
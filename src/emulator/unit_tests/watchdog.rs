@@ -0,0 +1,137 @@
+use crate::emulator::{
+    error::EmulatorError,
+    ppu::FRAME_CYCLES,
+    watchdog::{CpuState, Hang, Watchdog},
+    Emulator,
+};
+
+/// Long enough that the LCD being on means a frame always finishes first
+const LIMIT: u64 = 2 * FRAME_CYCLES as u64;
+
+fn with_code(code: &[u8]) -> Emulator {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    // RETI for every interrupt
+    for vector in [0x40, 0x48, 0x50, 0x58, 0x60] {
+        rom[vector] = 0xD9;
+    }
+    Emulator::new(&rom)
+}
+
+/// Steps until the watchdog fires, within `max_steps`
+fn hang(emulator: &mut Emulator, max_steps: usize) -> EmulatorError {
+    for _ in 0..max_steps {
+        if let Err(error) = emulator.step() {
+            return error;
+        }
+    }
+    panic!("No error after {} steps", max_steps);
+}
+
+#[test]
+fn halt_with_nothing_enabled() {
+    // IE is 0 after boot
+    let mut emulator = with_code(&[0x76]);
+    emulator.set_watchdog(Some(LIMIT));
+    match hang(&mut emulator, LIMIT as usize) {
+        EmulatorError::Hung {
+            hang, pc, ie, ime, ..
+        } => {
+            assert_eq!(hang, Hang::Halted);
+            assert_eq!(pc, 0x101);
+            assert_eq!(ie & 0x1F, 0);
+            assert!(!ime);
+        }
+        error => panic!("Unexpected error {}", error),
+    }
+}
+
+#[test]
+fn jump_to_itself_with_interrupts_off() {
+    // DI, JR -2
+    let mut emulator = with_code(&[0xF3, 0x18, 0xFE]);
+    emulator.set_watchdog(Some(LIMIT));
+    let error = hang(&mut emulator, LIMIT as usize);
+    assert!(matches!(
+        error,
+        EmulatorError::Hung {
+            hang: Hang::Loop,
+            pc: 0x101,
+            ..
+        }
+    ));
+    assert!(error
+        .to_string()
+        .starts_with("Emulation is stuck, looping in place with interrupts off for "));
+}
+
+#[test]
+fn lcd_left_off() {
+    // XOR A, LDH [LCDC], A, LD A, 1, LDH [IE], A, EI, then NOP, JR -3
+    let code = [
+        0xAF, 0xE0, 0x40, 0x3E, 0x01, 0xE0, 0xFF, 0xFB, 0x00, 0x18, 0xFD,
+    ];
+    let mut emulator = with_code(&code);
+    emulator.set_watchdog(Some(LIMIT));
+    let error = hang(&mut emulator, LIMIT as usize);
+    assert!(matches!(
+        error,
+        EmulatorError::Hung {
+            hang: Hang::NoFrames,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn waiting_for_interrupts_is_fine() {
+    // LD A, 1, LDH [IE], A, EI, then JR -2 until VBlank
+    let mut emulator = with_code(&[0x3E, 0x01, 0xE0, 0xFF, 0xFB, 0x18, 0xFE]);
+    emulator.set_watchdog(Some(LIMIT));
+    for _ in 0..10 {
+        emulator.run_frame().unwrap();
+    }
+}
+
+#[test]
+fn disabled() {
+    let mut emulator = with_code(&[0x76]);
+    emulator.set_watchdog(None);
+    for _ in 0..3 {
+        emulator.run_frame().unwrap();
+    }
+}
+
+#[test]
+fn crash_shows_recent_instructions() {
+    let mut emulator = with_code(&[0x00, 0x00, 0xF3, 0x18, 0xFE]);
+    emulator.set_watchdog(Some(LIMIT));
+    let error = hang(&mut emulator, LIMIT as usize);
+    let crash = emulator.crash(&error);
+    assert!(crash.message.starts_with("Emulation is stuck"));
+    let recent = crash
+        .cpu_dump
+        .split_once("Recent instructions, oldest first:\n")
+        .unwrap()
+        .1;
+    assert_eq!(recent.lines().count(), 16);
+    assert!(recent.lines().all(|line| line == "0x0103"));
+}
+
+#[test]
+fn restarts_counting_after_firing() {
+    let mut watchdog = Watchdog::new(10);
+    assert_eq!(watchdog.tick(8, true, CpuState::Halted, false, false), None);
+    assert_eq!(
+        watchdog.tick(4, true, CpuState::Halted, false, false),
+        Some((Hang::Halted, 12))
+    );
+    assert_eq!(watchdog.tick(4, true, CpuState::Halted, false, false), None);
+    // Something enabled in IE gets it out eventually
+    assert_eq!(watchdog.tick(8, false, CpuState::Halted, true, false), None);
+    assert_eq!(
+        watchdog.tick(4, false, CpuState::Running, true, true),
+        Some((Hang::NoFrames, 12))
+    );
+    assert_eq!(watchdog.tick(12, true, CpuState::Running, true, true), None);
+}
@@ -0,0 +1,106 @@
+//! Noticing when emulation will never get anywhere
+//!
+//! A game that's hung on real hardware just sits there, which looks exactly like an emulator
+//! bug. The watchdog turns the cases that can't recover into an
+//! [`EmulatorError::Hung`](crate::emulator::error::EmulatorError::Hung) instead: the PPU not
+//! finishing a frame for a long time (usually the LCD left off), a HALT with no interrupt
+//! enabled to wake it, or a jump to itself that no interrupt can get out of.
+use std::fmt;
+
+use crate::emulator::ppu::FRAME_CYCLES;
+
+/// Five seconds of emulated time
+pub const DEFAULT_LIMIT: u64 = 300 * FRAME_CYCLES as u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hang {
+    NoFrames,
+    /// Halted with nothing in IE
+    Halted,
+    /// An instruction that jumps to itself with interrupts off
+    Loop,
+}
+
+impl fmt::Display for Hang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hang::NoFrames => write!(f, "no frame was finished"),
+            Hang::Halted => write!(f, "halted with no interrupts enabled"),
+            Hang::Loop => write!(f, "looping in place with interrupts off"),
+        }
+    }
+}
+
+/// What the CPU was doing for [`Watchdog::tick`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpuState {
+    Running,
+    Halted,
+    /// The instruction left PC where it started
+    Looping,
+}
+
+#[derive(Clone, Debug)]
+pub struct Watchdog {
+    /// T-cycles before giving up
+    limit: u64,
+    since_frame: u64,
+    /// How long the CPU has been stuck, and how
+    stuck: Option<(Hang, u64)>,
+}
+
+impl Watchdog {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            since_frame: 0,
+            stuck: None,
+        }
+    }
+
+    /// Forgets everything seen so far, the limit stays
+    pub fn reset(&mut self) {
+        *self = Self::new(self.limit);
+    }
+
+    /// Called after every step. `wakeable` is whether any interrupt could get the CPU out of
+    /// a HALT (IE has something set), `interruptible` whether one could be serviced too (IME as
+    /// well). Returns why emulation is hung once it's been that way for the limit, and starts
+    /// counting again in case it's continued anyway.
+    pub fn tick(
+        &mut self,
+        cycles: u32,
+        frame_done: bool,
+        cpu: CpuState,
+        wakeable: bool,
+        interruptible: bool,
+    ) -> Option<(Hang, u64)> {
+        let cycles = cycles as u64;
+        self.since_frame = if frame_done {
+            0
+        } else {
+            self.since_frame + cycles
+        };
+        let hang = match cpu {
+            CpuState::Halted if !wakeable => Some(Hang::Halted),
+            CpuState::Looping if !interruptible => Some(Hang::Loop),
+            _ => None,
+        };
+        self.stuck = match (hang, self.stuck) {
+            (Some(hang), Some((stuck, time))) if hang == stuck => Some((hang, time + cycles)),
+            (Some(hang), _) => Some((hang, cycles)),
+            (None, _) => None,
+        };
+
+        let found = match self.stuck {
+            Some((hang, time)) if time >= self.limit => Some((hang, time)),
+            _ if self.since_frame >= self.limit => Some((Hang::NoFrames, self.since_frame)),
+            _ => None,
+        };
+        if found.is_some() {
+            self.since_frame = 0;
+            self.stuck = None;
+        }
+        found
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+};
+
+use winit::{
+    event::{ElementState, VirtualKeyCode, WindowEvent},
+    window::Window,
+};
+
+use crate::{
+    emulator::{
+        apu::view::ApuView, cheats::Cheat, debugger::DebugView, error::Crash, stats::Stats, Command,
+    },
+    logging::LogFilter,
+    renderer::{ColorAdjust, Palette},
+};
+
+mod audio;
+use audio::AudioPanel;
+mod browser;
+use browser::RomBrowser;
+mod cheats;
+use cheats::CheatsPanel;
+mod clipboard;
+mod console;
+use console::ConsolePanel;
+mod debugger;
+use debugger::DebuggerPanel;
+mod display;
+use display::DisplayPanel;
+mod input;
+use input::GuiInput;
+mod logging;
+use logging::LoggingPanel;
+mod osd;
+use osd::Osd;
+mod window_size;
+use window_size::WindowSize;
+
+/// The GUI's side of an emulator thread, from its
+/// [`EmulatorHandle`](crate::emulator::EmulatorHandle)
+pub struct Connection {
+    pub commands: Sender<Command>,
+    /// Saved for the game
+    pub cheats: Vec<Cheat>,
+    pub crashes: Receiver<Crash>,
+    pub debug_views: Receiver<DebugView>,
+    pub audio_views: Receiver<ApuView>,
+    pub serial_output: Receiver<Vec<u8>>,
+}
+
+/// egui overlay, toggled with Escape
+pub struct Gui {
+    ctx: egui::Context,
+    input: GuiInput,
+    visible: bool,
+    commands: Sender<Command>,
+    cheats: CheatsPanel,
+    debugger: DebuggerPanel,
+    audio: AudioPanel,
+    console: ConsolePanel,
+    logging: LoggingPanel,
+    crashes: Receiver<Crash>,
+    /// Shown whether the overlay is visible or not, there's nothing else to look at
+    crash: Option<Crash>,
+    /// Most recent first
+    recent: Vec<PathBuf>,
+    /// Picked from the menu, for the window to load
+    open_rom: Option<PathBuf>,
+    /// Only with a ROM directory
+    browser: Option<RomBrowser>,
+    osd: Osd,
+    window_size: WindowSize,
+    display: DisplayPanel,
+    /// In the corner over the game, whether the overlay is open or not
+    show_stats: bool,
+    stats: Stats,
+}
+
+impl Gui {
+    pub fn new(
+        connection: Connection,
+        log_filter: LogFilter,
+        recent: Vec<PathBuf>,
+        roms_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            input: GuiInput::default(),
+            visible: false,
+            commands: connection.commands,
+            cheats: CheatsPanel::new(connection.cheats),
+            debugger: DebuggerPanel::new(connection.debug_views),
+            audio: AudioPanel::new(connection.audio_views),
+            console: ConsolePanel::new(connection.serial_output),
+            logging: LoggingPanel::new(log_filter),
+            crashes: connection.crashes,
+            crash: None,
+            recent,
+            open_rom: None,
+            browser: roms_dir.map(|dir| RomBrowser::new(dir, false)),
+            osd: Osd::default(),
+            window_size: WindowSize::default(),
+            display: DisplayPanel::default(),
+            show_stats: false,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Switches to another emulator thread, for another game. Window and view settings stay.
+    pub fn attach(&mut self, connection: Connection, recent: Vec<PathBuf>) {
+        self.commands = connection.commands;
+        self.cheats = CheatsPanel::new(connection.cheats);
+        self.debugger = DebuggerPanel::new(connection.debug_views);
+        self.audio = AudioPanel::new(connection.audio_views);
+        self.console = ConsolePanel::new(connection.serial_output);
+        self.crashes = connection.crashes;
+        self.crash = None;
+        self.recent = recent;
+        // The new thread starts without ghosting, and with flashing only as the command line had it
+        if self.display.persistence > 0.0 {
+            let _ = self
+                .commands
+                .send(Command::SetFrameBlend(self.display.persistence));
+        }
+        let _ = self
+            .commands
+            .send(Command::SetReduceFlashing(self.display.reduce_flashing));
+        // Back to the game
+        self.visible = false;
+    }
+
+    /// Shows `message` over the game for a moment
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.osd.push(message.into());
+    }
+
+    /// Shows the ROM browser, if there's a directory to browse
+    pub fn browse(&mut self) {
+        if let Some(browser) = self.browser.as_mut() {
+            browser.open = true;
+            self.visible = true;
+        }
+    }
+
+    /// Ticks the Display panel's checkbox, for when the emulator started with the filter on
+    pub fn set_reduce_flashing(&mut self, enabled: bool) {
+        self.display.reduce_flashing = enabled;
+    }
+
+    pub fn color_adjust(&self) -> ColorAdjust {
+        self.display.adjust
+    }
+
+    pub fn palette(&self) -> Palette {
+        match self.display.palette {
+            Some(i) => Palette::ALL[i].1,
+            None => Palette::default(),
+        }
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+    }
+
+    pub fn take_open_rom(&mut self) -> Option<PathBuf> {
+        self.open_rom.take()
+    }
+
+    pub fn context(&self) -> &egui::Context {
+        &self.ctx
+    }
+
+    /// Returns true if the GUI used the event and the game shouldn't see it
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        if self.window_size.handle_event(window, event) {
+            return true;
+        }
+        if let WindowEvent::KeyboardInput { input, .. } = event {
+            if input.virtual_keycode == Some(VirtualKeyCode::Escape)
+                && input.state == ElementState::Pressed
+                && !self.ctx.wants_keyboard_input()
+            {
+                self.visible = !self.visible;
+                return true;
+            }
+        }
+
+        if !self.visible && self.crash.is_none() {
+            return false;
+        }
+
+        self.input.on_event(window, event);
+        match event {
+            WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => {
+                self.ctx.wants_keyboard_input()
+            }
+            WindowEvent::CursorMoved { .. } | WindowEvent::MouseInput { .. } => {
+                self.ctx.wants_pointer_input()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn run(&mut self, window: &Window) -> egui::FullOutput {
+        match self.crashes.try_recv() {
+            Ok(crash) => self.crash = Some(crash),
+            // Panics are caught and sent, so this means something outside of emulation died
+            Err(TryRecvError::Disconnected) if self.crash.is_none() => {
+                self.crash = Some(Crash {
+                    message: "The emulator thread stopped unexpectedly".into(),
+                    cpu_dump: String::new(),
+                })
+            }
+            Err(_) => {}
+        }
+        self.audio.update(&self.commands, self.visible);
+        self.console.update();
+        let raw_input = self.input.take(window);
+        let ctx = self.ctx.clone();
+        let mut output = ctx.run(raw_input, |ctx| {
+            self.osd.show(ctx);
+            if self.show_stats {
+                show_stats(ctx, &self.stats);
+            }
+            if let Some(crash) = &self.crash {
+                show_crash(ctx, crash);
+            }
+            if !self.visible {
+                return;
+            }
+
+            egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+                egui::menu::bar(ui, |ui| {
+                    ui.menu_button("File", |ui| {
+                        if ui.button("Reset").clicked() {
+                            let _ = self.commands.send(Command::Reset);
+                            self.visible = false;
+                            ui.close_menu();
+                        }
+                        if let Some(browser) = self.browser.as_mut() {
+                            if ui.button("Games...").clicked() {
+                                browser.open = true;
+                                ui.close_menu();
+                            }
+                        }
+                        ui.add_enabled_ui(!self.recent.is_empty(), |ui| {
+                            ui.menu_button("Open Recent", |ui| {
+                                for path in &self.recent {
+                                    let name = path.file_name().unwrap_or(path.as_os_str());
+                                    let button = ui.button(name.to_string_lossy().into_owned());
+                                    if button.on_hover_text(path.display().to_string()).clicked() {
+                                        self.open_rom = Some(path.clone());
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                    });
+                    ui.menu_button("View", |ui| {
+                        self.window_size.menu(ui, window);
+                        ui.separator();
+                        if ui.button("Display...").clicked() {
+                            self.display.open = true;
+                            ui.close_menu();
+                        }
+                        ui.checkbox(&mut self.show_stats, "Stats");
+                    });
+                    ui.menu_button("Tools", |ui| {
+                        if ui.button("Cheats").clicked() {
+                            self.cheats.open = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Debugger").clicked() {
+                            self.debugger.open = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Audio").clicked() {
+                            self.audio.open = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Serial Console").clicked() {
+                            self.console.open = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("Logging").clicked() {
+                            self.logging.open = true;
+                            ui.close_menu();
+                        }
+                    });
+                });
+            });
+
+            if let Some(browser) = self.browser.as_mut() {
+                if let Some(path) = browser.show(ctx) {
+                    self.open_rom = Some(path);
+                }
+            }
+            self.cheats.show(ctx, &self.commands, &mut self.osd);
+            self.debugger.show(ctx, &self.commands);
+            self.audio.show(ctx);
+            if let Some(text) = self.console.show(ctx) {
+                ctx.output().copied_text = text;
+            }
+            self.logging.show(ctx, &mut self.osd);
+            self.display.show(ctx, &self.commands);
+        });
+        // The renderer doesn't know about the clipboard, so this is the one place copies go
+        let copied = std::mem::take(&mut output.platform_output.copied_text);
+        if !copied.is_empty() {
+            match clipboard::copy(&copied) {
+                Ok(()) => self.osd.push("Copied to the clipboard".into()),
+                Err(e) => self.osd.push(e),
+            }
+        }
+        output
+    }
+}
+
+fn show_stats(ctx: &egui::Context, stats: &Stats) {
+    let mut lines = vec![
+        format!("{:6.1} FPS", stats.fps),
+        format!("{:6.0}% speed", stats.speed),
+        format!("{:6.0} cycles/frame", stats.cycles_per_frame),
+    ];
+    if let Some(buffer) = stats.audio_buffer {
+        lines.push(format!("{:6.0}% audio buffer", buffer * 100.0));
+    }
+    // Like the OSD's messages, readable over any game
+    egui::Area::new("stats")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha(180))
+                .inner_margin(4.0)
+                .show(ui, |ui| {
+                    let text = egui::RichText::new(lines.join("\n")).monospace();
+                    ui.label(text.color(egui::Color32::WHITE));
+                });
+        });
+}
+
+fn show_crash(ctx: &egui::Context, crash: &Crash) {
+    egui::Window::new("Emulation stopped")
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(&crash.message);
+            if crash.cpu_dump.is_empty() {
+                return;
+            }
+            ui.separator();
+            // egui doesn't lay out tabs
+            ui.monospace(crash.cpu_dump.replace('\t', "    "));
+        });
+}
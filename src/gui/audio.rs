@@ -0,0 +1,130 @@
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::emulator::{
+    apu::view::{ApuView, ChannelView},
+    Command,
+};
+
+const CHANNEL_NAMES: [&str; 4] = ["1 Pulse", "2 Pulse", "3 Wave", "4 Noise"];
+/// 0xFF10 to 0xFF26, with the two unused ones by address
+const REGISTER_NAMES: [&str; 0x17] = [
+    "NR10", "NR11", "NR12", "NR13", "NR14", "FF15", "NR21", "NR22", "NR23", "NR24", "NR30", "NR31",
+    "NR32", "NR33", "NR34", "FF1F", "NR41", "NR42", "NR43", "NR44", "NR50", "NR51", "NR52",
+];
+const SCOPE_SIZE: egui::Vec2 = egui::vec2(384.0, 48.0);
+
+pub struct AudioPanel {
+    pub open: bool,
+    /// Latest from the emulator thread, nothing until it's been open for a frame
+    view: Option<ApuView>,
+    views: Receiver<ApuView>,
+    /// What the emulator thread was last told with [`Command::SetAudioView`]
+    watching: bool,
+}
+
+impl AudioPanel {
+    pub fn new(views: Receiver<ApuView>) -> Self {
+        Self {
+            open: false,
+            view: None,
+            views,
+            watching: false,
+        }
+    }
+
+    /// Has the emulator thread send views only while they can be seen, and takes the latest.
+    /// Called every frame, whether the overlay is up or not, so they don't pile up.
+    pub fn update(&mut self, commands: &Sender<Command>, visible: bool) {
+        let watch = self.open && visible;
+        if watch != self.watching {
+            self.watching = watch;
+            let _ = commands.send(Command::SetAudioView(watch));
+        }
+        if let Some(view) = self.views.try_iter().last() {
+            self.view = Some(view);
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let Self { open, view, .. } = self;
+        egui::Window::new("Audio").open(open).show(ctx, |ui| {
+            let Some(view) = view else {
+                ui.label("Waiting for the emulator...");
+                return;
+            };
+            if !view.powered {
+                ui.label("Powered off (NR52 bit 7 clear)");
+            }
+            for (name, channel) in CHANNEL_NAMES.iter().zip(&view.channels) {
+                ui.monospace(describe(name, channel));
+                scope(ui, &channel.samples);
+            }
+            ui.separator();
+            registers(ui, view);
+        });
+    }
+}
+
+/// `1 Pulse  on   523.3 Hz vol 12 down/3 len 40`
+fn describe(name: &str, channel: &ChannelView) -> String {
+    let mut line = format!(
+        "{} {:3} {:8.1} Hz vol {:2}",
+        name,
+        if channel.on { "on" } else { "off" },
+        channel.hz,
+        channel.volume
+    );
+    if let Some(envelope) = channel.envelope {
+        match envelope.period() {
+            0 => line.push_str(" steady "),
+            period => {
+                let direction = if envelope.up() { "up" } else { "down" };
+                line.push_str(&format!(" {:>4}/{}", direction, period));
+            }
+        }
+    }
+    if channel.length.enabled() {
+        line.push_str(&format!(" len {}", channel.length.remaining()));
+    }
+    line
+}
+
+/// `samples` from 0 at the bottom to 15 at the top, oldest on the left
+fn scope(ui: &mut egui::Ui, samples: &[u8]) {
+    let (response, painter) = ui.allocate_painter(SCOPE_SIZE, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+    if samples.len() < 2 {
+        return;
+    }
+    let step = rect.width() / (samples.len() - 1) as f32;
+    let points = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &sample)| {
+            let y = rect.bottom() - 2.0 - (rect.height() - 4.0) * sample as f32 / 15.0;
+            egui::pos2(rect.left() + i as f32 * step, y)
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+    ));
+}
+
+fn registers(ui: &mut egui::Ui, view: &ApuView) {
+    egui::Grid::new("apu_registers").show(ui, |ui| {
+        for (i, (name, value)) in REGISTER_NAMES.iter().zip(view.registers).enumerate() {
+            ui.monospace(format!("{} {:02X}", name, value));
+            if i % 5 == 4 {
+                ui.end_row();
+            }
+        }
+    });
+    let wave: Vec<String> = view
+        .wave_ram
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    ui.monospace(format!("Wave {}", wave.join("")));
+}
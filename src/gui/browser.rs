@@ -0,0 +1,144 @@
+//! Picking a game from the ROM directory
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::emulator::{archive, rom};
+
+pub struct RomEntry {
+    pub path: PathBuf,
+    /// From the header, the file name if it doesn't have one
+    pub title: String,
+}
+
+/// The ROMs in `dir`, zipped or not, sorted by title
+pub fn scan(dir: &Path) -> io::Result<Vec<RomEntry>> {
+    let mut entries = Vec::new();
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if !archive::is_rom_file(&path) {
+            continue;
+        }
+        let header = if archive::is_archive(&path) {
+            // Opening it says what's wrong if it doesn't have a ROM in it
+            archive::extract(&path, std::fs::read(&path)?).unwrap_or_default()
+        } else {
+            // Only the header is needed, no point reading whole ROMs
+            let mut header = Vec::with_capacity(0x150);
+            File::open(&path)?.take(0x150).read_to_end(&mut header)?;
+            header
+        };
+        let mut title = rom::header_title(&header).trim().to_string();
+        if title.is_empty() {
+            title = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+        }
+        entries.push(RomEntry { path, title });
+    }
+    entries.sort_by(|a, b| a.title.cmp(&b.title).then_with(|| a.path.cmp(&b.path)));
+    Ok(entries)
+}
+
+pub struct RomBrowser {
+    pub open: bool,
+    dir: PathBuf,
+    entries: Vec<RomEntry>,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl RomBrowser {
+    pub fn new(dir: PathBuf, open: bool) -> Self {
+        let mut browser = Self {
+            open,
+            dir,
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+        };
+        browser.rescan();
+        browser
+    }
+
+    fn rescan(&mut self) {
+        match scan(&self.dir) {
+            Ok(entries) => {
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("Can't read {:?}: {}", self.dir, e)),
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Up and down move through the list, Enter starts the game. Returns the picked ROM.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<PathBuf> {
+        if !self.open {
+            return None;
+        }
+        let mut picked = None;
+        let mut open = self.open;
+        egui::Window::new("Games")
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(self.dir.display().to_string());
+                    if ui.small_button("Refresh").clicked() {
+                        self.rescan();
+                    }
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::RED, error.as_str());
+                }
+                if self.entries.is_empty() {
+                    ui.label("No ROMs here");
+                    return;
+                }
+
+                let input = ui.input();
+                let (up, down, enter) = (
+                    input.key_pressed(egui::Key::ArrowUp),
+                    input.key_pressed(egui::Key::ArrowDown),
+                    input.key_pressed(egui::Key::Enter),
+                );
+                drop(input);
+                if up {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                if down {
+                    self.selected = (self.selected + 1).min(self.entries.len() - 1);
+                }
+                if enter {
+                    picked = Some(self.entries[self.selected].path.clone());
+                }
+
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, entry) in self.entries.iter().enumerate() {
+                            let selected = i == self.selected;
+                            let response = ui.selectable_label(selected, &entry.title);
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            let response = response.on_hover_text(entry.path.display().to_string());
+                            if response.double_clicked() {
+                                picked = Some(entry.path.clone());
+                            } else if response.clicked() {
+                                self.selected = i;
+                            }
+                        }
+                    });
+            });
+        self.open = open && picked.is_none();
+        picked
+    }
+}
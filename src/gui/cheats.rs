@@ -0,0 +1,77 @@
+use std::sync::mpsc::Sender;
+
+use super::osd::Osd;
+use crate::emulator::{cheats::Cheat, Command};
+
+#[derive(Default)]
+pub struct CheatsPanel {
+    pub open: bool,
+    cheats: Vec<Cheat>,
+    new_code: String,
+    error: Option<String>,
+}
+
+impl CheatsPanel {
+    pub fn new(cheats: Vec<Cheat>) -> Self {
+        Self {
+            cheats,
+            ..Default::default()
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, commands: &Sender<Command>, osd: &mut Osd) {
+        let Self {
+            open,
+            cheats,
+            new_code,
+            error,
+        } = self;
+
+        let mut changed = false;
+        egui::Window::new("Cheats").open(open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let response = ui.text_edit_singleline(new_code);
+                let submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                if ui.button("Add").clicked() || submitted {
+                    match Cheat::parse(new_code) {
+                        Ok(cheat) => {
+                            cheats.push(cheat);
+                            new_code.clear();
+                            *error = None;
+                            changed = true;
+                        }
+                        Err(e) => *error = Some(format!("{}: {}", new_code, e)),
+                    }
+                }
+            });
+            if let Some(error) = error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            ui.separator();
+            if cheats.is_empty() {
+                ui.label("No cheats for this game");
+            }
+
+            let mut remove = None;
+            for (i, cheat) in cheats.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    changed |= ui.checkbox(&mut cheat.enabled, &cheat.code).changed();
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                cheats.remove(i);
+                changed = true;
+            }
+        });
+
+        if changed {
+            let _ = commands.send(Command::SetCheats(self.cheats.clone()));
+            let enabled = self.cheats.iter().filter(|cheat| cheat.enabled).count();
+            osd.push(format!("Cheats: {} of {} on", enabled, self.cheats.len()));
+        }
+    }
+}
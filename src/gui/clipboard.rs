@@ -0,0 +1,45 @@
+//! Putting text on the system clipboard, through whatever command line tool the platform has
+//! for it since there's no clipboard crate in the build
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Tried in order until one works
+fn tools() -> Vec<&'static [&'static str]> {
+    if cfg!(windows) {
+        vec![&["clip"]]
+    } else if cfg!(target_os = "macos") {
+        vec![&["pbcopy"]]
+    } else {
+        vec![
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+fn copy_with(tool: &[&str], text: &str) -> std::io::Result<bool> {
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Dropping stdin closes it, which is when the tool takes what it got
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    Ok(child.wait()?.success())
+}
+
+pub fn copy(text: &str) -> Result<(), String> {
+    let tools = tools();
+    if tools
+        .iter()
+        .any(|tool| copy_with(tool, text).unwrap_or(false))
+    {
+        return Ok(());
+    }
+    let names: Vec<&str> = tools.iter().map(|tool| tool[0]).collect();
+    Err(format!("Couldn't copy, tried {}", names.join(", ")))
+}
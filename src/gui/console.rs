@@ -0,0 +1,72 @@
+//! What the game sends over the link cable, as text. Test ROMs and homebrew print there.
+use std::sync::mpsc::Receiver;
+
+/// Older text goes first once there's more
+const MAX_TEXT: usize = 64 * 1024;
+
+pub struct ConsolePanel {
+    pub open: bool,
+    text: String,
+    output: Receiver<Vec<u8>>,
+}
+
+impl ConsolePanel {
+    pub fn new(output: Receiver<Vec<u8>>) -> Self {
+        Self {
+            open: false,
+            text: String::new(),
+            output,
+        }
+    }
+
+    /// Takes what's been sent since the last frame. Called every frame whether the panel's open
+    /// or not, so nothing's missing when it is.
+    pub fn update(&mut self) {
+        for bytes in self.output.try_iter() {
+            self.text.extend(bytes.into_iter().map(printable));
+        }
+        if self.text.len() > MAX_TEXT {
+            // Every char is ASCII, any index is a boundary
+            let cut = self.text.len() - MAX_TEXT;
+            self.text.drain(..cut);
+        }
+    }
+
+    /// Returns the text to copy when the button was clicked
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<String> {
+        let Self { open, text, .. } = self;
+        let mut copied = None;
+        egui::Window::new("Serial Console")
+            .open(open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Copy").clicked() {
+                        copied = Some(text.clone());
+                    }
+                    if ui.button("Clear").clicked() {
+                        text.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .stick_to_bottom()
+                    .show(ui, |ui| {
+                        if text.is_empty() {
+                            ui.label("Nothing sent yet");
+                        } else {
+                            ui.monospace(text.as_str());
+                        }
+                    });
+            });
+        copied
+    }
+}
+
+/// Control characters besides line breaks and tabs, and anything that isn't ASCII, as dots
+fn printable(byte: u8) -> char {
+    match byte {
+        b'\n' | b'\t' | b' '..=b'~' => byte as char,
+        _ => '.',
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    ops::RangeInclusive,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use crate::emulator::{
+    debugger::{
+        expression::Expression, watchpoints::Watchpoint, AccessView, BreakOn, Breakpoint,
+        DebugCommand, DebugView,
+    },
+    memory_bus::Interrupt,
+    Command,
+};
+
+pub struct DebuggerPanel {
+    pub open: bool,
+    /// Last thing the emulator thread sent, nothing until the first command
+    view: Option<DebugView>,
+    views: Receiver<DebugView>,
+    /// Hex address typed for run to
+    run_to: String,
+    /// With the condition as it was typed
+    breakpoints: Vec<(Breakpoint, String)>,
+    new_addr: String,
+    new_condition: String,
+    watchpoints: Vec<Watchpoint>,
+    /// Hex address or range typed for a new watchpoint
+    watch_range: String,
+    watch_read: bool,
+    watch_write: bool,
+    /// Hex address and bytes typed for patching memory
+    patch_addr: String,
+    patch_bytes: String,
+    break_on: BreakOn,
+    error: Option<String>,
+}
+
+impl DebuggerPanel {
+    pub fn new(views: Receiver<DebugView>) -> Self {
+        Self {
+            open: false,
+            view: None,
+            views,
+            run_to: String::new(),
+            breakpoints: Vec::new(),
+            new_addr: String::new(),
+            new_condition: String::new(),
+            watchpoints: Vec::new(),
+            watch_range: String::new(),
+            watch_read: false,
+            watch_write: true,
+            patch_addr: String::new(),
+            patch_bytes: String::new(),
+            break_on: BreakOn::default(),
+            error: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, commands: &Sender<Command>) {
+        if let Some(view) = self.views.try_iter().last() {
+            self.view = Some(view);
+        }
+        let Self {
+            open,
+            view,
+            run_to,
+            breakpoints,
+            new_addr,
+            new_condition,
+            watchpoints,
+            watch_range,
+            watch_read,
+            watch_write,
+            patch_addr,
+            patch_bytes,
+            break_on,
+            error,
+            ..
+        } = self;
+
+        let mut command = None;
+        egui::Window::new("Debugger").open(open).show(ctx, |ui| {
+            let paused = view.as_ref().is_some_and(|view| view.paused);
+            ui.horizontal(|ui| {
+                if paused {
+                    if ui.button("Continue").clicked() {
+                        command = Some(DebugCommand::Continue);
+                    }
+                } else if ui.button("Pause").clicked() {
+                    command = Some(DebugCommand::Pause);
+                }
+                if ui.button("Step").clicked() {
+                    command = Some(DebugCommand::Step);
+                }
+                if ui.button("Step over").clicked() {
+                    command = Some(DebugCommand::StepOver);
+                }
+                if ui.button("Step out").clicked() {
+                    command = Some(DebugCommand::StepOut);
+                }
+            });
+            ui.horizontal(|ui| {
+                let response = ui.text_edit_singleline(run_to);
+                let submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                if ui.button("Run to").clicked() || submitted {
+                    match parse_addr(run_to) {
+                        Ok(addr) => {
+                            command = Some(DebugCommand::RunTo(addr));
+                            *error = None;
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Patch");
+                ui.add(egui::TextEdit::singleline(patch_addr).desired_width(50.0));
+                ui.add(egui::TextEdit::singleline(patch_bytes).hint_text("00 C9"));
+                if ui.button("Write").clicked() {
+                    match parse_addr(patch_addr)
+                        .and_then(|addr| Ok((addr, parse_bytes(patch_bytes)?)))
+                    {
+                        Ok((addr, bytes)) => {
+                            command = Some(DebugCommand::Patch { addr, bytes });
+                            *error = None;
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+                if ui.button("NOP out").clicked() {
+                    match parse_addr(patch_addr) {
+                        Ok(addr) => {
+                            command = Some(DebugCommand::NopOut(addr));
+                            *error = None;
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.label("Breakpoints");
+            ui.horizontal(|ui| {
+                ui.label("Address");
+                ui.add(egui::TextEdit::singleline(new_addr).desired_width(50.0));
+                ui.label("If");
+                ui.text_edit_singleline(new_condition);
+                if ui.button("Add").clicked() {
+                    match parse_breakpoint(new_addr, new_condition) {
+                        Ok(breakpoint) => {
+                            breakpoints.push((breakpoint, new_condition.trim().to_string()));
+                            new_addr.clear();
+                            new_condition.clear();
+                            *error = None;
+                            command = Some(set_breakpoints(breakpoints));
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+            });
+            let mut remove = None;
+            for (i, (breakpoint, condition)) in breakpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if condition.is_empty() {
+                        ui.monospace(format!("{:#06X}", breakpoint.addr));
+                    } else {
+                        ui.monospace(format!("{:#06X} if {}", breakpoint.addr, condition));
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                breakpoints.remove(i);
+                command = Some(set_breakpoints(breakpoints));
+            }
+
+            ui.label("Watchpoints");
+            ui.horizontal(|ui| {
+                ui.label("Address");
+                ui.add(egui::TextEdit::singleline(watch_range).hint_text("C000-C0FF"));
+                ui.checkbox(watch_read, "Read");
+                ui.checkbox(watch_write, "Write");
+                if ui.button("Add").clicked() {
+                    match parse_range(watch_range) {
+                        Ok(_) if !*watch_read && !*watch_write => {
+                            *error = Some("Watch reads, writes or both".into());
+                        }
+                        Ok(range) => {
+                            watchpoints.push(Watchpoint {
+                                range,
+                                read: *watch_read,
+                                write: *watch_write,
+                            });
+                            watch_range.clear();
+                            *error = None;
+                            command = Some(DebugCommand::SetWatchpoints(watchpoints.clone()));
+                        }
+                        Err(e) => *error = Some(e),
+                    }
+                }
+            });
+            let mut remove = None;
+            for (i, watchpoint) in watchpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(watchpoint.to_string());
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                watchpoints.remove(i);
+                command = Some(DebugCommand::SetWatchpoints(watchpoints.clone()));
+            }
+            if let Some(error) = error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            ui.separator();
+            let mut changed = false;
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Break on");
+                for interrupt in Interrupt::ALL {
+                    changed |= toggle(
+                        ui,
+                        &mut break_on.interrupts,
+                        interrupt,
+                        format!("{:?}", interrupt),
+                    );
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Break on");
+                for vector in (0..8).map(|rst| rst * 8) {
+                    changed |= toggle(
+                        ui,
+                        &mut break_on.rst_vectors,
+                        vector,
+                        format!("RST {:02X}", vector),
+                    );
+                }
+            });
+            if changed {
+                command = Some(DebugCommand::SetBreakOn(break_on.clone()));
+            }
+
+            let Some(view) = view.as_ref().filter(|view| view.paused) else {
+                ui.label("Running");
+                return;
+            };
+            ui.separator();
+            if let Some(hit) = &view.watch_hit {
+                ui.label(hit);
+            }
+            ui.monospace(&view.next);
+            ui.monospace(&view.registers);
+            ui.separator();
+            ui.label("Backtrace");
+            for frame in &view.backtrace {
+                ui.monospace(frame);
+            }
+            if let Some(access) = &view.access {
+                ui.separator();
+                show_access(ui, access);
+            }
+        });
+
+        if let Some(command) = command {
+            let _ = commands.send(Command::Debug(command));
+        }
+    }
+}
+
+/// Hex, with or without `0x` or `$`
+fn parse_addr(text: &str) -> Result<u16, String> {
+    let hex = text.trim().trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(hex, 16).map_err(|_| format!("Not an address: {}", text))
+}
+
+/// One address, or the first and last separated by `-`
+fn parse_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+    if start > end {
+        return Err(format!("{} ends before it starts", text.trim()));
+    }
+    Ok(start..=end)
+}
+
+/// Hex bytes separated by whitespace
+fn parse_bytes(text: &str) -> Result<Vec<u8>, String> {
+    text.split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| format!("Not a byte: {}", byte)))
+        .collect()
+}
+
+fn parse_breakpoint(addr: &str, condition: &str) -> Result<Breakpoint, String> {
+    let addr = parse_addr(addr)?;
+    let condition = match condition.trim() {
+        "" => None,
+        condition => {
+            Some(Expression::parse(condition).map_err(|e| format!("{}: {}", condition, e))?)
+        }
+    };
+    Ok(Breakpoint { addr, condition })
+}
+
+fn set_breakpoints(breakpoints: &[(Breakpoint, String)]) -> DebugCommand {
+    DebugCommand::SetBreakpoints(
+        breakpoints
+            .iter()
+            .map(|(breakpoint, _)| breakpoint.clone())
+            .collect(),
+    )
+}
+
+/// A checkbox for whether `value` is in `values`, returns true if it changed
+fn toggle<T: PartialEq>(ui: &mut egui::Ui, values: &mut Vec<T>, value: T, label: String) -> bool {
+    let mut checked = values.contains(&value);
+    if !ui.checkbox(&mut checked, label).changed() {
+        return false;
+    }
+    if checked {
+        values.push(value);
+    } else {
+        values.retain(|v| *v != value);
+    }
+    true
+}
+
+/// Pixels per page of the heatmap
+const HEATMAP_CELL: f32 = 10.0;
+
+fn show_access(ui: &mut egui::Ui, access: &AccessView) {
+    ui.label("Memory accesses");
+    egui::Grid::new("access_regions").show(ui, |ui| {
+        for (region, accesses) in &access.regions {
+            ui.monospace(region.name());
+            ui.monospace(format!("R {}", accesses.reads));
+            ui.monospace(format!("W {}", accesses.writes));
+            ui.end_row();
+        }
+    });
+    for line in &access.hottest {
+        ui.monospace(line);
+    }
+
+    // 16x16 pages, 0x0000 top left and a row per 0x1000
+    let size = egui::vec2(16.0, 16.0) * HEATMAP_CELL;
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let origin = response.rect.min;
+    for (page, &heat) in access.heatmap.iter().enumerate() {
+        let cell = egui::vec2((page % 16) as f32, (page / 16) as f32) * HEATMAP_CELL;
+        let rect = egui::Rect::from_min_size(origin + cell, egui::vec2(HEATMAP_CELL, HEATMAP_CELL));
+        painter.rect_filled(rect, 0.0, egui::Color32::from_rgb(heat, 0, 255 - heat));
+    }
+    if let Some(pos) = response.hover_pos() {
+        let cell = (pos - origin) / HEATMAP_CELL;
+        let page = (cell.y as usize).min(15) * 16 + (cell.x as usize).min(15);
+        response.on_hover_text(format!("{:#06X}-{:#06X}", page << 8, (page << 8) | 0xFF));
+    }
+}
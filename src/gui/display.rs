@@ -0,0 +1,72 @@
+use std::sync::mpsc::Sender;
+
+use crate::{
+    emulator::Command,
+    renderer::{ColorAdjust, Palette},
+};
+
+#[derive(Default)]
+pub struct DisplayPanel {
+    pub open: bool,
+    pub adjust: ColorAdjust,
+    /// Index into [`Palette::ALL`], `None` for grayscale. Color models show the colors in
+    /// palette RAM whichever is picked, DMG games included.
+    pub palette: Option<usize>,
+    /// LCD ghosting, see [`Command::SetFrameBlend`]
+    pub persistence: f32,
+    /// See [`Command::SetReduceFlashing`]
+    pub reduce_flashing: bool,
+}
+
+impl DisplayPanel {
+    pub fn show(&mut self, ctx: &egui::Context, commands: &Sender<Command>) {
+        let Self {
+            open,
+            adjust,
+            palette,
+            persistence,
+            reduce_flashing,
+        } = self;
+
+        egui::Window::new("Display").open(open).show(ctx, |ui| {
+            egui::Grid::new("color_adjust").show(ui, |ui| {
+                ui.label("Palette");
+                egui::ComboBox::from_id_source("palette")
+                    .selected_text(palette.map_or("Automatic", |i| Palette::ALL[i].0))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(palette, None, "Automatic");
+                        for (i, (name, _)) in Palette::ALL.iter().enumerate() {
+                            ui.selectable_value(palette, Some(i), *name);
+                        }
+                    });
+                ui.end_row();
+                ui.label("Brightness");
+                ui.add(egui::Slider::new(&mut adjust.brightness, -0.5..=0.5));
+                ui.end_row();
+                ui.label("Contrast");
+                ui.add(egui::Slider::new(&mut adjust.contrast, 0.25..=2.0));
+                ui.end_row();
+                ui.label("Gamma");
+                ui.add(egui::Slider::new(&mut adjust.gamma, 0.5..=2.5));
+                ui.end_row();
+                ui.label("LCD ghosting");
+                let slider = egui::Slider::new(persistence, 0.0..=0.9);
+                if ui.add(slider).changed() {
+                    let _ = commands.send(Command::SetFrameBlend(*persistence));
+                }
+                ui.end_row();
+            });
+            if ui.checkbox(reduce_flashing, "Reduce flashing").changed() {
+                let _ = commands.send(Command::SetReduceFlashing(*reduce_flashing));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Default").clicked() {
+                    *adjust = ColorAdjust::NEUTRAL;
+                }
+                if ui.button("DMG washed out").clicked() {
+                    *adjust = ColorAdjust::DMG_WASHED_OUT;
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::Instant;
+
+use egui::{pos2, vec2, Event, Key, Modifiers, PointerButton, RawInput, Rect};
+use winit::{
+    event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
+    window::Window,
+};
+
+/// Turns winit window events into egui's [`RawInput`]
+pub struct GuiInput {
+    raw: RawInput,
+    pointer_pos: egui::Pos2,
+    modifiers: Modifiers,
+    start: Instant,
+}
+
+impl Default for GuiInput {
+    fn default() -> Self {
+        Self {
+            raw: RawInput::default(),
+            pointer_pos: egui::Pos2::ZERO,
+            modifiers: Modifiers::default(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl GuiInput {
+    pub fn on_event(&mut self, window: &Window, event: &WindowEvent) {
+        let pixels_per_point = window.scale_factor() as f32;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_pos = pos2(
+                    position.x as f32 / pixels_per_point,
+                    position.y as f32 / pixels_per_point,
+                );
+                self.raw.events.push(Event::PointerMoved(self.pointer_pos));
+            }
+            WindowEvent::CursorLeft { .. } => self.raw.events.push(Event::PointerGone),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::Other(_) => return,
+                };
+                self.raw.events.push(Event::PointerButton {
+                    pos: self.pointer_pos,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    // Same as egui-winit
+                    MouseScrollDelta::LineDelta(x, y) => vec2(*x, *y) * 50.0,
+                    MouseScrollDelta::PixelDelta(delta) => {
+                        vec2(delta.x as f32, delta.y as f32) / pixels_per_point
+                    }
+                };
+                self.raw.events.push(Event::Scroll(delta));
+            }
+            WindowEvent::ReceivedCharacter(ch) if !ch.is_control() => {
+                self.raw.events.push(Event::Text(ch.to_string()));
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = Modifiers {
+                    alt: state.alt(),
+                    ctrl: state.ctrl(),
+                    shift: state.shift(),
+                    mac_cmd: cfg!(target_os = "macos") && state.logo(),
+                    command: if cfg!(target_os = "macos") {
+                        state.logo()
+                    } else {
+                        state.ctrl()
+                    },
+                };
+                self.raw.modifiers = self.modifiers;
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode.and_then(translate_key) {
+                    self.raw.events.push(Event::Key {
+                        key,
+                        pressed: input.state == ElementState::Pressed,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Everything that happened since the last call
+    pub fn take(&mut self, window: &Window) -> RawInput {
+        let pixels_per_point = window.scale_factor() as f32;
+        let size = window.inner_size();
+        self.raw.screen_rect = Some(Rect::from_min_size(
+            egui::Pos2::ZERO,
+            vec2(
+                size.width as f32 / pixels_per_point,
+                size.height as f32 / pixels_per_point,
+            ),
+        ));
+        self.raw.pixels_per_point = Some(pixels_per_point);
+        self.raw.time = Some(self.start.elapsed().as_secs_f64());
+        self.raw.take()
+    }
+}
+
+fn translate_key(key: VirtualKeyCode) -> Option<Key> {
+    Some(match key {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Key::Num0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Key::Num1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Key::Num2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Key::Num3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Key::Num4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Key::Num5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Key::Num6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Key::Num7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Key::Num8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Key::Num9,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::B => Key::B,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::D => Key::D,
+        VirtualKeyCode::E => Key::E,
+        VirtualKeyCode::F => Key::F,
+        VirtualKeyCode::G => Key::G,
+        VirtualKeyCode::H => Key::H,
+        VirtualKeyCode::I => Key::I,
+        VirtualKeyCode::J => Key::J,
+        VirtualKeyCode::K => Key::K,
+        VirtualKeyCode::L => Key::L,
+        VirtualKeyCode::M => Key::M,
+        VirtualKeyCode::N => Key::N,
+        VirtualKeyCode::O => Key::O,
+        VirtualKeyCode::P => Key::P,
+        VirtualKeyCode::Q => Key::Q,
+        VirtualKeyCode::R => Key::R,
+        VirtualKeyCode::S => Key::S,
+        VirtualKeyCode::T => Key::T,
+        VirtualKeyCode::U => Key::U,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::W => Key::W,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
@@ -0,0 +1,101 @@
+use super::osd::Osd;
+use crate::logging::{LogFilter, DEFAULT_FILTER, TARGETS};
+
+const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+pub struct LoggingPanel {
+    pub open: bool,
+    log_filter: LogFilter,
+    /// Edited directly, or rebuilt from the level pickers
+    filter: String,
+    /// Level for everything without a target of its own
+    default: &'static str,
+    /// `None` to use the default
+    targets: [Option<&'static str>; TARGETS.len()],
+    error: Option<String>,
+}
+
+impl LoggingPanel {
+    pub fn new(log_filter: LogFilter) -> Self {
+        Self {
+            open: false,
+            log_filter,
+            filter: DEFAULT_FILTER.to_string(),
+            default: DEFAULT_FILTER,
+            targets: [None; TARGETS.len()],
+            error: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, osd: &mut Osd) {
+        let Self {
+            open,
+            log_filter,
+            filter,
+            default,
+            targets,
+            error,
+        } = self;
+
+        egui::Window::new("Logging").open(open).show(ctx, |ui| {
+            let mut picked = false;
+            egui::Grid::new("log_levels").show(ui, |ui| {
+                ui.label("Everything else");
+                picked |= level_picker(ui, "default", default);
+                ui.end_row();
+                for (target, level) in TARGETS.iter().zip(targets.iter_mut()) {
+                    ui.label(*target);
+                    let mut choice = level.unwrap_or("default");
+                    if level_picker(ui, target, &mut choice) {
+                        *level = Some(choice).filter(|&choice| choice != "default");
+                        picked = true;
+                    }
+                    ui.end_row();
+                }
+            });
+            if picked {
+                *filter = std::iter::once(default.to_string())
+                    .chain(
+                        TARGETS
+                            .iter()
+                            .zip(targets.iter())
+                            .filter_map(|(target, level)| {
+                                Some(format!("{}={}", target, (*level)?))
+                            }),
+                    )
+                    .collect::<Vec<_>>()
+                    .join(",");
+            }
+
+            ui.separator();
+            let submitted = ui.horizontal(|ui| {
+                let response = ui.text_edit_singleline(filter);
+                let entered = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                ui.button("Apply").clicked() || entered
+            });
+            if picked || submitted.inner {
+                *error = log_filter.set(filter).err();
+                if error.is_none() {
+                    osd.push(format!("Logging: {}", filter));
+                }
+            }
+            if let Some(error) = error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+        });
+    }
+}
+
+/// Returns true if a different level was picked
+fn level_picker(ui: &mut egui::Ui, id: &str, level: &mut &'static str) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_source(id)
+        .selected_text(*level)
+        .show_ui(ui, |ui| {
+            let choices = std::iter::once("default").filter(|_| id != "default");
+            for choice in choices.chain(LEVELS) {
+                changed |= ui.selectable_value(level, choice, choice).changed();
+            }
+        });
+    changed
+}
@@ -0,0 +1,51 @@
+//! Short messages over the game, like "Loaded TETRIS", shown whether the overlay is open or not
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const SHOW_FOR: Duration = Duration::from_secs(2);
+/// Out of [`SHOW_FOR`], at the end
+const FADE: Duration = Duration::from_millis(500);
+/// Older ones go first when there are more
+const MAX_MESSAGES: usize = 4;
+
+#[derive(Default)]
+pub struct Osd {
+    /// Oldest first, with when they were added
+    messages: VecDeque<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn push(&mut self, message: String) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((message, Instant::now()));
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+        self.messages
+            .retain(|(_, added)| now.duration_since(*added) < SHOW_FOR);
+        if self.messages.is_empty() {
+            return;
+        }
+        egui::Area::new("osd")
+            .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(8.0, -8.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                for (message, added) in &self.messages {
+                    let left = SHOW_FOR.saturating_sub(now.duration_since(*added));
+                    let alpha = (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0);
+                    egui::Frame::none()
+                        .fill(egui::Color32::from_black_alpha((alpha * 180.0) as u8))
+                        .inner_margin(4.0)
+                        .show(ui, |ui| {
+                            let color = egui::Color32::from_white_alpha((alpha * 255.0) as u8);
+                            ui.colored_label(color, message);
+                        });
+                }
+            });
+    }
+}
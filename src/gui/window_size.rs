@@ -0,0 +1,76 @@
+//! Keeping the window a whole multiple of the screen, so nearest filtering stays crisp
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, VirtualKeyCode, WindowEvent},
+    window::Window,
+};
+
+use crate::emulator::{GAMEBOY_HEIGHT, GAMEBOY_WIDTH};
+
+pub const SCALES: std::ops::RangeInclusive<u32> = 1..=6;
+
+/// `scale` times the Game Boy's resolution
+pub fn size(scale: u32) -> PhysicalSize<u32> {
+    PhysicalSize::new(GAMEBOY_WIDTH as u32 * scale, GAMEBOY_HEIGHT as u32 * scale)
+}
+
+/// The closest multiple to `size`, never smaller than 1x
+pub fn snapped(size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+    let x = size.width as f32 / GAMEBOY_WIDTH as f32;
+    let y = size.height as f32 / GAMEBOY_HEIGHT as f32;
+    self::size((x.min(y).round() as u32).max(1))
+}
+
+#[derive(Default)]
+pub struct WindowSize {
+    /// Snap resizes by dragging too
+    pub snap: bool,
+    alt: bool,
+}
+
+impl WindowSize {
+    /// Alt+1 to Alt+6 pick a size. Returns true for those.
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.alt = modifiers.alt(),
+            WindowEvent::KeyboardInput { input, .. }
+                if self.alt && input.state == ElementState::Pressed =>
+            {
+                let scale = match input.virtual_keycode {
+                    Some(VirtualKeyCode::Key1) => 1,
+                    Some(VirtualKeyCode::Key2) => 2,
+                    Some(VirtualKeyCode::Key3) => 3,
+                    Some(VirtualKeyCode::Key4) => 4,
+                    Some(VirtualKeyCode::Key5) => 5,
+                    Some(VirtualKeyCode::Key6) => 6,
+                    _ => return false,
+                };
+                window.set_inner_size(size(scale));
+                return true;
+            }
+            WindowEvent::Resized(new_size) if self.snap => {
+                let snapped = snapped(*new_size);
+                // Setting it sends another Resized, which is already snapped
+                if snapped != *new_size {
+                    window.set_inner_size(snapped);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    pub fn menu(&mut self, ui: &mut egui::Ui, window: &Window) {
+        for scale in SCALES {
+            let label = format!("{}x ({}x{})", scale, size(scale).width, size(scale).height);
+            if ui.button(label).clicked() {
+                window.set_inner_size(size(scale));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.checkbox(&mut self.snap, "Snap resizes").changed() && self.snap {
+            window.set_inner_size(snapped(window.inner_size()));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+use crate::emulator::{joypad::Button, mbc::mbc7::TiltDirection, Command};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Button(Button),
+    /// Autofire, see [`crate::emulator::joypad::TurboConfig`]
+    Turbo(Button),
+    /// Tips MBC7 cartridges, see [`crate::emulator::mbc::mbc7::Accelerometer`]
+    Tilt(TiltDirection),
+}
+
+pub struct KeyBindings {
+    bindings: Vec<(VirtualKeyCode, Binding)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (VirtualKeyCode::Right, Binding::Button(Button::Right)),
+                (VirtualKeyCode::Left, Binding::Button(Button::Left)),
+                (VirtualKeyCode::Up, Binding::Button(Button::Up)),
+                (VirtualKeyCode::Down, Binding::Button(Button::Down)),
+                (VirtualKeyCode::X, Binding::Button(Button::A)),
+                (VirtualKeyCode::Z, Binding::Button(Button::B)),
+                (VirtualKeyCode::Back, Binding::Button(Button::Select)),
+                (VirtualKeyCode::Return, Binding::Button(Button::Start)),
+                (VirtualKeyCode::S, Binding::Turbo(Button::A)),
+                (VirtualKeyCode::A, Binding::Turbo(Button::B)),
+                (VirtualKeyCode::I, Binding::Tilt(TiltDirection::Forward)),
+                (VirtualKeyCode::J, Binding::Tilt(TiltDirection::Left)),
+                (VirtualKeyCode::K, Binding::Tilt(TiltDirection::Back)),
+                (VirtualKeyCode::L, Binding::Tilt(TiltDirection::Right)),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Replaces whatever was bound to `key`
+    pub fn bind(&mut self, key: VirtualKeyCode, binding: Binding) {
+        self.unbind(key);
+        self.bindings.push((key, binding));
+    }
+
+    pub fn unbind(&mut self, key: VirtualKeyCode) {
+        self.bindings.retain(|(bound, _)| *bound != key);
+    }
+
+    pub fn get(&self, key: VirtualKeyCode) -> Option<Binding> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, binding)| *binding)
+    }
+
+    pub fn map_keyboard_input(&self, input: &KeyboardInput) -> Option<Command> {
+        let binding = self.get(input.virtual_keycode?)?;
+        let pressed = input.state == ElementState::Pressed;
+        Some(match binding {
+            Binding::Button(button) => Command::Button(button, pressed),
+            Binding::Turbo(button) => Command::Turbo(button, pressed),
+            Binding::Tilt(direction) => Command::Tilt(direction, pressed),
+        })
+    }
+}
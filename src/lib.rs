@@ -0,0 +1,8 @@
+//! Emulator core, usable without the desktop frontend in main.rs
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod emulator;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
@@ -0,0 +1,569 @@
+//! libretro core
+//!
+//! Build with `--features libretro` and load the resulting cdylib into RetroArch (or any other
+//! libretro frontend). Audio is silence until there's an APU. The Game Boy Camera sees through
+//! the frontend's camera, if it has one.
+use std::{
+    ffi::{c_char, c_uint, c_void, CStr, CString},
+    sync::Mutex,
+};
+
+use tracing::{error, info, warn};
+
+use crate::emulator::{
+    cheats::Cheat,
+    joypad::Button,
+    mbc::camera::{self, netpbm, CameraSource, Picture},
+    Emulator, GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
+};
+
+pub const RETRO_API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_SET_MESSAGE: c_uint = 6;
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const ENVIRONMENT_EXPERIMENTAL: c_uint = 0x10000;
+const ENVIRONMENT_GET_CAMERA_INTERFACE: c_uint = 26 | ENVIRONMENT_EXPERIMENTAL;
+const CAMERA_BUFFER_RAW_FRAMEBUFFER: u64 = 1 << 1;
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const DEVICE_JOYPAD: c_uint = 1;
+const DEVICE_ANALOG: c_uint = 5;
+const DEVICE_INDEX_ANALOG_LEFT: c_uint = 0;
+const DEVICE_ID_ANALOG_X: c_uint = 0;
+const DEVICE_ID_ANALOG_Y: c_uint = 1;
+const REGION_NTSC: c_uint = 0;
+
+/// 4194304Hz / 70224 cycles per frame
+const FPS: f64 = 59.727500569606;
+const SAMPLE_RATE: f64 = 44100.0;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FPS) as usize;
+
+/// libretro joypad IDs and what they're mapped to
+const JOYPAD_MAPPING: [(c_uint, Button); 8] = [
+    (0, Button::B),
+    (2, Button::Select),
+    (3, Button::Start),
+    (4, Button::Up),
+    (5, Button::Down),
+    (6, Button::Left),
+    (7, Button::Right),
+    (8, Button::A),
+];
+
+#[repr(C)]
+pub struct SystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    pub geometry: GameGeometry,
+    pub timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct Message {
+    pub msg: *const c_char,
+    pub frames: c_uint,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+pub struct CameraCallback {
+    pub caps: u64,
+    pub width: c_uint,
+    pub height: c_uint,
+    /// Filled in by the frontend, like `stop`
+    pub start: Option<unsafe extern "C" fn() -> bool>,
+    pub stop: Option<unsafe extern "C" fn()>,
+    pub frame_raw_framebuffer: Option<
+        unsafe extern "C" fn(buffer: *const u32, width: c_uint, height: c_uint, pitch: usize),
+    >,
+    pub frame_opengl_texture: Option<
+        unsafe extern "C" fn(texture_id: c_uint, texture_target: c_uint, affine: *const f32),
+    >,
+    pub initialized: Option<unsafe extern "C" fn()>,
+    pub deinitialized: Option<unsafe extern "C" fn()>,
+}
+
+pub type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+pub type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type InputPollFn = unsafe extern "C" fn();
+pub type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+struct Core {
+    emulator: Option<Emulator>,
+    cheats: Vec<Cheat>,
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    /// The frame converted to XRGB8888
+    video: Vec<u32>,
+    /// Game Boy cheats can be several codes joined with `+`, so each index can be a list
+    cheat_slots: Vec<(bool, Vec<Cheat>)>,
+    /// Emulation hit an error and stays on the last frame until reset
+    stopped: bool,
+}
+
+static CORE: Mutex<Core> = Mutex::new(Core {
+    emulator: None,
+    cheats: Vec::new(),
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+    video: Vec::new(),
+    cheat_slots: Vec::new(),
+    stopped: false,
+});
+
+/// The frontend's camera. Kept apart from [`Core`], the frontend can hand over frames during
+/// [`retro_run`].
+struct HostCamera {
+    start: Option<unsafe extern "C" fn() -> bool>,
+    stop: Option<unsafe extern "C" fn()>,
+    started: bool,
+    latest: Option<Picture>,
+}
+
+static HOST_CAMERA: Mutex<HostCamera> = Mutex::new(HostCamera {
+    start: None,
+    stop: None,
+    started: false,
+    latest: None,
+});
+
+fn host_camera() -> std::sync::MutexGuard<'static, HostCamera> {
+    HOST_CAMERA
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Whatever the frontend's camera showed last
+#[derive(Debug)]
+struct FrontendCamera;
+
+impl CameraSource for FrontendCamera {
+    fn capture(&mut self) -> Option<Picture> {
+        host_camera().latest.clone()
+    }
+}
+
+unsafe extern "C" fn camera_initialized() {
+    let mut camera = host_camera();
+    if let Some(start) = camera.start {
+        camera.started = start();
+        if !camera.started {
+            warn!("The frontend's camera didn't start");
+        }
+    }
+}
+
+unsafe extern "C" fn camera_deinitialized() {
+    host_camera().started = false;
+}
+
+/// XRGB8888, `pitch` in bytes
+unsafe extern "C" fn camera_frame(buffer: *const u32, width: c_uint, height: c_uint, pitch: usize) {
+    if buffer.is_null() || width == 0 || height == 0 {
+        return;
+    }
+    let (width, height) = (width as usize, height as usize);
+    let stride = pitch / std::mem::size_of::<u32>();
+    let pixels = (0..height)
+        .flat_map(|y| std::slice::from_raw_parts(buffer.add(y * stride), width))
+        .map(|&pixel| {
+            let [b, g, r, _] = pixel.to_le_bytes();
+            netpbm::luma(r, g, b)
+        })
+        .collect();
+    host_camera().latest = Some(Picture::new(width, height, pixels));
+}
+
+/// Asks the frontend for its camera, and has frames go to [`HOST_CAMERA`]
+unsafe fn request_camera(environment: EnvironmentFn) -> bool {
+    let mut callback = CameraCallback {
+        caps: CAMERA_BUFFER_RAW_FRAMEBUFFER,
+        width: camera::WIDTH as c_uint,
+        height: camera::HEIGHT as c_uint,
+        start: None,
+        stop: None,
+        frame_raw_framebuffer: Some(camera_frame),
+        frame_opengl_texture: None,
+        initialized: Some(camera_initialized),
+        deinitialized: Some(camera_deinitialized),
+    };
+    if !environment(
+        ENVIRONMENT_GET_CAMERA_INTERFACE,
+        (&mut callback as *mut CameraCallback).cast(),
+    ) {
+        return false;
+    }
+    let mut camera = host_camera();
+    camera.start = callback.start;
+    camera.stop = callback.stop;
+    camera.latest = None;
+    true
+}
+
+fn stop_camera() {
+    let mut camera = host_camera();
+    if let (true, Some(stop)) = (camera.started, camera.stop) {
+        // Safety: the frontend handed it over for this
+        unsafe { stop() };
+    }
+    camera.started = false;
+}
+
+fn core() -> std::sync::MutexGuard<'static, Core> {
+    // A panic in an earlier call shouldn't take the frontend down with it
+    CORE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Core {
+    fn apply_cheats(&mut self) {
+        self.cheats = self
+            .cheat_slots
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .flat_map(|(_, cheats)| cheats.iter().cloned())
+            .collect();
+        if let Some(emulator) = self.emulator.as_mut() {
+            emulator
+                .memory_bus_mut()
+                .cheats_mut()
+                .set(self.cheats.clone());
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+/// `info` has to point to a writable [`SystemInfo`]
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: c"gameboy_emulator".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"gb|dmg".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` has to point to a writable [`SystemAvInfo`]
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: GAMEBOY_WIDTH as c_uint,
+            base_height: GAMEBOY_HEIGHT as c_uint,
+            max_width: GAMEBOY_WIDTH as c_uint,
+            max_height: GAMEBOY_HEIGHT as c_uint,
+            aspect_ratio: GAMEBOY_WIDTH as f32 / GAMEBOY_HEIGHT as f32,
+        },
+        timing: SystemTiming {
+            fps: FPS,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    core().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    core().video_refresh = Some(callback);
+}
+
+/// Unused, audio goes through [`retro_set_audio_sample_batch`]
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    core().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    core().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    core().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    let mut core = core();
+    core.emulator = None;
+    core.cheat_slots.clear();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    let mut core = core();
+    if let Some(emulator) = core.emulator.as_mut() {
+        emulator.reset();
+        core.stopped = false;
+    }
+}
+
+/// # Safety
+/// The callbacks handed over by the frontend have to be safe to call
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let mut core = core();
+    let core = &mut *core;
+    let Some(emulator) = core.emulator.as_mut() else {
+        return;
+    };
+    if core.stopped {
+        if let Some(video_refresh) = core.video_refresh {
+            // Null repeats the last frame
+            video_refresh(
+                std::ptr::null(),
+                GAMEBOY_WIDTH as c_uint,
+                GAMEBOY_HEIGHT as c_uint,
+                0,
+            );
+        }
+        return;
+    }
+
+    if let (Some(poll), Some(state)) = (core.input_poll, core.input_state) {
+        poll();
+        let joypad = emulator.memory_bus_mut().joypad_mut();
+        for (id, button) in JOYPAD_MAPPING {
+            joypad.set_button(button, state(0, DEVICE_JOYPAD, 0, id) != 0);
+        }
+        // MBC7 games are played by tilting, the left stick stands in for that. Pushing it up
+        // tips the cartridge forward, but analog Y counts up going down.
+        if let Some(accelerometer) = emulator.memory_bus_mut().accelerometer_mut() {
+            let axis = |id| state(0, DEVICE_ANALOG, DEVICE_INDEX_ANALOG_LEFT, id) as f32 / 32768.0;
+            accelerometer.set_analog(axis(DEVICE_ID_ANALOG_X), -axis(DEVICE_ID_ANALOG_Y));
+        }
+    }
+
+    // Unwinding out of an extern "C" fn would abort the frontend
+    match emulator.run_frame_catching() {
+        Ok(_) => {}
+        Err(crash) => {
+            error!("Emulation stopped: {}\n{}", crash.message, crash.cpu_dump);
+            core.stopped = true;
+            if let (Some(environment), Ok(text)) = (core.environment, CString::new(crash.message)) {
+                let mut message = Message {
+                    msg: text.as_ptr(),
+                    frames: 10 * FPS as c_uint,
+                };
+                environment(
+                    ENVIRONMENT_SET_MESSAGE,
+                    (&mut message as *mut Message).cast(),
+                );
+            }
+            return;
+        }
+    }
+    core.video.clear();
+    match emulator.color_frame() {
+        Some(colors) => core.video.extend(colors.iter().map(|&color| {
+            let channel = |shift: u16| {
+                let value = (color >> shift & 0x1F) as u32;
+                value << 3 | value >> 2
+            };
+            channel(0) << 16 | channel(5) << 8 | channel(10)
+        })),
+        None => core
+            .video
+            .extend(emulator.frame_buffer().iter().map(|&shade| {
+                let shade = shade as u32;
+                shade << 16 | shade << 8 | shade
+            })),
+    }
+    if let Some(video_refresh) = core.video_refresh {
+        video_refresh(
+            core.video.as_ptr().cast(),
+            GAMEBOY_WIDTH as c_uint,
+            GAMEBOY_HEIGHT as c_uint,
+            GAMEBOY_WIDTH * std::mem::size_of::<u32>(),
+        );
+    }
+
+    if let Some(audio_sample_batch) = core.audio_sample_batch {
+        let silence = [0i16; SAMPLES_PER_FRAME * 2];
+        audio_sample_batch(silence.as_ptr(), SAMPLES_PER_FRAME);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    0
+}
+
+/// Save states aren't supported yet
+#[no_mangle]
+pub extern "C" fn retro_serialize(_data: *mut c_void, _size: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unserialize(_data: *const c_void, _size: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    let mut core = core();
+    core.cheat_slots.clear();
+    core.apply_cheats();
+}
+
+/// # Safety
+/// `code` has to be a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let code = CStr::from_ptr(code).to_string_lossy();
+    let cheats = code
+        .split('+')
+        .filter_map(|code| {
+            Cheat::parse(code)
+                .map_err(|e| warn!("Ignoring cheat {}: {}", code, e))
+                .ok()
+        })
+        .collect();
+
+    let mut core = core();
+    let index = index as usize;
+    if core.cheat_slots.len() <= index {
+        core.cheat_slots.resize(index + 1, (false, Vec::new()));
+    }
+    core.cheat_slots[index] = (enabled, cheats);
+    core.apply_cheats();
+}
+
+/// # Safety
+/// `game` has to point to a valid [`GameInfo`] with `size` bytes at `data`
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        error!("retro_load_game without a game");
+        return false;
+    };
+    if game.data.is_null() {
+        error!("retro_load_game without ROM data");
+        return false;
+    }
+
+    let mut core = core();
+    if let Some(environment) = core.environment {
+        let mut format = PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            ENVIRONMENT_SET_PIXEL_FORMAT,
+            (&mut format as *mut c_uint).cast(),
+        ) {
+            error!("Frontend doesn't support XRGB8888");
+            return false;
+        }
+    }
+
+    let rom = std::slice::from_raw_parts(game.data.cast::<u8>(), game.size);
+    let mut emulator = Emulator::new(rom);
+    info!("Loaded {}", emulator.memory_bus().rom_title());
+    if let (Some(camera), Some(environment)) =
+        (emulator.memory_bus_mut().camera_mut(), core.environment)
+    {
+        if request_camera(environment) {
+            camera.connect(Box::new(FrontendCamera));
+        } else {
+            warn!("The frontend has no camera, the Game Boy Camera will only see black");
+        }
+    }
+    emulator
+        .memory_bus_mut()
+        .cheats_mut()
+        .set(core.cheats.clone());
+    core.emulator = Some(emulator);
+    core.stopped = false;
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    core().emulator = None;
+    stop_camera();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
@@ -0,0 +1,42 @@
+//! Log filtering that can be changed while running
+//!
+//! The chattiest subsystems log under their own targets instead of module paths, so
+//! `info,cpu=trace` shows every instruction without the rest of the emulator. The filter
+//! starts out as `--log` and can be replaced from the GUI.
+use std::str::FromStr;
+
+use tracing_subscriber::{filter::Targets, prelude::*, reload, Registry};
+
+/// Subsystems with their own target, everything else logs under its module path
+pub const TARGETS: [&str; 7] = ["cpu", "bus", "ppu", "apu", "timer", "serial", "joypad"];
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Changes the filter of the global subscriber
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<Targets, Registry>,
+}
+
+impl LogFilter {
+    /// Replaces the filter, which is comma separated `target=level` with a bare level for
+    /// everything else
+    pub fn set(&self, filter: &str) -> Result<(), String> {
+        let targets = parse(filter)?;
+        self.handle.reload(targets).map_err(|e| e.to_string())
+    }
+}
+
+fn parse(filter: &str) -> Result<Targets, String> {
+    Targets::from_str(filter).map_err(|e| format!("Bad log filter '{}': {}", filter, e))
+}
+
+/// Installs the global subscriber, logging to stderr since stdout is kept for output like
+/// frame hashes
+pub fn init(filter: &str) -> Result<LogFilter, String> {
+    let (filter, handle) = reload::Layer::new(parse(filter)?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .init();
+    Ok(LogFilter { handle })
+}
@@ -1,39 +1,560 @@
+use cli::{Args, IrArg, LinkArg};
+use emulator::{
+    archive,
+    boot::Boot,
+    hardware::HardwareModel,
+    infrared::{self, file::FileLink, InfraredLink},
+    mbc::camera::StillImage,
+    serial::{partner::Partner, printer::Printer, SerialLink},
+    stats::Stats,
+    symbols::Symbols,
+    Command,
+};
+use gameboy_emulator::emulator;
+use gui::{Connection, Gui};
+use input::KeyBindings;
+use logging::LogFilter;
+use recent::RecentRoms;
 use renderer::Renderer;
+use std::{
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread::JoinHandle,
+};
 use winit::{
     event::{Event, WindowEvent},
-    event_loop::ControlFlow,
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
 };
 
-pub mod emulator;
+pub mod cli;
+pub mod gui;
+pub mod input;
+pub mod logging;
+pub mod recent;
 pub mod renderer;
+pub mod subcommand;
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    let mut args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if let Some(subcommand) = &args.subcommand {
+        match subcommand::run(subcommand, args.dirs.paths().as_ref()) {
+            Ok(done) => println!("{}", done),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let log_filter = match logging::init(args.log.as_deref().unwrap_or(logging::DEFAULT_FILTER)) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
-    let event_loop = winit::event_loop::EventLoop::new();
-    let window = winit::window::WindowBuilder::new()
-        .with_decorations(true)
-        .with_resizable(true)
-        .with_transparent(false)
-        .with_title("Gameboy Emulator")
-        .build(&event_loop)
-        .expect("Failed to create window with winit");
+    args.options.paths = args.dirs.paths();
+    let mut recent = RecentRoms::load(args.options.paths.as_ref()).unwrap_or_else(|e| {
+        eprintln!("Failed to load recent ROMs: {}", e);
+        RecentRoms::default()
+    });
+    if let Some(n) = args.recent {
+        match recent.get(n) {
+            Some(path) => args.rom = Some(path.to_path_buf()),
+            None => {
+                eprintln!("Only {} ROMs were opened recently", recent.paths().len());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = &args.rom {
+        match read_rom(path, args.options.verify_rom) {
+            Ok(rom) => args.options.rom = Some(rom),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = recent.add(path) {
+            eprintln!("Failed to save recent ROMs: {}", e);
+        }
+    }
+    let sym_file = match (&args.symbols, &args.rom) {
+        (Some(path), _) => Some(path.clone()),
+        (None, Some(rom)) => sym_file(rom),
+        (None, None) => None,
+    };
+    if let Some(path) = sym_file {
+        match Symbols::load(&path) {
+            Ok(symbols) => args.options.symbols = Some(symbols),
+            Err(e) => {
+                eprintln!("Failed to load symbols from {:?}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = &args.boot_rom {
+        match read_boot_rom(path, args.options.model) {
+            Ok(boot) => args.options.boot = boot,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(link) = args.link.as_ref().filter(|link| **link != LinkArg::Local) {
+        args.options.link = Some(plug_in(link));
+    }
+    if let Some(infrared) = &args.infrared {
+        args.options.infrared = Some(point_at(infrared));
+    }
+    if let Some(path) = &args.camera {
+        match StillImage::open(path) {
+            Ok(picture) => args.options.camera = Some(Box::new(picture)),
+            Err(e) => {
+                eprintln!(
+                    "Failed to load a picture for the camera from {:?}: {}",
+                    path, e
+                );
+                std::process::exit(1);
+            }
+        }
+    }
 
-    let buffer = emulator::run();
-    let mut renderer = Renderer::new(&window, buffer);
+    if let Some(headless) = args.headless {
+        run_headless(args.options, headless);
+        return;
+    }
+    if let Some(frames) = args.bench {
+        bench(args.options, frames);
+        return;
+    }
+
+    let event_loop = winit::event_loop::EventLoop::new();
+    let mut instances = Vec::new();
+    if args.link == Some(LinkArg::Local) {
+        let (first, second) = emulator::serial::link_pair();
+        let (first_ir, second_ir) = infrared::link_pair();
+        let second_options = emulator::Options {
+            rom: args.options.rom.clone(),
+            link: Some(Box::new(second)),
+            infrared: args
+                .options
+                .infrared
+                .is_none()
+                .then(|| Box::new(second_ir) as Box<dyn InfraredLink>),
+            symbols: args.options.symbols.clone(),
+            // Both would save over the same state
+            resume: false,
+            ..settings(&args.options)
+        };
+        args.options.link = Some(Box::new(first));
+        if args.options.infrared.is_none() {
+            args.options.infrared = Some(Box::new(first_ir));
+        }
+        let title = "Gameboy Emulator - Player 1";
+        let p1 = Instance::new(
+            &event_loop,
+            title,
+            args.options,
+            log_filter.clone(),
+            recent.clone(),
+            args.roms.clone(),
+        );
+        let title = "Gameboy Emulator - Player 2";
+        let p2 = Instance::new(
+            &event_loop,
+            title,
+            second_options,
+            log_filter,
+            recent,
+            args.roms,
+        );
+        instances.extend([p1, p2]);
+    } else {
+        let title = "Gameboy Emulator";
+        // Nothing to run yet, it'll be picked from the list
+        let browse = args.rom.is_none() && args.roms.is_some();
+        args.options.paused |= browse;
+        let mut instance = Instance::new(
+            &event_loop,
+            title,
+            args.options,
+            log_filter,
+            recent,
+            args.roms,
+        );
+        if browse {
+            instance.gui.browse();
+        }
+        instance.pause_in_background = args.pause_in_background;
+        instances.push(instance);
+    }
+    let key_bindings = KeyBindings::default();
 
     event_loop.run(move |event, _, control_flow| {
-        if renderer.handle_event(&window, &event, control_flow) {
-            return;
+        let mut closed = false;
+        for instance in &mut instances {
+            closed |= instance.handle_event(&event, &key_bindings, control_flow);
+        }
+        if closed {
+            // Linked instances go down together, the other one can't do much on its own
+            for instance in &mut instances {
+                instance.quit();
+            }
+            *control_flow = ControlFlow::Exit;
+        }
+    })
+}
+
+/// Everything in `options` that isn't about a particular game or connection
+fn settings(options: &emulator::Options) -> emulator::Options {
+    emulator::Options {
+        paths: options.paths.clone(),
+        strict_memory: options.strict_memory,
+        model: options.model,
+        boot: options.boot.clone(),
+        no_oam_bug: options.no_oam_bug,
+        no_watchdog: options.no_watchdog,
+        resume: options.resume,
+        reduce_flashing: options.reduce_flashing,
+        cgb_palette: options.cgb_palette,
+        backend: options.backend,
+        verify_rom: options.verify_rom,
+        ..Default::default()
+    }
+}
+
+/// Which has to be a dump of `model`'s, see [`Boot::dump`]
+fn read_boot_rom(path: &Path, model: HardwareModel) -> Result<Boot, String> {
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to read {:?}: {}", path, e);
+    let rom = std::fs::read(path).map_err(|e| failed(&e))?;
+    Boot::dump(rom, model).map_err(|e| format!("{:?} isn't a boot ROM: {}", path, e))
+}
+
+/// Unzipping it first if it's compressed. With `verify`, ROMs that fail
+/// [`emulator::rom::check_integrity`] are an error.
+fn read_rom(path: &Path, verify: bool) -> Result<Vec<u8>, String> {
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to read {:?}: {}", path, e);
+    let bytes = std::fs::read(path).map_err(|e| failed(&e))?;
+    let rom = archive::extract(path, bytes).map_err(|e| failed(&e))?;
+    if rom.len() < emulator::rom::MIN_SIZE {
+        return Err(format!(
+            "{:?} is too small to be a ROM ({} bytes)",
+            path,
+            rom.len()
+        ));
+    }
+    let problems = emulator::rom::check_integrity(&rom);
+    if verify && !problems.is_empty() {
+        let problems: Vec<String> = problems.iter().map(|problem| problem.to_string()).collect();
+        return Err(format!(
+            "Not running {:?}, it failed verification:\n{}",
+            path,
+            problems.join("\n")
+        ));
+    }
+    Ok(rom)
+}
+
+/// Assemblers put it next to the ROM
+fn sym_file(rom: &Path) -> Option<PathBuf> {
+    Some(rom.with_extension("sym")).filter(|path| path.exists())
+}
+
+/// Whatever goes in the link port for anything but [`LinkArg::Local`], exits if it can't be
+/// set up
+fn plug_in(link: &LinkArg) -> Box<dyn SerialLink> {
+    let (addr, result) = match link {
+        LinkArg::Printer(dir) => return Box::new(Printer::new(dir.clone())),
+        LinkArg::Partner { byte, hz } => return Box::new(Partner::new(*byte, *hz)),
+        LinkArg::Host(addr) => (addr, emulator::serial::tcp::host(addr)),
+        LinkArg::Connect(addr) => (addr, emulator::serial::tcp::connect(addr)),
+        LinkArg::Local => unreachable!("Local links are two instances, not a device"),
+    };
+    match result {
+        Ok(link) => Box::new(link),
+        Err(e) => {
+            eprintln!("Failed to set up link cable with {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// What the infrared port is pointed at, exits if it can't be set up
+fn point_at(infrared: &IrArg) -> Box<dyn InfraredLink> {
+    fn boxed<L: InfraredLink + 'static, E: ToString>(
+        result: Result<L, E>,
+    ) -> Result<Box<dyn InfraredLink>, String> {
+        match result {
+            Ok(link) => Ok(Box::new(link)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+    let result = match infrared {
+        IrArg::Loopback => return Box::new(infrared::Loopback::default()),
+        IrArg::Record(path) => boxed(FileLink::record(path)),
+        IrArg::Play(path) => boxed(FileLink::play(path)),
+        IrArg::Host(addr) => boxed(infrared::tcp::host(addr)),
+        IrArg::Connect(addr) => boxed(infrared::tcp::connect(addr)),
+    };
+    match result {
+        Ok(link) => link,
+        Err(e) => {
+            eprintln!("Failed to set up infrared with {:?}: {}", infrared, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints what [`emulator::print_headless`] does to stdout. Exits with an error if emulation
+/// stops early, or never prints a match for `--serial-until`.
+fn run_headless(options: emulator::Options, headless: cli::Headless) {
+    let output = emulator::HeadlessOutput {
+        hash_every: headless.hash_every,
+        serial: headless.serial,
+    };
+    let until = headless.serial_until.as_ref();
+    let result = emulator::print_headless(
+        options,
+        headless.frames,
+        output,
+        |line| until.is_some_and(|until| until.is_match(line)),
+        &mut std::io::stdout().lock(),
+    );
+    match (result, until) {
+        (Err(crash), _) => {
+            eprintln!("{}\n\n{}", crash.message, crash.cpu_dump);
+            std::process::exit(1);
+        }
+        (Ok(false), Some(until)) => {
+            eprintln!("Nothing printed over serial matched '{}'", until);
+            std::process::exit(1);
+        }
+        (Ok(_), _) => {}
+    }
+}
+
+/// Prints how fast `frames` frames ran, exits with an error if emulation stops early
+fn bench(options: emulator::Options, frames: u32) {
+    match emulator::run_headless(options, frames, |_, _, _| true) {
+        Ok(throughput) => println!("{}", throughput),
+        Err(crash) => {
+            eprintln!("{}\n\n{}", crash.message, crash.cpu_dump);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// An emulator thread along with its window
+struct Instance {
+    window: Window,
+    renderer: Renderer,
+    gui: Gui,
+    commands: Sender<Command>,
+    thread: Option<JoinHandle<()>>,
+    /// What a ROM opened from the menu runs with
+    settings: emulator::Options,
+    recent: RecentRoms,
+    pause_in_background: bool,
+    /// What the window's called without the stats after it
+    title: String,
+    stats: Arc<Mutex<Stats>>,
+    /// What the title shows
+    shown_stats: Stats,
+}
+
+impl Instance {
+    fn new(
+        event_loop: &EventLoop<()>,
+        title: &str,
+        options: emulator::Options,
+        log_filter: LogFilter,
+        recent: RecentRoms,
+        roms_dir: Option<PathBuf>,
+    ) -> Self {
+        let window = winit::window::WindowBuilder::new()
+            .with_decorations(true)
+            .with_resizable(true)
+            .with_transparent(false)
+            .with_title(title)
+            .build(event_loop)
+            .expect("Failed to create window with winit");
+
+        let settings = settings(&options);
+        let handle = emulator::run(options);
+        let renderer = Renderer::new(&window, handle.buffer);
+        let mut gui = Gui::new(
+            Connection {
+                commands: handle.commands.clone(),
+                cheats: handle.cheats,
+                crashes: handle.crashes,
+                debug_views: handle.debug_views,
+                audio_views: handle.audio_views,
+                serial_output: handle.serial_output,
+            },
+            log_filter,
+            recent.paths().to_vec(),
+            roms_dir,
+        );
+        gui.set_reduce_flashing(settings.reduce_flashing);
+        for problem in &handle.rom_problems {
+            gui.notify(problem.to_string());
+        }
+        Self {
+            window,
+            renderer,
+            gui,
+            commands: handle.commands,
+            thread: Some(handle.thread),
+            settings,
+            recent,
+            pause_in_background: false,
+            title: title.to_string(),
+            stats: handle.stats,
+            shown_stats: Stats::default(),
+        }
+    }
+
+    /// Stops the running game and starts `path` in its place
+    fn open(&mut self, path: &Path) {
+        let rom = match read_rom(path, self.settings.verify_rom) {
+            Ok(rom) => rom,
+            Err(e) => {
+                eprintln!("{}", e);
+                self.gui.notify(e);
+                return;
+            }
+        };
+        if let Err(e) = self.recent.add(path) {
+            eprintln!("Failed to save recent ROMs: {}", e);
+        }
+        let symbols = sym_file(path).and_then(|sym| match Symbols::load(&sym) {
+            Ok(symbols) => Some(symbols),
+            Err(e) => {
+                eprintln!("Failed to load symbols from {:?}: {}", sym, e);
+                None
+            }
+        });
+        self.quit();
+
+        let title = emulator::rom::header_title(&rom);
+        let handle = emulator::run(emulator::Options {
+            rom: Some(rom),
+            symbols,
+            ..settings(&self.settings)
+        });
+        self.renderer.set_buffer(handle.buffer);
+        self.gui.attach(
+            Connection {
+                commands: handle.commands.clone(),
+                cheats: handle.cheats,
+                crashes: handle.crashes,
+                debug_views: handle.debug_views,
+                audio_views: handle.audio_views,
+                serial_output: handle.serial_output,
+            },
+            self.recent.paths().to_vec(),
+        );
+        self.commands = handle.commands;
+        self.thread = Some(handle.thread);
+        self.stats = handle.stats;
+        self.gui.notify(format!("Loaded {}", title));
+        for problem in &handle.rom_problems {
+            self.gui.notify(problem.to_string());
+        }
+    }
+
+    /// Returns true if the window was closed
+    fn handle_event(
+        &mut self,
+        event: &Event<()>,
+        key_bindings: &KeyBindings,
+        control_flow: &mut ControlFlow,
+    ) -> bool {
+        if let Some(path) = self.gui.take_open_rom() {
+            self.open(&path);
+        }
+        self.update_stats();
+        if let Event::WindowEvent { window_id, event } = event {
+            if *window_id == self.window.id() && self.gui.handle_event(&self.window, event) {
+                return false;
+            }
+        }
+        if self
+            .renderer
+            .handle_event(&self.window, &mut self.gui, event, control_flow)
+        {
+            return false;
         }
         match event {
             Event::WindowEvent {
                 window_id,
-                ref event,
-            } if window_id == window.id() && matches!(event, WindowEvent::CloseRequested) => {
-                *control_flow = ControlFlow::Exit;
+                event: WindowEvent::CloseRequested,
+            } if *window_id == self.window.id() => true,
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(focused),
+            } if *window_id == self.window.id() => {
+                if self.pause_in_background {
+                    let _ = self.commands.send(Command::SetInactive(!focused));
+                    self.gui.notify(if *focused { "Resumed" } else { "Paused" });
+                }
+                false
             }
-            _ => {}
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::KeyboardInput { input, .. },
+            } if *window_id == self.window.id() => {
+                if let Some(command) = key_bindings.map_keyboard_input(input) {
+                    // The emulator thread only goes away if it died, nothing to tell it then
+                    let _ = self.commands.send(command);
+                }
+                false
+            }
+            _ => false,
         }
-    })
+    }
+
+    /// Shows the latest stats in the title and the overlay
+    fn update_stats(&mut self) {
+        let stats = *self.stats.lock().unwrap();
+        if stats == self.shown_stats {
+            return;
+        }
+        self.shown_stats = stats;
+        if stats.fps > 0.0 {
+            let title = format!(
+                "{} - {:.1} FPS ({:.0}%)",
+                self.title, stats.fps, stats.speed
+            );
+            self.window.set_title(&title);
+        } else {
+            self.window.set_title(&self.title);
+        }
+        self.gui.set_stats(stats);
+    }
+
+    fn quit(&mut self) {
+        // Give the emulator a chance to flush whatever it's writing
+        let _ = self.commands.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
 }
@@ -0,0 +1,64 @@
+//! Recently opened ROMs, most recent first, one path per line in `recent.txt`
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use gameboy_emulator::emulator::{paths::Paths, save_file};
+
+/// How many are remembered
+pub const MAX_RECENT: usize = 10;
+
+#[derive(Clone, Debug, Default)]
+pub struct RecentRoms {
+    paths: Vec<PathBuf>,
+    /// Nothing is saved without one
+    file: Option<PathBuf>,
+}
+
+impl RecentRoms {
+    /// A missing file just means nothing was opened yet
+    pub fn load(paths: Option<&Paths>) -> io::Result<Self> {
+        let file = paths.map(|paths| paths.config.join("recent.txt"));
+        let paths = match file.as_deref().map(fs::read_to_string) {
+            Some(Ok(contents)) => contents
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .take(MAX_RECENT)
+                .collect(),
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => Vec::new(),
+        };
+        Ok(Self { paths, file })
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The `n`th most recent, starting at 1
+    pub fn get(&self, n: u32) -> Option<&Path> {
+        self.paths
+            .get(n.checked_sub(1)? as usize)
+            .map(PathBuf::as_path)
+    }
+
+    /// Moves `rom` to the front and saves the list
+    pub fn add(&mut self, rom: &Path) -> io::Result<()> {
+        // The same file through a different relative path is still the same file
+        let rom = fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf());
+        self.paths.retain(|path| *path != rom);
+        self.paths.insert(0, rom);
+        self.paths.truncate(MAX_RECENT);
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut contents = String::new();
+        for path in &self.paths {
+            contents.push_str(&path.to_string_lossy());
+            contents.push('\n');
+        }
+        save_file::write_atomic(file, contents.as_bytes())
+    }
+}
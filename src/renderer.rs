@@ -11,24 +11,40 @@ use wgpu_core::WGPUCore;
 
 mod gameboy_pass;
 use gameboy_pass::GameBoyPass;
+pub use gameboy_pass::{ColorAdjust, Palette};
 
-use crate::emulator;
+mod egui_pass;
+use egui_pass::EguiPass;
+
+use crate::{emulator, gui::Gui};
 
 pub struct Renderer {
     core: WGPUCore,
     gameboy_pass: GameBoyPass,
+    egui_pass: EguiPass,
 }
 
 impl Renderer {
-    pub fn new(window: &Window, buffer: Arc<emulator::DoubleBuffer>) -> Self {
+    pub fn new(window: &Window, buffer: Arc<emulator::triple_buffer::TripleBuffer>) -> Self {
         let core = WGPUCore::new(window);
         let gameboy_pass = GameBoyPass::new(&core, buffer);
-        Self { core, gameboy_pass }
+        let egui_pass = EguiPass::new(&core);
+        Self {
+            core,
+            gameboy_pass,
+            egui_pass,
+        }
+    }
+
+    /// Shows frames from another emulator
+    pub fn set_buffer(&mut self, buffer: Arc<emulator::triple_buffer::TripleBuffer>) {
+        self.gameboy_pass.buffer = buffer;
     }
 
     pub fn handle_event(
         &mut self,
         window: &Window,
+        gui: &mut Gui,
         event: &Event<()>,
         control_flow: &mut ControlFlow,
     ) -> bool {
@@ -70,10 +86,26 @@ impl Renderer {
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
 
+                let gui_output = gui.run(window);
+                let primitives = gui.context().tessellate(gui_output.shapes);
+                self.egui_pass
+                    .set_textures(&self.core, &gui_output.textures_delta);
+
                 // TODO: Intermediate texture
+                self.gameboy_pass
+                    .set_color_adjust(&self.core, gui.color_adjust());
+                self.gameboy_pass.set_palette(&self.core, gui.palette());
                 self.gameboy_pass.render(&self.core, &output_view);
+                self.egui_pass.render(
+                    &self.core,
+                    &output_view,
+                    &primitives,
+                    window.scale_factor() as f32,
+                );
                 output.present();
 
+                self.egui_pass.free_textures(&gui_output.textures_delta);
+
                 true
             }
             _ => false,
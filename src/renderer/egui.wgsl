@@ -0,0 +1,60 @@
+// Vertex shader
+struct ScreenUniform {
+    // In points
+    size: vec2<f32>,
+    // 1 if the render target is sRGB and vertex colors have to be linearized
+    linearize: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    if (screen.linearize == 1u) {
+        out.color = vec4<f32>(linear_from_srgb(model.color.rgb), model.color.a);
+    } else {
+        out.color = model.color;
+    }
+    out.clip_position = vec4<f32>(
+        2.0 * model.position.x / screen.size.x - 1.0,
+        1.0 - 2.0 * model.position.y / screen.size.y,
+        0.0,
+        1.0,
+    );
+    return out;
+}
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(t_diffuse, s_diffuse, in.tex_coords);
+}
@@ -0,0 +1,466 @@
+use std::{collections::HashMap, num::NonZeroU32};
+
+use bytemuck::{Pod, Zeroable};
+use egui::{epaint::Primitive, ClippedPrimitive, ImageData, TextureId, TexturesDelta};
+use wgpu::util::DeviceExt;
+
+use super::wgpu_core::WGPUCore;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [u8; 4],
+}
+
+// SAFETY: repr(C), 20 bytes with no padding.
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
+
+impl Vertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: 16,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Unorm8x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct ScreenUniform {
+    size: [f32; 2],
+    linearize: u32,
+    _padding: u32,
+}
+
+// SAFETY: repr(C), 16 bytes with no padding.
+unsafe impl Zeroable for ScreenUniform {}
+unsafe impl Pod for ScreenUniform {}
+
+struct EguiTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Paints egui output on top of whatever has already been rendered
+pub struct EguiPass {
+    pipeline: wgpu::RenderPipeline,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: HashMap<TextureId, EguiTexture>,
+    srgb: bool,
+}
+
+impl EguiPass {
+    pub fn new(core: &WGPUCore) -> Self {
+        let srgb = core.surface_config.format.describe().srgb;
+
+        let screen_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Egui Screen Uniform Buffer"),
+                contents: bytemuck::bytes_of(&ScreenUniform {
+                    size: [1.0, 1.0],
+                    linearize: srgb as u32,
+                    _padding: 0,
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let screen_bind_group_layout =
+            core.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Egui Screen Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let screen_bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Egui Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let texture_bind_group_layout =
+            core.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Egui Texture Bind Group Layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+
+        let sampler = core.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Egui Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let shader = core
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Egui Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("egui.wgsl").into()),
+            });
+
+        let pipeline_layout = core
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Egui Pipeline Layout"),
+                bind_group_layouts: &[&screen_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = core
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Egui Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: core.surface_config.format,
+                        // egui hands us premultiplied alpha
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            screen_buffer,
+            screen_bind_group,
+            texture_bind_group_layout,
+            sampler,
+            textures: HashMap::new(),
+            srgb,
+        }
+    }
+
+    /// Must be called before [`Self::render`], see [`egui::FullOutput::textures_delta`]
+    pub fn set_textures(&mut self, core: &WGPUCore, delta: &TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let (width, height) = (image_delta.image.width(), image_delta.image.height());
+            let pixels: Vec<u8> = match &image_delta.image {
+                ImageData::Color(image) => image
+                    .pixels
+                    .iter()
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+                ImageData::Font(image) => image
+                    .srgba_pixels(1.0)
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+            };
+
+            let size = wgpu::Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            };
+
+            let origin = match image_delta.pos {
+                Some([x, y]) => wgpu::Origin3d {
+                    x: x as u32,
+                    y: y as u32,
+                    z: 0,
+                },
+                None => {
+                    let texture = self.create_texture(core, size);
+                    self.textures.insert(*id, texture);
+                    wgpu::Origin3d::ZERO
+                }
+            };
+
+            let texture = match self.textures.get(id) {
+                Some(texture) => &texture.texture,
+                None => {
+                    tracing::warn!("Egui tried to update unknown texture {:?}", id);
+                    continue;
+                }
+            };
+
+            core.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture,
+                    mip_level: 0,
+                    origin,
+                },
+                &pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(width as u32 * 4),
+                    rows_per_image: NonZeroU32::new(height as u32),
+                },
+                size,
+            );
+        }
+    }
+
+    /// Must be called after [`Self::render`]
+    pub fn free_textures(&mut self, delta: &TexturesDelta) {
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    fn create_texture(&self, core: &WGPUCore, size: wgpu::Extent3d) -> EguiTexture {
+        let texture = core.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Egui Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: if self.srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Egui Texture Bind Group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        EguiTexture {
+            texture,
+            bind_group,
+        }
+    }
+
+    pub fn render(
+        &self,
+        core: &WGPUCore,
+        output: &wgpu::TextureView,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+    ) {
+        let (width, height) = (core.surface_config.width, core.surface_config.height);
+        core.queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::bytes_of(&ScreenUniform {
+                size: [
+                    width as f32 / pixels_per_point,
+                    height as f32 / pixels_per_point,
+                ],
+                linearize: self.srgb as u32,
+                _padding: 0,
+            }),
+        );
+
+        // Everything goes into one big vertex/index buffer, each mesh is drawn from its own range
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut draws = vec![];
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in primitives
+        {
+            let mesh = match primitive {
+                Primitive::Mesh(mesh) => mesh,
+                Primitive::Callback(_) => {
+                    tracing::warn!("Egui paint callbacks are not supported");
+                    continue;
+                }
+            };
+
+            let base_vertex = vertices.len() as i32;
+            let first_index = indices.len() as u32;
+            vertices.extend(mesh.vertices.iter().map(|vertex| Vertex {
+                position: [vertex.pos.x, vertex.pos.y],
+                tex_coords: [vertex.uv.x, vertex.uv.y],
+                color: vertex.color.to_array(),
+            }));
+            indices.extend_from_slice(&mesh.indices);
+
+            // Scissor rects are in physical pixels and have to stay inside the target
+            let min_x = (clip_rect.min.x * pixels_per_point)
+                .round()
+                .clamp(0.0, width as f32);
+            let min_y = (clip_rect.min.y * pixels_per_point)
+                .round()
+                .clamp(0.0, height as f32);
+            let max_x = (clip_rect.max.x * pixels_per_point)
+                .round()
+                .clamp(min_x, width as f32);
+            let max_y = (clip_rect.max.y * pixels_per_point)
+                .round()
+                .clamp(min_y, height as f32);
+            if max_x - min_x < 1.0 || max_y - min_y < 1.0 {
+                continue;
+            }
+
+            draws.push((
+                mesh.texture_id,
+                [
+                    min_x as u32,
+                    min_y as u32,
+                    (max_x - min_x) as u32,
+                    (max_y - min_y) as u32,
+                ],
+                first_index..(first_index + mesh.indices.len() as u32),
+                base_vertex,
+            ));
+        }
+
+        if draws.is_empty() {
+            return;
+        }
+
+        let vertex_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Egui Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let index_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Egui Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        let mut encoder = core
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Egui Render Encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Egui Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+            for (texture_id, [x, y, w, h], index_range, base_vertex) in draws {
+                let texture = match self.textures.get(&texture_id) {
+                    Some(texture) => texture,
+                    None => continue,
+                };
+                render_pass.set_bind_group(1, &texture.bind_group, &[]);
+                render_pass.set_scissor_rect(x, y, w, h);
+                render_pass.draw_indexed(index_range, base_vertex, 0..1);
+            }
+        }
+
+        core.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
@@ -14,11 +14,16 @@ const GAMEBOY_SCREEN: wgpu::Extent3d = wgpu::Extent3d {
 };
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug)]
 struct Vertex {
     position: [f32; 3],
 }
 
+// SAFETY: repr(C), only made up of f32s, no padding.
+// Not derived because the derive's generated checks trip rustc's dead code lint.
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
+
 impl Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -45,6 +50,135 @@ const VERTICES: &[Vertex] = &[
     },
 ];
 
+/// Applied to the screen after scaling
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorAdjust {
+    /// Added after contrast, -1 to 1
+    pub brightness: f32,
+    /// Scales around mid grey, 1 leaves it alone
+    pub contrast: f32,
+    /// Above 1 brightens the midtones
+    pub gamma: f32,
+    _padding: f32,
+}
+
+// SAFETY: repr(C), 16 bytes of f32s with no padding.
+unsafe impl Zeroable for ColorAdjust {}
+unsafe impl Pod for ColorAdjust {}
+
+impl ColorAdjust {
+    pub const NEUTRAL: Self = Self::new(0.0, 1.0, 1.0);
+    /// Like an original DMG screen: no real black, everything a bit faded
+    pub const DMG_WASHED_OUT: Self = Self::new(0.08, 0.7, 1.2);
+
+    pub const fn new(brightness: f32, contrast: f32, gamma: f32) -> Self {
+        Self {
+            brightness,
+            contrast,
+            gamma,
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        Self::NEUTRAL
+    }
+}
+
+/// What the four shades the PPU puts out look like, applied before [`ColorAdjust`]. In between
+/// shades (from ghosting or scaling) are mixed from the two closest colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// Lightest first, the last component is unused
+    colors: [[f32; 4]; 4],
+}
+
+// SAFETY: repr(C), 64 bytes of f32s with no padding.
+unsafe impl Zeroable for Palette {}
+unsafe impl Pod for Palette {}
+
+impl Palette {
+    /// The same greys as the frame buffer, so it looks like there's no palette
+    pub const GRAYSCALE: Self = Self::new([0xFFFFFF, 0xC0C0C0, 0x5F5F5F, 0x000000]);
+    pub const DMG_GREEN: Self = Self::new([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F]);
+    pub const POCKET: Self = Self::new([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F]);
+    /// As far apart in brightness as they'll go, with different hues on top
+    pub const HIGH_CONTRAST: Self = Self::new([0xFFFFFF, 0xFFD800, 0x0050D0, 0x000000]);
+    /// Orange and blue instead of anything that relies on telling red from green
+    pub const DEUTERANOPIA: Self = Self::new([0xFFF5E0, 0xE69F00, 0x004488, 0x111111]);
+    /// Yellow and blue, reds look too dark with protanopia
+    pub const PROTANOPIA: Self = Self::new([0xFFFFF0, 0xF0E442, 0x0072B2, 0x000000]);
+
+    pub const ALL: [(&'static str, Self); 6] = [
+        ("Grayscale", Self::GRAYSCALE),
+        ("DMG green", Self::DMG_GREEN),
+        ("Pocket", Self::POCKET),
+        ("High contrast", Self::HIGH_CONTRAST),
+        ("Deuteranopia", Self::DEUTERANOPIA),
+        ("Protanopia", Self::PROTANOPIA),
+    ];
+
+    /// From `0xRRGGBB` colors, lightest first
+    pub const fn new(rgb: [u32; 4]) -> Self {
+        const fn channel(rgb: u32, shift: u32) -> f32 {
+            ((rgb >> shift) & 0xFF) as f32 / 255.0
+        }
+        let mut colors = [[0.0; 4]; 4];
+        let mut i = 0;
+        while i < 4 {
+            colors[i] = [
+                channel(rgb[i], 16),
+                channel(rgb[i], 8),
+                channel(rgb[i], 0),
+                1.0,
+            ];
+            i += 1;
+        }
+        Self { colors }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::GRAYSCALE
+    }
+}
+
+/// Whether the frame's shown from its colors, for the shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct FrameKind {
+    colors: u32,
+    _padding: [u32; 3],
+}
+
+// SAFETY: repr(C), 16 bytes of u32s with no padding.
+unsafe impl Zeroable for FrameKind {}
+unsafe impl Pod for FrameKind {}
+
+/// RGB555 from the PPU to the RGBA8 the color texture has
+fn rgba8(colors: &emulator::ppu::ColorFrameBuffer) -> Vec<u8> {
+    let channel = |color: u16, shift: u16| {
+        let value = (color >> shift & 0x1F) as u8;
+        value << 3 | value >> 2
+    };
+    colors
+        .iter()
+        .flat_map(|&color| {
+            [
+                channel(color, 0),
+                channel(color, 5),
+                channel(color, 10),
+                255,
+            ]
+        })
+        .collect()
+}
+
 // const INDICES: &[u16] = &[0, 1, 2];
 const INDICES: &[u16] = &[0, 1, 2];
 
@@ -56,33 +190,46 @@ pub enum GameBoyPassPipelineChoice {
 }
 
 pub struct GameBoyPass {
-    buffer: Arc<emulator::DoubleBuffer>,
+    pub buffer: Arc<emulator::triple_buffer::TripleBuffer>,
     texture: wgpu::Texture,
+    color_texture: wgpu::Texture,
     texture_bind_group: wgpu::BindGroup,
     naive_pipeline: wgpu::RenderPipeline,
     xbr_pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+    adjust_buffer: wgpu::Buffer,
+    palette_buffer: wgpu::Buffer,
+    frame_kind_buffer: wgpu::Buffer,
+    adjust_bind_group: wgpu::BindGroup,
     pub pipeline_to_use: GameBoyPassPipelineChoice,
 }
 
 impl GameBoyPass {
-    pub fn new(core: &WGPUCore, buffer: Arc<emulator::DoubleBuffer>) -> Self {
-        let (texture, texture_bind_group_layout, texture_bind_group) =
+    pub fn new(core: &WGPUCore, buffer: Arc<emulator::triple_buffer::TripleBuffer>) -> Self {
+        let (texture, color_texture, texture_bind_group_layout, texture_bind_group) =
             Self::create_framebuffer_texture(core);
+        let (
+            adjust_buffer,
+            palette_buffer,
+            frame_kind_buffer,
+            adjust_bind_group_layout,
+            adjust_bind_group,
+        ) = Self::create_adjust_buffers(core);
+        let bind_group_layouts = [&texture_bind_group_layout, &adjust_bind_group_layout];
 
         let naive_pipeline = Self::create_pipeline(
             core,
             "Naive",
             include_str!("gameboy_naive.wgsl"),
-            &texture_bind_group_layout,
+            &bind_group_layouts,
         );
 
         let xbr_pipeline = Self::create_pipeline(
             core,
             "XBR",
             include_str!("gameboy_xbr.wgsl"),
-            &texture_bind_group_layout,
+            &bind_group_layouts,
         );
 
         let vertex_buffer = core
@@ -104,11 +251,16 @@ impl GameBoyPass {
         Self {
             buffer,
             texture,
+            color_texture,
             texture_bind_group,
             naive_pipeline,
             xbr_pipeline,
             vertex_buffer,
             index_buffer,
+            adjust_buffer,
+            palette_buffer,
+            frame_kind_buffer,
+            adjust_bind_group,
             pipeline_to_use: GameBoyPassPipelineChoice::Naive,
         }
     }
@@ -117,7 +269,7 @@ impl GameBoyPass {
         core: &WGPUCore,
         name: &str,
         shader_source: &str,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
     ) -> wgpu::RenderPipeline {
         let shader = core
             .device
@@ -130,7 +282,7 @@ impl GameBoyPass {
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some(&format!("{} Gameboy Pipeline Layout", name)),
-                bind_group_layouts: &[texture_bind_group_layout],
+                bind_group_layouts,
                 push_constant_ranges: &[],
             });
 
@@ -174,9 +326,15 @@ impl GameBoyPass {
         pipeline
     }
 
+    /// The shades texture, and the color one CGB frames are shown from
     fn create_framebuffer_texture(
         core: &WGPUCore,
-    ) -> (wgpu::Texture, wgpu::BindGroupLayout, wgpu::BindGroup) {
+    ) -> (
+        wgpu::Texture,
+        wgpu::Texture,
+        wgpu::BindGroupLayout,
+        wgpu::BindGroup,
+    ) {
         let texture = core.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Gameboy Framebuffer Texture"),
             size: GAMEBOY_SCREEN,
@@ -189,6 +347,18 @@ impl GameBoyPass {
 
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let color_texture = core.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Gameboy Color Framebuffer Texture"),
+            size: GAMEBOY_SCREEN,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+
+        let color_texture_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
         let sampler = core.device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Gameboy Framebuffer Sampler"),
             address_mode_u: wgpu::AddressMode::Repeat,
@@ -221,6 +391,16 @@ impl GameBoyPass {
                             ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -236,20 +416,112 @@ impl GameBoyPass {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&color_texture_view),
+                },
             ],
         });
 
-        (texture, texture_bind_group_layout, texture_bind_group)
+        (
+            texture,
+            color_texture,
+            texture_bind_group_layout,
+            texture_bind_group,
+        )
+    }
+
+    /// The [`ColorAdjust`], [`Palette`] and [`FrameKind`] uniforms
+    fn create_adjust_buffers(
+        core: &WGPUCore,
+    ) -> (
+        wgpu::Buffer,
+        wgpu::Buffer,
+        wgpu::Buffer,
+        wgpu::BindGroupLayout,
+        wgpu::BindGroup,
+    ) {
+        let adjust_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gameboy Color Adjust Buffer"),
+                contents: bytemuck::bytes_of(&ColorAdjust::NEUTRAL),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let palette_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gameboy Palette Buffer"),
+                contents: bytemuck::bytes_of(&Palette::GRAYSCALE),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let frame_kind_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gameboy Frame Kind Buffer"),
+                contents: bytemuck::bytes_of(&FrameKind::zeroed()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = core
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Gameboy Color Adjust Bind Group Layout"),
+                entries: &[uniform(0), uniform(1), uniform(2)],
+            });
+
+        let bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Gameboy Color Adjust Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: adjust_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: palette_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: frame_kind_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        (
+            adjust_buffer,
+            palette_buffer,
+            frame_kind_buffer,
+            layout,
+            bind_group,
+        )
     }
 }
 
 impl GameBoyPass {
+    pub fn set_color_adjust(&self, core: &WGPUCore, adjust: ColorAdjust) {
+        core.queue
+            .write_buffer(&self.adjust_buffer, 0, bytemuck::bytes_of(&adjust));
+    }
+
+    pub fn set_palette(&self, core: &WGPUCore, palette: Palette) {
+        core.queue
+            .write_buffer(&self.palette_buffer, 0, bytemuck::bytes_of(&palette));
+    }
+
     pub fn render(&self, core: &WGPUCore, output: &wgpu::TextureView) {
-        let data = self
-            .buffer
-            .get_current()
-            .lock()
-            .expect("Emulator thread poisoned");
+        let data = self.buffer.latest();
 
         core.queue.write_texture(
             wgpu::ImageCopyTexture {
@@ -266,6 +538,29 @@ impl GameBoyPass {
             },
             GAMEBOY_SCREEN,
         );
+        if let Some(colors) = data.colors() {
+            core.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &self.color_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &rgba8(colors),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(160 * 4),
+                    rows_per_image: NonZeroU32::new(144),
+                },
+                GAMEBOY_SCREEN,
+            );
+        }
+        let frame_kind = FrameKind {
+            colors: data.colors().is_some() as u32,
+            _padding: [0; 3],
+        };
+        core.queue
+            .write_buffer(&self.frame_kind_buffer, 0, bytemuck::bytes_of(&frame_kind));
 
         drop(data);
 
@@ -301,6 +596,7 @@ impl GameBoyPass {
 
             render_pass.set_pipeline(pipeline);
             render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.adjust_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..(INDICES.len() as u32), 0, 0..1);
@@ -23,6 +23,59 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+// What CGB games draw, only used when the frame has colors
+@group(0) @binding(2)
+var t_colors: texture_2d<f32>;
+
+struct ColorAdjust {
+    // Added after contrast, -1 to 1
+    brightness: f32,
+    // Scales around mid grey
+    contrast: f32,
+    // Above 1 brightens the midtones
+    gamma: f32,
+    _padding: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> color_adjust: ColorAdjust;
+
+struct Palette {
+    // Lightest first
+    colors: array<vec4<f32>, 4>,
+}
+
+@group(1) @binding(1)
+var<uniform> palette: Palette;
+
+struct FrameKind {
+    // 1 when t_colors has the frame, the shades are only its brightness then
+    colors: u32,
+    _padding: vec3<u32>,
+}
+
+@group(1) @binding(2)
+var<uniform> frame_kind: FrameKind;
+
+// The PPU's shades are 255, 192, 95 and 0, anything in between is mixed
+fn apply_palette(shade: f32) -> vec3<f32> {
+    if (shade >= 192.0 / 255.0) {
+        let t = (shade - 192.0 / 255.0) / (63.0 / 255.0);
+        return mix(palette.colors[1].xyz, palette.colors[0].xyz, t);
+    }
+    if (shade >= 95.0 / 255.0) {
+        let t = (shade - 95.0 / 255.0) / (97.0 / 255.0);
+        return mix(palette.colors[2].xyz, palette.colors[1].xyz, t);
+    }
+    let t = shade / (95.0 / 255.0);
+    return mix(palette.colors[3].xyz, palette.colors[2].xyz, t);
+}
+
+fn adjust(color: vec3<f32>) -> vec3<f32> {
+    var res = pow(max(color, vec3<f32>(0.0)), vec3<f32>(1.0 / color_adjust.gamma));
+    res = (res - 0.5) * color_adjust.contrast + 0.5 + color_adjust.brightness;
+    return clamp(res, vec3<f32>(0.0), vec3<f32>(1.0));
+}
 
 let XBR_Y_WEIGHT: f32 = 48.0;
 let XBR_EQ_THRESHOLD: f32 = 15.0;
@@ -107,19 +160,19 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // colors.y = colors.x;
     // colors.z = colors.x;
 
-    var res: vec3<f32>;
+    // Edges are found from the shades, colors are taken from the same place
+    var offset: vec2<f32>;
     if (nc) {
         if (px) {
-            res = F;
+            offset = -g2;
         } else {
-            res = H;
+            offset = -g1;
         }
     } else {
-        res = E;
+        offset = vec2<f32>(0.0, 0.0);
     }
+    let res = textureSample(t_diffuse, s_diffuse, in.tex_coords + offset).xyz;
+    let color = textureSample(t_colors, s_diffuse, in.tex_coords + offset).xyz;
 
-    res.y = res.x;
-    res.z = res.x;
-
-    return vec4(res, 1.0);
+    return vec4(adjust(select(apply_palette(res.x), color, frame_kind.colors == 1u)), 1.0);
 }
\ No newline at end of file
@@ -2,6 +2,7 @@ use winit::window::Window;
 
 pub struct WGPUCore {
     pub size: winit::dpi::PhysicalSize<u32>,
+    #[allow(dead_code)]
     pub instance: wgpu::Instance,
     pub surface: wgpu::Surface,
     pub device: wgpu::Device,
@@ -47,8 +48,8 @@ impl WGPUCore {
         wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface.get_supported_formats(adapter)[0],
-            width: size.width as u32,
-            height: size.height as u32,
+            width: size.width,
+            height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
         }
     }
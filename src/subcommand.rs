@@ -0,0 +1,107 @@
+//! `savestate` and `sram`, for scripting backups without opening a window, and `trace`
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use gameboy_emulator::emulator::{
+    archive,
+    paths::Paths,
+    rtc, save_file, state,
+    trace::{self, Outcome},
+    Emulator,
+};
+
+use crate::cli::Subcommand;
+
+/// Returns what to print when it worked
+pub fn run(subcommand: &Subcommand, paths: Option<&Paths>) -> Result<String, String> {
+    match subcommand {
+        Subcommand::ExportState { rom, file } => {
+            let (_, resume_file) = resume_file(rom, paths)?;
+            let state = read(&resume_file)?;
+            write(file, &state)?;
+            Ok(format!("Exported {:?} to {:?}", resume_file, file))
+        }
+        Subcommand::ImportState { rom, file } => {
+            let (mut emulator, resume_file) = resume_file(rom, paths)?;
+            let state = read(file)?;
+            emulator
+                .load_state(&state)
+                .map_err(|e| format!("Can't import {:?}: {}", file, e))?;
+            save_file::write_atomic(&resume_file, &state)
+                .map_err(|e| format!("Failed to write {:?}: {}", resume_file, e))?;
+            Ok(format!("Imported {:?} to {:?}", file, resume_file))
+        }
+        Subcommand::ExportSram { sav, file } => {
+            let bytes = read(sav)?;
+            let (ram, _) = rtc::split_sav(&bytes);
+            write(file, ram)?;
+            Ok(format!("Exported {} bytes of RAM to {:?}", ram.len(), file))
+        }
+        Subcommand::ImportSram { sav, file } => {
+            let bytes = read(file)?;
+            // Saves from other emulators can have their own footer, only take the RAM
+            let (ram, imported_rtc) = rtc::split_sav(&bytes);
+            if ram.len() % rtc::RAM_GRANULE != 0 {
+                return Err(format!(
+                    "{:?} is {} bytes, cartridge RAM comes in multiples of {}",
+                    file,
+                    ram.len(),
+                    rtc::RAM_GRANULE
+                ));
+            }
+            let existing = match fs::read(sav) {
+                Ok(existing) => existing,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(format!("Failed to read {:?}: {}", sav, e)),
+            };
+            let footer = rtc::split_sav(&existing).1.or(imported_rtc);
+            let out = match &footer {
+                Some(footer) => rtc::join_sav(ram, footer),
+                None => ram.to_vec(),
+            };
+            save_file::write_atomic(sav, &out)
+                .map_err(|e| format!("Failed to write {:?}: {}", sav, e))?;
+            Ok(format!(
+                "Imported {} bytes of RAM into {:?}",
+                ram.len(),
+                sav
+            ))
+        }
+        Subcommand::DiffTrace { rom, log } => {
+            let mut emulator = Emulator::new(&read_rom(rom)?);
+            let file = File::open(log).map_err(|e| format!("Failed to read {:?}: {}", log, e))?;
+            match trace::diverge(&mut emulator, BufReader::new(file)) {
+                Ok(Outcome::Matched(lines)) => {
+                    Ok(format!("All {} lines of {:?} match", lines, log))
+                }
+                Ok(Outcome::Diverged(divergence)) => Err(divergence.to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    }
+}
+
+/// The emulator with `rom` loaded, and where its `--resume` state is kept
+fn resume_file(rom: &Path, paths: Option<&Paths>) -> Result<(Emulator, PathBuf), String> {
+    let paths = paths.ok_or("Couldn't find where save states are kept")?;
+    let emulator = Emulator::new(&read_rom(rom)?);
+    let memory_bus = emulator.memory_bus();
+    let path = state::resume_file(paths, &memory_bus.rom_title(), memory_bus.rom_checksum());
+    Ok((emulator, path))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+/// Unzipping it first if it's compressed
+fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    archive::extract(path, read(path)?).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(path, bytes).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
@@ -0,0 +1,109 @@
+//! WebAssembly exports for the browser frontend in `web/`
+//!
+//! Plain exported functions, so the module can be loaded with nothing but
+//! `WebAssembly.instantiate`. The page copies the ROM into the buffer from
+//! [`gb_rom_buffer`], calls [`gb_load`] and then [`gb_run_frame`] once per frame.
+use std::{cell::RefCell, ffi::CString};
+
+use crate::emulator::{joypad::Button, rom, Emulator, GAMEBOY_HEIGHT, GAMEBOY_WIDTH};
+
+/// Indices used by [`gb_set_button`]
+const BUTTONS: [Button; 8] = [
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+];
+
+#[derive(Default)]
+struct State {
+    rom: Vec<u8>,
+    emulator: Option<Emulator>,
+    /// The frame as RGBA, ready for `ImageData`
+    rgba: Vec<u8>,
+    /// Why emulation stopped, NUL terminated for [`gb_error`]
+    error: Option<CString>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+/// Space for a ROM of `len` bytes, filled in by the page before calling [`gb_load`]
+#[no_mangle]
+pub extern "C" fn gb_rom_buffer(len: usize) -> *mut u8 {
+    STATE.with_borrow_mut(|state| {
+        state.rom = vec![0; len];
+        state.rom.as_mut_ptr()
+    })
+}
+
+/// Starts the ROM from [`gb_rom_buffer`], returns false if it's too small to be one
+#[no_mangle]
+pub extern "C" fn gb_load() -> bool {
+    STATE.with_borrow_mut(|state| {
+        if state.rom.len() < rom::MIN_SIZE {
+            return false;
+        }
+        state.emulator = Some(Emulator::new(&state.rom));
+        state.rom = Vec::new();
+        state.error = None;
+        true
+    })
+}
+
+/// Runs until the next frame and returns it as 160x144 RGBA.
+/// Null without a ROM or once emulation has stopped on an error, see [`gb_error`].
+#[no_mangle]
+pub extern "C" fn gb_run_frame() -> *const u8 {
+    STATE.with_borrow_mut(|state| {
+        let Some(emulator) = state.emulator.as_mut() else {
+            return std::ptr::null();
+        };
+        let frame = match emulator.run_frame() {
+            Ok(frame) => frame,
+            Err(e) => {
+                let crash = emulator.crash(&e);
+                let report = format!("{}\n\n{}", crash.message, crash.cpu_dump);
+                state.error = CString::new(report.replace('\0', "")).ok();
+                state.emulator = None;
+                return std::ptr::null();
+            }
+        };
+        state.rgba.clear();
+        state.rgba.reserve(GAMEBOY_WIDTH * GAMEBOY_HEIGHT * 4);
+        for &shade in frame.iter() {
+            state.rgba.extend_from_slice(&[shade, shade, shade, 0xFF]);
+        }
+        state.rgba.as_ptr()
+    })
+}
+
+/// Why emulation stopped as a NUL terminated string, null if it hasn't
+#[no_mangle]
+pub extern "C" fn gb_error() -> *const u8 {
+    STATE.with_borrow(|state| match &state.error {
+        Some(error) => error.as_ptr().cast(),
+        None => std::ptr::null(),
+    })
+}
+
+/// `button` is Right, Left, Up, Down, A, B, Select, Start from 0 to 7
+#[no_mangle]
+pub extern "C" fn gb_set_button(button: u32, pressed: bool) {
+    let Some(&button) = BUTTONS.get(button as usize) else {
+        return;
+    };
+    STATE.with_borrow_mut(|state| {
+        if let Some(emulator) = state.emulator.as_mut() {
+            emulator
+                .memory_bus_mut()
+                .joypad_mut()
+                .set_button(button, pressed);
+        }
+    });
+}
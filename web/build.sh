@@ -0,0 +1,6 @@
+#!/bin/bash
+# Builds the wasm core next to index.html, serve this directory with any static file server
+set -e
+cd "$(dirname "$0")"
+cargo build --release --lib --target wasm32-unknown-unknown
+cp ../target/wasm32-unknown-unknown/release/gameboy_emulator.wasm .
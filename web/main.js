@@ -0,0 +1,82 @@
+// Browser frontend for the wasm build, see build.sh
+const WIDTH = 160;
+const HEIGHT = 144;
+const FRAME_MS = 1000 / 59.7275;
+
+// Same bindings as the desktop frontend, values are the indices gb_set_button takes
+const KEYS = {
+    ArrowRight: 0,
+    ArrowLeft: 1,
+    ArrowUp: 2,
+    ArrowDown: 3,
+    KeyX: 4,
+    KeyZ: 5,
+    Backspace: 6,
+    Enter: 7,
+};
+
+const status = document.getElementById("status");
+const context = document.getElementById("screen").getContext("2d");
+
+const { instance } = await WebAssembly.instantiateStreaming(fetch("gameboy_emulator.wasm"), {});
+const gb = instance.exports;
+
+let running = false;
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+    const file = event.target.files[0];
+    if (!file) {
+        return;
+    }
+    const rom = new Uint8Array(await file.arrayBuffer());
+    const pointer = gb.gb_rom_buffer(rom.length);
+    new Uint8Array(gb.memory.buffer, pointer, rom.length).set(rom);
+    if (!gb.gb_load()) {
+        status.textContent = `${file.name} is too small to be a ROM`;
+        return;
+    }
+    status.textContent = `Running ${file.name}`;
+    if (!running) {
+        running = true;
+        requestAnimationFrame(loop);
+    }
+});
+
+function onKey(event, pressed) {
+    const button = KEYS[event.code];
+    if (button === undefined) {
+        return;
+    }
+    event.preventDefault();
+    gb.gb_set_button(button, pressed);
+}
+
+document.addEventListener("keydown", (event) => onKey(event, true));
+document.addEventListener("keyup", (event) => onKey(event, false));
+
+// requestAnimationFrame doesn't run at 59.7Hz, catch up on however many frames are due
+let last = performance.now();
+let due = 0;
+
+function loop(now) {
+    due += (now - last) / FRAME_MS;
+    last = now;
+    // Don't try to catch up after the tab was in the background
+    due = Math.min(due, 4);
+
+    let frame = 0;
+    while (due >= 1) {
+        frame = gb.gb_run_frame();
+        due -= 1;
+    }
+    const error = gb.gb_error();
+    if (error !== 0) {
+        const bytes = new Uint8Array(gb.memory.buffer, error);
+        status.textContent = new TextDecoder().decode(bytes.subarray(0, bytes.indexOf(0)));
+    } else if (frame !== 0) {
+        // Memory can grow between frames, so the view has to be made fresh each time
+        const pixels = new Uint8ClampedArray(gb.memory.buffer, frame, WIDTH * HEIGHT * 4);
+        context.putImageData(new ImageData(pixels, WIDTH, HEIGHT), 0, 0);
+    }
+    requestAnimationFrame(loop);
+}